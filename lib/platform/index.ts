@@ -56,11 +56,39 @@ export {
   saveAPIKeySecurely,
   getAPIKeySecurely,
   deleteAPIKeySecurely,
+  listAPIKeyProviders,
+  getAPIKeyMetadata,
+  listAPIKeyProfiles,
+  setActiveAPIKeyProfile,
+  validateAPIKey,
   hasAPIKeyStored,
+  saveProviderTenant,
+  getProviderTenant,
+  saveAzureDeployment,
+  getAzureDeployment,
+  getCustomProviders,
+  saveCustomProvider,
+  deleteCustomProvider,
+  getAIFallbackChain,
+  setAIFallbackChain,
+  getAIProviderHeaders,
+  setAIProviderHeaders,
+  getAIDebugLog,
+  clearAIDebugLog,
+  listLocalModels,
+  downloadLocalModel,
+  deleteLocalModel,
   exportConversation,
+  suggestConversationTitle,
   proxyAIRequest,
+  proxyAIBatchRequest,
+  transcribeAudio,
+  generateAIImage,
   getAIUsageStats,
   updateAIUsageStats,
+  getAIPricing,
+  setAIModelPrice,
+  resetAIPricing,
   clearAIUsageStats,
   isTauriAIAvailable,
   getStorageRecommendation,
@@ -84,6 +112,31 @@ export {
   exportMCPServersClaudeFormat,
   detectExternalMCPConfigs,
   type SecureStorageItem,
+  type StoredAPIKey,
+  type APIKeyMetadata,
+  type APIKeyProfiles,
+  type APIKeyValidation,
+  type APIKeyErrorKind,
+  type ProviderTenant,
+  type AzureDeployment,
+  type CustomProvider,
+  type FallbackTarget,
+  type AIDebugLogEntry,
+  type ModelPrice,
+  type ModelPricing,
+  type AIContentPart,
+  type AISamplingOptions,
+  type AIJsonSchemaFormat,
+  type AIRequestOptions,
+  type AIBatchPrompt,
+  type AIBatchResult,
+  type AIBatchSummary,
+  type AIChatReply,
+  type AIModerationResult,
+  type Transcription,
+  type AIImageOptions,
+  type GeneratedImage,
+  type LocalModel,
   type AIUsageStats,
   type TauriMCPServerConfig,
   type MCPServerStatus,
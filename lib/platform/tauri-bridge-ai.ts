@@ -1,4 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { toast } from "sonner";
 import { isTauri } from "./tauri-bridge";
 
@@ -125,10 +126,13 @@ export interface SecureStorageItem {
  *
  * @param provider - Provider identifier (can be built-in like "openai" or custom provider ID)
  * @param apiKey - The API key to store
+ * @param profile - Key profile to store it in, the active one by default
+ *   (Tauri only)
  */
 export async function saveAPIKeySecurely(
   provider: string,
-  apiKey: string
+  apiKey: string,
+  profile?: string
 ): Promise<void> {
   if (!isTauri()) {
     // Browser mode: encrypt before storing in localStorage
@@ -146,6 +150,7 @@ export async function saveAPIKeySecurely(
     await invoke("save_api_key", {
       provider,
       apiKey,
+      profile,
     });
   } catch (error) {
     console.error("Failed to save API key securely:", error);
@@ -217,8 +222,12 @@ export async function getAPIKeySecurely(
  * Delete API key securely
  *
  * @param provider - Provider identifier (can be built-in like "openai" or custom provider ID)
+ * @param profile - Key profile to delete, every one by default (Tauri only)
  */
-export async function deleteAPIKeySecurely(provider: string): Promise<void> {
+export async function deleteAPIKeySecurely(
+  provider: string,
+  profile?: string
+): Promise<void> {
   if (!isTauri()) {
     // Browser mode: delete from localStorage
     localStorage.removeItem(`ai_api_key_${provider}`);
@@ -228,6 +237,7 @@ export async function deleteAPIKeySecurely(provider: string): Promise<void> {
   try {
     await invoke("delete_api_key", {
       provider,
+      profile,
     });
   } catch (error) {
     console.error("Failed to delete API key:", error);
@@ -235,6 +245,525 @@ export async function deleteAPIKeySecurely(provider: string): Promise<void> {
   }
 }
 
+/**
+ * A provider with an API key in the OS credential manager
+ */
+export interface StoredAPIKey {
+  provider: string;
+  /** Key profile requests use */
+  profile: string;
+  /** Name of a custom provider */
+  name: string | null;
+  /** First and last characters of the key */
+  preview: string;
+}
+
+/**
+ * List the built-in and custom providers with a stored API key (Tauri only)
+ */
+export async function listAPIKeyProviders(): Promise<StoredAPIKey[]> {
+  if (!isTauri()) {
+    throw new Error("Key listing is only available in Tauri desktop mode");
+  }
+
+  try {
+    return await invoke<StoredAPIKey[]>("list_api_key_providers");
+  } catch (error) {
+    console.error("Failed to list API key providers:", error);
+    throw error;
+  }
+}
+
+/**
+ * What is known of a stored API key without reading it
+ */
+export interface APIKeyMetadata {
+  /** Unix seconds; null for keys saved before this was kept */
+  createdAt: number | null;
+  /** Unix seconds of the last request that succeeded with the key */
+  lastUsedAt: number | null;
+  /** First and last characters of the key */
+  preview: string;
+}
+
+/**
+ * Get the metadata of the stored API keys, by provider then key profile
+ * (Tauri only)
+ */
+export async function getAPIKeyMetadata(): Promise<
+  Record<string, Record<string, APIKeyMetadata>>
+> {
+  if (!isTauri()) {
+    throw new Error("Key metadata is only available in Tauri desktop mode");
+  }
+
+  try {
+    return await invoke<Record<string, Record<string, APIKeyMetadata>>>(
+      "get_api_key_metadata"
+    );
+  } catch (error) {
+    console.error("Failed to get API key metadata:", error);
+    throw error;
+  }
+}
+
+/**
+ * Named API keys of a provider, such as "work" and "personal"
+ */
+export interface APIKeyProfiles {
+  /** Profile whose key requests use */
+  active: string;
+  /** Profile names, "default" first */
+  profiles: string[];
+}
+
+/**
+ * List the key profiles of a provider (Tauri only)
+ */
+export async function listAPIKeyProfiles(
+  provider: string
+): Promise<APIKeyProfiles> {
+  if (!isTauri()) {
+    throw new Error("Key profiles are only available in Tauri desktop mode");
+  }
+
+  try {
+    return await invoke<APIKeyProfiles>("list_api_key_profiles", {
+      provider,
+    });
+  } catch (error) {
+    console.error("Failed to list API key profiles:", error);
+    throw error;
+  }
+}
+
+/**
+ * Choose the key profile whose key requests to a provider use (Tauri only)
+ */
+export async function setActiveAPIKeyProfile(
+  provider: string,
+  profile: string
+): Promise<void> {
+  if (!isTauri()) {
+    throw new Error("Key profiles are only available in Tauri desktop mode");
+  }
+
+  try {
+    await invoke("set_active_api_key_profile", { provider, profile });
+  } catch (error) {
+    console.error("Failed to switch API key profile:", error);
+    throw error;
+  }
+}
+
+/**
+ * Why a key check failed: only "auth" means the key was rejected
+ */
+export type APIKeyErrorKind =
+  | "network"
+  | "auth"
+  | "rate-limit"
+  | "not-found"
+  | "internal";
+
+/**
+ * Whether a provider accepted a stored API key, and why not; `valid` is
+ * null when the provider could not be reached or failed to answer
+ */
+export interface APIKeyValidation {
+  valid: boolean | null;
+  error: string | null;
+  errorKind: APIKeyErrorKind | null;
+}
+
+/**
+ * Check the stored API key of a provider with a lightweight authenticated
+ * call, listing its models (Tauri only)
+ */
+export async function validateAPIKey(
+  provider: string
+): Promise<APIKeyValidation> {
+  if (!isTauri()) {
+    throw new Error("Key validation is only available in Tauri desktop mode");
+  }
+
+  try {
+    return await invoke<APIKeyValidation>("validate_api_key", { provider });
+  } catch (error) {
+    console.error("Failed to validate API key:", error);
+    throw error;
+  }
+}
+
+/**
+ * Organization and project a provider bills requests to
+ * (OpenAI enterprise and edu accounts)
+ */
+export interface ProviderTenant {
+  organization?: string | null;
+  project?: string | null;
+}
+
+/**
+ * Save the organization and project ids sent with requests to a provider
+ * Empty ids remove them. Desktop only.
+ */
+export async function saveProviderTenant(
+  provider: string,
+  tenant: ProviderTenant
+): Promise<void> {
+  if (!isTauri()) {
+    throw new Error("Organization ids are only available in Tauri desktop mode");
+  }
+
+  try {
+    await invoke("save_provider_tenant", { provider, tenant });
+  } catch (error) {
+    console.error("Failed to save provider organization:", error);
+    throw error;
+  }
+}
+
+/**
+ * Get the organization and project ids saved for a provider
+ */
+export async function getProviderTenant(
+  provider: string
+): Promise<ProviderTenant | null> {
+  if (!isTauri()) {
+    return null;
+  }
+
+  try {
+    return await invoke<ProviderTenant | null>("get_provider_tenant", {
+      provider,
+    });
+  } catch (error) {
+    console.error("Failed to get provider organization:", error);
+    return null;
+  }
+}
+
+/**
+ * Azure OpenAI resource and model deployment requests are sent to
+ */
+export interface AzureDeployment {
+  resourceName: string;
+  deploymentName: string;
+  /** Empty for the default API version */
+  apiVersion?: string;
+}
+
+/**
+ * Save the Azure OpenAI deployment used by the "azure" provider. Desktop only.
+ */
+export async function saveAzureDeployment(
+  deployment: AzureDeployment,
+  provider: string = "azure"
+): Promise<void> {
+  if (!isTauri()) {
+    throw new Error(
+      "Azure deployments are only available in Tauri desktop mode"
+    );
+  }
+
+  try {
+    await invoke("save_azure_deployment", {
+      provider,
+      deployment: { apiVersion: "", ...deployment },
+    });
+  } catch (error) {
+    console.error("Failed to save Azure deployment:", error);
+    throw error;
+  }
+}
+
+/**
+ * Get the Azure OpenAI deployment saved for a provider
+ */
+export async function getAzureDeployment(
+  provider: string = "azure"
+): Promise<AzureDeployment | null> {
+  if (!isTauri()) {
+    return null;
+  }
+
+  try {
+    return await invoke<AzureDeployment | null>("get_azure_deployment", {
+      provider,
+    });
+  } catch (error) {
+    console.error("Failed to get Azure deployment:", error);
+    return null;
+  }
+}
+
+/**
+ * A user-registered OpenAI-compatible provider. Its id is the provider
+ * name used in requests and for its API key.
+ */
+export interface CustomProvider {
+  /** Empty when registering a new provider */
+  id: string;
+  name: string;
+  /** API base URL, such as https://llm.example.com/v1 */
+  baseUrl: string;
+  /** Headers added to every request */
+  headers?: Record<string, string>;
+  createdAt?: number;
+  updatedAt?: number;
+}
+
+/**
+ * Get the registered custom providers
+ */
+export async function getCustomProviders(): Promise<CustomProvider[]> {
+  if (!isTauri()) {
+    return [];
+  }
+
+  try {
+    return await invoke<CustomProvider[]>("get_custom_providers");
+  } catch (error) {
+    console.error("Failed to get custom providers:", error);
+    return [];
+  }
+}
+
+/**
+ * Register a custom provider, or update the one with its id. Desktop only.
+ */
+export async function saveCustomProvider(
+  provider: CustomProvider
+): Promise<CustomProvider> {
+  if (!isTauri()) {
+    throw new Error("Custom providers are only available in Tauri desktop mode");
+  }
+
+  try {
+    return await invoke<CustomProvider>("save_custom_provider", { provider });
+  } catch (error) {
+    console.error("Failed to save custom provider:", error);
+    throw error;
+  }
+}
+
+/**
+ * Remove a custom provider and its API key
+ */
+export async function deleteCustomProvider(id: string): Promise<void> {
+  if (!isTauri()) {
+    throw new Error("Custom providers are only available in Tauri desktop mode");
+  }
+
+  try {
+    await invoke("delete_custom_provider", { id });
+  } catch (error) {
+    console.error("Failed to delete custom provider:", error);
+    throw error;
+  }
+}
+
+/**
+ * A provider, and the model to ask there, that chat requests fail over to
+ */
+export interface FallbackTarget {
+  provider: string;
+  model: string;
+}
+
+/**
+ * Get the providers a failed chat request is sent to next, in order
+ */
+export async function getAIFallbackChain(): Promise<FallbackTarget[]> {
+  if (!isTauri()) {
+    return [];
+  }
+
+  try {
+    return await invoke<FallbackTarget[]>("get_ai_fallback_chain");
+  } catch (error) {
+    console.error("Failed to get fallback providers:", error);
+    return [];
+  }
+}
+
+/**
+ * Replace the fallback providers, such as openai, then openrouter, then
+ * deepseek. Each provider may be listed once. Desktop only.
+ */
+export async function setAIFallbackChain(
+  chain: FallbackTarget[]
+): Promise<FallbackTarget[]> {
+  if (!isTauri()) {
+    throw new Error(
+      "Fallback providers are only available in Tauri desktop mode"
+    );
+  }
+
+  try {
+    return await invoke<FallbackTarget[]>("set_ai_fallback_chain", { chain });
+  } catch (error) {
+    console.error("Failed to set fallback providers:", error);
+    throw error;
+  }
+}
+
+/**
+ * Get the headers sent with every request to each provider, including the
+ * built-in OpenRouter attribution headers
+ */
+export async function getAIProviderHeaders(): Promise<
+  Record<string, Record<string, string>>
+> {
+  if (!isTauri()) {
+    return {};
+  }
+
+  try {
+    return await invoke<Record<string, Record<string, string>>>(
+      "get_ai_provider_headers"
+    );
+  } catch (error) {
+    console.error("Failed to get provider headers:", error);
+    return {};
+  }
+}
+
+/**
+ * Replace the headers set for a provider. An empty value drops a built-in
+ * header, and no headers restore the built-in ones. Credential headers are
+ * rejected, since they come from the stored API key.
+ */
+export async function setAIProviderHeaders(
+  provider: string,
+  headers: Record<string, string>
+): Promise<void> {
+  if (!isTauri()) {
+    throw new Error(
+      "Provider headers are only available in Tauri desktop mode"
+    );
+  }
+
+  try {
+    await invoke("set_ai_provider_headers", { provider, headers });
+  } catch (error) {
+    console.error("Failed to set provider headers:", error);
+    throw error;
+  }
+}
+
+/**
+ * A request in the AI debug log, kept while the ai.debugLog setting is on.
+ * API keys are never recorded; message text is kept as the
+ * ai.debugLogBodies setting says (redacted, truncated or full).
+ */
+export interface AIDebugLogEntry {
+  /** Milliseconds since the Unix epoch */
+  timestamp: number;
+  provider: string;
+  url: string;
+  request: unknown;
+  status: number | null;
+  response: unknown;
+  error: string | null;
+  durationMs: number;
+}
+
+/**
+ * Get the latest AI debug log entries, oldest first, for bug reports
+ */
+export async function getAIDebugLog(
+  limit?: number
+): Promise<AIDebugLogEntry[]> {
+  if (!isTauri()) {
+    return [];
+  }
+
+  try {
+    return await invoke<AIDebugLogEntry[]>("get_ai_debug_log", { limit });
+  } catch (error) {
+    console.error("Failed to get AI debug log:", error);
+    return [];
+  }
+}
+
+/**
+ * Delete the AI debug log
+ */
+export async function clearAIDebugLog(): Promise<void> {
+  if (!isTauri()) {
+    return;
+  }
+
+  try {
+    await invoke("clear_ai_debug_log");
+  } catch (error) {
+    console.error("Failed to clear AI debug log:", error);
+    throw error;
+  }
+}
+
+export interface LocalModel {
+  name: string;
+  size: number;
+  modifiedAt: number | null;
+  loaded: boolean;
+}
+
+/**
+ * List the downloaded local GGUF models
+ * Empty when the app was built without local inference.
+ */
+export async function listLocalModels(): Promise<LocalModel[]> {
+  if (!isTauri()) {
+    return [];
+  }
+
+  try {
+    return await invoke<LocalModel[]>("list_local_models");
+  } catch (error) {
+    console.error("Failed to list local models:", error);
+    return [];
+  }
+}
+
+/**
+ * Download a GGUF model for the "local" provider
+ * Progress is emitted on the "local-model-download" event.
+ */
+export async function downloadLocalModel(
+  url: string,
+  name?: string
+): Promise<LocalModel> {
+  if (!isTauri()) {
+    throw new Error("Local models are only available in Tauri desktop mode");
+  }
+
+  try {
+    return await invoke<LocalModel>("download_local_model", { url, name });
+  } catch (error) {
+    console.error("Failed to download local model:", error);
+    throw error;
+  }
+}
+
+/**
+ * Delete a local model, unloading it first if it is loaded
+ */
+export async function deleteLocalModel(name: string): Promise<void> {
+  if (!isTauri()) {
+    throw new Error("Local models are only available in Tauri desktop mode");
+  }
+
+  try {
+    await invoke("delete_local_model", { name });
+  } catch (error) {
+    console.error("Failed to delete local model:", error);
+    throw error;
+  }
+}
+
 /**
  * Check if API key exists
  *
@@ -276,6 +805,75 @@ export async function exportConversation(
   }
 }
 
+/**
+ * Generate a short title for a chat from its first messages (Tauri only)
+ * The prompt and the model, configured with the title generation settings
+ * unless given, are chosen by the backend
+ */
+export async function suggestConversationTitle(
+  messages: { role: string; content: string }[],
+  provider?: string,
+  model?: string
+): Promise<string> {
+  if (!isTauri()) {
+    throw new Error("Title generation is only available in Tauri desktop mode");
+  }
+
+  try {
+    return await invoke<string>("suggest_conversation_title", {
+      messages,
+      provider,
+      model,
+    });
+  } catch (error) {
+    console.error("Failed to generate conversation title:", error);
+    throw error;
+  }
+}
+
+/**
+ * Part of a user message: text, or an image by URL (https: or data:) or as
+ * base64 data with its media type (image/png, jpeg, gif or webp)
+ */
+export type AIContentPart =
+  | { type: "text"; text: string }
+  | { type: "image"; url: string }
+  | { type: "image"; data: string; mediaType: string };
+
+/**
+ * Sampling parameters of a proxied request; unset ones are left to the
+ * provider, except maxTokens, which Anthropic always needs (4096 by default).
+ * Anthropic accepts temperatures up to 1 and no penalties.
+ */
+export interface AISamplingOptions {
+  temperature?: number;
+  topP?: number;
+  maxTokens?: number;
+  stop?: string[];
+  frequencyPenalty?: number;
+  presencePenalty?: number;
+}
+
+/**
+ * A JSON schema the reply must follow. The name may use letters, digits,
+ * _ and -. Not supported by Anthropic.
+ */
+export interface AIJsonSchemaFormat {
+  name: string;
+  description?: string;
+  schema: Record<string, unknown>;
+  strict?: boolean;
+}
+
+/**
+ * Options of a proxied request. With jsonSchema, the reply is checked
+ * against the schema in the backend, and a reply that is not valid JSON or
+ * does not match fails with an "Invalid model output" error.
+ */
+export interface AIRequestOptions extends AISamplingOptions {
+  jsonSchema?: AIJsonSchemaFormat;
+}
+
 /**
  * Proxy AI request through Tauri backend (optional, for enhanced privacy)
  * This allows the Rust backend to make API calls instead of the frontend
@@ -283,8 +881,9 @@ export async function exportConversation(
 export async function proxyAIRequest(
   provider: string,
   model: string,
-  messages: Array<{ role: string; content: string }>,
-  systemPrompt?: string
+  messages: Array<{ role: string; content: string | AIContentPart[] }>,
+  systemPrompt?: string,
+  options?: AIRequestOptions
 ): Promise<string> {
   if (!isTauri()) {
     throw new Error("AI proxy is only available in Tauri desktop mode");
@@ -296,6 +895,7 @@ export async function proxyAIRequest(
       model,
       messages,
       systemPrompt,
+      options,
     });
     return response;
   } catch (error) {
@@ -304,6 +904,203 @@ export async function proxyAIRequest(
   }
 }
 
+/**
+ * One prompt of a batch; `id` matches its result to it
+ */
+export interface AIBatchPrompt {
+  id: string;
+  messages: Array<{ role: string; content: string | AIContentPart[] }>;
+}
+
+/**
+ * The reply to one prompt of a batch, or its error
+ */
+export interface AIBatchResult {
+  batchId: string;
+  promptId: string;
+  reply: AIChatReply | null;
+  error: string | null;
+  /** Prompts of the batch completed so far, this one included */
+  completed: number;
+  total: number;
+}
+
+/**
+ * Reply of a proxied chat request
+ */
+export interface AIChatReply {
+  provider: string;
+  model: string;
+  content: string;
+  choices: Array<{
+    index: number;
+    content: string;
+    finishReason: string | null;
+  }>;
+  usage: {
+    inputTokens: number;
+    outputTokens: number;
+    cachedTokens: number;
+    cacheWriteTokens: number;
+  } | null;
+  servedBy: string | null;
+  structured: unknown;
+  /** What moderation found in the request, when ai.moderation is on */
+  moderation: AIModerationResult | null;
+}
+
+/**
+ * Moderation of a chat request: whether it was flagged, the categories
+ * flagged, and the score of every category from 0 to 1
+ */
+export interface AIModerationResult {
+  flagged: boolean;
+  categories: string[];
+  scores: Record<string, number>;
+}
+
+export interface AIBatchSummary {
+  batchId: string;
+  succeeded: number;
+  failed: number;
+}
+
+/**
+ * Send many prompts to one provider and model, a few at a time (4 unless
+ * `concurrency` says, up to 16), such as to summarize every chapter of a
+ * book. `onResult` gets each result as it completes, in completion order;
+ * the promise resolves once every prompt is answered.
+ */
+export async function proxyAIBatchRequest(
+  provider: string,
+  model: string,
+  prompts: AIBatchPrompt[],
+  options?: {
+    systemPrompt?: string;
+    requestOptions?: AIRequestOptions;
+    concurrency?: number;
+    onResult?: (result: AIBatchResult) => void;
+  }
+): Promise<AIBatchSummary> {
+  if (!isTauri()) {
+    throw new Error("AI batches are only available in Tauri desktop mode");
+  }
+
+  const batchId = crypto.randomUUID();
+  const onResult = options?.onResult;
+  const unlisten = onResult
+    ? await listen<AIBatchResult>("ai-batch-result", (event) => {
+        if (event.payload.batchId === batchId) {
+          onResult(event.payload);
+        }
+      })
+    : undefined;
+  try {
+    return await invoke<AIBatchSummary>("proxy_ai_batch_request", {
+      batchId,
+      provider,
+      model,
+      prompts,
+      systemPrompt: options?.systemPrompt,
+      options: options?.requestOptions,
+      concurrency: options?.concurrency,
+    });
+  } catch (error) {
+    console.error("AI batch request failed:", error);
+    throw error;
+  } finally {
+    unlisten?.();
+  }
+}
+
+/**
+ * Transcript of an audio file
+ */
+export interface Transcription {
+  text: string;
+  /** Language spoken, as detected or given */
+  language: string | null;
+  /** Length of the audio in seconds */
+  duration: number | null;
+}
+
+/**
+ * Transcribe an audio file with OpenAI or Groq Whisper, using the stored
+ * key, for voice input in the AI chat. `language` is an ISO-639-1 code,
+ * detected when unset; `prompt` is text the speech is likely to contain.
+ */
+export async function transcribeAudio(
+  provider: string,
+  path: string,
+  options?: { model?: string; language?: string; prompt?: string }
+): Promise<Transcription> {
+  if (!isTauri()) {
+    throw new Error("Transcription is only available in Tauri desktop mode");
+  }
+
+  try {
+    return await invoke<Transcription>("proxy_transcription_request", {
+      provider,
+      path,
+      model: options?.model,
+      language: options?.language,
+      prompt: options?.prompt,
+    });
+  } catch (error) {
+    console.error("Transcription request failed:", error);
+    throw error;
+  }
+}
+
+/**
+ * Options of an image generation request; unset ones are left to the
+ * provider
+ */
+export interface AIImageOptions {
+  /** Number of images, 1 to 10 (DALL·E 3 makes one) */
+  n?: number;
+  /** `WIDTHxHEIGHT`, such as `1024x1024`, or `auto` */
+  size?: string;
+  quality?: string;
+  style?: string;
+}
+
+/**
+ * A generated image, saved in the app data and served by the asset protocol
+ */
+export interface GeneratedImage {
+  path: string;
+  /** Prompt the provider rewrote the request into (DALL·E 3) */
+  revisedPrompt: string | null;
+}
+
+/**
+ * Generate images with OpenAI or a custom OpenAI-compatible provider; use
+ * `convertFileSrc` on the returned paths to show them
+ */
+export async function generateAIImage(
+  provider: string,
+  model: string,
+  prompt: string,
+  options?: AIImageOptions
+): Promise<GeneratedImage[]> {
+  if (!isTauri()) {
+    throw new Error("Image generation is only available in Tauri desktop mode");
+  }
+
+  try {
+    return await invoke<GeneratedImage[]>("proxy_image_request", {
+      provider,
+      model,
+      prompt,
+      options,
+    });
+  } catch (error) {
+    console.error("Image generation request failed:", error);
+    throw error;
+  }
+}
+
 /**
  * AI usage statistics interface
  */
@@ -320,10 +1117,24 @@ export interface AIUsageStats {
       totalTokens: number;
       totalRequests: number;
       costEstimate: number;
+      upstreams?: Record<string, number>;
     }
   >;
   firstRequestAt: number | null;
   lastRequestAt: number | null;
+  mcpStats: {
+    totalCalls: number;
+    failedCalls: number;
+    serverStats: Record<
+      string,
+      {
+        totalCalls: number;
+        failedCalls: number;
+        tools: Record<string, number>;
+      }
+    >;
+    lastCallAt: number | null;
+  };
 }
 
 /**
@@ -345,14 +1156,16 @@ export async function getAIUsageStats(): Promise<AIUsageStats | null> {
 }
 
 /**
- * Update AI usage statistics after a request
+ * Update AI usage statistics after a request sent without the AI proxy.
+ * Requests through `proxyAIRequest` are counted by the backend, which also
+ * prices the tokens from the model (see `getAIPricing`).
  */
 export async function updateAIUsageStats(
   provider: string,
+  model: string | null,
   inputTokens: number,
   outputTokens: number,
-  cachedTokens?: number,
-  cost?: number
+  cachedTokens?: number
 ): Promise<void> {
   if (!isTauri()) {
     return;
@@ -361,16 +1174,89 @@ export async function updateAIUsageStats(
   try {
     await invoke("update_ai_usage_stats", {
       provider,
+      model,
       inputTokens,
       outputTokens,
       cachedTokens: cachedTokens ?? null,
-      cost: cost ?? null,
     });
   } catch (error) {
     console.error("Failed to update AI usage stats:", error);
   }
 }
 
+/**
+ * Price of a model in US dollars per million tokens; cached prompt tokens
+ * cost the input price unless priced separately
+ */
+export interface ModelPrice {
+  input: number;
+  output: number;
+  cachedInput?: number;
+  cacheWrite?: number;
+}
+
+/**
+ * A priced model; `custom` when the user set the price
+ */
+export interface ModelPricing {
+  provider: string;
+  model: string;
+  price: ModelPrice;
+  custom: boolean;
+}
+
+/**
+ * Get the model prices the usage statistics are costed with
+ */
+export async function getAIPricing(): Promise<ModelPricing[]> {
+  if (!isTauri()) {
+    return [];
+  }
+
+  try {
+    return await invoke<ModelPricing[]>("get_ai_pricing");
+  } catch (error) {
+    console.error("Failed to get AI pricing:", error);
+    return [];
+  }
+}
+
+/**
+ * Set the price of a model, or with `null` restore the bundled price
+ */
+export async function setAIModelPrice(
+  provider: string,
+  model: string,
+  price: ModelPrice | null
+): Promise<void> {
+  if (!isTauri()) {
+    throw new Error("AI pricing is only available in Tauri desktop mode");
+  }
+
+  try {
+    await invoke("set_ai_model_price", { provider, model, price });
+  } catch (error) {
+    console.error("Failed to set AI model price:", error);
+    throw error;
+  }
+}
+
+/**
+ * Drop every price set by the user
+ */
+export async function resetAIPricing(): Promise<void> {
+  if (!isTauri()) {
+    return;
+  }
+
+  try {
+    await invoke("reset_ai_pricing");
+  } catch (error) {
+    console.error("Failed to reset AI pricing:", error);
+    throw error;
+  }
+}
+
 /**
  * Clear AI usage statistics
  */
@@ -588,6 +1474,8 @@ export interface MCPExportResult {
   filePath?: string;
   serverCount: number;
   error?: string;
+  /** Fields blanked by a safe-share export, as `server.env.NAME` */
+  strippedFields?: string[];
 }
 
 /**
@@ -760,8 +1648,9 @@ export async function importMCPServersFromFile(
 
 /**
  * Export MCP servers to JSON string
+ * With safeShare, env values and headers that look like secrets are blanked
  */
-export async function exportMCPServers(): Promise<string> {
+export async function exportMCPServers(safeShare = false): Promise<string> {
   if (!isTauri()) {
     throw new Error(
       "MCP server export is only available in Tauri desktop mode"
@@ -769,7 +1658,7 @@ export async function exportMCPServers(): Promise<string> {
   }
 
   try {
-    const data = await invoke<string>("export_mcp_servers");
+    const data = await invoke<string>("export_mcp_servers", { safeShare });
     return data;
   } catch (error) {
     console.error("Failed to export MCP servers:", error);
@@ -781,7 +1670,8 @@ export async function exportMCPServers(): Promise<string> {
  * Export MCP servers to a file
  */
 export async function exportMCPServersToFile(
-  filePath: string
+  filePath: string,
+  safeShare = false
 ): Promise<MCPExportResult> {
   if (!isTauri()) {
     throw new Error(
@@ -792,6 +1682,7 @@ export async function exportMCPServersToFile(
   try {
     const result = await invoke<MCPExportResult>("export_mcp_servers_to_file", {
       filePath,
+      safeShare,
     });
     return result;
   } catch (error) {
@@ -803,8 +1694,11 @@ export async function exportMCPServersToFile(
 /**
  * Export MCP servers in Claude Desktop format
  * This format is compatible with Claude Desktop, Cursor, and other tools
+ * With safeShare, env values and headers that look like secrets are blanked
  */
-export async function exportMCPServersClaudeFormat(): Promise<string> {
+export async function exportMCPServersClaudeFormat(
+  safeShare = false
+): Promise<string> {
   if (!isTauri()) {
     throw new Error(
       "MCP server export is only available in Tauri desktop mode"
@@ -812,7 +1706,9 @@ export async function exportMCPServersClaudeFormat(): Promise<string> {
   }
 
   try {
-    const data = await invoke<string>("export_mcp_servers_claude_format");
+    const data = await invoke<string>("export_mcp_servers_claude_format", {
+      safeShare,
+    });
     return data;
   } catch (error) {
     console.error("Failed to export MCP servers in Claude format:", error);
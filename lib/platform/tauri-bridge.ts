@@ -355,8 +355,15 @@ export async function getFileTimes(path: string): Promise<FileTimes | null> {
 export async function revealInFileManager(path: string): Promise<boolean> {
   if (!isTauriRuntime) return false;
 
-  const result = await safeInvoke<boolean>("reveal_in_file_manager", { path });
-  return result ?? false;
+  try {
+    const invoke = await getInvoke();
+    if (!invoke) return false;
+    await invoke<void>("reveal_in_file_manager", { path });
+    return true;
+  } catch (error) {
+    console.error("Failed to reveal file in file manager", path, error);
+    return false;
+  }
 }
 
 export async function renameFile(
@@ -0,0 +1,254 @@
+//! Batches of chat requests
+//!
+//! Bulk operations, such as summarizing every chapter of a book, send many
+//! prompts to the same provider and model. `proxy_ai_batch_request` sends
+//! them concurrently, a bounded number at a time so rate limits are not
+//! tripped, and emits each result as an `ai-batch-result` event as soon as
+//! it arrives, in completion order. Each prompt is sent like a proxied chat
+//! request, with failover, retries and usage statistics; one failing does
+//! not stop the others. The command returns once every prompt is answered.
+
+use crate::commands::ai_proxy::{
+    check_completion_options, record_reply_usage, send_chat_request, AIMessage, ChatOptions,
+    ChatReply,
+};
+use crate::commands::events::emit_event;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Event emitted with an `AIBatchResult` as each prompt of a batch completes
+pub const AI_BATCH_RESULT_EVENT: &str = "ai-batch-result";
+
+/// Prompts of a batch sent at once when the request does not say
+const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+const MAX_BATCH_CONCURRENCY: usize = 16;
+/// Most prompts one batch may hold
+const MAX_BATCH_PROMPTS: usize = 1000;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// One prompt of a batch
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchPrompt {
+    /// Chosen by the caller to match the result to the prompt, such as a
+    /// chapter id
+    pub id: String,
+    pub messages: Vec<AIMessage>,
+}
+
+/// Payload of `ai-batch-result`: the reply to one prompt, or its error
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AIBatchResult {
+    pub batch_id: String,
+    pub prompt_id: String,
+    pub reply: Option<ChatReply>,
+    pub error: Option<String>,
+    /// Prompts of the batch completed so far, this one included
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Outcome of a whole batch
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AIBatchSummary {
+    pub batch_id: String,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Run a task for each item, at most `concurrency` at a time, handing each
+/// output with the index of its item to `done` as soon as it completes
+pub async fn run_bounded<I, T, F, Fut>(
+    items: Vec<I>,
+    concurrency: usize,
+    run: F,
+    mut done: impl FnMut(usize, T),
+) where
+    F: Fn(I) -> Fut,
+    Fut: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (index, item) in items.into_iter().enumerate() {
+        let permits = permits.clone();
+        let task = run(item);
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (index, task.await)
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((index, output)) => done(index, output),
+            Err(e) => log::error!("Batch task failed: {}", e),
+        }
+    }
+}
+
+fn check_batch(prompts: &[BatchPrompt], concurrency: usize) -> Result<(), AppError> {
+    if prompts.is_empty() || prompts.len() > MAX_BATCH_PROMPTS {
+        return Err(AppError::InvalidInput(format!(
+            "A batch holds 1 to {} prompts",
+            MAX_BATCH_PROMPTS
+        )));
+    }
+    if !(1..=MAX_BATCH_CONCURRENCY).contains(&concurrency) {
+        return Err(AppError::InvalidInput(format!(
+            "Batch concurrency must be within 1..={}",
+            MAX_BATCH_CONCURRENCY
+        )));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Send prompts to a provider concurrently, emitting each result as it
+/// completes; `batch_id`, chosen by the caller, tags the events
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn proxy_ai_batch_request(
+    app: tauri::AppHandle,
+    batch_id: String,
+    provider: String,
+    model: String,
+    prompts: Vec<BatchPrompt>,
+    system_prompt: Option<String>,
+    options: Option<ChatOptions>,
+    concurrency: Option<usize>,
+) -> Result<AIBatchSummary, AppError> {
+    let concurrency = concurrency.unwrap_or(DEFAULT_BATCH_CONCURRENCY);
+    check_batch(&prompts, concurrency)?;
+    let options = Arc::new(options.unwrap_or_default());
+    check_completion_options(&provider, &options)?;
+
+    let total = prompts.len();
+    let ids: Vec<String> = prompts.iter().map(|prompt| prompt.id.clone()).collect();
+    log::info!(
+        "Batch {}: {} prompts to {} {}, {} at a time",
+        batch_id,
+        total,
+        provider,
+        model,
+        concurrency
+    );
+    let mut summary = AIBatchSummary {
+        batch_id: batch_id.clone(),
+        succeeded: 0,
+        failed: 0,
+    };
+    run_bounded(
+        prompts,
+        concurrency,
+        |prompt| {
+            let (provider, model, system_prompt, options) = (
+                provider.clone(),
+                model.clone(),
+                system_prompt.clone(),
+                options.clone(),
+            );
+            async move {
+                send_chat_request(&provider, &model, prompt.messages, system_prompt, &options).await
+            }
+        },
+        |index, result| {
+            let (reply, error) = match result {
+                Ok(reply) => {
+                    record_reply_usage(&app, &provider, &reply);
+                    summary.succeeded += 1;
+                    (Some(reply), None)
+                }
+                Err(e) => {
+                    log::warn!("Batch {} prompt {} failed: {}", batch_id, ids[index], e);
+                    summary.failed += 1;
+                    (None, Some(e.to_string()))
+                }
+            };
+            emit_event(
+                &app,
+                &AIBatchResult {
+                    batch_id: batch_id.clone(),
+                    prompt_id: ids[index].clone(),
+                    reply,
+                    error,
+                    completed: summary.succeeded + summary.failed,
+                    total,
+                },
+            );
+        },
+    )
+    .await;
+    log::info!(
+        "Batch {} done: {} answered, {} failed",
+        batch_id,
+        summary.succeeded,
+        summary.failed
+    );
+    Ok(summary)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn batches_run_a_bounded_number_at_once() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let mut finished = Vec::new();
+        run_bounded(
+            (0..12u64).collect(),
+            3,
+            |item| {
+                let (running, most) = (running.clone(), most.clone());
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    // Later items finish first
+                    tokio::time::sleep(Duration::from_millis(120 - item * 8)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    item * 10
+                }
+            },
+            |index, output| finished.push((index, output)),
+        )
+        .await;
+        assert_eq!(most.load(Ordering::SeqCst), 3);
+        assert_eq!(finished.len(), 12);
+        assert!(finished
+            .iter()
+            .all(|(index, output)| *output == *index as u64 * 10));
+        assert_ne!(finished[0].0, 0);
+
+        let prompts = [BatchPrompt {
+            id: "chapter-1".to_string(),
+            messages: Vec::new(),
+        }];
+        assert!(check_batch(&prompts, 4).is_ok());
+        assert!(check_batch(&[], 4).is_err());
+        assert!(check_batch(&prompts, 0).is_err());
+        assert!(check_batch(&prompts, MAX_BATCH_CONCURRENCY + 1).is_err());
+    }
+}
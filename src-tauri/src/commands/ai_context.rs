@@ -0,0 +1,384 @@
+//! Document context assembly for AI chat
+//!
+//! Selects the passages of a library document most relevant to a question and
+//! fits them to a token budget, so "chat with this book" gets a ready-made
+//! context block with numbered citations.
+
+use crate::commands::library::{get_document, lock_library, LibraryDocument, LibraryState};
+use crate::commands::rag::{
+    chunk_sections, extract_document_sections, get_index_status, run_semantic_search,
+    SemanticSearchQuery, DEFAULT_CHUNK_CHARS, DEFAULT_CHUNK_OVERLAP,
+};
+use crate::error::AppError;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Budget used when the caller does not give one
+const DEFAULT_TOKEN_BUDGET: usize = 4000;
+
+/// Chunks requested from the index before fitting them to the budget
+const SEMANTIC_CANDIDATES: usize = 50;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Where a cited passage comes from
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextCitation {
+    /// Number used as `[n]` in the context text
+    pub marker: u32,
+    pub location_kind: String,
+    pub location: u32,
+    pub location_label: Option<String>,
+    pub char_start: usize,
+    pub char_end: usize,
+    /// Similarity to the query for semantic selection
+    pub score: Option<f32>,
+}
+
+/// A context block ready to be injected into a prompt
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentContext {
+    pub document_id: String,
+    /// "semantic" (embedding index), "keyword" or "outline" (no usable query)
+    pub strategy: String,
+    pub context: String,
+    pub citations: Vec<ContextCitation>,
+    pub token_count: usize,
+    /// Some relevant passages did not fit in the budget
+    pub truncated: bool,
+}
+
+/// A passage that may be included in the context
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContextCandidate {
+    /// Position in the document, used to keep selected passages in reading order
+    pub order: usize,
+    pub location_kind: String,
+    pub location: u32,
+    pub location_label: Option<String>,
+    pub char_start: usize,
+    pub char_end: usize,
+    pub text: String,
+    pub score: Option<f32>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Count tokens with the o200k BPE, a close estimate for current chat models
+pub fn count_tokens(text: &str) -> usize {
+    tiktoken_rs::o200k_base_singleton()
+        .encode_ordinary(text)
+        .len()
+}
+
+/// Lowercase query words worth matching
+fn query_terms(query: &str) -> HashSet<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Rank candidates by query word matches, with chapter titles counting extra
+///
+/// Without any match the first passage of every section is used, giving an
+/// outline of the document. Returns the strategy name and the ranked list.
+pub fn rank_by_keywords(
+    candidates: Vec<ContextCandidate>,
+    query: &str,
+) -> (&'static str, Vec<ContextCandidate>) {
+    let terms = query_terms(query);
+    let mut scored: Vec<(usize, ContextCandidate)> = candidates
+        .iter()
+        .map(|candidate| {
+            let text = candidate.text.to_lowercase();
+            let label = candidate
+                .location_label
+                .as_deref()
+                .unwrap_or_default()
+                .to_lowercase();
+            let score = terms
+                .iter()
+                .map(|term| text.matches(term.as_str()).count() + 3 * label.contains(term) as usize)
+                .sum();
+            (score, candidate.clone())
+        })
+        .filter(|(score, _)| *score > 0)
+        .collect();
+
+    if scored.is_empty() {
+        let mut seen = HashSet::new();
+        let outline = candidates
+            .into_iter()
+            .filter(|c| seen.insert((c.location_kind.clone(), c.location)))
+            .collect();
+        return ("outline", outline);
+    }
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.order.cmp(&b.1.order)));
+    ("keyword", scored.into_iter().map(|(_, c)| c).collect())
+}
+
+/// Human-readable location of a passage
+pub fn describe_location(kind: &str, location: u32, label: Option<&str>) -> String {
+    match (kind, label) {
+        ("page", _) => format!("Page {}", location),
+        (_, Some(label)) => label.to_string(),
+        ("chapter", None) => format!("Chapter {}", location + 1),
+        _ => "Document".to_string(),
+    }
+}
+
+fn context_header(document: &LibraryDocument) -> String {
+    let title = document.title.as_deref().unwrap_or(&document.file_name);
+    match document.author.as_deref() {
+        Some(author) => format!(
+            "Excerpts from \"{}\" by {}. Cite them by their [n] markers.",
+            title, author
+        ),
+        None => format!(
+            "Excerpts from \"{}\". Cite them by their [n] markers.",
+            title
+        ),
+    }
+}
+
+/// Take ranked passages while they fit in the budget, then restore reading order
+///
+/// Returns the context text, its citations, token count and whether any
+/// passage was left out.
+pub fn fit_to_budget(
+    header: &str,
+    ranked: Vec<ContextCandidate>,
+    token_budget: usize,
+) -> (String, Vec<ContextCitation>, usize, bool) {
+    let mut used = count_tokens(header);
+    let mut truncated = false;
+    let mut selected: Vec<ContextCandidate> = Vec::new();
+    for candidate in ranked {
+        // The marker is not known yet; "[000]" costs as much as any real one
+        let entry = format!(
+            "\n\n[000] {}\n{}",
+            describe_location(
+                &candidate.location_kind,
+                candidate.location,
+                candidate.location_label.as_deref()
+            ),
+            candidate.text
+        );
+        let cost = count_tokens(&entry);
+        if used + cost > token_budget {
+            truncated = true;
+            continue;
+        }
+        used += cost;
+        selected.push(candidate);
+    }
+    selected.sort_by_key(|candidate| candidate.order);
+
+    let mut context = header.to_string();
+    let mut citations = Vec::with_capacity(selected.len());
+    for (candidate, marker) in selected.into_iter().zip(1u32..) {
+        let location = describe_location(
+            &candidate.location_kind,
+            candidate.location,
+            candidate.location_label.as_deref(),
+        );
+        context.push_str(&format!(
+            "\n\n[{}] {}\n{}",
+            marker, location, candidate.text
+        ));
+        citations.push(ContextCitation {
+            marker,
+            location_kind: candidate.location_kind,
+            location: candidate.location,
+            location_label: candidate.location_label,
+            char_start: candidate.char_start,
+            char_end: candidate.char_end,
+            score: candidate.score,
+        });
+    }
+    let token_count = count_tokens(&context);
+    (context, citations, token_count, truncated)
+}
+
+/// Chunks of the document ranked through its embedding index, if it has an
+/// up-to-date one
+async fn semantic_candidates(
+    library: &LibraryState,
+    document_id: &str,
+    query: &str,
+) -> Option<Vec<ContextCandidate>> {
+    let status = get_index_status(&*lock_library(library).ok()?, document_id).ok()??;
+    if status.stale || query.trim().is_empty() {
+        return None;
+    }
+    let search = SemanticSearchQuery {
+        query: query.to_string(),
+        document_ids: Some(vec![document_id.to_string()]),
+        top_k: Some(SEMANTIC_CANDIDATES),
+        provider: Some(status.provider),
+        model: Some(status.model),
+    };
+    match run_semantic_search(library, &search).await {
+        Ok(hits) => Some(
+            hits.into_iter()
+                .map(|hit| ContextCandidate {
+                    order: hit.chunk_index as usize,
+                    location_kind: hit.location_kind,
+                    location: hit.location,
+                    location_label: hit.location_label,
+                    char_start: hit.char_start,
+                    char_end: hit.char_end,
+                    text: hit.text,
+                    score: Some(hit.score),
+                })
+                .collect(),
+        ),
+        Err(e) => {
+            log::warn!("Semantic search failed, using keyword selection: {}", e);
+            None
+        }
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Build a context block from a library document for answering `query`
+///
+/// Uses the document's embedding index when it has one, and keyword matches
+/// against the text and chapter titles otherwise.
+#[tauri::command]
+pub async fn build_document_context(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+    query: String,
+    token_budget: Option<usize>,
+) -> Result<DocumentContext, AppError> {
+    let library = state.inner().clone();
+    let document = get_document(&*lock_library(&library)?, &document_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Document '{}' not found", document_id)))?;
+    let token_budget = token_budget.unwrap_or(DEFAULT_TOKEN_BUDGET);
+
+    let (strategy, ranked) = match semantic_candidates(&library, &document_id, &query).await {
+        Some(candidates) => ("semantic", candidates),
+        None => {
+            let path = PathBuf::from(&document.path);
+            if !path.is_file() {
+                return Err(AppError::NotFound(format!(
+                    "File not found: {}",
+                    document.path
+                )));
+            }
+            let format = document.format.clone();
+            let sections = tauri::async_runtime::spawn_blocking(move || {
+                extract_document_sections(&path, &format)
+            })
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))??;
+            let candidates = chunk_sections(&sections, DEFAULT_CHUNK_CHARS, DEFAULT_CHUNK_OVERLAP)
+                .into_iter()
+                .enumerate()
+                .map(|(order, chunk)| {
+                    let section = &sections[chunk.section];
+                    ContextCandidate {
+                        order,
+                        location_kind: section.kind.clone(),
+                        location: section.location,
+                        location_label: section.label.clone(),
+                        char_start: chunk.char_start,
+                        char_end: chunk.char_end,
+                        text: chunk.text,
+                        score: None,
+                    }
+                })
+                .collect();
+            rank_by_keywords(candidates, &query)
+        }
+    };
+
+    let header = context_header(&document);
+    let (context, citations, token_count, truncated) =
+        tauri::async_runtime::spawn_blocking(move || fit_to_budget(&header, ranked, token_budget))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(DocumentContext {
+        document_id,
+        strategy: strategy.to_string(),
+        context,
+        citations,
+        token_count,
+        truncated,
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(order: usize, location: u32, label: Option<&str>, text: &str) -> ContextCandidate {
+        ContextCandidate {
+            order,
+            location_kind: "chapter".to_string(),
+            location,
+            location_label: label.map(|l| l.to_string()),
+            char_start: 0,
+            char_end: text.chars().count(),
+            text: text.to_string(),
+            score: None,
+        }
+    }
+
+    #[test]
+    fn keyword_ranking_prefers_titles_and_falls_back_to_outline() {
+        let candidates = vec![
+            candidate(0, 0, Some("Intro"), "Whales are mentioned once."),
+            candidate(1, 0, Some("Intro"), "More of the introduction."),
+            candidate(2, 1, Some("Whales"), "The sea is deep."),
+            candidate(3, 2, None, "Whales and whales again, whales."),
+        ];
+        let (strategy, ranked) = rank_by_keywords(candidates.clone(), "Tell me about whales");
+        assert_eq!(strategy, "keyword");
+        let orders: Vec<usize> = ranked.iter().map(|c| c.order).collect();
+        assert_eq!(orders, vec![2, 3, 0]);
+
+        let (strategy, ranked) = rank_by_keywords(candidates, "?");
+        assert_eq!(strategy, "outline");
+        let orders: Vec<usize> = ranked.iter().map(|c| c.order).collect();
+        assert_eq!(orders, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn fit_to_budget_skips_oversized_passages_and_keeps_reading_order() {
+        let long = "word ".repeat(200);
+        let ranked = vec![
+            candidate(5, 3, None, "Later passage."),
+            candidate(1, 0, Some("Opening"), &long),
+            candidate(2, 1, None, "Earlier passage."),
+        ];
+        let (context, citations, tokens, truncated) = fit_to_budget("Header.", ranked, 60);
+        assert!(truncated);
+        assert!(tokens <= 60);
+        assert_eq!(
+            context,
+            "Header.\n\n[1] Chapter 2\nEarlier passage.\n\n[2] Chapter 4\nLater passage."
+        );
+        let markers: Vec<(u32, u32)> = citations.iter().map(|c| (c.marker, c.location)).collect();
+        assert_eq!(markers, vec![(1, 1), (2, 3)]);
+        assert_eq!(describe_location("page", 7, Some("x")), "Page 7");
+    }
+}
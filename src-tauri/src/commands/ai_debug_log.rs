@@ -0,0 +1,476 @@
+//! Debug log of AI requests
+//!
+//! When the `ai.debugLog` setting is on, every request the AI proxy sends is
+//! appended to `ai_debug.jsonl` in the log directory with its response or
+//! error, status and duration, so users can attach it to bug reports. API
+//! keys never reach the log: credentials travel in headers, which are not
+//! recorded, and fields and URL parameters named like secrets are replaced.
+//! `ai.debugLogBodies` decides how much of the message text is kept:
+//! "redacted" replaces it with its length, "truncated" keeps the start of
+//! long text, and "full" keeps everything. Embedding vectors are only
+//! counted unless bodies are kept in full. The file is capped: past
+//! `MAX_DEBUG_LOG_BYTES` it replaces the previous file, and only those two
+//! are kept.
+
+use crate::commands::logging::get_log_dir;
+use crate::commands::settings::{setting, SETTING_CHANGED_EVENT};
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+use tauri::Listener;
+
+/// Setting that turns the debug log on
+pub const AI_DEBUG_LOG_SETTING: &str = "ai.debugLog";
+
+/// Setting holding how much message text is logged
+pub const AI_DEBUG_LOG_BODIES_SETTING: &str = "ai.debugLogBodies";
+
+/// Debug log file name in the log directory
+pub const AI_DEBUG_LOG_FILE: &str = "ai_debug.jsonl";
+
+/// Previous debug log, replaced when the current one is full
+const AI_DEBUG_LOG_PREVIOUS_FILE: &str = "ai_debug.1.jsonl";
+
+/// Size past which the debug log starts over
+const MAX_DEBUG_LOG_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Characters of a text kept when bodies are truncated
+const TRUNCATED_TEXT_CHARS: usize = 200;
+
+/// Fields holding message text, logged as the body mode says
+const TEXT_FIELDS: &[&str] = &[
+    "content",
+    "text",
+    "system",
+    "input",
+    "prompt",
+    "instructions",
+    "arguments",
+    "data",
+    "url",
+];
+
+/// Fields describing the text around them, kept inside message text
+const STRUCTURE_FIELDS: &[&str] = &[
+    "type",
+    "role",
+    "model",
+    "id",
+    "object",
+    "media_type",
+    "detail",
+    "finish_reason",
+    "stop_reason",
+];
+
+/// Fields and URL parameters holding credentials, never logged
+const SECRET_FIELDS: &[&str] = &[
+    "api_key",
+    "apikey",
+    "api-key",
+    "key",
+    "authorization",
+    "access_token",
+    "refresh_token",
+    "token",
+    "client_secret",
+    "secret",
+    "password",
+];
+
+const REDACTED: &str = "[redacted]";
+
+/// Number arrays longer than this, such as embeddings, are logged by length
+const COUNTED_ARRAY_LENGTH: usize = 16;
+
+const DEFAULT_DEBUG_LOG_ENTRIES: usize = 200;
+const MAX_DEBUG_LOG_ENTRIES: usize = 2000;
+
+/// Where entries are written and how, while the log is on
+static AI_DEBUG_LOG: RwLock<Option<DebugLogTarget>> = RwLock::new(None);
+
+/// Serializes appends and rotation of the file
+static AI_DEBUG_LOG_LOCK: Mutex<()> = Mutex::new(());
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// How much message text is logged
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyMode {
+    Redacted,
+    Truncated,
+    Full,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct DebugLogTarget {
+    dir: PathBuf,
+    bodies: BodyMode,
+}
+
+/// One request and its outcome
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AIDebugLogEntry {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+    pub provider: String,
+    pub url: String,
+    pub request: Value,
+    /// HTTP status, when the provider answered
+    pub status: Option<u16>,
+    /// Response body, as JSON when it parses
+    pub response: Option<Value>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// A request to record, before redaction
+pub struct AIExchange<'a> {
+    pub provider: &'a str,
+    pub url: &'a str,
+    pub request: &'a Value,
+    pub status: Option<u16>,
+    pub response: Option<&'a str>,
+    pub error: Option<&'a str>,
+    pub duration: Duration,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn is_secret_field(name: &str) -> bool {
+    SECRET_FIELDS.contains(&name.to_ascii_lowercase().as_str())
+}
+
+/// A URL with the values of secret parameters replaced
+pub fn redact_url(url: &str) -> String {
+    let Ok(mut parsed) = url::Url::parse(url) else {
+        return url.to_string();
+    };
+    if parsed.query().is_none() {
+        return url.to_string();
+    }
+    let pairs: Vec<(String, String)> = parsed
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if is_secret_field(&name) {
+                REDACTED.to_string()
+            } else {
+                value.into_owned()
+            };
+            (name.into_owned(), value)
+        })
+        .collect();
+    parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    parsed.to_string()
+}
+
+fn redact_text(text: &str, bodies: BodyMode) -> Value {
+    match bodies {
+        BodyMode::Full => Value::from(text),
+        BodyMode::Redacted => Value::from(format!("[{} chars]", text.chars().count())),
+        BodyMode::Truncated => {
+            let length = text.chars().count();
+            if length <= TRUNCATED_TEXT_CHARS {
+                return Value::from(text);
+            }
+            let start: String = text.chars().take(TRUNCATED_TEXT_CHARS).collect();
+            Value::from(format!(
+                "{}…[+{} chars]",
+                start,
+                length - TRUNCATED_TEXT_CHARS
+            ))
+        }
+    }
+}
+
+/// A JSON body as the log keeps it: secrets replaced, message text kept as
+/// the body mode says and long number arrays, such as embeddings, counted
+pub fn redact_body(value: &Value, bodies: BodyMode) -> Value {
+    redact_value(value, bodies, false)
+}
+
+fn redact_value(value: &Value, bodies: BodyMode, text: bool) -> Value {
+    match value {
+        Value::String(content) if text => redact_text(content, bodies),
+        Value::Array(items)
+            if bodies != BodyMode::Full
+                && items.len() > COUNTED_ARRAY_LENGTH
+                && items.iter().all(Value::is_number) =>
+        {
+            Value::from(format!("[{} numbers]", items.len()))
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| redact_value(item, bodies, text))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| {
+                    let name_is = |fields: &[&str]| fields.contains(&name.as_str());
+                    let field = if is_secret_field(name) {
+                        Value::from(REDACTED)
+                    } else if name_is(STRUCTURE_FIELDS) {
+                        redact_value(field, bodies, false)
+                    } else {
+                        redact_value(field, bodies, text || name_is(TEXT_FIELDS))
+                    };
+                    (name.clone(), field)
+                })
+                .collect::<Map<String, Value>>(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+/// The log entry of an exchange
+pub fn debug_log_entry(exchange: &AIExchange, bodies: BodyMode, timestamp: i64) -> AIDebugLogEntry {
+    AIDebugLogEntry {
+        timestamp,
+        provider: exchange.provider.to_string(),
+        url: redact_url(exchange.url),
+        request: redact_body(exchange.request, bodies),
+        status: exchange.status,
+        response: exchange.response.map(|body| {
+            match serde_json::from_str::<Value>(body) {
+                Ok(json) => redact_body(&json, bodies),
+                // Error pages and other text
+                Err(_) => redact_text(body, bodies),
+            }
+        }),
+        error: exchange.error.map(str::to_string),
+        duration_ms: exchange.duration.as_millis() as u64,
+    }
+}
+
+/// Append an entry, starting over when the file is full
+pub fn append_debug_log_entry(dir: &Path, entry: &AIDebugLogEntry) -> Result<(), AppError> {
+    let _guard = AI_DEBUG_LOG_LOCK
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    fs::create_dir_all(dir)?;
+    let path = dir.join(AI_DEBUG_LOG_FILE);
+    if fs::metadata(&path).is_ok_and(|metadata| metadata.len() >= MAX_DEBUG_LOG_BYTES) {
+        fs::rename(&path, dir.join(AI_DEBUG_LOG_PREVIOUS_FILE))?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// The latest entries, oldest first
+pub fn read_debug_log(dir: &Path, limit: usize) -> Vec<AIDebugLogEntry> {
+    let mut entries: Vec<AIDebugLogEntry> = [AI_DEBUG_LOG_PREVIOUS_FILE, AI_DEBUG_LOG_FILE]
+        .iter()
+        .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+        .flat_map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let skip = entries.len().saturating_sub(limit);
+    entries.drain(..skip);
+    entries
+}
+
+/// Whether the debug log is on
+pub fn ai_debug_log_enabled() -> bool {
+    AI_DEBUG_LOG.read().is_ok_and(|target| target.is_some())
+}
+
+/// Record an exchange if the log is on; failures to write are only logged
+pub fn record_ai_exchange(exchange: AIExchange) {
+    let Some(target) = AI_DEBUG_LOG.read().ok().and_then(|target| target.clone()) else {
+        return;
+    };
+    let entry = debug_log_entry(
+        &exchange,
+        target.bodies,
+        chrono::Utc::now().timestamp_millis(),
+    );
+    if let Err(e) = append_debug_log_entry(&target.dir, &entry) {
+        log::warn!("Failed to write the AI debug log: {}", e);
+    }
+}
+
+fn load_debug_log_target(app: &tauri::AppHandle) -> Result<Option<DebugLogTarget>, AppError> {
+    if !setting::<bool>(app, AI_DEBUG_LOG_SETTING)? {
+        return Ok(None);
+    }
+    Ok(Some(DebugLogTarget {
+        dir: get_log_dir(app)?,
+        bodies: setting(app, AI_DEBUG_LOG_BODIES_SETTING)?,
+    }))
+}
+
+fn load_ai_debug_log(app: &tauri::AppHandle) {
+    let target = load_debug_log_target(app).unwrap_or_else(|e| {
+        log::warn!("Failed to load the AI debug log settings: {}", e);
+        None
+    });
+    if let Some(target) = &target {
+        log::info!("AI debug log on, bodies {:?}", target.bodies);
+    }
+    if let Ok(mut current) = AI_DEBUG_LOG.write() {
+        *current = target;
+    }
+}
+
+/// Load the debug log settings and reload them whenever they change
+pub fn init_ai_debug_log(app: &tauri::AppHandle) {
+    load_ai_debug_log(app);
+    let handle = app.clone();
+    app.listen_any(SETTING_CHANGED_EVENT, move |event| {
+        let key = serde_json::from_str::<Value>(event.payload())
+            .ok()
+            .and_then(|change| change["key"].as_str().map(str::to_string));
+        if matches!(
+            key.as_deref(),
+            Some(AI_DEBUG_LOG_SETTING | AI_DEBUG_LOG_BODIES_SETTING)
+        ) {
+            load_ai_debug_log(&handle);
+        }
+    });
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the latest AI debug log entries, oldest first
+#[tauri::command]
+pub fn get_ai_debug_log(
+    app: tauri::AppHandle,
+    limit: Option<usize>,
+) -> Result<Vec<AIDebugLogEntry>, AppError> {
+    let limit = limit
+        .unwrap_or(DEFAULT_DEBUG_LOG_ENTRIES)
+        .min(MAX_DEBUG_LOG_ENTRIES);
+    Ok(read_debug_log(&get_log_dir(&app)?, limit))
+}
+
+/// Delete the AI debug log
+#[tauri::command]
+pub fn clear_ai_debug_log(app: tauri::AppHandle) -> Result<(), AppError> {
+    let dir = get_log_dir(&app)?;
+    let _guard = AI_DEBUG_LOG_LOCK
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    for name in [AI_DEBUG_LOG_FILE, AI_DEBUG_LOG_PREVIOUS_FILE] {
+        let path = dir.join(name);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    log::info!("AI debug log cleared");
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn exchanges_are_redacted_and_the_log_is_capped() {
+        let request = json!({
+            "model": "gpt-4o",
+            "max_tokens": 100,
+            "api_key": "sk-secret",
+            "messages": [
+                {"role": "user", "content": [
+                    {"type": "text", "text": "a".repeat(300)},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}},
+                ]},
+            ],
+            "input": [vec![0.5; 20]],
+        });
+        let exchange = AIExchange {
+            provider: "openai",
+            url: "https://api.example.com/v1/chat?key=sk-secret&alt=json",
+            request: &request,
+            status: Some(200),
+            response: Some(r#"{"choices": [{"message": {"content": "Hello"}}]}"#),
+            error: None,
+            duration: Duration::from_millis(1500),
+        };
+
+        let entry = debug_log_entry(&exchange, BodyMode::Redacted, 1);
+        assert!(!serde_json::to_string(&entry).unwrap().contains("sk-secret"));
+        assert_eq!(
+            entry.url,
+            "https://api.example.com/v1/chat?key=%5Bredacted%5D&alt=json"
+        );
+        assert_eq!(entry.request["model"], "gpt-4o");
+        assert_eq!(entry.request["max_tokens"], 100);
+        let parts = &entry.request["messages"][0]["content"];
+        assert_eq!(parts[0], json!({"type": "text", "text": "[300 chars]"}));
+        assert_eq!(parts[1]["image_url"]["url"], "[26 chars]");
+        assert_eq!(entry.request["input"][0], "[20 numbers]");
+        assert_eq!(
+            entry.response.as_ref().unwrap()["choices"][0]["message"]["content"],
+            "[5 chars]"
+        );
+        assert_eq!(entry.duration_ms, 1500);
+
+        let truncated = debug_log_entry(&exchange, BodyMode::Truncated, 2);
+        let text = truncated.request["messages"][0]["content"][0]["text"]
+            .as_str()
+            .unwrap();
+        assert!(text.ends_with("…[+100 chars]"));
+        let full = debug_log_entry(&exchange, BodyMode::Full, 3);
+        assert_eq!(full.request["messages"], request["messages"]);
+        assert_eq!(full.request["api_key"], REDACTED);
+
+        let dir = tempdir().unwrap();
+        let failed = AIDebugLogEntry {
+            status: Some(500),
+            response: Some(Value::from("Bad gateway")),
+            error: Some("API request failed".to_string()),
+            ..entry.clone()
+        };
+        append_debug_log_entry(dir.path(), &entry).unwrap();
+        append_debug_log_entry(dir.path(), &failed).unwrap();
+        assert_eq!(
+            read_debug_log(dir.path(), 10),
+            [entry.clone(), failed.clone()]
+        );
+        assert_eq!(read_debug_log(dir.path(), 1), [failed]);
+
+        // A full file is kept as the previous one, and the one before dropped
+        fs::write(dir.path().join(AI_DEBUG_LOG_PREVIOUS_FILE), "").unwrap();
+        let file = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(AI_DEBUG_LOG_FILE))
+            .unwrap();
+        file.set_len(MAX_DEBUG_LOG_BYTES).unwrap();
+        append_debug_log_entry(dir.path(), &entry).unwrap();
+        assert_eq!(
+            fs::metadata(dir.path().join(AI_DEBUG_LOG_PREVIOUS_FILE))
+                .unwrap()
+                .len(),
+            MAX_DEBUG_LOG_BYTES
+        );
+        assert_eq!(read_debug_log(dir.path(), 10).last(), Some(&entry));
+    }
+}
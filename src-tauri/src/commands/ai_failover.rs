@@ -0,0 +1,312 @@
+//! Failover of chat requests to other providers
+//!
+//! Users may list providers, each with the model to ask there, that a chat
+//! request goes to when the provider it was meant for fails, such as
+//! openai, then openrouter, then deepseek. Each is tried in turn until one
+//! answers, and the reply names the provider that did. Only requests that
+//! went unanswered, were rate limited or were refused the key move on;
+//! requests rejected for what they ask would fail elsewhere too. The list
+//! is stored in the internal `ai.fallbackProviders` setting as
+//! `provider:model` entries, loaded at startup and reloaded when it
+//! changes.
+
+use crate::commands::ai_keys::AZURE_PROVIDER;
+use crate::commands::ai_providers::find_custom_provider;
+use crate::commands::ai_proxy::get_provider_base_url;
+#[cfg(feature = "local-inference")]
+use crate::commands::local_inference::LOCAL_PROVIDER;
+use crate::commands::settings::{change_setting, setting, SETTING_CHANGED_EVENT};
+use crate::error::{AppError, ErrorCategory};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::RwLock;
+use tauri::Listener;
+
+/// Setting holding the fallback providers, in order
+pub const FALLBACK_PROVIDERS_SETTING: &str = "ai.fallbackProviders";
+
+/// Fallback providers in effect
+static FALLBACK_CHAIN: RwLock<Vec<FallbackTarget>> = RwLock::new(Vec::new());
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// A provider and the model to ask there
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FallbackTarget {
+    pub provider: String,
+    pub model: String,
+}
+
+impl FallbackTarget {
+    pub fn new(provider: &str, model: &str) -> FallbackTarget {
+        FallbackTarget {
+            provider: provider.to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    /// Parse a `provider:model` setting entry
+    fn parse(entry: &str) -> Option<FallbackTarget> {
+        let (provider, model) = entry.split_once(':')?;
+        let (provider, model) = (provider.trim(), model.trim());
+        (!provider.is_empty() && !model.is_empty()).then(|| FallbackTarget::new(provider, model))
+    }
+
+    fn entry(&self) -> String {
+        format!("{}:{}", self.provider, self.model)
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// The fallback providers in effect
+pub fn fallback_chain() -> Vec<FallbackTarget> {
+    FALLBACK_CHAIN
+        .read()
+        .map(|chain| chain.clone())
+        .unwrap_or_default()
+}
+
+/// Whether a failed request may be sent to the next provider; only
+/// failures of the provider itself are, not of the request
+pub fn fails_over(error: &AppError) -> bool {
+    matches!(
+        error.category(),
+        ErrorCategory::Network | ErrorCategory::RateLimit | ErrorCategory::Auth
+    )
+}
+
+/// Whether requests can be sent to a provider
+fn is_known_provider(provider: &str) -> bool {
+    #[cfg(feature = "local-inference")]
+    if provider == LOCAL_PROVIDER {
+        return true;
+    }
+    get_provider_base_url(provider).is_some()
+        || provider == AZURE_PROVIDER
+        || find_custom_provider(provider).is_some()
+}
+
+/// Send a request to a provider, then to the fallbacks for other providers
+/// in turn while it fails; returns the first reply, or the first error
+pub async fn failover_with<T, F, Fut>(
+    chain: &[FallbackTarget],
+    primary: FallbackTarget,
+    mut send: F,
+) -> Result<T, AppError>
+where
+    F: FnMut(FallbackTarget) -> Fut,
+    Fut: Future<Output = Result<T, AppError>>,
+{
+    let fallbacks = chain
+        .iter()
+        .filter(|target| target.provider != primary.provider)
+        .cloned()
+        .collect::<Vec<_>>();
+    let primary_provider = primary.provider.clone();
+    let first_error = match send(primary).await {
+        Err(e) if fails_over(&e) && !fallbacks.is_empty() => e,
+        result => return result,
+    };
+    let mut failed = primary_provider;
+    for target in fallbacks {
+        log::warn!(
+            "Chat request to {} failed, trying {}: {}",
+            failed,
+            target.entry(),
+            first_error
+        );
+        failed = target.provider.clone();
+        match send(target).await {
+            Ok(reply) => return Ok(reply),
+            Err(e) => log::warn!("Fallback {} failed: {}", failed, e),
+        }
+    }
+    Err(first_error)
+}
+
+/// Send a request to a provider, failing over to the providers in effect
+pub async fn with_failover<T, F, Fut>(provider: &str, model: &str, send: F) -> Result<T, AppError>
+where
+    F: FnMut(FallbackTarget) -> Fut,
+    Fut: Future<Output = Result<T, AppError>>,
+{
+    failover_with(
+        &fallback_chain(),
+        FallbackTarget::new(provider, model),
+        send,
+    )
+    .await
+}
+
+fn load_fallback_chain(app: &tauri::AppHandle) {
+    match setting::<Vec<String>>(app, FALLBACK_PROVIDERS_SETTING) {
+        Ok(entries) => {
+            let chain = entries
+                .iter()
+                .filter_map(|entry| FallbackTarget::parse(entry))
+                .collect();
+            if let Ok(mut current) = FALLBACK_CHAIN.write() {
+                *current = chain;
+            }
+        }
+        Err(e) => log::warn!("Failed to load the fallback providers: {}", e),
+    }
+}
+
+/// Load the fallback providers and reload them whenever they change
+pub fn init_fallback_chain(app: &tauri::AppHandle) {
+    load_fallback_chain(app);
+    let handle = app.clone();
+    app.listen_any(SETTING_CHANGED_EVENT, move |event| {
+        let key = serde_json::from_str::<serde_json::Value>(event.payload())
+            .ok()
+            .and_then(|change| change["key"].as_str().map(str::to_string));
+        if key.as_deref() == Some(FALLBACK_PROVIDERS_SETTING) {
+            load_fallback_chain(&handle);
+        }
+    });
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the fallback providers, in order
+#[tauri::command]
+pub fn get_ai_fallback_chain() -> Vec<FallbackTarget> {
+    fallback_chain()
+}
+
+/// Replace the fallback providers; each must be a provider requests can be
+/// sent to, listed once
+#[tauri::command]
+pub fn set_ai_fallback_chain(
+    app: tauri::AppHandle,
+    chain: Vec<FallbackTarget>,
+) -> Result<Vec<FallbackTarget>, AppError> {
+    let mut entries: Vec<String> = Vec::new();
+    for target in &chain {
+        let target = FallbackTarget::new(target.provider.trim(), target.model.trim());
+        if target.model.is_empty() || !is_known_provider(&target.provider) {
+            return Err(AppError::InvalidInput(format!(
+                "Invalid fallback provider: {}",
+                target.entry()
+            )));
+        }
+        if entries
+            .iter()
+            .any(|entry| entry.starts_with(&format!("{}:", target.provider)))
+        {
+            return Err(AppError::InvalidInput(format!(
+                "{} is listed more than once",
+                target.provider
+            )));
+        }
+        entries.push(target.entry());
+    }
+    change_setting(
+        &app,
+        FALLBACK_PROVIDERS_SETTING,
+        Some(serde_json::to_value(&entries)?),
+    )?;
+    load_fallback_chain(&app);
+    log::info!("Fallback providers set: {}", entries.join(", "));
+    Ok(fallback_chain())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[tokio::test]
+    async fn failed_requests_move_down_the_chain() {
+        assert_eq!(
+            FallbackTarget::parse(" ollama : llama3:8b "),
+            Some(FallbackTarget::new("ollama", "llama3:8b"))
+        );
+        assert_eq!(FallbackTarget::parse("openai:"), None);
+        assert_eq!(FallbackTarget::parse("openai"), None);
+
+        let chain = [
+            FallbackTarget::new("openai", "gpt-4o"),
+            FallbackTarget::new("openrouter", "openai/gpt-4o"),
+            FallbackTarget::new("deepseek", "deepseek-chat"),
+        ];
+        let tried = RefCell::new(Vec::new());
+        let send = |down: &'static [&'static str]| {
+            let tried = &tried;
+            move |target: FallbackTarget| {
+                tried.borrow_mut().push(target.provider.clone());
+                async move {
+                    if down.contains(&target.provider.as_str()) {
+                        Err(AppError::http_status(
+                            reqwest::StatusCode::TOO_MANY_REQUESTS,
+                            format!("{} failed with status 429", target.provider),
+                        ))
+                    } else {
+                        Ok(target)
+                    }
+                }
+            }
+        };
+
+        let primary = FallbackTarget::new("openai", "gpt-4o-mini");
+        let answered = failover_with(&chain, primary.clone(), send(&["openai", "openrouter"]))
+            .await
+            .unwrap();
+        assert_eq!(answered, chain[2]);
+        assert_eq!(*tried.borrow(), ["openai", "openrouter", "deepseek"]);
+
+        tried.borrow_mut().clear();
+        let answered = failover_with(&chain, primary.clone(), send(&[]))
+            .await
+            .unwrap();
+        assert_eq!(answered, primary);
+        assert_eq!(*tried.borrow(), ["openai"]);
+
+        tried.borrow_mut().clear();
+        let error = failover_with(
+            &chain,
+            primary.clone(),
+            send(&["openai", "openrouter", "deepseek"]),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("openai failed"));
+        assert_eq!(tried.borrow().len(), 3);
+
+        tried.borrow_mut().clear();
+        let rejected = failover_with(&chain, primary.clone(), |target: FallbackTarget| {
+            tried.borrow_mut().push(target.provider);
+            async { Err::<(), _>(AppError::InvalidInput("bad request".to_string())) }
+        })
+        .await;
+        assert!(rejected.is_err());
+        assert_eq!(*tried.borrow(), ["openai"]);
+
+        tried.borrow_mut().clear();
+        let rejected = failover_with(&chain, primary, |target: FallbackTarget| {
+            tried.borrow_mut().push(target.provider);
+            async {
+                Err::<(), _>(AppError::http_status(
+                    reqwest::StatusCode::BAD_REQUEST,
+                    "openai failed with status 400",
+                ))
+            }
+        })
+        .await;
+        assert!(rejected.is_err());
+        assert_eq!(*tried.borrow(), ["openai"]);
+    }
+}
@@ -0,0 +1,254 @@
+//! Default headers of AI providers
+//!
+//! Every request the AI proxy sends a provider carries the provider's
+//! default headers, after its credentials. OpenRouter gets its attribution
+//! headers, `HTTP-Referer` and `X-Title`, out of the box; users may add
+//! headers for any provider, such as `OpenAI-Organization`, or replace the
+//! built-in ones, and an empty value drops a header. Credential headers
+//! cannot be set this way, since they come from the stored key. The user's
+//! headers are kept in the internal `ai.providerHeaders` setting, loaded at
+//! startup and reloaded when it changes.
+
+use crate::commands::settings::{change_setting, setting, SETTING_CHANGED_EVENT};
+use crate::error::AppError;
+use reqwest::header::{HeaderName, HeaderValue};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use tauri::Listener;
+
+/// Setting holding the user's headers, by provider
+pub const PROVIDER_HEADERS_SETTING: &str = "ai.providerHeaders";
+
+/// Headers sent without any set by the user
+const BUILTIN_HEADERS: &[(&str, &[(&str, &str)])] = &[(
+    "openrouter",
+    &[
+        (
+            "HTTP-Referer",
+            "https://github.com/NJUPT-SAST-CXX/sast-readium-web",
+        ),
+        ("X-Title", "SAST Readium"),
+    ],
+)];
+
+/// Headers holding credentials, which only the stored key sets
+const CREDENTIAL_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "api-key",
+    "cookie",
+];
+
+/// Headers by provider
+pub type ProviderHeaders = BTreeMap<String, BTreeMap<String, String>>;
+
+/// The user's headers in effect
+static PROVIDER_HEADERS: RwLock<ProviderHeaders> = RwLock::new(BTreeMap::new());
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Built-in headers of a provider with the user's applied; names match
+/// whatever their case, and empty values are dropped
+pub fn merge_headers(
+    builtin: &[(&str, &str)],
+    custom: Option<&BTreeMap<String, String>>,
+) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = builtin
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    for (name, value) in custom.into_iter().flatten() {
+        headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        headers.push((name.clone(), value.clone()));
+    }
+    headers.retain(|(_, value)| !value.is_empty());
+    headers
+}
+
+/// Headers to send with every request to a provider
+pub fn default_headers(provider: &str) -> Vec<(String, String)> {
+    let builtin = BUILTIN_HEADERS
+        .iter()
+        .find(|(name, _)| *name == provider)
+        .map_or(&[][..], |(_, headers)| headers);
+    match PROVIDER_HEADERS.read() {
+        Ok(custom) => merge_headers(builtin, custom.get(provider)),
+        Err(_) => merge_headers(builtin, None),
+    }
+}
+
+/// Add the default headers of a provider to a request, but none of those
+/// already set on it, such as its organization
+pub fn apply_default_headers(
+    request: reqwest::RequestBuilder,
+    provider: &str,
+    set: &[&str],
+) -> reqwest::RequestBuilder {
+    default_headers(provider)
+        .into_iter()
+        .filter(|(name, _)| !set.iter().any(|set| set.eq_ignore_ascii_case(name)))
+        .fold(request, |request, (name, value)| {
+            request.header(name, value)
+        })
+}
+
+/// Trim header names and reject those requests could not be sent with or
+/// that would replace the credentials
+pub fn check_headers(
+    provider: &str,
+    headers: BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, AppError> {
+    let mut checked = BTreeMap::new();
+    for (name, value) in headers {
+        let name = name.trim().to_string();
+        let value = value.trim().to_string();
+        if HeaderName::from_bytes(name.as_bytes()).is_err()
+            || HeaderValue::from_str(&value).is_err()
+        {
+            return Err(AppError::InvalidInput(format!(
+                "Invalid header for {}: {}",
+                provider, name
+            )));
+        }
+        if CREDENTIAL_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            return Err(AppError::InvalidInput(format!(
+                "{} is set from the stored API key",
+                name
+            )));
+        }
+        checked.insert(name, value);
+    }
+    Ok(checked)
+}
+
+fn load_provider_headers(app: &tauri::AppHandle) {
+    match setting::<ProviderHeaders>(app, PROVIDER_HEADERS_SETTING) {
+        Ok(headers) => {
+            if let Ok(mut current) = PROVIDER_HEADERS.write() {
+                *current = headers;
+            }
+        }
+        Err(e) => log::warn!("Failed to load the provider headers: {}", e),
+    }
+}
+
+/// Load the user's headers and reload them whenever they change
+pub fn init_provider_headers(app: &tauri::AppHandle) {
+    load_provider_headers(app);
+    let handle = app.clone();
+    app.listen_any(SETTING_CHANGED_EVENT, move |event| {
+        let key = serde_json::from_str::<serde_json::Value>(event.payload())
+            .ok()
+            .and_then(|change| change["key"].as_str().map(str::to_string));
+        if key.as_deref() == Some(PROVIDER_HEADERS_SETTING) {
+            load_provider_headers(&handle);
+        }
+    });
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the headers each provider is sent, built-in ones included
+#[tauri::command]
+pub fn get_ai_provider_headers() -> ProviderHeaders {
+    let mut providers: Vec<String> = BUILTIN_HEADERS
+        .iter()
+        .map(|(provider, _)| provider.to_string())
+        .collect();
+    if let Ok(custom) = PROVIDER_HEADERS.read() {
+        providers.extend(custom.keys().cloned());
+    }
+    providers
+        .into_iter()
+        .map(|provider| {
+            let headers = default_headers(&provider).into_iter().collect();
+            (provider, headers)
+        })
+        .collect()
+}
+
+/// Replace the headers the user set for a provider; an empty value drops a
+/// built-in header, and no headers restore the built-in ones
+#[tauri::command]
+pub fn set_ai_provider_headers(
+    app: tauri::AppHandle,
+    provider: String,
+    headers: BTreeMap<String, String>,
+) -> Result<(), AppError> {
+    let provider = provider.trim().to_string();
+    if provider.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Headers need a provider".to_string(),
+        ));
+    }
+    let headers = check_headers(&provider, headers)?;
+    let mut all = PROVIDER_HEADERS
+        .read()
+        .map(|current| current.clone())
+        .unwrap_or_default();
+    if headers.is_empty() {
+        all.remove(&provider);
+    } else {
+        all.insert(provider.clone(), headers);
+    }
+    change_setting(
+        &app,
+        PROVIDER_HEADERS_SETTING,
+        Some(serde_json::to_value(&all)?),
+    )?;
+    load_provider_headers(&app);
+    log::info!("Default headers of {} updated", provider);
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_headers_replace_built_in_ones() {
+        let openrouter = BUILTIN_HEADERS[0].1;
+        assert_eq!(
+            default_headers("openrouter"),
+            merge_headers(openrouter, None)
+        );
+        assert!(default_headers("openai").is_empty());
+
+        let custom = BTreeMap::from([
+            ("x-title".to_string(), "My Reader".to_string()),
+            ("HTTP-Referer".to_string(), String::new()),
+            ("X-Team".to_string(), "papers".to_string()),
+        ]);
+        assert_eq!(
+            merge_headers(openrouter, Some(&custom)),
+            vec![
+                ("X-Team".to_string(), "papers".to_string()),
+                ("x-title".to_string(), "My Reader".to_string()),
+            ]
+        );
+
+        let checked = check_headers(
+            "openai",
+            BTreeMap::from([(" OpenAI-Organization ".to_string(), " org-1 ".to_string())]),
+        )
+        .unwrap();
+        assert_eq!(checked["OpenAI-Organization"], "org-1");
+        for (name, value) in [
+            ("Authorization", "Bearer sk"),
+            ("X-Api-Key", "sk"),
+            ("Bad Name", "1"),
+        ] {
+            let headers = BTreeMap::from([(name.to_string(), value.to_string())]);
+            assert!(check_headers("openai", headers).is_err(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Image generation through the AI proxy
+//!
+//! Prompts are sent to the image generation endpoint of OpenAI (DALL·E and
+//! GPT Image models) or of a custom OpenAI-compatible provider, with the
+//! stored key. The images, returned as base64 or as links that are fetched
+//! right away since they expire, are saved in the `generated-images`
+//! directory of the app data, which the asset protocol serves, and their
+//! paths returned for the chat to show.
+
+use crate::commands::ai_providers::find_custom_provider;
+use crate::commands::ai_proxy::{ai_http_client, post_json, provider_request};
+use crate::commands::retry::with_retry;
+use crate::error::AppError;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+/// Directory of generated images in the app data directory
+pub const GENERATED_IMAGES_DIR: &str = "generated-images";
+
+/// Image generation endpoint of OpenAI
+const OPENAI_IMAGES_ENDPOINT: &str = "https://api.openai.com/v1/images/generations";
+
+/// Most images one request may ask for
+const MAX_IMAGES: u32 = 10;
+
+/// Image formats saved; others are rejected as invalid output
+const IMAGE_FORMATS: &[image::ImageFormat] = &[
+    image::ImageFormat::Png,
+    image::ImageFormat::Jpeg,
+    image::ImageFormat::WebP,
+    image::ImageFormat::Gif,
+];
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Options of an image generation request; unset ones are left to the
+/// provider
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageOptions {
+    /// Number of images, 1 to 10 (DALL·E 3 makes one)
+    pub n: Option<u32>,
+    /// `WIDTHxHEIGHT`, such as `1024x1024`, or `auto`
+    pub size: Option<String>,
+    /// Such as `standard` or `hd` (DALL·E 3), `low` to `high` (GPT Image)
+    pub quality: Option<String>,
+    /// `vivid` or `natural` (DALL·E 3)
+    pub style: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ImageRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quality: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    style: Option<&'a str>,
+    /// GPT Image models always return base64 and reject this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<&'static str>,
+}
+
+#[derive(Deserialize)]
+struct ImageResponse {
+    data: Vec<ImageData>,
+}
+
+#[derive(Deserialize)]
+struct ImageData {
+    b64_json: Option<String>,
+    url: Option<String>,
+    revised_prompt: Option<String>,
+}
+
+/// A generated image saved in the app data
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneratedImage {
+    pub path: String,
+    /// Prompt the provider rewrote the request into (DALL·E 3)
+    pub revised_prompt: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Image generation URL of a provider: OpenAI's, or a custom one's
+pub fn get_images_endpoint(provider: &str) -> Result<String, AppError> {
+    if let Some(custom) = find_custom_provider(provider) {
+        return Ok(format!("{}/images/generations", custom.base_url));
+    }
+    match provider {
+        "openai" => Ok(OPENAI_IMAGES_ENDPOINT.to_string()),
+        _ => Err(AppError::InvalidInput(format!(
+            "Provider {} does not offer image generation",
+            provider
+        ))),
+    }
+}
+
+fn check_image_request(prompt: &str, options: &ImageOptions) -> Result<(), AppError> {
+    if prompt.trim().is_empty() {
+        return Err(AppError::InvalidInput(
+            "An image needs a prompt".to_string(),
+        ));
+    }
+    if options.n.is_some_and(|n| n == 0 || n > MAX_IMAGES) {
+        return Err(AppError::InvalidInput(format!(
+            "Between 1 and {} images can be generated at once",
+            MAX_IMAGES
+        )));
+    }
+    if let Some(size) = &options.size {
+        let valid = size == "auto"
+            || size.split_once('x').is_some_and(|(width, height)| {
+                width.parse::<u32>().is_ok() && height.parse::<u32>().is_ok()
+            });
+        if !valid {
+            return Err(AppError::InvalidInput(format!(
+                "Invalid image size: {}",
+                size
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Save an image in a directory under a new name, with the extension of
+/// its format
+pub fn save_generated_image(dir: &Path, bytes: &[u8]) -> Result<PathBuf, AppError> {
+    let format = image::guess_format(bytes)
+        .ok()
+        .filter(|format| IMAGE_FORMATS.contains(format))
+        .ok_or_else(|| {
+            AppError::InvalidOutput("The provider did not return an image".to_string())
+        })?;
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "{}.{}",
+        uuid::Uuid::new_v4(),
+        format.extensions_str()[0]
+    ));
+    fs::write(&path, bytes)?;
+    Ok(path)
+}
+
+/// The bytes of a returned image, decoded or downloaded
+async fn image_bytes(data: &ImageData) -> Result<Vec<u8>, AppError> {
+    if let Some(encoded) = &data.b64_json {
+        return base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| AppError::InvalidOutput(format!("Invalid image data: {}", e)));
+    }
+    let url = data
+        .url
+        .as_deref()
+        .ok_or_else(|| AppError::InvalidOutput("The provider returned no image".to_string()))?;
+    let failed = |e: reqwest::Error| AppError::Http {
+        status: e.status().map(|status| status.as_u16()),
+        detail: format!("Failed to download the image: {}", e),
+    };
+    let response = ai_http_client()
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(failed)?;
+    Ok(response.bytes().await.map_err(failed)?.to_vec())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Generate images from a prompt and save them in the app data, returning
+/// their paths
+#[tauri::command]
+pub async fn proxy_image_request(
+    app: tauri::AppHandle,
+    provider: String,
+    model: String,
+    prompt: String,
+    options: Option<ImageOptions>,
+) -> Result<Vec<GeneratedImage>, AppError> {
+    let options = options.unwrap_or_default();
+    check_image_request(&prompt, &options)?;
+    let endpoint = get_images_endpoint(&provider)?;
+    let request = ImageRequest {
+        model: &model,
+        prompt: &prompt,
+        n: options.n,
+        size: options.size.as_deref(),
+        quality: options.quality.as_deref(),
+        style: options.style.as_deref(),
+        response_format: (!model.starts_with("gpt-image")).then_some("b64_json"),
+    };
+    let response: ImageResponse = with_retry("Image request", || async {
+        post_json(
+            &provider,
+            "Image request",
+            provider_request(&provider, &endpoint)?,
+            &request,
+        )
+        .await
+    })
+    .await?;
+
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?
+        .join(GENERATED_IMAGES_DIR);
+    let mut images = Vec::with_capacity(response.data.len());
+    for data in &response.data {
+        let path = save_generated_image(&dir, &image_bytes(data).await?)?;
+        images.push(GeneratedImage {
+            path: path.to_string_lossy().into_owned(),
+            revised_prompt: data.revised_prompt.clone(),
+        });
+    }
+    log::info!(
+        "Generated {} images with {} {}",
+        images.len(),
+        provider,
+        model
+    );
+    Ok(images)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn generated_images_are_checked_and_saved() {
+        assert_eq!(
+            get_images_endpoint("openai").unwrap(),
+            OPENAI_IMAGES_ENDPOINT
+        );
+        assert!(get_images_endpoint("anthropic").is_err());
+
+        let options = |n, size: &str| ImageOptions {
+            n,
+            size: Some(size.to_string()),
+            ..Default::default()
+        };
+        assert!(check_image_request("A lighthouse", &options(Some(2), "1024x1792")).is_ok());
+        assert!(check_image_request("A lighthouse", &options(None, "auto")).is_ok());
+        assert!(check_image_request(" ", &ImageOptions::default()).is_err());
+        assert!(check_image_request("A lighthouse", &options(Some(0), "auto")).is_err());
+        assert!(check_image_request("A lighthouse", &options(None, "large")).is_err());
+
+        let dir = tempdir().unwrap();
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(2, 2)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let path = save_generated_image(dir.path(), png.get_ref()).unwrap();
+        assert_eq!(path.extension().unwrap(), "png");
+        assert_eq!(fs::read(&path).unwrap(), *png.get_ref());
+        assert!(matches!(
+            save_generated_image(dir.path(), b"{\"error\": \"content policy\"}"),
+            Err(AppError::InvalidOutput(_))
+        ));
+    }
+}
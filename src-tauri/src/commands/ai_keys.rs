@@ -1,45 +1,745 @@
 //! AI API key secure storage commands
+//!
+//! Keys live in the OS credential manager, one entry per provider. A
+//! provider may also have named key profiles, such as a "work" and a
+//! "personal" OpenAI key, each in its own entry; requests use the active
+//! profile's key, the `default` profile's unless another was chosen. What a
+//! provider needs besides the key (organization and project ids, the
+//! resource and deployment of an Azure OpenAI provider) is stored in
+//! entries next to it and deleted with it.
+//!
+//! Metadata of each key that is no secret (when it was saved, when a
+//! request last succeeded with it, and a masked preview) is kept in a file
+//! in the app data directory, so settings can show it without reading the
+//! key.
 
+use crate::commands::ai_providers::get_custom_providers;
 use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Manager;
 
 /// Keyring service name for secure storage
 pub const KEYRING_SERVICE: &str = "sast-readium";
 
-/// Save an API key securely using OS credential manager
-#[tauri::command]
-pub fn save_api_key(provider: String, api_key: String) -> Result<(), AppError> {
-    let entry = keyring::Entry::new(KEYRING_SERVICE, &provider)
-        .map_err(|e| AppError::Keyring(e.to_string()))?;
+/// Providers the AI proxy knows, whose keys may be in the keyring
+pub const API_KEY_PROVIDERS: &[&str] = &[
+    "openai",
+    "anthropic",
+    "deepseek",
+    "groq",
+    "openrouter",
+    AZURE_PROVIDER,
+];
+
+/// Provider whose requests go to an Azure OpenAI deployment
+pub const AZURE_PROVIDER: &str = "azure";
+
+/// `api-version` of Azure OpenAI requests when none is saved
+pub const DEFAULT_AZURE_API_VERSION: &str = "2024-10-21";
+
+/// Profile whose key is the provider's own entry, active unless another is
+pub const DEFAULT_KEY_PROFILE: &str = "default";
+
+const MAX_KEY_PROFILE_CHARS: usize = 32;
+
+/// File in the app data directory holding the metadata of stored keys
+pub const KEY_METADATA_FILE: &str = "ai_key_metadata.json";
+
+/// Seconds between recorded uses of a key, so requests rarely write the file
+const KEY_USE_RESOLUTION: i64 = 60;
+
+/// Held while the key metadata file is read and written back
+static KEY_METADATA_LOCK: Mutex<()> = Mutex::new(());
+
+/// Characters shown at each end of a key preview
+const KEY_PREVIEW_CHARS: usize = 4;
+
+/// Headers carrying the organization and project of a request, by provider
+pub const TENANT_HEADERS: &[(&str, &str, &str)] =
+    &[("openai", "OpenAI-Organization", "OpenAI-Project")];
+
+/// Organization and project a provider bills requests to, which enterprise
+/// and edu accounts belonging to several require
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderTenant {
+    pub organization: Option<String>,
+    pub project: Option<String>,
+}
+
+impl ProviderTenant {
+    /// Drop blank ids
+    fn trimmed(self) -> ProviderTenant {
+        let clean = |id: Option<String>| {
+            id.map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+        };
+        ProviderTenant {
+            organization: clean(self.organization),
+            project: clean(self.project),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.organization.is_none() && self.project.is_none()
+    }
+}
+
+/// Key profiles of a provider and the one requests use
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyProfiles {
+    pub active: String,
+    /// Profile names, `default` first
+    pub profiles: Vec<String>,
+}
+
+impl Default for KeyProfiles {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_KEY_PROFILE.to_string(),
+            profiles: vec![DEFAULT_KEY_PROFILE.to_string()],
+        }
+    }
+}
+
+impl KeyProfiles {
+    fn add(&mut self, profile: &str) {
+        if !self.profiles.iter().any(|p| p == profile) {
+            self.profiles.push(profile.to_string());
+        }
+    }
+
+    /// Drop a named profile; the default one becomes active if it was
+    fn remove(&mut self, profile: &str) {
+        if profile == DEFAULT_KEY_PROFILE {
+            return;
+        }
+        self.profiles.retain(|p| p != profile);
+        if self.active == profile {
+            self.active = DEFAULT_KEY_PROFILE.to_string();
+        }
+    }
+
+    fn activate(&mut self, profile: &str) -> Result<(), AppError> {
+        if !self.profiles.iter().any(|p| p == profile) {
+            return Err(AppError::NotFound(format!(
+                "Key profile '{}' not found",
+                profile
+            )));
+        }
+        self.active = profile.to_string();
+        Ok(())
+    }
+}
+
+/// What is known of a stored key without reading it
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyMetadata {
+    /// When the key was saved; unknown for keys saved before it was kept
+    pub created_at: Option<i64>,
+    /// When a request last succeeded with the key
+    pub last_used_at: Option<i64>,
+    pub preview: String,
+}
+
+/// Key metadata by provider, then profile
+pub type KeyMetadataStore = BTreeMap<String, BTreeMap<String, KeyMetadata>>;
+
+/// A provider with an API key in the keyring
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredApiKey {
+    pub provider: String,
+    /// Profile whose key requests use
+    pub profile: String,
+    /// Name of a custom provider
+    pub name: Option<String>,
+    /// Start and end of the key, enough to tell keys apart
+    pub preview: String,
+}
+
+/// Azure OpenAI resource and model deployment requests are sent to
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AzureDeployment {
+    /// Resource name, the subdomain of `openai.azure.com` it is served on
+    pub resource_name: String,
+    pub deployment_name: String,
+    /// `api-version` query parameter; empty for the default
+    #[serde(default)]
+    pub api_version: String,
+}
+
+impl AzureDeployment {
+    /// Trim the fields and reject those that would not form a valid URL
+    fn validated(self) -> Result<AzureDeployment, AppError> {
+        let resource_name = self.resource_name.trim().to_ascii_lowercase();
+        let deployment_name = self.deployment_name.trim().to_string();
+        let api_version = match self.api_version.trim() {
+            "" => DEFAULT_AZURE_API_VERSION.to_string(),
+            version => version.to_string(),
+        };
+        let is_name = |name: &str| {
+            !name.is_empty()
+                && !name.starts_with('.')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+        if !is_name(&resource_name) || resource_name.contains(['_', '.']) {
+            return Err(AppError::InvalidInput(format!(
+                "Invalid Azure resource name: {}",
+                resource_name
+            )));
+        }
+        if !is_name(&deployment_name) || !is_name(&api_version) {
+            return Err(AppError::InvalidInput(format!(
+                "Invalid Azure deployment: {} (API version {})",
+                deployment_name, api_version
+            )));
+        }
+        Ok(AzureDeployment {
+            resource_name,
+            deployment_name,
+            api_version,
+        })
+    }
+
+    /// Chat completions URL of the deployment
+    pub fn chat_url(&self) -> String {
+        format!(
+            "https://{}.openai.azure.com/openai/deployments/{}/chat/completions?api-version={}",
+            self.resource_name, self.deployment_name, self.api_version
+        )
+    }
+    /// URL listing the models of the resource
+    pub fn models_url(&self) -> String {
+        format!(
+            "https://{}.openai.azure.com/openai/models?api-version={}",
+            self.resource_name, self.api_version
+        )
+    }
+}
+
+/// Headers to send a provider for its organization and project
+pub fn tenant_headers(provider: &str, tenant: &ProviderTenant) -> Vec<(&'static str, String)> {
+    let Some((_, organization, project)) = TENANT_HEADERS.iter().find(|(p, _, _)| *p == provider)
+    else {
+        return Vec::new();
+    };
+    [
+        (*organization, tenant.organization.clone()),
+        (*project, tenant.project.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(header, id)| Some((header, id?)))
+    .collect()
+}
+
+/// Preview of a key: its first and last characters, or none of them when
+/// it is too short for that to hide most of it
+pub fn mask_api_key(key: &str) -> String {
+    let chars: Vec<char> = key.trim().chars().collect();
+    if chars.len() < KEY_PREVIEW_CHARS * 4 {
+        return "••••".to_string();
+    }
+    let start: String = chars[..KEY_PREVIEW_CHARS].iter().collect();
+    let end: String = chars[chars.len() - KEY_PREVIEW_CHARS..].iter().collect();
+    format!("{}…{}", start, end)
+}
+
+/// Trim a profile name and reject names that are empty, too long, or not
+/// made of letters, digits, `-` and `_`
+pub fn check_profile_name(profile: &str) -> Result<String, AppError> {
+    let profile = profile.trim();
+    let valid = !profile.is_empty()
+        && profile.chars().count() <= MAX_KEY_PROFILE_CHARS
+        && profile
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_'));
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid key profile name: {}",
+            profile
+        )));
+    }
+    Ok(profile.to_string())
+}
+
+/// Keyring entry of a profile's key; the default profile's is the
+/// provider's own
+fn key_entry(provider: &str, profile: &str) -> Result<keyring::Entry, AppError> {
+    let user = if profile == DEFAULT_KEY_PROFILE {
+        provider.to_string()
+    } else {
+        format!("{}:profile:{}", provider, profile)
+    };
+    keyring::Entry::new(KEYRING_SERVICE, &user).map_err(|e| AppError::Keyring(e.to_string()))
+}
+
+/// Keyring entry of a provider's key profiles, next to its key
+fn profiles_entry(provider: &str) -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(KEYRING_SERVICE, &format!("{}:profiles", provider))
+        .map_err(|e| AppError::Keyring(e.to_string()))
+}
+
+fn save_key_profiles(provider: &str, profiles: &KeyProfiles) -> Result<(), AppError> {
+    let entry = profiles_entry(provider)?;
+    if *profiles == KeyProfiles::default() {
+        return match entry.delete_credential() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(AppError::Keyring(e.to_string())),
+        };
+    }
     entry
+        .set_password(&serde_json::to_string(profiles)?)
+        .map_err(|e| AppError::Keyring(e.to_string()))
+}
+
+fn delete_key_entry(provider: &str, profile: &str) -> Result<(), AppError> {
+    match key_entry(provider, profile)?.delete_credential() {
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(AppError::Keyring(e.to_string())),
+    }
+}
+
+fn get_key_metadata_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    fs::create_dir_all(&data_dir)?;
+    Ok(data_dir.join(KEY_METADATA_FILE))
+}
+
+pub fn load_key_metadata_from_file(path: &Path) -> Result<KeyMetadataStore, AppError> {
+    if !path.exists() {
+        return Ok(KeyMetadataStore::default());
+    }
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Change the stored key metadata; `change` tells whether it changed
+/// anything to write back
+fn update_key_metadata(
+    app: &tauri::AppHandle,
+    change: impl FnOnce(&mut KeyMetadataStore) -> bool,
+) -> Result<(), AppError> {
+    let _guard = KEY_METADATA_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = get_key_metadata_path(app)?;
+    let mut store = load_key_metadata_from_file(&path)?;
+    if change(&mut store) {
+        fs::write(&path, serde_json::to_string_pretty(&store)?)?;
+    }
+    Ok(())
+}
+
+/// Note a use of a profile's key at `now`, unless one was noted less than
+/// a minute before; `preview` gives the preview of a key without metadata
+/// yet. Returns whether the store changed.
+pub fn mark_key_used(
+    store: &mut KeyMetadataStore,
+    provider: &str,
+    profile: &str,
+    now: i64,
+    preview: impl FnOnce() -> Option<String>,
+) -> bool {
+    if !store
+        .get(provider)
+        .is_some_and(|profiles| profiles.contains_key(profile))
+    {
+        let Some(preview) = preview() else {
+            return false;
+        };
+        let metadata = KeyMetadata {
+            preview,
+            ..Default::default()
+        };
+        store
+            .entry(provider.to_string())
+            .or_default()
+            .insert(profile.to_string(), metadata);
+    }
+    let Some(metadata) = store
+        .get_mut(provider)
+        .and_then(|profiles| profiles.get_mut(profile))
+    else {
+        return false;
+    };
+    if metadata
+        .last_used_at
+        .is_some_and(|used| now - used < KEY_USE_RESOLUTION)
+    {
+        return false;
+    }
+    metadata.last_used_at = Some(now);
+    true
+}
+
+/// Note that a request to a provider succeeded with its active key
+pub fn record_key_use(app: &tauri::AppHandle, provider: &str) {
+    let recorded = get_key_profiles(provider).and_then(|profiles| {
+        update_key_metadata(app, |store| {
+            mark_key_used(
+                store,
+                provider,
+                &profiles.active,
+                chrono::Utc::now().timestamp(),
+                || {
+                    get_api_key(provider.to_string())
+                        .ok()
+                        .flatten()
+                        .map(|key| mask_api_key(&key))
+                },
+            )
+        })
+    });
+    if let Err(e) = recorded {
+        log::warn!("Failed to record the use of the {} key: {}", provider, e);
+    }
+}
+
+/// Keyring entry of a provider's organization and project, next to its key
+fn tenant_entry(provider: &str) -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(KEYRING_SERVICE, &format!("{}:tenant", provider))
+        .map_err(|e| AppError::Keyring(e.to_string()))
+}
+
+/// Keyring entry of an Azure provider's deployment, next to its key
+fn deployment_entry(provider: &str) -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(KEYRING_SERVICE, &format!("{}:deployment", provider))
+        .map_err(|e| AppError::Keyring(e.to_string()))
+}
+
+/// Providers with a saved API key (the keyring cannot list its entries)
+pub fn saved_api_key_providers() -> Vec<String> {
+    API_KEY_PROVIDERS
+        .iter()
+        .filter(|provider| matches!(get_api_key(provider.to_string()), Ok(Some(_))))
+        .map(|provider| provider.to_string())
+        .collect()
+}
+
+/// Get the key profiles of a provider
+pub fn get_key_profiles(provider: &str) -> Result<KeyProfiles, AppError> {
+    match profiles_entry(provider)?.get_password() {
+        Ok(stored) => Ok(serde_json::from_str(&stored)?),
+        Err(keyring::Error::NoEntry) => Ok(KeyProfiles::default()),
+        Err(e) => Err(AppError::Keyring(e.to_string())),
+    }
+}
+
+/// Save an API key securely using OS credential manager, in the given
+/// profile or the active one
+#[tauri::command]
+pub fn save_api_key(
+    app: tauri::AppHandle,
+    provider: String,
+    api_key: String,
+    profile: Option<String>,
+) -> Result<(), AppError> {
+    let mut profiles = get_key_profiles(&provider)?;
+    let profile = match profile {
+        Some(profile) => check_profile_name(&profile)?,
+        None => profiles.active.clone(),
+    };
+    key_entry(&provider, &profile)?
         .set_password(&api_key)
         .map_err(|e| AppError::Keyring(e.to_string()))?;
-    log::info!("API key saved for provider: {}", provider);
+    profiles.add(&profile);
+    save_key_profiles(&provider, &profiles)?;
+    update_key_metadata(&app, |store| {
+        let metadata = KeyMetadata {
+            created_at: Some(chrono::Utc::now().timestamp()),
+            last_used_at: None,
+            preview: mask_api_key(&api_key),
+        };
+        store
+            .entry(provider.clone())
+            .or_default()
+            .insert(profile.clone(), metadata);
+        true
+    })?;
+    log::info!(
+        "API key saved for provider: {} (profile {})",
+        provider,
+        profile
+    );
     Ok(())
 }
 
-/// Get an API key from OS credential manager
+/// Get the API key of a provider's active profile from OS credential
+/// manager
 #[tauri::command]
 pub fn get_api_key(provider: String) -> Result<Option<String>, AppError> {
-    let entry = keyring::Entry::new(KEYRING_SERVICE, &provider)
-        .map_err(|e| AppError::Keyring(e.to_string()))?;
-    match entry.get_password() {
+    let profile = get_key_profiles(&provider)?.active;
+    match key_entry(&provider, &profile)?.get_password() {
         Ok(password) => Ok(Some(password)),
         Err(keyring::Error::NoEntry) => Ok(None),
         Err(e) => Err(AppError::Keyring(e.to_string())),
     }
 }
 
-/// Delete an API key from OS credential manager
+/// Delete the key of one profile from OS credential manager, or without a
+/// profile every key of the provider and what is stored next to them
 #[tauri::command]
-pub fn delete_api_key(provider: String) -> Result<(), AppError> {
-    let entry = keyring::Entry::new(KEYRING_SERVICE, &provider)
-        .map_err(|e| AppError::Keyring(e.to_string()))?;
-    match entry.delete_credential() {
-        Ok(_) => {
-            log::info!("API key deleted for provider: {}", provider);
-            Ok(())
+pub fn delete_api_key(
+    app: tauri::AppHandle,
+    provider: String,
+    profile: Option<String>,
+) -> Result<(), AppError> {
+    let mut profiles = get_key_profiles(&provider)?;
+    if let Some(profile) = profile {
+        delete_key_entry(&provider, &profile)?;
+        profiles.remove(&profile);
+        save_key_profiles(&provider, &profiles)?;
+        update_key_metadata(&app, |store| {
+            store
+                .get_mut(&provider)
+                .and_then(|profiles| profiles.remove(&profile))
+                .is_some()
+        })?;
+        log::info!(
+            "API key deleted for provider: {} (profile {})",
+            provider,
+            profile
+        );
+        return Ok(());
+    }
+    for profile in &profiles.profiles {
+        delete_key_entry(&provider, profile)?;
+    }
+    save_key_profiles(&provider, &KeyProfiles::default())?;
+    update_key_metadata(&app, |store| store.remove(&provider).is_some())?;
+    log::info!("API key deleted for provider: {}", provider);
+    // The organization, project and deployment go with the key
+    if provider == AZURE_PROVIDER {
+        match deployment_entry(&provider)?.delete_credential() {
+            Ok(_) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(AppError::Keyring(e.to_string())),
+        }
+    }
+    save_provider_tenant(provider, ProviderTenant::default())
+}
+
+/// List the known and custom providers with a key in the keyring, with a
+/// preview of each key
+#[tauri::command]
+pub fn list_api_key_providers() -> Result<Vec<StoredApiKey>, AppError> {
+    let builtin = API_KEY_PROVIDERS
+        .iter()
+        .map(|provider| (provider.to_string(), None));
+    let custom = get_custom_providers()
+        .into_iter()
+        .map(|provider| (provider.id, Some(provider.name)));
+    let mut stored = Vec::new();
+    for (provider, name) in builtin.chain(custom) {
+        if let Some(key) = get_api_key(provider.clone())? {
+            stored.push(StoredApiKey {
+                profile: get_key_profiles(&provider)?.active,
+                preview: mask_api_key(&key),
+                provider,
+                name,
+            });
         }
-        Err(keyring::Error::NoEntry) => Ok(()), // Already deleted
+    }
+    Ok(stored)
+}
+
+/// Get when each stored key was saved and last used, with its preview
+#[tauri::command]
+pub fn get_api_key_metadata(app: tauri::AppHandle) -> Result<KeyMetadataStore, AppError> {
+    let _guard = KEY_METADATA_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_key_metadata_from_file(&get_key_metadata_path(&app)?)
+}
+
+/// List the key profiles of a provider and the active one
+#[tauri::command]
+pub fn list_api_key_profiles(provider: String) -> Result<KeyProfiles, AppError> {
+    get_key_profiles(&provider)
+}
+
+/// Make a profile the one whose key requests to a provider use
+#[tauri::command]
+pub fn set_active_api_key_profile(provider: String, profile: String) -> Result<(), AppError> {
+    let mut profiles = get_key_profiles(&provider)?;
+    profiles.activate(profile.trim())?;
+    save_key_profiles(&provider, &profiles)?;
+    log::info!("Key profile {} active for provider: {}", profile, provider);
+    Ok(())
+}
+
+/// Save the organization and project of a provider; empty ids remove them
+#[tauri::command]
+pub fn save_provider_tenant(provider: String, tenant: ProviderTenant) -> Result<(), AppError> {
+    let tenant = tenant.trimmed();
+    let entry = tenant_entry(&provider)?;
+    if tenant.is_empty() {
+        return match entry.delete_credential() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(AppError::Keyring(e.to_string())),
+        };
+    }
+    if !TENANT_HEADERS.iter().any(|(p, _, _)| *p == provider) {
+        return Err(AppError::InvalidInput(format!(
+            "Provider {} has no organization or project ids",
+            provider
+        )));
+    }
+    entry
+        .set_password(&serde_json::to_string(&tenant)?)
+        .map_err(|e| AppError::Keyring(e.to_string()))?;
+    log::info!("Organization and project saved for provider: {}", provider);
+    Ok(())
+}
+
+/// Get the organization and project of a provider, if saved
+#[tauri::command]
+pub fn get_provider_tenant(provider: String) -> Result<Option<ProviderTenant>, AppError> {
+    match tenant_entry(&provider)?.get_password() {
+        Ok(stored) => Ok(Some(serde_json::from_str(&stored)?)),
+        Err(keyring::Error::NoEntry) => Ok(None),
         Err(e) => Err(AppError::Keyring(e.to_string())),
     }
 }
+
+/// Save the Azure OpenAI resource and deployment of a provider
+#[tauri::command]
+pub fn save_azure_deployment(
+    provider: String,
+    deployment: AzureDeployment,
+) -> Result<(), AppError> {
+    if provider != AZURE_PROVIDER {
+        return Err(AppError::InvalidInput(format!(
+            "Provider {} is not an Azure OpenAI provider",
+            provider
+        )));
+    }
+    let deployment = deployment.validated()?;
+    deployment_entry(&provider)?
+        .set_password(&serde_json::to_string(&deployment)?)
+        .map_err(|e| AppError::Keyring(e.to_string()))?;
+    log::info!(
+        "Azure deployment {} saved for provider: {}",
+        deployment.deployment_name,
+        provider
+    );
+    Ok(())
+}
+
+/// Get the Azure OpenAI resource and deployment of a provider, if saved
+#[tauri::command]
+pub fn get_azure_deployment(provider: String) -> Result<Option<AzureDeployment>, AppError> {
+    match deployment_entry(&provider)?.get_password() {
+        Ok(stored) => Ok(Some(serde_json::from_str(&stored)?)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(AppError::Keyring(e.to_string())),
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tenant_headers_follow_the_provider() {
+        let tenant = ProviderTenant {
+            organization: Some(" org-1 ".to_string()),
+            project: Some("".to_string()),
+        }
+        .trimmed();
+        assert_eq!(tenant.organization.as_deref(), Some("org-1"));
+        assert_eq!(tenant.project, None);
+        assert_eq!(
+            tenant_headers("openai", &tenant),
+            vec![("OpenAI-Organization", "org-1".to_string())]
+        );
+        assert!(tenant_headers("groq", &tenant).is_empty());
+        assert!(ProviderTenant::default().is_empty());
+    }
+
+    #[test]
+    fn key_profiles_fall_back_to_the_default() {
+        let mut profiles = KeyProfiles::default();
+        profiles.add("work");
+        profiles.add("work");
+        assert_eq!(profiles.profiles, ["default", "work"]);
+        profiles.activate("work").unwrap();
+        assert!(profiles.activate("personal").is_err());
+        profiles.remove("default");
+        profiles.remove("work");
+        assert_eq!(profiles, KeyProfiles::default());
+
+        assert_eq!(check_profile_name(" work_2 ").unwrap(), "work_2");
+        assert!(check_profile_name("").is_err());
+        assert!(check_profile_name("a:b").is_err());
+        assert!(check_profile_name(&"x".repeat(33)).is_err());
+    }
+
+    #[test]
+    fn key_previews_hide_most_of_the_key() {
+        let mut store = KeyMetadataStore::default();
+        assert!(!mark_key_used(&mut store, "groq", "default", 100, || None));
+        assert!(store.is_empty());
+        let preview = || Some(mask_api_key("sk-proj-abcdefgh1234"));
+        assert!(mark_key_used(&mut store, "openai", "work", 100, preview));
+        assert!(!mark_key_used(&mut store, "openai", "work", 130, || None));
+        assert!(mark_key_used(&mut store, "openai", "work", 160, || None));
+        assert_eq!(
+            store["openai"]["work"],
+            KeyMetadata {
+                created_at: None,
+                last_used_at: Some(160),
+                preview: "sk-p…1234".to_string(),
+            }
+        );
+
+        assert_eq!(mask_api_key(" sk-proj-abcdefgh1234 "), "sk-p…1234");
+        assert_eq!(mask_api_key("short-key-12345"), "••••");
+        assert_eq!(mask_api_key(""), "••••");
+    }
+
+    #[test]
+    fn azure_deployments_build_the_deployment_url() {
+        let deployment = AzureDeployment {
+            resource_name: " Contoso-AI ".to_string(),
+            deployment_name: "gpt-4o".to_string(),
+            api_version: String::new(),
+        }
+        .validated()
+        .unwrap();
+        assert_eq!(
+            deployment.chat_url(),
+            "https://contoso-ai.openai.azure.com/openai/deployments/gpt-4o/chat/completions?api-version=2024-10-21"
+        );
+        assert_eq!(
+            deployment.models_url(),
+            "https://contoso-ai.openai.azure.com/openai/models?api-version=2024-10-21"
+        );
+        let invalid = |resource: &str, name: &str| {
+            AzureDeployment {
+                resource_name: resource.to_string(),
+                deployment_name: name.to_string(),
+                api_version: "2024-06-01".to_string(),
+            }
+            .validated()
+            .is_err()
+        };
+        assert!(invalid("evil.com/x", "gpt-4o"));
+        assert!(invalid("contoso", "../models"));
+        assert!(invalid("contoso", ""));
+        assert!(invalid("contoso", ".."));
+        assert!(!invalid("contoso", "gpt-4o_mini.2"));
+    }
+}
@@ -0,0 +1,266 @@
+//! Moderation of chat requests
+//!
+//! Deployments that need content filtering can have each chat request
+//! checked before it is sent. The latest user message goes to the
+//! `/moderations` endpoint of a provider, OpenAI's by default or any
+//! compatible one, and the flagged categories come back with the reply.
+//! With `ai.moderation` set to `block`, flagged requests are refused, as
+//! are requests that could not be checked; with `flag`, they are sent
+//! anyway. The settings are loaded at startup and reloaded when they
+//! change.
+
+use crate::commands::ai_proxy::{post_json, provider_request, raw_request_url, AIMessage};
+use crate::commands::settings::{setting, SETTING_CHANGED_EVENT};
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use tauri::Listener;
+
+/// Setting choosing whether requests are checked, and what a flag does
+pub const MODERATION_MODE_SETTING: &str = "ai.moderation";
+/// Setting naming the provider that checks requests
+pub const MODERATION_PROVIDER_SETTING: &str = "ai.moderationProvider";
+/// Setting naming the model that checks requests
+pub const MODERATION_MODEL_SETTING: &str = "ai.moderationModel";
+
+/// Moderation in effect; off until loaded
+static MODERATION: RwLock<Option<ModerationConfig>> = RwLock::new(None);
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModerationMode {
+    /// Report the flags with the reply
+    Flag,
+    /// Refuse flagged requests
+    Block,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModerationConfig {
+    pub mode: ModerationMode,
+    pub provider: String,
+    pub model: String,
+}
+
+/// What moderation found in a request
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ModerationResult {
+    pub flagged: bool,
+    /// Categories flagged, such as `harassment` or `self-harm/intent`
+    pub categories: Vec<String>,
+    /// Score of every category, from 0 to 1
+    pub scores: BTreeMap<String, f64>,
+}
+
+#[derive(Serialize)]
+struct ModerationRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ModerationResponse {
+    results: Vec<ModerationEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModerationEntry {
+    flagged: bool,
+    #[serde(default)]
+    categories: BTreeMap<String, bool>,
+    #[serde(default)]
+    category_scores: BTreeMap<String, f64>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Merge the results of a moderation response, one per input
+fn moderation_result(response: ModerationResponse) -> ModerationResult {
+    let mut result = ModerationResult::default();
+    for entry in response.results {
+        result.flagged |= entry.flagged;
+        for (category, flagged) in entry.categories {
+            if flagged && !result.categories.contains(&category) {
+                result.categories.push(category);
+            }
+        }
+        for (category, score) in entry.category_scores {
+            let best = result.scores.entry(category).or_default();
+            *best = best.max(score);
+        }
+    }
+    result.categories.sort();
+    result
+}
+
+/// Whether a request may be sent after moderation
+pub fn check_moderation(
+    mode: ModerationMode,
+    result: &Result<ModerationResult, AppError>,
+) -> Result<(), AppError> {
+    match (mode, result) {
+        (ModerationMode::Block, Ok(result)) if result.flagged => {
+            Err(AppError::PermissionDenied(format!(
+                "The request was flagged by moderation: {}",
+                result.categories.join(", ")
+            )))
+        }
+        (ModerationMode::Block, Err(e)) => Err(AppError::PermissionDenied(format!(
+            "The request could not be moderated: {}",
+            e
+        ))),
+        _ => Ok(()),
+    }
+}
+
+async fn request_moderation(
+    config: &ModerationConfig,
+    input: &str,
+) -> Result<ModerationResult, AppError> {
+    let url = raw_request_url(&config.provider, "/moderations")?;
+    let response: ModerationResponse = post_json(
+        &config.provider,
+        "Moderation request",
+        provider_request(&config.provider, &url)?,
+        &ModerationRequest {
+            model: &config.model,
+            input,
+        },
+    )
+    .await?;
+    Ok(moderation_result(response))
+}
+
+/// Check the latest user message of a request when moderation is on;
+/// fails when the request must not be sent
+pub async fn moderate_messages(
+    messages: &[AIMessage],
+) -> Result<Option<ModerationResult>, AppError> {
+    let Some(config) = MODERATION.read().ok().and_then(|config| config.clone()) else {
+        return Ok(None);
+    };
+    let Some(input) = messages
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .map(|message| message.content.text())
+        .filter(|text| !text.trim().is_empty())
+    else {
+        return Ok(None);
+    };
+    let result = request_moderation(&config, &input).await;
+    check_moderation(config.mode, &result)?;
+    match result {
+        Ok(result) => {
+            if result.flagged {
+                log::warn!(
+                    "Chat request flagged by moderation: {}",
+                    result.categories.join(", ")
+                );
+            }
+            Ok(Some(result))
+        }
+        Err(e) => {
+            log::warn!("Moderation failed, sending the request anyway: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+fn load_moderation_config(app: &tauri::AppHandle) -> Result<Option<ModerationConfig>, AppError> {
+    let mode = match setting::<String>(app, MODERATION_MODE_SETTING)?.as_str() {
+        "flag" => ModerationMode::Flag,
+        "block" => ModerationMode::Block,
+        _ => return Ok(None),
+    };
+    Ok(Some(ModerationConfig {
+        mode,
+        provider: setting(app, MODERATION_PROVIDER_SETTING)?,
+        model: setting(app, MODERATION_MODEL_SETTING)?,
+    }))
+}
+
+fn load_moderation(app: &tauri::AppHandle) {
+    let config = load_moderation_config(app).unwrap_or_else(|e| {
+        log::warn!("Failed to load the moderation settings: {}", e);
+        None
+    });
+    if let Ok(mut current) = MODERATION.write() {
+        *current = config;
+    }
+}
+
+/// Load the moderation settings and reload them whenever they change
+pub fn init_moderation(app: &tauri::AppHandle) {
+    load_moderation(app);
+    let handle = app.clone();
+    app.listen_any(SETTING_CHANGED_EVENT, move |event| {
+        let key = serde_json::from_str::<serde_json::Value>(event.payload())
+            .ok()
+            .and_then(|change| change["key"].as_str().map(str::to_string));
+        if key.as_deref().is_some_and(|key| {
+            [
+                MODERATION_MODE_SETTING,
+                MODERATION_PROVIDER_SETTING,
+                MODERATION_MODEL_SETTING,
+            ]
+            .contains(&key)
+        }) {
+            load_moderation(&handle);
+        }
+    });
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn flagged_requests_are_blocked_only_when_asked() {
+        let response: ModerationResponse = serde_json::from_value(json!({
+            "id": "modr-1",
+            "model": "omni-moderation-latest",
+            "results": [
+                {
+                    "flagged": true,
+                    "categories": {"harassment": true, "violence": false},
+                    "category_scores": {"harassment": 0.91, "violence": 0.2}
+                },
+                {
+                    "flagged": false,
+                    "categories": {"harassment": false, "violence": false},
+                    "category_scores": {"harassment": 0.1, "violence": 0.4}
+                }
+            ]
+        }))
+        .unwrap();
+        let flagged = moderation_result(response);
+        assert!(flagged.flagged);
+        assert_eq!(flagged.categories, ["harassment"]);
+        assert_eq!(flagged.scores["violence"], 0.4);
+
+        let clean = Ok(ModerationResult::default());
+        let unreachable = Err(AppError::http("timed out"));
+        let flagged = Ok(flagged);
+        assert!(check_moderation(ModerationMode::Flag, &flagged).is_ok());
+        assert!(check_moderation(ModerationMode::Flag, &unreachable).is_ok());
+        assert!(check_moderation(ModerationMode::Block, &clean).is_ok());
+        match check_moderation(ModerationMode::Block, &flagged) {
+            Err(AppError::PermissionDenied(reason)) => assert!(reason.ends_with("harassment")),
+            other => panic!("expected a refusal, got {:?}", other),
+        }
+        assert!(check_moderation(ModerationMode::Block, &unreachable).is_err());
+    }
+}
@@ -0,0 +1,371 @@
+//! Prices of AI models, for the cost of each request
+//!
+//! The cost in the usage statistics is computed in the backend from the
+//! tokens a request used and the price of its model, in US dollars per
+//! million tokens. Prices ship in `resources/ai_pricing.json`, updated with
+//! the app, and users can override or add models; their prices are stored
+//! in `ai_pricing.json` in the app data directory and win over the bundled
+//! ones. A model is priced by its exact name, else by the longest priced
+//! name it starts with (`gpt-4o-mini` for `gpt-4o-mini-2024-07-18`); models
+//! routed as `vendor/model`, as on OpenRouter, fall back to the vendor's
+//! price. Requests to models without a price cost nothing.
+
+use crate::commands::ai_proxy::TokenUsage;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tauri::Manager;
+
+/// User prices file name in the app data directory
+pub const AI_PRICING_FILE: &str = "ai_pricing.json";
+
+/// Prices shipped with the app
+const BUNDLED_PRICING: &str = include_str!("../../resources/ai_pricing.json");
+
+/// Prices in effect: the bundled ones with the user's applied
+static AI_PRICING: RwLock<Option<PricingTable>> = RwLock::new(None);
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Price of a model in US dollars per million tokens
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// Prompt tokens read from the cache; the input price when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
+    /// Prompt tokens written to the cache; the input price when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write: Option<f64>,
+}
+
+/// Prices by provider, then model
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingTable {
+    /// Date of the bundled prices
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub models: BTreeMap<String, BTreeMap<String, ModelPrice>>,
+}
+
+/// A priced model, as listed to the user
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPricing {
+    pub provider: String,
+    pub model: String,
+    pub price: ModelPrice,
+    /// Set or changed by the user
+    pub custom: bool,
+}
+
+impl ModelPrice {
+    fn validated(self) -> Result<ModelPrice, AppError> {
+        let prices = [
+            Some(self.input),
+            Some(self.output),
+            self.cached_input,
+            self.cache_write,
+        ];
+        if prices
+            .iter()
+            .flatten()
+            .any(|price| !price.is_finite() || *price < 0.0)
+        {
+            return Err(AppError::InvalidInput(
+                "Prices must be zero or more".to_string(),
+            ));
+        }
+        Ok(self)
+    }
+
+    /// Cost of a request's tokens in US dollars
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let cached = usage.cached_tokens.min(usage.input_tokens);
+        let written = usage.cache_write_tokens.min(usage.input_tokens - cached);
+        let uncached = usage.input_tokens - cached - written;
+        (uncached as f64 * self.input
+            + cached as f64 * self.cached_input.unwrap_or(self.input)
+            + written as f64 * self.cache_write.unwrap_or(self.input)
+            + usage.output_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+impl PricingTable {
+    /// Price of a model: its own, else that of the longest priced name it
+    /// starts with, else the vendor's for `vendor/model`
+    pub fn find(&self, provider: &str, model: &str) -> Option<ModelPrice> {
+        let by_prefix = |models: &BTreeMap<String, ModelPrice>, model: &str| {
+            models
+                .iter()
+                .filter(|(name, _)| {
+                    model
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['-', ':', '@']))
+                })
+                .max_by_key(|(name, _)| name.len())
+                .map(|(_, price)| *price)
+        };
+        self.models
+            .get(provider)
+            .and_then(|models| by_prefix(models, model))
+            .or_else(|| {
+                let (vendor, model) = model.split_once('/')?;
+                by_prefix(self.models.get(vendor)?, model)
+            })
+    }
+
+    /// Add prices to the table, replacing those of the same models
+    fn apply(&mut self, prices: &PricingTable) {
+        for (provider, models) in &prices.models {
+            let entry = self.models.entry(provider.clone()).or_default();
+            for (model, price) in models {
+                entry.insert(model.clone(), *price);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Prices shipped with the app
+pub fn bundled_pricing() -> PricingTable {
+    serde_json::from_str(BUNDLED_PRICING).unwrap_or_else(|e| {
+        log::error!("Bundled AI prices are invalid: {}", e);
+        PricingTable::default()
+    })
+}
+
+/// The bundled prices with the user's applied
+pub fn effective_pricing(custom: &PricingTable) -> PricingTable {
+    let mut table = bundled_pricing();
+    table.apply(custom);
+    table
+}
+
+fn set_pricing(table: PricingTable) {
+    if let Ok(mut current) = AI_PRICING.write() {
+        *current = Some(table);
+    }
+}
+
+/// Cost of a request in US dollars under the prices in effect; none for
+/// models without a price
+pub fn request_cost(provider: &str, model: &str, usage: &TokenUsage) -> Option<f64> {
+    let table = AI_PRICING.read().ok()?;
+    let price = match table.as_ref() {
+        Some(table) => table.find(provider, model),
+        // Asked before `init_ai_pricing`
+        None => bundled_pricing().find(provider, model),
+    };
+    price.map(|price| price.cost(usage))
+}
+
+fn get_pricing_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    fs::create_dir_all(&data_dir)?;
+    Ok(data_dir.join(AI_PRICING_FILE))
+}
+
+pub fn load_pricing_from_file(path: &Path) -> Result<PricingTable, AppError> {
+    if !path.exists() {
+        return Ok(PricingTable::default());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn save_pricing_to_file(path: &Path, table: &PricingTable) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(table)?)?;
+    Ok(())
+}
+
+/// Set, or with no price remove, the user's price of a model
+pub fn set_custom_price(
+    custom: &mut PricingTable,
+    provider: &str,
+    model: &str,
+    price: Option<ModelPrice>,
+) -> Result<(), AppError> {
+    let (provider, model) = (provider.trim(), model.trim());
+    if provider.is_empty() || model.is_empty() {
+        return Err(AppError::InvalidInput(
+            "A price needs a provider and a model".to_string(),
+        ));
+    }
+    match price {
+        Some(price) => {
+            custom
+                .models
+                .entry(provider.to_string())
+                .or_default()
+                .insert(model.to_string(), price.validated()?);
+        }
+        None => {
+            if let Some(models) = custom.models.get_mut(provider) {
+                models.remove(model);
+                if models.is_empty() {
+                    custom.models.remove(provider);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Load the user's prices; called at startup. Unreadable prices are
+/// logged and the bundled ones used
+pub fn init_ai_pricing(app: &tauri::AppHandle) {
+    let custom = match get_pricing_path(app).and_then(|path| load_pricing_from_file(&path)) {
+        Ok(custom) => custom,
+        Err(e) => {
+            log::warn!("Failed to load the user's AI prices: {}", e);
+            PricingTable::default()
+        }
+    };
+    let table = effective_pricing(&custom);
+    log::info!(
+        "AI prices {} loaded, {} set by the user",
+        table.version,
+        custom.models.values().map(BTreeMap::len).sum::<usize>()
+    );
+    set_pricing(table);
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the priced models, the user's marked custom
+#[tauri::command]
+pub fn get_ai_pricing(app: tauri::AppHandle) -> Result<Vec<ModelPricing>, AppError> {
+    let custom = load_pricing_from_file(&get_pricing_path(&app)?)?;
+    let table = effective_pricing(&custom);
+    Ok(table
+        .models
+        .iter()
+        .flat_map(|(provider, models)| {
+            models.iter().map(|(model, price)| ModelPricing {
+                provider: provider.clone(),
+                model: model.clone(),
+                price: *price,
+                custom: custom
+                    .models
+                    .get(provider)
+                    .is_some_and(|models| models.contains_key(model)),
+            })
+        })
+        .collect())
+}
+
+/// Set the price of a model, or with no price restore the bundled one
+#[tauri::command]
+pub fn set_ai_model_price(
+    app: tauri::AppHandle,
+    provider: String,
+    model: String,
+    price: Option<ModelPrice>,
+) -> Result<(), AppError> {
+    let path = get_pricing_path(&app)?;
+    let mut custom = load_pricing_from_file(&path)?;
+    set_custom_price(&mut custom, &provider, &model, price)?;
+    save_pricing_to_file(&path, &custom)?;
+    set_pricing(effective_pricing(&custom));
+    log::info!("Price of {}/{} set to {:?}", provider, model, price);
+    Ok(())
+}
+
+/// Drop the user's prices, back to the bundled ones
+#[tauri::command]
+pub fn reset_ai_pricing(app: tauri::AppHandle) -> Result<(), AppError> {
+    let path = get_pricing_path(&app)?;
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    set_pricing(bundled_pricing());
+    log::info!("AI prices reset to the bundled ones");
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_priced_by_model() {
+        let bundled = bundled_pricing();
+        assert!(!bundled.version.is_empty());
+        let price = |provider: &str, model: &str| bundled.find(provider, model);
+        assert_eq!(
+            price("openai", "gpt-4o-mini-2024-07-18"),
+            price("openai", "gpt-4o-mini")
+        );
+        assert_ne!(price("openai", "gpt-4o-mini"), price("openai", "gpt-4o"));
+        assert_eq!(
+            price("openrouter", "anthropic/claude-sonnet-4"),
+            price("anthropic", "claude-sonnet-4-20250514")
+        );
+        assert_eq!(price("openai", "gpt-4omni"), None);
+        assert_eq!(price("local", "llama"), None);
+
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cached_tokens: 200_000,
+            cache_write_tokens: 300_000,
+        };
+        let sonnet = ModelPrice {
+            input: 3.0,
+            output: 15.0,
+            cached_input: Some(0.3),
+            cache_write: Some(3.75),
+        };
+        // 0.5M at 3, 0.2M at 0.3, 0.3M at 3.75, 0.1M at 15
+        assert!((sonnet.cost(&usage) - 4.185).abs() < 1e-9);
+        let plain = ModelPrice {
+            input: 1.0,
+            output: 2.0,
+            ..Default::default()
+        };
+        assert!((plain.cost(&usage) - 1.2).abs() < 1e-9);
+
+        let mut custom = PricingTable::default();
+        set_custom_price(&mut custom, "openai", "gpt-4o", Some(plain)).unwrap();
+        set_custom_price(&mut custom, "lab", "mistral", Some(plain)).unwrap();
+        let table = effective_pricing(&custom);
+        assert_eq!(table.find("openai", "gpt-4o-2024-08-06"), Some(plain));
+        assert_eq!(table.find("lab", "mistral"), Some(plain));
+        assert_eq!(
+            table.find("openai", "gpt-4o-mini"),
+            price("openai", "gpt-4o-mini")
+        );
+        let negative = ModelPrice {
+            input: -1.0,
+            ..plain
+        };
+        assert!(set_custom_price(&mut custom, "lab", "x", Some(negative)).is_err());
+        set_custom_price(&mut custom, "lab", "mistral", None).unwrap();
+        assert!(!custom.models.contains_key("lab"));
+    }
+}
@@ -0,0 +1,337 @@
+//! User-registered OpenAI-compatible providers
+//!
+//! Besides the built-in providers, any service speaking the OpenAI chat
+//! completions API can be registered by name and base URL, with headers
+//! added to every request. The registry is stored in `ai_providers.json` in
+//! the app data directory and kept in memory, so the AI proxy resolves
+//! endpoints without an app handle. A custom provider's API key is saved
+//! under its id like any other; providers that need none, such as servers
+//! on the local network, work without one.
+
+use crate::commands::ai_keys::delete_api_key;
+use crate::error::AppError;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tauri::Manager;
+use uuid::Uuid;
+
+/// Custom providers file name in the app data directory
+pub const CUSTOM_PROVIDERS_FILE: &str = "ai_providers.json";
+
+/// Prefix of custom provider ids, which built-in providers never use
+const CUSTOM_PROVIDER_PREFIX: &str = "custom_";
+
+/// Registered providers, loaded at startup
+static CUSTOM_PROVIDERS: RwLock<Vec<CustomProvider>> = RwLock::new(Vec::new());
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomProvider {
+    /// `custom_<uuid>`, the provider name requests use; empty to add one
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    /// API base URL, such as `https://llm.example.com/v1`
+    pub base_url: String,
+    /// Headers added to every request
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomProvidersStore {
+    #[serde(default)]
+    pub providers: Vec<CustomProvider>,
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+impl CustomProvider {
+    /// Trim the fields and reject a base URL or headers requests could not
+    /// be sent with
+    fn validated(self) -> Result<CustomProvider, AppError> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            return Err(AppError::InvalidInput(
+                "A custom provider needs a name".to_string(),
+            ));
+        }
+        let base_url = self.base_url.trim().trim_end_matches('/').to_string();
+        let valid_url = url::Url::parse(&base_url).is_ok_and(|url| {
+            matches!(url.scheme(), "http" | "https")
+                && url.has_host()
+                && url.query().is_none()
+                && url.fragment().is_none()
+        });
+        if !valid_url {
+            return Err(AppError::InvalidInput(format!(
+                "Invalid provider base URL: {}",
+                base_url
+            )));
+        }
+        let mut headers = BTreeMap::new();
+        for (header, value) in self.headers {
+            let header = header.trim().to_string();
+            if HeaderName::from_bytes(header.as_bytes()).is_err()
+                || HeaderValue::from_str(&value).is_err()
+            {
+                return Err(AppError::InvalidInput(format!(
+                    "Invalid header for {}: {}",
+                    name, header
+                )));
+            }
+            headers.insert(header, value);
+        }
+        Ok(CustomProvider {
+            name,
+            base_url,
+            headers,
+            ..self
+        })
+    }
+
+    /// Chat completions URL of the provider
+    pub fn chat_url(&self) -> String {
+        format!("{}/chat/completions", self.base_url)
+    }
+
+    /// Add the provider's headers, and its key if it has one, to a request
+    pub fn authorize(
+        &self,
+        mut request: reqwest::RequestBuilder,
+        api_key: Option<&str>,
+    ) -> reqwest::RequestBuilder {
+        if let Some(api_key) = api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        for (header, value) in &self.headers {
+            request = request.header(header.as_str(), value.as_str());
+        }
+        request
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Whether a provider name is that of a custom provider
+pub fn is_custom_provider(provider: &str) -> bool {
+    provider.starts_with(CUSTOM_PROVIDER_PREFIX)
+}
+
+/// The registered provider with an id
+pub fn find_custom_provider(provider: &str) -> Option<CustomProvider> {
+    if !is_custom_provider(provider) {
+        return None;
+    }
+    CUSTOM_PROVIDERS
+        .read()
+        .ok()?
+        .iter()
+        .find(|custom| custom.id == provider)
+        .cloned()
+}
+
+fn set_custom_providers(providers: Vec<CustomProvider>) {
+    if let Ok(mut current) = CUSTOM_PROVIDERS.write() {
+        *current = providers;
+    }
+}
+
+fn get_custom_providers_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    fs::create_dir_all(&data_dir)?;
+    Ok(data_dir.join(CUSTOM_PROVIDERS_FILE))
+}
+
+pub fn load_custom_providers_from_file(path: &Path) -> Result<CustomProvidersStore, AppError> {
+    if !path.exists() {
+        return Ok(CustomProvidersStore::default());
+    }
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+pub fn save_custom_providers_to_file(
+    path: &Path,
+    store: &CustomProvidersStore,
+) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(store)?)?;
+    Ok(())
+}
+
+/// Add a provider to a store, or replace the one with its id; returns it
+/// as stored
+pub fn upsert_custom_provider(
+    store: &mut CustomProvidersStore,
+    provider: CustomProvider,
+    now: i64,
+) -> Result<CustomProvider, AppError> {
+    let mut provider = provider.validated()?;
+    if store
+        .providers
+        .iter()
+        .any(|other| other.id != provider.id && other.name.eq_ignore_ascii_case(&provider.name))
+    {
+        return Err(AppError::InvalidInput(format!(
+            "A provider named '{}' already exists",
+            provider.name
+        )));
+    }
+    provider.updated_at = now;
+    store.updated_at = now;
+    if provider.id.is_empty() {
+        provider.id = format!("{}{}", CUSTOM_PROVIDER_PREFIX, Uuid::new_v4());
+        provider.created_at = now;
+        store.providers.push(provider.clone());
+        return Ok(provider);
+    }
+    let existing = store
+        .providers
+        .iter_mut()
+        .find(|existing| existing.id == provider.id)
+        .ok_or_else(|| AppError::NotFound(format!("Custom provider {}", provider.id)))?;
+    provider.created_at = existing.created_at;
+    *existing = provider.clone();
+    Ok(provider)
+}
+
+/// Load the registry; called at startup
+pub fn init_custom_providers(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let store = load_custom_providers_from_file(&get_custom_providers_path(app)?)?;
+    log::info!("{} custom AI providers registered", store.providers.len());
+    set_custom_providers(store.providers);
+    Ok(())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the registered custom providers
+#[tauri::command]
+pub fn get_custom_providers() -> Vec<CustomProvider> {
+    CUSTOM_PROVIDERS
+        .read()
+        .map(|providers| providers.clone())
+        .unwrap_or_default()
+}
+
+/// Register a custom provider, or update the one with its id
+#[tauri::command]
+pub fn save_custom_provider(
+    app: tauri::AppHandle,
+    provider: CustomProvider,
+) -> Result<CustomProvider, AppError> {
+    let path = get_custom_providers_path(&app)?;
+    let mut store = load_custom_providers_from_file(&path)?;
+    let saved = upsert_custom_provider(&mut store, provider, chrono::Utc::now().timestamp())?;
+    save_custom_providers_to_file(&path, &store)?;
+    set_custom_providers(store.providers);
+    log::info!("Custom provider saved: {} ({})", saved.name, saved.base_url);
+    Ok(saved)
+}
+
+/// Remove a custom provider and its API key
+#[tauri::command]
+pub fn delete_custom_provider(app: tauri::AppHandle, id: String) -> Result<(), AppError> {
+    let path = get_custom_providers_path(&app)?;
+    let mut store = load_custom_providers_from_file(&path)?;
+    let count = store.providers.len();
+    store.providers.retain(|provider| provider.id != id);
+    if store.providers.len() == count {
+        return Err(AppError::NotFound(format!("Custom provider {}", id)));
+    }
+    store.updated_at = chrono::Utc::now().timestamp();
+    save_custom_providers_to_file(&path, &store)?;
+    set_custom_providers(store.providers);
+    delete_api_key(app, id.clone(), None)?;
+    log::info!("Custom provider deleted: {}", id);
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_providers_are_validated_and_upserted() {
+        let mut store = CustomProvidersStore::default();
+        let provider = |name: &str, base_url: &str| CustomProvider {
+            name: name.to_string(),
+            base_url: base_url.to_string(),
+            headers: BTreeMap::from([("X-Team".to_string(), "reader".to_string())]),
+            ..Default::default()
+        };
+
+        let added = upsert_custom_provider(
+            &mut store,
+            provider(" Lab ", "https://llm.example.com/v1/"),
+            10,
+        )
+        .unwrap();
+        assert!(is_custom_provider(&added.id));
+        assert_eq!(added.name, "Lab");
+        assert_eq!(
+            added.chat_url(),
+            "https://llm.example.com/v1/chat/completions"
+        );
+        assert_eq!(added.created_at, 10);
+
+        let renamed = upsert_custom_provider(
+            &mut store,
+            CustomProvider {
+                id: added.id.clone(),
+                ..provider("Lab server", "http://10.0.0.2:8000/v1")
+            },
+            20,
+        )
+        .unwrap();
+        assert_eq!((renamed.created_at, renamed.updated_at), (10, 20));
+        assert_eq!(store.providers, vec![renamed]);
+
+        assert!(
+            upsert_custom_provider(&mut store, provider("lab SERVER", "https://a.b"), 30).is_err()
+        );
+        assert!(upsert_custom_provider(&mut store, provider("Other", "ftp://a.b"), 30).is_err());
+        assert!(
+            upsert_custom_provider(&mut store, provider("Other", "https://a.b?k=1"), 30).is_err()
+        );
+        let mut bad_header = provider("Other", "https://a.b");
+        bad_header
+            .headers
+            .insert("Bad Header".to_string(), "x".to_string());
+        assert!(upsert_custom_provider(&mut store, bad_header, 30).is_err());
+        let missing = CustomProvider {
+            id: "custom_missing".to_string(),
+            ..provider("Other", "https://a.b")
+        };
+        assert!(upsert_custom_provider(&mut store, missing, 30).is_err());
+        assert_eq!(store.providers.len(), 1);
+        assert!(!is_custom_provider("openai"));
+    }
+}
@@ -1,18 +1,246 @@
 //! AI proxy request command
+//!
+//! Chat requests go to OpenAI-compatible providers in their format and to
+//! Anthropic through its Messages API, where the system prompt and messages
+//! flagged `cache` can be marked for prompt caching. User messages may mix
+//! text with images, such as page screenshots, sent by URL or as base64.
+//! OpenRouter requests can carry routing preferences, and the upstream
+//! provider that served them is reported. When a provider fails, chat
+//! requests move on to the fallback providers the user listed. Requests and
+//! responses are recorded in the AI debug log while it is on. Token usage
+//! of proxied requests, cached tokens included, is added to the usage
+//! statistics. Chat and embedding requests that fail on the network or with
+//! a rate limit are retried. With the `local-inference` feature, the
+//! `local` provider runs GGUF models on this machine. The `azure` provider
+//! sends requests to the Azure OpenAI deployment saved with its key, and
+//! custom providers to the base URL they were registered with. Each request
+//! carries the provider's default headers (see `ai_headers`). Chat requests
+//! may be moderated before they are sent (see `ai_moderation`).
+//!
+//! `proxy_transcription_request` uploads an audio file to the Whisper
+//! endpoint of OpenAI or Groq and returns the transcript, for voice input.
+//!
+//! When the `ai.rawRequests` setting is on, `proxy_ai_request_raw` forwards
+//! any JSON body to a provider API path with the stored key added, for
+//! features the typed requests do not cover yet.
 
-use crate::commands::ai_keys::KEYRING_SERVICE;
-use crate::error::AppError;
+use crate::commands::ai_debug_log::{ai_debug_log_enabled, record_ai_exchange, AIExchange};
+use crate::commands::ai_failover::with_failover;
+use crate::commands::ai_headers::apply_default_headers;
+use crate::commands::ai_keys::{
+    get_api_key, get_azure_deployment, get_provider_tenant, record_key_use, tenant_headers,
+    AZURE_PROVIDER,
+};
+use crate::commands::ai_moderation::{moderate_messages, ModerationResult};
+use crate::commands::ai_providers::find_custom_provider;
+use crate::commands::ai_structured::{
+    check_schema_format, parse_structured_reply, JsonSchemaFormat,
+};
+use crate::commands::ai_usage::record_usage;
+use crate::commands::conversations::{
+    lock_conversations, resolve_system_prompt, ConversationState,
+};
+#[cfg(feature = "local-inference")]
+use crate::commands::local_inference::{local_chat_endpoint, LOCAL_PROVIDER};
+use crate::commands::network::{
+    apply_proxy, PROXY_MODE_SETTING, PROXY_URL_SETTING, REQUEST_TIMEOUT_SETTING,
+};
+use crate::commands::retry::with_retry;
+use crate::commands::settings::{setting, SETTING_CHANGED_EVENT};
+use crate::error::{AppError, ErrorCategory};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tauri::Listener;
+
+/// Client shared by AI requests; building one loads the TLS roots, so it is
+/// built in the background after startup, and again when the proxy or
+/// timeout settings change
+static AI_HTTP_CLIENT: RwLock<Option<reqwest::Client>> = RwLock::new(None);
+
+/// Version of the Anthropic Messages API requests are written for
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Cache breakpoints Anthropic accepts in one request
+const MAX_CACHE_BREAKPOINTS: usize = 4;
+
+/// Setting that enables `proxy_ai_request_raw`
+pub const RAW_REQUESTS_SETTING: &str = "ai.rawRequests";
+
+/// Reply length asked of Anthropic, which requires one, when none is set
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Most alternative replies one request may ask for
+const MAX_CHOICES: u32 = 8;
+/// Most stop sequences OpenAI-compatible APIs accept
+const MAX_STOP_SEQUENCES: usize = 4;
+const MAX_TOP_LOGPROBS: u32 = 20;
+/// Highest temperature OpenAI-compatible APIs accept; Anthropic's is 1
+const MAX_TEMPERATURE: f32 = 2.0;
+/// Largest frequency or presence penalty, either way
+const MAX_PENALTY: f32 = 2.0;
+
+/// Image types every vision-capable provider accepts
+const IMAGE_MEDIA_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// Audio file extensions Whisper endpoints accept, with their media types
+const AUDIO_MEDIA_TYPES: &[(&str, &str)] = &[
+    ("flac", "audio/flac"),
+    ("m4a", "audio/mp4"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "audio/mp4"),
+    ("mpeg", "audio/mpeg"),
+    ("mpga", "audio/mpeg"),
+    ("oga", "audio/ogg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("webm", "audio/webm"),
+];
+
+/// Largest audio file Whisper endpoints accept
+const MAX_AUDIO_BYTES: u64 = 25 * 1024 * 1024;
 
 // ============================================================================
 // Data Structures
 // ============================================================================
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AIMessage {
     pub role: String,
+    pub content: MessageContent,
+    /// Mark the prompt up to this message for caching (Anthropic), for
+    /// context such as book text that is sent again with every question
+    #[serde(default)]
+    pub cache: bool,
+}
+
+/// Text of a message, or its parts when it has images
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ContentPart {
+    Text { text: String },
+    Image(ImagePart),
+}
+
+/// An image, by URL or as base64 data
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImagePart {
+    /// `https:` URL, or a `data:` URL with base64 data
+    pub url: Option<String>,
+    /// Base64 image data, sent with `media_type`
+    pub data: Option<String>,
+    /// One of `IMAGE_MEDIA_TYPES`
+    pub media_type: Option<String>,
+}
+
+/// Where an image comes from, once checked
+#[derive(Debug, PartialEq)]
+enum ImageSource {
+    Url(String),
+    Base64 { media_type: String, data: String },
+}
+
+/// Request options beyond the messages
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatOptions {
+    /// Mark the system prompt for caching (Anthropic)
+    #[serde(default)]
+    pub cache_system_prompt: bool,
+    /// Upstream provider preferences (OpenRouter)
+    pub routing: Option<OpenRouterRouting>,
+    /// Sampling randomness; the provider's default when unset
+    pub temperature: Option<f32>,
+    /// Probability mass sampled from, 0 to 1
+    pub top_p: Option<f32>,
+    /// Most tokens in the reply
+    pub max_tokens: Option<u32>,
+    /// Penalty on tokens by how often they appeared (OpenAI-compatible)
+    pub frequency_penalty: Option<f32>,
+    /// Penalty on tokens that appeared at all (OpenAI-compatible)
+    pub presence_penalty: Option<f32>,
+    /// Sequences that end the reply where they would appear
+    #[serde(default)]
+    pub stop: Vec<String>,
+    /// Alternative replies to generate (OpenAI-compatible providers)
+    pub n: Option<u32>,
+    /// Return the log probabilities of the reply tokens (OpenAI-compatible)
+    #[serde(default)]
+    pub logprobs: bool,
+    /// Most likely alternatives returned per token with `logprobs`
+    pub top_logprobs: Option<u32>,
+    /// JSON schema every choice must follow (OpenAI-compatible)
+    pub json_schema: Option<JsonSchemaFormat>,
+}
+
+/// Which upstream providers OpenRouter may route a request to
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenRouterRouting {
+    /// Providers to try, in order
+    #[serde(default)]
+    pub order: Vec<String>,
+    /// Whether other providers may serve the request when those fail
+    pub allow_fallbacks: Option<bool>,
+    /// "deny" to use only providers that do not store or train on prompts
+    pub data_collection: Option<String>,
+}
+
+/// Tokens of one request
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    /// Prompt tokens, cached ones included
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Prompt tokens read from the cache
+    pub cached_tokens: u64,
+    /// Prompt tokens written to the cache
+    pub cache_write_tokens: u64,
+}
+
+/// One alternative reply
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatChoice {
+    pub index: u32,
     pub content: String,
+    /// Why generation stopped, as the provider reports it
+    pub finish_reason: Option<String>,
+    /// Token log probabilities, in the provider's format
+    pub logprobs: Option<serde_json::Value>,
+}
+
+/// Reply text and the tokens it took
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatReply {
+    /// Provider that answered: the one asked, or a fallback when it failed
+    pub provider: String,
+    /// Model that answered
+    pub model: String,
+    /// Text of the first choice
+    pub content: String,
+    /// Every choice, in order
+    pub choices: Vec<ChatChoice>,
+    pub usage: Option<TokenUsage>,
+    /// Upstream provider that served the request, when routed (OpenRouter)
+    pub served_by: Option<String>,
+    /// The first choice parsed, when the request gave a JSON schema
+    pub structured: Option<serde_json::Value>,
+    /// What moderation found in the request, when it is on
+    pub moderation: Option<ModerationResult>,
 }
 
 #[derive(Serialize)]
@@ -23,33 +251,295 @@ struct OpenAIRequest {
     max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<u32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    logprobs: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<OpenAIResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider: Option<OpenRouterProvider>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct OpenAIResponseFormat {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    json_schema: JsonSchemaFormat,
+}
+
+/// `provider` object of an OpenRouter request
+#[derive(Serialize, Debug, PartialEq)]
+struct OpenRouterProvider {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    order: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allow_fallbacks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_collection: Option<String>,
 }
 
 #[derive(Serialize)]
 struct OpenAIMessage {
     role: String,
-    content: String,
+    content: OpenAIContent,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(untagged)]
+enum OpenAIContent {
+    Text(String),
+    Parts(Vec<OpenAIContentPart>),
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OpenAIContentPart {
+    Text { text: String },
+    ImageUrl { image_url: OpenAIImageUrl },
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct OpenAIImageUrl {
+    url: String,
 }
 
 #[derive(Deserialize)]
 struct OpenAIResponse {
     choices: Vec<OpenAIChoice>,
+    usage: Option<OpenAIUsage>,
+    /// Upstream provider, in OpenRouter responses
+    provider: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    prompt_tokens_details: Option<OpenAIPromptTokensDetails>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIPromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: u64,
 }
 
 #[derive(Deserialize)]
 struct OpenAIChoice {
+    #[serde(default)]
+    index: u32,
     message: OpenAIResponseMessage,
+    finish_reason: Option<String>,
+    logprobs: Option<serde_json::Value>,
 }
 
 #[derive(Deserialize)]
 struct OpenAIResponseMessage {
-    content: String,
+    content: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct AnthropicRequest {
+    model: String,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    system: Vec<AnthropicContentBlock>,
+    messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct AnthropicMessage {
+    role: String,
+    content: Vec<AnthropicContentBlock>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct AnthropicContentBlock {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<AnthropicImageSource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_control: Option<CacheControl>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum AnthropicImageSource {
+    Base64 { media_type: String, data: String },
+    Url { url: String },
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct CacheControl {
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+#[derive(Deserialize)]
+struct AnthropicResponse {
+    content: Vec<AnthropicResponseBlock>,
+    stop_reason: Option<String>,
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Deserialize)]
+struct AnthropicResponseBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct AnthropicUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+    #[serde(default)]
+    cache_creation_input_tokens: u64,
+    #[serde(default)]
+    cache_read_input_tokens: u64,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+/// Whether a provider accepted a stored key, and why not
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyValidation {
+    /// Unknown when the provider could not be reached or failed to answer
+    pub valid: Option<bool>,
+    pub error: Option<String>,
+    pub error_kind: Option<ErrorCategory>,
+}
+
+impl KeyValidation {
+    /// Only missing or rejected credentials make a key invalid
+    fn from_check<T>(check: Result<T, AppError>) -> Self {
+        match check {
+            Ok(_) => KeyValidation {
+                valid: Some(true),
+                error: None,
+                error_kind: None,
+            },
+            Err(e) => {
+                let kind = e.category();
+                KeyValidation {
+                    valid: (kind == ErrorCategory::Auth).then_some(false),
+                    error: Some(e.to_string()),
+                    error_kind: Some(kind),
+                }
+            }
+        }
+    }
+}
+
+/// Transcript of an audio file
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Transcription {
+    pub text: String,
+    /// Language spoken, as detected or given
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Length of the audio in seconds
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
 }
 
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
+/// The shared HTTP client for AI requests
+pub fn ai_http_client() -> reqwest::Client {
+    if let Some(client) = AI_HTTP_CLIENT.read().ok().and_then(|client| client.clone()) {
+        return client;
+    }
+    // Requested before `init_ai_http_client`: the system proxy, no timeout
+    let client = reqwest::Client::new();
+    if let Ok(mut current) = AI_HTTP_CLIENT.write() {
+        current.get_or_insert_with(|| client.clone());
+    }
+    client
+}
+
+fn build_ai_http_client(app: &tauri::AppHandle) -> Result<reqwest::Client, AppError> {
+    let timeout = Duration::from_secs(setting(app, REQUEST_TIMEOUT_SETTING)?);
+    let mode: String = setting(app, PROXY_MODE_SETTING)?;
+    let url: Option<String> = setting(app, PROXY_URL_SETTING)?;
+    apply_proxy(
+        reqwest::Client::builder().timeout(timeout),
+        &mode,
+        url.as_deref(),
+    )?
+    .build()
+    .map_err(AppError::from)
+}
+
+fn load_ai_http_client(app: &tauri::AppHandle) {
+    let client = build_ai_http_client(app).unwrap_or_else(|e| {
+        log::error!("Failed to apply the network settings to AI requests: {}", e);
+        reqwest::Client::new()
+    });
+    if let Ok(mut current) = AI_HTTP_CLIENT.write() {
+        *current = Some(client);
+    }
+}
+
+/// Build the client from the network settings, and rebuild it whenever they
+/// change
+pub fn init_ai_http_client(app: &tauri::AppHandle) {
+    load_ai_http_client(app);
+    let handle = app.clone();
+    app.listen_any(SETTING_CHANGED_EVENT, move |event| {
+        let key = serde_json::from_str::<serde_json::Value>(event.payload())
+            .ok()
+            .and_then(|change| change["key"].as_str().map(str::to_string));
+        if matches!(
+            key.as_deref(),
+            Some(PROXY_MODE_SETTING | PROXY_URL_SETTING | REQUEST_TIMEOUT_SETTING)
+        ) {
+            log::info!("Network settings changed, rebuilding the AI HTTP client");
+            load_ai_http_client(&handle);
+        }
+    });
+}
+
 /// Get the API endpoint for a provider
 pub fn get_provider_endpoint(provider: &str) -> &'static str {
     match provider {
@@ -62,26 +552,805 @@ pub fn get_provider_endpoint(provider: &str) -> &'static str {
     }
 }
 
-// ============================================================================
-// Commands
-// ============================================================================
+/// Get the API base URL of a known provider, without a trailing slash
+pub fn get_provider_base_url(provider: &str) -> Option<&'static str> {
+    match provider {
+        "openai" => Some("https://api.openai.com/v1"),
+        "anthropic" => Some("https://api.anthropic.com/v1"),
+        "deepseek" => Some("https://api.deepseek.com/v1"),
+        "groq" => Some("https://api.groq.com/openai/v1"),
+        "openrouter" => Some("https://openrouter.ai/api/v1"),
+        _ => None,
+    }
+}
 
-/// Proxy AI request through the Rust backend
-#[tauri::command]
-pub async fn proxy_ai_request(
-    provider: String,
-    model: String,
+/// Whether `url` is `base` or under it, once both are normalized
+fn is_under_base(base: &url::Url, url: &url::Url) -> bool {
+    let base_path = base.path().trim_end_matches('/');
+    url.origin() == base.origin()
+        && (url.path() == base_path || url.path().starts_with(&format!("{}/", base_path)))
+}
+
+/// URL of an API path of a provider; the path must stay under its base URL,
+/// encoded dot segments included
+pub fn raw_request_url(provider: &str, path: &str) -> Result<String, AppError> {
+    let base = match find_custom_provider(provider) {
+        Some(custom) => custom.base_url,
+        None => get_provider_base_url(provider)
+            .ok_or_else(|| AppError::InvalidInput(format!("Unknown provider: {}", provider)))?
+            .to_string(),
+    };
+    let route = path.split(['?', '#']).next().unwrap_or_default();
+    let valid = path.starts_with('/')
+        && !path.starts_with("//")
+        && !route
+            .split('/')
+            .any(|segment| segment == ".." || segment == ".")
+        && !path.contains('\\')
+        && !path.chars().any(char::is_whitespace);
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid API path: {}",
+            path
+        )));
+    }
+    let invalid_base = |e: url::ParseError| {
+        AppError::InvalidInput(format!("Invalid base URL of {}: {}", provider, e))
+    };
+    let base_url = url::Url::parse(&base).map_err(invalid_base)?;
+    let url = url::Url::parse(&format!("{}{}", base, path)).map_err(invalid_base)?;
+    if !is_under_base(&base_url, &url) {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid API path: {}",
+            path
+        )));
+    }
+    Ok(url.into())
+}
+
+/// Read the API key of a provider's active key profile from the keyring
+fn provider_api_key(provider: &str) -> Result<String, AppError> {
+    get_api_key(provider.to_string())?
+        .ok_or_else(|| AppError::PermissionDenied(format!("No API key found for {}", provider)))
+}
+
+/// Add the credentials of a provider to a request: its key, and the
+/// organization and project headers it takes, if saved; then its default
+/// headers
+fn authorize(
+    request: reqwest::RequestBuilder,
+    provider: &str,
+    api_key: &str,
+) -> reqwest::RequestBuilder {
+    let mut request = if provider == "anthropic" {
+        request
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+    } else if provider == AZURE_PROVIDER {
+        request.header("api-key", api_key)
+    } else {
+        request.header("Authorization", format!("Bearer {}", api_key))
+    };
+    let mut set = Vec::new();
+    match get_provider_tenant(provider.to_string()) {
+        Ok(Some(tenant)) => {
+            for (header, id) in tenant_headers(provider, &tenant) {
+                request = request.header(header, id);
+                set.push(header);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("Failed to read the organization of {}: {}", provider, e),
+    }
+    apply_default_headers(request, provider, &set)
+}
+
+/// Add the stored credentials of a provider to a request; custom providers
+/// may have no key
+fn with_credentials(
+    provider: &str,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::RequestBuilder, AppError> {
+    if let Some(custom) = find_custom_provider(provider) {
+        let api_key = get_api_key(provider.to_string())?;
+        let set: Vec<&str> = custom.headers.keys().map(String::as_str).collect();
+        return Ok(apply_default_headers(
+            custom.authorize(request, api_key.as_deref()),
+            provider,
+            &set,
+        ));
+    }
+    let api_key = provider_api_key(provider)?;
+    Ok(authorize(request, provider, &api_key))
+}
+
+/// Prepare a POST to a URL of a provider with its credentials
+pub fn provider_request(provider: &str, url: &str) -> Result<reqwest::RequestBuilder, AppError> {
+    with_credentials(provider, ai_http_client().post(url))
+}
+
+/// URL listing the models of a provider, which any valid key may read
+fn models_url(provider: &str) -> Result<String, AppError> {
+    if provider == AZURE_PROVIDER {
+        return Ok(get_azure_deployment(provider.to_string())?
+            .ok_or_else(|| {
+                AppError::InvalidInput(format!("No Azure deployment saved for {}", provider))
+            })?
+            .models_url());
+    }
+    raw_request_url(provider, "/models")
+}
+
+/// Chat completions URL of an OpenAI-compatible provider: a custom one's,
+/// the saved Azure deployment's, or a built-in one's
+fn chat_endpoint(provider: &str) -> Result<String, AppError> {
+    if let Some(custom) = find_custom_provider(provider) {
+        return Ok(custom.chat_url());
+    }
+    if provider == AZURE_PROVIDER {
+        return Ok(get_azure_deployment(provider.to_string())?
+            .ok_or_else(|| {
+                AppError::InvalidInput(format!("No Azure deployment saved for {}", provider))
+            })?
+            .chat_url());
+    }
+    Ok(get_provider_endpoint(provider).to_string())
+}
+
+/// Get the embeddings endpoint for a provider, if it offers one
+pub fn get_embeddings_endpoint(provider: &str) -> Option<&'static str> {
+    match provider {
+        "openai" => Some("https://api.openai.com/v1/embeddings"),
+        "openrouter" => Some("https://openrouter.ai/api/v1/embeddings"),
+        _ => None,
+    }
+}
+
+/// Embed a batch of texts, returning one vector per input in input order
+pub async fn request_embeddings(
+    provider: &str,
+    model: &str,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>, AppError> {
+    let endpoint = get_embeddings_endpoint(provider).ok_or_else(|| {
+        AppError::InvalidInput(format!("Provider {} does not offer embeddings", provider))
+    })?;
+    if inputs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let api_key = provider_api_key(provider)?;
+
+    let request = EmbeddingRequest {
+        model,
+        input: inputs,
+    };
+    let mut body: EmbeddingResponse = with_retry("Embeddings request", || {
+        post_json(
+            provider,
+            "Embeddings request",
+            authorize(ai_http_client().post(endpoint), provider, &api_key),
+            &request,
+        )
+    })
+    .await?;
+    if body.data.len() != inputs.len() {
+        return Err(AppError::InvalidOutput(format!(
+            "Expected {} embeddings, got {}",
+            inputs.len(),
+            body.data.len()
+        )));
+    }
+    body.data.sort_by_key(|d| d.index);
+    Ok(body.data.into_iter().map(|d| d.embedding).collect())
+}
+
+/// Get the transcription endpoint and default model of a provider, if it
+/// offers speech to text
+pub fn get_transcription_endpoint(provider: &str) -> Option<(&'static str, &'static str)> {
+    match provider {
+        "openai" => Some((
+            "https://api.openai.com/v1/audio/transcriptions",
+            "whisper-1",
+        )),
+        "groq" => Some((
+            "https://api.groq.com/openai/v1/audio/transcriptions",
+            "whisper-large-v3-turbo",
+        )),
+        _ => None,
+    }
+}
+
+/// Media type of an audio file Whisper endpoints accept, by its extension
+fn audio_media_type(path: &Path) -> Result<&'static str, AppError> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    AUDIO_MEDIA_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, media_type)| *media_type)
+        .ok_or_else(|| {
+            AppError::InvalidInput(format!("Unsupported audio file: {}", path.display()))
+        })
+}
+
+/// Body of a `multipart/form-data` request with text fields and one file
+fn multipart_form(
+    boundary: &str,
+    fields: &[(&str, &str)],
+    file_name: &str,
+    media_type: &str,
+    file: &[u8],
+) -> Vec<u8> {
+    let mut body = Vec::with_capacity(file.len() + 1024);
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                boundary, name, value
+            )
+            .as_bytes(),
+        );
+    }
+    let file_name = file_name.replace(['"', '\r', '\n'], "_");
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+            boundary, file_name, media_type
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(file);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+/// Transcribe an audio file with the Whisper endpoint of a provider
+pub async fn request_transcription(
+    provider: &str,
+    model: Option<&str>,
+    path: &Path,
+    language: Option<&str>,
+    prompt: Option<&str>,
+) -> Result<Transcription, AppError> {
+    let (endpoint, default_model) = get_transcription_endpoint(provider).ok_or_else(|| {
+        AppError::InvalidInput(format!(
+            "Provider {} does not offer transcription",
+            provider
+        ))
+    })?;
+    let media_type = audio_media_type(path)?;
+    let size = tokio::fs::metadata(path).await?.len();
+    if size > MAX_AUDIO_BYTES {
+        return Err(AppError::InvalidInput(format!(
+            "Audio files are limited to {} MB",
+            MAX_AUDIO_BYTES / (1024 * 1024)
+        )));
+    }
+    let audio = tokio::fs::read(path).await?;
+    let api_key = provider_api_key(provider)?;
+
+    let model = model.unwrap_or(default_model);
+    let mut fields = vec![("model", model), ("response_format", "verbose_json")];
+    fields.extend(language.map(|language| ("language", language)));
+    fields.extend(prompt.map(|prompt| ("prompt", prompt)));
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let boundary = format!("readium-{}", uuid::Uuid::new_v4().simple());
+    let body = multipart_form(&boundary, &fields, &file_name, media_type, &audio);
+    let logged = || {
+        let mut request: serde_json::Map<String, serde_json::Value> = fields
+            .iter()
+            .map(|(name, value)| (name.to_string(), (*value).into()))
+            .collect();
+        request.insert(
+            "file".to_string(),
+            serde_json::json!({ "name": file_name, "bytes": audio.len() }),
+        );
+        serde_json::Value::Object(request)
+    };
+    with_retry("Transcription request", || {
+        let builder = authorize(ai_http_client().post(endpoint), provider, &api_key)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(body.clone());
+        post_request(provider, "Transcription request", builder, logged)
+    })
+    .await
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        MessageContent::Text(text)
+    }
+}
+
+impl MessageContent {
+    /// Text of the message, its text parts joined by new lines
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    ContentPart::Image(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    fn images(&self) -> impl Iterator<Item = &ImagePart> {
+        let parts = match self {
+            MessageContent::Text(_) => &[][..],
+            MessageContent::Parts(parts) => parts.as_slice(),
+        };
+        parts.iter().filter_map(|part| match part {
+            ContentPart::Image(image) => Some(image),
+            ContentPart::Text { .. } => None,
+        })
+    }
+}
+
+impl ImagePart {
+    /// Check the image and tell where it comes from; `data:` URLs count as
+    /// base64 data
+    fn source(&self) -> Result<ImageSource, AppError> {
+        let invalid = |reason: &str| AppError::InvalidInput(format!("Invalid image: {}", reason));
+        let (media_type, data) = match (&self.url, &self.data) {
+            (Some(url), None) => match url.strip_prefix("data:") {
+                Some(inline) => {
+                    let (media_type, data) = inline
+                        .split_once(";base64,")
+                        .ok_or_else(|| invalid("data URLs must be base64"))?;
+                    (media_type.to_string(), data.to_string())
+                }
+                None if url.starts_with("https://") || url.starts_with("http://") => {
+                    return Ok(ImageSource::Url(url.clone()));
+                }
+                None => return Err(invalid("only http(s) and data URLs are supported")),
+            },
+            (None, Some(data)) => (
+                self.media_type.clone().unwrap_or_default(),
+                data.trim().to_string(),
+            ),
+            _ => return Err(invalid("give either a URL or base64 data")),
+        };
+        if !IMAGE_MEDIA_TYPES.contains(&media_type.as_str()) {
+            return Err(invalid(&format!("unsupported type '{}'", media_type)));
+        }
+        if data.is_empty() {
+            return Err(invalid("no data"));
+        }
+        Ok(ImageSource::Base64 { media_type, data })
+    }
+}
+
+/// Check that images are only in user messages and can be sent
+fn check_message_content(messages: &[AIMessage]) -> Result<(), AppError> {
+    for message in messages {
+        let mut images = message.content.images().peekable();
+        if images.peek().is_some() && message.role != "user" {
+            return Err(AppError::InvalidInput(format!(
+                "Images can only be sent in user messages, not {} messages",
+                message.role
+            )));
+        }
+        for image in images {
+            image.source()?;
+        }
+    }
+    Ok(())
+}
+
+/// Map message content to the OpenAI format; images were checked by
+/// `check_message_content`
+fn openai_content(content: MessageContent) -> OpenAIContent {
+    let parts = match content {
+        MessageContent::Text(text) => return OpenAIContent::Text(text),
+        MessageContent::Parts(parts) => parts,
+    };
+    OpenAIContent::Parts(
+        parts
+            .into_iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(OpenAIContentPart::Text { text }),
+                ContentPart::Image(image) => {
+                    let url = match image.source().ok()? {
+                        ImageSource::Url(url) => url,
+                        ImageSource::Base64 { media_type, data } => {
+                            format!("data:{};base64,{}", media_type, data)
+                        }
+                    };
+                    Some(OpenAIContentPart::ImageUrl {
+                        image_url: OpenAIImageUrl { url },
+                    })
+                }
+            })
+            .collect(),
+    )
+}
+
+fn text_block(text: String, cache: bool) -> AnthropicContentBlock {
+    AnthropicContentBlock {
+        kind: "text",
+        text: Some(text),
+        source: None,
+        cache_control: cache.then_some(CacheControl { kind: "ephemeral" }),
+    }
+}
+
+/// Map message content to Messages API blocks, the cache breakpoint on the
+/// last one; images were checked by `check_message_content`
+fn anthropic_blocks(content: MessageContent, cache: bool) -> Vec<AnthropicContentBlock> {
+    let parts = match content {
+        MessageContent::Text(text) => return vec![text_block(text, cache)],
+        MessageContent::Parts(parts) => parts,
+    };
+    let mut blocks: Vec<AnthropicContentBlock> = parts
+        .into_iter()
+        .filter_map(|part| match part {
+            ContentPart::Text { text } => Some(text_block(text, false)),
+            ContentPart::Image(image) => Some(AnthropicContentBlock {
+                kind: "image",
+                text: None,
+                source: Some(match image.source().ok()? {
+                    ImageSource::Url(url) => AnthropicImageSource::Url { url },
+                    ImageSource::Base64 { media_type, data } => {
+                        AnthropicImageSource::Base64 { media_type, data }
+                    }
+                }),
+                cache_control: None,
+            }),
+        })
+        .collect();
+    if let Some(last) = blocks.last_mut() {
+        last.cache_control = cache.then_some(CacheControl { kind: "ephemeral" });
+    }
+    blocks
+}
+
+/// Build a Messages API request; system messages join the system prompt, and
+/// cache breakpoints go on the system prompt and the last flagged messages
+fn build_anthropic_request(
+    model: &str,
     messages: Vec<AIMessage>,
     system_prompt: Option<String>,
-) -> Result<String, AppError> {
-    // Get API key from secure storage
-    let entry = keyring::Entry::new(KEYRING_SERVICE, &provider)
-        .map_err(|e| AppError::Keyring(e.to_string()))?;
-    let api_key = entry
-        .get_password()
-        .map_err(|e| AppError::Keyring(format!("No API key found for {}: {}", provider, e)))?;
+    options: &ChatOptions,
+) -> AnthropicRequest {
+    let mut system = Vec::new();
+    if let Some(text) = system_prompt {
+        system.push(text_block(text, options.cache_system_prompt));
+    }
+    let (system_messages, messages): (Vec<AIMessage>, Vec<AIMessage>) = messages
+        .into_iter()
+        .partition(|message| message.role == "system");
+    for message in system_messages {
+        system.push(text_block(message.content.text(), message.cache));
+    }
+
+    let mut breakpoints = system
+        .iter()
+        .filter(|block| block.cache_control.is_some())
+        .count();
+    let mut cached: Vec<bool> = vec![false; messages.len()];
+    for (index, message) in messages.iter().enumerate().rev() {
+        if message.cache && breakpoints < MAX_CACHE_BREAKPOINTS {
+            cached[index] = true;
+            breakpoints += 1;
+        }
+    }
+    AnthropicRequest {
+        model: model.to_string(),
+        max_tokens: options.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        system,
+        messages: messages
+            .into_iter()
+            .zip(cached)
+            .map(|(message, cache)| AnthropicMessage {
+                role: message.role,
+                content: anthropic_blocks(message.content, cache),
+            })
+            .collect(),
+        temperature: options.temperature,
+        top_p: options.top_p,
+        stop_sequences: options.stop.clone(),
+    }
+}
+
+/// Check the sampling parameters against the ranges the provider accepts
+fn check_sampling_options(provider: &str, options: &ChatOptions) -> Result<(), AppError> {
+    let anthropic = provider == "anthropic";
+    let max_temperature = if anthropic { 1.0 } else { MAX_TEMPERATURE };
+    if let Some(temperature) = options.temperature {
+        if !(0.0..=max_temperature).contains(&temperature) {
+            return Err(AppError::InvalidInput(format!(
+                "Temperature must be 0 to {} for {}",
+                max_temperature, provider
+            )));
+        }
+    }
+    if options
+        .top_p
+        .is_some_and(|top_p| !(0.0..=1.0).contains(&top_p))
+    {
+        return Err(AppError::InvalidInput("Top p must be 0 to 1".to_string()));
+    }
+    if options.max_tokens == Some(0) {
+        return Err(AppError::InvalidInput(
+            "The reply must be allowed at least one token".to_string(),
+        ));
+    }
+    let penalties = [options.frequency_penalty, options.presence_penalty];
+    if anthropic && penalties.iter().any(Option::is_some) {
+        return Err(AppError::InvalidInput(
+            "Anthropic does not support frequency or presence penalties".to_string(),
+        ));
+    }
+    if penalties
+        .iter()
+        .flatten()
+        .any(|penalty| !(-MAX_PENALTY..=MAX_PENALTY).contains(penalty))
+    {
+        return Err(AppError::InvalidInput(format!(
+            "Penalties must be -{} to {}",
+            MAX_PENALTY, MAX_PENALTY
+        )));
+    }
+    Ok(())
+}
+
+/// Check the stop sequences, choice count, log probabilities and response
+/// schema against what the provider supports
+pub fn check_completion_options(provider: &str, options: &ChatOptions) -> Result<(), AppError> {
+    check_sampling_options(provider, options)?;
+    if options.stop.iter().any(|stop| stop.is_empty()) {
+        return Err(AppError::InvalidInput(
+            "Stop sequences must not be empty".to_string(),
+        ));
+    }
+    if let Some(n) = options.n {
+        if !(1..=MAX_CHOICES).contains(&n) {
+            return Err(AppError::InvalidInput(format!(
+                "The number of choices must be 1 to {}",
+                MAX_CHOICES
+            )));
+        }
+    }
+    if let Some(top) = options.top_logprobs {
+        if !options.logprobs || top > MAX_TOP_LOGPROBS {
+            return Err(AppError::InvalidInput(format!(
+                "Top log probabilities need logprobs and must be at most {}",
+                MAX_TOP_LOGPROBS
+            )));
+        }
+    }
+    if let Some(format) = &options.json_schema {
+        check_schema_format(format)?;
+    }
+    if provider == "anthropic" {
+        if options.json_schema.is_some() {
+            return Err(AppError::InvalidInput(
+                "Anthropic does not support JSON schema output".to_string(),
+            ));
+        }
+        if options.n.is_some_and(|n| n > 1) || options.logprobs {
+            return Err(AppError::InvalidInput(
+                "Anthropic does not support multiple choices or log probabilities".to_string(),
+            ));
+        }
+    } else if options.stop.len() > MAX_STOP_SEQUENCES {
+        return Err(AppError::InvalidInput(format!(
+            "At most {} stop sequences are supported",
+            MAX_STOP_SEQUENCES
+        )));
+    }
+    Ok(())
+}
+
+/// Check routing preferences and convert them for an OpenRouter request
+fn openrouter_provider(
+    provider: &str,
+    routing: Option<&OpenRouterRouting>,
+) -> Result<Option<OpenRouterProvider>, AppError> {
+    let Some(routing) = routing else {
+        return Ok(None);
+    };
+    if provider != "openrouter" {
+        return Err(AppError::InvalidInput(format!(
+            "Routing preferences are not supported by {}",
+            provider
+        )));
+    }
+    if let Some(data_collection) = &routing.data_collection {
+        if !matches!(data_collection.as_str(), "allow" | "deny") {
+            return Err(AppError::InvalidInput(format!(
+                "Data collection must be allow or deny, not {}",
+                data_collection
+            )));
+        }
+    }
+    Ok(Some(OpenRouterProvider {
+        order: routing
+            .order
+            .iter()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect(),
+        allow_fallbacks: routing.allow_fallbacks,
+        data_collection: routing.data_collection.clone(),
+    }))
+}
+
+fn anthropic_token_usage(usage: &AnthropicUsage) -> TokenUsage {
+    TokenUsage {
+        input_tokens: usage.input_tokens
+            + usage.cache_creation_input_tokens
+            + usage.cache_read_input_tokens,
+        output_tokens: usage.output_tokens,
+        cached_tokens: usage.cache_read_input_tokens,
+        cache_write_tokens: usage.cache_creation_input_tokens,
+    }
+}
+
+fn openai_token_usage(usage: &OpenAIUsage) -> TokenUsage {
+    TokenUsage {
+        input_tokens: usage.prompt_tokens,
+        output_tokens: usage.completion_tokens,
+        cached_tokens: usage
+            .prompt_tokens_details
+            .as_ref()
+            .map_or(0, |details| details.cached_tokens),
+        cache_write_tokens: 0,
+    }
+}
+
+/// POST a JSON body to a provider and parse the JSON reply, failing on an
+/// error status with the body the provider sent; `what` names the request
+/// in errors. The exchange is recorded in the AI debug log when it is on.
+pub async fn post_json<B: Serialize, T: DeserializeOwned>(
+    provider: &str,
+    what: &str,
+    builder: reqwest::RequestBuilder,
+    body: &B,
+) -> Result<T, AppError> {
+    post_request(provider, what, builder.json(body), || {
+        serde_json::to_value(body).unwrap_or_default()
+    })
+    .await
+}
 
-    let endpoint = get_provider_endpoint(&provider);
+/// Send a prepared request to a provider and parse the JSON reply, as
+/// `post_json` does; `logged` gives the request as the debug log shows it
+async fn post_request<T: DeserializeOwned>(
+    provider: &str,
+    what: &str,
+    builder: reqwest::RequestBuilder,
+    logged: impl FnOnce() -> serde_json::Value,
+) -> Result<T, AppError> {
+    let (client, request) = builder.build_split();
+    let request = request?;
+    let url = request.url().to_string();
+    let started = Instant::now();
+    let outcome = match client.execute(request).await {
+        Ok(response) => {
+            let status = response.status();
+            response.text().await.map(|text| (status, text))
+        }
+        Err(e) => Err(e),
+    };
+    let result = match &outcome {
+        Ok((status, text)) if status.is_success() => serde_json::from_str(text).map_err(|e| {
+            AppError::http_status(*status, format!("Failed to parse response: {}", e))
+        }),
+        Ok((status, text)) => Err(AppError::http_status(
+            *status,
+            format!("{} failed with status {}: {}", what, status, text),
+        )),
+        Err(e) => Err(AppError::http(e.to_string())),
+    };
+    if ai_debug_log_enabled() {
+        let error = result.as_ref().err().map(AppError::to_string);
+        record_ai_exchange(AIExchange {
+            provider,
+            url: &url,
+            request: &logged(),
+            status: outcome.as_ref().ok().map(|(status, _)| status.as_u16()),
+            response: outcome.as_ref().ok().map(|(_, text)| text.as_str()),
+            error: error.as_deref(),
+            duration: started.elapsed(),
+        });
+    }
+    result
+}
+
+async fn send_anthropic_request(
+    api_key: &str,
+    request: &AnthropicRequest,
+) -> Result<ChatReply, AppError> {
+    let builder = authorize(
+        ai_http_client().post(get_provider_endpoint("anthropic")),
+        "anthropic",
+        api_key,
+    );
+    let body: AnthropicResponse = post_json("anthropic", "API request", builder, request).await?;
+    let content: String = body
+        .content
+        .into_iter()
+        .filter(|block| block.kind == "text")
+        .map(|block| block.text)
+        .collect();
+    Ok(ChatReply {
+        choices: vec![ChatChoice {
+            index: 0,
+            content: content.clone(),
+            finish_reason: body.stop_reason,
+            logprobs: None,
+        }],
+        content,
+        usage: body.usage.as_ref().map(anthropic_token_usage),
+        served_by: None,
+        ..Default::default()
+    })
+}
+
+/// Send a chat request in the OpenAI format to a prepared endpoint
+async fn send_openai_compatible(
+    provider: &str,
+    builder: reqwest::RequestBuilder,
+    request: &OpenAIRequest,
+) -> Result<ChatReply, AppError> {
+    let body: OpenAIResponse = post_json(provider, "API request", builder, request).await?;
+    let choices: Vec<ChatChoice> = body
+        .choices
+        .into_iter()
+        .map(|choice| ChatChoice {
+            index: choice.index,
+            content: choice.message.content.unwrap_or_default(),
+            finish_reason: choice.finish_reason,
+            logprobs: choice.logprobs,
+        })
+        .collect();
+    Ok(ChatReply {
+        content: choices
+            .first()
+            .map(|choice| choice.content.clone())
+            .unwrap_or_default(),
+        choices,
+        usage: body.usage.as_ref().map(openai_token_usage),
+        served_by: body.provider,
+        ..Default::default()
+    })
+}
+
+/// Send a chat request to one provider
+async fn send_to_provider(
+    provider: &str,
+    model: &str,
+    messages: Vec<AIMessage>,
+    system_prompt: Option<String>,
+    options: &ChatOptions,
+) -> Result<ChatReply, AppError> {
+    check_completion_options(provider, options)?;
+    check_message_content(&messages)?;
+    let routing = openrouter_provider(provider, options.routing.as_ref())?;
+
+    if provider == "anthropic" {
+        let api_key = provider_api_key(provider)?;
+        let request = build_anthropic_request(model, messages, system_prompt, options);
+        return with_retry("Chat request", || {
+            send_anthropic_request(&api_key, &request)
+        })
+        .await;
+    }
 
     // Build messages array
     let mut openai_messages: Vec<OpenAIMessage> = Vec::new();
@@ -90,7 +1359,7 @@ pub async fn proxy_ai_request(
     if let Some(system) = system_prompt {
         openai_messages.push(OpenAIMessage {
             role: "system".to_string(),
-            content: system,
+            content: OpenAIContent::Text(system),
         });
     }
 
@@ -98,49 +1367,338 @@ pub async fn proxy_ai_request(
     for msg in messages {
         openai_messages.push(OpenAIMessage {
             role: msg.role,
-            content: msg.content,
+            content: openai_content(msg.content),
         });
     }
 
     let request_body = OpenAIRequest {
-        model,
+        model: model.to_string(),
         messages: openai_messages,
-        max_tokens: Some(4096),
-        temperature: Some(0.7),
+        max_tokens: options.max_tokens,
+        temperature: options.temperature,
+        top_p: options.top_p,
+        frequency_penalty: options.frequency_penalty,
+        presence_penalty: options.presence_penalty,
+        stop: options.stop.clone(),
+        n: options.n,
+        logprobs: options.logprobs,
+        top_logprobs: options.top_logprobs,
+        response_format: options
+            .json_schema
+            .clone()
+            .map(|json_schema| OpenAIResponseFormat {
+                kind: "json_schema",
+                json_schema,
+            }),
+        provider: routing,
     };
 
-    // Make HTTP request
-    let client = reqwest::Client::new();
-    let response = client
-        .post(endpoint)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
+    // Local models need no key
+    #[cfg(feature = "local-inference")]
+    if provider == LOCAL_PROVIDER {
+        let endpoint = local_chat_endpoint(model).await?;
+        return send_openai_compatible(provider, ai_http_client().post(endpoint), &request_body)
+            .await;
+    }
+
+    let endpoint = chat_endpoint(provider)?;
+    with_retry("Chat request", || async {
+        send_openai_compatible(
+            provider,
+            provider_request(provider, &endpoint)?,
+            &request_body,
+        )
         .await
-        .map_err(|e| AppError::Http(e.to_string()))?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(AppError::Http(format!(
-            "API request failed with status {}: {}",
-            status, error_text
+    })
+    .await
+}
+
+/// Check every choice of a reply against the schema of the request; returns
+/// the first one parsed
+fn structured_reply(
+    format: Option<&JsonSchemaFormat>,
+    reply: &ChatReply,
+) -> Result<Option<serde_json::Value>, AppError> {
+    let Some(format) = format else {
+        return Ok(None);
+    };
+    let mut parsed = reply
+        .choices
+        .iter()
+        .map(|choice| parse_structured_reply(format, &choice.content))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((!parsed.is_empty()).then(|| parsed.swap_remove(0)))
+}
+
+/// Send a chat request and return the reply with its token usage; when the
+/// provider fails, the fallback providers are tried in turn. The request is
+/// moderated first when moderation is on
+pub async fn send_chat_request(
+    provider: &str,
+    model: &str,
+    messages: Vec<AIMessage>,
+    system_prompt: Option<String>,
+    options: &ChatOptions,
+) -> Result<ChatReply, AppError> {
+    let moderation = moderate_messages(&messages).await?;
+    let reply = with_failover(provider, model, |target| {
+        let messages = messages.clone();
+        let system_prompt = system_prompt.clone();
+        let mut options = options.clone();
+        async move {
+            // Routing preferences only apply to OpenRouter
+            if target.provider != provider && target.provider != "openrouter" {
+                options.routing = None;
+            }
+            let reply = send_to_provider(
+                &target.provider,
+                &target.model,
+                messages,
+                system_prompt,
+                &options,
+            )
+            .await?;
+            Ok(ChatReply {
+                provider: target.provider,
+                model: target.model,
+                structured: structured_reply(options.json_schema.as_ref(), &reply)?,
+                ..reply
+            })
+        }
+    })
+    .await?;
+    Ok(ChatReply {
+        moderation,
+        ..reply
+    })
+}
+
+/// Send a chat completion request and return the reply text
+pub async fn request_chat_completion(
+    provider: &str,
+    model: &str,
+    messages: Vec<AIMessage>,
+    system_prompt: Option<String>,
+) -> Result<String, AppError> {
+    let reply = send_chat_request(
+        provider,
+        model,
+        messages,
+        system_prompt,
+        &ChatOptions::default(),
+    )
+    .await?;
+    Ok(reply.content)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// The system prompt to send: the given text, else the preset
+/// `system_prompt_id`, else the default for the document, collection or all
+/// requests
+fn resolve_request_prompt(
+    state: &ConversationState,
+    system_prompt: Option<String>,
+    system_prompt_id: Option<String>,
+    document_id: Option<String>,
+    collection_id: Option<String>,
+) -> Result<Option<String>, AppError> {
+    match system_prompt {
+        Some(text) => Ok(Some(text)),
+        None => {
+            let conn = lock_conversations(state)?;
+            resolve_system_prompt(
+                &conn,
+                system_prompt_id.as_deref(),
+                document_id.as_deref(),
+                collection_id.as_deref(),
+            )
+        }
+    }
+}
+
+/// Add the usage of a proxied reply to the statistics, under the provider
+/// that answered, and note the use of its key
+pub fn record_reply_usage(app: &tauri::AppHandle, provider: &str, reply: &ChatReply) {
+    record_key_use(app, &reply.provider);
+    if reply.provider != provider {
+        log::info!(
+            "{} request answered by fallback {} ({})",
+            provider,
+            reply.provider,
+            reply.model
+        );
+    }
+    if let Some(usage) = &reply.usage {
+        if let Err(e) = record_usage(
+            app,
+            &reply.provider,
+            &reply.model,
+            usage,
+            reply.served_by.as_deref(),
+        ) {
+            log::warn!("Failed to record AI usage: {}", e);
+        }
+    }
+}
+
+/// Proxy AI request through the Rust backend
+///
+/// Without `system_prompt` text, the preset `system_prompt_id` or the default
+/// preset for the document, collection or all requests is sent.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn proxy_ai_request(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, ConversationState>,
+    provider: String,
+    model: String,
+    messages: Vec<AIMessage>,
+    system_prompt: Option<String>,
+    system_prompt_id: Option<String>,
+    document_id: Option<String>,
+    collection_id: Option<String>,
+    options: Option<ChatOptions>,
+) -> Result<String, AppError> {
+    let system_prompt = resolve_request_prompt(
+        &state,
+        system_prompt,
+        system_prompt_id,
+        document_id,
+        collection_id,
+    )?;
+    let options = options.unwrap_or_default();
+    let reply = send_chat_request(&provider, &model, messages, system_prompt, &options).await?;
+    record_reply_usage(&app, &provider, &reply);
+    Ok(reply.content)
+}
+
+/// Like `proxy_ai_request`, returning every choice with its token usage, the
+/// provider and model that answered, and the upstream provider that served it
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn proxy_ai_chat(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, ConversationState>,
+    provider: String,
+    model: String,
+    messages: Vec<AIMessage>,
+    system_prompt: Option<String>,
+    system_prompt_id: Option<String>,
+    document_id: Option<String>,
+    collection_id: Option<String>,
+    options: Option<ChatOptions>,
+) -> Result<ChatReply, AppError> {
+    let system_prompt = resolve_request_prompt(
+        &state,
+        system_prompt,
+        system_prompt_id,
+        document_id,
+        collection_id,
+    )?;
+    let options = options.unwrap_or_default();
+    let reply = send_chat_request(&provider, &model, messages, system_prompt, &options).await?;
+    record_reply_usage(&app, &provider, &reply);
+    if let Some(upstream) = &reply.served_by {
+        log::info!("{} request served by {}", provider, upstream);
+    }
+    Ok(reply)
+}
+
+/// Forward a JSON body to an API path of a provider, such as `/responses`,
+/// with the stored key added; only when the `ai.rawRequests` setting is on
+#[tauri::command]
+pub async fn proxy_ai_request_raw(
+    app: tauri::AppHandle,
+    provider: String,
+    path: String,
+    body: serde_json::Value,
+) -> Result<serde_json::Value, AppError> {
+    if !setting::<bool>(&app, RAW_REQUESTS_SETTING)? {
+        return Err(AppError::PermissionDenied(format!(
+            "Raw AI requests are turned off ({})",
+            RAW_REQUESTS_SETTING
         )));
     }
+    let url = raw_request_url(&provider, &path)?;
+    log::info!("Raw {} request to {}", provider, path);
+    post_json(
+        &provider,
+        "API request",
+        provider_request(&provider, &url)?,
+        &body,
+    )
+    .await
+}
 
-    let response_body: OpenAIResponse = response
-        .json()
+/// Check the stored key of a provider by listing its models, returning why
+/// the provider refused it, if it did
+#[tauri::command]
+pub async fn validate_api_key(
+    app: tauri::AppHandle,
+    provider: String,
+) -> Result<KeyValidation, AppError> {
+    let url = models_url(&provider)?;
+    let check = async {
+        let request = with_credentials(&provider, ai_http_client().get(&url))?;
+        post_request::<serde_json::Value>(&provider, "Key check", request, || {
+            serde_json::Value::Null
+        })
         .await
-        .map_err(|e| AppError::Http(format!("Failed to parse response: {}", e)))?;
+    };
+    let validation = KeyValidation::from_check(check.await);
+    if validation.valid == Some(true) {
+        record_key_use(&app, &provider);
+    }
+    log::info!(
+        "API key of {} checked: {}",
+        provider,
+        match validation.valid {
+            Some(true) => "valid",
+            Some(false) => "invalid",
+            None => "unknown",
+        }
+    );
+    Ok(validation)
+}
 
-    let content = response_body
-        .choices
-        .first()
-        .map(|c| c.message.content.clone())
-        .unwrap_or_default();
+/// Proxy an embeddings request through the Rust backend
+#[tauri::command]
+pub async fn proxy_embeddings_request(
+    provider: String,
+    model: String,
+    input: Vec<String>,
+) -> Result<Vec<Vec<f32>>, AppError> {
+    request_embeddings(&provider, &model, &input).await
+}
 
-    Ok(content)
+/// Transcribe an audio file with OpenAI or Groq Whisper; `language` is an
+/// ISO-639-1 code, detected when unset, and `prompt` text the speech is
+/// likely to contain, such as names from the document
+#[tauri::command]
+pub async fn proxy_transcription_request(
+    provider: String,
+    path: String,
+    model: Option<String>,
+    language: Option<String>,
+    prompt: Option<String>,
+) -> Result<Transcription, AppError> {
+    let transcription = request_transcription(
+        &provider,
+        model.as_deref(),
+        Path::new(&path),
+        language.as_deref(),
+        prompt.as_deref(),
+    )
+    .await?;
+    log::info!(
+        "Transcribed {:.1}s of audio with {}",
+        transcription.duration.unwrap_or_default(),
+        provider
+    );
+    Ok(transcription)
 }
 
 // ============================================================================
@@ -166,4 +1724,398 @@ mod tests {
             "https://api.openai.com/v1/chat/completions"
         );
     }
+
+    fn message(role: &str, content: &str, cache: bool) -> AIMessage {
+        AIMessage {
+            role: role.to_string(),
+            content: content.to_string().into(),
+            cache,
+        }
+    }
+
+    #[test]
+    fn anthropic_requests_mark_cache_breakpoints() {
+        let options = ChatOptions {
+            cache_system_prompt: true,
+            ..Default::default()
+        };
+        let mut messages = vec![message("system", "Book: chapter one", true)];
+        for i in 0..4 {
+            messages.push(message("user", &format!("context {}", i), true));
+            messages.push(message("assistant", "ok", false));
+        }
+        messages.push(message("user", "question", false));
+        let request =
+            build_anthropic_request("claude", messages, Some("Be brief".into()), &options);
+
+        assert_eq!(request.system.len(), 2);
+        assert!(request.system.iter().all(|b| b.cache_control.is_some()));
+        assert_eq!(request.messages.len(), 9);
+        let cached: Vec<usize> = request
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.content[0].cache_control.is_some())
+            .map(|(i, _)| i)
+            .collect();
+        // Two breakpoints are left after the system prompt: the latest ones
+        assert_eq!(cached, [4, 6]);
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["system"][0]["cache_control"]["type"], "ephemeral");
+        assert!(json["messages"][8]["content"][0]
+            .get("cache_control")
+            .is_none());
+
+        let plain = build_anthropic_request(
+            "claude",
+            vec![message("user", "hi", false)],
+            None,
+            &ChatOptions::default(),
+        );
+        assert!(serde_json::to_value(&plain)
+            .unwrap()
+            .get("system")
+            .is_none());
+    }
+
+    #[test]
+    fn token_usage_counts_cached_prompt_tokens() {
+        let usage: AnthropicUsage = serde_json::from_value(serde_json::json!({
+            "input_tokens": 20,
+            "output_tokens": 100,
+            "cache_creation_input_tokens": 0,
+            "cache_read_input_tokens": 3000
+        }))
+        .unwrap();
+        assert_eq!(
+            anthropic_token_usage(&usage),
+            TokenUsage {
+                input_tokens: 3020,
+                output_tokens: 100,
+                cached_tokens: 3000,
+                cache_write_tokens: 0,
+            }
+        );
+        let usage: OpenAIUsage = serde_json::from_value(serde_json::json!({
+            "prompt_tokens": 2048,
+            "completion_tokens": 10,
+            "prompt_tokens_details": {"cached_tokens": 1024}
+        }))
+        .unwrap();
+        assert_eq!(openai_token_usage(&usage).cached_tokens, 1024);
+    }
+
+    #[test]
+    fn routing_preferences_only_for_openrouter() {
+        let routing = OpenRouterRouting {
+            order: vec!["Anthropic".into(), " ".into(), "Together".into()],
+            allow_fallbacks: Some(false),
+            data_collection: Some("deny".into()),
+        };
+        let provider = openrouter_provider("openrouter", Some(&routing))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&provider).unwrap(),
+            serde_json::json!({
+                "order": ["Anthropic", "Together"],
+                "allow_fallbacks": false,
+                "data_collection": "deny"
+            })
+        );
+        assert!(openrouter_provider("openai", Some(&routing)).is_err());
+        assert!(openrouter_provider("openai", None).unwrap().is_none());
+        let invalid = OpenRouterRouting {
+            data_collection: Some("never".into()),
+            ..Default::default()
+        };
+        assert!(openrouter_provider("openrouter", Some(&invalid)).is_err());
+
+        let body: OpenAIResponse = serde_json::from_value(serde_json::json!({
+            "provider": "Together",
+            "choices": [{"message": {"content": "hi"}}]
+        }))
+        .unwrap();
+        assert_eq!(body.provider.as_deref(), Some("Together"));
+    }
+
+    #[test]
+    fn raw_request_paths_stay_under_the_provider_api() {
+        assert_eq!(
+            raw_request_url("openai", "/responses").unwrap(),
+            "https://api.openai.com/v1/responses"
+        );
+        assert_eq!(
+            raw_request_url("anthropic", "/messages/count_tokens?beta=true").unwrap(),
+            "https://api.anthropic.com/v1/messages/count_tokens?beta=true"
+        );
+        for path in [
+            "responses",
+            "//evil.example/x",
+            "/../admin",
+            "/a/./b",
+            "/a b",
+            "/%2e%2e/admin",
+            "/%2E%2e/%2e%2E/x",
+            "/.%2e/admin",
+        ] {
+            assert!(raw_request_url("openai", path).is_err(), "{}", path);
+        }
+        assert!(raw_request_url("unknown", "/chat/completions").is_err());
+        assert_eq!(
+            models_url("groq").unwrap(),
+            "https://api.groq.com/openai/v1/models"
+        );
+        assert!(models_url("local").is_err());
+    }
+
+    #[test]
+    fn completion_options_are_checked_per_provider() {
+        let options = ChatOptions {
+            stop: vec!["\n\n".into()],
+            n: Some(3),
+            logprobs: true,
+            top_logprobs: Some(2),
+            ..Default::default()
+        };
+        assert!(check_completion_options("openai", &options).is_ok());
+        assert!(check_completion_options("anthropic", &options).is_err());
+        let stop_only = ChatOptions {
+            stop: vec!["END".into(); 5],
+            ..Default::default()
+        };
+        assert!(check_completion_options("anthropic", &stop_only).is_ok());
+        assert!(check_completion_options("openai", &stop_only).is_err());
+        let no_logprobs = ChatOptions {
+            top_logprobs: Some(2),
+            ..Default::default()
+        };
+        assert!(check_completion_options("openai", &no_logprobs).is_err());
+        let too_many = ChatOptions {
+            n: Some(MAX_CHOICES + 1),
+            ..Default::default()
+        };
+        assert!(check_completion_options("openai", &too_many).is_err());
+        let structured = ChatOptions {
+            json_schema: Some(JsonSchemaFormat {
+                name: "answer".into(),
+                description: None,
+                schema: serde_json::json!({"type": "object"}),
+                strict: Some(true),
+            }),
+            ..Default::default()
+        };
+        assert!(check_completion_options("openai", &structured).is_ok());
+        assert!(check_completion_options("anthropic", &structured).is_err());
+
+        let body: OpenAIResponse = serde_json::from_value(serde_json::json!({
+            "choices": [
+                {"index": 0, "message": {"content": "A"}, "finish_reason": "stop"},
+                {"index": 1, "message": {"content": null}, "finish_reason": "length",
+                 "logprobs": {"content": []}}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(body.choices.len(), 2);
+        assert_eq!(body.choices[1].message.content, None);
+        assert!(body.choices[1].logprobs.is_some());
+    }
+
+    #[test]
+    fn sampling_options_are_sent_only_when_set() {
+        let plain = build_anthropic_request(
+            "claude",
+            vec![message("user", "hi", false)],
+            None,
+            &ChatOptions::default(),
+        );
+        let json = serde_json::to_value(&plain).unwrap();
+        assert_eq!(json["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert!(json.get("temperature").is_none() && json.get("top_p").is_none());
+
+        let options = ChatOptions {
+            temperature: Some(0.25),
+            top_p: Some(0.5),
+            max_tokens: Some(200),
+            ..Default::default()
+        };
+        let request =
+            build_anthropic_request("claude", vec![message("user", "hi", false)], None, &options);
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            (&json["max_tokens"], &json["temperature"], &json["top_p"]),
+            (&200.into(), &0.25.into(), &0.5.into())
+        );
+        assert!(check_completion_options("anthropic", &options).is_ok());
+
+        let hot = ChatOptions {
+            temperature: Some(1.5),
+            frequency_penalty: Some(-1.0),
+            presence_penalty: Some(0.5),
+            ..Default::default()
+        };
+        assert!(check_completion_options("openai", &hot).is_ok());
+        assert!(check_completion_options("anthropic", &hot).is_err());
+        let invalid = [
+            ChatOptions {
+                temperature: Some(2.5),
+                ..Default::default()
+            },
+            ChatOptions {
+                top_p: Some(1.5),
+                ..Default::default()
+            },
+            ChatOptions {
+                max_tokens: Some(0),
+                ..Default::default()
+            },
+            ChatOptions {
+                presence_penalty: Some(-3.0),
+                ..Default::default()
+            },
+        ];
+        for options in &invalid {
+            assert!(check_completion_options("openai", options).is_err());
+        }
+    }
+
+    #[test]
+    fn get_embeddings_endpoint_only_for_supporting_providers() {
+        assert_eq!(
+            get_embeddings_endpoint("openai"),
+            Some("https://api.openai.com/v1/embeddings")
+        );
+        assert_eq!(get_embeddings_endpoint("anthropic"), None);
+    }
+
+    #[test]
+    fn transcription_uploads_audio_as_multipart() {
+        assert_eq!(
+            get_transcription_endpoint("groq"),
+            Some((
+                "https://api.groq.com/openai/v1/audio/transcriptions",
+                "whisper-large-v3-turbo"
+            ))
+        );
+        assert_eq!(get_transcription_endpoint("anthropic"), None);
+        assert_eq!(
+            audio_media_type(Path::new("/tmp/Note.M4A")).unwrap(),
+            "audio/mp4"
+        );
+        assert!(audio_media_type(Path::new("/tmp/notes.txt")).is_err());
+        assert!(audio_media_type(Path::new("/tmp/recording")).is_err());
+
+        let body = multipart_form(
+            "b0",
+            &[("model", "whisper-1"), ("language", "zh")],
+            "a\"b.wav",
+            "audio/wav",
+            b"RIFF",
+        );
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "--b0\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n\
+             --b0\r\nContent-Disposition: form-data; name=\"language\"\r\n\r\nzh\r\n\
+             --b0\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a_b.wav\"\r\n\
+             Content-Type: audio/wav\r\n\r\nRIFF\r\n--b0--\r\n"
+        );
+    }
+
+    #[test]
+    fn only_rejected_keys_are_invalid() {
+        let check = |e: AppError| KeyValidation::from_check::<()>(Err(e));
+        assert_eq!(KeyValidation::from_check(Ok(())).valid, Some(true));
+        let rejected = check(AppError::http_status(
+            reqwest::StatusCode::UNAUTHORIZED,
+            "Key check failed with status 401 Unauthorized: {}",
+        ));
+        assert_eq!(rejected.valid, Some(false));
+        assert_eq!(rejected.error_kind, Some(ErrorCategory::Auth));
+        assert_eq!(
+            check(AppError::PermissionDenied("No API key found".to_string())).valid,
+            Some(false)
+        );
+
+        let offline = check(AppError::http("dns error: failed to lookup"));
+        assert_eq!(offline.valid, None);
+        assert_eq!(offline.error_kind, Some(ErrorCategory::Network));
+        let locked = check(AppError::Keyring("The keyring is locked".to_string()));
+        assert_eq!(
+            (locked.valid, locked.error_kind),
+            (None, Some(ErrorCategory::Internal))
+        );
+        let limited = check(AppError::http_status(
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+            "Key check failed with status 429 Too Many Requests: {}",
+        ));
+        assert_eq!(
+            (limited.valid, limited.error_kind),
+            (None, Some(ErrorCategory::RateLimit))
+        );
+    }
+
+    #[test]
+    fn image_parts_map_to_each_provider_format() {
+        let messages: Vec<AIMessage> = serde_json::from_value(serde_json::json!([
+            { "role": "user", "content": "plain" },
+            { "role": "user", "cache": true, "content": [
+                { "type": "text", "text": "What does this figure show?" },
+                { "type": "image", "data": "iVBORw0", "mediaType": "image/png" },
+                { "type": "image", "url": "https://example.com/page-3.jpg" },
+            ] },
+        ]))
+        .unwrap();
+        check_message_content(&messages).unwrap();
+        assert_eq!(messages[1].content.text(), "What does this figure show?");
+
+        let openai = serde_json::to_value(openai_content(messages[1].content.clone())).unwrap();
+        assert_eq!(
+            openai[1],
+            serde_json::json!({
+                "type": "image_url",
+                "image_url": { "url": "data:image/png;base64,iVBORw0" }
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(openai_content(messages[0].content.clone())).unwrap(),
+            "plain"
+        );
+
+        let request = build_anthropic_request("claude", messages, None, &ChatOptions::default());
+        let json = serde_json::to_value(&request).unwrap();
+        let blocks = &json["messages"][1]["content"];
+        assert_eq!(
+            blocks[1]["source"],
+            serde_json::json!({ "type": "base64", "media_type": "image/png", "data": "iVBORw0" })
+        );
+        assert_eq!(blocks[2]["source"]["type"], "url");
+        assert_eq!(blocks[2]["cache_control"]["type"], "ephemeral");
+        assert!(blocks[0].get("cache_control").is_none());
+
+        let image = |part: serde_json::Value| serde_json::from_value::<ImagePart>(part).unwrap();
+        assert_eq!(
+            image(serde_json::json!({ "url": "data:image/webp;base64,UklG" }))
+                .source()
+                .unwrap(),
+            ImageSource::Base64 {
+                media_type: "image/webp".to_string(),
+                data: "UklG".to_string()
+            }
+        );
+        assert!(image(serde_json::json!({ "url": "file:///etc/passwd" }))
+            .source()
+            .is_err());
+        assert!(
+            image(serde_json::json!({ "data": "AAAA", "mediaType": "image/svg+xml" }))
+                .source()
+                .is_err()
+        );
+        assert!(image(serde_json::json!({})).source().is_err());
+        let assistant_image: Vec<AIMessage> = serde_json::from_value(serde_json::json!([
+            { "role": "assistant", "content": [{ "type": "image", "url": "https://a.b/c.png" }] },
+        ]))
+        .unwrap();
+        assert!(check_message_content(&assistant_image).is_err());
+    }
 }
@@ -0,0 +1,236 @@
+//! Structured output of chat requests
+//!
+//! A chat request may give a JSON schema the reply must follow. It is sent
+//! to OpenAI-compatible providers as a `json_schema` response format, and
+//! every choice of the reply is parsed and checked against the schema
+//! before it is returned, so a model that ignores the format fails with an
+//! `InvalidOutput` error rather than handing malformed JSON to the
+//! frontend. The check covers the keywords providers accept in response
+//! schemas: `type`, `enum`, `const`, `properties`, `required`,
+//! `additionalProperties`, `items`, `anyOf` and local `$ref`s; others are
+//! not checked.
+
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Longest schema name providers accept
+const MAX_SCHEMA_NAME_LENGTH: usize = 64;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// A JSON schema the reply must follow
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonSchemaFormat {
+    /// Letters, digits, `_` and `-`, up to 64 characters
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub schema: Value,
+    /// Ask the provider to follow the schema exactly (OpenAI)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Check that a format can be sent
+pub fn check_schema_format(format: &JsonSchemaFormat) -> Result<(), AppError> {
+    let valid_name = !format.name.is_empty()
+        && format.name.len() <= MAX_SCHEMA_NAME_LENGTH
+        && format
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid_name {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid schema name: {}",
+            format.name
+        )));
+    }
+    if !format.schema.is_object() {
+        return Err(AppError::InvalidInput(
+            "A response schema must be a JSON object".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, kind: &str) -> bool {
+    match kind {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+/// Check a value against a schema, describing the first mismatch
+fn check_value(root: &Value, schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let Some(keywords) = schema.as_object() else {
+        return Ok(());
+    };
+    if let Some(reference) = keywords.get("$ref").and_then(Value::as_str) {
+        let target = reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer))
+            .ok_or_else(|| format!("{} refers to unknown schema {}", path, reference))?;
+        check_value(root, target, value, path)?;
+    }
+    if keywords
+        .get("const")
+        .is_some_and(|expected| expected != value)
+    {
+        return Err(format!("{} should be {}", path, keywords["const"]));
+    }
+    if let Some(Value::Array(options)) = keywords.get("enum") {
+        if !options.contains(value) {
+            return Err(format!("{} is not one of the allowed values", path));
+        }
+    }
+    let types: Vec<&str> = match keywords.get("type") {
+        Some(Value::String(kind)) => vec![kind.as_str()],
+        Some(Value::Array(kinds)) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|kind| has_type(value, kind)) {
+        return Err(format!("{} should be {}", path, types.join(" or ")));
+    }
+    if let Some(Value::Array(schemas)) = keywords.get("anyOf") {
+        if !schemas
+            .iter()
+            .any(|schema| check_value(root, schema, value, path).is_ok())
+        {
+            return Err(format!("{} matches none of the allowed schemas", path));
+        }
+    }
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = keywords.get("required") {
+                if let Some(missing) = required
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .find(|name| !object.contains_key(*name))
+                {
+                    return Err(format!("{} is missing {}", path, missing));
+                }
+            }
+            let properties = keywords.get("properties").and_then(Value::as_object);
+            for (name, item) in object {
+                let item_path = format!("{}.{}", path, name);
+                match (
+                    properties.and_then(|properties| properties.get(name)),
+                    keywords.get("additionalProperties"),
+                ) {
+                    (Some(schema), _) => check_value(root, schema, item, &item_path)?,
+                    (None, Some(Value::Bool(false))) => {
+                        return Err(format!("{} is not allowed", item_path))
+                    }
+                    (None, Some(schema)) => check_value(root, schema, item, &item_path)?,
+                    (None, None) => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(schema) = keywords.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check_value(root, schema, item, &format!("{}[{}]", path, index))?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Parse a reply and check it against the schema of the request
+pub fn parse_structured_reply(format: &JsonSchemaFormat, content: &str) -> Result<Value, AppError> {
+    let value: Value = serde_json::from_str(content.trim())
+        .map_err(|e| AppError::InvalidOutput(format!("The reply is not valid JSON: {}", e)))?;
+    check_value(&format.schema, &format.schema, &value, "$").map_err(|mismatch| {
+        AppError::InvalidOutput(format!(
+            "The reply does not match schema {}: {}",
+            format.name, mismatch
+        ))
+    })?;
+    Ok(value)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn replies_are_checked_against_the_schema() {
+        let format = JsonSchemaFormat {
+            name: "flashcards".to_string(),
+            description: None,
+            schema: json!({
+                "type": "object",
+                "properties": {
+                    "cards": {"type": "array", "items": {"$ref": "#/$defs/card"}},
+                    "level": {"enum": ["easy", "hard"]},
+                },
+                "required": ["cards"],
+                "additionalProperties": false,
+                "$defs": {
+                    "card": {
+                        "type": "object",
+                        "properties": {
+                            "front": {"type": "string"},
+                            "page": {"type": ["integer", "null"]},
+                        },
+                        "required": ["front", "page"],
+                    },
+                },
+            }),
+            strict: Some(true),
+        };
+        assert!(check_schema_format(&format).is_ok());
+
+        let reply =
+            r#" {"cards": [{"front": "Who?", "page": 3}, {"front": "Why?", "page": null}]} "#;
+        assert_eq!(
+            parse_structured_reply(&format, reply).unwrap()["cards"][0]["page"],
+            3
+        );
+
+        let mismatch = |reply: &str| match parse_structured_reply(&format, reply) {
+            Err(AppError::InvalidOutput(detail)) => detail,
+            other => panic!("expected invalid output, got {:?}", other),
+        };
+        assert!(mismatch("Sure! Here are your cards").contains("not valid JSON"));
+        assert!(mismatch(r#"{"level": "easy"}"#).ends_with("$ is missing cards"));
+        assert!(mismatch(r#"{"cards": [], "level": "medium"}"#).contains("$.level"));
+        assert!(mismatch(r#"{"cards": [], "extra": 1}"#).ends_with("$.extra is not allowed"));
+        assert!(mismatch(r#"{"cards": [{"front": "Q", "page": 1.5}]}"#)
+            .ends_with("$.cards[0].page should be integer or null"));
+
+        let unnamed = JsonSchemaFormat {
+            name: "my schema".to_string(),
+            ..format.clone()
+        };
+        assert!(check_schema_format(&unnamed).is_err());
+        let not_object = JsonSchemaFormat {
+            schema: json!(true),
+            ..format
+        };
+        assert!(check_schema_format(&not_object).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+//! Cached AI summaries of documents and chapters
+//!
+//! Summaries are keyed by the document's content hash, the chapter and the
+//! model, so re-opening (or re-adding) the same book does not spend tokens
+//! again.
+
+use crate::commands::ai_proxy::{request_chat_completion, AIMessage};
+use crate::commands::library::{get_document, lock_library, LibraryState};
+use crate::commands::rag::{extract_document_sections, DocumentSection};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Chapter value stored for whole-document summaries
+const WHOLE_DOCUMENT: i64 = -1;
+
+/// Longest text sent in a single summarization request
+const MAX_SUMMARY_INPUT_CHARS: usize = 48_000;
+
+const SUMMARY_SYSTEM_PROMPT: &str = "You summarize books and documents for a reader. \
+Write a concise summary of the main points in a few paragraphs, in the language of the text. \
+Do not add information that is not in the text.";
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// A generated summary
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentSummary {
+    pub document_id: String,
+    /// Spine index (EPUB) or page number (PDF); None for the whole document
+    pub chapter: Option<u32>,
+    pub provider: String,
+    pub model: String,
+    pub summary: String,
+    /// Returned from the cache rather than generated by this call
+    pub cached: bool,
+    pub created_at: i64,
+}
+
+/// A summary row of the cache
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedSummary {
+    pub provider: String,
+    pub summary: String,
+    pub created_at: i64,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn chapter_key(chapter: Option<u32>) -> i64 {
+    chapter.map_or(WHOLE_DOCUMENT, i64::from)
+}
+
+/// Look up a cached summary
+pub fn get_cached_summary(
+    conn: &Connection,
+    document_hash: &str,
+    chapter: Option<u32>,
+    model: &str,
+) -> Result<Option<CachedSummary>, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT provider, summary, created_at FROM ai_summaries
+             WHERE document_hash = ?1 AND chapter = ?2 AND model = ?3",
+            params![document_hash, chapter_key(chapter), model],
+            |row| {
+                Ok(CachedSummary {
+                    provider: row.get(0)?,
+                    summary: row.get(1)?,
+                    created_at: row.get(2)?,
+                })
+            },
+        )
+        .optional()?)
+}
+
+/// Store a summary, replacing any previous one for the same key
+pub fn store_summary(
+    conn: &Connection,
+    document_hash: &str,
+    chapter: Option<u32>,
+    model: &str,
+    summary: &CachedSummary,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO ai_summaries
+             (document_hash, chapter, model, provider, summary, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            document_hash,
+            chapter_key(chapter),
+            model,
+            summary.provider,
+            summary.summary,
+            summary.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Split the text to summarize into request-sized parts
+///
+/// A chapter is truncated to a single part; a whole document is grouped
+/// section by section into as many parts as needed.
+pub fn summary_parts(
+    sections: &[DocumentSection],
+    chapter: Option<u32>,
+    max_chars: usize,
+) -> Result<Vec<String>, AppError> {
+    if let Some(chapter) = chapter {
+        let section = sections
+            .iter()
+            .find(|s| s.location == chapter)
+            .ok_or_else(|| AppError::NotFound(format!("Chapter {} has no text", chapter)))?;
+        return Ok(vec![section.text.chars().take(max_chars).collect()]);
+    }
+
+    let mut parts: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for section in sections {
+        let chars: Vec<char> = section.text.chars().collect();
+        for piece in chars.chunks(max_chars) {
+            if current_chars > 0 && current_chars + piece.len() + 2 > max_chars {
+                parts.push(std::mem::take(&mut current));
+                current_chars = 0;
+            }
+            if current_chars > 0 {
+                current.push_str("\n\n");
+                current_chars += 2;
+            }
+            current.extend(piece);
+            current_chars += piece.len();
+        }
+    }
+    if current_chars > 0 {
+        parts.push(current);
+    }
+    if parts.is_empty() {
+        return Err(AppError::InvalidInput(
+            "No text could be extracted from the document".to_string(),
+        ));
+    }
+    Ok(parts)
+}
+
+async fn summarize_text(provider: &str, model: &str, text: String) -> Result<String, AppError> {
+    let messages = vec![AIMessage {
+        role: "user".to_string(),
+        content: text.into(),
+        cache: false,
+    }];
+    let summary = request_chat_completion(
+        provider,
+        model,
+        messages,
+        Some(SUMMARY_SYSTEM_PROMPT.into()),
+    )
+    .await?;
+    Ok(summary.trim().to_string())
+}
+
+/// Summarize the parts, combining per-part summaries when there are several
+async fn summarize_parts(
+    provider: &str,
+    model: &str,
+    parts: Vec<String>,
+) -> Result<String, AppError> {
+    if parts.len() == 1 {
+        return summarize_text(
+            provider,
+            model,
+            parts.into_iter().next().unwrap_or_default(),
+        )
+        .await;
+    }
+    let total = parts.len();
+    let mut partial = Vec::with_capacity(total);
+    for (index, part) in parts.into_iter().enumerate() {
+        log::debug!("Summarizing part {}/{}", index + 1, total);
+        partial.push(summarize_text(provider, model, part).await?);
+    }
+    let combined = partial
+        .iter()
+        .enumerate()
+        .map(|(index, summary)| format!("Part {}:\n{}", index + 1, summary))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    summarize_text(
+        provider,
+        model,
+        format!(
+            "These are summaries of consecutive parts of one document. \
+             Combine them into a single summary of the whole document.\n\n{}",
+            combined
+        ),
+    )
+    .await
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the summary of a document or chapter, generating and caching it on a miss
+///
+/// `chapter` is a spine index for EPUBs and a page number for PDFs.
+#[tauri::command]
+pub async fn get_or_generate_summary(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+    chapter: Option<u32>,
+    provider: String,
+    model: String,
+    regenerate: Option<bool>,
+) -> Result<DocumentSummary, AppError> {
+    let library = state.inner().clone();
+    let (document, cached) = {
+        let conn = lock_library(&library)?;
+        let document = get_document(&conn, &document_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Document '{}' not found", document_id)))?;
+        let cached = get_cached_summary(&conn, &document.hash, chapter, &model)?;
+        (document, cached)
+    };
+    if let Some(cached) = cached.filter(|_| !regenerate.unwrap_or(false)) {
+        return Ok(DocumentSummary {
+            document_id,
+            chapter,
+            provider: cached.provider,
+            model,
+            summary: cached.summary,
+            cached: true,
+            created_at: cached.created_at,
+        });
+    }
+
+    let path = PathBuf::from(&document.path);
+    if !path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "File not found: {}",
+            document.path
+        )));
+    }
+    let format = document.format.clone();
+    let sections =
+        tauri::async_runtime::spawn_blocking(move || extract_document_sections(&path, &format))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))??;
+    let parts = summary_parts(&sections, chapter, MAX_SUMMARY_INPUT_CHARS)?;
+    let summary = summarize_parts(&provider, &model, parts).await?;
+
+    let stored = CachedSummary {
+        provider,
+        summary,
+        created_at: chrono::Utc::now().timestamp(),
+    };
+    store_summary(
+        &*lock_library(&library)?,
+        &document.hash,
+        chapter,
+        &model,
+        &stored,
+    )?;
+    log::info!("Summary generated for {} ({:?})", document.path, chapter);
+
+    Ok(DocumentSummary {
+        document_id,
+        chapter,
+        provider: stored.provider,
+        model,
+        summary: stored.summary,
+        cached: false,
+        created_at: stored.created_at,
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::library::LIBRARY_MIGRATIONS;
+    use crate::db;
+
+    fn section(location: u32, text: &str) -> DocumentSection {
+        DocumentSection {
+            kind: "chapter".to_string(),
+            location,
+            label: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn summaries_are_cached_per_chapter_and_model() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let summary = |text: &str, at| CachedSummary {
+            provider: "openai".to_string(),
+            summary: text.to_string(),
+            created_at: at,
+        };
+        store_summary(&conn, "hash", None, "gpt", &summary("book", 1)).unwrap();
+        store_summary(&conn, "hash", Some(3), "gpt", &summary("chapter", 2)).unwrap();
+
+        assert_eq!(
+            get_cached_summary(&conn, "hash", None, "gpt").unwrap(),
+            Some(summary("book", 1))
+        );
+        assert_eq!(
+            get_cached_summary(&conn, "hash", Some(3), "gpt")
+                .unwrap()
+                .unwrap()
+                .summary,
+            "chapter"
+        );
+        assert!(get_cached_summary(&conn, "hash", None, "other")
+            .unwrap()
+            .is_none());
+
+        store_summary(&conn, "hash", None, "gpt", &summary("again", 5)).unwrap();
+        assert_eq!(
+            get_cached_summary(&conn, "hash", None, "gpt")
+                .unwrap()
+                .unwrap()
+                .summary,
+            "again"
+        );
+    }
+
+    #[test]
+    fn summary_parts_group_and_truncate_sections() {
+        let sections = vec![
+            section(0, "aaaa"),
+            section(1, "bbbb"),
+            section(2, "cccccccccc"),
+        ];
+        assert_eq!(
+            summary_parts(&sections, None, 10).unwrap(),
+            vec!["aaaa\n\nbbbb", "cccccccccc"]
+        );
+        assert_eq!(
+            summary_parts(&sections, None, 6).unwrap(),
+            vec!["aaaa", "bbbb", "cccccc", "cccc"]
+        );
+        assert_eq!(summary_parts(&sections, Some(2), 3).unwrap(), vec!["ccc"]);
+        assert!(matches!(
+            summary_parts(&sections, Some(9), 3),
+            Err(AppError::NotFound(_))
+        ));
+        assert!(summary_parts(&[], None, 3).is_err());
+    }
+}
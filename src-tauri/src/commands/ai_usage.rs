@@ -1,12 +1,28 @@
 //! AI usage statistics commands
+//!
+//! Requests made through the AI proxy are counted by the backend as their
+//! replies arrive, cached tokens and the serving upstream included, so the
+//! frontend only reports requests it sends itself. Every update loads,
+//! changes and saves the statistics file under one lock and replaces the
+//! file in a single rename, so concurrent requests are all counted and an
+//! interrupted write cannot truncate it. Costs are computed here from the
+//! tokens and the model's price (see `ai_pricing`), never taken from the
+//! frontend.
 
+use crate::commands::ai_pricing::request_cost;
+use crate::commands::ai_proxy::TokenUsage;
+use crate::commands::migrations::JsonMigration;
 use crate::error::AppError;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tauri::Manager;
 
+/// Held while the statistics file is read, changed and written back
+static USAGE_STATS_LOCK: Mutex<()> = Mutex::new(());
+
 // ============================================================================
 // Data Structures
 // ============================================================================
@@ -26,6 +42,9 @@ pub struct AIUsageStats {
     // Timestamps
     pub first_request_at: Option<i64>,
     pub last_request_at: Option<i64>,
+    /// MCP tool calls made for the AI
+    #[serde(default)]
+    pub mcp_stats: MCPUsageStats,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -34,19 +53,71 @@ pub struct ProviderUsageStats {
     pub total_tokens: u64,
     pub total_requests: u64,
     pub cost_estimate: f64,
+    /// Requests by the upstream provider that served them (OpenRouter)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub upstreams: HashMap<String, u64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MCPUsageStats {
+    pub total_calls: u64,
+    /// Calls that errored or whose result was an error
+    pub failed_calls: u64,
+    pub server_stats: HashMap<String, MCPServerUsageStats>,
+    pub last_call_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MCPServerUsageStats {
+    pub total_calls: u64,
+    pub failed_calls: u64,
+    /// Calls by tool name
+    pub tools: HashMap<String, u64>,
 }
 
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
+/// Usage statistics file name in the app data directory
+pub const USAGE_STATS_FILE: &str = "ai_usage_stats.json";
+
+/// Upgrades of the usage statistics file, run at startup (see `migrations`)
+pub const USAGE_STATS_MIGRATIONS: &[JsonMigration] = &[fill_usage_counters];
+
+/// v1: add the token breakdown and per-provider counters missing from
+/// early statistics files
+fn fill_usage_counters(value: &mut serde_json::Value) -> Result<(), AppError> {
+    let Some(stats) = value.as_object_mut() else {
+        return Err(AppError::InvalidInput(
+            "Usage statistics file is not an object".to_string(),
+        ));
+    };
+    for counter in [
+        "totalTokens",
+        "totalRequests",
+        "inputTokens",
+        "outputTokens",
+        "cachedTokens",
+    ] {
+        stats.entry(counter).or_insert(0.into());
+    }
+    stats.entry("costEstimate").or_insert(0.0.into());
+    stats
+        .entry("providerStats")
+        .or_insert(serde_json::json!({}));
+    Ok(())
+}
+
 fn get_usage_stats_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
     let data_dir = app
         .path()
         .app_data_dir()
         .map_err(|e| AppError::NotFound(e.to_string()))?;
     fs::create_dir_all(&data_dir)?;
-    Ok(data_dir.join("ai_usage_stats.json"))
+    Ok(data_dir.join(USAGE_STATS_FILE))
 }
 
 pub fn load_usage_stats_from_file(path: &Path) -> Result<AIUsageStats, AppError> {
@@ -63,18 +134,38 @@ pub fn save_usage_stats_to_file(path: &Path, stats: &AIUsageStats) -> Result<(),
         fs::create_dir_all(parent)?;
     }
     let content = serde_json::to_string_pretty(stats)?;
-    fs::write(path, content)?;
+    let partial = path.with_extension("json.part");
+    fs::write(&partial, content)?;
+    fs::rename(&partial, path)?;
     Ok(())
 }
 
+/// Apply a change to the statistics file, with no other update in between
+pub fn update_usage_stats_file<F>(path: &Path, change: F) -> Result<(), AppError>
+where
+    F: FnOnce(&mut AIUsageStats),
+{
+    let _guard = USAGE_STATS_LOCK
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut stats = load_usage_stats_from_file(path)?;
+    change(&mut stats);
+    save_usage_stats_to_file(path, &stats)
+}
+
 fn load_usage_stats(app: &tauri::AppHandle) -> Result<AIUsageStats, AppError> {
     let path = get_usage_stats_path(app)?;
+    let _guard = USAGE_STATS_LOCK
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
     load_usage_stats_from_file(&path)
 }
 
-fn save_usage_stats(app: &tauri::AppHandle, stats: &AIUsageStats) -> Result<(), AppError> {
-    let path = get_usage_stats_path(app)?;
-    save_usage_stats_to_file(&path, stats)
+fn update_usage_stats<F>(app: &tauri::AppHandle, change: F) -> Result<(), AppError>
+where
+    F: FnOnce(&mut AIUsageStats),
+{
+    update_usage_stats_file(&get_usage_stats_path(app)?, change)
 }
 
 pub fn apply_usage_update(
@@ -108,6 +199,75 @@ pub fn apply_usage_update(
     provider_stats.cost_estimate += cost.unwrap_or(0.0);
 }
 
+/// Count a request against the upstream provider that served it
+pub fn apply_upstream(stats: &mut AIUsageStats, provider: &str, upstream: &str) {
+    *stats
+        .provider_stats
+        .entry(provider.to_string())
+        .or_default()
+        .upstreams
+        .entry(upstream.to_string())
+        .or_default() += 1;
+}
+
+/// Count an MCP tool call
+pub fn apply_mcp_tool_call(
+    stats: &mut AIUsageStats,
+    server_id: &str,
+    tool_name: &str,
+    failed: bool,
+    timestamp: i64,
+) {
+    let mcp = &mut stats.mcp_stats;
+    mcp.total_calls += 1;
+    mcp.failed_calls += u64::from(failed);
+    mcp.last_call_at = Some(timestamp);
+
+    let server = mcp.server_stats.entry(server_id.to_string()).or_default();
+    server.total_calls += 1;
+    server.failed_calls += u64::from(failed);
+    *server.tools.entry(tool_name.to_string()).or_default() += 1;
+}
+
+/// Add an MCP tool call made by the backend
+pub fn record_mcp_tool_call(
+    app: &tauri::AppHandle,
+    server_id: &str,
+    tool_name: &str,
+    failed: bool,
+) -> Result<(), AppError> {
+    let now = chrono::Utc::now().timestamp();
+    update_usage_stats(app, |stats| {
+        apply_mcp_tool_call(stats, server_id, tool_name, failed, now)
+    })
+}
+
+/// Add the usage of a request made by the backend, priced by its model
+pub fn record_usage(
+    app: &tauri::AppHandle,
+    provider: &str,
+    model: &str,
+    usage: &TokenUsage,
+    upstream: Option<&str>,
+) -> Result<(), AppError> {
+    let now = chrono::Utc::now().timestamp();
+    let cost = request_cost(provider, model, usage);
+    update_usage_stats(app, |stats| {
+        apply_usage_update(
+            stats,
+            provider,
+            usage.input_tokens,
+            usage.output_tokens,
+            Some(usage.cached_tokens),
+            cost,
+            now,
+        );
+        if let Some(upstream) = upstream {
+            apply_upstream(stats, provider, upstream);
+        }
+    })
+}
+
 // ============================================================================
 // Commands
 // ============================================================================
@@ -121,35 +281,46 @@ pub fn get_ai_usage_stats(app: tauri::AppHandle) -> Result<AIUsageStats, AppErro
 /// Clear AI usage statistics
 #[tauri::command]
 pub fn clear_ai_usage_stats(app: tauri::AppHandle) -> Result<(), AppError> {
-    let stats = AIUsageStats::default();
-    save_usage_stats(&app, &stats)?;
+    update_usage_stats(&app, |stats| *stats = AIUsageStats::default())?;
     log::info!("AI usage stats cleared");
     Ok(())
 }
 
-/// Update AI usage statistics (called after each AI request)
+/// Update AI usage statistics after a request the frontend sent itself;
+/// requests through the AI proxy are already counted. The cost is priced
+/// from the model, if given
 #[tauri::command]
 pub fn update_ai_usage_stats(
     app: tauri::AppHandle,
     provider: String,
+    model: Option<String>,
     input_tokens: u64,
     output_tokens: u64,
     cached_tokens: Option<u64>,
-    cost: Option<f64>,
 ) -> Result<(), AppError> {
-    let mut stats = load_usage_stats(&app)?;
-    let now = chrono::Utc::now().timestamp();
-    apply_usage_update(
-        &mut stats,
-        &provider,
+    let usage = TokenUsage {
         input_tokens,
         output_tokens,
-        cached_tokens,
-        cost,
-        now,
-    );
-    save_usage_stats(&app, &stats)?;
-    Ok(())
+        cached_tokens: cached_tokens.unwrap_or(0),
+        ..Default::default()
+    };
+    match model {
+        Some(model) => record_usage(&app, &provider, &model, &usage, None),
+        None => {
+            let now = chrono::Utc::now().timestamp();
+            update_usage_stats(&app, |stats| {
+                apply_usage_update(
+                    stats,
+                    &provider,
+                    input_tokens,
+                    output_tokens,
+                    cached_tokens,
+                    None,
+                    now,
+                )
+            })
+        }
+    }
 }
 
 // ============================================================================
@@ -179,6 +350,40 @@ mod tests {
         assert_eq!(provider_stats.total_tokens, 150);
         assert_eq!(provider_stats.total_requests, 1);
         assert_eq!(provider_stats.cost_estimate, 0.25);
+
+        apply_upstream(&mut stats, "openrouter", "Together");
+        apply_upstream(&mut stats, "openrouter", "Together");
+        assert_eq!(
+            stats.provider_stats["openrouter"].upstreams.get("Together"),
+            Some(&2)
+        );
+    }
+
+    #[test]
+    fn apply_mcp_tool_call_counts_servers_tools_and_failures() {
+        let mut stats = AIUsageStats::default();
+        apply_mcp_tool_call(&mut stats, "github", "search_issues", false, 100);
+        apply_mcp_tool_call(&mut stats, "github", "search_issues", true, 200);
+        apply_mcp_tool_call(&mut stats, "files", "read_file", false, 300);
+
+        assert_eq!(stats.mcp_stats.total_calls, 3);
+        assert_eq!(stats.mcp_stats.failed_calls, 1);
+        assert_eq!(stats.mcp_stats.last_call_at, Some(300));
+        let github = &stats.mcp_stats.server_stats["github"];
+        assert_eq!(github.total_calls, 2);
+        assert_eq!(github.failed_calls, 1);
+        assert_eq!(github.tools["search_issues"], 2);
+        // Tool calls are not AI requests
+        assert_eq!(stats.total_requests, 0);
+
+        // Files written before MCP stats existed still load
+        let old: AIUsageStats = serde_json::from_value(serde_json::json!({
+            "totalTokens": 0, "totalRequests": 0, "costEstimate": 0.0,
+            "inputTokens": 0, "outputTokens": 0, "cachedTokens": 0,
+            "providerStats": {}, "firstRequestAt": null, "lastRequestAt": null
+        }))
+        .unwrap();
+        assert_eq!(old.mcp_stats.total_calls, 0);
     }
 
     #[test]
@@ -195,6 +400,7 @@ mod tests {
                 total_tokens: 200,
                 total_requests: 2,
                 cost_estimate: 0.5,
+                ..Default::default()
             },
         );
 
@@ -231,4 +437,32 @@ mod tests {
         let loaded = load_usage_stats_from_file(&nested).unwrap();
         assert_eq!(loaded.total_tokens, 42);
     }
+
+    #[test]
+    fn concurrent_updates_are_all_counted() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stats.json");
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        update_usage_stats_file(&path, |stats| {
+                            apply_usage_update(stats, "openai", 3, 2, Some(1), None, 1)
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let stats = load_usage_stats_from_file(&path).unwrap();
+        assert_eq!(stats.total_requests, 80);
+        assert_eq!(stats.total_tokens, 400);
+        assert_eq!(stats.cached_tokens, 80);
+        assert!(!dir.path().join("stats.json.part").exists());
+    }
 }
@@ -0,0 +1,508 @@
+//! Annotation export commands
+//!
+//! Annotations live in the frontend store and are passed in with the request;
+//! library metadata and bookmarks are merged in from the library database.
+//! Supported formats: per-book Markdown notes, Readwise CSV and Calibre
+//! highlights JSON.
+
+use crate::commands::library::{
+    get_document, list_bookmarks, lock_library, LibraryBookmark, LibraryState,
+};
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Calibre's built-in highlight colours and their RGB values
+const CALIBRE_COLORS: &[(&str, (u8, u8, u8))] = &[
+    ("yellow", (255, 255, 0)),
+    ("green", (0, 255, 0)),
+    ("blue", (0, 0, 255)),
+    ("red", (255, 0, 0)),
+    ("purple", (128, 0, 128)),
+];
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// A single annotation as stored by the frontend
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotationRecord {
+    pub id: String,
+    /// "highlight" | "comment" | "text" | ...
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub page_number: Option<u32>,
+    /// Highlighted text or comment body
+    pub content: Option<String>,
+    /// Note attached to a highlight
+    pub note: Option<String>,
+    pub color: Option<String>,
+    /// Creation time in milliseconds since the epoch
+    pub timestamp: i64,
+}
+
+/// Annotations of one document
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotatedDocument {
+    /// Library document id, used to fill in metadata and bookmarks
+    pub document_id: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub url: Option<String>,
+    pub annotations: Vec<AnnotationRecord>,
+}
+
+/// Annotation export request
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotationExportRequest {
+    /// One entry per document; pass every document to export all
+    pub documents: Vec<AnnotatedDocument>,
+    /// "markdown" | "readwise" | "calibre"
+    pub format: String,
+    /// Target file, or directory for per-book formats with several documents
+    pub path: String,
+    /// Include library bookmarks (Markdown and Calibre only, default true)
+    pub include_bookmarks: Option<bool>,
+}
+
+/// Annotation export result
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotationExportResult {
+    pub files: Vec<String>,
+    pub document_count: usize,
+    pub annotation_count: usize,
+}
+
+/// A document prepared for export
+#[derive(Clone, Debug, Default)]
+pub struct ExportBook {
+    pub title: String,
+    pub author: Option<String>,
+    pub url: Option<String>,
+    pub annotations: Vec<AnnotationRecord>,
+    pub bookmarks: Vec<LibraryBookmark>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Annotations worth exporting: those with text, in page order
+fn exportable_annotations(mut annotations: Vec<AnnotationRecord>) -> Vec<AnnotationRecord> {
+    annotations.retain(|a| {
+        a.content.as_deref().is_some_and(|c| !c.trim().is_empty())
+            || a.note.as_deref().is_some_and(|n| !n.trim().is_empty())
+    });
+    annotations.sort_by_key(|a| (a.page_number.unwrap_or(0), a.timestamp));
+    annotations
+}
+
+fn format_timestamp(timestamp_ms: i64, format: &str) -> String {
+    chrono::DateTime::from_timestamp_millis(timestamp_ms)
+        .map(|date| date.format(format).to_string())
+        .unwrap_or_default()
+}
+
+/// Replace characters that are not allowed in file names
+pub fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_matches('.').to_string();
+    if cleaned.is_empty() {
+        "Untitled".to_string()
+    } else {
+        cleaned.chars().take(120).collect()
+    }
+}
+
+/// Quote a CSV field when needed
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render one book as Markdown notes
+pub fn render_markdown(book: &ExportBook) -> String {
+    let mut out = format!("# {}\n\n", book.title);
+    if let Some(author) = &book.author {
+        out.push_str(&format!("*{}*\n\n", author));
+    }
+    if let Some(url) = &book.url {
+        out.push_str(&format!("Source: <{}>\n\n", url));
+    }
+
+    if !book.annotations.is_empty() {
+        out.push_str("## Annotations\n\n");
+        for annotation in &book.annotations {
+            if let Some(content) = annotation
+                .content
+                .as_deref()
+                .filter(|c| !c.trim().is_empty())
+            {
+                if annotation.kind == "highlight" {
+                    for line in content.trim().lines() {
+                        out.push_str(&format!("> {}\n", line));
+                    }
+                } else {
+                    out.push_str(&format!("{}\n", content.trim()));
+                }
+                out.push('\n');
+            }
+            if let Some(note) = annotation.note.as_deref().filter(|n| !n.trim().is_empty()) {
+                out.push_str(&format!("**Note:** {}\n\n", note.trim()));
+            }
+            let mut details = Vec::new();
+            if let Some(page) = annotation.page_number {
+                details.push(format!("Page {}", page));
+            }
+            details.push(format_timestamp(annotation.timestamp, "%Y-%m-%d"));
+            out.push_str(&format!("*{}*\n\n", details.join(" · ")));
+        }
+    }
+
+    if !book.bookmarks.is_empty() {
+        out.push_str("## Bookmarks\n\n");
+        for bookmark in &book.bookmarks {
+            match &bookmark.label {
+                Some(label) => out.push_str(&format!("- {} ({})\n", label, bookmark.locator)),
+                None => out.push_str(&format!("- {}\n", bookmark.locator)),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Render books as a Readwise-compatible CSV import file
+pub fn render_readwise_csv(books: &[ExportBook]) -> String {
+    let mut out = String::from("Highlight,Title,Author,URL,Note,Location,Location Type,Date\n");
+    for book in books {
+        for annotation in &book.annotations {
+            // Readwise requires highlight text; fall back to the note for comments
+            let highlight = annotation
+                .content
+                .as_deref()
+                .filter(|c| !c.trim().is_empty())
+                .or(annotation.note.as_deref())
+                .unwrap_or_default();
+            let note = if annotation
+                .content
+                .as_deref()
+                .is_some_and(|c| !c.trim().is_empty())
+            {
+                annotation.note.clone().unwrap_or_default()
+            } else {
+                String::new()
+            };
+            let row = [
+                highlight.trim().to_string(),
+                book.title.clone(),
+                book.author.clone().unwrap_or_default(),
+                book.url.clone().unwrap_or_default(),
+                note,
+                annotation
+                    .page_number
+                    .map(|p| p.to_string())
+                    .unwrap_or_default(),
+                if annotation.page_number.is_some() {
+                    "page"
+                } else {
+                    ""
+                }
+                .to_string(),
+                format_timestamp(annotation.timestamp, "%Y-%m-%d %H:%M:%S"),
+            ];
+            out.push_str(
+                &row.iter()
+                    .map(|f| csv_field(f))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Map a CSS hex colour to the nearest Calibre built-in highlight colour
+fn calibre_color(color: Option<&str>) -> &'static str {
+    let Some(hex) = color
+        .map(|c| c.trim_start_matches('#'))
+        .filter(|c| c.len() >= 6)
+    else {
+        return "yellow";
+    };
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2).unwrap_or("00"), 16).unwrap_or(0);
+    let (r, g, b) = (channel(0) as i32, channel(2) as i32, channel(4) as i32);
+    CALIBRE_COLORS
+        .iter()
+        .min_by_key(|(_, (cr, cg, cb))| {
+            (r - *cr as i32).pow(2) + (g - *cg as i32).pow(2) + (b - *cb as i32).pow(2)
+        })
+        .map(|(name, _)| *name)
+        .unwrap_or("yellow")
+}
+
+/// Render one book as a Calibre highlights JSON document
+pub fn render_calibre_json(book: &ExportBook) -> Result<String, AppError> {
+    let mut highlights: Vec<serde_json::Value> = book
+        .annotations
+        .iter()
+        .map(|annotation| {
+            serde_json::json!({
+                "type": "highlight",
+                "uuid": annotation.id,
+                "timestamp": format_timestamp(annotation.timestamp, "%Y-%m-%dT%H:%M:%S%.3fZ"),
+                "highlighted_text": annotation.content.clone().unwrap_or_default(),
+                "notes": annotation.note.clone().unwrap_or_default(),
+                "style": {
+                    "kind": "color",
+                    "type": "builtin",
+                    "which": calibre_color(annotation.color.as_deref()),
+                },
+                "page": annotation.page_number,
+            })
+        })
+        .collect();
+    highlights.extend(book.bookmarks.iter().map(|bookmark| {
+        serde_json::json!({
+            "type": "bookmark",
+            "title": bookmark.label.clone().unwrap_or_else(|| bookmark.locator.clone()),
+            "pos": bookmark.locator,
+            "pos_type": if bookmark.locator.starts_with("epubcfi(") { "epubcfi" } else { "page" },
+            "timestamp": format_timestamp(bookmark.created_at * 1000, "%Y-%m-%dT%H:%M:%S%.3fZ"),
+        })
+    }));
+
+    Ok(serde_json::to_string_pretty(&serde_json::json!({
+        "type": "calibre_highlights",
+        "version": 1,
+        "title": book.title,
+        "authors": book.author.iter().collect::<Vec<_>>(),
+        "highlights": highlights,
+    }))?)
+}
+
+/// Render the export and decide which files to write
+///
+/// Readwise produces one CSV file. Markdown and Calibre produce one file per
+/// book inside `path`, unless a single book is exported to a path that already
+/// has the format's extension.
+pub fn plan_export_files(
+    format: &str,
+    path: &Path,
+    books: &[ExportBook],
+) -> Result<Vec<(PathBuf, String)>, AppError> {
+    let extension = match format {
+        "readwise" => return Ok(vec![(path.to_path_buf(), render_readwise_csv(books))]),
+        "markdown" => "md",
+        "calibre" => "json",
+        other => {
+            return Err(AppError::InvalidInput(format!(
+                "Unsupported annotation export format: {}",
+                other
+            )))
+        }
+    };
+    let render = |book: &ExportBook| match format {
+        "markdown" => Ok(render_markdown(book)),
+        _ => render_calibre_json(book),
+    };
+
+    let single_file = books.len() == 1
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(extension));
+    if single_file {
+        return Ok(vec![(path.to_path_buf(), render(&books[0])?)]);
+    }
+
+    let mut used_names = HashSet::new();
+    books
+        .iter()
+        .map(|book| {
+            let base = sanitize_file_name(&book.title);
+            let mut name = format!("{}.{}", base, extension);
+            let mut counter = 2;
+            while !used_names.insert(name.to_lowercase()) {
+                name = format!("{} ({}).{}", base, counter, extension);
+                counter += 1;
+            }
+            Ok((path.join(name), render(book)?))
+        })
+        .collect()
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Export annotations (and library bookmarks) as Markdown, Readwise CSV or Calibre JSON
+#[tauri::command]
+pub fn export_annotations(
+    state: tauri::State<'_, LibraryState>,
+    request: AnnotationExportRequest,
+) -> Result<AnnotationExportResult, AppError> {
+    let include_bookmarks =
+        request.include_bookmarks.unwrap_or(true) && request.format != "readwise";
+
+    let books = {
+        let conn = lock_library(&state)?;
+        request
+            .documents
+            .into_iter()
+            .map(|document| {
+                let stored = match document.document_id.as_deref() {
+                    Some(id) => get_document(&conn, id)?,
+                    None => None,
+                };
+                let bookmarks = match (&stored, include_bookmarks) {
+                    (Some(stored), true) => list_bookmarks(&conn, &stored.id)?,
+                    _ => Vec::new(),
+                };
+                Ok(ExportBook {
+                    title: document
+                        .title
+                        .or_else(|| stored.as_ref().and_then(|d| d.title.clone()))
+                        .or_else(|| stored.as_ref().map(|d| d.file_name.clone()))
+                        .unwrap_or_else(|| "Untitled".to_string()),
+                    author: document
+                        .author
+                        .or_else(|| stored.as_ref().and_then(|d| d.author.clone())),
+                    url: document.url,
+                    annotations: exportable_annotations(document.annotations),
+                    bookmarks,
+                })
+            })
+            .collect::<Result<Vec<_>, AppError>>()?
+    };
+
+    let files = plan_export_files(&request.format, Path::new(&request.path), &books)?;
+    for (path, content) in &files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+    }
+    log::info!("Exported annotations to {} file(s)", files.len());
+
+    Ok(AnnotationExportResult {
+        files: files
+            .iter()
+            .map(|(path, _)| path.to_string_lossy().to_string())
+            .collect(),
+        document_count: books.len(),
+        annotation_count: books.iter().map(|book| book.annotations.len()).sum(),
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotation(
+        kind: &str,
+        page: u32,
+        content: Option<&str>,
+        note: Option<&str>,
+    ) -> AnnotationRecord {
+        AnnotationRecord {
+            id: format!("a{}", page),
+            kind: kind.to_string(),
+            page_number: Some(page),
+            content: content.map(|c| c.to_string()),
+            note: note.map(|n| n.to_string()),
+            color: Some("#00ff10".to_string()),
+            timestamp: 1_700_000_000_000,
+        }
+    }
+
+    fn book() -> ExportBook {
+        ExportBook {
+            title: "Dune: Deluxe".to_string(),
+            author: Some("Frank Herbert".to_string()),
+            url: None,
+            annotations: exportable_annotations(vec![
+                annotation("comment", 9, Some("Remember this"), None),
+                annotation(
+                    "highlight",
+                    2,
+                    Some("Fear is the mind-killer."),
+                    Some("Litany, \"classic\""),
+                ),
+                annotation("drawing", 1, None, None),
+            ]),
+            bookmarks: vec![LibraryBookmark {
+                id: "bm".to_string(),
+                document_id: "doc".to_string(),
+                locator: "page=4".to_string(),
+                label: Some("Arrakis".to_string()),
+                created_at: 1_700_000_000,
+            }],
+        }
+    }
+
+    #[test]
+    fn render_markdown_quotes_highlights_and_lists_bookmarks() {
+        let markdown = render_markdown(&book());
+
+        assert!(markdown.starts_with(
+            "# Dune: Deluxe\n\n*Frank Herbert*\n\n## Annotations\n\n> Fear is the mind-killer.\n"
+        ));
+        assert!(markdown.contains("**Note:** Litany, \"classic\"\n\n*Page 2 · 2023-11-14*"));
+        assert!(markdown.contains("Remember this\n\n*Page 9"));
+        assert!(markdown.contains("## Bookmarks\n\n- Arrakis (page=4)\n"));
+    }
+
+    #[test]
+    fn render_readwise_csv_escapes_fields() {
+        let csv = render_readwise_csv(&[book()]);
+        let lines: Vec<_> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            "Fear is the mind-killer.,Dune: Deluxe,Frank Herbert,,\"Litany, \"\"classic\"\"\",2,page,2023-11-14 22:13:20"
+        );
+        assert!(lines[2].starts_with("Remember this,"));
+    }
+
+    #[test]
+    fn calibre_json_and_file_planning() {
+        let json: serde_json::Value =
+            serde_json::from_str(&render_calibre_json(&book()).unwrap()).unwrap();
+        assert_eq!(json["type"], "calibre_highlights");
+        assert_eq!(json["highlights"][0]["style"]["which"], "green");
+        assert_eq!(json["highlights"][2]["type"], "bookmark");
+
+        let dir = Path::new("/exports");
+        let single = plan_export_files("markdown", &dir.join("notes.md"), &[book()]).unwrap();
+        assert_eq!(single[0].0, dir.join("notes.md"));
+
+        let many = plan_export_files("calibre", dir, &[book(), book()]).unwrap();
+        assert_eq!(many[0].0, dir.join("Dune_ Deluxe.json"));
+        assert_eq!(many[1].0, dir.join("Dune_ Deluxe (2).json"));
+
+        assert!(plan_export_files("pdf", dir, &[book()]).is_err());
+    }
+}
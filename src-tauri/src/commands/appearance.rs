@@ -0,0 +1,284 @@
+//! System theme (light or dark) detection
+//!
+//! On Windows and macOS the window reports the OS theme and its changes. On
+//! Linux the webview only sees the GTK theme, so the freedesktop settings
+//! portal is asked for the color scheme, falling back to GNOME settings.
+//! Changes are watched through the portal's `SettingChanged` signal, or by
+//! polling where no session bus is available.
+
+use crate::commands::events::emit_event;
+use crate::error::AppError;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+
+/// Event emitted with the new `SystemTheme` when the OS theme changes
+pub const SYSTEM_THEME_EVENT: &str = "system-theme-changed";
+
+pub const THEME_LIGHT: &str = "light";
+pub const THEME_DARK: &str = "dark";
+
+/// Last theme seen, to emit events on changes only
+pub type ThemeState = Arc<Mutex<Option<String>>>;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemTheme {
+    /// "light" or "dark"
+    pub theme: String,
+    /// Where the theme was read: "window", "portal", "gsettings" or "default"
+    pub source: String,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn theme_name(theme: tauri::Theme) -> &'static str {
+    match theme {
+        tauri::Theme::Dark => THEME_DARK,
+        _ => THEME_LIGHT,
+    }
+}
+
+/// Theme from the portal's `color-scheme` reply, such as `(<<uint32 1>>,)`
+///
+/// 1 is prefer dark, 2 prefer light and 0 no preference.
+pub fn parse_portal_color_scheme(output: &str) -> Option<&'static str> {
+    let value = output.split("uint32").nth(1)?;
+    let digits: String = value
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    match digits.as_str() {
+        "1" => Some(THEME_DARK),
+        "2" => Some(THEME_LIGHT),
+        _ => None,
+    }
+}
+
+/// Theme from GNOME's `color-scheme` or `gtk-theme` setting
+pub fn parse_gsettings_theme(color_scheme: &str, gtk_theme: &str) -> Option<&'static str> {
+    match color_scheme.trim().trim_matches('\'') {
+        "prefer-dark" => return Some(THEME_DARK),
+        "prefer-light" => return Some(THEME_LIGHT),
+        _ => {}
+    }
+    let gtk_theme = gtk_theme.trim().trim_matches('\'');
+    if gtk_theme.is_empty() {
+        None
+    } else if gtk_theme.to_ascii_lowercase().contains("dark") {
+        Some(THEME_DARK)
+    } else {
+        Some(THEME_LIGHT)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(target_os = "linux")]
+const PORTAL_ARGS: &[&str] = &[
+    "--session",
+    "--dest",
+    "org.freedesktop.portal.Desktop",
+    "--object-path",
+    "/org/freedesktop/portal/desktop",
+];
+
+/// Read the theme from the settings portal, then from GNOME settings
+#[cfg(target_os = "linux")]
+fn detect_linux_theme() -> Option<SystemTheme> {
+    let mut args = vec!["call"];
+    args.extend_from_slice(PORTAL_ARGS);
+    args.extend_from_slice(&[
+        "--method",
+        "org.freedesktop.portal.Settings.Read",
+        "org.freedesktop.appearance",
+        "color-scheme",
+    ]);
+    if let Some(theme) = command_output("gdbus", &args)
+        .as_deref()
+        .and_then(parse_portal_color_scheme)
+    {
+        return Some(SystemTheme {
+            theme: theme.to_string(),
+            source: "portal".to_string(),
+        });
+    }
+
+    let read = |key| {
+        command_output("gsettings", &["get", "org.gnome.desktop.interface", key])
+            .unwrap_or_default()
+    };
+    parse_gsettings_theme(&read("color-scheme"), &read("gtk-theme")).map(|theme| SystemTheme {
+        theme: theme.to_string(),
+        source: "gsettings".to_string(),
+    })
+}
+
+/// Detect the OS theme
+pub fn detect_system_theme(app: &tauri::AppHandle) -> SystemTheme {
+    #[cfg(target_os = "linux")]
+    if let Some(theme) = detect_linux_theme() {
+        return theme;
+    }
+    let window_theme = app
+        .webview_windows()
+        .values()
+        .find_map(|window| window.theme().ok());
+    match window_theme {
+        Some(theme) => SystemTheme {
+            theme: theme_name(theme).to_string(),
+            source: "window".to_string(),
+        },
+        None => SystemTheme {
+            theme: THEME_LIGHT.to_string(),
+            source: "default".to_string(),
+        },
+    }
+}
+
+/// Remember the theme, emitting an event when it changed
+pub fn publish_theme(app: &tauri::AppHandle, theme: SystemTheme) {
+    let changed = {
+        let state = app.state::<ThemeState>();
+        let Ok(mut last) = state.lock() else {
+            return;
+        };
+        let changed = last.is_some() && last.as_deref() != Some(theme.theme.as_str());
+        *last = Some(theme.theme.clone());
+        changed
+    };
+    if changed {
+        log::info!("System theme: {}", theme.theme);
+        emit_event(app, &theme);
+    }
+}
+
+/// Follow a theme change reported by a window
+///
+/// On Linux the window only reports GTK theme changes, so the theme is
+/// detected again, off the event loop.
+pub fn handle_window_theme_changed(app: &tauri::AppHandle, theme: tauri::Theme) {
+    if cfg!(target_os = "linux") {
+        let app = app.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            publish_theme(&app, detect_system_theme(&app));
+        });
+        return;
+    }
+    let theme = SystemTheme {
+        theme: theme_name(theme).to_string(),
+        source: "window".to_string(),
+    };
+    publish_theme(app, theme);
+}
+
+/// Start watching the OS theme where windows do not report it (Linux)
+pub fn start_theme_monitor(app: tauri::AppHandle) {
+    #[cfg(target_os = "linux")]
+    std::thread::spawn(move || watch_linux_theme(app));
+    #[cfg(not(target_os = "linux"))]
+    publish_theme(&app, detect_system_theme(&app));
+}
+
+/// Poll interval when the portal cannot be monitored
+#[cfg(target_os = "linux")]
+const THEME_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[cfg(target_os = "linux")]
+fn watch_linux_theme(app: tauri::AppHandle) {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    publish_theme(&app, detect_system_theme(&app));
+    let mut args = vec!["monitor"];
+    args.extend_from_slice(PORTAL_ARGS);
+    let monitor = Command::new("gdbus")
+        .args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Ok(mut child) = monitor {
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if line.contains("SettingChanged") && line.contains("color-scheme") {
+                    publish_theme(&app, detect_system_theme(&app));
+                }
+            }
+        }
+        let _ = child.wait();
+        log::warn!("Settings portal monitor stopped, polling the system theme");
+    }
+    loop {
+        std::thread::sleep(THEME_POLL_INTERVAL);
+        publish_theme(&app, detect_system_theme(&app));
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the OS theme (light or dark)
+#[tauri::command]
+pub async fn get_system_theme(app: tauri::AppHandle) -> Result<SystemTheme, AppError> {
+    let handle = app.clone();
+    let theme = tauri::async_runtime::spawn_blocking(move || detect_system_theme(&handle))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    publish_theme(&app, theme.clone());
+    Ok(theme)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linux_theme_settings_are_parsed() {
+        assert_eq!(
+            parse_portal_color_scheme("(<<uint32 1>>,)\n"),
+            Some(THEME_DARK)
+        );
+        assert_eq!(
+            parse_portal_color_scheme("(<uint32 2>,)"),
+            Some(THEME_LIGHT)
+        );
+        assert_eq!(parse_portal_color_scheme("(<<uint32 0>>,)"), None);
+        assert_eq!(parse_portal_color_scheme(""), None);
+
+        assert_eq!(
+            parse_gsettings_theme("'prefer-dark'\n", "'Adwaita'"),
+            Some(THEME_DARK)
+        );
+        assert_eq!(
+            parse_gsettings_theme("'default'", "'Adwaita-dark'\n"),
+            Some(THEME_DARK)
+        );
+        assert_eq!(
+            parse_gsettings_theme("'default'", "'Yaru'"),
+            Some(THEME_LIGHT)
+        );
+        assert_eq!(parse_gsettings_theme("", ""), None);
+    }
+}
@@ -0,0 +1,219 @@
+//! Passphrase encryption of backups
+//!
+//! The key is derived from the passphrase with scrypt (RFC 7914) and a
+//! random salt stored in the manifest, next to a value encrypted with the
+//! key so a wrong passphrase is reported before anything is restored. Every
+//! backed up file is encrypted on its own with AES-256-GCM, bound to its
+//! path in the backup. Manifest checksums cover the encrypted files, so a
+//! backup can be verified without the passphrase.
+
+use super::types::BackupEncryption;
+use crate::commands::hex::{from_hex, to_hex};
+use crate::error::AppError;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::fs;
+use std::path::Path;
+
+pub const BACKUP_KDF: &str = "scrypt";
+
+/// scrypt cost: N = 2^15, r = 8, p = 1 (32 MiB of memory)
+#[cfg(not(test))]
+const SCRYPT_LOG_N: u8 = 15;
+/// Cheaper in tests, which run unoptimized
+#[cfg(test)]
+const SCRYPT_LOG_N: u8 = 10;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+/// Upper bounds on costs read from a manifest, against crafted backups
+const MAX_SCRYPT_LOG_N: u8 = 20;
+const MAX_SCRYPT_P: u32 = 4;
+/// Memory scrypt needs is `128 * r * N` bytes
+const MAX_SCRYPT_MEMORY: u64 = 64 * 1024 * 1024;
+
+const KEY_CHECK: &[u8] = b"readium-backup";
+const NONCE_LEN: usize = 12;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn derive_cipher(
+    passphrase: &str,
+    salt: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+) -> Result<Aes256Gcm, AppError> {
+    let mut key = [0u8; 32];
+    let params = scrypt::Params::new(log_n, r, p, key.len())
+        .map_err(|_| AppError::InvalidInput("Unsupported backup encryption".to_string()))?;
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| AppError::Internal(format!("Key derivation failed: {}", e)))?;
+    Ok(Aes256Gcm::new(&key.into()))
+}
+
+/// Whether scrypt costs read from a manifest stay within the limits
+fn costs_within_limits(log_n: u8, r: u32, p: u32) -> bool {
+    (1..=MAX_SCRYPT_LOG_N).contains(&log_n)
+        && (1..=MAX_SCRYPT_P).contains(&p)
+        && r >= 1
+        && (128 * r as u64) << log_n <= MAX_SCRYPT_MEMORY
+}
+
+fn encrypt_bytes(cipher: &Aes256Gcm, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, AppError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: data, aad })
+        .map_err(|e| AppError::Internal(format!("Encryption failed: {}", e)))?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn decrypt_bytes(cipher: &Aes256Gcm, data: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .ok()
+}
+
+/// Derive the key of a new encrypted backup
+pub fn new_backup_key(passphrase: &str) -> Result<(BackupEncryption, Aes256Gcm), AppError> {
+    if passphrase.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Backup passphrase cannot be empty".to_string(),
+        ));
+    }
+    let mut salt = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| AppError::Internal("Failed to generate a backup salt".to_string()))?;
+    let cipher = derive_cipher(passphrase, &salt, SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P)?;
+    let encryption = BackupEncryption {
+        kdf: BACKUP_KDF.to_string(),
+        log_n: SCRYPT_LOG_N,
+        r: SCRYPT_R,
+        p: SCRYPT_P,
+        salt: to_hex(&salt),
+        check: to_hex(&encrypt_bytes(&cipher, KEY_CHECK, b"")?),
+    };
+    Ok((encryption, cipher))
+}
+
+/// Derive the key of an encrypted backup, checking the passphrase
+pub fn unlock_backup_key(
+    passphrase: &str,
+    encryption: &BackupEncryption,
+) -> Result<Aes256Gcm, AppError> {
+    let salt = from_hex(&encryption.salt);
+    let check = from_hex(&encryption.check);
+    let supported = encryption.kdf == BACKUP_KDF
+        && costs_within_limits(encryption.log_n, encryption.r, encryption.p);
+    let (Some(salt), Some(check), true) = (salt, check, supported) else {
+        return Err(AppError::InvalidInput(
+            "Unsupported backup encryption".to_string(),
+        ));
+    };
+    let cipher = derive_cipher(
+        passphrase,
+        &salt,
+        encryption.log_n,
+        encryption.r,
+        encryption.p,
+    )?;
+    if decrypt_bytes(&cipher, &check, b"").as_deref() != Some(KEY_CHECK) {
+        return Err(AppError::InvalidInput(
+            "Wrong backup passphrase".to_string(),
+        ));
+    }
+    Ok(cipher)
+}
+
+/// Write a backed up file encrypted, bound to its path in the backup
+pub fn encrypt_file(
+    cipher: &Aes256Gcm,
+    data: &[u8],
+    path: &str,
+    target: &Path,
+) -> Result<(), AppError> {
+    fs::write(target, encrypt_bytes(cipher, data, path.as_bytes())?)?;
+    Ok(())
+}
+
+/// Decrypt a backed up file to `target`
+pub fn decrypt_file(
+    cipher: &Aes256Gcm,
+    file: &Path,
+    path: &str,
+    target: &Path,
+) -> Result<(), AppError> {
+    let data = decrypt_bytes(cipher, &fs::read(file)?, path.as_bytes())
+        .ok_or_else(|| AppError::InvalidInput(format!("Backup file {} failed to decrypt", path)))?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(target, data)?;
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn manifest_costs_are_limited() {
+        assert!(costs_within_limits(SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P));
+        assert!(costs_within_limits(15, 8, 4));
+        // 128 * 8 * 2^17 bytes is 128 MiB
+        assert!(!costs_within_limits(17, 8, 1));
+        assert!(!costs_within_limits(10, 8, 16));
+        assert!(!costs_within_limits(10, 0, 1));
+
+        let (mut encryption, _) = new_backup_key("correct horse").unwrap();
+        encryption.r = 1 << 16;
+        assert!(matches!(
+            unlock_backup_key("correct horse", &encryption),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn files_need_the_passphrase_and_their_path() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("settings.json");
+        let restored = dir.path().join("restored.json");
+
+        let (encryption, cipher) = new_backup_key("correct horse").unwrap();
+        encrypt_file(&cipher, br#"{"theme":"dark"}"#, "settings.json", &file).unwrap();
+        assert!(!fs::read_to_string(&file)
+            .unwrap_or_default()
+            .contains("dark"));
+
+        assert!(matches!(
+            unlock_backup_key("wrong", &encryption),
+            Err(AppError::InvalidInput(_))
+        ));
+        let cipher = unlock_backup_key("correct horse", &encryption).unwrap();
+        assert!(decrypt_file(&cipher, &file, "mcp_servers.json", &restored).is_err());
+        decrypt_file(&cipher, &file, "settings.json", &restored).unwrap();
+        assert_eq!(
+            fs::read_to_string(&restored).unwrap(),
+            r#"{"theme":"dark"}"#
+        );
+        assert!(new_backup_key("").is_err());
+    }
+}
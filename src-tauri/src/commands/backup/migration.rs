@@ -0,0 +1,451 @@
+//! One-archive migration to another machine
+//!
+//! A migration archive is a zip holding a full backup below `backup/` and a
+//! `migration.json` listing what the backup cannot carry: the providers
+//! that had an API key, and the MCP servers, whose commands have to be
+//! installed again. Importing extracts the backup next to the local ones,
+//! so it can be restored again later, and restores it.
+
+use super::restore::{reload_restored_data, restore_backup_in};
+use super::snapshot::{
+    create_backup_in, get_backups_dir, resolve_entry_path, validate_backup_id, validate_categories,
+    with_live_databases, LiveDatabase,
+};
+use super::types::{
+    ExternalDependency, MigrationImportReport, MigrationManifest, MigrationPreview, RestoreReport,
+};
+use crate::commands::ai_keys::saved_api_key_providers;
+use crate::commands::conversion::find_executable;
+use crate::commands::mcp::{load_mcp_servers_from_file, MCP_SERVERS_FILE};
+use crate::error::AppError;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const MIGRATION_MANIFEST_ENTRY: &str = "migration.json";
+const MIGRATION_BACKUP_PREFIX: &str = "backup/";
+const MIGRATION_FORMAT_VERSION: u32 = 1;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn zip_err(e: zip::result::ZipError) -> AppError {
+    AppError::InvalidInput(format!("Invalid migration archive: {}", e))
+}
+
+/// MCP servers configured in `app_data`
+fn mcp_dependencies(app_data: &Path) -> Vec<ExternalDependency> {
+    let store = match load_mcp_servers_from_file(&app_data.join(MCP_SERVERS_FILE)) {
+        Ok(store) => store,
+        Err(e) => {
+            log::warn!("Skipping unreadable MCP servers in migration: {}", e);
+            return Vec::new();
+        }
+    };
+    store
+        .servers
+        .into_iter()
+        .map(|server| ExternalDependency {
+            kind: "mcp-server".to_string(),
+            name: server.name,
+            command: server.command,
+            url: server.url,
+        })
+        .collect()
+}
+
+fn search_path() -> Vec<PathBuf> {
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default()
+}
+
+/// Dependencies whose command is not found in `dirs`
+pub fn missing_dependencies(
+    dependencies: &[ExternalDependency],
+    dirs: &[PathBuf],
+) -> Vec<ExternalDependency> {
+    dependencies
+        .iter()
+        .filter(|dependency| {
+            let Some(command) = dependency.command.as_deref() else {
+                return false;
+            };
+            let path = Path::new(command);
+            if path.components().count() > 1 {
+                !path.is_file()
+            } else {
+                find_executable(command, dirs).is_none()
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Back up all app data into a migration archive at `path`
+pub fn export_everything_to(
+    app_data: &Path,
+    path: &Path,
+    databases: &[LiveDatabase],
+    app_version: &str,
+    now: i64,
+    passphrase: Option<&str>,
+    api_key_providers: Vec<String>,
+) -> Result<MigrationManifest, AppError> {
+    let staging = app_data.join(format!(".export-{}", uuid::Uuid::new_v4()));
+    let temp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let exported = (|| {
+        let categories = validate_categories(None)?;
+        let backup = create_backup_in(
+            app_data,
+            &staging,
+            databases,
+            &categories,
+            app_version,
+            now,
+            passphrase,
+        )?;
+        let manifest = MigrationManifest {
+            version: MIGRATION_FORMAT_VERSION,
+            exported_at: now,
+            app_version: app_version.to_string(),
+            platform: std::env::consts::OS.to_string(),
+            backup_id: backup.id.clone(),
+            categories: backup.categories,
+            encrypted: backup.encrypted,
+            api_key_providers,
+            dependencies: mcp_dependencies(app_data),
+        };
+
+        let backup_dir = staging.join(&backup.id);
+        let mut zip = ZipWriter::new(BufWriter::new(File::create(&temp_path)?));
+        let options = SimpleFileOptions::default();
+        zip.start_file(MIGRATION_MANIFEST_ENTRY, options)
+            .map_err(zip_err)?;
+        zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+        for entry in WalkDir::new(&backup_dir).min_depth(1) {
+            let entry = entry.map_err(|e| AppError::Io(e.into()))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry
+                .path()
+                .strip_prefix(&backup_dir)
+                .map_err(|e| AppError::Internal(e.to_string()))?;
+            let name = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            zip.start_file(format!("{}{}", MIGRATION_BACKUP_PREFIX, name), options)
+                .map_err(zip_err)?;
+            std::io::copy(&mut File::open(entry.path())?, &mut zip)?;
+        }
+        zip.finish().map_err(zip_err)?.flush()?;
+        fs::rename(&temp_path, path)?;
+        Ok(manifest)
+    })();
+    let _ = fs::remove_dir_all(&staging);
+    if exported.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    exported
+}
+
+fn read_migration_manifest(archive: &mut ZipArchive<File>) -> Result<MigrationManifest, AppError> {
+    let mut text = String::new();
+    archive
+        .by_name(MIGRATION_MANIFEST_ENTRY)
+        .map_err(zip_err)?
+        .read_to_string(&mut text)?;
+    let manifest: MigrationManifest = serde_json::from_str(&text)?;
+    if manifest.version > MIGRATION_FORMAT_VERSION {
+        return Err(AppError::InvalidInput(format!(
+            "Migration format version {} is newer than this app supports",
+            manifest.version
+        )));
+    }
+    validate_backup_id(&manifest.backup_id)?;
+    Ok(manifest)
+}
+
+/// Read the manifest of the migration archive at `path`
+pub fn open_migration_archive(path: &Path) -> Result<MigrationManifest, AppError> {
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(zip_err)?;
+    read_migration_manifest(&mut archive)
+}
+
+/// Extract the backup of a migration archive into `backups_dir`, returning
+/// its id there
+fn extract_backup(
+    archive: &mut ZipArchive<File>,
+    manifest: &MigrationManifest,
+    backups_dir: &Path,
+) -> Result<String, AppError> {
+    let mut id = manifest.backup_id.clone();
+    let mut suffix = 1;
+    while backups_dir.join(&id).exists() {
+        suffix += 1;
+        id = format!("{}-{}", manifest.backup_id, suffix);
+    }
+    let partial = backups_dir.join(format!("{}.partial", id));
+    let extracted = (|| {
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(zip_err)?;
+            let Some(name) = entry.name().strip_prefix(MIGRATION_BACKUP_PREFIX) else {
+                continue;
+            };
+            if entry.is_dir() || name.is_empty() {
+                continue;
+            }
+            let target = resolve_entry_path(&partial, name)?;
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            std::io::copy(&mut entry, &mut File::create(&target)?)?;
+        }
+        fs::rename(&partial, backups_dir.join(&id))?;
+        Ok(id)
+    })();
+    if extracted.is_err() {
+        let _ = fs::remove_dir_all(&partial);
+    }
+    extracted
+}
+
+/// Import a migration archive: extract its backup and restore it (all its
+/// categories, or the given ones)
+///
+/// The extracted backup is removed again when the restore fails, e.g. for
+/// a wrong passphrase, so the import can simply be retried.
+pub fn import_everything_in(
+    app_data: &Path,
+    backups_dir: &Path,
+    path: &Path,
+    categories: Option<&[String]>,
+    passphrase: Option<&str>,
+    databases: &mut [LiveDatabase],
+) -> Result<(MigrationManifest, RestoreReport), AppError> {
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(zip_err)?;
+    let manifest = read_migration_manifest(&mut archive)?;
+    fs::create_dir_all(backups_dir)?;
+    let id = extract_backup(&mut archive, &manifest, backups_dir)?;
+    match restore_backup_in(
+        app_data,
+        backups_dir,
+        &id,
+        categories,
+        passphrase,
+        databases,
+    ) {
+        Ok(report) => Ok((manifest, report)),
+        Err(e) => {
+            let _ = fs::remove_dir_all(backups_dir.join(&id));
+            Err(e)
+        }
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Export all app data into one archive for moving to another machine,
+/// optionally encrypted with a passphrase
+#[tauri::command]
+pub async fn export_everything(
+    app: tauri::AppHandle,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<MigrationManifest, AppError> {
+    let app_data = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    let app_version = app.package_info().version.to_string();
+    let manifest = tauri::async_runtime::spawn_blocking(move || {
+        let api_key_providers = saved_api_key_providers();
+        with_live_databases(&app, |databases| {
+            export_everything_to(
+                &app_data,
+                Path::new(&path),
+                databases,
+                &app_version,
+                chrono::Utc::now().timestamp(),
+                passphrase.as_deref(),
+                api_key_providers,
+            )
+        })
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    log::info!("App data exported for migration: {}", manifest.backup_id);
+    Ok(manifest)
+}
+
+/// Describe a migration archive before importing it
+#[tauri::command]
+pub async fn preview_migration_archive(path: String) -> Result<MigrationPreview, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let manifest = open_migration_archive(Path::new(&path))?;
+        let missing_dependencies = missing_dependencies(&manifest.dependencies, &search_path());
+        Ok(MigrationPreview {
+            manifest,
+            missing_dependencies,
+        })
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Import a migration archive on a new machine (all its categories, or the
+/// given ones)
+#[tauri::command]
+pub async fn import_everything(
+    app: tauri::AppHandle,
+    path: String,
+    categories: Option<Vec<String>>,
+    passphrase: Option<String>,
+) -> Result<MigrationImportReport, AppError> {
+    let app_data = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    let backups_dir = get_backups_dir(&app)?;
+    let handle = app.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        let (manifest, restore) = with_live_databases(&handle, |databases| {
+            import_everything_in(
+                &app_data,
+                &backups_dir,
+                Path::new(&path),
+                categories.as_deref(),
+                passphrase.as_deref(),
+                databases,
+            )
+        })?;
+        let saved = saved_api_key_providers();
+        Ok::<_, AppError>(MigrationImportReport {
+            restore,
+            api_key_providers: manifest
+                .api_key_providers
+                .into_iter()
+                .filter(|provider| !saved.contains(provider))
+                .collect(),
+            missing_dependencies: missing_dependencies(&manifest.dependencies, &search_path()),
+        })
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    reload_restored_data(&app, &report.restore.restored_categories);
+    log::info!(
+        "Migration archive imported: {}",
+        report.restore.restored_categories.join(", ")
+    );
+    Ok(report)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::library::{LIBRARY_DB_FILE, LIBRARY_MIGRATIONS};
+    use crate::db;
+    use rusqlite::Connection;
+    use tempfile::tempdir;
+
+    fn live(conn: &mut Connection) -> [LiveDatabase<'_>; 1] {
+        [LiveDatabase {
+            file: LIBRARY_DB_FILE,
+            conn,
+            on_restore: None,
+        }]
+    }
+
+    fn collection_count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM collections", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn migration_archives_move_app_data_to_a_new_machine() {
+        let dir = tempdir().unwrap();
+        let (old, new) = (dir.path().join("old"), dir.path().join("new"));
+        fs::create_dir_all(&old).unwrap();
+        fs::create_dir_all(&new).unwrap();
+        fs::write(
+            old.join(MCP_SERVERS_FILE),
+            r#"{"version":1,"updatedAt":0,"servers":[{"id":"s1","name":"Notes",
+                "type":"stdio","enabled":true,"command":"readium-missing-tool",
+                "createdAt":0,"updatedAt":0}]}"#,
+        )
+        .unwrap();
+        let mut library = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        library
+            .execute_batch(
+                "INSERT INTO collections (id, name, kind, created_at, updated_at)
+                 VALUES ('c1', 'Moved', 'collection', 1, 1)",
+            )
+            .unwrap();
+
+        let archive = dir.path().join("move.zip");
+        let manifest = export_everything_to(
+            &old,
+            &archive,
+            &live(&mut library),
+            "1.0.0",
+            0,
+            Some("secret"),
+            vec!["openai".to_string()],
+        )
+        .unwrap();
+        assert!(manifest.encrypted);
+        assert_eq!(manifest.dependencies.len(), 1);
+        assert_eq!(open_migration_archive(&archive).unwrap(), manifest);
+        assert_eq!(
+            missing_dependencies(&manifest.dependencies, &[]),
+            manifest.dependencies
+        );
+        // Only the archive is left behind
+        assert_eq!(fs::read_dir(&old).unwrap().count(), 1);
+
+        let mut fresh = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let backups = new.join("backups");
+        assert!(import_everything_in(
+            &new,
+            &backups,
+            &archive,
+            None,
+            Some("wrong"),
+            &mut live(&mut fresh),
+        )
+        .is_err());
+        assert_eq!(fs::read_dir(&backups).unwrap().count(), 0);
+
+        let (imported, report) = import_everything_in(
+            &new,
+            &backups,
+            &archive,
+            None,
+            Some("secret"),
+            &mut live(&mut fresh),
+        )
+        .unwrap();
+        assert_eq!(imported.api_key_providers, vec!["openai"]);
+        assert!(report.restored_categories.contains(&"mcp".to_string()));
+        assert_eq!(collection_count(&fresh), 1);
+        assert_eq!(
+            fs::read(new.join(MCP_SERVERS_FILE)).unwrap(),
+            fs::read(old.join(MCP_SERVERS_FILE)).unwrap()
+        );
+        assert!(backups.join(&manifest.backup_id).is_dir());
+    }
+}
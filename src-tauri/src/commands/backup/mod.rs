@@ -0,0 +1,22 @@
+//! Versioned backups of app data
+//!
+//! Backups are timestamped snapshots in the app data directory:
+//! - Library and conversation databases, copied from their open connections
+//! - Conversation attachments, MCP servers, usage statistics and settings
+//! - A manifest with the size and checksum of every file
+//! - Restores of all or selected categories, verified before anything changes
+//! - Optional passphrase encryption, so backups can be kept on shared drives
+//! - Migration archives with everything needed to move to another machine
+
+mod types;
+mod crypto;
+mod snapshot;
+mod restore;
+mod migration;
+
+// Re-export all public items
+pub use types::*;
+pub use crypto::*;
+pub use snapshot::*;
+pub use restore::*;
+pub use migration::*;
@@ -0,0 +1,808 @@
+//! Restoring app data from a backup
+//!
+//! Every selected file is checked against the manifest, then staged and its
+//! databases migrated, since the backup may come from an older version,
+//! before anything is replaced; a category whose database is newer than this
+//! app supports is skipped. Open databases are overwritten through SQLite's
+//! online backup API, other files are moved into place. Files in backed up
+//! directories that are not in the backup are left alone.
+//!
+//! Restores can also select `annotations`: the bookmarks of the backed up
+//! library replace the current ones, and the rest of the library is kept.
+//! What the backend keeps in memory from restored files (custom providers,
+//! prices, settings) is reloaded afterwards, so no restart is needed.
+
+use super::crypto::{decrypt_file, unlock_backup_key};
+use super::snapshot::{
+    database_migrations, database_schema_version, get_backups_dir, read_manifest,
+    resolve_entry_path, validate_backup_id, validate_categories, verify_backup,
+    with_live_databases, LiveDatabase, BACKUP_DATA_DIR,
+};
+use super::types::{BackupFileEntry, RestoreReport, SkippedCategory};
+use crate::commands::ai_pricing::init_ai_pricing;
+use crate::commands::ai_providers::init_custom_providers;
+use crate::commands::library::{LIBRARY_DB_FILE, LIBRARY_MIGRATIONS};
+use crate::commands::messages::init_backend_locale;
+use crate::commands::settings::announce_settings;
+use crate::db;
+use crate::error::AppError;
+use aes_gcm::Aes256Gcm;
+use rusqlite::backup::Progress;
+use rusqlite::{params, Connection, DatabaseName};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+/// Restore-only category: the bookmarks of the backed up library database
+pub const ANNOTATIONS_CATEGORY: &str = "annotations";
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Check the categories selected for a restore
+///
+/// Besides the backup categories, `annotations` is accepted; it is dropped
+/// when the whole library is restored anyway.
+fn validate_restore_categories(categories: &[String]) -> Result<Vec<String>, AppError> {
+    let (annotations, others): (Vec<String>, Vec<String>) = categories
+        .iter()
+        .cloned()
+        .partition(|c| c == ANNOTATIONS_CATEGORY);
+    let mut valid = if others.is_empty() {
+        Vec::new()
+    } else {
+        validate_categories(Some(&others))?
+    };
+    if !annotations.is_empty() && !valid.iter().any(|c| c == "library") {
+        valid.push(ANNOTATIONS_CATEGORY.to_string());
+    }
+    Ok(valid)
+}
+
+/// Whether a backup file is needed to restore a category
+fn restores_category(file: &BackupFileEntry, category: &str) -> bool {
+    match category {
+        ANNOTATIONS_CATEGORY => file.kind == "database" && file.path == LIBRARY_DB_FILE,
+        _ => file.category == category,
+    }
+}
+
+/// Replace the bookmarks of a library with those of a staged library
+/// database; bookmarks of documents the library no longer has are dropped
+fn restore_bookmarks(conn: &mut Connection, staged_library: &Path) -> Result<(), AppError> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS staged",
+        params![staged_library.to_string_lossy()],
+    )?;
+    let restored = (|| {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM main.bookmarks", [])?;
+        let count = tx.execute(
+            "INSERT INTO main.bookmarks (id, document_id, locator, label, created_at)
+             SELECT id, document_id, locator, label, created_at FROM staged.bookmarks
+             WHERE document_id IN (SELECT id FROM main.documents)",
+            [],
+        )?;
+        tx.commit()?;
+        log::info!("Restored {} bookmarks", count);
+        Ok(())
+    })();
+    conn.execute("DETACH DATABASE staged", [])?;
+    restored
+}
+
+/// Restore bookmarks into the open library, or its file when it is not open
+fn restore_bookmarks_into(
+    app_data: &Path,
+    databases: &mut [LiveDatabase],
+    staged_library: &Path,
+) -> Result<(), AppError> {
+    match databases.iter_mut().find(|db| db.file == LIBRARY_DB_FILE) {
+        Some(db) => restore_bookmarks(db.conn, staged_library),
+        None => {
+            let path = app_data.join(LIBRARY_DB_FILE);
+            let mut conn = db::open_database(&path, LIBRARY_MIGRATIONS)?;
+            restore_bookmarks(&mut conn, staged_library)
+        }
+    }
+}
+
+/// Why a category of a backup cannot be restored, if it cannot
+fn category_incompatibility(
+    data: &Path,
+    files: &[&BackupFileEntry],
+) -> Result<Option<String>, AppError> {
+    for file in files.iter().filter(|f| f.kind == "database") {
+        let Some(migrations) = database_migrations(&file.path) else {
+            continue;
+        };
+        let version = match file.schema_version {
+            Some(version) => version,
+            None => database_schema_version(&resolve_entry_path(data, &file.path)?)?,
+        };
+        // Older schemas are migrated after the restore, newer ones cannot be
+        if version > migrations.len() {
+            return Ok(Some(format!(
+                "{} has schema version {}, this app supports up to {}",
+                file.path,
+                version,
+                migrations.len()
+            )));
+        }
+    }
+    Ok(None)
+}
+
+/// Copy, or decrypt, the selected backup files into a staging directory
+fn stage_files(
+    data: &Path,
+    staging: &Path,
+    files: &[&BackupFileEntry],
+    cipher: Option<&Aes256Gcm>,
+) -> Result<(), AppError> {
+    for file in files {
+        let source = resolve_entry_path(data, &file.path)?;
+        let target = resolve_entry_path(staging, &file.path)?;
+        match cipher {
+            Some(cipher) => decrypt_file(cipher, &source, &file.path, &target)?,
+            None => {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(&source, &target)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pick the categories that can be restored from the staged files, and bring
+/// their databases up to the current schema
+fn prepare_categories<'a>(
+    staged: &Path,
+    requested: Vec<String>,
+    files: &[&'a BackupFileEntry],
+    report: &mut RestoreReport,
+) -> Result<Vec<(String, Vec<&'a BackupFileEntry>)>, AppError> {
+    let mut ready = Vec::new();
+    for category in requested {
+        let category_files: Vec<_> = files
+            .iter()
+            .copied()
+            .filter(|f| restores_category(f, &category))
+            .collect();
+        if category_files.is_empty() {
+            report.missing_categories.push(category);
+            continue;
+        }
+        if let Some(reason) = category_incompatibility(staged, &category_files)? {
+            log::warn!("Not restoring backup category {}: {}", category, reason);
+            report
+                .skipped_categories
+                .push(SkippedCategory { category, reason });
+            continue;
+        }
+        for file in category_files.iter().filter(|f| f.kind == "database") {
+            let migrations = database_migrations(&file.path).unwrap_or_default();
+            let mut conn = Connection::open(resolve_entry_path(staged, &file.path)?)?;
+            db::apply_migrations(&mut conn, migrations)?;
+        }
+        ready.push((category, category_files));
+    }
+    Ok(ready)
+}
+
+/// A replaced piece of app data and how to put it back
+enum Replaced {
+    /// A file, moved aside if there was one
+    File(PathBuf, Option<PathBuf>),
+    /// An open database, copied aside
+    Database(usize, PathBuf),
+}
+
+fn reload_database(db: &mut LiveDatabase, file: &Path) -> Result<(), AppError> {
+    db.conn
+        .restore(DatabaseName::Main, file, None::<fn(Progress)>)?;
+    if let Some(on_restore) = db.on_restore {
+        on_restore(db.conn)?;
+    }
+    Ok(())
+}
+
+/// Swap staged files into the app data, keeping what they replace in
+/// `previous` so that a failure part way can be undone
+fn swap_in(
+    app_data: &Path,
+    staged: &Path,
+    previous: &Path,
+    files: &[&BackupFileEntry],
+    databases: &mut [LiveDatabase],
+    replaced: &mut Vec<Replaced>,
+) -> Result<(), AppError> {
+    for file in files {
+        let source = resolve_entry_path(staged, &file.path)?;
+        let saved = resolve_entry_path(previous, &file.path)?;
+        if let Some(parent) = saved.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let live = databases
+            .iter_mut()
+            .position(|db| file.kind == "database" && db.file == file.path);
+        match live {
+            Some(index) => {
+                let db = &mut databases[index];
+                db.conn
+                    .backup(DatabaseName::Main, &saved, None::<fn(Progress)>)?;
+                replaced.push(Replaced::Database(index, saved));
+                reload_database(db, &source)?;
+            }
+            None => {
+                let target = resolve_entry_path(app_data, &file.path)?;
+                if target.is_file() {
+                    fs::rename(&target, &saved)?;
+                    replaced.push(Replaced::File(target.clone(), Some(saved)));
+                } else {
+                    replaced.push(Replaced::File(target.clone(), None));
+                }
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(&source, &target)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Put back what a failed swap replaced, newest first
+fn undo_swap(replaced: Vec<Replaced>, databases: &mut [LiveDatabase]) {
+    for item in replaced.into_iter().rev() {
+        let undone = match &item {
+            Replaced::File(target, Some(saved)) => {
+                fs::rename(saved, target).map_err(AppError::from)
+            }
+            Replaced::File(target, None) => match fs::remove_file(target) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            Replaced::Database(index, saved) => reload_database(&mut databases[*index], saved),
+        };
+        if let Err(e) = undone {
+            log::error!("Failed to undo part of a backup restore: {}", e);
+        }
+    }
+}
+
+/// Replace app data with the files of the requested categories in `data`
+///
+/// Everything is staged first, in the app data directory and never next to
+/// the backup: decrypted, checked and migrated. App data is only replaced
+/// once that succeeded, and put back if replacing it fails part way, or if
+/// the bookmarks cannot be restored afterwards.
+fn restore_categories(
+    app_data: &Path,
+    data: &Path,
+    requested: Vec<String>,
+    files: &[&BackupFileEntry],
+    cipher: Option<&Aes256Gcm>,
+    databases: &mut [LiveDatabase],
+    report: &mut RestoreReport,
+) -> Result<(), AppError> {
+    let staging = app_data.join(format!(".restore-{}", uuid::Uuid::new_v4()));
+    let staged = staging.join("staged");
+    let previous = staging.join("previous");
+    let restored = (|| {
+        stage_files(data, &staged, files, cipher)?;
+        let ready = prepare_categories(&staged, requested, files, report)?;
+        let ready_files: Vec<_> = ready
+            .iter()
+            .filter(|(category, _)| category != ANNOTATIONS_CATEGORY)
+            .flat_map(|(_, files)| files.iter().copied())
+            .collect();
+        let annotations = ready
+            .iter()
+            .find(|(category, _)| category == ANNOTATIONS_CATEGORY)
+            .map(|(_, files)| resolve_entry_path(&staged, &files[0].path))
+            .transpose()?;
+        let mut replaced = Vec::new();
+        let swapped = swap_in(
+            app_data,
+            &staged,
+            &previous,
+            &ready_files,
+            databases,
+            &mut replaced,
+        )
+        .and_then(|()| match &annotations {
+            Some(staged_library) => restore_bookmarks_into(app_data, databases, staged_library),
+            None => Ok(()),
+        });
+        if let Err(e) = swapped {
+            undo_swap(replaced, databases);
+            return Err(e);
+        }
+        report.restored_files += ready_files.len();
+        report
+            .restored_categories
+            .extend(ready.into_iter().map(|(category, _)| category));
+        Ok(())
+    })();
+    let _ = fs::remove_dir_all(&staging);
+    restored
+}
+
+/// Restore a backup (all its categories, or the given ones) into `app_data`
+///
+/// Categories holding a database newer than this app supports are skipped
+/// and reported, the others are restored. Encrypted backups need their
+/// passphrase.
+pub fn restore_backup_in(
+    app_data: &Path,
+    backups_dir: &Path,
+    id: &str,
+    categories: Option<&[String]>,
+    passphrase: Option<&str>,
+    databases: &mut [LiveDatabase],
+) -> Result<RestoreReport, AppError> {
+    let backup_dir = backups_dir.join(validate_backup_id(id)?);
+    let manifest = read_manifest(&backup_dir)?;
+    let requested = match categories {
+        Some(categories) if !categories.is_empty() => validate_restore_categories(categories)?,
+        _ => manifest.categories.clone(),
+    };
+    let files: Vec<_> = manifest
+        .files
+        .iter()
+        .filter(|f| requested.iter().any(|c| restores_category(f, c)))
+        .collect();
+    verify_backup(&backup_dir, files.iter().copied())?;
+    let cipher = match (&manifest.encryption, passphrase) {
+        (Some(encryption), Some(passphrase)) => Some(unlock_backup_key(passphrase, encryption)?),
+        (Some(_), None) => {
+            return Err(AppError::InvalidInput(
+                "This backup is encrypted, a passphrase is required".to_string(),
+            ))
+        }
+        (None, _) => None,
+    };
+
+    let mut report = RestoreReport {
+        backup_id: manifest.id.clone(),
+        ..Default::default()
+    };
+    restore_categories(
+        app_data,
+        &backup_dir.join(BACKUP_DATA_DIR),
+        requested,
+        &files,
+        cipher.as_ref(),
+        databases,
+        &mut report,
+    )?;
+    Ok(report)
+}
+
+/// Reload what the backend keeps in memory from restored files
+///
+/// Settings are emitted as `setting-changed` events, which the frontend and
+/// the backend caches of settings (retry policy, failover, provider headers,
+/// moderation, debug log) listen for.
+pub fn reload_restored_data(app: &tauri::AppHandle, categories: &[String]) {
+    for category in categories {
+        let reloaded = match category.as_str() {
+            "providers" => init_custom_providers(app),
+            "usage" => {
+                init_ai_pricing(app);
+                Ok(())
+            }
+            "settings" => {
+                init_backend_locale(app);
+                announce_settings(app)
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = reloaded {
+            log::error!("Failed to reload restored {}: {}", category, e);
+        }
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Restore app data from a backup (all its categories, or the given ones)
+#[tauri::command]
+pub async fn restore_backup(
+    app: tauri::AppHandle,
+    id: String,
+    categories: Option<Vec<String>>,
+    passphrase: Option<String>,
+) -> Result<RestoreReport, AppError> {
+    let app_data = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    let backups_dir = get_backups_dir(&app)?;
+    let handle = app.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        with_live_databases(&handle, |databases| {
+            restore_backup_in(
+                &app_data,
+                &backups_dir,
+                &id,
+                categories.as_deref(),
+                passphrase.as_deref(),
+                databases,
+            )
+        })
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    reload_restored_data(&app, &report.restored_categories);
+    log::info!(
+        "Backup {} restored: {}",
+        report.backup_id,
+        report.restored_categories.join(", ")
+    );
+    Ok(report)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::snapshot::create_backup_in;
+    use super::*;
+    use crate::commands::conversations::CONVERSATION_ATTACHMENTS_DIR;
+    use crate::commands::library::{LIBRARY_DB_FILE, LIBRARY_MIGRATIONS};
+    use crate::commands::mcp::MCP_SERVERS_FILE;
+    use tempfile::tempdir;
+
+    fn live(conn: &mut Connection) -> [LiveDatabase<'_>; 1] {
+        [LiveDatabase {
+            file: LIBRARY_DB_FILE,
+            conn,
+            on_restore: None,
+        }]
+    }
+
+    fn collection_names(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM collections ORDER BY name")
+            .unwrap();
+        let names = stmt.query_map([], |row| row.get(0)).unwrap();
+        names.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn restore_replaces_databases_and_files() {
+        let dir = tempdir().unwrap();
+        let app_data = dir.path().join("app");
+        let backups = app_data.join("backups");
+        fs::create_dir_all(&app_data).unwrap();
+        fs::write(app_data.join(MCP_SERVERS_FILE), "before").unwrap();
+        let mut library = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        library
+            .execute_batch(
+                "INSERT INTO collections (id, name, kind, created_at, updated_at)
+                 VALUES ('c1', 'Kept', 'collection', 1, 1)",
+            )
+            .unwrap();
+
+        let categories = vec!["library".to_string(), "mcp".to_string()];
+        let backup = create_backup_in(
+            &app_data,
+            &backups,
+            &live(&mut library),
+            &categories,
+            "1.0.0",
+            0,
+            None,
+        )
+        .unwrap();
+
+        library
+            .execute_batch(
+                "INSERT INTO collections (id, name, kind, created_at, updated_at)
+                 VALUES ('c2', 'Later', 'collection', 2, 2)",
+            )
+            .unwrap();
+        fs::write(app_data.join(MCP_SERVERS_FILE), "after").unwrap();
+
+        // Only the selected category is restored
+        let only_mcp = vec!["mcp".to_string(), "usage".to_string()];
+        let report = restore_backup_in(
+            &app_data,
+            &backups,
+            &backup.id,
+            Some(&only_mcp),
+            None,
+            &mut live(&mut library),
+        )
+        .unwrap();
+        assert_eq!(report.restored_categories, vec!["mcp"]);
+        assert_eq!(report.missing_categories, vec!["usage"]);
+        assert_eq!(
+            fs::read_to_string(app_data.join(MCP_SERVERS_FILE)).unwrap(),
+            "before"
+        );
+        assert_eq!(collection_names(&library), vec!["Kept", "Later"]);
+
+        let report = restore_backup_in(
+            &app_data,
+            &backups,
+            &backup.id,
+            None,
+            None,
+            &mut live(&mut library),
+        )
+        .unwrap();
+        assert_eq!(report.restored_files, 2);
+        assert_eq!(collection_names(&library), vec!["Kept"]);
+        assert_eq!(
+            db::schema_version(&library).unwrap(),
+            LIBRARY_MIGRATIONS.len()
+        );
+
+        assert!(matches!(
+            restore_backup_in(
+                &app_data,
+                &backups,
+                "missing",
+                None,
+                None,
+                &mut live(&mut library)
+            ),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn annotations_restore_only_bookmarks() {
+        let dir = tempdir().unwrap();
+        let app_data = dir.path().join("app");
+        let backups = app_data.join("backups");
+        fs::create_dir_all(&app_data).unwrap();
+        let mut library = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        library
+            .execute_batch(
+                "INSERT INTO documents (id, path, file_name, hash, format, added_at, updated_at)
+                 VALUES ('d1', '/a.pdf', 'a.pdf', 'h', 'pdf', 1, 1);
+                 INSERT INTO bookmarks (id, document_id, locator, created_at)
+                 VALUES ('b1', 'd1', 'page=1', 1)",
+            )
+            .unwrap();
+
+        let categories = vec!["library".to_string()];
+        let backup = create_backup_in(
+            &app_data,
+            &backups,
+            &live(&mut library),
+            &categories,
+            "1.0.0",
+            0,
+            None,
+        )
+        .unwrap();
+
+        library
+            .execute_batch(
+                "DELETE FROM bookmarks;
+                 INSERT INTO bookmarks (id, document_id, locator, created_at)
+                 VALUES ('b2', 'd1', 'page=2', 2);
+                 INSERT INTO collections (id, name, kind, created_at, updated_at)
+                 VALUES ('c1', 'Later', 'collection', 2, 2)",
+            )
+            .unwrap();
+
+        let annotations = vec![ANNOTATIONS_CATEGORY.to_string()];
+        let report = restore_backup_in(
+            &app_data,
+            &backups,
+            &backup.id,
+            Some(&annotations),
+            None,
+            &mut live(&mut library),
+        )
+        .unwrap();
+        assert_eq!(report.restored_categories, annotations);
+        assert_eq!(report.restored_files, 0);
+        let bookmarks: Vec<String> = library
+            .prepare("SELECT id FROM bookmarks")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(bookmarks, vec!["b1"]);
+        assert_eq!(collection_names(&library), vec!["Later"]);
+
+        // Restoring the library as well makes the annotations redundant
+        assert_eq!(
+            validate_restore_categories(&[ANNOTATIONS_CATEGORY.to_string(), "library".to_string()])
+                .unwrap(),
+            vec!["library"]
+        );
+    }
+
+    #[test]
+    fn restore_skips_categories_from_newer_schemas() {
+        let dir = tempdir().unwrap();
+        let app_data = dir.path().join("app");
+        let backups = app_data.join("backups");
+        fs::create_dir_all(&app_data).unwrap();
+        fs::write(app_data.join(MCP_SERVERS_FILE), "backed up").unwrap();
+        let newer: Vec<&str> = LIBRARY_MIGRATIONS
+            .iter()
+            .copied()
+            .chain(["CREATE TABLE future (id INTEGER PRIMARY KEY);"])
+            .collect();
+        let mut future_library = db::open_in_memory(&newer).unwrap();
+
+        let categories = vec!["library".to_string(), "mcp".to_string()];
+        let backup = create_backup_in(
+            &app_data,
+            &backups,
+            &live(&mut future_library),
+            &categories,
+            "9.0.0",
+            0,
+            None,
+        )
+        .unwrap();
+        let manifest = read_manifest(&backups.join(&backup.id)).unwrap();
+        assert_eq!(manifest.files[0].schema_version, Some(newer.len()));
+        assert_eq!(manifest.files[1].schema_version, None);
+
+        fs::write(app_data.join(MCP_SERVERS_FILE), "current").unwrap();
+        let mut library = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let report = restore_backup_in(
+            &app_data,
+            &backups,
+            &backup.id,
+            None,
+            None,
+            &mut live(&mut library),
+        )
+        .unwrap();
+        assert_eq!(report.restored_categories, vec!["mcp"]);
+        assert_eq!(report.skipped_categories.len(), 1);
+        assert_eq!(report.skipped_categories[0].category, "library");
+        assert!(report.skipped_categories[0]
+            .reason
+            .contains(&format!("schema version {}", newer.len())));
+        assert_eq!(
+            fs::read_to_string(app_data.join(MCP_SERVERS_FILE)).unwrap(),
+            "backed up"
+        );
+        assert_eq!(
+            db::schema_version(&library).unwrap(),
+            LIBRARY_MIGRATIONS.len()
+        );
+    }
+
+    #[test]
+    fn encrypted_backups_need_the_passphrase() {
+        let dir = tempdir().unwrap();
+        let app_data = dir.path().join("app");
+        let backups = app_data.join("backups");
+        fs::create_dir_all(&app_data).unwrap();
+        fs::write(app_data.join(MCP_SERVERS_FILE), "secret-token").unwrap();
+        let mut library = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+
+        let categories = vec!["library".to_string(), "mcp".to_string()];
+        let backup = create_backup_in(
+            &app_data,
+            &backups,
+            &live(&mut library),
+            &categories,
+            "1.0.0",
+            0,
+            Some("hunter2"),
+        )
+        .unwrap();
+        assert!(backup.encrypted);
+        let stored = fs::read(
+            backups
+                .join(&backup.id)
+                .join(BACKUP_DATA_DIR)
+                .join(MCP_SERVERS_FILE),
+        )
+        .unwrap();
+        assert!(!String::from_utf8_lossy(&stored).contains("secret-token"));
+
+        fs::write(app_data.join(MCP_SERVERS_FILE), "changed").unwrap();
+        for passphrase in [None, Some("hunter3")] {
+            assert!(matches!(
+                restore_backup_in(
+                    &app_data,
+                    &backups,
+                    &backup.id,
+                    None,
+                    passphrase,
+                    &mut live(&mut library),
+                ),
+                Err(AppError::InvalidInput(_))
+            ));
+        }
+        let report = restore_backup_in(
+            &app_data,
+            &backups,
+            &backup.id,
+            None,
+            Some("hunter2"),
+            &mut live(&mut library),
+        )
+        .unwrap();
+        assert_eq!(report.restored_categories, categories);
+        assert_eq!(
+            fs::read_to_string(app_data.join(MCP_SERVERS_FILE)).unwrap(),
+            "secret-token"
+        );
+        assert_eq!(fs::read_dir(&app_data).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn failed_restores_change_nothing() {
+        let dir = tempdir().unwrap();
+        let app_data = dir.path().join("app");
+        let backups = app_data.join("backups");
+        let attachments = app_data.join(CONVERSATION_ATTACHMENTS_DIR);
+        fs::create_dir_all(&attachments).unwrap();
+        fs::write(attachments.join("a.txt"), "attached").unwrap();
+        fs::write(app_data.join(MCP_SERVERS_FILE), "before").unwrap();
+        let mut library = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+
+        let categories = vec![
+            "library".to_string(),
+            "mcp".to_string(),
+            "conversations".to_string(),
+        ];
+        let backup = create_backup_in(
+            &app_data,
+            &backups,
+            &live(&mut library),
+            &categories,
+            "1.0.0",
+            0,
+            None,
+        )
+        .unwrap();
+
+        library
+            .execute_batch(
+                "INSERT INTO collections (id, name, kind, created_at, updated_at)
+                 VALUES ('c1', 'Later', 'collection', 2, 2)",
+            )
+            .unwrap();
+        fs::write(app_data.join(MCP_SERVERS_FILE), "after").unwrap();
+        // The attachments can no longer be put in place, after the library
+        // and MCP servers were
+        fs::remove_dir_all(&attachments).unwrap();
+        fs::write(&attachments, "in the way").unwrap();
+
+        assert!(restore_backup_in(
+            &app_data,
+            &backups,
+            &backup.id,
+            None,
+            None,
+            &mut live(&mut library),
+        )
+        .is_err());
+        assert_eq!(collection_names(&library), vec!["Later"]);
+        assert_eq!(
+            fs::read_to_string(app_data.join(MCP_SERVERS_FILE)).unwrap(),
+            "after"
+        );
+        assert!(fs::read_dir(&app_data).unwrap().all(|e| !e
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with(".restore")));
+    }
+}
@@ -0,0 +1,643 @@
+//! Creating, listing and verifying backups
+//!
+//! A backup is a directory under `app_data/backups` named by its creation
+//! time. It holds a copy of the app data files below `data/` and a
+//! `manifest.json` with the size and SHA-256 checksum of every file.
+//! Databases are copied from their open connections with `VACUUM INTO`, so
+//! a backup is consistent while the app is running. Encrypted conversation
+//! content stays encrypted and needs the key in the OS keyring to be read.
+//! With a passphrase, every file is encrypted as it is written (see `crypto`).
+
+use super::crypto::{encrypt_file, new_backup_key};
+use super::types::{BackupFileEntry, BackupInfo, BackupManifest};
+use crate::commands::ai_images::GENERATED_IMAGES_DIR;
+use crate::commands::ai_pricing::AI_PRICING_FILE;
+use crate::commands::ai_providers::CUSTOM_PROVIDERS_FILE;
+use crate::commands::ai_usage::USAGE_STATS_FILE;
+use crate::commands::conversations::{
+    lock_conversations, restore_content_cipher, ConversationState, CONVERSATIONS_DB_FILE,
+    CONVERSATION_ATTACHMENTS_DIR, CONVERSATION_MIGRATIONS,
+};
+use crate::commands::kv::{lock_kv, KvState, KV_DB_FILE, KV_MIGRATIONS};
+use crate::commands::library::{
+    hash_file, lock_library, LibraryState, LIBRARY_DB_FILE, LIBRARY_MIGRATIONS,
+};
+use crate::commands::mcp::MCP_SERVERS_FILE;
+use crate::db;
+use crate::error::AppError;
+use aes_gcm::Aes256Gcm;
+use rusqlite::backup::Backup;
+use rusqlite::{params, Connection, DatabaseName, OpenFlags};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tauri::Manager;
+use walkdir::WalkDir;
+
+pub const BACKUP_MANIFEST_FILE: &str = "manifest.json";
+
+/// Directory inside a backup holding the copied app data
+pub const BACKUP_DATA_DIR: &str = "data";
+
+const BACKUP_FORMAT_VERSION: u32 = 1;
+
+/// Application settings file in the app data directory
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// A piece of app data belonging to a backup category
+pub enum BackupItem {
+    /// A SQLite database and its migrations, copied from its open connection
+    /// when there is one
+    Database(&'static str, &'static [&'static str]),
+    File(&'static str),
+    /// A directory, copied recursively
+    Directory(&'static str),
+}
+
+/// Backup categories and the app data they cover
+pub const BACKUP_CATEGORIES: &[(&str, &[BackupItem])] = &[
+    (
+        "library",
+        &[BackupItem::Database(LIBRARY_DB_FILE, LIBRARY_MIGRATIONS)],
+    ),
+    (
+        "conversations",
+        &[
+            BackupItem::Database(CONVERSATIONS_DB_FILE, CONVERSATION_MIGRATIONS),
+            BackupItem::Directory(CONVERSATION_ATTACHMENTS_DIR),
+            BackupItem::Directory(GENERATED_IMAGES_DIR),
+        ],
+    ),
+    ("mcp", &[BackupItem::File(MCP_SERVERS_FILE)]),
+    (
+        "usage",
+        &[
+            BackupItem::File(USAGE_STATS_FILE),
+            BackupItem::File(AI_PRICING_FILE),
+        ],
+    ),
+    ("providers", &[BackupItem::File(CUSTOM_PROVIDERS_FILE)]),
+    (
+        "settings",
+        &[
+            BackupItem::File(SETTINGS_FILE),
+            BackupItem::Database(KV_DB_FILE, KV_MIGRATIONS),
+        ],
+    ),
+];
+
+/// Called on a database after a restore, e.g. to reinstall SQL functions
+pub type RestoreHook = fn(&Connection) -> Result<(), AppError>;
+
+/// An open database taking part in a backup or restore
+pub struct LiveDatabase<'a> {
+    /// File name in the app data directory
+    pub file: &'static str,
+    pub conn: &'a mut Connection,
+    pub on_restore: Option<RestoreHook>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Get the directory holding backups
+pub fn get_backups_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(data_dir.join("backups"))
+}
+
+/// Check requested categories, defaulting to all of them
+pub fn validate_categories(categories: Option<&[String]>) -> Result<Vec<String>, AppError> {
+    let Some(categories) = categories.filter(|c| !c.is_empty()) else {
+        return Ok(BACKUP_CATEGORIES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect());
+    };
+    let mut valid: Vec<String> = Vec::new();
+    for category in categories {
+        if !BACKUP_CATEGORIES.iter().any(|(name, _)| name == category) {
+            return Err(AppError::InvalidInput(format!(
+                "Unknown backup category: {}",
+                category
+            )));
+        }
+        if !valid.contains(category) {
+            valid.push(category.clone());
+        }
+    }
+    Ok(valid)
+}
+
+/// Check that a backup id names a single directory
+pub fn validate_backup_id(id: &str) -> Result<&str, AppError> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    if !valid {
+        return Err(AppError::InvalidInput(format!("Invalid backup id: {}", id)));
+    }
+    Ok(id)
+}
+
+/// Resolve a manifest path below `base`, rejecting paths that leave it
+pub fn resolve_entry_path(base: &Path, path: &str) -> Result<PathBuf, AppError> {
+    let relative = Path::new(path);
+    let safe = relative.components().next().is_some()
+        && relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+    if !safe {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid path in backup manifest: {}",
+            path
+        )));
+    }
+    Ok(base.join(relative))
+}
+
+/// Path of a new file in a backup's data directory, creating its parents
+fn backup_target(data_dir: &Path, path: &str) -> Result<PathBuf, AppError> {
+    let target = resolve_entry_path(data_dir, path)?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(target)
+}
+
+/// Copy a file into a backup, encrypting it on the way when there is a key
+fn copy_into_backup(
+    source: &Path,
+    data_dir: &Path,
+    category: &str,
+    path: String,
+    kind: &str,
+    cipher: Option<&Aes256Gcm>,
+) -> Result<BackupFileEntry, AppError> {
+    let schema_version = match kind {
+        "database" => Some(database_schema_version(source)?),
+        _ => None,
+    };
+    let target = backup_target(data_dir, &path)?;
+    match cipher {
+        Some(cipher) => encrypt_file(cipher, &fs::read(source)?, &path, &target)?,
+        None => {
+            fs::copy(source, &target)?;
+        }
+    }
+    file_entry(&target, category, path, kind, schema_version)
+}
+
+fn file_entry(
+    file: &Path,
+    category: &str,
+    path: String,
+    kind: &str,
+    schema_version: Option<usize>,
+) -> Result<BackupFileEntry, AppError> {
+    Ok(BackupFileEntry {
+        category: category.to_string(),
+        path,
+        kind: kind.to_string(),
+        size: fs::metadata(file)?.len(),
+        sha256: hash_file(file)?,
+        schema_version,
+    })
+}
+
+/// Read the schema version of a database file without changing it
+pub fn database_schema_version(path: &Path) -> Result<usize, AppError> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    db::schema_version(&conn)
+}
+
+/// Migrations of a backed up database, by its file name
+pub fn database_migrations(file: &str) -> Option<&'static [&'static str]> {
+    BACKUP_CATEGORIES
+        .iter()
+        .flat_map(|(_, items)| items.iter())
+        .find_map(|item| match item {
+            BackupItem::Database(name, migrations) if *name == file => Some(*migrations),
+            _ => None,
+        })
+}
+
+/// Copy an open database into memory, compacted so that freed pages keep no
+/// old content, with its schema version
+fn serialize_database(conn: &Connection) -> Result<(Vec<u8>, usize), AppError> {
+    let mut copy = Connection::open_in_memory()?;
+    Backup::new(conn, &mut copy)?.run_to_completion(1024, Duration::ZERO, None)?;
+    copy.execute_batch("VACUUM")?;
+    let version = db::schema_version(&copy)?;
+    Ok((copy.serialize(DatabaseName::Main)?.to_vec(), version))
+}
+
+/// Copy the items of a category into a backup's data directory
+fn snapshot_category(
+    app_data: &Path,
+    backup_data: &Path,
+    category: &str,
+    items: &[BackupItem],
+    databases: &[LiveDatabase],
+    cipher: Option<&Aes256Gcm>,
+) -> Result<Vec<BackupFileEntry>, AppError> {
+    let mut entries = Vec::new();
+    for item in items {
+        match item {
+            BackupItem::Database(file, _) => {
+                let live = databases.iter().find(|db| db.file == *file);
+                if let Some(db) = live {
+                    let target = backup_target(backup_data, file)?;
+                    let version = match cipher {
+                        // Never write the plain database into the backup
+                        Some(cipher) => {
+                            let (data, version) = serialize_database(db.conn)?;
+                            encrypt_file(cipher, &data, file, &target)?;
+                            version
+                        }
+                        None => {
+                            db.conn.execute(
+                                "VACUUM INTO ?1",
+                                params![target.to_string_lossy().to_string()],
+                            )?;
+                            database_schema_version(&target)?
+                        }
+                    };
+                    entries.push(file_entry(
+                        &target,
+                        category,
+                        file.to_string(),
+                        "database",
+                        Some(version),
+                    )?);
+                } else if app_data.join(file).is_file() {
+                    entries.push(copy_into_backup(
+                        &app_data.join(file),
+                        backup_data,
+                        category,
+                        file.to_string(),
+                        "database",
+                        cipher,
+                    )?);
+                }
+            }
+            BackupItem::File(file) => {
+                let source = app_data.join(file);
+                if source.is_file() {
+                    entries.push(copy_into_backup(
+                        &source,
+                        backup_data,
+                        category,
+                        file.to_string(),
+                        "file",
+                        cipher,
+                    )?);
+                }
+            }
+            BackupItem::Directory(dir) => {
+                let source = app_data.join(dir);
+                if !source.is_dir() {
+                    continue;
+                }
+                for entry in WalkDir::new(&source).sort_by_file_name() {
+                    let entry = entry.map_err(|e| AppError::Io(e.into()))?;
+                    if !entry.file_type().is_file() {
+                        continue;
+                    }
+                    let relative = entry
+                        .path()
+                        .strip_prefix(app_data)
+                        .map_err(|e| AppError::Internal(e.to_string()))?;
+                    let path = relative
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    entries.push(copy_into_backup(
+                        entry.path(),
+                        backup_data,
+                        category,
+                        path,
+                        "file",
+                        cipher,
+                    )?);
+                }
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn backup_info(dir: &Path, manifest: &BackupManifest) -> BackupInfo {
+    let categories: BTreeSet<&str> = manifest.files.iter().map(|f| f.category.as_str()).collect();
+    BackupInfo {
+        id: manifest.id.clone(),
+        created_at: manifest.created_at,
+        app_version: manifest.app_version.clone(),
+        categories: manifest
+            .categories
+            .iter()
+            .filter(|c| categories.contains(c.as_str()))
+            .cloned()
+            .collect(),
+        file_count: manifest.files.len(),
+        total_size: manifest.files.iter().map(|f| f.size).sum(),
+        encrypted: manifest.encryption.is_some(),
+        path: dir.to_string_lossy().to_string(),
+    }
+}
+
+/// Snapshot app data into a new backup, encrypted if a passphrase is given
+pub fn create_backup_in(
+    app_data: &Path,
+    backups_dir: &Path,
+    databases: &[LiveDatabase],
+    categories: &[String],
+    app_version: &str,
+    now: i64,
+    passphrase: Option<&str>,
+) -> Result<BackupInfo, AppError> {
+    let key = passphrase.map(new_backup_key).transpose()?;
+    let stamp = chrono::DateTime::from_timestamp(now, 0)
+        .unwrap_or_default()
+        .format("%Y%m%d-%H%M%S")
+        .to_string();
+    let mut id = stamp.clone();
+    let mut suffix = 1;
+    while backups_dir.join(&id).exists() {
+        suffix += 1;
+        id = format!("{}-{}", stamp, suffix);
+    }
+
+    // Build the backup under a temporary name so listings never see half of it
+    let partial = backups_dir.join(format!("{}.partial", id));
+    let created = (|| {
+        let backup_data = partial.join(BACKUP_DATA_DIR);
+        fs::create_dir_all(&backup_data)?;
+        let mut files = Vec::new();
+        for (category, items) in BACKUP_CATEGORIES {
+            if categories.iter().any(|c| c == category) {
+                files.extend(snapshot_category(
+                    app_data,
+                    &backup_data,
+                    category,
+                    items,
+                    databases,
+                    key.as_ref().map(|(_, cipher)| cipher),
+                )?);
+            }
+        }
+        let manifest = BackupManifest {
+            version: BACKUP_FORMAT_VERSION,
+            id: id.clone(),
+            created_at: now,
+            app_version: app_version.to_string(),
+            categories: categories.to_vec(),
+            files,
+            encryption: key.as_ref().map(|(encryption, _)| encryption.clone()),
+        };
+        fs::write(
+            partial.join(BACKUP_MANIFEST_FILE),
+            serde_json::to_string_pretty(&manifest)?,
+        )?;
+        let target = backups_dir.join(&id);
+        fs::rename(&partial, &target)?;
+        Ok(backup_info(&target, &manifest))
+    })();
+    if created.is_err() {
+        let _ = fs::remove_dir_all(&partial);
+    }
+    created
+}
+
+/// Read the manifest of a backup
+pub fn read_manifest(backup_dir: &Path) -> Result<BackupManifest, AppError> {
+    let path = backup_dir.join(BACKUP_MANIFEST_FILE);
+    if !path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "Backup not found: {:?}",
+            backup_dir
+        )));
+    }
+    let manifest: BackupManifest = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if manifest.version > BACKUP_FORMAT_VERSION {
+        return Err(AppError::InvalidInput(format!(
+            "Backup format version {} is newer than this app supports",
+            manifest.version
+        )));
+    }
+    Ok(manifest)
+}
+
+/// List backups, newest first
+pub fn list_backups_in(backups_dir: &Path) -> Result<Vec<BackupInfo>, AppError> {
+    if !backups_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(backups_dir)?.flatten() {
+        let dir = entry.path();
+        if !dir.is_dir() || dir.extension().is_some_and(|ext| ext == "partial") {
+            continue;
+        }
+        match read_manifest(&dir) {
+            Ok(manifest) => backups.push(backup_info(&dir, &manifest)),
+            Err(e) => log::warn!("Skipping unreadable backup {:?}: {}", dir, e),
+        }
+    }
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+    Ok(backups)
+}
+
+/// Check the size and checksum of backed up files
+pub fn verify_backup<'a>(
+    backup_dir: &Path,
+    files: impl IntoIterator<Item = &'a BackupFileEntry>,
+) -> Result<(), AppError> {
+    let data = backup_dir.join(BACKUP_DATA_DIR);
+    let mut damaged = Vec::new();
+    for file in files {
+        let path = resolve_entry_path(&data, &file.path)?;
+        let intact = fs::metadata(&path).is_ok_and(|m| m.len() == file.size)
+            && hash_file(&path).is_ok_and(|hash| hash == file.sha256);
+        if !intact {
+            damaged.push(file.path.as_str());
+        }
+    }
+    if !damaged.is_empty() {
+        return Err(AppError::InvalidInput(format!(
+            "Backup files are missing or damaged: {}",
+            damaged.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Lock the app databases for a backup or restore
+pub fn with_live_databases<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut [LiveDatabase]) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let library = app.state::<LibraryState>().inner().clone();
+    let conversations = app.state::<ConversationState>().inner().clone();
+    let kv = app.state::<KvState>().inner().clone();
+    let mut library = lock_library(&library)?;
+    let mut conversations = lock_conversations(&conversations)?;
+    let mut kv = lock_kv(&kv)?;
+    let mut databases = [
+        LiveDatabase {
+            file: LIBRARY_DB_FILE,
+            conn: &mut library,
+            on_restore: None,
+        },
+        LiveDatabase {
+            file: CONVERSATIONS_DB_FILE,
+            conn: &mut conversations,
+            on_restore: Some(restore_content_cipher),
+        },
+        LiveDatabase {
+            file: KV_DB_FILE,
+            conn: &mut kv,
+            on_restore: None,
+        },
+    ];
+    f(&mut databases)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Back up app data (all categories, or the given ones), optionally
+/// encrypted with a passphrase
+#[tauri::command]
+pub async fn create_backup(
+    app: tauri::AppHandle,
+    categories: Option<Vec<String>>,
+    passphrase: Option<String>,
+) -> Result<BackupInfo, AppError> {
+    let categories = validate_categories(categories.as_deref())?;
+    let app_data = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    let backups_dir = get_backups_dir(&app)?;
+    let app_version = app.package_info().version.to_string();
+    let info = tauri::async_runtime::spawn_blocking(move || {
+        with_live_databases(&app, |databases| {
+            create_backup_in(
+                &app_data,
+                &backups_dir,
+                databases,
+                &categories,
+                &app_version,
+                chrono::Utc::now().timestamp(),
+                passphrase.as_deref(),
+            )
+        })
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    log::info!("Backup created: {} ({} files)", info.id, info.file_count);
+    Ok(info)
+}
+
+/// List backups, newest first
+#[tauri::command]
+pub fn list_backups(app: tauri::AppHandle) -> Result<Vec<BackupInfo>, AppError> {
+    list_backups_in(&get_backups_dir(&app)?)
+}
+
+/// Delete a backup
+#[tauri::command]
+pub fn delete_backup(app: tauri::AppHandle, id: String) -> Result<(), AppError> {
+    let dir = get_backups_dir(&app)?.join(validate_backup_id(&id)?);
+    read_manifest(&dir)?;
+    fs::remove_dir_all(&dir)?;
+    log::info!("Backup deleted: {}", id);
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use tempfile::tempdir;
+
+    #[test]
+    fn backups_hold_checked_copies_of_app_data() {
+        let dir = tempdir().unwrap();
+        let app_data = dir.path().join("app");
+        let backups = app_data.join("backups");
+        fs::create_dir_all(app_data.join(CONVERSATION_ATTACHMENTS_DIR)).unwrap();
+        fs::write(app_data.join(MCP_SERVERS_FILE), r#"{"servers":[]}"#).unwrap();
+        fs::write(
+            app_data.join(CONVERSATION_ATTACHMENTS_DIR).join("ab.txt"),
+            "notes",
+        )
+        .unwrap();
+        let mut library = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let databases = [LiveDatabase {
+            file: LIBRARY_DB_FILE,
+            conn: &mut library,
+            on_restore: None,
+        }];
+
+        let categories = validate_categories(None).unwrap();
+        let first = create_backup_in(
+            &app_data,
+            &backups,
+            &databases,
+            &categories,
+            "1.0.0",
+            0,
+            None,
+        )
+        .unwrap();
+        assert_eq!(first.id, "19700101-000000");
+        assert_eq!(first.categories, vec!["library", "conversations", "mcp"]);
+        assert_eq!(first.file_count, 3);
+
+        let only_mcp = validate_categories(Some(&["mcp".to_string()])).unwrap();
+        let second =
+            create_backup_in(&app_data, &backups, &databases, &only_mcp, "1.0.0", 0, None).unwrap();
+        assert_eq!(second.id, "19700101-000000-2");
+        let listed = list_backups_in(&backups).unwrap();
+        assert_eq!(listed, vec![second.clone(), first.clone()]);
+
+        let backup_dir = backups.join(&first.id);
+        let manifest = read_manifest(&backup_dir).unwrap();
+        let paths: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "library.db",
+                "conversation-attachments/ab.txt",
+                "mcp_servers.json"
+            ]
+        );
+        verify_backup(&backup_dir, &manifest.files).unwrap();
+        fs::write(
+            backup_dir.join(BACKUP_DATA_DIR).join(MCP_SERVERS_FILE),
+            "{}",
+        )
+        .unwrap();
+        assert!(verify_backup(&backup_dir, &manifest.files).is_err());
+
+        assert!(validate_categories(Some(&["photos".to_string()])).is_err());
+        assert!(validate_categories(Some(&["annotations".to_string()])).is_err());
+        assert!(validate_backup_id("../app").is_err());
+        assert!(resolve_entry_path(&app_data, "../escape").is_err());
+        assert!(resolve_entry_path(&app_data, "/etc/passwd").is_err());
+    }
+}
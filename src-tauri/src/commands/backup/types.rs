@@ -0,0 +1,154 @@
+//! Backup data structures
+
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// Manifest Types
+// ============================================================================
+
+/// Description of a backup, stored as `manifest.json` in its directory
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupManifest {
+    pub version: u32,
+    pub id: String,
+    pub created_at: i64,
+    pub app_version: String,
+    /// Categories the backup was asked to hold
+    pub categories: Vec<String>,
+    pub files: Vec<BackupFileEntry>,
+    /// Set when the files are encrypted with a passphrase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<BackupEncryption>,
+}
+
+/// A file in a backup
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupFileEntry {
+    pub category: String,
+    /// Path relative to the app data directory, `/`-separated
+    pub path: String,
+    /// "database" for SQLite databases, "file" otherwise
+    pub kind: String,
+    pub size: u64,
+    /// SHA-256 of the file content as stored (encrypted, if the backup is),
+    /// hex encoded
+    pub sha256: String,
+    /// Schema version of a database, missing in older backups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<usize>,
+}
+
+/// How the passphrase of an encrypted backup is turned into its key
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupEncryption {
+    /// Key derivation function, "scrypt"
+    pub kdf: String,
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+    /// Hex encoded
+    pub salt: String,
+    /// A known value encrypted with the key, hex encoded
+    pub check: String,
+}
+
+// ============================================================================
+// Result Types
+// ============================================================================
+
+/// A backup as shown in the backup list
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub id: String,
+    pub created_at: i64,
+    pub app_version: String,
+    /// Categories with at least one file in the backup
+    pub categories: Vec<String>,
+    pub file_count: usize,
+    pub total_size: u64,
+    /// Whether restoring needs a passphrase
+    pub encrypted: bool,
+    /// Absolute path of the backup directory
+    pub path: String,
+}
+
+/// What a restore replaced
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    pub backup_id: String,
+    pub restored_categories: Vec<String>,
+    pub restored_files: usize,
+    /// Requested categories the backup holds no files for
+    pub missing_categories: Vec<String>,
+    /// Requested categories left untouched because they cannot be restored
+    pub skipped_categories: Vec<SkippedCategory>,
+}
+
+/// A backup category a restore did not apply
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedCategory {
+    pub category: String,
+    pub reason: String,
+}
+
+// ============================================================================
+// Migration Types
+// ============================================================================
+
+/// Something exported data relies on that an archive cannot carry
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalDependency {
+    /// "mcp-server"
+    pub kind: String,
+    pub name: String,
+    /// Executable a local server is started with
+    pub command: Option<String>,
+    /// Endpoint of a remote server
+    pub url: Option<String>,
+}
+
+/// Description of a migration archive, stored as `migration.json` in it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationManifest {
+    pub version: u32,
+    pub exported_at: i64,
+    pub app_version: String,
+    /// OS the archive was exported on
+    pub platform: String,
+    /// Id of the backup inside the archive
+    pub backup_id: String,
+    pub categories: Vec<String>,
+    pub encrypted: bool,
+    /// Providers that had an API key. Keys never leave the OS keyring, so
+    /// they have to be entered again on the new machine.
+    pub api_key_providers: Vec<String>,
+    pub dependencies: Vec<ExternalDependency>,
+}
+
+/// What importing a migration archive would bring, for the first-run wizard
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationPreview {
+    pub manifest: MigrationManifest,
+    /// Dependencies whose command is not found on this machine
+    pub missing_dependencies: Vec<ExternalDependency>,
+}
+
+/// What importing a migration archive restored and what is left to do
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationImportReport {
+    pub restore: RestoreReport,
+    /// Providers whose API key still has to be entered
+    pub api_key_providers: Vec<String>,
+    /// Dependencies whose command is not found on this machine
+    pub missing_dependencies: Vec<ExternalDependency>,
+}
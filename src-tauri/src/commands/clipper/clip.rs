@@ -0,0 +1,387 @@
+//! Fetching web pages and clipping them into the library
+
+use super::package::{slugify, unique_clip_path, write_epub_clip, write_html_clip};
+use super::readability::extract_article;
+use super::types::{ClippedImage, ExtractedArticle, WebClipOptions, WebClipResult};
+use crate::commands::library::{
+    fingerprint_file, lock_library, upsert_document, LibraryDocument, LibraryDocumentInput,
+    LibraryState,
+};
+use crate::commands::retry::with_retry;
+use crate::error::AppError;
+use encoding_rs::{Encoding, UTF_8};
+use image::ImageFormat;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::Manager;
+use url::Url;
+
+const MAX_PAGE_BYTES: usize = 10 * 1024 * 1024;
+const MAX_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+const MAX_IMAGES: usize = 100;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Some sites serve stripped-down pages to clients that do not look like a browser
+const CLIPPER_USER_AGENT: &str = concat!(
+    "Mozilla/5.0 (compatible; sast-readium/",
+    env!("CARGO_PKG_VERSION"),
+    "; +https://github.com/NJUPT-SAST-CXX/sast-readium-web)"
+);
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Get the default directory for clipped articles
+pub fn get_clips_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(data_dir.join("clips"))
+}
+
+/// Read the `charset` parameter of a Content-Type header
+pub fn charset_from_content_type(content_type: &str) -> Option<String> {
+    content_type.split(';').find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        (key.trim().eq_ignore_ascii_case("charset"))
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Find a charset declared by a `<meta>` tag near the start of a page
+fn sniff_meta_charset(bytes: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(2048)]).to_lowercase();
+    let start = head.find("charset=")? + "charset=".len();
+    let label: String = head[start..]
+        .trim_start_matches(['"', '\''])
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .collect();
+    (!label.is_empty()).then_some(label)
+}
+
+/// Decode an HTML page using its BOM, declared charset or UTF-8
+pub fn decode_html(bytes: &[u8], content_type: Option<&str>) -> String {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return encoding
+            .decode_without_bom_handling(&bytes[bom_length..])
+            .0
+            .into_owned();
+    }
+    let encoding = content_type
+        .and_then(charset_from_content_type)
+        .or_else(|| sniff_meta_charset(bytes))
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8);
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+/// Detect the file extension and media type of downloaded image data
+pub fn detect_image_type(
+    data: &[u8],
+    content_type: Option<&str>,
+) -> Option<(&'static str, &'static str)> {
+    match image::guess_format(data) {
+        Ok(ImageFormat::Png) => return Some(("png", "image/png")),
+        Ok(ImageFormat::Jpeg) => return Some(("jpg", "image/jpeg")),
+        Ok(ImageFormat::Gif) => return Some(("gif", "image/gif")),
+        Ok(ImageFormat::WebP) => return Some(("webp", "image/webp")),
+        _ => {}
+    }
+    let is_svg = content_type
+        .map(|ct| ct.starts_with("image/svg+xml"))
+        .unwrap_or(false)
+        || String::from_utf8_lossy(&data[..data.len().min(512)]).contains("<svg");
+    is_svg.then_some(("svg", "image/svg+xml"))
+}
+
+/// Read a response body, failing once it grows past `limit` bytes
+pub async fn read_limited(
+    mut response: reqwest::Response,
+    limit: usize,
+) -> Result<Vec<u8>, AppError> {
+    let too_large =
+        |status| AppError::http_status(status, format!("Response is larger than {} bytes", limit));
+    let status = response.status();
+    if response.content_length().unwrap_or(0) as usize > limit {
+        return Err(too_large(status));
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(too_large(status));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Fetch a page, returning its decoded HTML and final URL after redirects
+async fn fetch_page(client: &reqwest::Client, url: &Url) -> Result<(String, Url), AppError> {
+    let response = with_retry("Page request", || async {
+        let response = client
+            .get(url.clone())
+            .header(
+                reqwest::header::ACCEPT,
+                "text/html,application/xhtml+xml;q=0.9,*/*;q=0.5",
+            )
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AppError::http_status(
+                status,
+                format!("Fetching {} failed with status {}", url, status),
+            ));
+        }
+        Ok(response)
+    })
+    .await?;
+
+    let final_url = response.url().clone();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_lowercase());
+    if let Some(content_type) = &content_type {
+        if !content_type.contains("html") {
+            return Err(AppError::InvalidInput(format!(
+                "{} is not an HTML page ({})",
+                final_url, content_type
+            )));
+        }
+    }
+    let body = read_limited(response, MAX_PAGE_BYTES).await?;
+    Ok((decode_html(&body, content_type.as_deref()), final_url))
+}
+
+async fn download_image(
+    client: &reqwest::Client,
+    url: &str,
+    index: usize,
+) -> Result<ClippedImage, AppError> {
+    let response = client.get(url).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(AppError::http_status(status, format!("status {}", status)));
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_lowercase());
+    let data = read_limited(response, MAX_IMAGE_BYTES).await?;
+    let (extension, media_type) = detect_image_type(&data, content_type.as_deref())
+        .ok_or_else(|| AppError::InvalidInput("unsupported image format".to_string()))?;
+    Ok(ClippedImage {
+        url: url.to_string(),
+        file_name: format!("img_{:03}.{}", index + 1, extension),
+        media_type: media_type.to_string(),
+        data,
+    })
+}
+
+/// Download article images, returning the saved images and the failed URLs
+async fn download_images(
+    client: &reqwest::Client,
+    urls: &[String],
+) -> (Vec<ClippedImage>, Vec<String>) {
+    let mut images = Vec::new();
+    let mut failed = Vec::new();
+    for (index, url) in urls.iter().enumerate() {
+        if index >= MAX_IMAGES {
+            failed.push(url.clone());
+            continue;
+        }
+        match download_image(client, url, index).await {
+            Ok(image) => images.push(image),
+            Err(e) => {
+                log::debug!("Failed to download clip image {}: {}", url, e);
+                failed.push(url.clone());
+            }
+        }
+    }
+    (images, failed)
+}
+
+/// Add a saved clip to the library with its source metadata
+pub fn import_clip(
+    state: &LibraryState,
+    path: &Path,
+    article: &ExtractedArticle,
+    source_url: &str,
+    now: i64,
+) -> Result<LibraryDocument, AppError> {
+    let fingerprint = fingerprint_file(path)?;
+    let input = LibraryDocumentInput {
+        path: path.to_string_lossy().to_string(),
+        title: Some(article.title.clone()),
+        author: article.byline.clone(),
+        metadata: Some(serde_json::json!({
+            "source": "web",
+            "sourceUrl": source_url,
+            "siteName": article.site_name,
+            "excerpt": article.excerpt,
+            "publishedAt": article.published_at,
+            "language": article.language,
+            "clippedAt": now,
+        })),
+    };
+    let conn = lock_library(state)?;
+    upsert_document(&conn, &input, &fingerprint, now)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Clip a web article into the library as clean HTML or EPUB
+#[tauri::command]
+pub async fn clip_web_article(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, LibraryState>,
+    url: String,
+    options: Option<WebClipOptions>,
+) -> Result<WebClipResult, AppError> {
+    let options = options.unwrap_or_default();
+    let url = Url::parse(url.trim())
+        .map_err(|e| AppError::InvalidInput(format!("Invalid URL '{}': {}", url, e)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AppError::InvalidInput(format!(
+            "Only http and https URLs can be clipped, got '{}'",
+            url.scheme()
+        )));
+    }
+    let format = options.format.as_deref().unwrap_or("html").to_lowercase();
+    if !matches!(format.as_str(), "html" | "epub") {
+        return Err(AppError::InvalidInput(format!(
+            "Unsupported clip format '{}'",
+            format
+        )));
+    }
+
+    let client = reqwest::Client::builder()
+        .user_agent(CLIPPER_USER_AGENT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    let (html, final_url) = fetch_page(&client, &url).await?;
+    let base_url = final_url.clone();
+    let article = tauri::async_runtime::spawn_blocking(move || extract_article(&html, &base_url))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+
+    let (images, failed_images) = if options.include_images.unwrap_or(true) {
+        download_images(&client, &article.images).await
+    } else {
+        (Vec::new(), article.images.clone())
+    };
+
+    let root = match &options.output_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => get_clips_dir(&app)?,
+    };
+    let state = state.inner().clone();
+    let source_url = final_url.to_string();
+    let image_count = images.len();
+    let (document, article) = tauri::async_runtime::spawn_blocking(move || {
+        std::fs::create_dir_all(&root)?;
+        let slug = slugify(&article.title);
+        let path = if format == "epub" {
+            let path = unique_clip_path(&root, &slug, Some("epub"));
+            write_epub_clip(&path, &article, &source_url, &images)?;
+            path
+        } else {
+            write_html_clip(&root, &slug, &article, &source_url, &images)?
+        };
+        let document = import_clip(
+            &state,
+            &path,
+            &article,
+            &source_url,
+            chrono::Utc::now().timestamp(),
+        )?;
+        Ok::<_, AppError>((document, article))
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+
+    log::info!(
+        "Clipped {} into {} ({} images, {} failed)",
+        final_url,
+        document.path,
+        image_count,
+        failed_images.len()
+    );
+    Ok(WebClipResult {
+        document,
+        source_url: final_url.to_string(),
+        title: article.title,
+        image_count,
+        failed_images,
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::library::LIBRARY_MIGRATIONS;
+    use crate::db;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn decode_html_honours_declared_charsets() {
+        let (gbk, _, _) = encoding_rs::GBK.encode("<p>阅读</p>");
+        let mut page = b"<html><head><meta charset=\"gbk\"></head><body>".to_vec();
+        page.extend_from_slice(&gbk);
+        assert!(decode_html(&page, None).contains("<p>阅读</p>"));
+        assert!(decode_html(&gbk, Some("text/html; charset=GBK")).contains("阅读"));
+        assert_eq!(decode_html("\u{feff}héllo".as_bytes(), None), "héllo");
+        assert_eq!(decode_html("héllo".as_bytes(), Some("text/html")), "héllo");
+    }
+
+    #[test]
+    fn detect_image_type_uses_magic_bytes() {
+        assert_eq!(
+            detect_image_type(b"\x89PNG\r\n\x1a\n0000", None),
+            Some(("png", "image/png"))
+        );
+        assert_eq!(
+            detect_image_type(b"\xff\xd8\xff\xe0", Some("application/octet-stream")),
+            Some(("jpg", "image/jpeg"))
+        );
+        assert_eq!(
+            detect_image_type(b"<?xml version=\"1.0\"?><svg></svg>", None),
+            Some(("svg", "image/svg+xml"))
+        );
+        assert_eq!(detect_image_type(b"<html></html>", Some("text/html")), None);
+    }
+
+    #[test]
+    fn import_clip_records_source_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.html");
+        std::fs::write(&path, "<html></html>").unwrap();
+        let state: LibraryState =
+            Arc::new(Mutex::new(db::open_in_memory(LIBRARY_MIGRATIONS).unwrap()));
+        let article = ExtractedArticle {
+            title: "Clipped".to_string(),
+            byline: Some("Ada".to_string()),
+            site_name: Some("Example".to_string()),
+            ..Default::default()
+        };
+
+        let document = import_clip(&state, &path, &article, "https://example.com/a", 42).unwrap();
+        assert_eq!(document.format, "html");
+        assert_eq!(document.title.as_deref(), Some("Clipped"));
+        assert_eq!(document.author.as_deref(), Some("Ada"));
+        assert_eq!(document.metadata["sourceUrl"], "https://example.com/a");
+        assert_eq!(document.metadata["clippedAt"], 42);
+    }
+}
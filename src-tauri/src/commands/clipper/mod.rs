@@ -0,0 +1,18 @@
+//! Web article clipping
+//!
+//! Pages are fetched by the backend (so they are not subject to CORS), reduced
+//! to their main article and saved with their images for offline reading:
+//! - Readability-style content extraction with page metadata
+//! - Output as a self-contained HTML folder or a single-chapter EPUB
+//! - Import into the library with the source URL and site metadata
+
+mod types;
+mod readability;
+mod package;
+mod clip;
+
+// Re-export all public items
+pub use types::*;
+pub use readability::*;
+pub use package::*;
+pub use clip::*;
@@ -0,0 +1,397 @@
+//! Saving clipped articles as HTML folders or EPUB files
+
+use super::readability::escape_xml;
+use super::types::{ClippedImage, ExtractedArticle};
+use crate::error::AppError;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Stylesheet shared by HTML clips and EPUB chapters
+const CLIP_STYLESHEET: &str = "body { max-width: 42em; margin: 0 auto; padding: 1em; \
+line-height: 1.6; font-family: serif; }
+header { margin-bottom: 2em; }
+header .byline, header .source { color: #666; font-size: 0.9em; margin: 0.2em 0; }
+img { max-width: 100%; height: auto; }
+figure { margin: 1em 0; }
+figcaption { color: #666; font-size: 0.9em; }
+pre { overflow-x: auto; white-space: pre-wrap; }
+blockquote { border-left: 3px solid #ccc; margin-left: 0; padding-left: 1em; color: #444; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #ccc; padding: 0.3em 0.6em; }";
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Turn an article title into a file system friendly name
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.chars().take(60).collect();
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "article".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Pick a path below `dir` that does not exist yet
+pub fn unique_clip_path(dir: &Path, name: &str, extension: Option<&str>) -> PathBuf {
+    let file_name = |suffix: String| match extension {
+        Some(ext) => format!("{}{}.{}", name, suffix, ext),
+        None => format!("{}{}", name, suffix),
+    };
+    let candidate = dir.join(file_name(String::new()));
+    if !candidate.exists() {
+        return candidate;
+    }
+    (1..)
+        .map(|n| dir.join(file_name(format!(" ({})", n))))
+        .find(|path| !path.exists())
+        .unwrap_or(candidate)
+}
+
+/// Point image references at the downloaded copies
+pub fn localize_images(content: &str, images: &[ClippedImage], prefix: &str) -> String {
+    images.iter().fold(content.to_string(), |content, image| {
+        content.replace(
+            &format!("src=\"{}\"", escape_xml(&image.url)),
+            &format!("src=\"{}{}\"", prefix, image.file_name),
+        )
+    })
+}
+
+/// Render a clipped article as a standalone XHTML document
+pub fn render_article_document(
+    article: &ExtractedArticle,
+    content: &str,
+    source_url: &str,
+    stylesheet: Option<&str>,
+) -> String {
+    let language = escape_xml(article.language.as_deref().unwrap_or("en"));
+    let title = escape_xml(&article.title);
+    let mut doc = String::new();
+    doc.push_str("<!DOCTYPE html>\n");
+    doc.push_str(&format!(
+        "<html xmlns=\"http://www.w3.org/1999/xhtml\" lang=\"{0}\" xml:lang=\"{0}\">\n<head>\n",
+        language
+    ));
+    doc.push_str("<meta charset=\"utf-8\"/>\n");
+    doc.push_str(&format!("<title>{}</title>\n", title));
+    if let Some(byline) = &article.byline {
+        doc.push_str(&format!(
+            "<meta name=\"author\" content=\"{}\"/>\n",
+            escape_xml(byline)
+        ));
+    }
+    doc.push_str(&format!(
+        "<link rel=\"canonical\" href=\"{}\"/>\n",
+        escape_xml(source_url)
+    ));
+    match stylesheet {
+        Some(href) => doc.push_str(&format!(
+            "<link rel=\"stylesheet\" type=\"text/css\" href=\"{}\"/>\n",
+            escape_xml(href)
+        )),
+        None => doc.push_str(&format!("<style>\n{}\n</style>\n", CLIP_STYLESHEET)),
+    }
+    doc.push_str("</head>\n<body>\n<article>\n<header>\n");
+    doc.push_str(&format!("<h1>{}</h1>\n", title));
+    if let Some(byline) = &article.byline {
+        doc.push_str(&format!("<p class=\"byline\">{}</p>\n", escape_xml(byline)));
+    }
+    let site = article.site_name.as_deref().unwrap_or(source_url);
+    doc.push_str(&format!(
+        "<p class=\"source\"><a href=\"{}\">{}</a></p>\n",
+        escape_xml(source_url),
+        escape_xml(site)
+    ));
+    doc.push_str("</header>\n");
+    doc.push_str(content);
+    doc.push_str("\n</article>\n</body>\n</html>\n");
+    doc
+}
+
+// ============================================================================
+// Writers
+// ============================================================================
+
+/// Save a clip as `<dir>/<slug>/<slug>.html` with images in an `images` folder
+pub fn write_html_clip(
+    root: &Path,
+    slug: &str,
+    article: &ExtractedArticle,
+    source_url: &str,
+    images: &[ClippedImage],
+) -> Result<PathBuf, AppError> {
+    let clip_dir = unique_clip_path(root, slug, None);
+    fs::create_dir_all(&clip_dir)?;
+    let result = (|| {
+        if !images.is_empty() {
+            let images_dir = clip_dir.join("images");
+            fs::create_dir_all(&images_dir)?;
+            for image in images {
+                fs::write(images_dir.join(&image.file_name), &image.data)?;
+            }
+        }
+        let content = localize_images(&article.content, images, "images/");
+        let path = clip_dir.join(format!("{}.html", slug));
+        fs::write(
+            &path,
+            render_article_document(article, &content, source_url, None),
+        )?;
+        Ok(path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&clip_dir);
+    }
+    result
+}
+
+fn build_package_opf(
+    article: &ExtractedArticle,
+    source_url: &str,
+    images: &[ClippedImage],
+    modified: &str,
+) -> String {
+    let mut metadata = vec![
+        format!(
+            "<dc:identifier id=\"uid\">urn:uuid:{}</dc:identifier>",
+            uuid::Uuid::new_v4()
+        ),
+        format!("<dc:title>{}</dc:title>", escape_xml(&article.title)),
+        format!(
+            "<dc:language>{}</dc:language>",
+            escape_xml(article.language.as_deref().unwrap_or("en"))
+        ),
+        format!("<dc:source>{}</dc:source>", escape_xml(source_url)),
+        format!("<meta property=\"dcterms:modified\">{}</meta>", modified),
+    ];
+    if let Some(byline) = &article.byline {
+        metadata.push(format!("<dc:creator>{}</dc:creator>", escape_xml(byline)));
+    }
+    if let Some(site) = &article.site_name {
+        metadata.push(format!("<dc:publisher>{}</dc:publisher>", escape_xml(site)));
+    }
+    if let Some(date) = &article.published_at {
+        metadata.push(format!("<dc:date>{}</dc:date>", escape_xml(date)));
+    }
+    if let Some(excerpt) = &article.excerpt {
+        metadata.push(format!(
+            "<dc:description>{}</dc:description>",
+            escape_xml(excerpt)
+        ));
+    }
+
+    let mut manifest = vec![
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>".to_string(),
+        "<item id=\"article\" href=\"article.xhtml\" media-type=\"application/xhtml+xml\"/>".to_string(),
+        "<item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>".to_string(),
+    ];
+    for (index, image) in images.iter().enumerate() {
+        manifest.push(format!(
+            "<item id=\"img{}\" href=\"images/{}\" media-type=\"{}\"/>",
+            index + 1,
+            escape_xml(&image.file_name),
+            escape_xml(&image.media_type)
+        ));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"uid\">
+  <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">
+    {}
+  </metadata>
+  <manifest>
+    {}
+  </manifest>
+  <spine>
+    <itemref idref=\"article\"/>
+  </spine>
+</package>
+",
+        metadata.join("\n    "),
+        manifest.join("\n    ")
+    )
+}
+
+fn build_nav_xhtml(article: &ExtractedArticle) -> String {
+    let title = escape_xml(&article.title);
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE html>
+<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">
+<head><title>{0}</title></head>
+<body>
+  <nav epub:type=\"toc\" id=\"toc\">
+    <ol><li><a href=\"article.xhtml\">{0}</a></li></ol>
+  </nav>
+</body>
+</html>
+",
+        title
+    )
+}
+
+/// Save a clip as a single-chapter EPUB 3 file
+pub fn write_epub_clip(
+    path: &Path,
+    article: &ExtractedArticle,
+    source_url: &str,
+    images: &[ClippedImage],
+) -> Result<(), AppError> {
+    let modified = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let content = localize_images(&article.content, images, "images/");
+    let chapter = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}",
+        render_article_document(article, &content, source_url, Some("style.css"))
+    );
+    let package = build_package_opf(article, source_url, images, &modified);
+    let nav = build_nav_xhtml(article);
+
+    let temp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let result = (|| {
+        let file = fs::File::create(&temp_path)?;
+        let mut zip = ZipWriter::new(BufWriter::new(file));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = SimpleFileOptions::default();
+        let zip_err = |e: zip::result::ZipError| AppError::Internal(e.to_string());
+
+        // The mimetype entry must come first and be stored uncompressed
+        zip.start_file("mimetype", stored).map_err(zip_err)?;
+        zip.write_all(b"application/epub+zip")?;
+        let entries: [(&str, &[u8]); 5] = [
+            ("META-INF/container.xml", CONTAINER_XML.as_bytes()),
+            ("OEBPS/content.opf", package.as_bytes()),
+            ("OEBPS/nav.xhtml", nav.as_bytes()),
+            ("OEBPS/article.xhtml", chapter.as_bytes()),
+            ("OEBPS/style.css", CLIP_STYLESHEET.as_bytes()),
+        ];
+        for (name, data) in entries {
+            zip.start_file(name, deflated).map_err(zip_err)?;
+            zip.write_all(data)?;
+        }
+        for image in images {
+            zip.start_file(format!("OEBPS/images/{}", image.file_name), stored)
+                .map_err(zip_err)?;
+            zip.write_all(&image.data)?;
+        }
+        zip.finish().map_err(zip_err)?.flush()?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::epub::{read_epub_structure, read_epub_text};
+
+    fn article() -> ExtractedArticle {
+        ExtractedArticle {
+            title: "Rust & Ownership".to_string(),
+            byline: Some("Ada".to_string()),
+            site_name: Some("Example".to_string()),
+            language: Some("en".to_string()),
+            content: r#"<p>Owned values are <em>moved</em>.</p><img src="https://example.com/a.png?x=1&amp;y=2" alt=""/>"#
+                .to_string(),
+            images: vec!["https://example.com/a.png?x=1&y=2".to_string()],
+            text_length: 24,
+            ..Default::default()
+        }
+    }
+
+    fn image() -> ClippedImage {
+        ClippedImage {
+            url: "https://example.com/a.png?x=1&y=2".to_string(),
+            file_name: "img_001.png".to_string(),
+            media_type: "image/png".to_string(),
+            data: vec![1, 2, 3],
+        }
+    }
+
+    #[test]
+    fn slugify_and_unique_paths() {
+        assert_eq!(slugify("Hello, World! 2024"), "hello-world-2024");
+        assert_eq!(slugify("  Rust 所有权  "), "rust-所有权");
+        assert_eq!(slugify("???"), "article");
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("post")).unwrap();
+        assert_eq!(
+            unique_clip_path(dir.path(), "post", None),
+            dir.path().join("post (1)")
+        );
+        assert_eq!(
+            unique_clip_path(dir.path(), "post", Some("epub")),
+            dir.path().join("post.epub")
+        );
+    }
+
+    #[test]
+    fn html_clip_localizes_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_html_clip(
+            dir.path(),
+            "rust",
+            &article(),
+            "https://example.com/post",
+            &[image()],
+        )
+        .unwrap();
+
+        assert_eq!(path, dir.path().join("rust").join("rust.html"));
+        let html = fs::read_to_string(&path).unwrap();
+        assert!(html.contains("<title>Rust &amp; Ownership</title>"));
+        assert!(html.contains(r#"<img src="images/img_001.png" alt=""/>"#));
+        assert!(html.contains(r#"<link rel="canonical" href="https://example.com/post"/>"#));
+        assert!(dir.path().join("rust/images/img_001.png").is_file());
+    }
+
+    #[test]
+    fn epub_clip_is_readable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rust.epub");
+        write_epub_clip(&path, &article(), "https://example.com/post", &[image()]).unwrap();
+
+        let structure = read_epub_structure(&path).unwrap();
+        assert_eq!(structure.title.as_deref(), Some("Rust & Ownership"));
+        assert_eq!(structure.creators, vec!["Ada"]);
+        assert_eq!(structure.spine.len(), 1);
+        assert_eq!(structure.toc.len(), 1);
+        assert!(structure
+            .manifest
+            .iter()
+            .any(|item| item.href.ends_with("images/img_001.png")));
+
+        let text = read_epub_text(&path, None).unwrap();
+        assert!(text.chapters[0].text.contains("Owned values are moved."));
+    }
+}
@@ -0,0 +1,929 @@
+//! Readability-style main content extraction
+//!
+//! A trimmed-down port of the Arc90/Mozilla Readability heuristics: obvious
+//! page chrome is stripped, paragraphs score their ancestors by text length
+//! and punctuation, and the best scoring container (plus related siblings) is
+//! kept. The result is serialized as a clean XHTML fragment so it can be used
+//! both as standalone HTML and as an EPUB chapter.
+
+use super::types::ExtractedArticle;
+use crate::error::AppError;
+use kuchikiki::traits::*;
+use kuchikiki::NodeRef;
+use quick_xml::escape::escape;
+use url::Url;
+
+/// Minimum text length for a block to contribute to scoring
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+/// Elements removed before scoring
+const STRIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "iframe", "form", "nav", "aside", "footer", "button", "input",
+    "select", "textarea", "svg", "canvas", "object", "embed", "template", "link", "meta",
+];
+
+/// Class/id hints of page chrome that is never part of the article
+const UNLIKELY_HINTS: &[&str] = &[
+    "-ad-",
+    "agegate",
+    "banner",
+    "breadcrumb",
+    "combx",
+    "comment",
+    "community",
+    "cookie",
+    "disqus",
+    "extra",
+    "footer",
+    "gdpr",
+    "header",
+    "legends",
+    "menu",
+    "newsletter",
+    "pager",
+    "pagination",
+    "popup",
+    "related",
+    "remark",
+    "replies",
+    "rss",
+    "share",
+    "shoutbox",
+    "sidebar",
+    "skyscraper",
+    "social",
+    "sponsor",
+    "subscribe",
+    "supplemental",
+];
+
+/// Class/id hints that rescue an element matching `UNLIKELY_HINTS`
+const MAYBE_CANDIDATE_HINTS: &[&str] = &["article", "body", "column", "content", "main", "post"];
+
+const POSITIVE_HINTS: &[&str] = &[
+    "article", "blog", "body", "content", "entry", "h-entry", "hentry", "main", "page", "post",
+    "story", "text",
+];
+const NEGATIVE_HINTS: &[&str] = &[
+    "banner",
+    "com-",
+    "combx",
+    "comment",
+    "contact",
+    "foot",
+    "footnote",
+    "hidden",
+    "masthead",
+    "media",
+    "meta",
+    "outbrain",
+    "promo",
+    "related",
+    "scroll",
+    "share",
+    "shopping",
+    "shoutbox",
+    "sidebar",
+    "skyscraper",
+    "sponsor",
+    "tags",
+    "tool",
+    "widget",
+];
+
+/// Block-level elements; a `div` without any of these is scored like a paragraph
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "blockquote",
+    "div",
+    "dl",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "img",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Elements kept in the serialized output; anything else is unwrapped
+const KEPT_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "article",
+    "b",
+    "blockquote",
+    "br",
+    "caption",
+    "cite",
+    "code",
+    "dd",
+    "del",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "samp",
+    "section",
+    "small",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "tr",
+    "u",
+    "ul",
+    "var",
+];
+
+/// Elements serialized as self-closing tags
+const VOID_TAGS: &[&str] = &["br", "hr", "img"];
+
+/// Schema.org types accepted from JSON-LD metadata
+const JSON_LD_ARTICLE_TYPES: &[&str] = &[
+    "Article",
+    "BlogPosting",
+    "NewsArticle",
+    "Report",
+    "ScholarlyArticle",
+    "TechArticle",
+];
+
+// ============================================================================
+// DOM Helpers
+// ============================================================================
+
+fn tag_name(node: &NodeRef) -> Option<String> {
+    node.as_element().map(|el| el.name.local.to_string())
+}
+
+fn attr(node: &NodeRef, name: &str) -> Option<String> {
+    node.as_element()
+        .and_then(|el| el.attributes.borrow().get(name).map(|v| v.to_string()))
+}
+
+fn normalized_text(node: &NodeRef) -> String {
+    node.text_contents()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn class_and_id(node: &NodeRef) -> String {
+    format!(
+        "{} {}",
+        attr(node, "class").unwrap_or_default(),
+        attr(node, "id").unwrap_or_default()
+    )
+    .to_lowercase()
+}
+
+fn matches_any(value: &str, hints: &[&str]) -> bool {
+    hints.iter().any(|hint| value.contains(hint))
+}
+
+/// Score adjustment from positive/negative class and id hints
+fn class_weight(node: &NodeRef) -> f32 {
+    let hints = class_and_id(node);
+    let mut weight = 0.0;
+    if matches_any(&hints, NEGATIVE_HINTS) {
+        weight -= 25.0;
+    }
+    if matches_any(&hints, POSITIVE_HINTS) {
+        weight += 25.0;
+    }
+    weight
+}
+
+/// Share of an element's text that sits inside links
+fn link_density(node: &NodeRef) -> f32 {
+    let length = normalized_text(node).chars().count();
+    if length == 0 {
+        return 0.0;
+    }
+    let link_length: usize = node
+        .select("a")
+        .map(|links| {
+            links
+                .map(|a| normalized_text(a.as_node()).chars().count())
+                .sum()
+        })
+        .unwrap_or(0);
+    link_length as f32 / length as f32
+}
+
+fn count_commas(text: &str) -> usize {
+    text.chars()
+        .filter(|c| matches!(c, ',' | '，' | '、'))
+        .count()
+}
+
+fn count_elements(node: &NodeRef, selector: &str) -> usize {
+    node.select(selector)
+        .map(|found| found.count())
+        .unwrap_or(0)
+}
+
+fn is_hidden(node: &NodeRef) -> bool {
+    if attr(node, "hidden").is_some() || attr(node, "aria-hidden").as_deref() == Some("true") {
+        return true;
+    }
+    attr(node, "style")
+        .map(|style| {
+            let style = style.replace(' ', "").to_lowercase();
+            style.contains("display:none") || style.contains("visibility:hidden")
+        })
+        .unwrap_or(false)
+}
+
+fn has_ancestor_tag(node: &NodeRef, tags: &[&str]) -> bool {
+    node.ancestors()
+        .filter_map(|ancestor| tag_name(&ancestor))
+        .any(|name| tags.contains(&name.as_str()))
+}
+
+fn has_block_children(node: &NodeRef) -> bool {
+    node.descendants()
+        .filter_map(|child| tag_name(&child))
+        .any(|name| BLOCK_TAGS.contains(&name.as_str()))
+}
+
+// ============================================================================
+// Metadata
+// ============================================================================
+
+/// Read the `content` of the first matching `<meta>` element
+fn meta_content(document: &NodeRef, selectors: &[&str]) -> Option<String> {
+    selectors.iter().find_map(|selector| {
+        let element = document.select_first(selector).ok()?;
+        let content = element
+            .attributes
+            .borrow()
+            .get("content")?
+            .trim()
+            .to_string();
+        (!content.is_empty()).then_some(content)
+    })
+}
+
+/// Article fields from schema.org JSON-LD blocks
+#[derive(Default)]
+struct JsonLdArticle {
+    headline: Option<String>,
+    author: Option<String>,
+    published_at: Option<String>,
+    publisher: Option<String>,
+}
+
+fn json_ld_name(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(name) => Some(name.clone()),
+        serde_json::Value::Array(items) => {
+            let names: Vec<String> = items.iter().filter_map(json_ld_name).collect();
+            (!names.is_empty()).then(|| names.join(", "))
+        }
+        serde_json::Value::Object(map) => map.get("name").and_then(json_ld_name),
+        _ => None,
+    }
+}
+
+fn find_json_ld_article(value: &serde_json::Value) -> Option<&serde_json::Value> {
+    match value {
+        serde_json::Value::Array(items) => items.iter().find_map(find_json_ld_article),
+        serde_json::Value::Object(map) => {
+            let is_article = match map.get("@type") {
+                Some(serde_json::Value::String(kind)) => {
+                    JSON_LD_ARTICLE_TYPES.contains(&kind.as_str())
+                }
+                Some(serde_json::Value::Array(kinds)) => kinds
+                    .iter()
+                    .filter_map(|kind| kind.as_str())
+                    .any(|kind| JSON_LD_ARTICLE_TYPES.contains(&kind)),
+                _ => false,
+            };
+            if is_article {
+                Some(value)
+            } else {
+                map.get("@graph").and_then(find_json_ld_article)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn read_json_ld(document: &NodeRef) -> JsonLdArticle {
+    let Ok(scripts) = document.select("script[type='application/ld+json']") else {
+        return JsonLdArticle::default();
+    };
+    for script in scripts {
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(&script.text_contents()) else {
+            continue;
+        };
+        if let Some(article) = find_json_ld_article(&value) {
+            return JsonLdArticle {
+                headline: article["headline"].as_str().map(|s| s.trim().to_string()),
+                author: json_ld_name(&article["author"]),
+                published_at: article["datePublished"].as_str().map(|s| s.to_string()),
+                publisher: json_ld_name(&article["publisher"]),
+            };
+        }
+    }
+    JsonLdArticle::default()
+}
+
+/// Strip a trailing " | Site name" style suffix from a document title
+fn clean_title(title: &str) -> String {
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    for separator in [" | ", " - ", " – ", " — ", " :: ", " _ "] {
+        if let Some((head, _)) = title.rsplit_once(separator) {
+            if head.chars().count() >= 10 {
+                return head.trim().to_string();
+            }
+        }
+    }
+    title
+}
+
+/// Byline from author markup in the page body
+fn find_byline(document: &NodeRef) -> Option<String> {
+    document
+        .select("[rel='author'], [itemprop='author'], .byline, .author")
+        .ok()?
+        .map(|element| normalized_text(element.as_node()))
+        .find(|text| !text.is_empty() && text.chars().count() < 100)
+}
+
+// ============================================================================
+// Content Extraction
+// ============================================================================
+
+/// Remove scripts, hidden elements and page chrome before scoring
+fn strip_unlikely(body: &NodeRef) {
+    let mut doomed = Vec::new();
+    for node in body.descendants() {
+        if node.as_comment().is_some() {
+            doomed.push(node);
+            continue;
+        }
+        let Some(name) = tag_name(&node) else {
+            continue;
+        };
+        if STRIPPED_TAGS.contains(&name.as_str()) || is_hidden(&node) {
+            doomed.push(node);
+            continue;
+        }
+        if matches!(name.as_str(), "body" | "article" | "main" | "a") {
+            continue;
+        }
+        let hints = class_and_id(&node);
+        if matches_any(&hints, UNLIKELY_HINTS)
+            && !matches_any(&hints, MAYBE_CANDIDATE_HINTS)
+            && !has_ancestor_tag(&node, &["table", "code", "pre"])
+        {
+            doomed.push(node);
+        }
+    }
+    for node in doomed {
+        node.detach();
+    }
+}
+
+/// Base score of a candidate container
+fn initial_score(node: &NodeRef) -> f32 {
+    let base = match tag_name(node).as_deref() {
+        Some("div") => 5.0,
+        Some("pre" | "td" | "blockquote") => 3.0,
+        Some("address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form") => -3.0,
+        Some("h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th") => -5.0,
+        _ => 0.0,
+    };
+    base + class_weight(node)
+}
+
+/// Score paragraph-like blocks into their ancestors, returning the candidates
+fn score_candidates(body: &NodeRef) -> Vec<NodeRef> {
+    let mut candidates = Vec::new();
+    let blocks: Vec<NodeRef> = body
+        .descendants()
+        .filter(|node| match tag_name(node).as_deref() {
+            Some("p" | "pre" | "td" | "blockquote") => true,
+            Some("div") => !has_block_children(node),
+            _ => false,
+        })
+        .collect();
+
+    for block in blocks {
+        let text = normalized_text(&block);
+        let length = text.chars().count();
+        if length < MIN_PARAGRAPH_LENGTH {
+            continue;
+        }
+        let score = 1.0 + count_commas(&text) as f32 + (length / 100).min(3) as f32;
+
+        for (level, ancestor) in block.ancestors().take(3).enumerate() {
+            let Some(element) = ancestor.as_element() else {
+                break;
+            };
+            if !element.is_candidate.get() {
+                element.is_candidate.set(true);
+                element.score.set(initial_score(&ancestor));
+                candidates.push(ancestor.clone());
+            }
+            let divider = match level {
+                0 => 1.0,
+                1 => 2.0,
+                level => level as f32 * 3.0,
+            };
+            element.score.set(element.score.get() + score / divider);
+        }
+    }
+
+    // Content mostly made of links (navigation, tag clouds) is penalized
+    for candidate in &candidates {
+        if let Some(element) = candidate.as_element() {
+            element
+                .score
+                .set(element.score.get() * (1.0 - link_density(candidate)));
+        }
+    }
+    candidates
+}
+
+fn score_of(node: &NodeRef) -> f32 {
+    node.as_element().map(|el| el.score.get()).unwrap_or(0.0)
+}
+
+/// Pick the best candidate and collect related siblings, in document order
+fn select_content(body: &NodeRef) -> Vec<NodeRef> {
+    let candidates = score_candidates(body);
+    let Some(mut top) = candidates
+        .iter()
+        .max_by(|a, b| score_of(a).total_cmp(&score_of(b)))
+        .cloned()
+    else {
+        return vec![body.clone()];
+    };
+
+    // A lone wrapper around the candidate carries the same content
+    while let Some(parent) = top.parent() {
+        let is_body = tag_name(&parent).as_deref() == Some("body");
+        let element_children = parent
+            .children()
+            .filter(|child| child.as_element().is_some())
+            .count();
+        if parent.as_element().is_none() || is_body || element_children != 1 {
+            break;
+        }
+        top = parent;
+    }
+
+    let Some(parent) = top.parent() else {
+        return vec![top];
+    };
+    let top_score = score_of(&top);
+    let threshold = (top_score * 0.2).max(10.0);
+    let top_class = attr(&top, "class").unwrap_or_default();
+
+    parent
+        .children()
+        .filter(|sibling| {
+            if *sibling == top {
+                return true;
+            }
+            let Some(element) = sibling.as_element() else {
+                return false;
+            };
+            let bonus =
+                if !top_class.is_empty() && attr(sibling, "class") == Some(top_class.clone()) {
+                    top_score * 0.2
+                } else {
+                    0.0
+                };
+            if element.is_candidate.get() && element.score.get() + bonus >= threshold {
+                return true;
+            }
+            if tag_name(sibling).as_deref() != Some("p") {
+                return false;
+            }
+            let text = normalized_text(sibling);
+            let length = text.chars().count();
+            let density = link_density(sibling);
+            (length > 80 && density < 0.25)
+                || (length > 0 && density == 0.0 && (text.contains(". ") || text.contains('。')))
+        })
+        .collect()
+}
+
+/// Drop link lists, widgets and other low-value containers from the content
+fn clean_content(root: &NodeRef, title: &str) {
+    let containers: Vec<NodeRef> = root
+        .descendants()
+        .filter(|node| {
+            matches!(
+                tag_name(node).as_deref(),
+                Some("div" | "section" | "table" | "ul" | "ol")
+            )
+        })
+        .collect();
+
+    // Children before parents, so a container is judged on its cleaned content
+    for node in containers.into_iter().rev() {
+        let weight = class_weight(&node);
+        if weight < 0.0 {
+            node.detach();
+            continue;
+        }
+        let text = normalized_text(&node);
+        if count_commas(&text) >= 10 {
+            continue;
+        }
+        let length = text.chars().count();
+        let images = count_elements(&node, "img");
+        let density = link_density(&node);
+        let is_data_table =
+            tag_name(&node).as_deref() == Some("table") && count_elements(&node, "th, caption") > 0;
+        if !is_data_table
+            && ((weight < 25.0 && density > 0.2)
+                || (weight >= 25.0 && density > 0.5)
+                || (length < MIN_PARAGRAPH_LENGTH
+                    && images == 0
+                    && count_elements(&node, "pre, table") == 0))
+        {
+            node.detach();
+        }
+    }
+
+    // The page template adds its own title heading
+    let headings: Vec<NodeRef> = root
+        .descendants()
+        .filter(|node| matches!(tag_name(node).as_deref(), Some("h1" | "h2")))
+        .collect();
+    for heading in headings {
+        if class_weight(&heading) < 0.0 || normalized_text(&heading) == title {
+            heading.detach();
+        }
+    }
+}
+
+// ============================================================================
+// Serialization
+// ============================================================================
+
+/// Escape text for XML, dropping control characters XML does not allow
+pub fn escape_xml(text: &str) -> String {
+    let cleaned: String = text
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\t' | '\r'))
+        .collect();
+    escape(cleaned.as_str()).into_owned()
+}
+
+/// Resolve the source of an image, following common lazy-loading attributes
+fn image_source(node: &NodeRef, base_url: &Url) -> Option<Url> {
+    let candidates = [
+        attr(node, "data-src"),
+        attr(node, "data-original"),
+        attr(node, "data-lazy-src"),
+        attr(node, "src"),
+        attr(node, "srcset").and_then(|set| set.split_whitespace().next().map(|s| s.to_string())),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .filter(|src| !src.trim().is_empty() && !src.starts_with("data:"))
+        .find_map(|src| base_url.join(src.trim()).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+struct Serializer<'a> {
+    base_url: &'a Url,
+    output: String,
+    images: Vec<String>,
+}
+
+impl Serializer<'_> {
+    fn write_node(&mut self, node: &NodeRef) {
+        if let Some(text) = node.as_text() {
+            self.output.push_str(&escape_xml(&text.borrow()));
+            return;
+        }
+        let Some(name) = tag_name(node) else {
+            return;
+        };
+        if !KEPT_TAGS.contains(&name.as_str()) {
+            for child in node.children() {
+                self.write_node(&child);
+            }
+            return;
+        }
+
+        let mut attributes: Vec<(&str, String)> = Vec::new();
+        match name.as_str() {
+            "a" => {
+                if let Some(href) = attr(node, "href") {
+                    if href.starts_with('#') {
+                        attributes.push(("href", href));
+                    } else if let Ok(url) = self.base_url.join(href.trim()) {
+                        if matches!(url.scheme(), "http" | "https" | "mailto") {
+                            attributes.push(("href", url.to_string()));
+                        }
+                    }
+                }
+            }
+            "img" => {
+                let Some(src) = image_source(node, self.base_url) else {
+                    return;
+                };
+                let src = src.to_string();
+                if !self.images.contains(&src) {
+                    self.images.push(src.clone());
+                }
+                attributes.push(("src", src));
+                attributes.push(("alt", attr(node, "alt").unwrap_or_default()));
+            }
+            "td" | "th" => {
+                for key in ["colspan", "rowspan"] {
+                    if let Some(value) = attr(node, key) {
+                        attributes.push((key, value));
+                    }
+                }
+            }
+            "ol" => {
+                if let Some(start) = attr(node, "start") {
+                    attributes.push(("start", start));
+                }
+            }
+            "abbr" => {
+                if let Some(title) = attr(node, "title") {
+                    attributes.push(("title", title));
+                }
+            }
+            _ => {}
+        }
+
+        self.output.push('<');
+        self.output.push_str(&name);
+        for (key, value) in &attributes {
+            self.output
+                .push_str(&format!(" {}=\"{}\"", key, escape_xml(value)));
+        }
+        if VOID_TAGS.contains(&name.as_str()) {
+            self.output.push_str("/>");
+            return;
+        }
+        self.output.push('>');
+        for child in node.children() {
+            self.write_node(&child);
+        }
+        self.output.push_str(&format!("</{}>", name));
+    }
+}
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Extract the readable article from an HTML page
+///
+/// Image and link URLs in the returned content are absolute; `images` lists
+/// the image URLs in document order.
+pub fn extract_article(html: &str, base_url: &Url) -> Result<ExtractedArticle, AppError> {
+    let document = kuchikiki::parse_html().one(html).document_node;
+    let json_ld = read_json_ld(&document);
+
+    let title = json_ld
+        .headline
+        .clone()
+        .or_else(|| {
+            meta_content(
+                &document,
+                &["meta[property='og:title']", "meta[name='twitter:title']"],
+            )
+        })
+        .or_else(|| {
+            document
+                .select_first("title")
+                .ok()
+                .map(|title| clean_title(&title.text_contents()))
+        })
+        .filter(|title| !title.is_empty())
+        .or_else(|| {
+            document
+                .select_first("h1")
+                .ok()
+                .map(|h1| normalized_text(h1.as_node()))
+        })
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| base_url.host_str().unwrap_or("Untitled").to_string());
+
+    let byline = json_ld
+        .author
+        .clone()
+        .or_else(|| meta_content(&document, &["meta[name='author']"]))
+        .or_else(|| {
+            meta_content(&document, &["meta[property='article:author']"])
+                .filter(|author| !author.starts_with("http"))
+        })
+        .or_else(|| find_byline(&document));
+    let site_name = meta_content(&document, &["meta[property='og:site_name']"])
+        .or(json_ld.publisher.clone())
+        .or_else(|| base_url.host_str().map(|host| host.to_string()));
+    let published_at = json_ld.published_at.clone().or_else(|| {
+        meta_content(
+            &document,
+            &[
+                "meta[property='article:published_time']",
+                "meta[name='date']",
+                "meta[itemprop='datePublished']",
+            ],
+        )
+    });
+    let language = document
+        .select_first("html")
+        .ok()
+        .and_then(|html| html.attributes.borrow().get("lang").map(|l| l.to_string()))
+        .filter(|lang| !lang.is_empty());
+    let description = meta_content(
+        &document,
+        &[
+            "meta[name='description']",
+            "meta[property='og:description']",
+        ],
+    );
+
+    let body = document
+        .select_first("body")
+        .map(|body| body.as_node().clone())
+        .unwrap_or_else(|_| document.clone());
+    strip_unlikely(&body);
+    let parts = select_content(&body);
+
+    let mut serializer = Serializer {
+        base_url,
+        output: String::new(),
+        images: Vec::new(),
+    };
+    let mut text_length = 0;
+    let mut first_paragraph = None;
+    for part in &parts {
+        clean_content(part, &title);
+        text_length += normalized_text(part).chars().count();
+        if first_paragraph.is_none() {
+            first_paragraph = part.select("p").ok().and_then(|mut paragraphs| {
+                paragraphs.find_map(|p| {
+                    let text = normalized_text(p.as_node());
+                    (text.chars().count() >= MIN_PARAGRAPH_LENGTH).then_some(text)
+                })
+            });
+        }
+        serializer.write_node(part);
+    }
+
+    if text_length == 0 {
+        return Err(AppError::InvalidInput(
+            "No readable content found on the page".to_string(),
+        ));
+    }
+
+    let excerpt = description
+        .or(first_paragraph)
+        .map(|text| text.chars().take(300).collect());
+    Ok(ExtractedArticle {
+        title,
+        byline,
+        site_name,
+        excerpt,
+        published_at,
+        language,
+        content: serializer.output,
+        images: serializer.images,
+        text_length,
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <title>Why Rust Ownership Matters | Example Blog</title>
+  <meta name="author" content="Ada Lovelace">
+  <meta property="og:site_name" content="Example Blog">
+  <meta property="article:published_time" content="2024-03-01T08:00:00Z">
+  <script>var tracking = true;</script>
+</head>
+<body>
+  <nav><a href="/">Home</a> <a href="/about">About</a></nav>
+  <div class="sidebar"><p>Subscribe to our newsletter, it is great, really, truly.</p></div>
+  <div id="main-content" class="post-body">
+    <h1>Why Rust Ownership Matters</h1>
+    <p>Ownership is the feature that sets Rust apart, and it shapes how programs are written, tested, and maintained.</p>
+    <p>Every value has a single owner, which means memory is released deterministically, without a garbage collector.</p>
+    <figure><img data-src="/img/diagram.png" src="data:image/gif;base64,R0lGOD" alt="Ownership &amp; borrowing"><figcaption>Moves and borrows</figcaption></figure>
+    <p>Borrowing lets code read data <a href="/borrowing">without taking ownership</a>, and the compiler checks every reference.</p>
+    <div class="share-links"><a href="https://x.com">Share</a> <a href="https://fb.com">Share</a></div>
+  </div>
+  <div class="comments"><p>Great post, thanks, very helpful, would read again, yes.</p></div>
+  <footer>Copyright</footer>
+</body>
+</html>"#;
+
+    #[test]
+    fn extract_article_keeps_main_content_and_metadata() {
+        let base = Url::parse("https://blog.example.com/posts/ownership").unwrap();
+        let article = extract_article(PAGE, &base).unwrap();
+
+        assert_eq!(article.title, "Why Rust Ownership Matters");
+        assert_eq!(article.byline.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(article.site_name.as_deref(), Some("Example Blog"));
+        assert_eq!(
+            article.published_at.as_deref(),
+            Some("2024-03-01T08:00:00Z")
+        );
+        assert_eq!(article.language.as_deref(), Some("en"));
+
+        assert!(article.content.contains("Every value has a single owner"));
+        assert!(article
+            .content
+            .contains(r#"<a href="https://blog.example.com/borrowing">"#));
+        assert!(article.content.contains(
+            r#"<img src="https://blog.example.com/img/diagram.png" alt="Ownership &amp; borrowing"/>"#
+        ));
+        assert!(!article.content.contains("<h1>"));
+        assert!(!article.content.contains("newsletter"));
+        assert!(!article.content.contains("Great post"));
+        assert!(!article.content.contains("Share"));
+        assert!(!article.content.contains("tracking"));
+        assert_eq!(
+            article.images,
+            vec!["https://blog.example.com/img/diagram.png"]
+        );
+        assert!(article
+            .excerpt
+            .unwrap()
+            .starts_with("Ownership is the feature"));
+    }
+
+    #[test]
+    fn extract_article_prefers_json_ld_and_cleans_titles() {
+        let html = r#"<html><head><title>Fallback title - Site</title>
+            <script type="application/ld+json">{"@graph":[{"@type":"WebSite","name":"Site"},
+              {"@type":["NewsArticle"],"headline":"Real Headline","author":[{"name":"A"},{"name":"B"}],
+               "datePublished":"2023-01-02"}]}</script></head>
+            <body><article><p>Some reasonably long paragraph of article text, with commas, to score.</p></article></body></html>"#;
+        let base = Url::parse("https://news.example.com/a").unwrap();
+        let article = extract_article(html, &base).unwrap();
+
+        assert_eq!(article.title, "Real Headline");
+        assert_eq!(article.byline.as_deref(), Some("A, B"));
+        assert_eq!(article.published_at.as_deref(), Some("2023-01-02"));
+        assert_eq!(
+            clean_title("A Long Enough Title | Site"),
+            "A Long Enough Title"
+        );
+        assert_eq!(clean_title("Short | Site"), "Short | Site");
+
+        let empty = extract_article("<html><body><nav>Menu</nav></body></html>", &base);
+        assert!(matches!(empty, Err(AppError::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,58 @@
+//! Web clipper data structures
+
+use crate::commands::library::LibraryDocument;
+use serde::{Deserialize, Serialize};
+
+/// Readable content and metadata extracted from a web page
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractedArticle {
+    pub title: String,
+    pub byline: Option<String>,
+    pub site_name: Option<String>,
+    pub excerpt: Option<String>,
+    pub published_at: Option<String>,
+    pub language: Option<String>,
+    /// Article body as an XHTML fragment with absolute URLs
+    pub content: String,
+    /// Image URLs referenced by the content, in document order
+    pub images: Vec<String>,
+    /// Length of the article text in characters
+    pub text_length: usize,
+}
+
+/// An image downloaded for a clipped article
+#[derive(Clone, Debug)]
+pub struct ClippedImage {
+    /// Original image URL
+    pub url: String,
+    /// File name inside the clip's `images` directory
+    pub file_name: String,
+    pub media_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Options for clipping a web article
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WebClipOptions {
+    /// Output format: "html" (default) or "epub"
+    pub format: Option<String>,
+    /// Whether to download images for offline reading (default true)
+    pub include_images: Option<bool>,
+    /// Output directory (defaults to `app_data/clips`)
+    pub output_dir: Option<String>,
+}
+
+/// Result of clipping a web article
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WebClipResult {
+    pub document: LibraryDocument,
+    /// Final URL after redirects
+    pub source_url: String,
+    pub title: String,
+    pub image_count: usize,
+    /// Images that could not be downloaded and still point to the web
+    pub failed_images: Vec<String>,
+}
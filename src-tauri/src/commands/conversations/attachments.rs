@@ -0,0 +1,344 @@
+//! Files attached to chat messages
+//!
+//! Attached files and images are copied into `app_data/conversation-attachments`
+//! under their SHA-256 digest, so the same file is stored once however often it
+//! is attached. The directory is exposed through the asset protocol, and files
+//! no message refers to any more are removed when conversations are deleted.
+
+use super::messages::get_message;
+use super::storage::{get_conversation_attachments_dir, lock_conversations};
+use super::types::{ConversationMessage, ConversationState, MessageAttachment};
+use crate::commands::clipper::detect_image_type;
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Stored files younger than this are kept even when unreferenced, they may
+/// belong to a message that is still being written
+const ORPHAN_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Bytes kept from the start of a file to detect images
+const SNIFF_BYTES: usize = 512;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Media type of common non-image attachments by extension
+fn mime_type_for_extension(extension: &str) -> Option<&'static str> {
+    match extension {
+        "pdf" => Some("application/pdf"),
+        "epub" => Some("application/epub+zip"),
+        "txt" => Some("text/plain"),
+        "md" => Some("text/markdown"),
+        "csv" => Some("text/csv"),
+        "html" | "htm" => Some("text/html"),
+        "json" => Some("application/json"),
+        _ => None,
+    }
+}
+
+/// Lowercase extension of a file name, if it is a plain one
+fn file_extension(name: &str) -> Option<String> {
+    let extension = Path::new(name)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+    (!extension.is_empty()
+        && extension.len() <= 10
+        && extension.chars().all(|c| c.is_ascii_alphanumeric()))
+    .then_some(extension)
+}
+
+/// Copy data into the attachment store, returning the attachment record
+///
+/// `name` is the original file name, kept for display.
+pub fn store_attachment<R: Read>(
+    dir: &Path,
+    reader: &mut R,
+    name: &str,
+) -> Result<MessageAttachment, AppError> {
+    Ok(store_attachment_created(dir, reader, name)?.0)
+}
+
+/// Like `store_attachment`, also telling whether the file is new to the store
+pub fn store_attachment_created<R: Read>(
+    dir: &Path,
+    reader: &mut R,
+    name: &str,
+) -> Result<(MessageAttachment, bool), AppError> {
+    fs::create_dir_all(dir)?;
+    let temp_path = dir.join(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let copied = (|| {
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        let mut hasher = Sha256::new();
+        let mut head = Vec::with_capacity(SNIFF_BYTES);
+        let mut size = 0u64;
+        let mut buffer = [0u8; 64 * 1024];
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            let chunk = &buffer[..read];
+            if head.len() < SNIFF_BYTES {
+                let take = (SNIFF_BYTES - head.len()).min(read);
+                head.extend_from_slice(&chunk[..take]);
+            }
+            hasher.update(chunk);
+            writer.write_all(chunk)?;
+            size += read as u64;
+        }
+        writer.flush()?;
+        Ok::<_, AppError>((format!("{:x}", hasher.finalize()), head, size))
+    })();
+    let (hash, head, size) = match copied {
+        Ok(copied) => copied,
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+    };
+
+    let (kind, extension, mime_type) = match detect_image_type(&head, None) {
+        Some((extension, mime_type)) => ("image", Some(extension.to_string()), Some(mime_type)),
+        None => {
+            let extension = file_extension(name);
+            let mime_type = extension.as_deref().and_then(mime_type_for_extension);
+            ("file", extension, mime_type)
+        }
+    };
+    let file_name = match &extension {
+        Some(extension) => format!("{}.{}", hash, extension),
+        None => hash,
+    };
+    let target = dir.join(file_name);
+    let existed = target.is_file();
+    if existed {
+        fs::remove_file(&temp_path)?;
+        // Restart the grace period of a file that was about to be collected
+        File::options()
+            .append(true)
+            .open(&target)?
+            .set_modified(SystemTime::now())?;
+    } else {
+        fs::rename(&temp_path, &target)?;
+    }
+
+    let attachment = MessageAttachment {
+        kind: kind.to_string(),
+        reference: target.to_string_lossy().to_string(),
+        name: Some(name.to_string()),
+        mime_type: mime_type.map(str::to_string),
+        size: Some(size),
+    };
+    Ok((attachment, !existed))
+}
+
+/// Copy a local file into the attachment store
+pub fn store_attachment_file(dir: &Path, path: &Path) -> Result<MessageAttachment, AppError> {
+    if !path.is_file() {
+        return Err(AppError::NotFound(format!("File not found: {:?}", path)));
+    }
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "attachment".to_string());
+    store_attachment(dir, &mut File::open(path)?, &name)
+}
+
+/// Record attachments on an existing message, after the ones it has
+pub fn add_message_attachments(
+    conn: &Connection,
+    message_id: &str,
+    attachments: &[MessageAttachment],
+) -> Result<ConversationMessage, AppError> {
+    let not_found = || AppError::NotFound(format!("Message '{}' not found", message_id));
+    let message = get_message(conn, message_id)?.ok_or_else(not_found)?;
+    let tx = conn.unchecked_transaction()?;
+    for (offset, attachment) in attachments.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO message_attachments (message_id, position, kind, reference, name,
+                 mime_type, size)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                message_id,
+                (message.attachments.len() + offset) as i64,
+                attachment.kind,
+                attachment.reference,
+                attachment.name,
+                attachment.mime_type,
+                attachment.size.map(|s| s as i64)
+            ],
+        )?;
+    }
+    tx.commit()?;
+    get_message(conn, message_id)?.ok_or_else(not_found)
+}
+
+/// Delete stored files no message refers to, returning how many were removed
+///
+/// Files modified within `grace` are kept.
+pub fn collect_orphan_attachments(
+    conn: &Connection,
+    dir: &Path,
+    grace: Duration,
+) -> Result<usize, AppError> {
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+    let mut stmt = conn.prepare("SELECT DISTINCT reference FROM message_attachments")?;
+    let referenced: HashSet<String> = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<_, _>>()?;
+    let cutoff = SystemTime::now() - grace;
+
+    let mut removed = 0;
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let recent = metadata
+            .modified()
+            .map_or(true, |modified| modified > cutoff);
+        if !metadata.is_file() || recent || referenced.contains(&*path.to_string_lossy()) {
+            continue;
+        }
+        if fs::remove_file(&path).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Remove unreferenced attachment files, logging instead of failing
+pub fn collect_orphans_quietly(app: &tauri::AppHandle, conn: &Connection) {
+    let removed = get_conversation_attachments_dir(app)
+        .and_then(|dir| collect_orphan_attachments(conn, &dir, ORPHAN_GRACE_PERIOD));
+    match removed {
+        Ok(0) => {}
+        Ok(count) => log::info!("Removed {} unreferenced conversation attachments", count),
+        Err(e) => log::warn!("Failed to remove unreferenced attachments: {}", e),
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Copy a file into the attachment store for a message about to be sent
+///
+/// The returned `reference` is loadable via `convertFileSrc`.
+#[tauri::command]
+pub async fn conversation_store_attachment(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<MessageAttachment, AppError> {
+    let dir = get_conversation_attachments_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || store_attachment_file(&dir, Path::new(&path)))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Copy files into the attachment store and attach them to a message
+#[tauri::command]
+pub async fn conversation_add_attachments(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, ConversationState>,
+    message_id: String,
+    paths: Vec<String>,
+) -> Result<ConversationMessage, AppError> {
+    let dir = get_conversation_attachments_dir(&app)?;
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let attachments = paths
+            .iter()
+            .map(|path| store_attachment_file(&dir, Path::new(path)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let conn = lock_conversations(&state)?;
+        add_message_attachments(&conn, &message_id, &attachments)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Remove stored attachment files no message refers to
+#[tauri::command]
+pub fn collect_conversation_attachments(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, ConversationState>,
+) -> Result<usize, AppError> {
+    let dir = get_conversation_attachments_dir(&app)?;
+    let conn = lock_conversations(&state)?;
+    let removed = collect_orphan_attachments(&conn, &dir, ORPHAN_GRACE_PERIOD)?;
+    log::info!("Removed {} unreferenced conversation attachments", removed);
+    Ok(removed)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::messages::append_message;
+    use super::super::records::{create_conversation, delete_conversation};
+    use super::super::storage::open_conversations_in_memory;
+    use super::super::types::{ConversationInput, MessageInput};
+    use super::*;
+
+    #[test]
+    fn attachments_are_deduplicated_and_collected_when_orphaned() {
+        let conn = open_conversations_in_memory();
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("store");
+        let source = dir.path().join("Notes.TXT");
+        fs::write(&source, "margin notes").unwrap();
+
+        let first = store_attachment_file(&store, &source).unwrap();
+        let image = store_attachment(&store, &mut &b"\x89PNG\r\n\x1a\n0000"[..], "shot").unwrap();
+        let again = store_attachment(&store, &mut "margin notes".as_bytes(), "copy.txt").unwrap();
+        assert_eq!(first.reference, again.reference);
+        assert!(first.reference.ends_with(".txt"));
+        assert_eq!(
+            (first.kind.as_str(), first.mime_type.as_deref(), first.size),
+            ("file", Some("text/plain"), Some(12))
+        );
+        assert_eq!(first.name.as_deref(), Some("Notes.TXT"));
+        assert_eq!(
+            (image.kind.as_str(), image.mime_type.as_deref()),
+            ("image", Some("image/png"))
+        );
+        assert_eq!(fs::read_dir(&store).unwrap().count(), 2);
+
+        let conv = create_conversation(&conn, &ConversationInput::default(), 1).unwrap();
+        let input = MessageInput {
+            role: "user".to_string(),
+            content: "See attached".to_string(),
+            attachments: vec![first.clone()],
+            ..Default::default()
+        };
+        let message = append_message(&conn, &conv.id, &input, 2).unwrap();
+        let message =
+            add_message_attachments(&conn, &message.id, std::slice::from_ref(&image)).unwrap();
+        assert_eq!(message.attachments, vec![first.clone(), image.clone()]);
+
+        // Fresh files are kept during the grace period
+        delete_conversation(&conn, &conv.id).unwrap();
+        assert_eq!(
+            collect_orphan_attachments(&conn, &store, ORPHAN_GRACE_PERIOD).unwrap(),
+            0
+        );
+        assert_eq!(
+            collect_orphan_attachments(&conn, &store, Duration::ZERO).unwrap(),
+            2
+        );
+        assert!(!Path::new(&first.reference).exists());
+    }
+}
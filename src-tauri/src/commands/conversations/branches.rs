@@ -0,0 +1,330 @@
+//! Message branches: edits and regenerations fork the conversation
+//!
+//! Every message links to the message it replies to, so a conversation is a
+//! tree. Editing or regenerating a message adds a sibling version under the
+//! same parent, and the conversation remembers which leaf is shown.
+
+use super::messages::{
+    get_message, insert_message, list_attachments, list_messages, row_to_message, MESSAGE_COLUMNS,
+};
+use super::records::get_conversation;
+use super::storage::lock_conversations;
+use super::types::{
+    Conversation, ConversationMessage, ConversationState, ConversationTree, MessageInput,
+    MessageTreeNode,
+};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn message_not_found(id: &str) -> AppError {
+    AppError::NotFound(format!("Message '{}' not found", id))
+}
+
+/// Messages from the root to the active leaf of a conversation
+pub fn list_active_messages(
+    conn: &Connection,
+    conversation_id: &str,
+) -> Result<Vec<ConversationMessage>, AppError> {
+    let sql = format!(
+        "WITH RECURSIVE path(message_id, depth) AS (
+             SELECT active_leaf_id, 0 FROM conversations
+             WHERE id = ?1 AND active_leaf_id IS NOT NULL
+             UNION ALL
+             SELECT m.parent_id, path.depth + 1 FROM messages m
+             JOIN path ON m.id = path.message_id
+             WHERE m.parent_id IS NOT NULL
+         )
+         SELECT {} FROM path JOIN messages ON messages.id = path.message_id
+         ORDER BY path.depth DESC",
+        MESSAGE_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![conversation_id], row_to_message)?;
+    let mut messages = rows.collect::<Result<Vec<_>, _>>()?;
+    for message in &mut messages {
+        message.attachments = list_attachments(conn, &message.id)?;
+    }
+    Ok(messages)
+}
+
+/// Add a new version of a message next to it, under the same parent
+///
+/// Used for regenerated replies; `input.parent_id` is ignored. The new
+/// version becomes the end of the active branch.
+pub fn fork_message(
+    conn: &Connection,
+    message_id: &str,
+    input: &MessageInput,
+    now: i64,
+) -> Result<ConversationMessage, AppError> {
+    let original = get_message(conn, message_id)?.ok_or_else(|| message_not_found(message_id))?;
+    insert_message(
+        conn,
+        &original.conversation_id,
+        original.parent_id.as_deref(),
+        input,
+        now,
+    )
+}
+
+/// Add an edited version of a message, keeping its role, model and attachments
+pub fn edit_message(
+    conn: &Connection,
+    message_id: &str,
+    content: &str,
+    now: i64,
+) -> Result<ConversationMessage, AppError> {
+    let original = get_message(conn, message_id)?.ok_or_else(|| message_not_found(message_id))?;
+    let input = MessageInput {
+        role: original.role,
+        content: content.to_string(),
+        model: original.model,
+        metadata: Some(original.metadata),
+        attachments: original.attachments,
+        ..Default::default()
+    };
+    insert_message(
+        conn,
+        &original.conversation_id,
+        original.parent_id.as_deref(),
+        &input,
+        now,
+    )
+}
+
+/// Show the branch through a message, following its latest replies
+///
+/// Returns the id of the new active leaf.
+pub fn switch_branch(conn: &Connection, message_id: &str) -> Result<String, AppError> {
+    let original = get_message(conn, message_id)?.ok_or_else(|| message_not_found(message_id))?;
+    let mut leaf = original.id;
+    while let Some(child) = conn
+        .query_row(
+            "SELECT id FROM messages WHERE parent_id = ?1 ORDER BY position DESC LIMIT 1",
+            params![leaf],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+    {
+        leaf = child;
+    }
+    conn.execute(
+        "UPDATE conversations SET active_leaf_id = ?2 WHERE id = ?1",
+        params![original.conversation_id, leaf],
+    )?;
+    Ok(leaf)
+}
+
+/// All messages of a conversation with their replies and versions
+pub fn get_conversation_tree(
+    conn: &Connection,
+    conversation_id: &str,
+) -> Result<ConversationTree, AppError> {
+    let active_leaf_id: Option<String> = conn
+        .query_row(
+            "SELECT active_leaf_id FROM conversations WHERE id = ?1",
+            params![conversation_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Conversation '{}' not found", conversation_id))
+        })?;
+
+    let messages = list_messages(conn, conversation_id)?;
+    let mut children: HashMap<Option<&str>, Vec<&str>> = HashMap::new();
+    for message in &messages {
+        children
+            .entry(message.parent_id.as_deref())
+            .or_default()
+            .push(&message.id);
+    }
+    let nodes = messages
+        .iter()
+        .map(|message| {
+            let siblings = &children[&message.parent_id.as_deref()];
+            let version = siblings
+                .iter()
+                .position(|id| *id == message.id)
+                .unwrap_or(0);
+            MessageTreeNode {
+                message: message.clone(),
+                children: children
+                    .get(&Some(message.id.as_str()))
+                    .map(|ids| ids.iter().map(|id| id.to_string()).collect())
+                    .unwrap_or_default(),
+                version: version as u32 + 1,
+                version_count: siblings.len() as u32,
+            }
+        })
+        .collect();
+
+    let parents: HashMap<&str, Option<&str>> = messages
+        .iter()
+        .map(|m| (m.id.as_str(), m.parent_id.as_deref()))
+        .collect();
+    let mut active_path = Vec::new();
+    let mut current = active_leaf_id.as_deref();
+    while let Some(id) = current {
+        active_path.push(id.to_string());
+        current = parents.get(id).copied().flatten();
+    }
+    active_path.reverse();
+
+    Ok(ConversationTree {
+        conversation_id: conversation_id.to_string(),
+        active_leaf_id,
+        active_path,
+        nodes,
+    })
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Add a regenerated version of a message next to it
+#[tauri::command]
+pub fn conversation_fork_message(
+    state: tauri::State<'_, ConversationState>,
+    message_id: String,
+    message: MessageInput,
+) -> Result<ConversationMessage, AppError> {
+    let conn = lock_conversations(&state)?;
+    fork_message(&conn, &message_id, &message, chrono::Utc::now().timestamp())
+}
+
+/// Add an edited version of a message, starting a new branch
+#[tauri::command]
+pub fn conversation_edit_message(
+    state: tauri::State<'_, ConversationState>,
+    message_id: String,
+    content: String,
+) -> Result<ConversationMessage, AppError> {
+    let conn = lock_conversations(&state)?;
+    edit_message(&conn, &message_id, &content, chrono::Utc::now().timestamp())
+}
+
+/// Show the branch through a message and return the updated conversation
+#[tauri::command]
+pub fn conversation_switch_branch(
+    state: tauri::State<'_, ConversationState>,
+    message_id: String,
+) -> Result<Conversation, AppError> {
+    let conn = lock_conversations(&state)?;
+    switch_branch(&conn, &message_id)?;
+    let conversation_id = get_message(&conn, &message_id)?
+        .ok_or_else(|| message_not_found(&message_id))?
+        .conversation_id;
+    get_conversation(&conn, &conversation_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Conversation '{}' not found", conversation_id)))
+}
+
+/// Get all branches of a conversation
+#[tauri::command]
+pub fn conversation_get_tree(
+    state: tauri::State<'_, ConversationState>,
+    conversation_id: String,
+) -> Result<ConversationTree, AppError> {
+    let conn = lock_conversations(&state)?;
+    get_conversation_tree(&conn, &conversation_id)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::encryption::install_content_cipher;
+    use super::super::messages::append_message;
+    use super::super::records::create_conversation;
+    use super::super::storage::{open_conversations_in_memory, CONVERSATION_MIGRATIONS};
+    use super::super::types::ConversationInput;
+    use super::*;
+    use crate::db;
+
+    fn message(role: &str, content: &str) -> MessageInput {
+        MessageInput {
+            role: role.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn active_contents(conn: &Connection, conversation_id: &str) -> Vec<String> {
+        list_active_messages(conn, conversation_id)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.content)
+            .collect()
+    }
+
+    #[test]
+    fn edits_and_regenerations_fork_the_conversation() {
+        let conn = open_conversations_in_memory();
+        let conv = create_conversation(&conn, &ConversationInput::default(), 1).unwrap();
+        let question = append_message(&conn, &conv.id, &message("user", "Q1"), 2).unwrap();
+        let answer = append_message(&conn, &conv.id, &message("assistant", "A1"), 3).unwrap();
+        assert_eq!(answer.parent_id.as_deref(), Some(question.id.as_str()));
+
+        let regenerated = fork_message(&conn, &answer.id, &message("assistant", "A1b"), 4).unwrap();
+        assert_eq!(active_contents(&conn, &conv.id), vec!["Q1", "A1b"]);
+
+        let edited = edit_message(&conn, &question.id, "Q2", 5).unwrap();
+        assert_eq!(edited.parent_id, None);
+        append_message(&conn, &conv.id, &message("assistant", "A2"), 6).unwrap();
+        assert_eq!(active_contents(&conn, &conv.id), vec!["Q2", "A2"]);
+
+        // Switching back to the first question follows its latest reply
+        assert_eq!(switch_branch(&conn, &question.id).unwrap(), regenerated.id);
+        assert_eq!(active_contents(&conn, &conv.id), vec!["Q1", "A1b"]);
+
+        let tree = get_conversation_tree(&conn, &conv.id).unwrap();
+        assert_eq!(tree.nodes.len(), 5);
+        assert_eq!(
+            tree.active_path,
+            vec![question.id.clone(), regenerated.id.clone()]
+        );
+        let first = &tree.nodes[0];
+        assert_eq!((first.version, first.version_count), (1, 2));
+        assert_eq!(
+            first.children,
+            vec![answer.id.clone(), regenerated.id.clone()]
+        );
+        assert_eq!(tree.nodes[2].version, 2);
+
+        let mut reply = message("user", "elsewhere");
+        reply.parent_id = Some("missing".to_string());
+        assert!(matches!(
+            append_message(&conn, &conv.id, &reply, 7),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn existing_messages_become_a_single_branch() {
+        let mut conn = db::open_in_memory(&CONVERSATION_MIGRATIONS[..3]).unwrap();
+        conn.execute_batch(
+            "INSERT INTO conversations (id, title, created_at, updated_at)
+                 VALUES ('conv_1', 'Old', 1, 1);
+             INSERT INTO messages (id, conversation_id, position, role, content, created_at)
+                 VALUES ('m2', 'conv_1', 1, 'assistant', 'A', 1),
+                        ('m1', 'conv_1', 0, 'user', 'Q', 1),
+                        ('m3', 'conv_1', 2, 'user', 'Q again', 1);",
+        )
+        .unwrap();
+        db::apply_migrations(&mut conn, CONVERSATION_MIGRATIONS).unwrap();
+        install_content_cipher(&conn, None).unwrap();
+
+        assert_eq!(active_contents(&conn, "conv_1"), vec!["Q", "A", "Q again"]);
+        let tree = get_conversation_tree(&conn, "conv_1").unwrap();
+        assert_eq!(tree.active_leaf_id.as_deref(), Some("m3"));
+        assert_eq!(tree.active_path, vec!["m1", "m2", "m3"]);
+    }
+}
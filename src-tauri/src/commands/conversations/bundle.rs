@@ -0,0 +1,717 @@
+//! Portable conversation bundles
+//!
+//! A bundle is a zip archive holding conversations with all their branches,
+//! the local files they attach and the prompt templates, for moving history
+//! to another machine. Bundles are written in plain text, also when
+//! conversation encryption is on. The manifest lists the SHA-256 of every
+//! other entry, and an import checks them all before changing anything.
+
+use super::attachments::store_attachment_created;
+use super::messages::{append_message, list_messages};
+use super::records::{create_conversation, delete_conversation, get_conversation_summary};
+use super::storage::{get_conversation_attachments_dir, lock_conversations};
+use super::templates::{export_templates_json, import_templates_json};
+use super::types::{
+    ConversationBundleExport, ConversationBundleImport, ConversationInput, ConversationState,
+    MessageAttachment, MessageInput,
+};
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const CONVERSATIONS_ENTRY: &str = "conversations.json";
+const TEMPLATES_ENTRY: &str = "prompt-templates.json";
+const ATTACHMENTS_PREFIX: &str = "attachments/";
+
+/// Attachment kinds whose local files are copied into bundles
+const FILE_ATTACHMENT_KINDS: &[&str] = &["file", "image"];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleManifest {
+    version: u32,
+    source: String,
+    exported_at: i64,
+    /// SHA-256 of every other entry by name, hex encoded; missing in older
+    /// bundles
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    checksums: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleConversation {
+    id: String,
+    title: String,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    document_name: Option<String>,
+    metadata: serde_json::Value,
+    created_at: i64,
+    updated_at: i64,
+    archived_at: Option<i64>,
+    active_leaf_id: Option<String>,
+    /// All branches, in creation order
+    messages: Vec<BundleMessage>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleMessage {
+    id: String,
+    parent_id: Option<String>,
+    role: String,
+    content: String,
+    model: Option<String>,
+    token_count: u32,
+    input_tokens: Option<u32>,
+    output_tokens: Option<u32>,
+    metadata: serde_json::Value,
+    attachments: Vec<BundleAttachment>,
+    created_at: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleAttachment {
+    #[serde(flatten)]
+    attachment: MessageAttachment,
+    /// Archive entry holding a copy of the attached file
+    entry: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn zip_err(e: zip::result::ZipError) -> AppError {
+    AppError::InvalidInput(format!("Invalid conversation bundle: {}", e))
+}
+
+/// Copy everything from `reader` to `writer`, returning the SHA-256 of it
+fn copy_hashed<R: Read, W: Write>(reader: &mut R, writer: &mut W) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Check every entry of a bundle against the manifest checksums
+///
+/// Fails naming each entry that is missing, not listed, unreadable or has
+/// different content. Bundles from before checksums were added are let
+/// through unverified.
+fn verify_bundle(
+    archive: &mut ZipArchive<File>,
+    checksums: &BTreeMap<String, String>,
+) -> Result<(), AppError> {
+    if checksums.is_empty() {
+        log::warn!("Conversation bundle has no checksums, importing it unverified");
+        return Ok(());
+    }
+    let mut failed = Vec::new();
+    let mut seen = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(zip_err)?;
+        let name = entry.name().to_string();
+        if name == MANIFEST_ENTRY {
+            continue;
+        }
+        let hash = copy_hashed(&mut entry, &mut std::io::sink());
+        match (checksums.get(&name), hash) {
+            (None, _) => failed.push(format!("{} (not in the manifest)", name)),
+            (Some(expected), Ok(hash)) if *expected == hash => {}
+            (Some(_), Ok(_)) => failed.push(format!("{} (checksum mismatch)", name)),
+            (Some(_), Err(e)) => failed.push(format!("{} ({})", name, e)),
+        }
+        seen.push(name);
+    }
+    for name in checksums.keys().filter(|name| !seen.contains(name)) {
+        failed.push(format!("{} (missing)", name));
+    }
+    if !failed.is_empty() {
+        return Err(AppError::InvalidInput(format!(
+            "Bundle entries failed verification: {}",
+            failed.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Keep only characters that are safe in a single path component
+fn safe_component(value: &str) -> String {
+    let safe: String = value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match safe.trim_matches('.') {
+        "" => "attachment".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+fn load_bundle_conversation(conn: &Connection, id: &str) -> Result<BundleConversation, AppError> {
+    let summary = get_conversation_summary(conn, id)?
+        .ok_or_else(|| AppError::NotFound(format!("Conversation '{}' not found", id)))?;
+    let active_leaf_id: Option<String> = conn.query_row(
+        "SELECT active_leaf_id FROM conversations WHERE id = ?1",
+        params![id],
+        |row| row.get(0),
+    )?;
+    let messages = list_messages(conn, id)?
+        .into_iter()
+        .map(|m| BundleMessage {
+            attachments: m
+                .attachments
+                .into_iter()
+                .map(|attachment| BundleAttachment {
+                    attachment,
+                    entry: None,
+                })
+                .collect(),
+            id: m.id,
+            parent_id: m.parent_id,
+            role: m.role,
+            content: m.content,
+            model: m.model,
+            token_count: m.token_count,
+            input_tokens: m.input_tokens,
+            output_tokens: m.output_tokens,
+            metadata: m.metadata,
+            created_at: m.created_at,
+        })
+        .collect();
+    Ok(BundleConversation {
+        id: summary.id,
+        title: summary.title,
+        document_id: summary.document_id,
+        document_path: summary.document_path,
+        document_name: summary.document_name,
+        metadata: summary.metadata,
+        created_at: summary.created_at,
+        updated_at: summary.updated_at,
+        archived_at: summary.archived_at,
+        active_leaf_id,
+        messages,
+    })
+}
+
+/// Write conversations (all, or the given ids), their attached files and all
+/// prompt templates to a bundle
+pub fn write_bundle(
+    conn: &Connection,
+    path: &Path,
+    ids: Option<&[String]>,
+) -> Result<ConversationBundleExport, AppError> {
+    let ids: Vec<String> = match ids {
+        Some(ids) => ids.to_vec(),
+        None => {
+            let mut stmt = conn.prepare("SELECT id FROM conversations ORDER BY created_at, id")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        }
+    };
+    let mut conversations = ids
+        .iter()
+        .map(|id| load_bundle_conversation(conn, id))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (templates, template_count) = export_templates_json(conn, None)?;
+
+    // Pick archive entries for attached files that still exist
+    let mut files: Vec<(String, PathBuf)> = Vec::new();
+    for message in conversations.iter_mut().flat_map(|c| c.messages.iter_mut()) {
+        for (position, bundled) in message.attachments.iter_mut().enumerate() {
+            let source = PathBuf::from(&bundled.attachment.reference);
+            if !FILE_ATTACHMENT_KINDS.contains(&bundled.attachment.kind.as_str())
+                || !source.is_file()
+            {
+                continue;
+            }
+            let file_name = source
+                .file_name()
+                .map(|name| safe_component(&name.to_string_lossy()))
+                .unwrap_or_else(|| "attachment".to_string());
+            let entry = format!(
+                "{}{}/{}-{}",
+                ATTACHMENTS_PREFIX,
+                safe_component(&message.id),
+                position,
+                file_name
+            );
+            bundled.entry = Some(entry.clone());
+            files.push((entry, source));
+        }
+    }
+
+    let mut manifest = BundleManifest {
+        version: 1,
+        source: "sast-readium".to_string(),
+        exported_at: chrono::Utc::now().timestamp(),
+        checksums: BTreeMap::new(),
+    };
+    let result = ConversationBundleExport {
+        conversation_count: conversations.len(),
+        message_count: conversations.iter().map(|c| c.messages.len()).sum(),
+        attachment_count: files.len(),
+        template_count,
+    };
+
+    let temp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let written = (|| {
+        let file = File::create(&temp_path)?;
+        let mut zip = ZipWriter::new(BufWriter::new(file));
+        let options = SimpleFileOptions::default();
+        let entries = [
+            (CONVERSATIONS_ENTRY, serde_json::to_string(&conversations)?),
+            (TEMPLATES_ENTRY, templates),
+        ];
+        for (name, data) in entries {
+            zip.start_file(name, options).map_err(zip_err)?;
+            let hash = copy_hashed(&mut data.as_bytes(), &mut zip)?;
+            manifest.checksums.insert(name.to_string(), hash);
+        }
+        // Hashed as copied, so a file changing meanwhile cannot mismatch
+        for (entry, source) in &files {
+            zip.start_file(entry.as_str(), options).map_err(zip_err)?;
+            let hash = copy_hashed(&mut File::open(source)?, &mut zip)?;
+            manifest.checksums.insert(entry.clone(), hash);
+        }
+        zip.start_file(MANIFEST_ENTRY, options).map_err(zip_err)?;
+        zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+        zip.finish().map_err(zip_err)?.flush()?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written.map(|_| result)
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<String>, AppError> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(zip_err(e)),
+    };
+    let mut text = String::new();
+    entry.read_to_string(&mut text)?;
+    Ok(Some(text))
+}
+
+/// Copy a bundled file into the attachment store in `dir`, returning its
+/// new path; files new to the store are added to `created`
+fn extract_attachment(
+    archive: &mut ZipArchive<File>,
+    entry: &str,
+    dir: &Path,
+    created: &mut Vec<PathBuf>,
+) -> Result<String, AppError> {
+    let mut file = archive.by_name(entry).map_err(zip_err)?;
+    let name = Path::new(entry)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "attachment".to_string());
+    let (attachment, new) = store_attachment_created(dir, &mut file, &name)?;
+    if new {
+        created.push(PathBuf::from(&attachment.reference));
+    }
+    Ok(attachment.reference)
+}
+
+fn import_conversation(
+    conn: &Connection,
+    archive: &mut ZipArchive<File>,
+    conversation: BundleConversation,
+    attachments_dir: &Path,
+    result: &mut ConversationBundleImport,
+    created: &mut Vec<PathBuf>,
+) -> Result<(), AppError> {
+    let input = ConversationInput {
+        id: Some(conversation.id.clone()),
+        title: Some(conversation.title),
+        document_id: conversation.document_id,
+        document_path: conversation.document_path,
+        document_name: conversation.document_name,
+        metadata: Some(conversation.metadata),
+        created_at: Some(conversation.created_at),
+    };
+    create_conversation(conn, &input, conversation.created_at)?;
+
+    for message in conversation.messages {
+        let mut attachments = Vec::with_capacity(message.attachments.len());
+        for bundled in message.attachments {
+            let mut attachment = bundled.attachment;
+            if let Some(entry) = bundled.entry.filter(|e| e.starts_with(ATTACHMENTS_PREFIX)) {
+                attachment.reference =
+                    extract_attachment(archive, &entry, attachments_dir, created)?;
+                result.attachment_count += 1;
+            }
+            attachments.push(attachment);
+        }
+        let input = MessageInput {
+            id: Some(message.id),
+            parent_id: message.parent_id,
+            role: message.role,
+            content: message.content,
+            model: message.model,
+            token_count: Some(message.token_count),
+            input_tokens: message.input_tokens,
+            output_tokens: message.output_tokens,
+            metadata: Some(message.metadata),
+            attachments,
+            created_at: Some(message.created_at),
+        };
+        append_message(conn, &conversation.id, &input, conversation.updated_at)?;
+        result.message_count += 1;
+    }
+
+    conn.execute(
+        "UPDATE conversations SET active_leaf_id = COALESCE(?2, active_leaf_id),
+             updated_at = ?3, archived_at = ?4
+         WHERE id = ?1",
+        params![
+            conversation.id,
+            conversation.active_leaf_id,
+            conversation.updated_at,
+            conversation.archived_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Import a bundle, skipping conversations whose id already exists
+///
+/// Nothing is imported unless every entry matches its manifest checksum.
+/// Attached files are copied into the attachment store in `attachments_dir`.
+/// Templates follow the template import rules.
+pub fn read_bundle(
+    conn: &Connection,
+    path: &Path,
+    attachments_dir: &Path,
+) -> Result<ConversationBundleImport, AppError> {
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(zip_err)?;
+    let manifest: BundleManifest = serde_json::from_str(
+        &read_entry(&mut archive, MANIFEST_ENTRY)?
+            .ok_or_else(|| AppError::InvalidInput("Bundle has no manifest".to_string()))?,
+    )?;
+    if manifest.version != 1 {
+        return Err(AppError::InvalidInput(format!(
+            "Unsupported bundle version {}",
+            manifest.version
+        )));
+    }
+    verify_bundle(&mut archive, &manifest.checksums)?;
+    let conversations: Vec<BundleConversation> =
+        match read_entry(&mut archive, CONVERSATIONS_ENTRY)? {
+            Some(data) => serde_json::from_str(&data)?,
+            None => Vec::new(),
+        };
+
+    let mut result = ConversationBundleImport::default();
+    for conversation in conversations {
+        if get_conversation_summary(conn, &conversation.id)?.is_some() {
+            result.skipped_count += 1;
+            continue;
+        }
+        let id = conversation.id.clone();
+        let title = conversation.title.clone();
+        let mut imported = ConversationBundleImport::default();
+        let mut created = Vec::new();
+        match import_conversation(
+            conn,
+            &mut archive,
+            conversation,
+            attachments_dir,
+            &mut imported,
+            &mut created,
+        ) {
+            Ok(()) => {
+                result.imported_count += 1;
+                result.message_count += imported.message_count;
+                result.attachment_count += imported.attachment_count;
+            }
+            Err(e) => {
+                // Leave no half-imported conversation or its files behind
+                let _ = delete_conversation(conn, &id);
+                for file in created {
+                    let _ = fs::remove_file(file);
+                }
+                result.skipped_count += 1;
+                result.errors.push(format!("Skipped '{}': {}", title, e));
+            }
+        }
+    }
+
+    if let Some(templates) = read_entry(&mut archive, TEMPLATES_ENTRY)? {
+        result.templates = import_templates_json(conn, &templates, chrono::Utc::now().timestamp())?;
+    }
+    Ok(result)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Export conversations (all, or the given ids) with their attachments and
+/// the prompt templates to a bundle file
+#[tauri::command]
+pub async fn export_conversations_bundle(
+    state: tauri::State<'_, ConversationState>,
+    path: String,
+    ids: Option<Vec<String>>,
+) -> Result<ConversationBundleExport, AppError> {
+    let state = state.inner().clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let conn = lock_conversations(&state)?;
+        write_bundle(&conn, Path::new(&path), ids.as_deref())
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    log::info!(
+        "Exported {} conversations to a bundle",
+        result.conversation_count
+    );
+    Ok(result)
+}
+
+/// Import a conversation bundle, skipping conversations that already exist
+#[tauri::command]
+pub async fn import_conversations_bundle(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, ConversationState>,
+    path: String,
+) -> Result<ConversationBundleImport, AppError> {
+    let bundle = PathBuf::from(&path);
+    if !bundle.is_file() {
+        return Err(AppError::NotFound(format!("File not found: {}", path)));
+    }
+    let attachments_dir = get_conversation_attachments_dir(&app)?;
+    let state = state.inner().clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let conn = lock_conversations(&state)?;
+        read_bundle(&conn, &bundle, &attachments_dir)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    log::info!(
+        "Imported {} conversations from a bundle, {} skipped",
+        result.imported_count,
+        result.skipped_count
+    );
+    Ok(result)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::branches::{fork_message, get_conversation_tree, switch_branch};
+    use super::super::storage::open_conversations_in_memory;
+    use super::super::templates::{create_template, list_templates};
+    use super::super::types::PromptTemplateInput;
+    use super::*;
+    use tempfile::tempdir;
+
+    fn message(role: &str, content: &str) -> MessageInput {
+        MessageInput {
+            role: role.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bundles_round_trip_branches_files_and_templates() {
+        let dir = tempdir().unwrap();
+        let notes = dir.path().join("notes.txt");
+        fs::write(&notes, "margin notes").unwrap();
+
+        let source = open_conversations_in_memory();
+        let input = ConversationInput {
+            id: Some("conv_1".to_string()),
+            title: Some("Whales".to_string()),
+            ..Default::default()
+        };
+        create_conversation(&source, &input, 1).unwrap();
+        let mut question = message("user", "Read my notes");
+        question.attachments = vec![MessageAttachment {
+            kind: "file".to_string(),
+            reference: notes.to_string_lossy().to_string(),
+            name: Some("notes.txt".to_string()),
+            mime_type: Some("text/plain".to_string()),
+            size: Some(12),
+        }];
+        append_message(&source, "conv_1", &question, 2).unwrap();
+        let answer = append_message(&source, "conv_1", &message("assistant", "A"), 3).unwrap();
+        fork_message(&source, &answer.id, &message("assistant", "B"), 4).unwrap();
+        switch_branch(&source, &answer.id).unwrap();
+        let template = PromptTemplateInput {
+            name: "Define".to_string(),
+            body: "Define {{word}}".to_string(),
+            ..Default::default()
+        };
+        create_template(&source, &template, 5).unwrap();
+
+        let bundle = dir.path().join("chats.zip");
+        let exported = write_bundle(&source, &bundle, None).unwrap();
+        assert_eq!(
+            (
+                exported.conversation_count,
+                exported.message_count,
+                exported.attachment_count
+            ),
+            (1, 3, 1)
+        );
+
+        let target = open_conversations_in_memory();
+        let attachments = dir.path().join("imported");
+        let imported = read_bundle(&target, &bundle, &attachments).unwrap();
+        assert_eq!((imported.imported_count, imported.message_count), (1, 3));
+        assert_eq!(imported.attachment_count, 1);
+        assert_eq!(imported.templates.imported_count, 1);
+        assert_eq!(list_templates(&target, None).unwrap().len(), 1);
+
+        let before = get_conversation_tree(&source, "conv_1").unwrap();
+        let after = get_conversation_tree(&target, "conv_1").unwrap();
+        assert_eq!(after.active_path, before.active_path);
+        assert_eq!(after.nodes.len(), 3);
+        let copied = &after.nodes[0].message.attachments[0].reference;
+        assert!(copied.starts_with(attachments.to_string_lossy().as_ref()));
+        assert_eq!(fs::read_to_string(copied).unwrap(), "margin notes");
+        let summary = get_conversation_summary(&target, "conv_1")
+            .unwrap()
+            .unwrap();
+        assert_eq!((summary.title.as_str(), summary.updated_at), ("Whales", 4));
+
+        // A second import finds everything already there
+        let again = read_bundle(&target, &bundle, &attachments).unwrap();
+        assert_eq!((again.imported_count, again.skipped_count), (0, 1));
+        assert_eq!(again.templates.skipped_count, 1);
+    }
+
+    #[test]
+    fn failed_imports_remove_their_files() {
+        let dir = tempdir().unwrap();
+        let notes = dir.path().join("notes.txt");
+        fs::write(&notes, "margin notes").unwrap();
+        let source = open_conversations_in_memory();
+        let input = ConversationInput {
+            id: Some("conv_1".to_string()),
+            ..Default::default()
+        };
+        create_conversation(&source, &input, 1).unwrap();
+        let mut question = message("user", "Read my notes");
+        question.attachments = vec![MessageAttachment {
+            kind: "file".to_string(),
+            reference: notes.to_string_lossy().to_string(),
+            name: None,
+            mime_type: None,
+            size: None,
+        }];
+        append_message(&source, "conv_1", &question, 2).unwrap();
+        append_message(&source, "conv_1", &message("assistant", "Refused"), 3).unwrap();
+        let bundle = dir.path().join("chats.zip");
+        write_bundle(&source, &bundle, None).unwrap();
+
+        // The second message fails after the first one's file was extracted
+        let target = open_conversations_in_memory();
+        target
+            .execute_batch(
+                "CREATE TEMP TRIGGER refuse BEFORE INSERT ON messages
+                 WHEN NEW.role = 'assistant' BEGIN SELECT RAISE(ABORT, 'refused'); END;",
+            )
+            .unwrap();
+        let attachments = dir.path().join("imported");
+        let imported = read_bundle(&target, &bundle, &attachments).unwrap();
+        assert_eq!((imported.imported_count, imported.skipped_count), (0, 1));
+        assert_eq!(imported.errors.len(), 1);
+        assert!(get_conversation_summary(&target, "conv_1")
+            .unwrap()
+            .is_none());
+        assert_eq!(fs::read_dir(&attachments).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn damaged_bundles_are_rejected_before_importing() {
+        let dir = tempdir().unwrap();
+        let source = open_conversations_in_memory();
+        let input = ConversationInput {
+            id: Some("conv_1".to_string()),
+            title: Some("Whales".to_string()),
+            ..Default::default()
+        };
+        create_conversation(&source, &input, 1).unwrap();
+        append_message(&source, "conv_1", &message("user", "Hello"), 2).unwrap();
+        let bundle = dir.path().join("chats.zip");
+        write_bundle(&source, &bundle, None).unwrap();
+
+        // Copy the bundle, changing the conversations and adding an entry
+        let damaged = dir.path().join("damaged.zip");
+        let mut archive = ZipArchive::new(File::open(&bundle).unwrap()).unwrap();
+        let mut zip = ZipWriter::new(File::create(&damaged).unwrap());
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).unwrap();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            if entry.name() == CONVERSATIONS_ENTRY {
+                data = String::from_utf8(data)
+                    .unwrap()
+                    .replace("Hello", "Goodbye")
+                    .into_bytes();
+            }
+            zip.start_file(entry.name(), SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(&data).unwrap();
+        }
+        zip.start_file("attachments/extra.bin", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"extra").unwrap();
+        zip.finish().unwrap();
+
+        let target = open_conversations_in_memory();
+        let attachments = dir.path().join("imported");
+        let err = read_bundle(&target, &damaged, &attachments)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("conversations.json (checksum mismatch)"));
+        assert!(err.contains("attachments/extra.bin (not in the manifest)"));
+        assert!(!err.contains(TEMPLATES_ENTRY));
+        assert!(get_conversation_summary(&target, "conv_1")
+            .unwrap()
+            .is_none());
+
+        let imported = read_bundle(&target, &bundle, &attachments).unwrap();
+        assert_eq!(imported.imported_count, 1);
+    }
+
+    #[test]
+    fn safe_component_strips_path_characters() {
+        assert_eq!(safe_component("../etc/passwd"), "_etc_passwd");
+        assert_eq!(safe_component(".."), "attachment");
+        assert_eq!(safe_component("笔记 1.pdf"), "笔记_1.pdf");
+    }
+}
@@ -0,0 +1,426 @@
+//! Opt-in encryption of stored message content
+//!
+//! Message content and metadata are encrypted with AES-256-GCM under a key
+//! kept in the OS keyring. The work happens inside SQLite through the
+//! `conv_encrypt` and `conv_decrypt` functions installed on the connection,
+//! so every query reads and writes plain text whether encryption is on or
+//! not. Titles and attachment references stay readable, and search scans the
+//! decrypted messages instead of the full-text index while encryption is on.
+
+use super::storage::{load_setting, lock_conversations, store_setting};
+use super::types::{ConversationEncryptionStatus, ConversationState};
+use crate::commands::ai_keys::KEYRING_SERVICE;
+use crate::commands::hex::{from_hex, to_hex};
+use crate::error::AppError;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+const ENCRYPTION_SETTINGS_KEY: &str = "encryption";
+
+/// Keyring entry holding the hex-encoded content key
+const KEYRING_KEY_ENTRY: &str = "conversation-encryption-key";
+
+/// Marks encrypted values; the control character keeps it out of typed text
+pub const ENCRYPTED_PREFIX: &str = "\u{1}enc1:";
+
+const NONCE_LEN: usize = 12;
+
+#[derive(Serialize, Deserialize, Default)]
+struct EncryptionSetting {
+    enabled: bool,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Encrypt a value; values that are already encrypted are returned as-is
+pub fn encrypt_value(cipher: &Aes256Gcm, value: &str) -> Result<String, AppError> {
+    if value.starts_with(ENCRYPTED_PREFIX) {
+        return Ok(value.to_string());
+    }
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| AppError::Internal("Failed to encrypt conversation content".to_string()))?;
+    Ok(format!(
+        "{}{}{}",
+        ENCRYPTED_PREFIX,
+        to_hex(&nonce),
+        to_hex(&ciphertext)
+    ))
+}
+
+/// Decrypt a value; plain values are returned as-is
+pub fn decrypt_value(cipher: Option<&Aes256Gcm>, value: &str) -> Result<String, AppError> {
+    let Some(encoded) = value.strip_prefix(ENCRYPTED_PREFIX) else {
+        return Ok(value.to_string());
+    };
+    let cipher = cipher.ok_or_else(|| {
+        AppError::Keyring("Conversation content is encrypted but no key is available".to_string())
+    })?;
+    let bytes = from_hex(encoded)
+        .filter(|bytes| bytes.len() > NONCE_LEN)
+        .ok_or_else(|| {
+            AppError::Internal("Malformed encrypted conversation content".to_string())
+        })?;
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let plain = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            AppError::Keyring(
+                "Conversation content cannot be decrypted with the stored key".to_string(),
+            )
+        })?;
+    String::from_utf8(plain).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Install the `conv_encrypt` and `conv_decrypt` SQL functions
+///
+/// Without a cipher, `conv_encrypt` stores plain text and `conv_decrypt`
+/// fails on encrypted values.
+pub fn install_content_cipher(
+    conn: &Connection,
+    cipher: Option<Aes256Gcm>,
+) -> Result<(), AppError> {
+    install_functions(conn, cipher, false)
+}
+
+/// Install the SQL functions for encryption that is on but has no readable key
+///
+/// Writes fail instead of storing plain text, and reads of encrypted values
+/// report the missing key.
+fn install_missing_key(conn: &Connection) -> Result<(), AppError> {
+    install_functions(conn, None, true)
+}
+
+fn key_unavailable() -> AppError {
+    AppError::Keyring("Conversation encryption is on but its key is unavailable".to_string())
+}
+
+fn install_functions(
+    conn: &Connection,
+    cipher: Option<Aes256Gcm>,
+    key_missing: bool,
+) -> Result<(), AppError> {
+    let encrypt_cipher = cipher.clone();
+    conn.create_scalar_function("conv_encrypt", 1, FunctionFlags::SQLITE_UTF8, move |ctx| {
+        let value: Option<String> = ctx.get(0)?;
+        match (&encrypt_cipher, value) {
+            (Some(cipher), Some(value)) => encrypt_value(cipher, &value)
+                .map(Some)
+                .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e))),
+            (None, Some(_)) if key_missing => Err(rusqlite::Error::UserFunctionError(Box::new(
+                key_unavailable(),
+            ))),
+            (_, value) => Ok(value),
+        }
+    })?;
+    conn.create_scalar_function(
+        "conv_decrypt",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        move |ctx| {
+            let value: Option<String> = ctx.get(0)?;
+            value
+                .map(|value| decrypt_value(cipher.as_ref(), &value))
+                .transpose()
+                .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))
+        },
+    )?;
+    conn.create_scalar_function(
+        "conv_key_available",
+        0,
+        FunctionFlags::SQLITE_UTF8,
+        move |_| Ok(!key_missing),
+    )?;
+    Ok(())
+}
+
+/// Whether new message content is stored encrypted
+pub fn is_encryption_enabled(conn: &Connection) -> Result<bool, AppError> {
+    Ok(load_setting::<EncryptionSetting>(conn, ENCRYPTION_SETTINGS_KEY)?.enabled)
+}
+
+fn key_entry() -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_KEY_ENTRY)
+        .map_err(|e| AppError::Keyring(e.to_string()))
+}
+
+fn cipher_from_hex(key: &str) -> Result<Aes256Gcm, AppError> {
+    from_hex(key.trim())
+        .and_then(|bytes| Aes256Gcm::new_from_slice(&bytes).ok())
+        .ok_or_else(|| AppError::Keyring("The stored conversation key is invalid".to_string()))
+}
+
+/// Read the content key from the keyring, creating one if there is none
+fn load_or_create_key() -> Result<Aes256Gcm, AppError> {
+    let entry = key_entry()?;
+    match entry.get_password() {
+        Ok(key) => cipher_from_hex(&key),
+        Err(keyring::Error::NoEntry) => {
+            let key = Aes256Gcm::generate_key(OsRng);
+            entry
+                .set_password(&to_hex(&key))
+                .map_err(|e| AppError::Keyring(e.to_string()))?;
+            Ok(Aes256Gcm::new(&key))
+        }
+        Err(e) => Err(AppError::Keyring(e.to_string())),
+    }
+}
+
+/// Install the cipher matching the stored setting, used when opening the database
+pub fn restore_content_cipher(conn: &Connection) -> Result<(), AppError> {
+    match is_encryption_enabled(conn) {
+        Ok(true) => {}
+        Ok(false) => return install_content_cipher(conn, None),
+        // e.g. a database left at an old version by a failed migration
+        Err(e) => {
+            log::error!("Conversation encryption state unreadable: {}", e);
+            return install_missing_key(conn);
+        }
+    }
+    let key = key_entry().and_then(|entry| {
+        entry
+            .get_password()
+            .map_err(|e| AppError::Keyring(e.to_string()))
+    });
+    // Keep the app usable; reading encrypted messages and writing new ones
+    // report the error, and the status shows the key as unavailable
+    match key.and_then(|key| cipher_from_hex(&key)) {
+        Ok(cipher) => install_content_cipher(conn, Some(cipher)),
+        Err(e) => {
+            log::error!("Conversation encryption key unavailable: {}", e);
+            install_missing_key(conn)
+        }
+    }
+}
+
+/// Drop freed pages and the WAL so no plain text is left behind on disk
+fn compact(conn: &Connection) -> Result<(), AppError> {
+    conn.execute_batch(
+        "INSERT INTO messages_fts (messages_fts) VALUES ('optimize');
+         VACUUM;",
+    )?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    Ok(())
+}
+
+/// Count of encrypted and all messages, and whether the key can be read
+pub fn encryption_status(conn: &Connection) -> Result<ConversationEncryptionStatus, AppError> {
+    let (encrypted_messages, total_messages, key_available) = conn.query_row(
+        "SELECT COALESCE(SUM(instr(content, ?1) = 1), 0), COUNT(*), conv_key_available()
+         FROM messages",
+        params![ENCRYPTED_PREFIX],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    Ok(ConversationEncryptionStatus {
+        enabled: is_encryption_enabled(conn)?,
+        key_available,
+        encrypted_messages,
+        total_messages,
+    })
+}
+
+/// Turn encryption on and encrypt all stored messages
+pub fn encrypt_existing(conn: &Connection, cipher: Aes256Gcm) -> Result<usize, AppError> {
+    install_content_cipher(conn, Some(cipher))?;
+    let tx = conn.unchecked_transaction()?;
+    let changed = tx.execute(
+        "UPDATE messages SET content = conv_encrypt(content), metadata = conv_encrypt(metadata)
+         WHERE instr(content, ?1) <> 1 OR instr(metadata, ?1) <> 1",
+        params![ENCRYPTED_PREFIX],
+    )?;
+    store_setting(
+        &tx,
+        ENCRYPTION_SETTINGS_KEY,
+        &EncryptionSetting { enabled: true },
+    )?;
+    tx.commit()?;
+    compact(conn)?;
+    Ok(changed)
+}
+
+/// Decrypt all stored messages and turn encryption off
+pub fn decrypt_existing(conn: &Connection) -> Result<usize, AppError> {
+    let tx = conn.unchecked_transaction()?;
+    let changed = tx.execute(
+        "UPDATE messages SET content = conv_decrypt(content), metadata = conv_decrypt(metadata)
+         WHERE instr(content, ?1) = 1 OR instr(metadata, ?1) = 1",
+        params![ENCRYPTED_PREFIX],
+    )?;
+    store_setting(
+        &tx,
+        ENCRYPTION_SETTINGS_KEY,
+        &EncryptionSetting { enabled: false },
+    )?;
+    tx.commit()?;
+    install_content_cipher(conn, None)?;
+    compact(conn)?;
+    Ok(changed)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get whether conversation content is encrypted
+#[tauri::command]
+pub fn get_conversation_encryption_status(
+    state: tauri::State<'_, ConversationState>,
+) -> Result<ConversationEncryptionStatus, AppError> {
+    let conn = lock_conversations(&state)?;
+    encryption_status(&conn)
+}
+
+/// Encrypt stored and future message content with a key kept in the OS keyring
+#[tauri::command]
+pub async fn enable_conversation_encryption(
+    state: tauri::State<'_, ConversationState>,
+) -> Result<ConversationEncryptionStatus, AppError> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let conn = lock_conversations(&state)?;
+        let changed = encrypt_existing(&conn, load_or_create_key()?)?;
+        log::info!(
+            "Conversation encryption enabled, {} messages encrypted",
+            changed
+        );
+        encryption_status(&conn)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Decrypt all message content, stop encrypting and remove the key
+#[tauri::command]
+pub async fn disable_conversation_encryption(
+    state: tauri::State<'_, ConversationState>,
+) -> Result<ConversationEncryptionStatus, AppError> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let conn = lock_conversations(&state)?;
+        let changed = decrypt_existing(&conn)?;
+        if let Err(e) = key_entry().and_then(|entry| {
+            entry
+                .delete_credential()
+                .map_err(|e| AppError::Keyring(e.to_string()))
+        }) {
+            log::warn!("Failed to remove the conversation key: {}", e);
+        }
+        log::info!(
+            "Conversation encryption disabled, {} messages decrypted",
+            changed
+        );
+        encryption_status(&conn)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::messages::{append_message, get_message};
+    use super::super::records::create_conversation;
+    use super::super::search::search_conversations_in;
+    use super::super::storage::open_conversations_in_memory;
+    use super::super::types::{ConversationInput, ConversationSearchFilters, MessageInput};
+    use super::*;
+
+    #[test]
+    fn values_round_trip_and_plain_text_passes_through() {
+        let cipher = Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng));
+        let encrypted = encrypt_value(&cipher, "secret 笔记").unwrap();
+        assert!(encrypted.starts_with(ENCRYPTED_PREFIX));
+        assert!(!encrypted.contains("secret"));
+        assert_eq!(encrypt_value(&cipher, &encrypted).unwrap(), encrypted);
+        assert_eq!(
+            decrypt_value(Some(&cipher), &encrypted).unwrap(),
+            "secret 笔记"
+        );
+        assert_eq!(decrypt_value(None, "plain").unwrap(), "plain");
+        assert!(decrypt_value(None, &encrypted).is_err());
+
+        let other = Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng));
+        assert!(decrypt_value(Some(&other), &encrypted).is_err());
+    }
+
+    #[test]
+    fn enabling_encrypts_stored_messages_transparently() {
+        let conn = open_conversations_in_memory();
+        let conv = create_conversation(&conn, &ConversationInput::default(), 1).unwrap();
+        let message = |content: &str| MessageInput {
+            role: "user".to_string(),
+            content: content.to_string(),
+            metadata: Some(serde_json::json!({"note": "private"})),
+            ..Default::default()
+        };
+        let before = append_message(&conn, &conv.id, &message("confidential report"), 2).unwrap();
+
+        let cipher = Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng));
+        assert_eq!(encrypt_existing(&conn, cipher).unwrap(), 1);
+        let after = append_message(&conn, &conv.id, &message("second report"), 3).unwrap();
+        assert_eq!(after.content, "second report");
+
+        let raw: Vec<String> = conn
+            .prepare("SELECT content || metadata FROM messages")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(raw
+            .iter()
+            .all(|r| !r.contains("report") && !r.contains("private")));
+        assert_eq!(get_message(&conn, &before.id).unwrap().unwrap(), before);
+
+        let status = encryption_status(&conn).unwrap();
+        assert!(status.enabled && status.key_available);
+        assert_eq!((status.encrypted_messages, status.total_messages), (2, 2));
+        let hits = search_conversations_in(&conn, "report", &ConversationSearchFilters::default())
+            .unwrap();
+        assert_eq!(hits.len(), 2);
+
+        assert_eq!(decrypt_existing(&conn).unwrap(), 2);
+        assert!(!is_encryption_enabled(&conn).unwrap());
+        let content: String = conn
+            .query_row(
+                "SELECT content FROM messages WHERE id = ?1",
+                params![before.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(content, "confidential report");
+    }
+
+    #[test]
+    fn missing_key_refuses_to_store_plain_text() {
+        let conn = open_conversations_in_memory();
+        let conv = create_conversation(&conn, &ConversationInput::default(), 1).unwrap();
+        let cipher = Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng));
+        encrypt_existing(&conn, cipher).unwrap();
+        install_missing_key(&conn).unwrap();
+
+        let message = MessageInput {
+            role: "user".to_string(),
+            content: "confidential report".to_string(),
+            ..Default::default()
+        };
+        assert!(append_message(&conn, &conv.id, &message, 2).is_err());
+        let count: u32 = conn
+            .query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+
+        let status = encryption_status(&conn).unwrap();
+        assert!(status.enabled);
+        assert!(!status.key_available);
+    }
+}
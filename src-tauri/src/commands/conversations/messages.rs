@@ -0,0 +1,305 @@
+//! Conversation messages and their attachment references
+
+use super::storage::lock_conversations;
+use super::titles::{needs_automatic_title, spawn_automatic_title};
+use super::types::{ConversationMessage, ConversationState, MessageAttachment, MessageInput};
+use crate::commands::ai_context::count_tokens;
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use uuid::Uuid;
+
+const MESSAGE_ROLES: &[&str] = &["user", "assistant", "system", "tool"];
+
+pub(super) const MESSAGE_COLUMNS: &str = "id, conversation_id, role, conv_decrypt(content), model,
+    token_count, input_tokens, output_tokens, conv_decrypt(metadata), created_at, parent_id";
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub(super) fn row_to_message(row: &Row) -> rusqlite::Result<ConversationMessage> {
+    let metadata: String = row.get(8)?;
+    Ok(ConversationMessage {
+        id: row.get(0)?,
+        conversation_id: row.get(1)?,
+        role: row.get(2)?,
+        content: row.get(3)?,
+        model: row.get(4)?,
+        token_count: row.get(5)?,
+        input_tokens: row.get(6)?,
+        output_tokens: row.get(7)?,
+        metadata: serde_json::from_str(&metadata).unwrap_or(serde_json::Value::Null),
+        attachments: Vec::new(),
+        created_at: row.get(9)?,
+        parent_id: row.get(10)?,
+    })
+}
+
+/// Attachment references of a message, in order
+pub fn list_attachments(
+    conn: &Connection,
+    message_id: &str,
+) -> Result<Vec<MessageAttachment>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT kind, reference, name, mime_type, size FROM message_attachments
+         WHERE message_id = ?1 ORDER BY position",
+    )?;
+    let rows = stmt.query_map(params![message_id], |row| {
+        Ok(MessageAttachment {
+            kind: row.get(0)?,
+            reference: row.get(1)?,
+            name: row.get(2)?,
+            mime_type: row.get(3)?,
+            size: row.get::<_, Option<i64>>(4)?.map(|s| s as u64),
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+/// Get a message by id
+pub fn get_message(conn: &Connection, id: &str) -> Result<Option<ConversationMessage>, AppError> {
+    let sql = format!("SELECT {} FROM messages WHERE id = ?1", MESSAGE_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query_map(params![id], row_to_message)?;
+    match rows.next() {
+        Some(message) => {
+            let mut message = message?;
+            message.attachments = list_attachments(conn, &message.id)?;
+            Ok(Some(message))
+        }
+        None => Ok(None),
+    }
+}
+
+/// All messages of a conversation across branches, in creation order
+pub fn list_messages(
+    conn: &Connection,
+    conversation_id: &str,
+) -> Result<Vec<ConversationMessage>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM messages WHERE conversation_id = ?1 ORDER BY position",
+        MESSAGE_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![conversation_id], row_to_message)?;
+    let mut messages = rows.collect::<Result<Vec<_>, _>>()?;
+    for message in &mut messages {
+        message.attachments = list_attachments(conn, &message.id)?;
+    }
+    Ok(messages)
+}
+
+/// Conversation a message belongs to
+fn message_conversation(conn: &Connection, id: &str) -> Result<Option<String>, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT conversation_id FROM messages WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Append a message to a conversation
+///
+/// The message follows `input.parent_id` when given and the end of the active
+/// branch otherwise, and becomes the end of the active branch.
+pub fn append_message(
+    conn: &Connection,
+    conversation_id: &str,
+    input: &MessageInput,
+    now: i64,
+) -> Result<ConversationMessage, AppError> {
+    let parent_id = match &input.parent_id {
+        Some(parent_id) => {
+            if message_conversation(conn, parent_id)?.as_deref() != Some(conversation_id) {
+                return Err(AppError::InvalidInput(format!(
+                    "Message '{}' is not part of conversation '{}'",
+                    parent_id, conversation_id
+                )));
+            }
+            Some(parent_id.clone())
+        }
+        None => conn
+            .query_row(
+                "SELECT active_leaf_id FROM conversations WHERE id = ?1",
+                params![conversation_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten(),
+    };
+    insert_message(conn, conversation_id, parent_id.as_deref(), input, now)
+}
+
+/// Insert a message under `parent_id` (None for a root message) and make it
+/// the end of the active branch
+pub(super) fn insert_message(
+    conn: &Connection,
+    conversation_id: &str,
+    parent_id: Option<&str>,
+    input: &MessageInput,
+    now: i64,
+) -> Result<ConversationMessage, AppError> {
+    if !MESSAGE_ROLES.contains(&input.role.as_str()) {
+        return Err(AppError::InvalidInput(format!(
+            "Unknown message role '{}'",
+            input.role
+        )));
+    }
+    let id = input
+        .id
+        .clone()
+        .unwrap_or_else(|| format!("msg_{}", Uuid::new_v4()));
+    let token_count = input
+        .token_count
+        .unwrap_or_else(|| count_tokens(&input.content) as u32);
+    let metadata = input
+        .metadata
+        .clone()
+        .unwrap_or_else(|| serde_json::json!({}))
+        .to_string();
+
+    let tx = conn.unchecked_transaction()?;
+    let touched = tx.execute(
+        "UPDATE conversations SET updated_at = ?2 WHERE id = ?1",
+        params![conversation_id, now],
+    )?;
+    if touched == 0 {
+        return Err(AppError::NotFound(format!(
+            "Conversation '{}' not found",
+            conversation_id
+        )));
+    }
+    tx.execute(
+        "INSERT INTO messages (id, conversation_id, parent_id, position, role, content, model,
+             token_count, input_tokens, output_tokens, metadata, created_at)
+         VALUES (?1, ?2, ?11,
+             (SELECT COALESCE(MAX(position), -1) + 1 FROM messages WHERE conversation_id = ?2),
+             ?3, conv_encrypt(?4), ?5, ?6, ?7, ?8, conv_encrypt(?9), ?10)",
+        params![
+            id,
+            conversation_id,
+            input.role,
+            input.content,
+            input.model,
+            token_count,
+            input.input_tokens,
+            input.output_tokens,
+            metadata,
+            input.created_at.unwrap_or(now),
+            parent_id
+        ],
+    )?;
+    tx.execute(
+        "UPDATE conversations SET active_leaf_id = ?2 WHERE id = ?1",
+        params![conversation_id, id],
+    )?;
+    for (position, attachment) in input.attachments.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO message_attachments (message_id, position, kind, reference, name,
+                 mime_type, size)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                id,
+                position as i64,
+                attachment.kind,
+                attachment.reference,
+                attachment.name,
+                attachment.mime_type,
+                attachment.size.map(|s| s as i64)
+            ],
+        )?;
+    }
+    tx.commit()?;
+
+    get_message(conn, &id)?.ok_or_else(|| AppError::NotFound(format!("Message '{}' not found", id)))
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Append a message to a conversation, after `message.parentId` or the end
+/// of the active branch
+///
+/// The first assistant reply starts automatic title generation when enabled.
+#[tauri::command]
+pub fn conversation_append_message(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, ConversationState>,
+    conversation_id: String,
+    message: MessageInput,
+) -> Result<ConversationMessage, AppError> {
+    let conn = lock_conversations(&state)?;
+    let stored = append_message(
+        &conn,
+        &conversation_id,
+        &message,
+        chrono::Utc::now().timestamp(),
+    )?;
+    if stored.role == "assistant" && needs_automatic_title(&conn, &conversation_id)? {
+        spawn_automatic_title(&app, conversation_id);
+    }
+    Ok(stored)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::records::{create_conversation, get_conversation};
+    use super::super::storage::open_conversations_in_memory;
+    use super::super::types::ConversationInput;
+    use super::*;
+
+    fn message(role: &str, content: &str) -> MessageInput {
+        MessageInput {
+            role: role.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn messages_are_appended_in_order_with_attachments_and_tokens() {
+        let conn = open_conversations_in_memory();
+        let conv = create_conversation(&conn, &ConversationInput::default(), 1).unwrap();
+
+        let mut question = message("user", "What is a CFI locator?");
+        question.attachments = vec![MessageAttachment {
+            kind: "document".to_string(),
+            reference: "doc_1".to_string(),
+            name: Some("Spec.epub".to_string()),
+            mime_type: None,
+            size: Some(42),
+        }];
+        let stored = append_message(&conn, &conv.id, &question, 5).unwrap();
+        assert!(stored.id.starts_with("msg_"));
+        assert!(stored.token_count > 0);
+        assert_eq!(stored.attachments, question.attachments);
+
+        let mut answer = message("assistant", "A path into the EPUB.");
+        answer.token_count = Some(7);
+        answer.output_tokens = Some(9);
+        append_message(&conn, &conv.id, &answer, 6).unwrap();
+
+        let loaded = get_conversation(&conn, &conv.id).unwrap().unwrap();
+        let roles: Vec<&str> = loaded.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant"]);
+        assert_eq!(loaded.summary.message_count, 2);
+        assert_eq!(loaded.summary.total_tokens, stored.token_count as u64 + 7);
+        assert_eq!(loaded.summary.updated_at, 6);
+
+        assert!(matches!(
+            append_message(&conn, &conv.id, &message("robot", "hi"), 7),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            append_message(&conn, "missing", &message("user", "hi"), 7),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,45 @@
+//! Chat conversation history
+//!
+//! Conversations are stored in their own SQLite database in the app data
+//! directory. It provides:
+//! - Conversation records with an optional link to a library document
+//! - Ordered messages with per-message token counts
+//! - Message branches from edits and regenerations, with a tree query
+//! - Attachment references (files, images, URLs, library documents), with
+//!   attached files copied into content-addressed storage
+//! - Opt-in encryption of message content with a key in the OS keyring
+//! - Archiving and a retention policy applied by a background task
+//! - Titles generated from the first exchanges, optionally automatically
+//! - Full-text search over titles and message content
+//! - Prompt templates with `{{variable}}` placeholders, import and export
+//! - System prompt presets, global or per document or collection
+//! - Portable bundles with conversations, attached files and templates
+
+mod types;
+mod storage;
+mod records;
+mod messages;
+mod attachments;
+mod branches;
+mod encryption;
+mod retention;
+mod titles;
+mod search;
+mod templates;
+mod presets;
+mod bundle;
+
+// Re-export all public items
+pub use types::*;
+pub use storage::*;
+pub use records::*;
+pub use messages::*;
+pub use attachments::*;
+pub use branches::*;
+pub use encryption::*;
+pub use retention::*;
+pub use titles::*;
+pub use search::*;
+pub use templates::*;
+pub use presets::*;
+pub use bundle::*;
@@ -0,0 +1,357 @@
+//! System prompt presets
+//!
+//! Presets are named system prompts that apply everywhere or only to one
+//! document or collection. A scope can have a default preset, used when an
+//! AI request names no system prompt.
+
+use super::storage::lock_conversations;
+use super::types::{
+    ConversationState, SystemPromptPreset, SystemPromptPresetInput, SystemPromptPresetUpdate,
+};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use uuid::Uuid;
+
+const PRESET_COLUMNS: &str =
+    "id, name, content, scope, scope_id, is_default, created_at, updated_at";
+
+const PRESET_SCOPES: &[&str] = &["global", "document", "collection"];
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn row_to_preset(row: &Row) -> rusqlite::Result<SystemPromptPreset> {
+    Ok(SystemPromptPreset {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        content: row.get(2)?,
+        scope: row.get(3)?,
+        scope_id: row.get(4)?,
+        is_default: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+fn not_found(id: &str) -> AppError {
+    AppError::NotFound(format!("System prompt preset '{}' not found", id))
+}
+
+/// Validate a scope and its id, defaulting to the global scope
+fn validate_scope<'a>(
+    scope: Option<&'a str>,
+    scope_id: Option<&'a str>,
+) -> Result<(&'a str, Option<&'a str>), AppError> {
+    let scope = scope.unwrap_or("global");
+    if !PRESET_SCOPES.contains(&scope) {
+        return Err(AppError::InvalidInput(format!(
+            "Unknown system prompt scope: {}",
+            scope
+        )));
+    }
+    let scope_id = scope_id.map(str::trim).filter(|id| !id.is_empty());
+    match (scope, scope_id) {
+        ("global", _) => Ok((scope, None)),
+        (_, Some(id)) => Ok((scope, Some(id))),
+        (_, None) => Err(AppError::InvalidInput(format!(
+            "A {} system prompt needs a scope id",
+            scope
+        ))),
+    }
+}
+
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput(
+            "System prompt name is required".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+/// Clear the default flag of the other presets in a preset's scope
+fn clear_other_defaults(conn: &Connection, id: &str) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE system_prompts SET is_default = 0
+         WHERE id != ?1 AND is_default = 1
+           AND (scope, IFNULL(scope_id, '')) =
+               (SELECT scope, IFNULL(scope_id, '') FROM system_prompts WHERE id = ?1)",
+        params![id],
+    )?;
+    Ok(())
+}
+
+/// Get a system prompt preset by id
+pub fn get_preset(conn: &Connection, id: &str) -> Result<Option<SystemPromptPreset>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM system_prompts WHERE id = ?1",
+        PRESET_COLUMNS
+    );
+    Ok(conn
+        .query_row(&sql, params![id], row_to_preset)
+        .optional()?)
+}
+
+/// List presets by name, optionally only those of a scope (and scope id)
+pub fn list_presets(
+    conn: &Connection,
+    scope: Option<&str>,
+    scope_id: Option<&str>,
+) -> Result<Vec<SystemPromptPreset>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM system_prompts
+         WHERE (?1 IS NULL OR scope = ?1) AND (?2 IS NULL OR scope_id = ?2)
+         ORDER BY name COLLATE NOCASE, id",
+        PRESET_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![scope, scope_id], row_to_preset)?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+/// Create a system prompt preset
+pub fn create_preset(
+    conn: &Connection,
+    input: &SystemPromptPresetInput,
+    now: i64,
+) -> Result<SystemPromptPreset, AppError> {
+    let name = validate_name(&input.name)?;
+    let (scope, scope_id) = validate_scope(input.scope.as_deref(), input.scope_id.as_deref())?;
+    let id = format!("sp_{}", Uuid::new_v4());
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO system_prompts
+             (id, name, content, scope, scope_id, is_default, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+        params![
+            id,
+            name,
+            input.content,
+            scope,
+            scope_id,
+            input.is_default,
+            now
+        ],
+    )?;
+    if input.is_default {
+        clear_other_defaults(&tx, &id)?;
+    }
+    tx.commit()?;
+    get_preset(conn, &id)?.ok_or_else(|| not_found(&id))
+}
+
+/// Update a system prompt preset
+pub fn update_preset(
+    conn: &Connection,
+    id: &str,
+    update: &SystemPromptPresetUpdate,
+    now: i64,
+) -> Result<SystemPromptPreset, AppError> {
+    let name = update.name.as_deref().map(validate_name).transpose()?;
+    let tx = conn.unchecked_transaction()?;
+    let changed = tx.execute(
+        "UPDATE system_prompts SET
+            name = COALESCE(?2, name),
+            content = COALESCE(?3, content),
+            is_default = COALESCE(?4, is_default),
+            updated_at = ?5
+         WHERE id = ?1",
+        params![id, name, update.content, update.is_default, now],
+    )?;
+    if changed == 0 {
+        return Err(not_found(id));
+    }
+    if update.is_default == Some(true) {
+        clear_other_defaults(&tx, id)?;
+    }
+    tx.commit()?;
+    get_preset(conn, id)?.ok_or_else(|| not_found(id))
+}
+
+/// Delete a system prompt preset
+pub fn delete_preset(conn: &Connection, id: &str) -> Result<(), AppError> {
+    let changed = conn.execute("DELETE FROM system_prompts WHERE id = ?1", params![id])?;
+    if changed == 0 {
+        return Err(not_found(id));
+    }
+    Ok(())
+}
+
+fn default_preset(
+    conn: &Connection,
+    scope: &str,
+    scope_id: Option<&str>,
+) -> Result<Option<String>, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT content FROM system_prompts
+             WHERE is_default = 1 AND scope = ?1 AND IFNULL(scope_id, '') = IFNULL(?2, '')
+             LIMIT 1",
+            params![scope, scope_id],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Resolve the system prompt text for a request
+///
+/// A named preset wins; otherwise the default of the document, then of the
+/// collection, then the global default is used.
+pub fn resolve_system_prompt(
+    conn: &Connection,
+    preset_id: Option<&str>,
+    document_id: Option<&str>,
+    collection_id: Option<&str>,
+) -> Result<Option<String>, AppError> {
+    if let Some(id) = preset_id {
+        return get_preset(conn, id)?
+            .map(|preset| Some(preset.content))
+            .ok_or_else(|| not_found(id));
+    }
+    if let Some(document_id) = document_id {
+        if let Some(content) = default_preset(conn, "document", Some(document_id))? {
+            return Ok(Some(content));
+        }
+    }
+    if let Some(collection_id) = collection_id {
+        if let Some(content) = default_preset(conn, "collection", Some(collection_id))? {
+            return Ok(Some(content));
+        }
+    }
+    default_preset(conn, "global", None)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// List system prompt presets, optionally only those of a scope
+#[tauri::command]
+pub fn system_prompt_list(
+    state: tauri::State<'_, ConversationState>,
+    scope: Option<String>,
+    scope_id: Option<String>,
+) -> Result<Vec<SystemPromptPreset>, AppError> {
+    let conn = lock_conversations(&state)?;
+    list_presets(&conn, scope.as_deref(), scope_id.as_deref())
+}
+
+/// Create a system prompt preset
+#[tauri::command]
+pub fn system_prompt_create(
+    state: tauri::State<'_, ConversationState>,
+    preset: SystemPromptPresetInput,
+) -> Result<SystemPromptPreset, AppError> {
+    let conn = lock_conversations(&state)?;
+    create_preset(&conn, &preset, chrono::Utc::now().timestamp())
+}
+
+/// Update a system prompt preset
+#[tauri::command]
+pub fn system_prompt_update(
+    state: tauri::State<'_, ConversationState>,
+    id: String,
+    update: SystemPromptPresetUpdate,
+) -> Result<SystemPromptPreset, AppError> {
+    let conn = lock_conversations(&state)?;
+    update_preset(&conn, &id, &update, chrono::Utc::now().timestamp())
+}
+
+/// Delete a system prompt preset
+#[tauri::command]
+pub fn system_prompt_delete(
+    state: tauri::State<'_, ConversationState>,
+    id: String,
+) -> Result<(), AppError> {
+    let conn = lock_conversations(&state)?;
+    delete_preset(&conn, &id)
+}
+
+/// Get the system prompt text a request with these options would use
+#[tauri::command]
+pub fn system_prompt_resolve(
+    state: tauri::State<'_, ConversationState>,
+    preset_id: Option<String>,
+    document_id: Option<String>,
+    collection_id: Option<String>,
+) -> Result<Option<String>, AppError> {
+    let conn = lock_conversations(&state)?;
+    resolve_system_prompt(
+        &conn,
+        preset_id.as_deref(),
+        document_id.as_deref(),
+        collection_id.as_deref(),
+    )
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::storage::open_conversations_in_memory;
+    use super::*;
+
+    fn preset(name: &str, scope: &str, scope_id: Option<&str>) -> SystemPromptPresetInput {
+        SystemPromptPresetInput {
+            name: name.to_string(),
+            content: format!("{} prompt", name),
+            scope: Some(scope.to_string()),
+            scope_id: scope_id.map(str::to_string),
+            is_default: true,
+        }
+    }
+
+    #[test]
+    fn presets_resolve_from_the_narrowest_default() {
+        let conn = open_conversations_in_memory();
+        let global = create_preset(&conn, &preset("Global", "global", Some("x")), 1).unwrap();
+        assert_eq!(global.scope_id, None);
+        create_preset(&conn, &preset("Shelf", "collection", Some("c1")), 1).unwrap();
+        let doc = create_preset(&conn, &preset("Doc", "document", Some("d1")), 1).unwrap();
+
+        let resolve = |doc: Option<&str>, col: Option<&str>| {
+            resolve_system_prompt(&conn, None, doc, col).unwrap()
+        };
+        assert_eq!(
+            resolve(Some("d1"), Some("c1")).as_deref(),
+            Some("Doc prompt")
+        );
+        assert_eq!(
+            resolve(Some("d2"), Some("c1")).as_deref(),
+            Some("Shelf prompt")
+        );
+        assert_eq!(resolve(None, None).as_deref(), Some("Global prompt"));
+        assert_eq!(
+            resolve_system_prompt(&conn, Some(&doc.id), None, None)
+                .unwrap()
+                .as_deref(),
+            Some("Doc prompt")
+        );
+        assert!(matches!(
+            resolve_system_prompt(&conn, Some("missing"), None, None),
+            Err(AppError::NotFound(_))
+        ));
+
+        // A new default replaces the previous one in the same scope only
+        let other = create_preset(&conn, &preset("Other", "global", None), 2).unwrap();
+        assert!(!get_preset(&conn, &global.id).unwrap().unwrap().is_default);
+        assert!(get_preset(&conn, &doc.id).unwrap().unwrap().is_default);
+        let update = SystemPromptPresetUpdate {
+            is_default: Some(false),
+            ..Default::default()
+        };
+        update_preset(&conn, &other.id, &update, 3).unwrap();
+        assert_eq!(resolve(None, None), None);
+
+        assert_eq!(list_presets(&conn, Some("global"), None).unwrap().len(), 2);
+        assert_eq!(list_presets(&conn, None, Some("d1")).unwrap().len(), 1);
+        assert!(create_preset(&conn, &preset("Doc", "document", None), 4).is_err());
+        assert!(create_preset(&conn, &preset("Doc", "folder", Some("f")), 4).is_err());
+        delete_preset(&conn, &doc.id).unwrap();
+        assert!(delete_preset(&conn, &doc.id).is_err());
+    }
+}
@@ -0,0 +1,308 @@
+//! Conversation CRUD
+
+use super::attachments::collect_orphans_quietly;
+use super::branches::list_active_messages;
+use super::storage::lock_conversations;
+use super::types::{
+    Conversation, ConversationInput, ConversationListQuery, ConversationState, ConversationSummary,
+    ConversationUpdate,
+};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use uuid::Uuid;
+
+/// Title of conversations created without one
+pub const DEFAULT_CONVERSATION_TITLE: &str = "New conversation";
+
+pub(super) const SUMMARY_COLUMNS: &str =
+    "c.id, c.title, c.document_id, c.document_path, c.document_name,
+    c.metadata,
+    (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id),
+    (SELECT COALESCE(SUM(m.token_count), 0) FROM messages m WHERE m.conversation_id = c.id),
+    c.created_at, c.updated_at, c.archived_at";
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub(super) fn row_to_summary(row: &Row) -> rusqlite::Result<ConversationSummary> {
+    let metadata: String = row.get(5)?;
+    Ok(ConversationSummary {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        document_id: row.get(2)?,
+        document_path: row.get(3)?,
+        document_name: row.get(4)?,
+        metadata: serde_json::from_str(&metadata).unwrap_or(serde_json::Value::Null),
+        message_count: row.get(6)?,
+        total_tokens: row.get::<_, i64>(7)? as u64,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        archived_at: row.get(10)?,
+    })
+}
+
+fn not_found(id: &str) -> AppError {
+    AppError::NotFound(format!("Conversation '{}' not found", id))
+}
+
+/// Get a conversation without its messages
+pub fn get_conversation_summary(
+    conn: &Connection,
+    id: &str,
+) -> Result<Option<ConversationSummary>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM conversations c WHERE c.id = ?1",
+        SUMMARY_COLUMNS
+    );
+    Ok(conn
+        .query_row(&sql, params![id], row_to_summary)
+        .optional()?)
+}
+
+/// Get a conversation with the messages of its active branch
+pub fn get_conversation(conn: &Connection, id: &str) -> Result<Option<Conversation>, AppError> {
+    let Some(summary) = get_conversation_summary(conn, id)? else {
+        return Ok(None);
+    };
+    let messages = list_active_messages(conn, id)?;
+    Ok(Some(Conversation { summary, messages }))
+}
+
+/// Create a conversation
+pub fn create_conversation(
+    conn: &Connection,
+    input: &ConversationInput,
+    now: i64,
+) -> Result<ConversationSummary, AppError> {
+    let id = input
+        .id
+        .clone()
+        .unwrap_or_else(|| format!("conv_{}", Uuid::new_v4()));
+    let title = input
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .unwrap_or(DEFAULT_CONVERSATION_TITLE);
+    let metadata = input
+        .metadata
+        .clone()
+        .unwrap_or_else(|| serde_json::json!({}))
+        .to_string();
+
+    let created_at = input.created_at.unwrap_or(now);
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO conversations (id, title, document_id, document_path,
+             document_name, metadata, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+        params![
+            id,
+            title,
+            input.document_id,
+            input.document_path,
+            input.document_name,
+            metadata,
+            created_at
+        ],
+    )?;
+    if inserted == 0 {
+        return Err(AppError::InvalidInput(format!(
+            "Conversation '{}' already exists",
+            id
+        )));
+    }
+    get_conversation_summary(conn, &id)?.ok_or_else(|| not_found(&id))
+}
+
+/// List active (or archived) conversations, most recently updated first
+pub fn list_conversations(
+    conn: &Connection,
+    query: &ConversationListQuery,
+) -> Result<Vec<ConversationSummary>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM conversations c
+         WHERE (?1 IS NULL OR c.document_id = ?1)
+           AND (c.archived_at IS NOT NULL) = ?4
+         ORDER BY c.updated_at DESC, c.id
+         LIMIT ?2 OFFSET ?3",
+        SUMMARY_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(
+        params![
+            query.document_id,
+            query.limit.map(i64::from).unwrap_or(-1),
+            query.offset.unwrap_or(0),
+            query.archived
+        ],
+        row_to_summary,
+    )?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+/// Update a conversation's title, document link or metadata
+pub fn update_conversation(
+    conn: &Connection,
+    id: &str,
+    update: &ConversationUpdate,
+    now: i64,
+) -> Result<ConversationSummary, AppError> {
+    let changed = conn.execute(
+        "UPDATE conversations SET
+            title = COALESCE(?2, title),
+            document_id = COALESCE(?3, document_id),
+            document_path = COALESCE(?4, document_path),
+            document_name = COALESCE(?5, document_name),
+            metadata = COALESCE(?6, metadata),
+            updated_at = ?7
+         WHERE id = ?1",
+        params![
+            id,
+            update
+                .title
+                .as_deref()
+                .map(str::trim)
+                .filter(|t| !t.is_empty()),
+            update.document_id,
+            update.document_path,
+            update.document_name,
+            update.metadata.as_ref().map(|m| m.to_string()),
+            now
+        ],
+    )?;
+    if changed == 0 {
+        return Err(not_found(id));
+    }
+    get_conversation_summary(conn, id)?.ok_or_else(|| not_found(id))
+}
+
+/// Delete a conversation and its messages
+pub fn delete_conversation(conn: &Connection, id: &str) -> Result<(), AppError> {
+    let changed = conn.execute("DELETE FROM conversations WHERE id = ?1", params![id])?;
+    if changed == 0 {
+        return Err(not_found(id));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Create a conversation
+#[tauri::command]
+pub fn conversation_create(
+    state: tauri::State<'_, ConversationState>,
+    conversation: Option<ConversationInput>,
+) -> Result<ConversationSummary, AppError> {
+    let conn = lock_conversations(&state)?;
+    create_conversation(
+        &conn,
+        &conversation.unwrap_or_default(),
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+/// List active (or archived) conversations, most recently updated first
+#[tauri::command]
+pub fn conversation_list(
+    state: tauri::State<'_, ConversationState>,
+    query: Option<ConversationListQuery>,
+) -> Result<Vec<ConversationSummary>, AppError> {
+    let conn = lock_conversations(&state)?;
+    list_conversations(&conn, &query.unwrap_or_default())
+}
+
+/// Get a conversation with its messages
+#[tauri::command]
+pub fn conversation_get(
+    state: tauri::State<'_, ConversationState>,
+    id: String,
+) -> Result<Option<Conversation>, AppError> {
+    let conn = lock_conversations(&state)?;
+    get_conversation(&conn, &id)
+}
+
+/// Update a conversation's title, document link or metadata
+#[tauri::command]
+pub fn conversation_update(
+    state: tauri::State<'_, ConversationState>,
+    id: String,
+    update: ConversationUpdate,
+) -> Result<ConversationSummary, AppError> {
+    let conn = lock_conversations(&state)?;
+    update_conversation(&conn, &id, &update, chrono::Utc::now().timestamp())
+}
+
+/// Delete a conversation and its messages
+#[tauri::command]
+pub fn conversation_delete(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, ConversationState>,
+    id: String,
+) -> Result<(), AppError> {
+    let conn = lock_conversations(&state)?;
+    delete_conversation(&conn, &id)?;
+    log::info!("Conversation deleted: {}", id);
+    collect_orphans_quietly(&app, &conn);
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::storage::open_conversations_in_memory;
+    use super::*;
+
+    #[test]
+    fn conversations_are_created_listed_updated_and_deleted() {
+        let conn = open_conversations_in_memory();
+        let a = create_conversation(&conn, &ConversationInput::default(), 1).unwrap();
+        assert!(a.id.starts_with("conv_"));
+        assert_eq!(a.title, DEFAULT_CONVERSATION_TITLE);
+        assert_eq!(a.metadata, serde_json::json!({}));
+
+        let input = ConversationInput {
+            id: Some("chat-1".to_string()),
+            title: Some("About CFI".to_string()),
+            document_id: Some("doc_1".to_string()),
+            ..Default::default()
+        };
+        let b = create_conversation(&conn, &input, 2).unwrap();
+        assert_eq!(b.id, "chat-1");
+        assert!(matches!(
+            create_conversation(&conn, &input, 3),
+            Err(AppError::InvalidInput(_))
+        ));
+
+        let ids: Vec<String> = list_conversations(&conn, &ConversationListQuery::default())
+            .unwrap()
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec!["chat-1".to_string(), a.id.clone()]);
+        let query = ConversationListQuery {
+            document_id: Some("doc_1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(list_conversations(&conn, &query).unwrap().len(), 1);
+
+        let update = ConversationUpdate {
+            title: Some("  Renamed ".to_string()),
+            ..Default::default()
+        };
+        let updated = update_conversation(&conn, &a.id, &update, 5).unwrap();
+        assert_eq!((updated.title.as_str(), updated.updated_at), ("Renamed", 5));
+
+        delete_conversation(&conn, &a.id).unwrap();
+        assert!(get_conversation(&conn, &a.id).unwrap().is_none());
+        assert!(matches!(
+            delete_conversation(&conn, &a.id),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
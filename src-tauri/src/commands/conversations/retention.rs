@@ -0,0 +1,368 @@
+//! Conversation archiving and the retention policy maintenance task
+
+use super::attachments::collect_orphans_quietly;
+use super::records::{get_conversation_summary, row_to_summary, SUMMARY_COLUMNS};
+use super::storage::{load_setting, lock_conversations, store_setting};
+use super::types::{ConversationState, ConversationSummary, RetentionPolicy, RetentionReport};
+use crate::commands::events::emit_event;
+use crate::commands::power::is_suspended;
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use std::time::Duration;
+use tauri::Manager;
+
+/// Event emitted when the maintenance task archived or deleted conversations
+pub const CONVERSATIONS_PRUNED_EVENT: &str = "conversations-pruned";
+
+const RETENTION_SETTINGS_KEY: &str = "retention";
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// How often the maintenance task applies the retention policy
+const MAINTENANCE_TICK: Duration = Duration::from_secs(60 * 60);
+/// Delay before the first maintenance run, keeps startup quiet
+const MAINTENANCE_STARTUP_DELAY: Duration = Duration::from_secs(60);
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn not_found(id: &str) -> AppError {
+    AppError::NotFound(format!("Conversation '{}' not found", id))
+}
+
+/// Load the retention policy
+pub fn get_retention_policy(conn: &Connection) -> Result<RetentionPolicy, AppError> {
+    load_setting(conn, RETENTION_SETTINGS_KEY)
+}
+
+/// Store the retention policy
+pub fn set_retention_policy(conn: &Connection, policy: &RetentionPolicy) -> Result<(), AppError> {
+    if policy.archive_after_days == Some(0) || policy.purge_archived_after_days == Some(0) {
+        return Err(AppError::InvalidInput(
+            "Retention periods must be at least one day".to_string(),
+        ));
+    }
+    store_setting(conn, RETENTION_SETTINGS_KEY, policy)
+}
+
+/// Archive a conversation; archiving keeps its last update time
+pub fn archive_conversation(
+    conn: &Connection,
+    id: &str,
+    now: i64,
+) -> Result<ConversationSummary, AppError> {
+    let changed = conn.execute(
+        "UPDATE conversations SET archived_at = COALESCE(archived_at, ?2) WHERE id = ?1",
+        params![id, now],
+    )?;
+    if changed == 0 {
+        return Err(not_found(id));
+    }
+    get_conversation_summary(conn, id)?.ok_or_else(|| not_found(id))
+}
+
+/// Restore an archived conversation
+///
+/// Its update time is reset so the idle rule does not archive it again
+/// right away.
+pub fn unarchive_conversation(
+    conn: &Connection,
+    id: &str,
+    now: i64,
+) -> Result<ConversationSummary, AppError> {
+    let changed = conn.execute(
+        "UPDATE conversations SET archived_at = NULL,
+             updated_at = CASE WHEN archived_at IS NULL THEN updated_at ELSE ?2 END
+         WHERE id = ?1",
+        params![id, now],
+    )?;
+    if changed == 0 {
+        return Err(not_found(id));
+    }
+    get_conversation_summary(conn, id)?.ok_or_else(|| not_found(id))
+}
+
+fn summaries_where(
+    conn: &Connection,
+    condition: &str,
+    cutoff: i64,
+) -> Result<Vec<ConversationSummary>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM conversations c WHERE {} ORDER BY c.updated_at, c.id",
+        SUMMARY_COLUMNS, condition
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![cutoff], row_to_summary)?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+/// Apply a retention policy, or only report what it would do
+///
+/// Conversations are purged before idle ones are archived, so nothing is
+/// archived and deleted in the same run.
+pub fn apply_retention(
+    conn: &Connection,
+    policy: &RetentionPolicy,
+    now: i64,
+    dry_run: bool,
+) -> Result<RetentionReport, AppError> {
+    let purged = match policy.purge_archived_after_days {
+        Some(days) => summaries_where(
+            conn,
+            "c.archived_at IS NOT NULL AND c.archived_at <= ?1",
+            now - i64::from(days) * SECONDS_PER_DAY,
+        )?,
+        None => Vec::new(),
+    };
+    let archived = match policy.archive_after_days {
+        Some(days) => summaries_where(
+            conn,
+            "c.archived_at IS NULL AND c.updated_at <= ?1",
+            now - i64::from(days) * SECONDS_PER_DAY,
+        )?,
+        None => Vec::new(),
+    };
+
+    if !dry_run {
+        let tx = conn.unchecked_transaction()?;
+        for summary in &purged {
+            tx.execute(
+                "DELETE FROM conversations WHERE id = ?1",
+                params![summary.id],
+            )?;
+        }
+        for summary in &archived {
+            tx.execute(
+                "UPDATE conversations SET archived_at = ?2 WHERE id = ?1",
+                params![summary.id, now],
+            )?;
+        }
+        tx.commit()?;
+    }
+
+    Ok(RetentionReport {
+        dry_run,
+        archived,
+        purged,
+        ran_at: now,
+    })
+}
+
+fn run_maintenance(
+    app: &tauri::AppHandle,
+    state: &ConversationState,
+) -> Result<Option<RetentionReport>, AppError> {
+    let conn = lock_conversations(state)?;
+    let policy = get_retention_policy(&conn)?;
+    if policy.archive_after_days.is_none() && policy.purge_archived_after_days.is_none() {
+        return Ok(None);
+    }
+    let report = apply_retention(&conn, &policy, chrono::Utc::now().timestamp(), false)?;
+    if !report.purged.is_empty() {
+        collect_orphans_quietly(app, &conn);
+    }
+    Ok(Some(report))
+}
+
+/// Start the background task that applies the retention policy
+pub fn start_conversation_maintenance(app: tauri::AppHandle) {
+    crate::commands::crash::spawn_guarded("conversation maintenance", async move {
+        tokio::time::sleep(MAINTENANCE_STARTUP_DELAY).await;
+        let mut interval = tokio::time::interval(MAINTENANCE_TICK);
+        loop {
+            interval.tick().await;
+            if is_suspended(&app) {
+                continue;
+            }
+            let state = app.state::<ConversationState>().inner().clone();
+            let report = match run_maintenance(&app, &state) {
+                Ok(Some(report)) => report,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::warn!("Conversation maintenance failed: {}", e);
+                    continue;
+                }
+            };
+            if report.archived.is_empty() && report.purged.is_empty() {
+                continue;
+            }
+            log::info!(
+                "Conversation maintenance archived {} and deleted {} conversations",
+                report.archived.len(),
+                report.purged.len()
+            );
+            emit_event(&app, &report);
+        }
+    });
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Archive a conversation
+#[tauri::command]
+pub fn conversation_archive(
+    state: tauri::State<'_, ConversationState>,
+    id: String,
+) -> Result<ConversationSummary, AppError> {
+    let conn = lock_conversations(&state)?;
+    archive_conversation(&conn, &id, chrono::Utc::now().timestamp())
+}
+
+/// Restore an archived conversation
+#[tauri::command]
+pub fn conversation_unarchive(
+    state: tauri::State<'_, ConversationState>,
+    id: String,
+) -> Result<ConversationSummary, AppError> {
+    let conn = lock_conversations(&state)?;
+    unarchive_conversation(&conn, &id, chrono::Utc::now().timestamp())
+}
+
+/// Get the conversation retention policy
+#[tauri::command]
+pub fn get_conversation_retention(
+    state: tauri::State<'_, ConversationState>,
+) -> Result<RetentionPolicy, AppError> {
+    let conn = lock_conversations(&state)?;
+    get_retention_policy(&conn)
+}
+
+/// Update the conversation retention policy
+#[tauri::command]
+pub fn set_conversation_retention(
+    state: tauri::State<'_, ConversationState>,
+    policy: RetentionPolicy,
+) -> Result<(), AppError> {
+    let conn = lock_conversations(&state)?;
+    set_retention_policy(&conn, &policy)
+}
+
+/// Report what the retention policy (or the given one) would archive and
+/// delete, without changing anything
+#[tauri::command]
+pub fn preview_conversation_retention(
+    state: tauri::State<'_, ConversationState>,
+    policy: Option<RetentionPolicy>,
+) -> Result<RetentionReport, AppError> {
+    let conn = lock_conversations(&state)?;
+    let policy = match policy {
+        Some(policy) => policy,
+        None => get_retention_policy(&conn)?,
+    };
+    apply_retention(&conn, &policy, chrono::Utc::now().timestamp(), true)
+}
+
+/// Apply the retention policy now
+#[tauri::command]
+pub fn run_conversation_retention(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, ConversationState>,
+) -> Result<RetentionReport, AppError> {
+    let conn = lock_conversations(&state)?;
+    let policy = get_retention_policy(&conn)?;
+    let report = apply_retention(&conn, &policy, chrono::Utc::now().timestamp(), false)?;
+    if !report.purged.is_empty() {
+        collect_orphans_quietly(&app, &conn);
+    }
+    log::info!(
+        "Retention archived {} and deleted {} conversations",
+        report.archived.len(),
+        report.purged.len()
+    );
+    Ok(report)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::records::{create_conversation, list_conversations};
+    use super::super::storage::open_conversations_in_memory;
+    use super::super::types::{ConversationInput, ConversationListQuery};
+    use super::*;
+
+    const DAY: i64 = SECONDS_PER_DAY;
+
+    fn ids(summaries: &[ConversationSummary]) -> Vec<&str> {
+        summaries.iter().map(|s| s.id.as_str()).collect()
+    }
+
+    fn create(conn: &Connection, id: &str, at: i64) {
+        let input = ConversationInput {
+            id: Some(id.to_string()),
+            created_at: Some(at),
+            ..Default::default()
+        };
+        create_conversation(conn, &input, at).unwrap();
+    }
+
+    #[test]
+    fn archived_conversations_are_listed_separately() {
+        let conn = open_conversations_in_memory();
+        create(&conn, "a", 1);
+        create(&conn, "b", 2);
+
+        let archived = archive_conversation(&conn, "a", 10).unwrap();
+        assert_eq!((archived.archived_at, archived.updated_at), (Some(10), 1));
+        let active = list_conversations(&conn, &ConversationListQuery::default()).unwrap();
+        assert_eq!(ids(&active), vec!["b"]);
+        let query = ConversationListQuery {
+            archived: true,
+            ..Default::default()
+        };
+        assert_eq!(ids(&list_conversations(&conn, &query).unwrap()), vec!["a"]);
+
+        let restored = unarchive_conversation(&conn, "a", 20).unwrap();
+        assert_eq!((restored.archived_at, restored.updated_at), (None, 20));
+        assert!(matches!(
+            archive_conversation(&conn, "missing", 1),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn retention_archives_idle_and_purges_old_archives() {
+        let conn = open_conversations_in_memory();
+        let now = 100 * DAY;
+        create(&conn, "idle", now - 40 * DAY);
+        create(&conn, "recent", now - DAY);
+        create(&conn, "old-archive", now - 200 * DAY);
+        archive_conversation(&conn, "old-archive", now - 95 * DAY).unwrap();
+
+        let policy = RetentionPolicy {
+            archive_after_days: Some(30),
+            purge_archived_after_days: Some(90),
+        };
+        let preview = apply_retention(&conn, &policy, now, true).unwrap();
+        assert_eq!(ids(&preview.archived), vec!["idle"]);
+        assert_eq!(ids(&preview.purged), vec!["old-archive"]);
+        assert!(get_conversation_summary(&conn, "old-archive")
+            .unwrap()
+            .is_some());
+
+        let report = apply_retention(&conn, &policy, now, false).unwrap();
+        assert_eq!(report.archived, preview.archived);
+        assert!(get_conversation_summary(&conn, "old-archive")
+            .unwrap()
+            .is_none());
+        let idle = get_conversation_summary(&conn, "idle").unwrap().unwrap();
+        assert_eq!(idle.archived_at, Some(now));
+
+        assert_eq!(
+            get_retention_policy(&conn).unwrap(),
+            RetentionPolicy::default()
+        );
+        set_retention_policy(&conn, &policy).unwrap();
+        assert_eq!(get_retention_policy(&conn).unwrap(), policy);
+        let invalid = RetentionPolicy {
+            archive_after_days: Some(0),
+            ..Default::default()
+        };
+        assert!(set_retention_policy(&conn, &invalid).is_err());
+    }
+}
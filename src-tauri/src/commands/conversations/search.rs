@@ -0,0 +1,413 @@
+//! Full-text search over conversation titles and messages
+//!
+//! Uses the FTS5 trigram indexes, which match substrings in any script. Terms
+//! shorter than three characters cannot use the index, so queries containing
+//! them fall back to a substring scan.
+
+use super::encryption::is_encryption_enabled;
+use super::storage::lock_conversations;
+use super::types::{ConversationSearchFilters, ConversationSearchHit, ConversationState};
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+
+const DEFAULT_SEARCH_LIMIT: u32 = 50;
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+/// Title matches rank as if they matched this many times more strongly
+const TITLE_BOOST: f64 = 2.0;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Split a query into search terms, keeping quoted phrases together
+pub fn search_terms(query: &str) -> Vec<String> {
+    query
+        .split('"')
+        .enumerate()
+        .flat_map(|(i, part)| {
+            if i % 2 == 1 {
+                vec![part.trim().to_string()]
+            } else {
+                part.split_whitespace().map(str::to_string).collect()
+            }
+        })
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
+/// FTS5 query matching all terms as literal strings
+fn fts_query(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Text around the first match, with every match wrapped in `<mark>` tags
+pub fn make_snippet(text: &str, terms: &[String]) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let needles: Vec<Vec<char>> = terms
+        .iter()
+        .map(|t| {
+            t.chars()
+                .map(|c| c.to_lowercase().next().unwrap_or(c))
+                .collect()
+        })
+        .filter(|n: &Vec<char>| !n.is_empty())
+        .collect();
+    let match_at = |i: usize| {
+        needles
+            .iter()
+            .filter(|n| lower[i..].starts_with(n))
+            .map(|n| n.len())
+            .max()
+    };
+
+    let first = (0..chars.len())
+        .find(|&i| match_at(i).is_some())
+        .unwrap_or(0);
+    let start = first.saturating_sub(SNIPPET_CONTEXT_CHARS);
+    let end = (first + SNIPPET_CONTEXT_CHARS * 2).min(chars.len());
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    let mut i = start;
+    while i < end {
+        match match_at(i) {
+            Some(len) => {
+                let stop = (i + len).min(chars.len());
+                snippet.push_str("<mark>");
+                snippet.extend(&chars[i..stop]);
+                snippet.push_str("</mark>");
+                i = stop;
+            }
+            None => {
+                snippet.push(if chars[i] == '\n' { ' ' } else { chars[i] });
+                i += 1;
+            }
+        }
+    }
+    if i < chars.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+fn escape_like(term: &str) -> String {
+    format!(
+        "%{}%",
+        term.replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    )
+}
+
+/// Ranked search through the FTS5 indexes
+fn search_indexed(
+    conn: &Connection,
+    terms: &[String],
+    filters: &ConversationSearchFilters,
+    limit: u32,
+) -> Result<Vec<ConversationSearchHit>, AppError> {
+    let query = fts_query(terms);
+    let mut hits = Vec::new();
+
+    let mut stmt = conn.prepare(
+        "SELECT m.conversation_id, c.title, m.id, m.role,
+             snippet(messages_fts, 0, '<mark>', '</mark>', '…', 24),
+             bm25(messages_fts), m.created_at
+         FROM messages_fts
+         JOIN messages m ON m.rowid = messages_fts.rowid
+         JOIN conversations c ON c.id = m.conversation_id
+         WHERE messages_fts MATCH ?1
+           AND (?2 IS NULL OR c.document_id = ?2)
+           AND (?3 IS NULL OR m.role = ?3)
+           AND (?4 IS NULL OR m.created_at >= ?4)
+           AND (?5 IS NULL OR m.created_at <= ?5)
+         ORDER BY bm25(messages_fts)
+         LIMIT ?6",
+    )?;
+    let rows = stmt.query_map(
+        params![
+            query,
+            filters.document_id,
+            filters.role,
+            filters.from,
+            filters.to,
+            limit
+        ],
+        |row| {
+            Ok(ConversationSearchHit {
+                conversation_id: row.get(0)?,
+                conversation_title: row.get(1)?,
+                message_id: row.get(2)?,
+                role: row.get(3)?,
+                snippet: row.get(4)?,
+                score: -row.get::<_, f64>(5)?,
+                created_at: row.get(6)?,
+            })
+        },
+    )?;
+    for row in rows {
+        hits.push(row?);
+    }
+
+    // Titles have no role or message date to filter on
+    if filters.role.is_none() {
+        let mut stmt = conn.prepare(
+            "SELECT c.id, c.title,
+                 highlight(conversations_fts, 0, '<mark>', '</mark>'),
+                 bm25(conversations_fts), c.updated_at
+             FROM conversations_fts
+             JOIN conversations c ON c.rowid = conversations_fts.rowid
+             WHERE conversations_fts MATCH ?1
+               AND (?2 IS NULL OR c.document_id = ?2)
+               AND (?3 IS NULL OR c.updated_at >= ?3)
+               AND (?4 IS NULL OR c.created_at <= ?4)
+             ORDER BY bm25(conversations_fts)
+             LIMIT ?5",
+        )?;
+        let rows = stmt.query_map(
+            params![query, filters.document_id, filters.from, filters.to, limit],
+            |row| {
+                Ok(ConversationSearchHit {
+                    conversation_id: row.get(0)?,
+                    conversation_title: row.get(1)?,
+                    message_id: None,
+                    role: None,
+                    snippet: row.get(2)?,
+                    score: -row.get::<_, f64>(3)? * TITLE_BOOST,
+                    created_at: row.get(4)?,
+                })
+            },
+        )?;
+        for row in rows {
+            hits.push(row?);
+        }
+    }
+    Ok(hits)
+}
+
+/// Substring scan for queries the trigram index cannot serve, newest first
+fn search_unindexed(
+    conn: &Connection,
+    terms: &[String],
+    filters: &ConversationSearchFilters,
+    limit: u32,
+) -> Result<Vec<ConversationSearchHit>, AppError> {
+    let patterns: Vec<String> = terms.iter().map(|t| escape_like(t)).collect();
+    let mut hits = Vec::new();
+
+    let mut stmt = conn.prepare(
+        "SELECT m.conversation_id, c.title, m.id, m.role, conv_decrypt(m.content), m.created_at
+         FROM messages m JOIN conversations c ON c.id = m.conversation_id
+         WHERE (?1 IS NULL OR c.document_id = ?1)
+           AND (?2 IS NULL OR m.role = ?2)
+           AND (?3 IS NULL OR m.created_at >= ?3)
+           AND (?4 IS NULL OR m.created_at <= ?4)
+         ORDER BY m.created_at DESC",
+    )?;
+    let mut rows = stmt.query(params![
+        filters.document_id,
+        filters.role,
+        filters.from,
+        filters.to
+    ])?;
+    let matches_all = |text: &str| {
+        let text = text.to_lowercase();
+        terms.iter().all(|t| text.contains(&t.to_lowercase()))
+    };
+    while let Some(row) = rows.next()? {
+        let content: String = row.get(4)?;
+        if !matches_all(&content) {
+            continue;
+        }
+        hits.push(ConversationSearchHit {
+            conversation_id: row.get(0)?,
+            conversation_title: row.get(1)?,
+            message_id: row.get(2)?,
+            role: row.get(3)?,
+            snippet: make_snippet(&content, terms),
+            score: 0.0,
+            created_at: row.get(5)?,
+        });
+        if hits.len() >= limit as usize {
+            break;
+        }
+    }
+
+    if filters.role.is_none() && !patterns.is_empty() {
+        let mut stmt = conn.prepare(
+            "SELECT id, title, updated_at FROM conversations
+             WHERE title LIKE ?1 ESCAPE '\\' AND (?2 IS NULL OR document_id = ?2)
+             ORDER BY updated_at DESC",
+        )?;
+        let rows = stmt.query_map(params![patterns[0], filters.document_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (id, title, updated_at) = row?;
+            if matches_all(&title) {
+                hits.push(ConversationSearchHit {
+                    conversation_id: id,
+                    snippet: make_snippet(&title, terms),
+                    conversation_title: title,
+                    message_id: None,
+                    role: None,
+                    score: 0.0,
+                    created_at: updated_at,
+                });
+            }
+        }
+    }
+    Ok(hits)
+}
+
+/// Search conversation titles and messages
+pub fn search_conversations_in(
+    conn: &Connection,
+    query: &str,
+    filters: &ConversationSearchFilters,
+) -> Result<Vec<ConversationSearchHit>, AppError> {
+    let terms = search_terms(query);
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let limit = filters.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).max(1);
+
+    // The index only holds ciphertext for encrypted messages
+    let indexable = terms.iter().all(|t| t.chars().count() >= 3);
+    let mut hits = if indexable && !is_encryption_enabled(conn)? {
+        search_indexed(conn, &terms, filters, limit)?
+    } else {
+        search_unindexed(conn, &terms, filters, limit)?
+    };
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.created_at.cmp(&a.created_at))
+    });
+    hits.truncate(limit as usize);
+    Ok(hits)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Search conversation titles and message content, best matches first
+#[tauri::command]
+pub fn search_conversations(
+    state: tauri::State<'_, ConversationState>,
+    query: String,
+    filters: Option<ConversationSearchFilters>,
+) -> Result<Vec<ConversationSearchHit>, AppError> {
+    let conn = lock_conversations(&state)?;
+    search_conversations_in(&conn, &query, &filters.unwrap_or_default())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::messages::append_message;
+    use super::super::records::{create_conversation, update_conversation};
+    use super::super::storage::open_conversations_in_memory;
+    use super::super::types::{ConversationInput, ConversationUpdate, MessageInput};
+    use super::*;
+
+    fn conversation(conn: &Connection, title: &str, messages: &[(&str, &str)]) -> String {
+        let input = ConversationInput {
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        let id = create_conversation(conn, &input, 1).unwrap().id;
+        for (i, (role, content)) in messages.iter().enumerate() {
+            let message = MessageInput {
+                role: role.to_string(),
+                content: content.to_string(),
+                created_at: Some(10 + i as i64),
+                ..Default::default()
+            };
+            append_message(conn, &id, &message, 10).unwrap();
+        }
+        id
+    }
+
+    #[test]
+    fn search_terms_keep_phrases() {
+        assert_eq!(
+            search_terms(r#"cfi "epub locator" x"#),
+            vec!["cfi", "epub locator", "x"]
+        );
+        assert_eq!(
+            make_snippet("Use a CFI to point into the book", &["cfi".to_string()]),
+            "Use a <mark>CFI</mark> to point into the book"
+        );
+    }
+
+    #[test]
+    fn searches_messages_and_titles_with_filters() {
+        let conn = open_conversations_in_memory();
+        let epub = conversation(
+            &conn,
+            "EPUB questions",
+            &[
+                ("user", "How do CFI locators work?"),
+                ("assistant", "A CFI locator is a path into the EPUB spine."),
+            ],
+        );
+        let chinese = conversation(&conn, "阅读笔记", &[("user", "什么是数字签名？")]);
+
+        let hits = search_conversations_in(&conn, "cfi locator", &Default::default()).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|h| h.conversation_id == epub));
+        assert!(hits[0].snippet.contains("<mark>"));
+
+        let filters = ConversationSearchFilters {
+            role: Some("assistant".to_string()),
+            ..Default::default()
+        };
+        let hits = search_conversations_in(&conn, "cfi", &filters).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].role.as_deref(), Some("assistant"));
+
+        let hits = search_conversations_in(&conn, "epub", &Default::default()).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits
+            .iter()
+            .any(|h| h.message_id.is_none() && h.snippet == "<mark>EPUB</mark> questions"));
+
+        // Two-character CJK words are below the trigram length
+        let hits = search_conversations_in(&conn, "签名", &Default::default()).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].snippet, "什么是数字<mark>签名</mark>？");
+        let hits = search_conversations_in(&conn, "笔记", &Default::default()).unwrap();
+        assert_eq!(hits[0].conversation_id, chinese);
+
+        let update = ConversationUpdate {
+            title: Some("Renamed thread".to_string()),
+            ..Default::default()
+        };
+        update_conversation(&conn, &chinese, &update, 20).unwrap();
+        let hits = search_conversations_in(&conn, "renamed", &Default::default()).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(search_conversations_in(&conn, "  ", &Default::default())
+            .unwrap()
+            .is_empty());
+    }
+}
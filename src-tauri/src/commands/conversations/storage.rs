@@ -0,0 +1,211 @@
+//! Conversation database location and schema
+
+use super::encryption::restore_content_cipher;
+use super::types::ConversationState;
+use crate::commands::migrations::open_store_database;
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tauri::Manager;
+
+/// Conversation database file name in the app data directory
+pub const CONVERSATIONS_DB_FILE: &str = "conversations.db";
+
+/// Attachment store directory name in the app data directory
+pub const CONVERSATION_ATTACHMENTS_DIR: &str = "conversation-attachments";
+
+/// Schema migrations of the conversation database, in order (append only)
+pub const CONVERSATION_MIGRATIONS: &[&str] = &[
+    // v1: conversations, messages and attachment references
+    "CREATE TABLE conversations (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        document_id TEXT,
+        document_path TEXT,
+        document_name TEXT,
+        metadata TEXT NOT NULL DEFAULT '{}',
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_conversations_updated ON conversations(updated_at);
+    CREATE INDEX idx_conversations_document ON conversations(document_id);
+    CREATE TABLE messages (
+        id TEXT PRIMARY KEY,
+        conversation_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL,
+        model TEXT,
+        token_count INTEGER NOT NULL DEFAULT 0,
+        input_tokens INTEGER,
+        output_tokens INTEGER,
+        metadata TEXT NOT NULL DEFAULT '{}',
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_messages_conversation ON messages(conversation_id, position);
+    CREATE TABLE message_attachments (
+        message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        reference TEXT NOT NULL,
+        name TEXT,
+        mime_type TEXT,
+        size INTEGER,
+        PRIMARY KEY (message_id, position)
+    );",
+    // v2: full-text search over message content and titles
+    "CREATE VIRTUAL TABLE messages_fts USING fts5(
+        content, content = 'messages', content_rowid = 'rowid', tokenize = 'trigram'
+    );
+    CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+        INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+    END;
+    CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, content)
+        VALUES ('delete', old.rowid, old.content);
+    END;
+    CREATE TRIGGER messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, content)
+        VALUES ('delete', old.rowid, old.content);
+        INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+    END;
+    CREATE VIRTUAL TABLE conversations_fts USING fts5(
+        title, content = 'conversations', content_rowid = 'rowid', tokenize = 'trigram'
+    );
+    CREATE TRIGGER conversations_fts_insert AFTER INSERT ON conversations BEGIN
+        INSERT INTO conversations_fts (rowid, title) VALUES (new.rowid, new.title);
+    END;
+    CREATE TRIGGER conversations_fts_delete AFTER DELETE ON conversations BEGIN
+        INSERT INTO conversations_fts (conversations_fts, rowid, title)
+        VALUES ('delete', old.rowid, old.title);
+    END;
+    CREATE TRIGGER conversations_fts_update AFTER UPDATE OF title ON conversations BEGIN
+        INSERT INTO conversations_fts (conversations_fts, rowid, title)
+        VALUES ('delete', old.rowid, old.title);
+        INSERT INTO conversations_fts (rowid, title) VALUES (new.rowid, new.title);
+    END;
+    INSERT INTO messages_fts (messages_fts) VALUES ('rebuild');
+    INSERT INTO conversations_fts (conversations_fts) VALUES ('rebuild');",
+    // v3: prompt templates
+    "CREATE TABLE prompt_templates (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        description TEXT,
+        body TEXT NOT NULL,
+        tags TEXT NOT NULL DEFAULT '[]',
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );",
+    // v4: message branches; existing messages become a single chain
+    "ALTER TABLE messages ADD COLUMN parent_id TEXT REFERENCES messages(id) ON DELETE CASCADE;
+    ALTER TABLE conversations ADD COLUMN active_leaf_id TEXT;
+    UPDATE messages SET parent_id = (
+        SELECT p.id FROM messages p
+        WHERE p.conversation_id = messages.conversation_id AND p.position < messages.position
+        ORDER BY p.position DESC LIMIT 1
+    );
+    UPDATE conversations SET active_leaf_id = (
+        SELECT m.id FROM messages m WHERE m.conversation_id = conversations.id
+        ORDER BY m.position DESC LIMIT 1
+    );
+    CREATE INDEX idx_messages_parent ON messages(parent_id);",
+    // v5: conversation settings (JSON values)
+    "CREATE TABLE conversation_settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    // v6: archived conversations
+    "ALTER TABLE conversations ADD COLUMN archived_at INTEGER;
+    CREATE INDEX idx_conversations_archived ON conversations(archived_at);",
+    // v7: system prompt presets, global or scoped to a document or collection
+    "CREATE TABLE system_prompts (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        content TEXT NOT NULL,
+        scope TEXT NOT NULL DEFAULT 'global',
+        scope_id TEXT,
+        is_default INTEGER NOT NULL DEFAULT 0,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_system_prompts_scope ON system_prompts(scope, scope_id);",
+];
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Get the conversation database file path
+pub fn get_conversations_db_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(data_dir.join(CONVERSATIONS_DB_FILE))
+}
+
+/// Get the directory for files attached to conversations
+pub fn get_conversation_attachments_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(data_dir.join(CONVERSATION_ATTACHMENTS_DIR))
+}
+
+/// Open the conversation database and wrap it as managed state
+pub fn init_conversation_state(app: &tauri::AppHandle) -> Result<ConversationState, AppError> {
+    let path = get_conversations_db_path(app)?;
+    let conn = open_store_database(&path, "conversations", CONVERSATION_MIGRATIONS)?;
+    restore_content_cipher(&conn)?;
+    log::info!("Conversation database opened: {:?}", path);
+    Ok(Arc::new(Mutex::new(conn)))
+}
+
+/// Lock the conversation connection
+pub fn lock_conversations(
+    state: &ConversationState,
+) -> Result<MutexGuard<'_, Connection>, AppError> {
+    state.lock().map_err(|e| AppError::Database(e.to_string()))
+}
+
+/// Load a JSON setting, falling back to its default when unset or unreadable
+pub fn load_setting<T: DeserializeOwned + Default>(
+    conn: &Connection,
+    key: &str,
+) -> Result<T, AppError> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM conversation_settings WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(value
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default())
+}
+
+/// Store a JSON setting
+pub fn store_setting<T: Serialize>(
+    conn: &Connection,
+    key: &str,
+    value: &T,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO conversation_settings (key, value) VALUES (?1, ?2)",
+        params![key, serde_json::to_string(value)?],
+    )?;
+    Ok(())
+}
+
+/// Open an in-memory conversation database without encryption (used by tests)
+#[cfg(test)]
+pub fn open_conversations_in_memory() -> Connection {
+    let conn = crate::db::open_in_memory(CONVERSATION_MIGRATIONS).unwrap();
+    super::encryption::install_content_cipher(&conn, None).unwrap();
+    conn
+}
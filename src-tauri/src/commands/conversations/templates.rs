@@ -0,0 +1,487 @@
+//! Prompt templates with `{{variable}}` placeholders
+
+use super::storage::lock_conversations;
+use super::types::{
+    ConversationState, PromptTemplate, PromptTemplateImportResult, PromptTemplateInput,
+    PromptTemplateUpdate, RenderedPrompt,
+};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+const TEMPLATE_COLUMNS: &str = "id, name, description, body, tags, created_at, updated_at";
+
+/// Template export file
+#[derive(Deserialize)]
+struct PromptTemplateFile {
+    templates: Vec<PromptTemplateInput>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Placeholders in a template body as (byte range, trimmed name)
+fn placeholders(body: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(open) = body[pos..].find("{{").map(|i| pos + i) {
+        let Some(close) = body[open + 2..].find("}}").map(|i| open + 2 + i) else {
+            break;
+        };
+        let name = body[open + 2..close].trim();
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if valid {
+            found.push((open..close + 2, name));
+            pos = close + 2;
+        } else {
+            pos = open + 2;
+        }
+    }
+    found
+}
+
+/// Variable names used in a template body, in order of first use
+pub fn template_variables(body: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, name) in placeholders(body) {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Substitute variables into a template body
+pub fn render_template(body: &str, variables: &HashMap<String, String>) -> RenderedPrompt {
+    let mut text = String::with_capacity(body.len());
+    let mut missing: Vec<String> = Vec::new();
+    let mut last = 0;
+    for (range, name) in placeholders(body) {
+        text.push_str(&body[last..range.start]);
+        match variables.get(name) {
+            Some(value) => text.push_str(value),
+            None => {
+                text.push_str(&body[range.clone()]);
+                if !missing.iter().any(|m| m == name) {
+                    missing.push(name.to_string());
+                }
+            }
+        }
+        last = range.end;
+    }
+    text.push_str(&body[last..]);
+    RenderedPrompt { text, missing }
+}
+
+fn row_to_template(row: &Row) -> rusqlite::Result<PromptTemplate> {
+    let body: String = row.get(3)?;
+    let tags: String = row.get(4)?;
+    Ok(PromptTemplate {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        variables: template_variables(&body),
+        body,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if !normalized.iter().any(|n| n.eq_ignore_ascii_case(tag)) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Template name is required".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+fn not_found(id: &str) -> AppError {
+    AppError::NotFound(format!("Prompt template '{}' not found", id))
+}
+
+/// Get a prompt template by id
+pub fn get_template(conn: &Connection, id: &str) -> Result<Option<PromptTemplate>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM prompt_templates WHERE id = ?1",
+        TEMPLATE_COLUMNS
+    );
+    Ok(conn
+        .query_row(&sql, params![id], row_to_template)
+        .optional()?)
+}
+
+/// List prompt templates by name, optionally only those with a tag
+pub fn list_templates(
+    conn: &Connection,
+    tag: Option<&str>,
+) -> Result<Vec<PromptTemplate>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM prompt_templates ORDER BY name COLLATE NOCASE, id",
+        TEMPLATE_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let templates = stmt
+        .query_map([], row_to_template)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(templates
+        .into_iter()
+        .filter(|t| {
+            tag.map_or(true, |tag| {
+                t.tags.iter().any(|x| x.eq_ignore_ascii_case(tag))
+            })
+        })
+        .collect())
+}
+
+/// Create a prompt template
+pub fn create_template(
+    conn: &Connection,
+    input: &PromptTemplateInput,
+    now: i64,
+) -> Result<PromptTemplate, AppError> {
+    let name = validate_name(&input.name)?;
+    let id = input
+        .id
+        .clone()
+        .unwrap_or_else(|| format!("tpl_{}", Uuid::new_v4()));
+    let created_at = input.created_at.unwrap_or(now);
+    conn.execute(
+        "INSERT INTO prompt_templates (id, name, description, body, tags, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            id,
+            name,
+            input.description,
+            input.body,
+            serde_json::to_string(&normalize_tags(&input.tags))?,
+            created_at,
+            input.updated_at.unwrap_or(created_at)
+        ],
+    )?;
+    get_template(conn, &id)?.ok_or_else(|| not_found(&id))
+}
+
+/// Update a prompt template
+pub fn update_template(
+    conn: &Connection,
+    id: &str,
+    update: &PromptTemplateUpdate,
+    now: i64,
+) -> Result<PromptTemplate, AppError> {
+    let name = update.name.as_deref().map(validate_name).transpose()?;
+    let tags = update
+        .tags
+        .as_ref()
+        .map(|tags| serde_json::to_string(&normalize_tags(tags)))
+        .transpose()?;
+    let changed = conn.execute(
+        "UPDATE prompt_templates SET
+            name = COALESCE(?2, name),
+            description = COALESCE(?3, description),
+            body = COALESCE(?4, body),
+            tags = COALESCE(?5, tags),
+            updated_at = ?6
+         WHERE id = ?1",
+        params![id, name, update.description, update.body, tags, now],
+    )?;
+    if changed == 0 {
+        return Err(not_found(id));
+    }
+    get_template(conn, id)?.ok_or_else(|| not_found(id))
+}
+
+/// Delete a prompt template
+pub fn delete_template(conn: &Connection, id: &str) -> Result<(), AppError> {
+    let changed = conn.execute("DELETE FROM prompt_templates WHERE id = ?1", params![id])?;
+    if changed == 0 {
+        return Err(not_found(id));
+    }
+    Ok(())
+}
+
+/// Serialize templates to the export format
+pub fn export_templates_json(
+    conn: &Connection,
+    ids: Option<&[String]>,
+) -> Result<(String, usize), AppError> {
+    let templates: Vec<PromptTemplate> = list_templates(conn, None)?
+        .into_iter()
+        .filter(|t| ids.map_or(true, |ids| ids.contains(&t.id)))
+        .collect();
+    let export_data = serde_json::json!({
+        "version": 1,
+        "source": "sast-readium",
+        "exportedAt": chrono::Utc::now().timestamp(),
+        "templates": templates
+    });
+    Ok((serde_json::to_string_pretty(&export_data)?, templates.len()))
+}
+
+/// Import templates, de-duplicating by id
+///
+/// A template whose id already exists replaces the stored one only when it
+/// was updated more recently.
+pub fn import_templates_json(
+    conn: &Connection,
+    data: &str,
+    now: i64,
+) -> Result<PromptTemplateImportResult, AppError> {
+    let file: PromptTemplateFile = serde_json::from_str(data)?;
+    let mut result = PromptTemplateImportResult::default();
+
+    for input in file.templates {
+        let existing = match input.id.as_deref() {
+            Some(id) => get_template(conn, id)?,
+            None => None,
+        };
+        let outcome = match existing {
+            Some(existing) if input.updated_at.unwrap_or(0) <= existing.updated_at => {
+                result.skipped_count += 1;
+                continue;
+            }
+            Some(existing) => conn
+                .execute(
+                    "UPDATE prompt_templates SET name = ?2, description = ?3, body = ?4,
+                         tags = ?5, updated_at = ?6
+                     WHERE id = ?1",
+                    params![
+                        existing.id,
+                        input.name.trim(),
+                        input.description,
+                        input.body,
+                        serde_json::to_string(&normalize_tags(&input.tags))?,
+                        input.updated_at.unwrap_or(now)
+                    ],
+                )
+                .map(|_| &mut result.updated_count)
+                .map_err(AppError::from),
+            None => create_template(conn, &input, now).map(|_| &mut result.imported_count),
+        };
+        match outcome {
+            Ok(count) => *count += 1,
+            Err(e) => {
+                result.skipped_count += 1;
+                result
+                    .errors
+                    .push(format!("Skipped '{}': {}", input.name, e));
+            }
+        }
+    }
+    Ok(result)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// List prompt templates, optionally only those with a tag
+#[tauri::command]
+pub fn prompt_template_list(
+    state: tauri::State<'_, ConversationState>,
+    tag: Option<String>,
+) -> Result<Vec<PromptTemplate>, AppError> {
+    let conn = lock_conversations(&state)?;
+    list_templates(&conn, tag.as_deref())
+}
+
+/// Create a prompt template
+#[tauri::command]
+pub fn prompt_template_create(
+    state: tauri::State<'_, ConversationState>,
+    template: PromptTemplateInput,
+) -> Result<PromptTemplate, AppError> {
+    let conn = lock_conversations(&state)?;
+    create_template(&conn, &template, chrono::Utc::now().timestamp())
+}
+
+/// Update a prompt template
+#[tauri::command]
+pub fn prompt_template_update(
+    state: tauri::State<'_, ConversationState>,
+    id: String,
+    update: PromptTemplateUpdate,
+) -> Result<PromptTemplate, AppError> {
+    let conn = lock_conversations(&state)?;
+    update_template(&conn, &id, &update, chrono::Utc::now().timestamp())
+}
+
+/// Delete a prompt template
+#[tauri::command]
+pub fn prompt_template_delete(
+    state: tauri::State<'_, ConversationState>,
+    id: String,
+) -> Result<(), AppError> {
+    let conn = lock_conversations(&state)?;
+    delete_template(&conn, &id)
+}
+
+/// Render a prompt template with the given variable values
+#[tauri::command]
+pub fn prompt_template_render(
+    state: tauri::State<'_, ConversationState>,
+    id: String,
+    variables: HashMap<String, String>,
+) -> Result<RenderedPrompt, AppError> {
+    let conn = lock_conversations(&state)?;
+    let template = get_template(&conn, &id)?.ok_or_else(|| not_found(&id))?;
+    Ok(render_template(&template.body, &variables))
+}
+
+/// Export prompt templates (all, or the given ids) to a JSON string
+#[tauri::command]
+pub fn export_prompt_templates(
+    state: tauri::State<'_, ConversationState>,
+    ids: Option<Vec<String>>,
+) -> Result<String, AppError> {
+    let conn = lock_conversations(&state)?;
+    Ok(export_templates_json(&conn, ids.as_deref())?.0)
+}
+
+/// Export prompt templates to a file, returning how many were written
+#[tauri::command]
+pub fn export_prompt_templates_to_file(
+    state: tauri::State<'_, ConversationState>,
+    file_path: String,
+    ids: Option<Vec<String>>,
+) -> Result<usize, AppError> {
+    let conn = lock_conversations(&state)?;
+    let (content, count) = export_templates_json(&conn, ids.as_deref())?;
+    fs::write(&file_path, content)?;
+    log::info!("Prompt templates exported to: {}", file_path);
+    Ok(count)
+}
+
+/// Import prompt templates from JSON data
+#[tauri::command]
+pub fn import_prompt_templates(
+    state: tauri::State<'_, ConversationState>,
+    data: String,
+) -> Result<PromptTemplateImportResult, AppError> {
+    let conn = lock_conversations(&state)?;
+    let result = import_templates_json(&conn, &data, chrono::Utc::now().timestamp())?;
+    log::info!(
+        "Prompt templates imported: {} new, {} updated, {} skipped",
+        result.imported_count,
+        result.updated_count,
+        result.skipped_count
+    );
+    Ok(result)
+}
+
+/// Import prompt templates from a file
+#[tauri::command]
+pub fn import_prompt_templates_from_file(
+    state: tauri::State<'_, ConversationState>,
+    file_path: String,
+) -> Result<PromptTemplateImportResult, AppError> {
+    let path = Path::new(&file_path);
+    if !path.exists() {
+        return Err(AppError::NotFound(format!("File not found: {}", file_path)));
+    }
+    let data = fs::read_to_string(path)?;
+    import_prompt_templates(state, data)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::storage::open_conversations_in_memory;
+    use super::*;
+
+    #[test]
+    fn renders_variables_and_reports_missing_ones() {
+        let body =
+            "Explain {{ term }} from {{book}} on page {{page}}. {{term}}! {{ not valid }} {{";
+        assert_eq!(template_variables(body), vec!["term", "book", "page"]);
+
+        let variables = HashMap::from([
+            ("term".to_string(), "CFI".to_string()),
+            ("book".to_string(), "EPUB 3".to_string()),
+        ]);
+        let rendered = render_template(body, &variables);
+        assert_eq!(
+            rendered.text,
+            "Explain CFI from EPUB 3 on page {{page}}. CFI! {{ not valid }} {{"
+        );
+        assert_eq!(rendered.missing, vec!["page"]);
+    }
+
+    #[test]
+    fn templates_are_stored_exported_and_imported_by_id() {
+        let conn = open_conversations_in_memory();
+        let input = PromptTemplateInput {
+            name: " Define ".to_string(),
+            body: "Define {{word}}".to_string(),
+            tags: vec!["study".to_string(), "Study".to_string(), " ".to_string()],
+            ..Default::default()
+        };
+        let template = create_template(&conn, &input, 10).unwrap();
+        assert_eq!(template.name, "Define");
+        assert_eq!(template.tags, vec!["study"]);
+        assert_eq!(template.variables, vec!["word"]);
+        assert_eq!(list_templates(&conn, Some("STUDY")).unwrap().len(), 1);
+        assert!(list_templates(&conn, Some("other")).unwrap().is_empty());
+
+        let (exported, count) = export_templates_json(&conn, None).unwrap();
+        assert_eq!(count, 1);
+
+        // Re-importing the same data changes nothing
+        let result = import_templates_json(&conn, &exported, 20).unwrap();
+        assert_eq!((result.imported_count, result.skipped_count), (0, 1));
+
+        let newer = exported
+            .replace("Define {{word}}", "Define {{word}} simply")
+            .replace("\"updatedAt\": 10", "\"updatedAt\": 30");
+        let result = import_templates_json(&conn, &newer, 40).unwrap();
+        assert_eq!(result.updated_count, 1);
+        assert_eq!(
+            get_template(&conn, &template.id).unwrap().unwrap().body,
+            "Define {{word}} simply"
+        );
+
+        let frontend = r#"{"templates": [{"name": "Quiz", "content": "Quiz me on {{topic}}"},
+                                         {"name": "  ", "content": "x"}]}"#;
+        let result = import_templates_json(&conn, frontend, 50).unwrap();
+        assert_eq!((result.imported_count, result.skipped_count), (1, 1));
+        assert_eq!(result.errors.len(), 1);
+
+        let update = PromptTemplateUpdate {
+            tags: Some(vec!["exam".to_string()]),
+            ..Default::default()
+        };
+        let updated = update_template(&conn, &template.id, &update, 60).unwrap();
+        assert_eq!(
+            (updated.tags, updated.updated_at),
+            (vec!["exam".to_string()], 60)
+        );
+        delete_template(&conn, &template.id).unwrap();
+        assert!(matches!(
+            delete_template(&conn, &template.id),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,340 @@
+//! Conversation titles generated from the first exchanges
+
+use super::branches::list_active_messages;
+use super::records::{get_conversation_summary, update_conversation, DEFAULT_CONVERSATION_TITLE};
+use super::storage::{load_setting, lock_conversations, store_setting};
+use super::types::{
+    ConversationMessage, ConversationState, ConversationSummary, ConversationUpdate,
+    TitleGenerationSettings, TitleMessage,
+};
+use crate::commands::ai_proxy::{request_chat_completion, AIMessage};
+use crate::commands::events::emit_event;
+use crate::error::AppError;
+use rusqlite::Connection;
+use tauri::Manager;
+
+/// Event emitted with the conversation summary after an automatic title
+pub const CONVERSATION_TITLE_EVENT: &str = "conversation-title-generated";
+
+const TITLE_SETTINGS_KEY: &str = "title_generation";
+
+/// Messages of the active branch sent for a title
+const TITLE_CONTEXT_MESSAGES: usize = 4;
+
+/// Characters of each message sent for a title
+const TITLE_CONTEXT_CHARS: usize = 1500;
+
+const MAX_TITLE_CHARS: usize = 80;
+
+const TITLE_SYSTEM_PROMPT: &str = "You name chat conversations. \
+Reply with a short title of at most six words for the conversation below, \
+in the language of the conversation. Reply with the title only.";
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Load the title generation settings
+pub fn get_title_settings(conn: &Connection) -> Result<TitleGenerationSettings, AppError> {
+    load_setting(conn, TITLE_SETTINGS_KEY)
+}
+
+/// Store the title generation settings
+pub fn set_title_settings(
+    conn: &Connection,
+    settings: &TitleGenerationSettings,
+) -> Result<(), AppError> {
+    if settings.provider.trim().is_empty() || settings.model.trim().is_empty() {
+        return Err(AppError::InvalidInput(
+            "Provider and model are required".to_string(),
+        ));
+    }
+    store_setting(conn, TITLE_SETTINGS_KEY, settings)
+}
+
+/// The opening user and assistant messages, given as role and content, as
+/// a transcript
+fn transcript<'a>(messages: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    messages
+        .filter(|(role, _)| *role == "user" || *role == "assistant")
+        .take(TITLE_CONTEXT_MESSAGES)
+        .map(|(role, content)| {
+            let speaker = if role == "user" { "User" } else { "Assistant" };
+            let text: String = content.trim().chars().take(TITLE_CONTEXT_CHARS).collect();
+            format!("{}: {}", speaker, text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The opening user and assistant messages as a transcript
+pub fn title_transcript(messages: &[ConversationMessage]) -> String {
+    transcript(
+        messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str())),
+    )
+}
+
+/// The opening user and assistant messages of an unstored chat as a
+/// transcript
+pub fn chat_title_transcript(messages: &[TitleMessage]) -> String {
+    transcript(
+        messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str())),
+    )
+}
+
+/// Reduce a model reply to a bare title
+pub fn clean_title(reply: &str) -> Option<String> {
+    let line = reply.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let line = line
+        .strip_prefix("Title:")
+        .or_else(|| line.strip_prefix("title:"))
+        .unwrap_or(line);
+    let title = line
+        .trim_matches(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '*' | '#' | '`'))
+        .trim_end_matches(['.', '。']);
+    let title: String = title.chars().take(MAX_TITLE_CHARS).collect();
+    let title = title.trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// Whether an appended reply should trigger an automatic title
+///
+/// True for the first assistant reply of a conversation that still has the
+/// default title, when automatic titles are enabled.
+pub fn needs_automatic_title(conn: &Connection, conversation_id: &str) -> Result<bool, AppError> {
+    if !get_title_settings(conn)?.auto_generate {
+        return Ok(false);
+    }
+    let Some(summary) = get_conversation_summary(conn, conversation_id)? else {
+        return Ok(false);
+    };
+    let replies = list_active_messages(conn, conversation_id)?
+        .iter()
+        .filter(|m| m.role == "assistant")
+        .count();
+    Ok(summary.title == DEFAULT_CONVERSATION_TITLE && replies == 1)
+}
+
+/// Ask the model for a title for a transcript with the fixed title prompt
+///
+/// Uses the given provider and model, or the configured ones.
+async fn request_title(
+    transcript: String,
+    settings: TitleGenerationSettings,
+    provider: Option<String>,
+    model: Option<String>,
+) -> Result<String, AppError> {
+    if transcript.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Conversation has no messages to title".to_string(),
+        ));
+    }
+
+    let provider = provider.unwrap_or(settings.provider);
+    let model = model.unwrap_or(settings.model);
+    let messages = vec![AIMessage {
+        role: "user".to_string(),
+        content: transcript.into(),
+        cache: false,
+    }];
+    let reply = request_chat_completion(
+        &provider,
+        &model,
+        messages,
+        Some(TITLE_SYSTEM_PROMPT.into()),
+    )
+    .await?;
+    clean_title(&reply)
+        .ok_or_else(|| AppError::InvalidOutput("The model returned an empty title".to_string()))
+}
+
+/// Generate and store a title for a conversation
+///
+/// Uses the given provider and model, or the configured ones.
+pub async fn generate_title(
+    state: &ConversationState,
+    conversation_id: &str,
+    provider: Option<String>,
+    model: Option<String>,
+) -> Result<ConversationSummary, AppError> {
+    let (transcript, settings) = {
+        let conn = lock_conversations(state)?;
+        if get_conversation_summary(&conn, conversation_id)?.is_none() {
+            return Err(AppError::NotFound(format!(
+                "Conversation '{}' not found",
+                conversation_id
+            )));
+        }
+        let messages = list_active_messages(&conn, conversation_id)?;
+        (title_transcript(&messages), get_title_settings(&conn)?)
+    };
+    let title = request_title(transcript, settings, provider, model).await?;
+
+    let conn = lock_conversations(state)?;
+    let update = ConversationUpdate {
+        title: Some(title),
+        ..Default::default()
+    };
+    update_conversation(
+        &conn,
+        conversation_id,
+        &update,
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+/// Generate a title in the background and announce it to the frontend
+pub fn spawn_automatic_title(app: &tauri::AppHandle, conversation_id: String) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<ConversationState>().inner().clone();
+        match generate_title(&state, &conversation_id, None, None).await {
+            Ok(summary) => {
+                emit_event(&app, &summary);
+            }
+            Err(e) => log::warn!(
+                "Automatic title for conversation {} failed: {}",
+                conversation_id,
+                e
+            ),
+        }
+    });
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Generate and store a title for a conversation from its first exchanges
+#[tauri::command]
+pub async fn generate_conversation_title(
+    state: tauri::State<'_, ConversationState>,
+    conversation_id: String,
+    provider: Option<String>,
+    model: Option<String>,
+) -> Result<ConversationSummary, AppError> {
+    let state = state.inner().clone();
+    generate_title(&state, &conversation_id, provider, model).await
+}
+
+/// Generate a title for a chat not kept in the conversation store from its
+/// first messages, without storing it
+#[tauri::command]
+pub async fn suggest_conversation_title(
+    state: tauri::State<'_, ConversationState>,
+    messages: Vec<TitleMessage>,
+    provider: Option<String>,
+    model: Option<String>,
+) -> Result<String, AppError> {
+    let settings = {
+        let conn = lock_conversations(&state)?;
+        get_title_settings(&conn)?
+    };
+    request_title(chat_title_transcript(&messages), settings, provider, model).await
+}
+
+/// Get the title generation settings
+#[tauri::command]
+pub fn get_title_generation_settings(
+    state: tauri::State<'_, ConversationState>,
+) -> Result<TitleGenerationSettings, AppError> {
+    let conn = lock_conversations(&state)?;
+    get_title_settings(&conn)
+}
+
+/// Update the title generation settings
+#[tauri::command]
+pub fn set_title_generation_settings(
+    state: tauri::State<'_, ConversationState>,
+    settings: TitleGenerationSettings,
+) -> Result<(), AppError> {
+    let conn = lock_conversations(&state)?;
+    set_title_settings(&conn, &settings)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::messages::append_message;
+    use super::super::records::create_conversation;
+    use super::super::storage::open_conversations_in_memory;
+    use super::super::types::{ConversationInput, MessageInput};
+    use super::*;
+
+    fn message(role: &str, content: &str) -> MessageInput {
+        MessageInput {
+            role: role.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cleans_model_replies_into_titles() {
+        assert_eq!(
+            clean_title("\n  Title: \"Whale Anatomy Basics.\"\nExtra").as_deref(),
+            Some("Whale Anatomy Basics")
+        );
+        assert_eq!(
+            clean_title("**鲸鱼的结构。**").as_deref(),
+            Some("鲸鱼的结构")
+        );
+        assert_eq!(clean_title(" \"\" "), None);
+        assert_eq!(
+            clean_title(&"x".repeat(200)).unwrap().len(),
+            MAX_TITLE_CHARS
+        );
+    }
+
+    #[test]
+    fn automatic_titles_follow_settings_and_first_reply() {
+        let conn = open_conversations_in_memory();
+        let conv = create_conversation(&conn, &ConversationInput::default(), 1).unwrap();
+        append_message(&conn, &conv.id, &message("system", "Be brief."), 2).unwrap();
+        append_message(&conn, &conv.id, &message("user", "What is a whale?"), 2).unwrap();
+        append_message(
+            &conn,
+            &conv.id,
+            &message("assistant", "A marine mammal."),
+            3,
+        )
+        .unwrap();
+
+        let messages = list_active_messages(&conn, &conv.id).unwrap();
+        assert_eq!(
+            title_transcript(&messages),
+            "User: What is a whale?\n\nAssistant: A marine mammal."
+        );
+        let chat = [
+            TitleMessage {
+                role: "user".to_string(),
+                content: " What is a whale? ".to_string(),
+            },
+            TitleMessage {
+                role: "tool".to_string(),
+                content: "{}".to_string(),
+            },
+        ];
+        assert_eq!(chat_title_transcript(&chat), "User: What is a whale?");
+
+        assert!(!needs_automatic_title(&conn, &conv.id).unwrap());
+        let settings = TitleGenerationSettings {
+            auto_generate: true,
+            ..Default::default()
+        };
+        set_title_settings(&conn, &settings).unwrap();
+        assert_eq!(get_title_settings(&conn).unwrap(), settings);
+        assert!(needs_automatic_title(&conn, &conv.id).unwrap());
+
+        append_message(&conn, &conv.id, &message("user", "And a dolphin?"), 4).unwrap();
+        append_message(&conn, &conv.id, &message("assistant", "Also one."), 5).unwrap();
+        assert!(!needs_automatic_title(&conn, &conv.id).unwrap());
+    }
+}
@@ -0,0 +1,386 @@
+//! Conversation type definitions
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+// ============================================================================
+// Core Types
+// ============================================================================
+
+/// A conversation with summary counts, as shown in conversation lists
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    /// Library document the conversation is about
+    pub document_id: Option<String>,
+    pub document_path: Option<String>,
+    pub document_name: Option<String>,
+    /// Free-form frontend state (model settings, usage totals, ...)
+    pub metadata: serde_json::Value,
+    pub message_count: u32,
+    pub total_tokens: u64,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// When the conversation was archived; None while active
+    pub archived_at: Option<i64>,
+}
+
+/// A conversation with the messages of its active branch in order
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Conversation {
+    #[serde(flatten)]
+    pub summary: ConversationSummary,
+    pub messages: Vec<ConversationMessage>,
+}
+
+/// A message in the conversation tree with its alternative versions
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageTreeNode {
+    #[serde(flatten)]
+    pub message: ConversationMessage,
+    /// Replies to this message, oldest first
+    pub children: Vec<String>,
+    /// 1-based position among messages sharing the same parent
+    pub version: u32,
+    pub version_count: u32,
+}
+
+/// All branches of a conversation
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationTree {
+    pub conversation_id: String,
+    /// Last message of the branch shown by default
+    pub active_leaf_id: Option<String>,
+    /// Message ids from the root to the active leaf
+    pub active_path: Vec<String>,
+    /// Messages in creation order
+    pub nodes: Vec<MessageTreeNode>,
+}
+
+/// A stored chat message
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationMessage {
+    pub id: String,
+    pub conversation_id: String,
+    /// "user" | "assistant" | "system" | "tool"
+    pub role: String,
+    pub content: String,
+    pub model: Option<String>,
+    /// Tokens in the message content
+    pub token_count: u32,
+    /// Prompt and completion tokens reported by the provider for this reply
+    pub input_tokens: Option<u32>,
+    pub output_tokens: Option<u32>,
+    /// Free-form frontend state (tool invocations, suggestions, ...)
+    pub metadata: serde_json::Value,
+    pub attachments: Vec<MessageAttachment>,
+    pub created_at: i64,
+    /// Message this one replies to; None for the first message of a branch
+    pub parent_id: Option<String>,
+}
+
+/// A file, image, URL or library document referenced by a message
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageAttachment {
+    /// "file" | "image" | "url" | "document"
+    pub kind: String,
+    /// Path, URL or library document id
+    pub reference: String,
+    pub name: Option<String>,
+    pub mime_type: Option<String>,
+    pub size: Option<u64>,
+}
+
+// ============================================================================
+// Input Types
+// ============================================================================
+
+/// Conversation input passed from the frontend when creating a conversation
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationInput {
+    /// Keep an existing id (when migrating stored chats); generated otherwise
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub document_id: Option<String>,
+    pub document_path: Option<String>,
+    pub document_name: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: Option<i64>,
+}
+
+/// Fields that can be updated on an existing conversation
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationUpdate {
+    pub title: Option<String>,
+    pub document_id: Option<String>,
+    pub document_path: Option<String>,
+    pub document_name: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Query options for listing conversations
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationListQuery {
+    /// Only return conversations about this library document
+    pub document_id: Option<String>,
+    /// List archived conversations instead of active ones
+    #[serde(default)]
+    pub archived: bool,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// Message input appended to a conversation
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageInput {
+    /// Keep an existing id (when migrating stored chats); generated otherwise
+    pub id: Option<String>,
+    /// Message to reply to; the end of the active branch when not given
+    pub parent_id: Option<String>,
+    pub role: String,
+    pub content: String,
+    pub model: Option<String>,
+    /// Counted from the content when not given
+    pub token_count: Option<u32>,
+    pub input_tokens: Option<u32>,
+    pub output_tokens: Option<u32>,
+    pub metadata: Option<serde_json::Value>,
+    #[serde(default)]
+    pub attachments: Vec<MessageAttachment>,
+    pub created_at: Option<i64>,
+}
+
+/// Filters narrowing a conversation search
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSearchFilters {
+    /// Only search conversations about this library document
+    pub document_id: Option<String>,
+    /// Only match messages with this role
+    pub role: Option<String>,
+    /// Only match messages created in this range (unix seconds)
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+/// A conversation title or message matching a search
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSearchHit {
+    pub conversation_id: String,
+    pub conversation_title: String,
+    /// None when the title matched
+    pub message_id: Option<String>,
+    pub role: Option<String>,
+    /// Matched text around the hit, with matches wrapped in `<mark>` tags
+    pub snippet: String,
+    /// Relevance, higher is better
+    pub score: f64,
+    pub created_at: i64,
+}
+
+/// A reusable prompt with `{{variable}}` placeholders
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplate {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub body: String,
+    pub tags: Vec<String>,
+    /// Placeholder names in order of first use
+    #[serde(default)]
+    pub variables: Vec<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Prompt template input passed from the frontend or an import file
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplateInput {
+    /// Keep an existing id (imports); generated otherwise
+    pub id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    /// Also accepted as `content`, the frontend's field name
+    #[serde(alias = "content")]
+    pub body: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub created_at: Option<i64>,
+    pub updated_at: Option<i64>,
+}
+
+/// Fields that can be updated on an existing prompt template
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplateUpdate {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub body: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+/// A template with its variables substituted
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedPrompt {
+    pub text: String,
+    /// Variables without a value, left in place as `{{name}}`
+    pub missing: Vec<String>,
+}
+
+/// Prompt template import result
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplateImportResult {
+    pub imported_count: usize,
+    pub updated_count: usize,
+    pub skipped_count: usize,
+    pub errors: Vec<String>,
+}
+
+/// A named system prompt, global or scoped to a document or collection
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemPromptPreset {
+    pub id: String,
+    pub name: String,
+    pub content: String,
+    /// "global", "document" or "collection"
+    pub scope: String,
+    /// Document or collection id for scoped presets
+    pub scope_id: Option<String>,
+    /// Used when a request in this scope names no system prompt
+    pub is_default: bool,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// System prompt preset input passed from the frontend
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemPromptPresetInput {
+    pub name: String,
+    pub content: String,
+    /// Defaults to "global"
+    pub scope: Option<String>,
+    pub scope_id: Option<String>,
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+/// Fields that can be updated on an existing system prompt preset
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemPromptPresetUpdate {
+    pub name: Option<String>,
+    pub content: Option<String>,
+    pub is_default: Option<bool>,
+}
+
+/// A message of a chat not kept in the conversation store, sent for a title
+#[derive(Deserialize, Clone, Debug)]
+pub struct TitleMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// Model used for conversation titles and whether they are generated
+/// automatically after the first reply
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TitleGenerationSettings {
+    pub auto_generate: bool,
+    pub provider: String,
+    pub model: String,
+}
+
+impl Default for TitleGenerationSettings {
+    fn default() -> Self {
+        Self {
+            auto_generate: false,
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+        }
+    }
+}
+
+/// When idle conversations are archived and archived ones deleted
+///
+/// Each rule is disabled when unset.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    /// Archive conversations not updated for this many days
+    pub archive_after_days: Option<u32>,
+    /// Delete conversations archived this many days ago
+    pub purge_archived_after_days: Option<u32>,
+}
+
+/// Conversations archived and deleted by a retention run
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionReport {
+    /// Nothing was changed; the lists show what a run would do
+    pub dry_run: bool,
+    pub archived: Vec<ConversationSummary>,
+    pub purged: Vec<ConversationSummary>,
+    pub ran_at: i64,
+}
+
+/// Whether message content is stored encrypted
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationEncryptionStatus {
+    pub enabled: bool,
+    /// False when encryption is on but the key cannot be read from the keyring
+    pub key_available: bool,
+    pub encrypted_messages: u32,
+    pub total_messages: u32,
+}
+
+/// Counts of a conversation bundle export
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationBundleExport {
+    pub conversation_count: usize,
+    pub message_count: usize,
+    /// Attached local files copied into the bundle
+    pub attachment_count: usize,
+    pub template_count: usize,
+}
+
+/// Conversation bundle import result
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationBundleImport {
+    pub imported_count: usize,
+    /// Conversations that already existed or failed to import
+    pub skipped_count: usize,
+    pub message_count: usize,
+    pub attachment_count: usize,
+    pub templates: PromptTemplateImportResult,
+    pub errors: Vec<String>,
+}
+
+// ============================================================================
+// State Types
+// ============================================================================
+
+/// Thread-safe conversation database connection
+pub type ConversationState = Arc<Mutex<Connection>>;
@@ -0,0 +1,703 @@
+//! Document format conversion through external tools
+//!
+//! Conversions are delegated to Calibre's `ebook-convert` or `pandoc` when
+//! they are installed. The tool runs as a child process whose output is
+//! streamed to the frontend as progress events; the converted file is then
+//! imported into the library.
+
+use crate::commands::events::emit_event;
+use crate::commands::library::{
+    detect_document_format, enrich_document_input, fingerprint_file, get_document_by_path,
+    lock_library, upsert_document, LibraryDocument, LibraryDocumentInput, LibraryState,
+};
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::{mpsc, oneshot};
+
+/// Event emitted for every line of tool output during a conversion
+pub const CONVERSION_PROGRESS_EVENT: &str = "conversion-progress";
+
+/// Number of output lines kept in the conversion log
+const MAX_LOG_LINES: usize = 500;
+
+const EBOOK_CONVERT: &str = "ebook-convert";
+const PANDOC: &str = "pandoc";
+
+const EBOOK_CONVERT_INPUTS: &[&str] = &[
+    "epub", "mobi", "azw", "azw3", "fb2", "pdf", "docx", "odt", "rtf", "txt", "html", "htm",
+    "htmlz", "lit", "pdb", "cbz", "cbr",
+];
+const EBOOK_CONVERT_OUTPUTS: &[&str] = &[
+    "epub", "mobi", "azw3", "pdf", "docx", "fb2", "txt", "rtf", "htmlz", "pdb",
+];
+const PANDOC_INPUTS: &[&str] = &[
+    "md", "markdown", "txt", "html", "htm", "docx", "odt", "epub", "rst", "tex", "org", "fb2",
+];
+const PANDOC_OUTPUTS: &[&str] = &[
+    "epub", "html", "docx", "odt", "md", "markdown", "rst", "txt", "fb2",
+];
+
+/// Source formats pandoc handles better than Calibre (lightweight markup)
+const PANDOC_PREFERRED_INPUTS: &[&str] = &["md", "markdown", "rst", "tex", "org"];
+
+/// Pending cancellation handles of running conversions, keyed by job id
+pub type ConversionState = Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// An installed conversion tool
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionTool {
+    /// Tool name (`ebook-convert` or `pandoc`)
+    pub name: String,
+    pub path: String,
+    pub version: Option<String>,
+    pub input_formats: Vec<String>,
+    pub output_formats: Vec<String>,
+}
+
+/// Conversion request
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionRequest {
+    pub source_path: String,
+    /// Target format as a file extension (e.g. `epub`, `pdf`, `azw3`)
+    pub target_format: String,
+    /// Output directory (defaults to `app_data/converted`)
+    pub output_dir: Option<String>,
+    /// Caller-chosen job id, used to match progress events and cancel the job
+    pub job_id: Option<String>,
+    /// Whether to add the converted file to the library (default true)
+    pub import_to_library: Option<bool>,
+}
+
+/// Progress event payload
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionProgress {
+    pub job_id: String,
+    pub message: String,
+    /// Completion percentage when the tool reports one
+    pub percent: Option<f32>,
+}
+
+/// Result of a finished conversion
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionResult {
+    pub job_id: String,
+    pub tool: String,
+    pub output_path: String,
+    /// Library record of the converted file, if it was imported
+    pub document: Option<LibraryDocument>,
+    /// Captured tool output (last lines only)
+    pub log: Vec<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn to_strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+/// Directories searched for conversion tools besides `PATH`
+///
+/// GUI apps on macOS do not inherit the shell `PATH`, so the usual package
+/// manager and Calibre install locations are checked explicitly.
+fn extra_tool_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(target_os = "macos") {
+        dirs.push(PathBuf::from("/Applications/calibre.app/Contents/MacOS"));
+        dirs.push(PathBuf::from("/opt/homebrew/bin"));
+        dirs.push(PathBuf::from("/usr/local/bin"));
+    } else if cfg!(target_os = "windows") {
+        for base in ["ProgramFiles", "ProgramFiles(x86)"] {
+            if let Ok(dir) = std::env::var(base) {
+                dirs.push(Path::new(&dir).join("Calibre2"));
+                dirs.push(Path::new(&dir).join("Pandoc"));
+            }
+        }
+        if let Ok(dir) = std::env::var("LOCALAPPDATA") {
+            dirs.push(Path::new(&dir).join("Pandoc"));
+        }
+    } else {
+        dirs.push(PathBuf::from("/opt/calibre"));
+        dirs.push(PathBuf::from("/usr/local/bin"));
+    }
+    dirs
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Find an executable by name in the given directories
+pub fn find_executable(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    let file_name = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    dirs.iter()
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// Read the first line of `<tool> --version`
+fn tool_version(path: &Path) -> Option<String> {
+    let output = std::process::Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_string())
+}
+
+/// Detect installed conversion tools
+pub fn detect_tools() -> Vec<ConversionTool> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    dirs.extend(extra_tool_dirs());
+
+    [
+        (EBOOK_CONVERT, EBOOK_CONVERT_INPUTS, EBOOK_CONVERT_OUTPUTS),
+        (PANDOC, PANDOC_INPUTS, PANDOC_OUTPUTS),
+    ]
+    .into_iter()
+    .filter_map(|(name, inputs, outputs)| {
+        let path = find_executable(name, &dirs)?;
+        Some(ConversionTool {
+            name: name.to_string(),
+            version: tool_version(&path),
+            path: path.to_string_lossy().to_string(),
+            input_formats: to_strings(inputs),
+            output_formats: to_strings(outputs),
+        })
+    })
+    .collect()
+}
+
+/// Pick the tool to convert between two formats
+pub fn choose_tool<'a>(
+    tools: &'a [ConversionTool],
+    source_format: &str,
+    target_format: &str,
+) -> Option<&'a ConversionTool> {
+    let order = if PANDOC_PREFERRED_INPUTS.contains(&source_format) {
+        [PANDOC, EBOOK_CONVERT]
+    } else {
+        [EBOOK_CONVERT, PANDOC]
+    };
+    order.iter().find_map(|name| {
+        tools.iter().find(|tool| {
+            tool.name == *name
+                && tool.input_formats.iter().any(|f| f == source_format)
+                && tool.output_formats.iter().any(|f| f == target_format)
+        })
+    })
+}
+
+/// Build the command line arguments for a conversion
+pub fn conversion_args(tool: &str, source: &Path, output: &Path) -> Vec<String> {
+    let source_str = source.to_string_lossy().to_string();
+    let output_str = output.to_string_lossy().to_string();
+    if tool != PANDOC {
+        return vec![source_str, output_str];
+    }
+
+    let mut args = Vec::new();
+    if detect_document_format(source) == "txt" {
+        args.extend(["--from".to_string(), "markdown".to_string()]);
+    }
+    args.extend([source_str, "--output".to_string(), output_str]);
+    match detect_document_format(output).as_str() {
+        "txt" => args.extend(["--to".to_string(), "plain".to_string()]),
+        "md" => args.extend(["--to".to_string(), "markdown".to_string()]),
+        "html" => args.push("--standalone".to_string()),
+        _ => {}
+    }
+    args
+}
+
+/// Parse a leading percentage from a line of tool output
+///
+/// `ebook-convert` reports progress as lines like `34% Running transforms on e-book`.
+pub fn parse_progress_percent(line: &str) -> Option<f32> {
+    let (number, _) = line.trim_start().split_once('%')?;
+    let percent: f32 = number.trim().parse().ok()?;
+    (0.0..=100.0).contains(&percent).then_some(percent)
+}
+
+/// Normalize a target format, which becomes the output file extension, and
+/// reject anything but ASCII letters and digits
+pub fn check_target_format(format: &str) -> Result<String, AppError> {
+    let format = format.trim().to_lowercase();
+    if format.is_empty()
+        || !format
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid target format: {}",
+            format
+        )));
+    }
+    Ok(format)
+}
+
+/// Register a running job under its id, refusing an id already in use
+pub fn register_job(
+    jobs: &mut HashMap<String, oneshot::Sender<()>>,
+    job_id: &str,
+    cancel: oneshot::Sender<()>,
+) -> Result<(), AppError> {
+    if jobs.contains_key(job_id) {
+        return Err(AppError::InvalidInput(format!(
+            "Conversion {} is already running",
+            job_id
+        )));
+    }
+    jobs.insert(job_id.to_string(), cancel);
+    Ok(())
+}
+
+/// Pick an output path in a directory without overwriting existing files
+pub fn unique_output_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let candidate = dir.join(format!("{}.{}", stem, extension));
+    if !candidate.exists() {
+        return candidate;
+    }
+    (1..)
+        .map(|n| dir.join(format!("{} ({}).{}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap_or(candidate)
+}
+
+/// Get the default conversion output directory
+pub fn get_conversion_output_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(data_dir.join("converted"))
+}
+
+/// Forward lines of a child process stream into a channel
+fn forward_lines<R>(reader: R, tx: mpsc::UnboundedSender<String>)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tauri::async_runtime::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Add a converted file to the library, carrying over the source's title and author
+pub fn import_converted_document(
+    state: &LibraryState,
+    source: &Path,
+    output: &Path,
+    tool: &str,
+) -> Result<LibraryDocument, AppError> {
+    let fingerprint = fingerprint_file(output)?;
+    let conn = lock_library(state)?;
+    let original = get_document_by_path(&conn, &source.to_string_lossy())?;
+    let mut input = LibraryDocumentInput {
+        path: output.to_string_lossy().to_string(),
+        title: original.as_ref().and_then(|doc| doc.title.clone()),
+        author: original.as_ref().and_then(|doc| doc.author.clone()),
+        metadata: Some(serde_json::json!({
+            "convertedFrom": source.to_string_lossy(),
+            "convertedFromId": original.as_ref().map(|doc| doc.id.clone()),
+            "conversionTool": tool,
+        })),
+    };
+    enrich_document_input(&mut input);
+    upsert_document(&conn, &input, &fingerprint, chrono::Utc::now().timestamp())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// List the conversion tools installed on this system
+#[tauri::command]
+pub async fn detect_conversion_tools() -> Result<Vec<ConversionTool>, AppError> {
+    tauri::async_runtime::spawn_blocking(detect_tools)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Convert a document to another format and import the result into the library
+#[tauri::command]
+pub async fn convert_document(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, LibraryState>,
+    conversions: tauri::State<'_, ConversionState>,
+    request: ConversionRequest,
+) -> Result<ConversionResult, AppError> {
+    let source = PathBuf::from(&request.source_path);
+    if !source.is_file() {
+        return Err(AppError::NotFound(format!(
+            "File not found: {}",
+            request.source_path
+        )));
+    }
+    let source_format = detect_document_format(&source);
+    let target_format = check_target_format(&request.target_format)?;
+
+    let tools = tauri::async_runtime::spawn_blocking(detect_tools)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    if tools.is_empty() {
+        return Err(AppError::NotFound(
+            "No conversion tool found, install Calibre or pandoc".to_string(),
+        ));
+    }
+    let tool = choose_tool(&tools, &source_format, &target_format)
+        .ok_or_else(|| {
+            AppError::InvalidInput(format!(
+                "Converting '{}' to '{}' is not supported by the installed tools",
+                source_format, target_format
+            ))
+        })?
+        .clone();
+
+    let output_dir = match &request.output_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => get_conversion_output_dir(&app)?,
+    };
+    tokio::fs::create_dir_all(&output_dir).await?;
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "converted".to_string());
+    let output = unique_output_path(&output_dir, &stem, &target_format);
+
+    let job_id = request
+        .job_id
+        .clone()
+        .unwrap_or_else(|| format!("conv_{}", uuid::Uuid::new_v4()));
+    let (cancel_tx, mut cancel_rx) = oneshot::channel();
+    let conversions = conversions.inner().clone();
+    register_job(
+        &mut *conversions
+            .lock()
+            .map_err(|e| AppError::Internal(e.to_string()))?,
+        &job_id,
+        cancel_tx,
+    )?;
+
+    let spawned = Command::new(&tool.path)
+        .args(conversion_args(&tool.name, &source, &output))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            if let Ok(mut jobs) = conversions.lock() {
+                jobs.remove(&job_id);
+            }
+            return Err(AppError::Internal(format!(
+                "Failed to start {}: {}",
+                tool.name, e
+            )));
+        }
+    };
+    log::info!(
+        "Conversion {} started: {} -> {:?} with {}",
+        job_id,
+        request.source_path,
+        output,
+        tool.name
+    );
+
+    let (line_tx, mut line_rx) = mpsc::unbounded_channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(stdout, line_tx.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(stderr, line_tx);
+    }
+
+    let mut log_lines: VecDeque<String> = VecDeque::new();
+    let mut handle_line = |line: String| {
+        let progress = ConversionProgress {
+            job_id: job_id.clone(),
+            percent: parse_progress_percent(&line),
+            message: line.clone(),
+        };
+        emit_event(&app, &progress);
+        if log_lines.len() == MAX_LOG_LINES {
+            log_lines.pop_front();
+        }
+        log_lines.push_back(line);
+    };
+
+    // A completed oneshot may not be polled again, so stop watching it once
+    // its sender is gone
+    let mut cancel_open = true;
+    let outcome = loop {
+        tokio::select! {
+            Some(line) = line_rx.recv() => handle_line(line),
+            status = child.wait() => break status.map(Some),
+            cancelled = &mut cancel_rx, if cancel_open => {
+                if cancelled.is_ok() {
+                    let _ = child.kill().await;
+                    break Ok(None);
+                }
+                cancel_open = false;
+            }
+        }
+    };
+    // Output written just before exit may still be buffered in the channel
+    while let Ok(line) = line_rx.try_recv() {
+        handle_line(line);
+    }
+    if let Ok(mut jobs) = conversions.lock() {
+        jobs.remove(&job_id);
+    }
+
+    let status = match outcome {
+        Ok(Some(status)) => status,
+        Ok(None) => {
+            let _ = tokio::fs::remove_file(&output).await;
+            log::info!("Conversion {} cancelled", job_id);
+            return Err(AppError::Internal("Conversion cancelled".to_string()));
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&output).await;
+            return Err(e.into());
+        }
+    };
+    let log: Vec<String> = log_lines.into_iter().collect();
+    if !status.success() || !output.is_file() {
+        let _ = tokio::fs::remove_file(&output).await;
+        let detail = log
+            .iter()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .cloned()
+            .unwrap_or_default();
+        return Err(AppError::Internal(format!(
+            "{} failed ({}): {}",
+            tool.name, status, detail
+        )));
+    }
+
+    let document = if request.import_to_library.unwrap_or(true) {
+        let library = library.inner().clone();
+        let (source, output, tool_name) = (source.clone(), output.clone(), tool.name.clone());
+        let document = tauri::async_runtime::spawn_blocking(move || {
+            import_converted_document(&library, &source, &output, &tool_name)
+        })
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+        Some(document)
+    } else {
+        None
+    };
+
+    log::info!("Conversion {} finished: {:?}", job_id, output);
+    Ok(ConversionResult {
+        job_id,
+        tool: tool.name,
+        output_path: output.to_string_lossy().to_string(),
+        document,
+        log,
+    })
+}
+
+/// Cancel a running conversion, returning whether the job was found
+#[tauri::command]
+pub fn cancel_conversion(
+    state: tauri::State<'_, ConversionState>,
+    job_id: String,
+) -> Result<bool, AppError> {
+    let sender = state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .remove(&job_id);
+    Ok(sender.map(|tx| tx.send(()).is_ok()).unwrap_or(false))
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::library::LIBRARY_MIGRATIONS;
+    use crate::db;
+    use std::fs;
+
+    fn tool(name: &str, inputs: &[&str], outputs: &[&str]) -> ConversionTool {
+        ConversionTool {
+            name: name.to_string(),
+            path: format!("/usr/bin/{}", name),
+            version: None,
+            input_formats: to_strings(inputs),
+            output_formats: to_strings(outputs),
+        }
+    }
+
+    #[test]
+    fn choose_tool_prefers_pandoc_for_markup_sources() {
+        let tools = vec![
+            tool(EBOOK_CONVERT, EBOOK_CONVERT_INPUTS, EBOOK_CONVERT_OUTPUTS),
+            tool(PANDOC, PANDOC_INPUTS, PANDOC_OUTPUTS),
+        ];
+
+        assert_eq!(choose_tool(&tools, "md", "epub").unwrap().name, PANDOC);
+        assert_eq!(
+            choose_tool(&tools, "docx", "epub").unwrap().name,
+            EBOOK_CONVERT
+        );
+        assert_eq!(choose_tool(&tools, "epub", "html").unwrap().name, PANDOC);
+        assert!(choose_tool(&tools, "md", "mobi").is_none());
+        assert!(choose_tool(&tools[..1], "md", "epub").is_none());
+    }
+
+    #[test]
+    fn conversion_args_and_progress_parsing() {
+        let args = conversion_args(EBOOK_CONVERT, Path::new("/a/b.epub"), Path::new("/c/b.pdf"));
+        assert_eq!(args, vec!["/a/b.epub", "/c/b.pdf"]);
+
+        let args = conversion_args(
+            PANDOC,
+            Path::new("/a/notes.txt"),
+            Path::new("/c/notes.html"),
+        );
+        assert_eq!(
+            args,
+            vec![
+                "--from",
+                "markdown",
+                "/a/notes.txt",
+                "--output",
+                "/c/notes.html",
+                "--standalone"
+            ]
+        );
+
+        assert_eq!(
+            parse_progress_percent("34% Running transforms on e-book"),
+            Some(34.0)
+        );
+        assert_eq!(parse_progress_percent("  1.5% Converting"), Some(1.5));
+        assert_eq!(parse_progress_percent("Output saved to 100%"), None);
+        assert_eq!(parse_progress_percent("250% nope"), None);
+    }
+
+    #[test]
+    fn job_ids_and_target_formats_are_checked() {
+        assert_eq!(check_target_format(" EPUB ").unwrap(), "epub");
+        assert_eq!(check_target_format("fb2").unwrap(), "fb2");
+        for invalid in ["", "pdf/../x", "tar.gz", "m d", "épub"] {
+            assert!(check_target_format(invalid).is_err(), "{:?}", invalid);
+        }
+
+        let mut jobs = HashMap::new();
+        let (first, mut first_rx) = oneshot::channel();
+        register_job(&mut jobs, "job", first).unwrap();
+        let (second, _) = oneshot::channel();
+        assert!(register_job(&mut jobs, "job", second).is_err());
+        jobs.remove("job").unwrap().send(()).unwrap();
+        assert!(first_rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn find_executable_and_unique_output_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir
+            .path()
+            .join(format!("pandoc{}", std::env::consts::EXE_SUFFIX));
+        fs::write(&exe, "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let dirs = vec![dir.path().join("missing"), dir.path().to_path_buf()];
+        assert_eq!(find_executable("pandoc", &dirs), Some(exe));
+        assert_eq!(find_executable("ebook-convert", &dirs), None);
+
+        fs::write(dir.path().join("book.epub"), "x").unwrap();
+        fs::write(dir.path().join("book (1).epub"), "x").unwrap();
+        assert_eq!(
+            unique_output_path(dir.path(), "book", "epub"),
+            dir.path().join("book (2).epub")
+        );
+        assert_eq!(
+            unique_output_path(dir.path(), "book", "pdf"),
+            dir.path().join("book.pdf")
+        );
+    }
+
+    #[test]
+    fn import_converted_document_inherits_source_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("book.mobi");
+        let output = dir.path().join("book.epub");
+        fs::write(&source, "mobi").unwrap();
+        fs::write(&output, "epub").unwrap();
+
+        let state: LibraryState =
+            Arc::new(Mutex::new(db::open_in_memory(LIBRARY_MIGRATIONS).unwrap()));
+        let original = {
+            let conn = state.lock().unwrap();
+            upsert_document(
+                &conn,
+                &LibraryDocumentInput {
+                    path: source.to_string_lossy().to_string(),
+                    title: Some("Dune".to_string()),
+                    author: Some("Frank Herbert".to_string()),
+                    metadata: None,
+                },
+                &fingerprint_file(&source).unwrap(),
+                1,
+            )
+            .unwrap()
+        };
+
+        let converted = import_converted_document(&state, &source, &output, EBOOK_CONVERT).unwrap();
+        assert_ne!(converted.id, original.id);
+        assert_eq!(converted.format, "epub");
+        assert_eq!(converted.title.as_deref(), Some("Dune"));
+        assert_eq!(converted.author.as_deref(), Some("Frank Herbert"));
+        assert_eq!(converted.metadata["convertedFromId"], original.id);
+        assert_eq!(converted.metadata["conversionTool"], EBOOK_CONVERT);
+    }
+}
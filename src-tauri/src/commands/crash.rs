@@ -0,0 +1,243 @@
+//! Crash reports
+//!
+//! A panic hook writes a report (message, location, backtrace, app version,
+//! system and the last log entries) to `crash_reports/` in the app data
+//! directory. Reports are kept until dismissed, so the app can show them on
+//! the next launch and attach them to a diagnostics bundle. Background tasks
+//! started with `spawn_guarded` also log which task panicked.
+
+use crate::commands::logging::{recent_log_entries, LogQuery};
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+/// Crash report directory in the app data directory
+pub const CRASH_REPORTS_DIR: &str = "crash_reports";
+
+/// Log entries kept in a report
+const CRASH_LOG_ENTRIES: usize = 100;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub id: String,
+    pub created_at: i64,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    /// Name of the thread that panicked
+    pub thread: Option<String>,
+    pub message: String,
+    /// "file:line:column" of the panic
+    pub location: Option<String>,
+    pub backtrace: String,
+    /// Last log entries before the panic, oldest first
+    pub recent_logs: Vec<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn get_crash_reports_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let app_data = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(app_data.join(CRASH_REPORTS_DIR))
+}
+
+/// Path of a report, rejecting ids that are not report ids
+fn crash_report_path(dir: &Path, id: &str) -> Result<PathBuf, AppError> {
+    uuid::Uuid::parse_str(id)
+        .map_err(|_| AppError::InvalidInput(format!("Invalid crash report id: {}", id)))?;
+    Ok(dir.join(format!("{}.json", id)))
+}
+
+/// Text of a panic payload
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// Report a panic, with the last entries of the logs in `log_dir`
+pub fn build_crash_report(
+    message: String,
+    location: Option<String>,
+    app_version: &str,
+    log_dir: &Path,
+) -> CrashReport {
+    let recent_logs = recent_log_entries(log_dir, &LogQuery::default(), CRASH_LOG_ENTRIES)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| {
+            format!(
+                "[{}][{}][{}] {}",
+                entry.timestamp, entry.target, entry.level, entry.message
+            )
+        })
+        .collect();
+    CrashReport {
+        id: uuid::Uuid::new_v4().to_string(),
+        created_at: chrono::Utc::now().timestamp(),
+        app_version: app_version.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        thread: std::thread::current().name().map(str::to_string),
+        message,
+        location,
+        backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+        recent_logs,
+    }
+}
+
+pub fn write_crash_report(dir: &Path, report: &CrashReport) -> Result<PathBuf, AppError> {
+    fs::create_dir_all(dir)?;
+    let path = crash_report_path(dir, &report.id)?;
+    fs::write(&path, serde_json::to_string_pretty(report)?)?;
+    Ok(path)
+}
+
+/// Reports in `dir`, newest first; unreadable files are skipped
+pub fn load_crash_reports(dir: &Path) -> Vec<CrashReport> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut reports: Vec<CrashReport> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let content = fs::read_to_string(entry.path()).ok()?;
+            match serde_json::from_str(&content) {
+                Ok(report) => Some(report),
+                Err(e) => {
+                    log::warn!("Skipping crash report {:?}: {}", entry.path(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+    reports.sort_by_key(|report| std::cmp::Reverse(report.created_at));
+    reports
+}
+
+/// Write a crash report on every panic, then run the default hook
+pub fn install_panic_hook(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let reports_dir = get_crash_reports_dir(app)?;
+    let log_dir = app
+        .path()
+        .app_log_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    let app_version = app.package_info().version.to_string();
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = panic_message(info.payload());
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+        let report = build_crash_report(message, location, &app_version, &log_dir);
+        match write_crash_report(&reports_dir, &report) {
+            Ok(path) => log::error!("Panic: {} (crash report {:?})", report.message, path),
+            Err(e) => log::error!("Panic: {} (crash report failed: {})", report.message, e),
+        }
+        previous(info);
+    }));
+    Ok(())
+}
+
+/// Spawn a background task, logging its name if it panics
+pub fn spawn_guarded<F>(name: &'static str, task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = tauri::async_runtime::spawn(task);
+    tauri::async_runtime::spawn(async move {
+        if let Err(tauri::Error::JoinError(e)) = handle.await {
+            if e.is_panic() {
+                log::error!("Background task '{}' panicked", name);
+            }
+        }
+    });
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// List crash reports, newest first
+#[tauri::command]
+pub fn get_crash_reports(app: tauri::AppHandle) -> Result<Vec<CrashReport>, AppError> {
+    Ok(load_crash_reports(&get_crash_reports_dir(&app)?))
+}
+
+/// Delete a crash report once the user has seen it
+#[tauri::command]
+pub fn dismiss_crash_report(app: tauri::AppHandle, id: String) -> Result<(), AppError> {
+    let path = crash_report_path(&get_crash_reports_dir(&app)?, &id)?;
+    if !path.exists() {
+        return Err(AppError::NotFound(format!(
+            "Crash report not found: {}",
+            id
+        )));
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn reports_are_written_and_listed() {
+        let dir = tempdir().unwrap();
+        let logs = dir.path().join("logs");
+        fs::create_dir_all(&logs).unwrap();
+        fs::write(
+            logs.join("sast-readium.log"),
+            "[2026-01-31][10:00:00][app_lib::commands::rag][INFO] indexing\n",
+        )
+        .unwrap();
+
+        let reports_dir = dir.path().join(CRASH_REPORTS_DIR);
+        let mut older = build_crash_report("first".to_string(), None, "1.0.0", &logs);
+        older.created_at -= 60;
+        let newer = build_crash_report(
+            panic_message(&"index out of bounds"),
+            Some("src/lib.rs:1:1".to_string()),
+            "1.0.0",
+            &logs,
+        );
+        write_crash_report(&reports_dir, &older).unwrap();
+        write_crash_report(&reports_dir, &newer).unwrap();
+        fs::write(reports_dir.join("broken.json"), "{").unwrap();
+
+        let reports = load_crash_reports(&reports_dir);
+        assert_eq!(reports, vec![newer.clone(), older]);
+        assert_eq!(
+            newer.recent_logs,
+            vec!["[2026-01-31 10:00:00][app_lib::commands::rag][INFO] indexing"]
+        );
+        assert!(matches!(
+            crash_report_path(&reports_dir, "../settings"),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+}
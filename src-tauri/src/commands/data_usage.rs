@@ -0,0 +1,333 @@
+//! Disk usage of app data, and clearing of caches
+//!
+//! App data is split into categories by the files and directories each
+//! feature keeps. Whatever no category claims is counted as "other". Only
+//! categories that are rebuilt on demand (thumbnails, caches, logs) can be
+//! cleared; the others hold user data and have their own commands.
+
+use crate::commands::ai_images::GENERATED_IMAGES_DIR;
+use crate::commands::backup::get_backups_dir;
+use crate::commands::conversations::{CONVERSATIONS_DB_FILE, CONVERSATION_ATTACHMENTS_DIR};
+use crate::commands::library::LIBRARY_DB_FILE;
+use crate::commands::migrations::MIGRATION_BACKUPS_DIR;
+use crate::commands::thumbnails::get_thumbnail_cache_dir;
+use crate::error::AppError;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+use walkdir::WalkDir;
+
+/// Name of the category for app data no other category claims
+pub const OTHER_DATA_CATEGORY: &str = "other";
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Files and directories belonging to a category
+#[derive(Clone, Debug)]
+pub struct DataCategory {
+    pub name: &'static str,
+    pub paths: Vec<PathBuf>,
+    /// Whether the content is rebuilt on demand and can be cleared
+    pub clearable: bool,
+}
+
+/// Disk usage of one category
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataUsageCategory {
+    pub category: String,
+    pub bytes: u64,
+    pub files: usize,
+    pub clearable: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppDataUsage {
+    pub categories: Vec<DataUsageCategory>,
+    pub total_bytes: u64,
+    /// Absolute path of the app data directory
+    pub path: String,
+}
+
+/// What clearing a cache freed
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClearedCache {
+    pub category: String,
+    pub removed_files: usize,
+    pub freed_bytes: u64,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// A database file with its write-ahead log and shared memory files
+fn database_files(dir: &Path, file: &str) -> Vec<PathBuf> {
+    ["", "-wal", "-shm"]
+        .iter()
+        .map(|suffix| dir.join(format!("{}{}", file, suffix)))
+        .collect()
+}
+
+fn get_data_categories(app: &tauri::AppHandle) -> Result<(PathBuf, Vec<DataCategory>), AppError> {
+    let path = app.path();
+    let not_found = |e: tauri::Error| AppError::NotFound(e.to_string());
+    let app_data = path.app_data_dir().map_err(not_found)?;
+    let categories = data_categories(
+        &app_data,
+        get_thumbnail_cache_dir(app)?,
+        path.app_cache_dir().map_err(not_found)?,
+        get_backups_dir(app)?,
+        path.app_log_dir().map_err(not_found)?,
+    );
+    Ok((app_data, categories))
+}
+
+/// The categories of app data, given where each kind of data lives
+pub fn data_categories(
+    app_data: &Path,
+    thumbnails: PathBuf,
+    cache: PathBuf,
+    backups: PathBuf,
+    logs: PathBuf,
+) -> Vec<DataCategory> {
+    let mut conversations = database_files(app_data, CONVERSATIONS_DB_FILE);
+    conversations.push(app_data.join(CONVERSATION_ATTACHMENTS_DIR));
+    conversations.push(app_data.join(GENERATED_IMAGES_DIR));
+    vec![
+        DataCategory {
+            name: "library",
+            paths: database_files(app_data, LIBRARY_DB_FILE),
+            clearable: false,
+        },
+        DataCategory {
+            name: "thumbnails",
+            paths: vec![thumbnails],
+            clearable: true,
+        },
+        DataCategory {
+            name: "caches",
+            paths: vec![cache],
+            clearable: true,
+        },
+        DataCategory {
+            name: "backups",
+            paths: vec![backups, app_data.join(MIGRATION_BACKUPS_DIR)],
+            clearable: false,
+        },
+        DataCategory {
+            name: "logs",
+            paths: vec![logs],
+            clearable: true,
+        },
+        DataCategory {
+            name: "conversations",
+            paths: conversations,
+            clearable: false,
+        },
+    ]
+}
+
+/// Files below `path` (or `path` itself), skipping those under `excluded`
+fn files_under<'a>(
+    path: &Path,
+    excluded: &'a [PathBuf],
+) -> impl Iterator<Item = (PathBuf, u64)> + 'a {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_entry(move |entry| !excluded.iter().any(|e| e.as_path() == entry.path()))
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let size = entry.metadata().ok()?.len();
+            Some((entry.into_path(), size))
+        })
+}
+
+/// Measure each category, and what is left of `app_data` as "other"
+///
+/// Categories may live inside one another (logs inside app data on Linux);
+/// each file is counted once, for the most specific category.
+pub fn measure_data_usage(app_data: &Path, categories: &[DataCategory]) -> Vec<DataUsageCategory> {
+    let all_paths: Vec<PathBuf> = categories
+        .iter()
+        .flat_map(|c| c.paths.iter().cloned())
+        .collect();
+    let mut usage: Vec<DataUsageCategory> = categories
+        .iter()
+        .map(|category| {
+            let nested: Vec<PathBuf> = all_paths
+                .iter()
+                .filter(|other| {
+                    !category.paths.contains(other)
+                        && category.paths.iter().any(|p| other.starts_with(p))
+                })
+                .cloned()
+                .collect();
+            let (mut bytes, mut files) = (0, 0);
+            for path in &category.paths {
+                for (_, size) in files_under(path, &nested) {
+                    bytes += size;
+                    files += 1;
+                }
+            }
+            DataUsageCategory {
+                category: category.name.to_string(),
+                bytes,
+                files,
+                clearable: category.clearable,
+            }
+        })
+        .collect();
+
+    let (mut bytes, mut files) = (0, 0);
+    for (_, size) in files_under(app_data, &all_paths) {
+        bytes += size;
+        files += 1;
+    }
+    usage.push(DataUsageCategory {
+        category: OTHER_DATA_CATEGORY.to_string(),
+        bytes,
+        files,
+        clearable: false,
+    });
+    usage
+}
+
+/// Delete the files of a clearable category, keeping its directories
+///
+/// Files that cannot be removed, such as the log being written on Windows,
+/// are left in place.
+pub fn clear_category(category: &DataCategory) -> Result<ClearedCache, AppError> {
+    if !category.clearable {
+        return Err(AppError::InvalidInput(format!(
+            "Category '{}' cannot be cleared",
+            category.name
+        )));
+    }
+    let mut cleared = ClearedCache {
+        category: category.name.to_string(),
+        removed_files: 0,
+        freed_bytes: 0,
+    };
+    for path in &category.paths {
+        for (file, size) in files_under(path, &[]) {
+            if fs::remove_file(&file).is_ok() {
+                cleared.removed_files += 1;
+                cleared.freed_bytes += size;
+            }
+        }
+    }
+    Ok(cleared)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the disk usage of app data per category
+#[tauri::command]
+pub async fn get_app_data_usage(app: tauri::AppHandle) -> Result<AppDataUsage, AppError> {
+    let (app_data, categories) = get_data_categories(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let categories = measure_data_usage(&app_data, &categories);
+        Ok(AppDataUsage {
+            total_bytes: categories.iter().map(|c| c.bytes).sum(),
+            categories,
+            path: app_data.to_string_lossy().to_string(),
+        })
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Delete the content of a cache category (thumbnails, caches or logs)
+#[tauri::command]
+pub async fn clear_cache(
+    app: tauri::AppHandle,
+    category: String,
+) -> Result<ClearedCache, AppError> {
+    let (_, categories) = get_data_categories(&app)?;
+    let category = categories
+        .into_iter()
+        .find(|c| c.name == category)
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown data category: {}", category)))?;
+    let cleared = tauri::async_runtime::spawn_blocking(move || clear_category(&category))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+    log::info!(
+        "Cleared {}: {} files, {} bytes",
+        cleared.category,
+        cleared.removed_files,
+        cleared.freed_bytes
+    );
+    Ok(cleared)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write(path: &Path, size: usize) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0u8; size]).unwrap();
+    }
+
+    #[test]
+    fn usage_counts_each_file_once_and_caches_can_be_cleared() {
+        let dir = tempdir().unwrap();
+        let app_data = dir.path().join("data");
+        let cache = dir.path().join("cache");
+        write(&app_data.join(LIBRARY_DB_FILE), 100);
+        write(&app_data.join(format!("{}-wal", LIBRARY_DB_FILE)), 10);
+        write(&app_data.join("thumbnails/a_320.jpg"), 20);
+        write(&app_data.join("backups/20260101-000000/manifest.json"), 30);
+        write(&app_data.join("logs/app.log"), 40);
+        write(&app_data.join("dictionaries/en.ifo"), 5);
+        write(&cache.join("tts/clip.mp3"), 50);
+
+        let categories = data_categories(
+            &app_data,
+            app_data.join("thumbnails"),
+            cache,
+            app_data.join("backups"),
+            app_data.join("logs"),
+        );
+        let usage = measure_data_usage(&app_data, &categories);
+        let bytes: Vec<(&str, u64, usize)> = usage
+            .iter()
+            .map(|c| (c.category.as_str(), c.bytes, c.files))
+            .collect();
+        assert_eq!(
+            bytes,
+            vec![
+                ("library", 110, 2),
+                ("thumbnails", 20, 1),
+                ("caches", 50, 1),
+                ("backups", 30, 1),
+                ("logs", 40, 1),
+                ("conversations", 0, 0),
+                (OTHER_DATA_CATEGORY, 5, 1),
+            ]
+        );
+
+        let cleared = clear_category(&categories[2]).unwrap();
+        assert_eq!((cleared.removed_files, cleared.freed_bytes), (1, 50));
+        assert!(categories[2].paths[0].join("tts").is_dir());
+        assert!(matches!(
+            clear_category(&categories[0]),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(app_data.join(LIBRARY_DB_FILE).exists());
+    }
+}
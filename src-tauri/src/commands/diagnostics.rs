@@ -0,0 +1,494 @@
+//! Diagnostics bundle for bug reports
+//!
+//! The bundle is a zip holding `diagnostics.json` (app version, system,
+//! locale, MCP servers, network and power state, data store versions), the
+//! recent logs, and the config files. Secrets are stripped from the config:
+//! values of keys that look secret, MCP server environment variables and
+//! headers, and secret-looking command line arguments. The home directory is
+//! replaced with `~` everywhere, so the bundle does not carry the user name.
+//! Crash reports the user picks are added under `crash_reports/`.
+
+use crate::commands::appearance::ThemeState;
+use crate::commands::backup::SETTINGS_FILE;
+use crate::commands::crash::{load_crash_reports, CrashReport, CRASH_REPORTS_DIR};
+use crate::commands::logging::LOG_SETTINGS_FILE;
+use crate::commands::mcp::{
+    get_mcp_server_statuses, mcp_get_connected_clients, MCPClientStateHandle, MCPState,
+    MCP_SERVERS_FILE,
+};
+use crate::commands::migrations::{get_data_migration_status, MIGRATION_REGISTRY_FILE};
+use crate::commands::network::NetworkState;
+use crate::commands::power::PowerState;
+use crate::commands::system::{get_app_runtime_info, get_locale_info, get_system_info};
+use crate::error::AppError;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::Manager;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+pub const DIAGNOSTICS_REPORT_ENTRY: &str = "diagnostics.json";
+const DIAGNOSTICS_LOGS_PREFIX: &str = "logs/";
+const DIAGNOSTICS_CONFIG_PREFIX: &str = "config/";
+const DIAGNOSTICS_CRASH_PREFIX: &str = "crash_reports/";
+const DIAGNOSTICS_FORMAT_VERSION: u32 = 1;
+
+/// Config files from the app data directory included in the bundle
+const DIAGNOSTICS_CONFIG_FILES: &[&str] = &[
+    SETTINGS_FILE,
+    MCP_SERVERS_FILE,
+    MIGRATION_REGISTRY_FILE,
+    LOG_SETTINGS_FILE,
+];
+
+/// Bytes kept from the end of each log file
+const MAX_LOG_BYTES: u64 = 2 * 1024 * 1024;
+/// Logs last written before this are left out
+const MAX_LOG_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Replacement for stripped secrets
+pub const REDACTED: &str = "[redacted]";
+
+/// Parts of key and argument names marking a secret
+const SECRET_MARKERS: &[&str] = &[
+    "key",
+    "token",
+    "secret",
+    "password",
+    "passphrase",
+    "auth",
+    "cookie",
+    "credential",
+];
+
+/// Maps whose values are all stripped, whatever their names
+const SECRET_MAPS: &[&str] = &["env", "headers"];
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// What went into an exported bundle
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsExport {
+    pub path: String,
+    /// Entries of the archive
+    pub files: Vec<String>,
+    pub size: u64,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn zip_err(e: zip::result::ZipError) -> AppError {
+    AppError::Internal(format!("Failed to write diagnostics archive: {}", e))
+}
+
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_MARKERS.iter().any(|marker| name.contains(marker))
+}
+
+/// Replace the home directory in `text` with `~`
+pub fn scrub_home(text: &str, home: Option<&Path>) -> String {
+    let Some(home) = home.map(|home| home.to_string_lossy()) else {
+        return text.to_string();
+    };
+    let home = home.trim_end_matches(['/', '\\']);
+    if home.len() < 2 {
+        return text.to_string();
+    }
+    text.replace(home, "~")
+}
+
+/// Strip secrets from command line arguments: `--token=x` and `--token x`
+fn sanitize_args(args: &mut [Value]) {
+    let mut secret_follows = false;
+    for arg in args.iter_mut() {
+        let Value::String(text) = arg else {
+            secret_follows = false;
+            continue;
+        };
+        if secret_follows && !text.starts_with('-') {
+            *text = REDACTED.to_string();
+            secret_follows = false;
+            continue;
+        }
+        secret_follows = false;
+        if let Some((name, _)) = text.split_once('=') {
+            if is_secret_name(name) {
+                *text = format!("{}={}", name, REDACTED);
+            }
+        } else if text.starts_with('-') && is_secret_name(text) {
+            secret_follows = true;
+        }
+    }
+}
+
+/// Strip secrets and the home directory from a JSON value
+pub fn sanitize_value(value: &mut Value, home: Option<&Path>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let lower = key.to_ascii_lowercase();
+                if SECRET_MAPS.contains(&lower.as_str()) {
+                    if let Value::Object(entries) = value {
+                        for entry in entries.values_mut() {
+                            *entry = Value::String(REDACTED.to_string());
+                        }
+                        continue;
+                    }
+                }
+                if lower == "args" {
+                    if let Value::Array(args) = value {
+                        sanitize_args(args);
+                    }
+                }
+                let has_content = value.as_str().is_some_and(|text| !text.is_empty());
+                if is_secret_name(key) && has_content {
+                    *value = Value::String(REDACTED.to_string());
+                    continue;
+                }
+                sanitize_value(value, home);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                sanitize_value(item, home);
+            }
+        }
+        Value::String(text) => *text = scrub_home(text, home),
+        _ => {}
+    }
+}
+
+/// The end of a log file, starting at a line boundary
+fn read_log_tail(path: &Path) -> Result<String, AppError> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let truncated = len > MAX_LOG_BYTES;
+    if truncated {
+        file.seek(SeekFrom::Start(len - MAX_LOG_BYTES))?;
+    }
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes);
+    Ok(match text.split_once('\n') {
+        Some((_, rest)) if truncated => rest.to_string(),
+        _ => text.to_string(),
+    })
+}
+
+/// Log files last written after `since`, newest first
+fn recent_logs(log_dir: &Path, since: SystemTime) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(log_dir) else {
+        return Vec::new();
+    };
+    let mut logs: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata.modified().ok()?;
+            (metadata.is_file() && modified >= since).then(|| (modified, entry.path()))
+        })
+        .collect();
+    logs.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    logs.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Write the diagnostics bundle to `path`
+pub fn write_diagnostics_archive(
+    path: &Path,
+    report: &Value,
+    app_data: &Path,
+    log_dir: &Path,
+    crash_reports: &[CrashReport],
+    home: Option<&Path>,
+) -> Result<DiagnosticsExport, AppError> {
+    let temp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let written = (|| {
+        let mut zip = ZipWriter::new(BufWriter::new(File::create(&temp_path)?));
+        let options = SimpleFileOptions::default();
+        let mut files = Vec::new();
+        let mut add = |zip: &mut ZipWriter<BufWriter<File>>, name: String, content: &str| {
+            zip.start_file(name.as_str(), options).map_err(zip_err)?;
+            zip.write_all(content.as_bytes())?;
+            files.push(name);
+            Ok::<_, AppError>(())
+        };
+
+        let mut report = report.clone();
+        sanitize_value(&mut report, home);
+        add(
+            &mut zip,
+            DIAGNOSTICS_REPORT_ENTRY.to_string(),
+            &serde_json::to_string_pretty(&report)?,
+        )?;
+
+        for file in DIAGNOSTICS_CONFIG_FILES {
+            let Ok(content) = fs::read_to_string(app_data.join(file)) else {
+                continue;
+            };
+            let content = match serde_json::from_str::<Value>(&content) {
+                Ok(mut value) => {
+                    sanitize_value(&mut value, home);
+                    serde_json::to_string_pretty(&value)?
+                }
+                // Unparsable config is the bug; keep what can be shown safely
+                Err(e) => format!("Unreadable JSON: {}", e),
+            };
+            add(
+                &mut zip,
+                format!("{}{}", DIAGNOSTICS_CONFIG_PREFIX, file),
+                &content,
+            )?;
+        }
+
+        let since = SystemTime::now()
+            .checked_sub(MAX_LOG_AGE)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        for log in recent_logs(log_dir, since) {
+            let name = log.file_name().unwrap_or_default().to_string_lossy();
+            match read_log_tail(&log) {
+                Ok(content) => add(
+                    &mut zip,
+                    format!("{}{}", DIAGNOSTICS_LOGS_PREFIX, name),
+                    &scrub_home(&content, home),
+                )?,
+                Err(e) => log::warn!("Skipping log {} in diagnostics: {}", name, e),
+            }
+        }
+
+        for crash in crash_reports {
+            let mut value = serde_json::to_value(crash)?;
+            sanitize_value(&mut value, home);
+            add(
+                &mut zip,
+                format!("{}{}.json", DIAGNOSTICS_CRASH_PREFIX, crash.id),
+                &serde_json::to_string_pretty(&value)?,
+            )?;
+        }
+
+        zip.finish().map_err(zip_err)?.flush()?;
+        fs::rename(&temp_path, path)?;
+        Ok(DiagnosticsExport {
+            path: path.to_string_lossy().to_string(),
+            files,
+            size: fs::metadata(path)?.len(),
+        })
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written
+}
+
+fn to_json<T: Serialize>(value: Result<T, AppError>) -> Result<Value, AppError> {
+    Ok(serde_json::to_value(value?)?)
+}
+
+/// Collect the state of the app for the report
+async fn collect_report(app: &tauri::AppHandle) -> Value {
+    let mut errors = Vec::new();
+    let mut section = |name: &str, result: Result<Value, AppError>| {
+        result.unwrap_or_else(|e| {
+            errors.push(format!("{}: {}", name, e));
+            Value::Null
+        })
+    };
+    let mcp_servers = section(
+        "mcpServers",
+        to_json(get_mcp_server_statuses(app.state::<MCPState>())),
+    );
+    let mcp_clients = section(
+        "mcpClients",
+        to_json(mcp_get_connected_clients(app.state::<MCPClientStateHandle>()).await),
+    );
+    let data_migrations = section(
+        "dataMigrations",
+        to_json(get_data_migration_status(app.clone())),
+    );
+    let network = app
+        .state::<NetworkState>()
+        .lock()
+        .map(|state| state.clone())
+        .unwrap_or_default();
+    let theme = app
+        .state::<ThemeState>()
+        .lock()
+        .map(|state| state.clone())
+        .unwrap_or_default();
+    let power = section(
+        "power",
+        to_json(
+            app.state::<PowerState>()
+                .lock()
+                .map(|status| status.clone())
+                .map_err(|e| AppError::Internal(e.to_string())),
+        ),
+    );
+
+    serde_json::json!({
+        "version": DIAGNOSTICS_FORMAT_VERSION,
+        "createdAt": chrono::Utc::now().timestamp(),
+        "appVersion": app.package_info().version.to_string(),
+        "runtime": get_app_runtime_info(),
+        "system": get_system_info(),
+        "locale": get_locale_info(),
+        "mcpServers": mcp_servers,
+        "mcpClients": mcp_clients,
+        "dataMigrations": data_migrations,
+        "networkState": network,
+        "systemTheme": theme,
+        "power": power,
+        "errors": errors,
+    })
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Export logs, sanitized config and system information to a zip for bug
+/// reports, with the crash reports whose ids are given
+#[tauri::command]
+pub async fn export_diagnostics(
+    app: tauri::AppHandle,
+    path: String,
+    crash_report_ids: Option<Vec<String>>,
+) -> Result<DiagnosticsExport, AppError> {
+    let paths = app.path();
+    let not_found = |e: tauri::Error| AppError::NotFound(e.to_string());
+    let app_data = paths.app_data_dir().map_err(not_found)?;
+    let log_dir = paths.app_log_dir().map_err(not_found)?;
+    let report = collect_report(&app).await;
+    let export = tauri::async_runtime::spawn_blocking(move || {
+        let ids = crash_report_ids.unwrap_or_default();
+        let crash_reports: Vec<CrashReport> = load_crash_reports(&app_data.join(CRASH_REPORTS_DIR))
+            .into_iter()
+            .filter(|crash| ids.contains(&crash.id))
+            .collect();
+        write_diagnostics_archive(
+            Path::new(&path),
+            &report,
+            &app_data,
+            &log_dir,
+            &crash_reports,
+            dirs::home_dir().as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    log::info!(
+        "Diagnostics exported: {} files, {} bytes",
+        export.files.len(),
+        export.size
+    );
+    Ok(export)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::crash::build_crash_report;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn bundle_strips_secrets_and_home() {
+        let dir = tempdir().unwrap();
+        let home = dir.path().join("home").join("alice");
+        let app_data = dir.path().join("data");
+        let logs = dir.path().join("logs");
+        fs::create_dir_all(&app_data).unwrap();
+        fs::create_dir_all(&logs).unwrap();
+        fs::write(
+            app_data.join(SETTINGS_FILE),
+            r#"{"theme":"dark","apiKey":"sk-123","tokenLimit":4096,"useAuth":true}"#,
+        )
+        .unwrap();
+        let mcp = serde_json::json!({"servers": [{
+            "name": "github",
+            "command": home.join("bin/server").to_string_lossy(),
+            "args": ["--port", "3000", "--api-key", "abc", "--token=xyz"],
+            "env": {"GITHUB_PERSONAL_ACCESS_TOKEN": "ghp_1", "DEBUG": "1"},
+            "headers": {"X-Api": "v"}
+        }]});
+        fs::write(app_data.join(MCP_SERVERS_FILE), mcp.to_string()).unwrap();
+        fs::write(
+            logs.join("app.log"),
+            format!("opened {}/book.pdf\n", home.display()),
+        )
+        .unwrap();
+
+        let archive = dir.path().join("diagnostics.zip");
+        let report =
+            serde_json::json!({"runtime": {"exePath": home.join("app").to_string_lossy()}});
+        let mut crash = build_crash_report("boom".to_string(), None, "1.0.0", &logs);
+        crash.location = Some(home.join("src/main.rs:1:1").to_string_lossy().to_string());
+        let export = write_diagnostics_archive(
+            &archive,
+            &report,
+            &app_data,
+            &logs,
+            std::slice::from_ref(&crash),
+            Some(&home),
+        )
+        .unwrap();
+        let crash_entry = format!("crash_reports/{}.json", crash.id);
+        assert_eq!(
+            export.files,
+            vec![
+                "diagnostics.json",
+                "config/settings.json",
+                "config/mcp_servers.json",
+                "logs/app.log",
+                crash_entry.as_str()
+            ]
+        );
+
+        let mut zip = ZipArchive::new(File::open(&archive).unwrap()).unwrap();
+        let mut read = |name: &str| {
+            let mut text = String::new();
+            zip.by_name(name)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            text
+        };
+        let settings: Value = serde_json::from_str(&read("config/settings.json")).unwrap();
+        assert_eq!(settings["theme"], "dark");
+        assert_eq!(settings["apiKey"], REDACTED);
+        assert_eq!(settings["tokenLimit"], 4096);
+        assert_eq!(settings["useAuth"], true);
+        let mcp: Value = serde_json::from_str(&read("config/mcp_servers.json")).unwrap();
+        let server = &mcp["servers"][0];
+        assert_eq!(server["command"], "~/bin/server");
+        assert_eq!(
+            server["args"],
+            serde_json::json!([
+                "--port",
+                "3000",
+                "--api-key",
+                REDACTED,
+                "--token=[redacted]"
+            ])
+        );
+        assert_eq!(server["env"]["DEBUG"], REDACTED);
+        assert_eq!(server["headers"]["X-Api"], REDACTED);
+        assert_eq!(read("logs/app.log"), "opened ~/book.pdf\n");
+        assert!(read(DIAGNOSTICS_REPORT_ENTRY).contains("\"~/app\""));
+        let crash: Value = serde_json::from_str(&read(&crash_entry)).unwrap();
+        assert_eq!(crash["location"], "~/src/main.rs:1:1");
+    }
+}
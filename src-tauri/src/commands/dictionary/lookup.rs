@@ -0,0 +1,671 @@
+//! Word lookup across local dictionaries with an online fallback
+
+use super::mdx::MdxDictionary;
+use super::stardict::{StarDict, StarDictField};
+use super::types::{DictionaryEntry, DictionaryInfo, WordLookupResult, WordMeaning, WordSense};
+use crate::commands::epub::xhtml_to_text;
+use crate::error::AppError;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tauri::Manager;
+use url::Url;
+use walkdir::WalkDir;
+
+const ONLINE_DICTIONARY_URL: &str = "https://api.dictionaryapi.dev/api/v2/entries";
+const ONLINE_DICTIONARY_NAME: &str = "Free Dictionary API";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most bytes read or inflated from a dictionary file at once; larger sizes
+/// in its headers mean the file is corrupt
+pub const MAX_DICTIONARY_READ: u64 = 256 * 1024 * 1024;
+
+/// Maximum number of `@@@LINK=` redirects followed for one MDX entry
+const MAX_LINK_DEPTH: usize = 3;
+
+/// Part-of-speech markers that start a new meaning in plain text definitions
+const PART_OF_SPEECH_MARKERS: &[&str] = &[
+    "n.", "v.", "vt.", "vi.", "adj.", "a.", "adv.", "ad.", "prep.", "conj.", "pron.", "int.",
+    "interj.", "num.", "art.", "aux.", "abbr.",
+];
+
+enum DictionaryKind {
+    StarDict(StarDict),
+    Mdx(MdxDictionary),
+}
+
+/// A dictionary loaded from the dictionaries directory
+pub struct LocalDictionary {
+    info: DictionaryInfo,
+    kind: DictionaryKind,
+}
+
+/// Dictionaries loaded from disk, reloaded when the directory changes
+#[derive(Default)]
+pub struct DictionaryRegistry {
+    signature: Vec<(PathBuf, Option<SystemTime>)>,
+    dictionaries: Vec<LocalDictionary>,
+}
+
+/// Managed state holding the loaded dictionaries
+pub type DictionaryState = Arc<Mutex<DictionaryRegistry>>;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Read `len` bytes of a dictionary file at `offset`, rejecting sizes past
+/// the end of the file or above `MAX_DICTIONARY_READ` before allocating
+pub fn read_at(file: &mut File, offset: u64, len: u64) -> Result<Vec<u8>, AppError> {
+    let file_len = file.metadata()?.len();
+    if len > MAX_DICTIONARY_READ || offset.checked_add(len).map_or(true, |end| end > file_len) {
+        return Err(AppError::InvalidInput(format!(
+            "Dictionary data at {} ({} bytes) lies outside the file",
+            offset, len
+        )));
+    }
+    let mut buffer = Vec::new();
+    buffer
+        .try_reserve_exact(len as usize)
+        .map_err(|e| AppError::InvalidInput(format!("Dictionary data too large: {}", e)))?;
+    buffer.resize(len as usize, 0);
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Read a decompressing reader to its end, failing once it yields more than
+/// `MAX_DICTIONARY_READ` bytes
+pub fn read_inflated(reader: impl Read) -> Result<Vec<u8>, AppError> {
+    let mut output = Vec::new();
+    reader
+        .take(MAX_DICTIONARY_READ + 1)
+        .read_to_end(&mut output)
+        .map_err(|e| AppError::InvalidInput(format!("Corrupt dictionary data: {}", e)))?;
+    if output.len() as u64 > MAX_DICTIONARY_READ {
+        return Err(AppError::InvalidInput(
+            "Dictionary data inflates beyond the size limit".to_string(),
+        ));
+    }
+    Ok(output)
+}
+
+/// Normalize a headword for case-insensitive matching
+pub fn normalize_headword(word: &str) -> String {
+    word.trim().to_lowercase()
+}
+
+/// Candidate dictionary forms of a word as tapped in a text
+///
+/// Surrounding punctuation is stripped; for English, common inflections are
+/// reduced so "studies" also finds "study".
+pub fn lookup_forms(word: &str, lang: Option<&str>) -> Vec<String> {
+    let cleaned = word
+        .trim()
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .replace('\u{2019}', "'");
+    let base = normalize_headword(&cleaned);
+    let mut forms = vec![base.clone()];
+    if base.is_empty() {
+        return Vec::new();
+    }
+
+    let is_english = lang.map_or(true, |lang| lang.to_lowercase().starts_with("en"));
+    if is_english {
+        let stripped = base.strip_suffix("'s").unwrap_or(&base);
+        let mut push = |form: String| {
+            if form.chars().count() > 1 && !forms.contains(&form) {
+                forms.push(form);
+            }
+        };
+        push(stripped.to_string());
+        if let Some(stem) = stripped.strip_suffix("ies") {
+            push(format!("{}y", stem));
+        }
+        if let Some(stem) = stripped.strip_suffix("ied") {
+            push(format!("{}y", stem));
+        }
+        for suffix in ["es", "s", "ed", "ing", "er", "est", "ly"] {
+            if let Some(stem) = stripped.strip_suffix(suffix) {
+                push(stem.to_string());
+                // "taking" -> "take", "hoped" -> "hope"
+                if matches!(suffix, "ed" | "ing" | "er" | "est") {
+                    push(format!("{}e", stem));
+                }
+                // "running" -> "run"
+                let chars: Vec<char> = stem.chars().collect();
+                if chars.len() > 2 && chars[chars.len() - 1] == chars[chars.len() - 2] {
+                    push(chars[..chars.len() - 1].iter().collect());
+                }
+            }
+        }
+    }
+    forms
+}
+
+fn is_part_of_speech(token: &str) -> bool {
+    PART_OF_SPEECH_MARKERS
+        .iter()
+        .any(|marker| token.eq_ignore_ascii_case(marker))
+}
+
+/// Structure a plain text definition into meanings
+///
+/// Every line is a sense; a leading part-of-speech marker such as `n.` or
+/// `adj.` starts a new meaning.
+pub fn meanings_from_text(text: &str) -> Vec<WordMeaning> {
+    let mut meanings: Vec<WordMeaning> = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (part_of_speech, definition) = match line.split_once(char::is_whitespace) {
+            Some((token, rest)) if is_part_of_speech(token) => (Some(token), rest.trim()),
+            _ if is_part_of_speech(line) => (Some(line), ""),
+            _ => (None, line),
+        };
+        if part_of_speech.is_some() || meanings.is_empty() {
+            meanings.push(WordMeaning {
+                part_of_speech: part_of_speech.map(str::to_string),
+                senses: Vec::new(),
+            });
+        }
+        if !definition.is_empty() {
+            if let Some(meaning) = meanings.last_mut() {
+                meaning.senses.push(WordSense {
+                    definition: definition.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    meanings.retain(|meaning| !meaning.senses.is_empty());
+    meanings
+}
+
+fn html_to_text(html: &str) -> String {
+    xhtml_to_text(&format!("<div>{}</div>", html.replace("<br>", "<br/>"))).text
+}
+
+fn stardict_entry(
+    dictionary: &str,
+    headword: String,
+    fields: Vec<StarDictField>,
+) -> DictionaryEntry {
+    let mut entry = DictionaryEntry {
+        headword,
+        dictionary: dictionary.to_string(),
+        source: "local".to_string(),
+        ..Default::default()
+    };
+    let mut text = Vec::new();
+    for field in fields {
+        match field.kind {
+            't' => entry.phonetic = Some(field.text),
+            // HTML, XDXF and Pango markup
+            'h' | 'x' | 'g' => {
+                text.push(html_to_text(&field.text));
+                entry.html.get_or_insert(field.text);
+            }
+            'm' | 'l' | 'y' | 'k' | 'w' => text.push(field.text),
+            _ => {}
+        }
+    }
+    entry.meanings = meanings_from_text(&text.join("\n"));
+    entry
+}
+
+fn mdx_entry(dictionary: &str, headword: String, html: String) -> DictionaryEntry {
+    DictionaryEntry {
+        headword,
+        dictionary: dictionary.to_string(),
+        source: "local".to_string(),
+        meanings: meanings_from_text(&html_to_text(&html)),
+        html: Some(html),
+        ..Default::default()
+    }
+}
+
+/// Whether a dictionary applies to the requested language
+fn matches_language(dictionary: Option<&str>, lang: Option<&str>) -> bool {
+    let primary = |tag: &str| {
+        tag.split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+    };
+    match (dictionary, lang) {
+        (Some(dictionary), Some(lang)) => primary(dictionary) == primary(lang),
+        _ => true,
+    }
+}
+
+/// Language of a dictionary from its top-level directory, e.g. `en/oxford/`
+fn language_from_path(relative: &Path) -> Option<String> {
+    let mut components = relative.components();
+    let first = components.next()?.as_os_str().to_str()?;
+    components.next()?;
+    let primary = first.split(['-', '_']).next()?;
+    let looks_like_language = (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && first.len() <= 8;
+    looks_like_language.then(|| first.to_string())
+}
+
+/// Find `.ifo` and `.mdx` files in the dictionaries directory
+fn find_dictionary_files(root: &Path) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files: Vec<_> = WalkDir::new(root)
+        .max_depth(4)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| {
+            entry.path().extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("ifo") || ext.eq_ignore_ascii_case("mdx")
+            })
+        })
+        .map(|entry| {
+            let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+            (entry.into_path(), modified)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn load_dictionary(root: &Path, path: &Path) -> Result<LocalDictionary, AppError> {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let is_mdx = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("mdx"));
+    let (kind, name, description, word_count) = if is_mdx {
+        let dictionary = MdxDictionary::open(path)?;
+        let (name, description, count) = (
+            dictionary.name.clone(),
+            dictionary.description.clone(),
+            dictionary.word_count(),
+        );
+        (DictionaryKind::Mdx(dictionary), name, description, count)
+    } else {
+        let dictionary = StarDict::open(path)?;
+        let (name, description, count) = (
+            dictionary.name.clone(),
+            dictionary.description.clone(),
+            dictionary.word_count,
+        );
+        (
+            DictionaryKind::StarDict(dictionary),
+            name,
+            description,
+            count,
+        )
+    };
+    Ok(LocalDictionary {
+        info: DictionaryInfo {
+            id: relative.to_string_lossy().replace('\\', "/"),
+            name,
+            format: if is_mdx { "mdx" } else { "stardict" }.to_string(),
+            language: language_from_path(relative),
+            description,
+            word_count,
+            path: path.to_string_lossy().to_string(),
+        },
+        kind,
+    })
+}
+
+impl LocalDictionary {
+    fn lookup(&self, key: &str) -> Result<Vec<DictionaryEntry>, AppError> {
+        let name = &self.info.name;
+        match &self.kind {
+            DictionaryKind::StarDict(dictionary) => Ok(dictionary
+                .lookup(key)?
+                .into_iter()
+                .map(|(headword, fields)| stardict_entry(name, headword, fields))
+                .collect()),
+            DictionaryKind::Mdx(dictionary) => {
+                let mut entries = Vec::new();
+                for (headword, mut record) in dictionary.lookup(key)? {
+                    // Follow cross references to the target entry
+                    for _ in 0..MAX_LINK_DEPTH {
+                        let Some(target) = record.strip_prefix("@@@LINK=") else {
+                            break;
+                        };
+                        match dictionary
+                            .lookup(&normalize_headword(target))?
+                            .into_iter()
+                            .next()
+                        {
+                            Some((_, linked)) => record = linked,
+                            None => break,
+                        }
+                    }
+                    if !record.starts_with("@@@LINK=") {
+                        entries.push(mdx_entry(name, headword, record));
+                    }
+                }
+                Ok(entries)
+            }
+        }
+    }
+}
+
+impl DictionaryRegistry {
+    /// Reload the dictionaries if files were added, removed or changed
+    pub fn refresh(&mut self, root: &Path) {
+        let signature = find_dictionary_files(root);
+        if signature == self.signature {
+            return;
+        }
+        self.dictionaries = signature
+            .iter()
+            .filter_map(|(path, _)| match load_dictionary(root, path) {
+                Ok(dictionary) => Some(dictionary),
+                Err(e) => {
+                    log::warn!("Skipping dictionary {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect();
+        self.signature = signature;
+        log::info!("Loaded {} dictionaries", self.dictionaries.len());
+    }
+
+    pub fn infos(&self) -> Vec<DictionaryInfo> {
+        self.dictionaries.iter().map(|d| d.info.clone()).collect()
+    }
+
+    /// Look a word up in every dictionary matching the language
+    ///
+    /// Each dictionary contributes the entries of the first form it knows.
+    pub fn lookup(&self, word: &str, lang: Option<&str>) -> Vec<DictionaryEntry> {
+        let forms = lookup_forms(word, lang);
+        let mut entries = Vec::new();
+        for dictionary in &self.dictionaries {
+            if !matches_language(dictionary.info.language.as_deref(), lang) {
+                continue;
+            }
+            for form in &forms {
+                match dictionary.lookup(form) {
+                    Ok(found) if !found.is_empty() => {
+                        entries.extend(found);
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::warn!("Lookup in {} failed: {}", dictionary.info.name, e);
+                        break;
+                    }
+                }
+            }
+        }
+        entries
+    }
+}
+
+/// Get the directory scanned for local dictionaries
+pub fn get_dictionaries_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(data_dir.join("dictionaries"))
+}
+
+fn string_list(value: &Value, key: &str) -> Vec<String> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse a Free Dictionary API response
+pub fn parse_online_entries(body: &Value) -> Vec<DictionaryEntry> {
+    let Some(items) = body.as_array() else {
+        return Vec::new();
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            let headword = item.get("word")?.as_str()?.to_string();
+            let phonetics = item.get("phonetics").and_then(Value::as_array);
+            let phonetic = item
+                .get("phonetic")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .or_else(|| {
+                    phonetics?
+                        .iter()
+                        .find_map(|p| p.get("text")?.as_str().map(str::to_string))
+                });
+            let audio_url = phonetics.and_then(|phonetics| {
+                phonetics.iter().find_map(|p| {
+                    p.get("audio")?
+                        .as_str()
+                        .filter(|audio| !audio.is_empty())
+                        .map(str::to_string)
+                })
+            });
+            let meanings = item
+                .get("meanings")
+                .and_then(Value::as_array)
+                .map(|meanings| {
+                    meanings
+                        .iter()
+                        .map(|meaning| WordMeaning {
+                            part_of_speech: meaning
+                                .get("partOfSpeech")
+                                .and_then(Value::as_str)
+                                .map(str::to_string),
+                            senses: meaning
+                                .get("definitions")
+                                .and_then(Value::as_array)
+                                .map(|definitions| {
+                                    definitions
+                                        .iter()
+                                        .filter_map(|d| {
+                                            Some(WordSense {
+                                                definition: d
+                                                    .get("definition")?
+                                                    .as_str()?
+                                                    .to_string(),
+                                                example: d
+                                                    .get("example")
+                                                    .and_then(Value::as_str)
+                                                    .map(str::to_string),
+                                                synonyms: string_list(d, "synonyms"),
+                                            })
+                                        })
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(DictionaryEntry {
+                headword,
+                dictionary: ONLINE_DICTIONARY_NAME.to_string(),
+                source: "online".to_string(),
+                phonetic,
+                audio_url,
+                meanings,
+                html: None,
+            })
+        })
+        .collect()
+}
+
+async fn lookup_online(word: &str, lang: &str) -> Result<Vec<DictionaryEntry>, AppError> {
+    let mut url =
+        Url::parse(ONLINE_DICTIONARY_URL).map_err(|e| AppError::Internal(e.to_string()))?;
+    url.path_segments_mut()
+        .map_err(|_| AppError::Internal("Invalid dictionary URL".to_string()))?
+        .push(lang)
+        .push(word);
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    let response = client.get(url).send().await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(Vec::new());
+    }
+    if !response.status().is_success() {
+        return Err(AppError::http_status(
+            response.status(),
+            format!("Dictionary service returned status {}", response.status()),
+        ));
+    }
+    let body: Value = response.json().await?;
+    Ok(parse_online_entries(&body))
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// List the local dictionaries in the dictionaries directory
+#[tauri::command]
+pub async fn list_dictionaries(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, DictionaryState>,
+) -> Result<Vec<DictionaryInfo>, AppError> {
+    let root = get_dictionaries_dir(&app)?;
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        // A reader that failed midway leaves the registry usable
+        let mut registry = state.lock().unwrap_or_else(|e| e.into_inner());
+        registry.refresh(&root);
+        Ok(registry.infos())
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Look up a word in the local dictionaries
+///
+/// Falls back to an online dictionary when no local dictionary knows the word,
+/// unless `online` is false.
+#[tauri::command]
+pub async fn lookup_word(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, DictionaryState>,
+    word: String,
+    lang: Option<String>,
+    online: Option<bool>,
+) -> Result<WordLookupResult, AppError> {
+    let forms = lookup_forms(&word, lang.as_deref());
+    let Some(cleaned) = forms.first().cloned() else {
+        return Err(AppError::InvalidInput("Word is empty".to_string()));
+    };
+
+    let root = get_dictionaries_dir(&app)?;
+    let state = state.inner().clone();
+    let (query, language) = (word.clone(), lang.clone());
+    let mut entries = tauri::async_runtime::spawn_blocking(move || {
+        // A reader that failed midway leaves the registry usable
+        let mut registry = state.lock().unwrap_or_else(|e| e.into_inner());
+        registry.refresh(&root);
+        Ok::<_, AppError>(registry.lookup(&query, language.as_deref()))
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+
+    if entries.is_empty() && online.unwrap_or(true) {
+        let online_lang = lang
+            .as_deref()
+            .and_then(|lang| lang.split(['-', '_']).next())
+            .unwrap_or("en")
+            .to_lowercase();
+        match lookup_online(&cleaned, &online_lang).await {
+            Ok(found) => entries = found,
+            Err(e) => log::warn!("Online lookup of '{}' failed: {}", cleaned, e),
+        }
+    }
+
+    Ok(WordLookupResult {
+        query: word,
+        lang,
+        entries,
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn lookup_forms_strip_punctuation_and_inflections() {
+        assert_eq!(lookup_forms("  “Hello,”  ", Some("en-US")), vec!["hello"]);
+        assert!(lookup_forms("studies", None).contains(&"study".to_string()));
+        assert!(lookup_forms("running", None).contains(&"run".to_string()));
+        assert!(lookup_forms("taking", Some("en")).contains(&"take".to_string()));
+        assert!(lookup_forms("author's", Some("en")).contains(&"author".to_string()));
+        assert_eq!(lookup_forms("Häuser", Some("de")), vec!["häuser"]);
+        assert!(lookup_forms("...", None).is_empty());
+    }
+
+    #[test]
+    fn structures_plain_text_definitions() {
+        let meanings =
+            meanings_from_text("n. a fruit\nthe tree bearing it\nv. to pick apples\nadj.\n");
+        assert_eq!(meanings.len(), 2);
+        assert_eq!(meanings[0].part_of_speech.as_deref(), Some("n."));
+        assert_eq!(meanings[0].senses.len(), 2);
+        assert_eq!(meanings[1].senses[0].definition, "to pick apples");
+
+        let unlabeled = meanings_from_text("just a definition");
+        assert_eq!(unlabeled[0].part_of_speech, None);
+
+        assert_eq!(
+            language_from_path(Path::new("en/oxford/oxford.ifo")).as_deref(),
+            Some("en")
+        );
+        assert_eq!(
+            language_from_path(Path::new("zh-CN/cedict.mdx")).as_deref(),
+            Some("zh-CN")
+        );
+        assert_eq!(language_from_path(Path::new("oxford.ifo")), None);
+        assert_eq!(language_from_path(Path::new("collins/collins.mdx")), None);
+        assert!(matches_language(Some("en"), Some("en-GB")));
+        assert!(!matches_language(Some("de"), Some("en")));
+        assert!(matches_language(None, Some("en")));
+    }
+
+    #[test]
+    fn parses_online_dictionary_response() {
+        let body = json!([{
+            "word": "hello",
+            "phonetics": [{"text": "/həˈləʊ/", "audio": ""}, {"audio": "https://example.com/hello.mp3"}],
+            "meanings": [{
+                "partOfSpeech": "noun",
+                "definitions": [{"definition": "A greeting.", "example": "She said hello.", "synonyms": ["greeting"]}]
+            }]
+        }]);
+        let entries = parse_online_entries(&body);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].phonetic.as_deref(), Some("/həˈləʊ/"));
+        assert_eq!(
+            entries[0].audio_url.as_deref(),
+            Some("https://example.com/hello.mp3")
+        );
+        assert_eq!(entries[0].source, "online");
+        let sense = &entries[0].meanings[0].senses[0];
+        assert_eq!(sense.example.as_deref(), Some("She said hello."));
+        assert_eq!(sense.synonyms, vec!["greeting"]);
+        assert!(parse_online_entries(&json!({"title": "No Definitions Found"})).is_empty());
+    }
+}
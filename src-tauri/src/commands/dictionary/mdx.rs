@@ -0,0 +1,556 @@
+//! MDict (`.mdx`) dictionary reader
+//!
+//! Supports format versions 1.x and 2.x with uncompressed or zlib compressed
+//! blocks, including dictionaries whose key index is encrypted (`Encrypted="2"`).
+//! Dictionaries that require a registration code and LZO compressed blocks are
+//! rejected.
+
+use super::lookup::{normalize_headword, read_at, read_inflated};
+use crate::commands::epub::xml_attr;
+use crate::error::AppError;
+use encoding_rs::{Encoding, UTF_16LE, UTF_8};
+use flate2::read::ZlibDecoder;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use ripemd::{Digest, Ripemd128};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+struct MdxKey {
+    key: String,
+    word: String,
+    record_start: u64,
+    record_end: u64,
+}
+
+#[derive(Debug)]
+struct RecordBlock {
+    file_offset: u64,
+    compressed_size: u64,
+    decompressed_offset: u64,
+    decompressed_size: u64,
+}
+
+/// An opened MDict dictionary
+#[derive(Debug)]
+pub struct MdxDictionary {
+    pub name: String,
+    pub description: Option<String>,
+    path: PathBuf,
+    encoding: &'static Encoding,
+    keys: Vec<MdxKey>,
+    record_blocks: Vec<RecordBlock>,
+}
+
+/// Reads big-endian numbers of the dictionary's word size from a buffer
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+    number_width: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8], number_width: usize) -> Self {
+        Self {
+            data,
+            pos: 0,
+            number_width,
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], AppError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| AppError::InvalidInput("Truncated MDX data".to_string()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, width: usize) -> Result<u64, AppError> {
+        Ok(self
+            .bytes(width)?
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    fn number(&mut self) -> Result<u64, AppError> {
+        self.uint(self.number_width)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn truncated(what: &str) -> AppError {
+    AppError::InvalidInput(format!("Truncated MDX {}", what))
+}
+
+/// `a + b` for sizes read from the file, which may not overflow
+fn add_size(a: u64, b: u64) -> Result<u64, AppError> {
+    a.checked_add(b)
+        .ok_or_else(|| AppError::InvalidInput("Corrupt MDX sizes".to_string()))
+}
+
+/// Attributes of the `<Dictionary>` header element
+fn parse_header(xml: &str) -> Vec<(String, String)> {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                return e
+                    .attributes()
+                    .flatten()
+                    .filter_map(|attr| {
+                        let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                        xml_attr(&e, &key).map(|value| (key, value))
+                    })
+                    .collect();
+            }
+            Ok(Event::Eof) | Err(_) => return Vec::new(),
+            _ => {}
+        }
+    }
+}
+
+/// Decrypt the key block index of dictionaries with `Encrypted="2"`
+fn decrypt_key_index(block: &[u8]) -> Result<Vec<u8>, AppError> {
+    if block.len() < 8 {
+        return Err(truncated("key index"));
+    }
+    let mut hasher = Ripemd128::new();
+    hasher.update(&block[4..8]);
+    hasher.update([0x95, 0x36, 0x00, 0x00]);
+    let key = hasher.finalize();
+
+    let mut output = block.to_vec();
+    let mut previous = 0x36u8;
+    for (i, byte) in output[8..].iter_mut().enumerate() {
+        let encrypted = *byte;
+        *byte = encrypted.rotate_left(4) ^ previous ^ (i as u8) ^ key[i % key.len()];
+        previous = encrypted;
+    }
+    Ok(output)
+}
+
+/// Decompress a key or record block according to its type header
+fn decompress_block(block: &[u8]) -> Result<Vec<u8>, AppError> {
+    let kind = block
+        .get(..4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| truncated("block"))?;
+    let data = block.get(8..).unwrap_or_default();
+    match kind {
+        0 => Ok(data.to_vec()),
+        2 => read_inflated(ZlibDecoder::new(data)),
+        1 => Err(AppError::InvalidInput(
+            "LZO compressed MDX dictionaries are not supported".to_string(),
+        )),
+        other => Err(AppError::InvalidInput(format!(
+            "Unknown MDX block compression {}",
+            other
+        ))),
+    }
+}
+
+/// Read the key id / headword pairs of a decompressed key block
+fn read_key_block(
+    data: &[u8],
+    number_width: usize,
+    encoding: &'static Encoding,
+) -> Result<Vec<(u64, String)>, AppError> {
+    let unit = if encoding == UTF_16LE { 2 } else { 1 };
+    let mut cursor = Cursor::new(data, number_width);
+    let mut keys = Vec::new();
+    while !cursor.is_empty() {
+        let id = cursor.number()?;
+        let rest = &data[cursor.pos..];
+        let len = rest
+            .chunks(unit)
+            .position(|c| c.iter().all(|&b| b == 0))
+            .map(|n| n * unit)
+            .unwrap_or(rest.len());
+        let (text, _) = encoding.decode_without_bom_handling(&rest[..len]);
+        keys.push((id, text.to_string()));
+        cursor.pos += (len + unit).min(rest.len());
+    }
+    Ok(keys)
+}
+
+// ============================================================================
+// Dictionary
+// ============================================================================
+
+impl MdxDictionary {
+    /// Open a dictionary and load its key index
+    pub fn open(path: &Path) -> Result<Self, AppError> {
+        let mut file = File::open(path)?;
+
+        let header_len = u32::from_be_bytes(read_at(&mut file, 0, 4)?.try_into().unwrap()) as u64;
+        let header_bytes = read_at(&mut file, 4, header_len)?;
+        let (header_xml, _) = UTF_16LE.decode_without_bom_handling(&header_bytes);
+        let header = parse_header(header_xml.trim_end_matches('\0'));
+        let attr = |name: &str| {
+            header
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        let version: f32 = attr("GeneratedByEngineVersion")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2.0);
+        let encrypted = match attr("Encrypted").as_deref() {
+            Some("Yes") => 1,
+            Some(value) => value.parse::<u32>().unwrap_or(0),
+            None => 0,
+        };
+        if encrypted & 1 != 0 {
+            return Err(AppError::InvalidInput(
+                "MDX dictionary requires a registration code".to_string(),
+            ));
+        }
+        let encoding = match attr("Encoding").map(|e| e.to_lowercase()).as_deref() {
+            None | Some("") => UTF_8,
+            Some(label) if label.starts_with("utf-16") || label == "utf16" => UTF_16LE,
+            Some("gbk") | Some("gb2312") => encoding_rs::GB18030,
+            Some(label) => Encoding::for_label(label.as_bytes()).unwrap_or(UTF_8),
+        };
+        let is_v2 = version >= 2.0;
+        let number_width = if is_v2 { 8 } else { 4 };
+
+        // Key section header
+        let mut offset = add_size(4 + header_len, 4)?;
+        let key_header_len = if is_v2 { 8 * 5 + 4 } else { 4 * 4 };
+        let key_header = read_at(&mut file, offset, key_header_len)?;
+        let mut cursor = Cursor::new(&key_header, number_width);
+        let block_count = cursor.number()?;
+        let _entry_count = cursor.number()?;
+        if is_v2 {
+            cursor.number()?;
+        }
+        let key_index_size = cursor.number()?;
+        let key_blocks_size = cursor.number()?;
+        offset = add_size(offset, key_header_len)?;
+
+        // Key block index: compressed and decompressed size of each key block
+        let key_index = read_at(&mut file, offset, key_index_size)?;
+        offset = add_size(offset, key_index_size)?;
+        let key_index = if is_v2 {
+            let key_index = if encrypted & 2 != 0 {
+                decrypt_key_index(&key_index)?
+            } else {
+                key_index
+            };
+            decompress_block(&key_index)?
+        } else {
+            key_index
+        };
+
+        let unit = if encoding == UTF_16LE { 2 } else { 1 };
+        let (size_width, terminator) = if is_v2 { (2, unit) } else { (1, 0) };
+        let mut cursor = Cursor::new(&key_index, number_width);
+        let mut block_sizes = Vec::new();
+        for _ in 0..block_count {
+            cursor.number()?;
+            for _ in 0..2 {
+                let text_len = cursor.uint(size_width)? as usize;
+                cursor.bytes(text_len * unit + terminator)?;
+            }
+            block_sizes.push((cursor.number()?, cursor.number()?));
+        }
+
+        // Key blocks
+        let key_blocks = read_at(&mut file, offset, key_blocks_size)?;
+        offset = add_size(offset, key_blocks_size)?;
+        let mut ordered = Vec::new();
+        let mut block_offset = 0usize;
+        for (compressed_size, _) in block_sizes {
+            let end = usize::try_from(compressed_size)
+                .ok()
+                .and_then(|size| block_offset.checked_add(size))
+                .ok_or_else(|| truncated("key block"))?;
+            let block = key_blocks
+                .get(block_offset..end)
+                .ok_or_else(|| truncated("key block"))?;
+            ordered.extend(read_key_block(
+                &decompress_block(block)?,
+                number_width,
+                encoding,
+            )?);
+            block_offset = end;
+        }
+
+        // Record section header and block index
+        let record_header = read_at(&mut file, offset, 4 * number_width as u64)?;
+        let mut cursor = Cursor::new(&record_header, number_width);
+        let record_block_count = cursor.number()?;
+        cursor.number()?;
+        let record_index_size = cursor.number()?;
+        cursor.number()?;
+        offset = add_size(offset, 4 * number_width as u64)?;
+
+        let record_index = read_at(&mut file, offset, record_index_size)?;
+        offset = add_size(offset, record_index_size)?;
+        let mut cursor = Cursor::new(&record_index, number_width);
+        let mut record_blocks = Vec::new();
+        let mut decompressed_offset = 0;
+        for _ in 0..record_block_count {
+            let compressed_size = cursor.number()?;
+            let decompressed_size = cursor.number()?;
+            record_blocks.push(RecordBlock {
+                file_offset: offset,
+                compressed_size,
+                decompressed_offset,
+                decompressed_size,
+            });
+            offset = add_size(offset, compressed_size)?;
+            decompressed_offset = add_size(decompressed_offset, decompressed_size)?;
+        }
+
+        let mut keys: Vec<MdxKey> = ordered
+            .iter()
+            .enumerate()
+            .map(|(i, (start, word))| MdxKey {
+                key: normalize_headword(word),
+                word: word.clone(),
+                record_start: *start,
+                record_end: ordered
+                    .get(i + 1)
+                    .map(|(next, _)| *next)
+                    .unwrap_or(decompressed_offset),
+            })
+            .collect();
+        keys.sort_by(|a, b| a.key.cmp(&b.key));
+
+        Ok(Self {
+            name: attr("Title")
+                .filter(|title| title != "Title (No HTML code allowed)")
+                .unwrap_or_else(|| {
+                    path.file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                }),
+            description: attr("Description"),
+            path: path.to_path_buf(),
+            encoding,
+            keys,
+            record_blocks,
+        })
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn read_record(&self, key: &MdxKey) -> Result<String, AppError> {
+        let block_index = self.record_blocks.partition_point(|block| {
+            block.decompressed_offset + block.decompressed_size <= key.record_start
+        });
+        let mut file = File::open(&self.path)?;
+        let mut data = Vec::new();
+        // A record may continue into the following block
+        for block in &self.record_blocks[block_index.min(self.record_blocks.len())..] {
+            if block.decompressed_offset >= key.record_end {
+                break;
+            }
+            let raw = read_at(&mut file, block.file_offset, block.compressed_size)?;
+            let decompressed = decompress_block(&raw)?;
+            let start = key.record_start.saturating_sub(block.decompressed_offset) as usize;
+            let end =
+                ((key.record_end - block.decompressed_offset) as usize).min(decompressed.len());
+            data.extend_from_slice(decompressed.get(start..end).unwrap_or_default());
+        }
+        let (text, _) = self.encoding.decode_without_bom_handling(&data);
+        Ok(text.trim_end_matches('\0').trim().to_string())
+    }
+
+    /// Look up the records of all headwords whose normalized form equals `key`
+    pub fn lookup(&self, key: &str) -> Result<Vec<(String, String)>, AppError> {
+        let start = self.keys.partition_point(|entry| entry.key.as_str() < key);
+        self.keys[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .map(|entry| Ok((entry.word.clone(), self.read_record(entry)?)))
+            .collect()
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn zlib_block(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        let mut block = vec![2, 0, 0, 0, 0, 0, 0, 0];
+        block.extend(encoder.finish().unwrap());
+        block
+    }
+
+    /// Build a version 2.0 UTF-8 dictionary with one key block and two
+    /// record blocks
+    fn build_mdx(entries: &[(&str, &str)]) -> Vec<u8> {
+        let header = r#"<Dictionary GeneratedByEngineVersion="2.0" Encrypted="0" Encoding="UTF-8" Title="Test MDX" Description="A &lt;b&gt;test&lt;/b&gt; dictionary"/>"#;
+        let header: Vec<u8> = header
+            .encode_utf16()
+            .chain([0])
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+
+        let mut key_block = Vec::new();
+        let mut records = Vec::new();
+        for (word, definition) in entries {
+            key_block.extend_from_slice(&(records.len() as u64).to_be_bytes());
+            key_block.extend_from_slice(word.as_bytes());
+            key_block.push(0);
+            records.extend_from_slice(definition.as_bytes());
+            records.push(0);
+        }
+        let key_block = zlib_block(&key_block);
+
+        let (first, last) = (entries[0].0, entries[entries.len() - 1].0);
+        let mut key_index = Vec::new();
+        key_index.extend_from_slice(&(entries.len() as u64).to_be_bytes());
+        for word in [first, last] {
+            key_index.extend_from_slice(&(word.len() as u16).to_be_bytes());
+            key_index.extend_from_slice(word.as_bytes());
+            key_index.push(0);
+        }
+        key_index.extend_from_slice(&(key_block.len() as u64).to_be_bytes());
+        key_index.extend_from_slice(&0u64.to_be_bytes());
+        let key_index = zlib_block(&key_index);
+
+        let split = records.len() / 2;
+        let record_blocks = [zlib_block(&records[..split]), zlib_block(&records[split..])];
+        let sizes = [split, records.len() - split];
+
+        let mut file = Vec::new();
+        file.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        file.extend_from_slice(&header);
+        file.extend_from_slice(&[0; 4]);
+        for number in [1, entries.len(), 0, key_index.len(), key_block.len()] {
+            file.extend_from_slice(&(number as u64).to_be_bytes());
+        }
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&key_index);
+        file.extend_from_slice(&key_block);
+        let record_data_len: usize = record_blocks.iter().map(Vec::len).sum();
+        for number in [2, entries.len(), 32, record_data_len] {
+            file.extend_from_slice(&(number as u64).to_be_bytes());
+        }
+        for (block, size) in record_blocks.iter().zip(sizes) {
+            file.extend_from_slice(&(block.len() as u64).to_be_bytes());
+            file.extend_from_slice(&(size as u64).to_be_bytes());
+        }
+        for block in &record_blocks {
+            file.extend_from_slice(block);
+        }
+        file
+    }
+
+    #[test]
+    fn reads_mdx_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.mdx");
+        std::fs::write(
+            &path,
+            build_mdx(&[
+                ("apple", "<b>apple</b> a round fruit"),
+                ("Banana", "<i>n.</i> a long yellow fruit that spans blocks"),
+                ("cherry", "@@@LINK=apple"),
+            ]),
+        )
+        .unwrap();
+
+        let dictionary = MdxDictionary::open(&path).unwrap();
+        assert_eq!(dictionary.name, "Test MDX");
+        assert_eq!(
+            dictionary.description.as_deref(),
+            Some("A <b>test</b> dictionary")
+        );
+        assert_eq!(dictionary.word_count(), 3);
+        assert_eq!(
+            dictionary.lookup("banana").unwrap(),
+            vec![(
+                "Banana".to_string(),
+                "<i>n.</i> a long yellow fruit that spans blocks".to_string()
+            )]
+        );
+        assert_eq!(dictionary.lookup("cherry").unwrap()[0].1, "@@@LINK=apple");
+        assert!(dictionary.lookup("durian").unwrap().is_empty());
+    }
+
+    #[test]
+    fn decrypts_key_index() {
+        let plain = zlib_block(b"key index");
+        let mut hasher = Ripemd128::new();
+        hasher.update(&plain[4..8]);
+        hasher.update([0x95, 0x36, 0x00, 0x00]);
+        let key = hasher.finalize();
+
+        // Inverse of the MDict key index cipher
+        let mut encrypted = plain.clone();
+        let mut previous = 0x36u8;
+        for (i, byte) in encrypted[8..].iter_mut().enumerate() {
+            let value = (*byte ^ previous ^ (i as u8) ^ key[i % key.len()]).rotate_right(4);
+            *byte = value;
+            previous = value;
+        }
+
+        assert_eq!(decrypt_key_index(&encrypted).unwrap(), plain);
+        assert_eq!(
+            decompress_block(&decrypt_key_index(&encrypted).unwrap()).unwrap(),
+            b"key index"
+        );
+        assert!(decrypt_key_index(&encrypted[..6]).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_and_oversized_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.mdx");
+        let valid = build_mdx(&[("apple", "a round fruit"), ("pear", "another fruit")]);
+
+        // Cut inside the key blocks
+        std::fs::write(&path, &valid[..valid.len() / 2]).unwrap();
+        assert!(matches!(
+            MdxDictionary::open(&path),
+            Err(AppError::InvalidInput(_))
+        ));
+
+        // A header claiming gigabytes of XML
+        let mut oversized = valid.clone();
+        oversized[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        std::fs::write(&path, &oversized).unwrap();
+        assert!(matches!(
+            MdxDictionary::open(&path),
+            Err(AppError::InvalidInput(_))
+        ));
+
+        // Key blocks claiming more than the largest read
+        let header_len = u32::from_be_bytes(valid[..4].try_into().unwrap()) as usize;
+        let key_blocks_size = 4 + header_len + 4 + 4 * 8;
+        let mut oversized = valid;
+        oversized[key_blocks_size..key_blocks_size + 8].copy_from_slice(&u64::MAX.to_be_bytes());
+        std::fs::write(&path, &oversized).unwrap();
+        assert!(matches!(
+            MdxDictionary::open(&path),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,18 @@
+//! Dictionary lookup
+//!
+//! Words are looked up in dictionaries the user places in the app data
+//! `dictionaries` directory, with an optional online fallback:
+//! - StarDict dictionaries (`.ifo`/`.idx`/`.dict[.dz]`)
+//! - MDict dictionaries (`.mdx`)
+//! - Inflection-aware lookup and structured definitions
+
+mod types;
+mod stardict;
+mod mdx;
+mod lookup;
+
+// Re-export all public items
+pub use types::*;
+pub use stardict::*;
+pub use mdx::*;
+pub use lookup::*;
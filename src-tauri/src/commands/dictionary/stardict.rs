@@ -0,0 +1,537 @@
+//! StarDict dictionary reader
+//!
+//! A StarDict dictionary is a `.ifo` metadata file next to a sorted `.idx`
+//! word index (optionally gzipped) and the `.dict` definition data, which is
+//! usually compressed with dictzip so definitions can be read without
+//! inflating the whole file.
+
+use super::lookup::{normalize_headword, read_at, read_inflated, MAX_DICTIONARY_READ};
+use crate::error::AppError;
+use flate2::read::GzDecoder;
+use flate2::{Decompress, FlushDecompress};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const IFO_MAGIC: &str = "StarDict's dict ifo file";
+
+/// A field of a StarDict definition, tagged with its StarDict type character
+#[derive(Clone, Debug, PartialEq)]
+pub struct StarDictField {
+    pub kind: char,
+    pub text: String,
+}
+
+/// Where the definition data lives and how to read it
+#[derive(Debug)]
+enum DictData {
+    Plain(PathBuf),
+    /// dictzip file with independently inflatable chunks
+    DictZip {
+        path: PathBuf,
+        chunk_length: u64,
+        /// File offset and compressed size of each chunk
+        chunks: Vec<(u64, u64)>,
+    },
+    /// gzip file without a chunk table, inflated once on load
+    Memory(Vec<u8>),
+}
+
+/// Random access table of a dictzip file
+struct ChunkTable {
+    chunk_length: u64,
+    /// Compressed size of each chunk
+    sizes: Vec<u64>,
+}
+
+#[derive(Debug)]
+struct IndexEntry {
+    key: String,
+    word: String,
+    offset: u64,
+    size: u64,
+}
+
+/// An opened StarDict dictionary
+#[derive(Debug)]
+pub struct StarDict {
+    pub name: String,
+    pub description: Option<String>,
+    pub word_count: usize,
+    same_type_sequence: Option<String>,
+    index: Vec<IndexEntry>,
+    data: DictData,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn sibling(path: &Path, extension: &str) -> Option<PathBuf> {
+    let candidate = path.with_extension(extension);
+    candidate.is_file().then_some(candidate)
+}
+
+fn parse_ifo(content: &str) -> Result<Vec<(String, String)>, AppError> {
+    let mut lines = content.lines();
+    if lines
+        .next()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+        != Some(IFO_MAGIC)
+    {
+        return Err(AppError::InvalidInput(
+            "Not a StarDict .ifo file".to_string(),
+        ));
+    }
+    Ok(lines
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect())
+}
+
+fn read_index(data: &[u8], offset_bits: u32) -> Result<Vec<IndexEntry>, AppError> {
+    let offset_len = if offset_bits == 64 { 8 } else { 4 };
+    let truncated = || AppError::InvalidInput("Truncated StarDict index".to_string());
+    let mut index = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let end = data[pos..]
+            .iter()
+            .position(|&b| b == 0)
+            .map(|n| pos + n)
+            .ok_or_else(truncated)?;
+        let word = String::from_utf8_lossy(&data[pos..end]).to_string();
+        pos = end + 1;
+        let numbers = data.get(pos..pos + offset_len + 4).ok_or_else(truncated)?;
+        let offset = numbers[..offset_len]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 8) | b as u64);
+        let size = u32::from_be_bytes(numbers[offset_len..].try_into().unwrap()) as u64;
+        pos += offset_len + 4;
+        index.push(IndexEntry {
+            key: normalize_headword(&word),
+            word,
+            offset,
+            size,
+        });
+    }
+    index.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(index)
+}
+
+/// Parse the gzip header of a dictzip file, returning the data offset and
+/// the random access chunk table if present
+fn parse_dictzip_header(header: &[u8]) -> Option<(u64, Option<ChunkTable>)> {
+    if header.len() < 10 || header[0] != 0x1f || header[1] != 0x8b || header[2] != 8 {
+        return None;
+    }
+    let flags = header[3];
+    let mut pos = 10;
+    let mut chunks = None;
+
+    if flags & 0x04 != 0 {
+        let extra_len = u16::from_le_bytes(header.get(pos..pos + 2)?.try_into().ok()?) as usize;
+        pos += 2;
+        let extra = header.get(pos..pos + extra_len)?;
+        let mut sub = 0;
+        while sub + 4 <= extra.len() {
+            let id = &extra[sub..sub + 2];
+            let len = u16::from_le_bytes([extra[sub + 2], extra[sub + 3]]) as usize;
+            let field = extra.get(sub + 4..sub + 4 + len)?;
+            if id == b"RA" && field.len() >= 6 {
+                let chunk_length = u16::from_le_bytes([field[2], field[3]]) as u64;
+                let count = u16::from_le_bytes([field[4], field[5]]) as usize;
+                let sizes = field[6..]
+                    .chunks_exact(2)
+                    .take(count)
+                    .map(|size| u16::from_le_bytes([size[0], size[1]]) as u64)
+                    .collect::<Vec<_>>();
+                if sizes.len() == count {
+                    chunks = Some(ChunkTable {
+                        chunk_length,
+                        sizes,
+                    });
+                }
+            }
+            sub += 4 + len;
+        }
+        pos += extra_len;
+    }
+    // Skip the optional file name and comment
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            pos += header.get(pos..)?.iter().position(|&b| b == 0)? + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+    Some((pos as u64, chunks))
+}
+
+fn open_dict_data(dict_path: &Path) -> Result<DictData, AppError> {
+    let is_compressed = dict_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("dz"));
+    if !is_compressed {
+        return Ok(DictData::Plain(dict_path.to_path_buf()));
+    }
+
+    let mut header = Vec::new();
+    File::open(dict_path)?
+        .take(64 * 1024)
+        .read_to_end(&mut header)?;
+    match parse_dictzip_header(&header) {
+        Some((data_offset, Some(table))) if table.chunk_length > 0 => {
+            let mut offset = data_offset;
+            let chunks = table
+                .sizes
+                .into_iter()
+                .map(|size| {
+                    let chunk = (offset, size);
+                    offset += size;
+                    chunk
+                })
+                .collect();
+            Ok(DictData::DictZip {
+                path: dict_path.to_path_buf(),
+                chunk_length: table.chunk_length,
+                chunks,
+            })
+        }
+        _ => Ok(DictData::Memory(read_inflated(GzDecoder::new(
+            File::open(dict_path)?,
+        ))?)),
+    }
+}
+
+fn read_dictzip_range(
+    path: &Path,
+    chunk_length: u64,
+    chunks: &[(u64, u64)],
+    offset: u64,
+    size: u64,
+) -> Result<Vec<u8>, AppError> {
+    if size == 0 {
+        return Ok(Vec::new());
+    }
+    let first = (offset / chunk_length) as usize;
+    let last = offset
+        .checked_add(size - 1)
+        .map_or(usize::MAX, |end| (end / chunk_length) as usize);
+    if last >= chunks.len() {
+        return Err(AppError::InvalidInput(
+            "Definition lies outside the dictionary data".to_string(),
+        ));
+    }
+
+    let mut file = File::open(path)?;
+    let mut inflated = Vec::new();
+    for &(chunk_offset, chunk_size) in &chunks[first..=last] {
+        let compressed = read_at(&mut file, chunk_offset, chunk_size)?;
+        // Chunks are flushed independently, so each one inflates on its own
+        let mut output = Vec::with_capacity(chunk_length as usize);
+        Decompress::new(false)
+            .decompress_vec(&compressed, &mut output, FlushDecompress::Sync)
+            .map_err(|e| AppError::InvalidInput(format!("Corrupt dictzip chunk: {}", e)))?;
+        inflated.extend_from_slice(&output);
+    }
+
+    let start = (offset - first as u64 * chunk_length) as usize;
+    inflated
+        .get(start..start + size as usize)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| AppError::InvalidInput("Truncated dictzip data".to_string()))
+}
+
+fn take_field(data: &[u8], kind: char, is_last: bool) -> (Vec<u8>, usize) {
+    if is_last {
+        return (data.to_vec(), data.len());
+    }
+    if kind.is_ascii_uppercase() {
+        // Binary fields are prefixed with their size
+        let size = data
+            .get(..4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize)
+            .unwrap_or(0);
+        let end = (4 + size).min(data.len());
+        return (data[4.min(data.len())..end].to_vec(), end);
+    }
+    match data.iter().position(|&b| b == 0) {
+        Some(end) => (data[..end].to_vec(), end + 1),
+        None => (data.to_vec(), data.len()),
+    }
+}
+
+/// Split raw definition data into its typed fields
+///
+/// Only text fields are returned; binary fields such as sounds and pictures
+/// are skipped.
+pub fn parse_definition(data: &[u8], same_type_sequence: Option<&str>) -> Vec<StarDictField> {
+    let mut fields = Vec::new();
+    let mut push = |kind: char, bytes: Vec<u8>| {
+        if kind.is_ascii_lowercase() {
+            let text = String::from_utf8_lossy(&bytes).trim().to_string();
+            if !text.is_empty() {
+                fields.push(StarDictField { kind, text });
+            }
+        }
+    };
+
+    match same_type_sequence.filter(|s| !s.is_empty()) {
+        Some(sequence) => {
+            let kinds: Vec<char> = sequence.chars().collect();
+            let mut pos = 0;
+            for (i, &kind) in kinds.iter().enumerate() {
+                let (bytes, used) = take_field(&data[pos..], kind, i + 1 == kinds.len());
+                pos += used;
+                push(kind, bytes);
+            }
+        }
+        None => {
+            let mut pos = 0;
+            while pos < data.len() {
+                let kind = data[pos] as char;
+                let (bytes, used) = take_field(&data[pos + 1..], kind, false);
+                pos += 1 + used;
+                push(kind, bytes);
+            }
+        }
+    }
+    fields
+}
+
+// ============================================================================
+// Dictionary
+// ============================================================================
+
+impl StarDict {
+    /// Open a dictionary from its `.ifo` file
+    pub fn open(ifo_path: &Path) -> Result<Self, AppError> {
+        let ifo = parse_ifo(&std::fs::read_to_string(ifo_path)?)?;
+        let value = |key: &str| {
+            ifo.iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+                .filter(|v| !v.is_empty())
+        };
+
+        let idx_path = sibling(ifo_path, "idx").or_else(|| sibling(ifo_path, "idx.gz"));
+        let dict_path = sibling(ifo_path, "dict").or_else(|| sibling(ifo_path, "dict.dz"));
+        let (Some(idx_path), Some(dict_path)) = (idx_path, dict_path) else {
+            return Err(AppError::NotFound(format!(
+                "Missing .idx or .dict file for {}",
+                ifo_path.display()
+            )));
+        };
+
+        let idx = if idx_path.extension().is_some_and(|ext| ext == "gz") {
+            read_inflated(GzDecoder::new(File::open(&idx_path)?))?
+        } else {
+            std::fs::read(&idx_path)?
+        };
+        let offset_bits = value("idxoffsetbits")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(32);
+        let index = read_index(&idx, offset_bits)?;
+
+        Ok(Self {
+            name: value("bookname").unwrap_or_else(|| {
+                ifo_path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            }),
+            description: value("description"),
+            word_count: index.len(),
+            same_type_sequence: value("sametypesequence"),
+            index,
+            data: open_dict_data(&dict_path)?,
+        })
+    }
+
+    fn read_range(&self, offset: u64, size: u64) -> Result<Vec<u8>, AppError> {
+        if size > MAX_DICTIONARY_READ {
+            return Err(AppError::InvalidInput(format!(
+                "Definition of {} bytes is too large",
+                size
+            )));
+        }
+        match &self.data {
+            DictData::Plain(path) => read_at(&mut File::open(path)?, offset, size),
+            DictData::DictZip {
+                path,
+                chunk_length,
+                chunks,
+            } => read_dictzip_range(path, *chunk_length, chunks, offset, size),
+            DictData::Memory(data) => data
+                .get(offset as usize..offset.saturating_add(size) as usize)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| AppError::InvalidInput("Truncated dictionary data".to_string())),
+        }
+    }
+
+    /// Look up all entries whose normalized headword equals `key`
+    pub fn lookup(&self, key: &str) -> Result<Vec<(String, Vec<StarDictField>)>, AppError> {
+        let start = self.index.partition_point(|entry| entry.key.as_str() < key);
+        self.index[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .map(|entry| {
+                let data = self.read_range(entry.offset, entry.size)?;
+                Ok((
+                    entry.word.clone(),
+                    parse_definition(&data, self.same_type_sequence.as_deref()),
+                ))
+            })
+            .collect()
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compress, Compression, FlushCompress};
+    use std::io::Write;
+
+    /// Write a StarDict dictionary, returning the `.ifo` path
+    fn write_dictionary(dir: &Path, entries: &[(&str, &str)], dictzip: bool) -> PathBuf {
+        let mut data = Vec::new();
+        let mut idx = Vec::new();
+        for (word, definition) in entries {
+            idx.extend_from_slice(word.as_bytes());
+            idx.push(0);
+            idx.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            idx.extend_from_slice(&(definition.len() as u32).to_be_bytes());
+            data.extend_from_slice(definition.as_bytes());
+        }
+        std::fs::write(
+            dir.join("test.ifo"),
+            format!(
+                "{}\nversion=2.4.2\nbookname=Test Dictionary\nwordcount={}\nsametypesequence=m\n",
+                IFO_MAGIC,
+                entries.len()
+            ),
+        )
+        .unwrap();
+        std::fs::write(dir.join("test.idx"), idx).unwrap();
+
+        if !dictzip {
+            std::fs::write(dir.join("test.dict"), data).unwrap();
+            return dir.join("test.ifo");
+        }
+
+        // Compress in small chunks so lookups span chunk boundaries
+        let chunk_length = 8usize;
+        let mut compressed_chunks = Vec::new();
+        let mut compress = Compress::new(Compression::default(), false);
+        for chunk in data.chunks(chunk_length) {
+            let mut output = Vec::with_capacity(chunk.len() + 64);
+            let before = compress.total_out();
+            compress
+                .compress_vec(chunk, &mut output, FlushCompress::Full)
+                .unwrap();
+            assert_eq!(compress.total_out() - before, output.len() as u64);
+            compressed_chunks.push(output);
+        }
+        let mut extra = b"RA".to_vec();
+        extra.extend_from_slice(&((6 + 2 * compressed_chunks.len()) as u16).to_le_bytes());
+        extra.extend_from_slice(&1u16.to_le_bytes());
+        extra.extend_from_slice(&(chunk_length as u16).to_le_bytes());
+        extra.extend_from_slice(&(compressed_chunks.len() as u16).to_le_bytes());
+        for chunk in &compressed_chunks {
+            extra.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+        }
+
+        let mut file = File::create(dir.join("test.dict.dz")).unwrap();
+        file.write_all(&[0x1f, 0x8b, 8, 0x04, 0, 0, 0, 0, 0, 3])
+            .unwrap();
+        file.write_all(&(extra.len() as u16).to_le_bytes()).unwrap();
+        file.write_all(&extra).unwrap();
+        for chunk in &compressed_chunks {
+            file.write_all(chunk).unwrap();
+        }
+        dir.join("test.ifo")
+    }
+
+    #[test]
+    fn reads_plain_and_dictzip_dictionaries() {
+        let entries = [
+            ("Apple", "n. a round fruit"),
+            ("apple", "the fruit of the apple tree"),
+            ("banana", "n. a long yellow fruit\nv. (slang) to go mad"),
+        ];
+        for dictzip in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let dictionary =
+                StarDict::open(&write_dictionary(dir.path(), &entries, dictzip)).unwrap();
+            assert_eq!(dictionary.name, "Test Dictionary");
+            assert_eq!(dictionary.word_count, 3);
+
+            let apples = dictionary.lookup("apple").unwrap();
+            assert_eq!(apples.len(), 2);
+            assert_eq!(apples[0].0, "Apple");
+            assert_eq!(apples[0].1[0].text, "n. a round fruit");
+
+            let banana = dictionary.lookup("banana").unwrap();
+            assert_eq!(
+                banana[0].1,
+                vec![StarDictField {
+                    kind: 'm',
+                    text: "n. a long yellow fruit\nv. (slang) to go mad".to_string()
+                }]
+            );
+            assert!(dictionary.lookup("cherry").unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn rejects_definitions_outside_the_data() {
+        for dictzip in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let ifo = write_dictionary(dir.path(), &[("apple", "a round fruit")], dictzip);
+            let mut idx = Vec::new();
+            for (word, offset, size) in [("apple", 0, u32::MAX), ("pear", u32::MAX - 4, 4)] {
+                idx.extend_from_slice(word.as_bytes());
+                idx.push(0);
+                idx.extend_from_slice(&offset.to_be_bytes());
+                idx.extend_from_slice(&size.to_be_bytes());
+            }
+            std::fs::write(dir.path().join("test.idx"), &idx).unwrap();
+            let dictionary = StarDict::open(&ifo).unwrap();
+            assert!(matches!(
+                dictionary.lookup("apple"),
+                Err(AppError::InvalidInput(_))
+            ));
+            assert!(matches!(
+                dictionary.lookup("pear"),
+                Err(AppError::InvalidInput(_))
+            ));
+
+            std::fs::write(dir.path().join("test.idx"), b"apple\0\0\0").unwrap();
+            assert!(StarDict::open(&ifo).is_err());
+        }
+    }
+
+    #[test]
+    fn parses_definitions_without_type_sequence() {
+        let mut data = b"t".to_vec();
+        data.extend_from_slice(b"/ap.l/\0");
+        data.push(b'W');
+        data.extend_from_slice(&3u32.to_be_bytes());
+        data.extend_from_slice(&[1, 2, 3]);
+        data.push(b'h');
+        data.extend_from_slice(b"<b>fruit</b>\0");
+
+        let fields = parse_definition(&data, None);
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].kind, 't');
+        assert_eq!(fields[0].text, "/ap.l/");
+        assert_eq!(fields[1].kind, 'h');
+        assert_eq!(fields[1].text, "<b>fruit</b>");
+    }
+}
@@ -0,0 +1,63 @@
+//! Dictionary data structures
+
+use serde::Serialize;
+
+/// A local dictionary file found in the dictionaries directory
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DictionaryInfo {
+    /// Path of the dictionary relative to the dictionaries directory
+    pub id: String,
+    pub name: String,
+    /// "stardict" or "mdx"
+    pub format: String,
+    /// Language taken from the parent directory name (e.g. `en/`), if any
+    pub language: Option<String>,
+    pub description: Option<String>,
+    pub word_count: usize,
+    pub path: String,
+}
+
+/// A single sense of a word
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WordSense {
+    pub definition: String,
+    pub example: Option<String>,
+    pub synonyms: Vec<String>,
+}
+
+/// Senses grouped by part of speech
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WordMeaning {
+    pub part_of_speech: Option<String>,
+    pub senses: Vec<WordSense>,
+}
+
+/// The definition of a word from one dictionary
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DictionaryEntry {
+    /// Headword as written in the dictionary
+    pub headword: String,
+    /// Dictionary name, or the online service name
+    pub dictionary: String,
+    /// "local" or "online"
+    pub source: String,
+    pub phonetic: Option<String>,
+    pub audio_url: Option<String>,
+    pub meanings: Vec<WordMeaning>,
+    /// Original HTML definition for dictionaries that provide markup
+    pub html: Option<String>,
+}
+
+/// Result of a word lookup
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WordLookupResult {
+    /// The word as requested
+    pub query: String,
+    pub lang: Option<String>,
+    pub entries: Vec<DictionaryEntry>,
+}
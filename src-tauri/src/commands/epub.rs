@@ -0,0 +1,1536 @@
+//! EPUB package parsing helpers and commands
+//!
+//! An EPUB is a zip archive whose `META-INF/container.xml` points at the OPF
+//! package document, which in turn lists the manifest, spine and metadata.
+//! Metadata edited in the library can be written back into the OPF.
+
+use crate::error::AppError;
+use quick_xml::escape::resolve_html5_entity;
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// A manifest entry with its href resolved to a full archive path
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubManifestItem {
+    pub id: String,
+    pub href: String,
+    pub media_type: String,
+    pub properties: Option<String>,
+}
+
+/// Parsed OPF package document
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubPackage {
+    pub opf_path: String,
+    pub title: Option<String>,
+    pub creators: Vec<String>,
+    pub language: Option<String>,
+    pub identifier: Option<String>,
+    pub manifest: Vec<EpubManifestItem>,
+    /// Manifest ids in reading order
+    pub spine: Vec<String>,
+    /// Spine ids marked `linear="no"` (auxiliary content such as footnotes)
+    pub non_linear: Vec<String>,
+    /// Manifest id of the NCX table of contents (EPUB 2)
+    pub toc_id: Option<String>,
+    /// Manifest id referenced by `<meta name="cover">` (EPUB 2)
+    pub cover_id: Option<String>,
+}
+
+impl EpubPackage {
+    /// Find a manifest item by id
+    pub fn item(&self, id: &str) -> Option<&EpubManifestItem> {
+        self.manifest.iter().find(|item| item.id == id)
+    }
+
+    /// Find the cover image, trying EPUB 3 properties, EPUB 2 meta, then file names
+    pub fn cover_item(&self) -> Option<&EpubManifestItem> {
+        let is_image = |item: &&EpubManifestItem| item.media_type.starts_with("image/");
+        self.manifest
+            .iter()
+            .find(|item| {
+                item.properties
+                    .as_deref()
+                    .map(|p| p.split_whitespace().any(|p| p == "cover-image"))
+                    .unwrap_or(false)
+            })
+            .or_else(|| {
+                self.cover_id
+                    .as_deref()
+                    .and_then(|id| self.item(id))
+                    .filter(is_image)
+            })
+            .or_else(|| {
+                self.manifest.iter().filter(is_image).find(|item| {
+                    item.id.to_lowercase().contains("cover")
+                        || item.href.to_lowercase().contains("cover")
+                })
+            })
+    }
+}
+
+/// Plain text of one spine document
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubChapterText {
+    /// Position in the spine
+    pub index: usize,
+    pub id: String,
+    pub href: String,
+    /// First heading of the chapter, or its `<title>`
+    pub title: Option<String>,
+    /// Paragraphs separated by newlines
+    pub text: String,
+}
+
+/// Extracted text of an EPUB
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubTextContent {
+    pub title: Option<String>,
+    pub creators: Vec<String>,
+    /// Total number of chapters in the spine
+    pub chapter_count: usize,
+    pub chapters: Vec<EpubChapterText>,
+}
+
+/// A spine entry with its manifest details
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubSpineItem {
+    pub index: usize,
+    pub id: String,
+    pub href: String,
+    pub media_type: String,
+    pub linear: bool,
+}
+
+/// A table of contents entry
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubTocEntry {
+    pub label: String,
+    /// Archive path of the target document (None for heading-only entries)
+    pub href: Option<String>,
+    /// Fragment identifier within the target document
+    pub fragment: Option<String>,
+    /// Index of the target document in the spine
+    pub spine_index: Option<usize>,
+    pub children: Vec<EpubTocEntry>,
+}
+
+/// Navigation structure of an EPUB
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubStructure {
+    pub title: Option<String>,
+    pub creators: Vec<String>,
+    pub language: Option<String>,
+    pub spine: Vec<EpubSpineItem>,
+    pub toc: Vec<EpubTocEntry>,
+    /// Where the TOC came from: "nav" (EPUB 3) | "ncx" (EPUB 2) | "none"
+    pub toc_source: String,
+    pub manifest: Vec<EpubManifestItem>,
+}
+
+/// Metadata to write into an EPUB; fields left out are kept as they are
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubMetadataUpdate {
+    pub title: Option<String>,
+    /// Replaces every creator, in order
+    pub authors: Option<Vec<String>>,
+    /// An empty series removes it
+    pub series: Option<String>,
+    pub series_index: Option<f64>,
+    /// Image file to replace the cover with
+    pub cover_path: Option<String>,
+}
+
+/// A cover image to write into an EPUB
+struct NewCover {
+    /// Manifest id, of the current cover or a new item
+    id: String,
+    /// Archive path
+    path: String,
+    /// href relative to the OPF, when a manifest item has to be added
+    href: Option<String>,
+    media_type: &'static str,
+    data: Vec<u8>,
+}
+
+/// Plain text converted from an XHTML document
+#[derive(Debug, Default, PartialEq)]
+pub struct XhtmlText {
+    pub title: Option<String>,
+    pub text: String,
+}
+
+/// Elements whose content is never part of the readable text
+const SKIPPED_ELEMENTS: &[&str] = &["head", "script", "style", "template", "svg", "math"];
+
+/// Elements that start a new line of text
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "br",
+    "caption",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Open an EPUB archive
+pub fn open_epub(path: &Path) -> Result<ZipArchive<File>, AppError> {
+    let file = File::open(path)?;
+    ZipArchive::new(file).map_err(|e| AppError::Internal(format!("Invalid EPUB archive: {}", e)))
+}
+
+/// Read an archive entry into memory
+pub fn read_epub_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, AppError> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| AppError::NotFound(format!("EPUB entry not found: {}", name)))?;
+    let mut buffer = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Decode `%XX` escapes in an href
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Resolve an href relative to the directory of a document inside the archive
+///
+/// Fragments are dropped and `.`/`..` segments are normalized.
+pub fn resolve_epub_href(base_document: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let href = percent_decode(href);
+    let mut segments: Vec<&str> = match base_document.rfind('/') {
+        Some(index) if !href.starts_with('/') => base_document[..index].split('/').collect(),
+        _ => Vec::new(),
+    };
+    for segment in href.trim_start_matches('/').split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            other => segments.push(other),
+        }
+    }
+    segments.join("/")
+}
+
+/// Read an attribute value by local name (ignoring namespace prefixes)
+pub fn xml_attr(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|attr| attr.key.local_name().as_ref() == name.as_bytes())
+        .and_then(|attr| attr.unescape_value().ok())
+        .map(|value| value.to_string())
+}
+
+/// Find the OPF package path in `META-INF/container.xml`
+pub fn find_opf_path(container_xml: &str) -> Option<String> {
+    let mut reader = Reader::from_str(container_xml);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name().as_ref() == b"rootfile" => {
+                if let Some(path) = xml_attr(&e, "full-path") {
+                    return Some(path);
+                }
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Parse an OPF package document
+pub fn parse_opf(opf_xml: &str, opf_path: &str) -> EpubPackage {
+    let mut package = EpubPackage {
+        opf_path: opf_path.to_string(),
+        ..Default::default()
+    };
+    let mut reader = Reader::from_str(opf_xml);
+    reader.config_mut().trim_text(true);
+    let mut current_text_element: Option<String> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                match name.as_str() {
+                    "item" => {
+                        if let (Some(id), Some(href)) = (xml_attr(&e, "id"), xml_attr(&e, "href")) {
+                            package.manifest.push(EpubManifestItem {
+                                id,
+                                href: resolve_epub_href(opf_path, &href),
+                                media_type: xml_attr(&e, "media-type").unwrap_or_default(),
+                                properties: xml_attr(&e, "properties"),
+                            });
+                        }
+                    }
+                    "itemref" => {
+                        if let Some(idref) = xml_attr(&e, "idref") {
+                            if xml_attr(&e, "linear").as_deref() == Some("no") {
+                                package.non_linear.push(idref.clone());
+                            }
+                            package.spine.push(idref);
+                        }
+                    }
+                    "spine" => package.toc_id = xml_attr(&e, "toc"),
+                    "meta" if xml_attr(&e, "name").as_deref() == Some("cover") => {
+                        package.cover_id = xml_attr(&e, "content");
+                    }
+                    "title" | "creator" | "language" | "identifier" => {
+                        current_text_element = Some(name);
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::Text(e)) => {
+                if let (Some(element), Ok(text)) = (current_text_element.as_deref(), e.unescape()) {
+                    let text = text.trim().to_string();
+                    if !text.is_empty() {
+                        match element {
+                            "title" if package.title.is_none() => package.title = Some(text),
+                            "creator" => package.creators.push(text),
+                            "language" if package.language.is_none() => {
+                                package.language = Some(text)
+                            }
+                            "identifier" if package.identifier.is_none() => {
+                                package.identifier = Some(text)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            Ok(Event::End(_)) => current_text_element = None,
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    package
+}
+
+/// Locate and parse the OPF package of an open EPUB archive
+pub fn read_epub_package(archive: &mut ZipArchive<File>) -> Result<EpubPackage, AppError> {
+    let container = read_epub_entry(archive, "META-INF/container.xml")?;
+    let opf_path = find_opf_path(&String::from_utf8_lossy(&container))
+        .ok_or_else(|| AppError::NotFound("EPUB container has no rootfile".to_string()))?;
+    let opf = read_epub_entry(archive, &opf_path)?;
+    Ok(parse_opf(&String::from_utf8_lossy(&opf), &opf_path))
+}
+
+/// Read the cover image bytes of an EPUB, if it declares or contains one
+pub fn read_epub_cover(path: &Path) -> Result<Option<Vec<u8>>, AppError> {
+    let mut archive = open_epub(path)?;
+    let package = read_epub_package(&mut archive)?;
+    match package.cover_item() {
+        Some(item) => Ok(Some(read_epub_entry(&mut archive, &item.href)?)),
+        None => Ok(None),
+    }
+}
+
+/// Resolve an href to an archive path, keeping its fragment separately
+pub fn resolve_epub_target(base_document: &str, href: &str) -> (String, Option<String>) {
+    let fragment = href
+        .split_once('#')
+        .map(|(_, fragment)| fragment.to_string())
+        .filter(|fragment| !fragment.is_empty());
+    (resolve_epub_href(base_document, href), fragment)
+}
+
+/// Trim labels and drop empty leaf entries, recursively
+fn finish_toc(entries: Vec<EpubTocEntry>) -> Vec<EpubTocEntry> {
+    entries
+        .into_iter()
+        .map(|mut entry| {
+            entry.label = entry.label.trim().to_string();
+            entry.children = finish_toc(entry.children);
+            entry
+        })
+        .filter(|entry| {
+            !entry.label.is_empty() || entry.href.is_some() || !entry.children.is_empty()
+        })
+        .collect()
+}
+
+/// Parse the `<nav epub:type="toc">` list of an EPUB 3 navigation document
+pub fn parse_nav_toc(nav_xhtml: &str, nav_path: &str) -> Vec<EpubTocEntry> {
+    let mut reader = Reader::from_str(nav_xhtml);
+    reader.config_mut().check_end_names = false;
+
+    let mut in_toc = false;
+    let mut capturing = false;
+    let mut lists: Vec<Vec<EpubTocEntry>> = Vec::new();
+    let mut items: Vec<EpubTocEntry> = Vec::new();
+    let mut root: Vec<EpubTocEntry> = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_lowercase();
+                if name == "nav" {
+                    in_toc = xml_attr(&e, "type")
+                        .is_some_and(|kind| kind.split_whitespace().any(|k| k == "toc"));
+                    continue;
+                }
+                if !in_toc {
+                    continue;
+                }
+                match name.as_str() {
+                    "ol" => lists.push(Vec::new()),
+                    "li" => items.push(EpubTocEntry::default()),
+                    "a" => {
+                        if let (Some(item), Some(href)) = (items.last_mut(), xml_attr(&e, "href")) {
+                            let (path, fragment) = resolve_epub_target(nav_path, &href);
+                            item.href = Some(path);
+                            item.fragment = fragment;
+                        }
+                        capturing = true;
+                    }
+                    "span" => capturing = true,
+                    _ => {}
+                }
+            }
+            Ok(Event::Text(e)) if in_toc && capturing => {
+                if let Some(item) = items.last_mut() {
+                    push_collapsed(&mut item.label, &decode_xhtml_text(&e));
+                }
+            }
+            Ok(Event::End(e)) if in_toc => match e.local_name().as_ref() {
+                b"a" | b"span" => capturing = false,
+                b"li" => {
+                    if let (Some(item), Some(list)) = (items.pop(), lists.last_mut()) {
+                        list.push(item);
+                    }
+                }
+                b"ol" => {
+                    if let Some(list) = lists.pop() {
+                        match items.last_mut() {
+                            Some(parent) => parent.children = list,
+                            None => root.extend(list),
+                        }
+                    }
+                }
+                b"nav" => break,
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                log::debug!("Stopped parsing malformed nav document: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    finish_toc(root)
+}
+
+/// Parse the `navMap` of an EPUB 2 NCX document
+pub fn parse_ncx_toc(ncx_xml: &str, ncx_path: &str) -> Vec<EpubTocEntry> {
+    let mut reader = Reader::from_str(ncx_xml);
+    let mut in_nav_map = false;
+    let mut capturing = false;
+    let mut stack: Vec<EpubTocEntry> = Vec::new();
+    let mut root: Vec<EpubTocEntry> = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => match e.local_name().as_ref() {
+                b"navMap" => in_nav_map = true,
+                b"navPoint" if in_nav_map => stack.push(EpubTocEntry::default()),
+                b"text" if in_nav_map => capturing = true,
+                b"content" if in_nav_map => set_ncx_target(&e, ncx_path, stack.last_mut()),
+                _ => {}
+            },
+            Ok(Event::Empty(e)) if in_nav_map && e.local_name().as_ref() == b"content" => {
+                set_ncx_target(&e, ncx_path, stack.last_mut());
+            }
+            Ok(Event::Text(e)) if capturing => {
+                if let Some(entry) = stack.last_mut() {
+                    push_collapsed(&mut entry.label, &decode_xhtml_text(&e));
+                }
+            }
+            Ok(Event::End(e)) => match e.local_name().as_ref() {
+                b"text" => capturing = false,
+                b"navPoint" => {
+                    if let Some(entry) = stack.pop() {
+                        match stack.last_mut() {
+                            Some(parent) => parent.children.push(entry),
+                            None => root.push(entry),
+                        }
+                    }
+                }
+                b"navMap" => break,
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                log::debug!("Stopped parsing malformed NCX document: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    finish_toc(root)
+}
+
+/// Set the target of an NCX entry from a `<content src>` element
+fn set_ncx_target(element: &BytesStart, ncx_path: &str, entry: Option<&mut EpubTocEntry>) {
+    if let (Some(entry), Some(src)) = (entry, xml_attr(element, "src")) {
+        let (path, fragment) = resolve_epub_target(ncx_path, &src);
+        entry.href = Some(path);
+        entry.fragment = fragment;
+    }
+}
+
+/// Fill in spine indices for TOC entries that point at spine documents
+fn link_toc_to_spine(entries: &mut [EpubTocEntry], spine: &[EpubSpineItem]) {
+    for entry in entries {
+        entry.spine_index = entry
+            .href
+            .as_deref()
+            .and_then(|href| spine.iter().find(|item| item.href == href))
+            .map(|item| item.index);
+        link_toc_to_spine(&mut entry.children, spine);
+    }
+}
+
+/// Read the spine, manifest and table of contents of an EPUB
+pub fn read_epub_structure(path: &Path) -> Result<EpubStructure, AppError> {
+    let mut archive = open_epub(path)?;
+    let package = read_epub_package(&mut archive)?;
+
+    let spine: Vec<EpubSpineItem> = package
+        .spine
+        .iter()
+        .enumerate()
+        .filter_map(|(index, id)| {
+            package.item(id).map(|item| EpubSpineItem {
+                index,
+                id: item.id.clone(),
+                href: item.href.clone(),
+                media_type: item.media_type.clone(),
+                linear: !package.non_linear.contains(id),
+            })
+        })
+        .collect();
+
+    // Prefer the EPUB 3 nav document, fall back to the EPUB 2 NCX
+    let nav_item = package.manifest.iter().find(|item| {
+        item.properties
+            .as_deref()
+            .is_some_and(|p| p.split_whitespace().any(|p| p == "nav"))
+    });
+    let ncx_item = package
+        .toc_id
+        .as_deref()
+        .and_then(|id| package.item(id))
+        .or_else(|| {
+            package
+                .manifest
+                .iter()
+                .find(|item| item.media_type == "application/x-dtbncx+xml")
+        });
+
+    let mut toc = Vec::new();
+    let mut toc_source = "none";
+    if let Some(item) = nav_item {
+        if let Ok(content) = read_epub_entry(&mut archive, &item.href) {
+            toc = parse_nav_toc(&String::from_utf8_lossy(&content), &item.href);
+            toc_source = "nav";
+        }
+    }
+    if toc.is_empty() {
+        if let Some(item) = ncx_item {
+            if let Ok(content) = read_epub_entry(&mut archive, &item.href) {
+                toc = parse_ncx_toc(&String::from_utf8_lossy(&content), &item.href);
+                toc_source = "ncx";
+            }
+        }
+    }
+    if toc.is_empty() {
+        toc_source = "none";
+    }
+    link_toc_to_spine(&mut toc, &spine);
+
+    Ok(EpubStructure {
+        title: package.title,
+        creators: package.creators,
+        language: package.language,
+        spine,
+        toc,
+        toc_source: toc_source.to_string(),
+        manifest: package.manifest,
+    })
+}
+
+/// Append text to a line buffer, collapsing whitespace runs
+fn push_collapsed(buffer: &mut String, text: &str) {
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            if !buffer.is_empty() && !buffer.ends_with(' ') {
+                buffer.push(' ');
+            }
+        } else {
+            buffer.push(ch);
+        }
+    }
+}
+
+/// Decode a text node, resolving HTML entities and keeping unknown ones verbatim
+pub fn decode_xhtml_text(text: &BytesText) -> String {
+    text.unescape_with(resolve_html5_entity)
+        .map(|text| text.to_string())
+        .unwrap_or_else(|_| String::from_utf8_lossy(text).to_string())
+}
+
+/// Strip an XHTML document down to plain text, one block element per line
+///
+/// Malformed markup is tolerated: whatever was read before the error is kept.
+pub fn xhtml_to_text(xhtml: &str) -> XhtmlText {
+    let mut reader = Reader::from_str(xhtml);
+    reader.config_mut().check_end_names = false;
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut skip_depth = 0usize;
+    let mut in_title = false;
+    let mut document_title = String::new();
+    let mut heading: Option<String> = None;
+    let mut first_heading: Option<String> = None;
+
+    let flush = |line: &mut String, lines: &mut Vec<String>| {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            lines.push(trimmed.to_string());
+        }
+        line.clear();
+    };
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_lowercase();
+                if name == "title" {
+                    in_title = true;
+                }
+                if SKIPPED_ELEMENTS.contains(&name.as_str()) {
+                    skip_depth += 1;
+                } else if skip_depth == 0 && BLOCK_ELEMENTS.contains(&name.as_str()) {
+                    flush(&mut line, &mut lines);
+                    if first_heading.is_none() && matches!(name.as_str(), "h1" | "h2" | "h3") {
+                        heading = Some(String::new());
+                    }
+                }
+            }
+            Ok(Event::Empty(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_lowercase();
+                if skip_depth == 0 && BLOCK_ELEMENTS.contains(&name.as_str()) {
+                    flush(&mut line, &mut lines);
+                }
+            }
+            Ok(Event::End(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_lowercase();
+                if name == "title" {
+                    in_title = false;
+                }
+                if SKIPPED_ELEMENTS.contains(&name.as_str()) {
+                    skip_depth = skip_depth.saturating_sub(1);
+                } else if skip_depth == 0 && BLOCK_ELEMENTS.contains(&name.as_str()) {
+                    flush(&mut line, &mut lines);
+                    if let Some(text) = heading.take() {
+                        let text = text.trim().to_string();
+                        if !text.is_empty() {
+                            first_heading = Some(text);
+                        }
+                    }
+                }
+            }
+            Ok(Event::Text(e)) => {
+                let text = decode_xhtml_text(&e);
+                if in_title {
+                    push_collapsed(&mut document_title, &text);
+                } else if skip_depth == 0 {
+                    push_collapsed(&mut line, &text);
+                    if let Some(heading) = heading.as_mut() {
+                        push_collapsed(heading, &text);
+                    }
+                }
+            }
+            Ok(Event::CData(e)) if skip_depth == 0 => {
+                push_collapsed(&mut line, &String::from_utf8_lossy(&e));
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                log::debug!("Stopped parsing malformed XHTML: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+    flush(&mut line, &mut lines);
+
+    let document_title = document_title.trim().to_string();
+    XhtmlText {
+        title: first_heading.or((!document_title.is_empty()).then_some(document_title)),
+        text: lines.join("\n"),
+    }
+}
+
+/// Extract plain text from the spine documents of an EPUB
+///
+/// When `chapter` is given only that spine index is extracted.
+pub fn read_epub_text(path: &Path, chapter: Option<usize>) -> Result<EpubTextContent, AppError> {
+    let mut archive = open_epub(path)?;
+    let package = read_epub_package(&mut archive)?;
+    let chapter_count = package.spine.len();
+
+    let indices: Vec<usize> = match chapter {
+        Some(index) if index >= chapter_count => {
+            return Err(AppError::InvalidInput(format!(
+                "Chapter {} out of range (the book has {} chapters)",
+                index, chapter_count
+            )));
+        }
+        Some(index) => vec![index],
+        None => (0..chapter_count).collect(),
+    };
+
+    let mut chapters = Vec::with_capacity(indices.len());
+    for index in indices {
+        let Some(item) = package.item(&package.spine[index]) else {
+            log::warn!("Spine item {} missing from manifest", package.spine[index]);
+            continue;
+        };
+        let content = match read_epub_entry(&mut archive, &item.href) {
+            Ok(content) => content,
+            Err(e) => {
+                log::warn!("Skipping unreadable chapter {}: {}", item.href, e);
+                continue;
+            }
+        };
+        let XhtmlText { title, text } = xhtml_to_text(&String::from_utf8_lossy(&content));
+        chapters.push(EpubChapterText {
+            index,
+            id: item.id.clone(),
+            href: item.href.clone(),
+            title,
+            text,
+        });
+    }
+
+    Ok(EpubTextContent {
+        title: package.title,
+        creators: package.creators,
+        chapter_count,
+        chapters,
+    })
+}
+
+fn opf_err(e: quick_xml::Error) -> AppError {
+    AppError::InvalidInput(format!("Invalid OPF package: {}", e))
+}
+
+fn epub_zip_err(e: zip::result::ZipError) -> AppError {
+    AppError::Internal(format!("Invalid EPUB archive: {}", e))
+}
+
+/// Media type of a cover image file, by extension
+fn image_media_type(path: &Path) -> Option<&'static str> {
+    match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Which updatable field an OPF element holds, if any
+fn metadata_field(element: &BytesStart) -> Option<&'static str> {
+    match element.local_name().as_ref() {
+        b"title" => Some("title"),
+        b"creator" => Some("authors"),
+        b"meta" => match (
+            xml_attr(element, "name").as_deref(),
+            xml_attr(element, "property").as_deref(),
+        ) {
+            (Some("calibre:series" | "calibre:series_index"), _)
+            | (_, Some("belongs-to-collection")) => Some("series"),
+            (Some("cover"), _) => Some("cover"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Trim an update and check it changes something
+fn normalize_metadata_update(update: &EpubMetadataUpdate) -> Result<EpubMetadataUpdate, AppError> {
+    let title = update.title.as_deref().map(str::trim);
+    if title == Some("") {
+        return Err(AppError::InvalidInput("Title cannot be empty".to_string()));
+    }
+    let update = EpubMetadataUpdate {
+        title: title.map(str::to_string),
+        authors: update.authors.as_ref().map(|authors| {
+            authors
+                .iter()
+                .map(|author| author.trim().to_string())
+                .filter(|author| !author.is_empty())
+                .collect()
+        }),
+        series: update.series.as_deref().map(|s| s.trim().to_string()),
+        series_index: update.series_index,
+        cover_path: update.cover_path.clone(),
+    };
+    if update.series_index.is_some() && update.series.as_deref().map_or(true, str::is_empty) {
+        return Err(AppError::InvalidInput(
+            "A series index needs a series".to_string(),
+        ));
+    }
+    if update.title.is_none()
+        && update.authors.is_none()
+        && update.series.is_none()
+        && update.cover_path.is_none()
+    {
+        return Err(AppError::InvalidInput("Nothing to update".to_string()));
+    }
+    Ok(update)
+}
+
+/// Read a cover image and pick where it goes: over the current cover, or
+/// into a new manifest item next to the OPF
+fn new_cover(
+    package: &EpubPackage,
+    archive: &ZipArchive<File>,
+    image: &Path,
+) -> Result<NewCover, AppError> {
+    let media_type = image_media_type(image).ok_or_else(|| {
+        AppError::InvalidInput(format!(
+            "Cover must be a JPEG, PNG, GIF or WebP image: {}",
+            image.display()
+        ))
+    })?;
+    let data = fs::read(image)?;
+    if let Some(item) = package.cover_item() {
+        return Ok(NewCover {
+            id: item.id.clone(),
+            path: item.href.clone(),
+            href: None,
+            media_type,
+            data,
+        });
+    }
+    let extension = image
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let mut suffix = String::new();
+    for n in 1.. {
+        let id = format!("cover-image{}", suffix);
+        let href = format!("cover{}.{}", suffix, extension);
+        let path = resolve_epub_href(&package.opf_path, &href);
+        if package.item(&id).is_none() && archive.index_for_name(&path).is_none() {
+            return Ok(NewCover {
+                id,
+                path,
+                href: Some(href),
+                media_type,
+                data,
+            });
+        }
+        suffix = format!("-{}", n);
+    }
+    unreachable!()
+}
+
+/// Write an element into the OPF on a line of its own
+fn write_opf_element(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    attributes: &[(&str, &str)],
+    text: Option<&str>,
+) -> Result<(), AppError> {
+    writer.write_event(Event::Text(BytesText::new("\n    ")))?;
+    let element = writer
+        .create_element(name)
+        .with_attributes(attributes.iter().copied());
+    match text {
+        Some(text) => element.write_text_content(BytesText::new(text))?,
+        None => element.write_empty()?,
+    };
+    Ok(())
+}
+
+/// A manifest item with its media type replaced
+fn with_media_type(item: &BytesStart, media_type: &str) -> BytesStart<'static> {
+    let mut updated = BytesStart::new(String::from_utf8_lossy(item.name().as_ref()).to_string());
+    for attribute in item.attributes().flatten() {
+        if attribute.key.local_name().as_ref() != b"media-type" {
+            updated.push_attribute(attribute);
+        }
+    }
+    updated.push_attribute(("media-type", media_type));
+    updated
+}
+
+/// Rewrite an OPF package with updated metadata
+///
+/// Replaced elements are dropped with the `refines` metadata pointing at
+/// them (such as creator roles), and their new values are appended to
+/// `<metadata>`. Everything else is kept as written.
+fn rewrite_opf(
+    opf_xml: &str,
+    update: &EpubMetadataUpdate,
+    cover: Option<&NewCover>,
+) -> Result<Vec<u8>, AppError> {
+    let replaced: Vec<&str> = [
+        ("title", update.title.is_some()),
+        ("authors", update.authors.is_some()),
+        ("series", update.series.is_some()),
+        ("cover", cover.is_some_and(|cover| cover.href.is_some())),
+    ]
+    .into_iter()
+    .filter_map(|(field, on)| on.then_some(field))
+    .collect();
+
+    // First pass: the EPUB version, the Dublin Core prefix and the ids of
+    // replaced elements
+    let mut version = String::new();
+    let mut dc_prefix = None;
+    let mut dropped_ids = Vec::new();
+    let mut reader = Reader::from_str(opf_xml);
+    loop {
+        match reader.read_event().map_err(opf_err)? {
+            Event::Start(e) | Event::Empty(e) => {
+                if e.local_name().as_ref() == b"package" {
+                    version = xml_attr(&e, "version").unwrap_or_default();
+                }
+                let field = metadata_field(&e);
+                if dc_prefix.is_none() && matches!(field, Some("title" | "authors")) {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    dc_prefix = Some(
+                        name.split_once(':')
+                            .map(|(prefix, _)| format!("{}:", prefix))
+                            .unwrap_or_default(),
+                    );
+                }
+                if field.is_some_and(|field| replaced.contains(&field)) {
+                    dropped_ids.extend(xml_attr(&e, "id"));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    let dc = dc_prefix.unwrap_or_else(|| "dc:".to_string());
+    let epub3 = version.starts_with('3');
+    let drops = |e: &BytesStart| {
+        metadata_field(e).is_some_and(|field| replaced.contains(&field))
+            || xml_attr(e, "refines").is_some_and(|target| {
+                dropped_ids
+                    .iter()
+                    .any(|id| target.trim_start_matches('#') == id)
+            })
+    };
+    let is_cover_item = |e: &BytesStart| {
+        e.local_name().as_ref() == b"item"
+            && cover.is_some_and(|cover| {
+                cover.href.is_none() && xml_attr(e, "id").as_deref() == Some(&cover.id)
+            })
+    };
+
+    let mut reader = Reader::from_str(opf_xml);
+    let mut writer = Writer::new(Vec::new());
+    // Whitespace is held back so it goes with a dropped element
+    let mut pending_space = None;
+    let mut skip_depth = 0usize;
+    loop {
+        let event = reader.read_event().map_err(opf_err)?;
+        if skip_depth > 0 {
+            match event {
+                Event::Start(_) => skip_depth += 1,
+                Event::End(_) => skip_depth -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            continue;
+        }
+        let event = match event {
+            Event::Text(text) if text.iter().all(u8::is_ascii_whitespace) => {
+                if let Some(space) = pending_space.replace(Event::Text(text)) {
+                    writer.write_event(space)?;
+                }
+                continue;
+            }
+            Event::Start(e) if drops(&e) => {
+                skip_depth = 1;
+                pending_space = None;
+                continue;
+            }
+            Event::Empty(e) if drops(&e) => {
+                pending_space = None;
+                continue;
+            }
+            Event::Start(e) if is_cover_item(&e) => {
+                Event::Start(with_media_type(&e, cover.map_or("", |c| c.media_type)))
+            }
+            Event::Empty(e) if is_cover_item(&e) => {
+                Event::Empty(with_media_type(&e, cover.map_or("", |c| c.media_type)))
+            }
+            Event::End(e) if e.local_name().as_ref() == b"metadata" => {
+                if let Some(title) = &update.title {
+                    write_opf_element(&mut writer, &format!("{}title", dc), &[], Some(title))?;
+                }
+                for author in update.authors.iter().flatten() {
+                    write_opf_element(&mut writer, &format!("{}creator", dc), &[], Some(author))?;
+                }
+                if let Some(series) = update.series.as_deref().filter(|s| !s.is_empty()) {
+                    let index = update.series_index.map(|index| index.to_string());
+                    write_opf_element(
+                        &mut writer,
+                        "meta",
+                        &[("name", "calibre:series"), ("content", series)],
+                        None,
+                    )?;
+                    if let Some(index) = &index {
+                        write_opf_element(
+                            &mut writer,
+                            "meta",
+                            &[("name", "calibre:series_index"), ("content", index)],
+                            None,
+                        )?;
+                    }
+                    if epub3 {
+                        write_opf_element(
+                            &mut writer,
+                            "meta",
+                            &[("property", "belongs-to-collection"), ("id", "series")],
+                            Some(series),
+                        )?;
+                        write_opf_element(
+                            &mut writer,
+                            "meta",
+                            &[("refines", "#series"), ("property", "collection-type")],
+                            Some("series"),
+                        )?;
+                        if let Some(index) = &index {
+                            write_opf_element(
+                                &mut writer,
+                                "meta",
+                                &[("refines", "#series"), ("property", "group-position")],
+                                Some(index),
+                            )?;
+                        }
+                    }
+                }
+                if let Some(cover) = cover.filter(|cover| cover.href.is_some()) {
+                    write_opf_element(
+                        &mut writer,
+                        "meta",
+                        &[("name", "cover"), ("content", &cover.id)],
+                        None,
+                    )?;
+                }
+                Event::End(e)
+            }
+            Event::End(e) if e.local_name().as_ref() == b"manifest" => {
+                if let Some((cover, href)) =
+                    cover.and_then(|cover| Some((cover, cover.href.as_deref()?)))
+                {
+                    let mut attributes = vec![
+                        ("id", cover.id.as_str()),
+                        ("href", href),
+                        ("media-type", cover.media_type),
+                    ];
+                    if epub3 {
+                        attributes.push(("properties", "cover-image"));
+                    }
+                    write_opf_element(&mut writer, "item", &attributes, None)?;
+                }
+                Event::End(e)
+            }
+            Event::Eof => break,
+            event => event,
+        };
+        if let Some(space) = pending_space.take() {
+            writer.write_event(space)?;
+        }
+        writer.write_event(event)?;
+    }
+    if let Some(space) = pending_space {
+        writer.write_event(space)?;
+    }
+    Ok(writer.into_inner())
+}
+
+/// Write metadata and a new cover into an EPUB
+///
+/// The archive is rewritten to a temporary file next to it and checked to
+/// open before it replaces the original, so a failure leaves the book as
+/// it was. Entries other than the OPF and the cover are copied unchanged.
+pub fn write_epub_metadata(
+    path: &Path,
+    update: &EpubMetadataUpdate,
+) -> Result<EpubPackage, AppError> {
+    let update = normalize_metadata_update(update)?;
+    let mut archive = open_epub(path)?;
+    let package = read_epub_package(&mut archive)?;
+    let opf = read_epub_entry(&mut archive, &package.opf_path)?;
+    let cover = match &update.cover_path {
+        Some(image) => Some(new_cover(&package, &archive, Path::new(image))?),
+        None => None,
+    };
+    let opf = rewrite_opf(&String::from_utf8_lossy(&opf), &update, cover.as_ref())?;
+
+    let temp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let written = (|| {
+        let mut zip = ZipWriter::new(BufWriter::new(File::create(&temp_path)?));
+        let options = SimpleFileOptions::default();
+        let mut cover_written = false;
+        for index in 0..archive.len() {
+            let entry = archive.by_index_raw(index).map_err(epub_zip_err)?;
+            let name = entry.name().to_string();
+            if name == package.opf_path {
+                zip.start_file(name, options).map_err(epub_zip_err)?;
+                zip.write_all(&opf)?;
+            } else if let Some(cover) = cover.as_ref().filter(|cover| cover.path == name) {
+                zip.start_file(name, options).map_err(epub_zip_err)?;
+                zip.write_all(&cover.data)?;
+                cover_written = true;
+            } else {
+                zip.raw_copy_file(entry).map_err(epub_zip_err)?;
+            }
+        }
+        if let Some(cover) = cover.as_ref().filter(|_| !cover_written) {
+            zip.start_file(cover.path.as_str(), options)
+                .map_err(epub_zip_err)?;
+            zip.write_all(&cover.data)?;
+        }
+        zip.finish().map_err(epub_zip_err)?.flush()?;
+        read_epub_package(&mut open_epub(&temp_path)?)
+    })();
+    drop(archive);
+    let written = written.and_then(|package| {
+        fs::rename(&temp_path, path)?;
+        Ok(package)
+    });
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Extract the plain text of an EPUB, per chapter in reading order
+#[tauri::command]
+pub async fn extract_epub_text(
+    path: String,
+    chapter: Option<usize>,
+) -> Result<EpubTextContent, AppError> {
+    let file_path = Path::new(&path).to_path_buf();
+    if !file_path.is_file() {
+        return Err(AppError::NotFound(format!("File not found: {}", path)));
+    }
+    tauri::async_runtime::spawn_blocking(move || read_epub_text(&file_path, chapter))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Get the spine order, table of contents and manifest of an EPUB
+#[tauri::command]
+pub async fn get_epub_structure(path: String) -> Result<EpubStructure, AppError> {
+    let file_path = Path::new(&path).to_path_buf();
+    if !file_path.is_file() {
+        return Err(AppError::NotFound(format!("File not found: {}", path)));
+    }
+    tauri::async_runtime::spawn_blocking(move || read_epub_structure(&file_path))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Write title, authors, series or a new cover into an EPUB file
+#[tauri::command]
+pub async fn update_epub_metadata(
+    path: String,
+    fields: EpubMetadataUpdate,
+) -> Result<EpubPackage, AppError> {
+    let file_path = Path::new(&path).to_path_buf();
+    if !file_path.is_file() {
+        return Err(AppError::NotFound(format!("File not found: {}", path)));
+    }
+    let package =
+        tauri::async_runtime::spawn_blocking(move || write_epub_metadata(&file_path, &fields))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))??;
+    log::info!("EPUB metadata updated: {}", path);
+    Ok(package)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    pub(crate) const CONTAINER_XML: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+    pub(crate) const OPF_XML: &str = r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Sample Book</dc:title>
+    <dc:creator>Jane Doe</dc:creator>
+    <dc:language>en</dc:language>
+    <dc:identifier>urn:isbn:9780000000002</dc:identifier>
+    <meta name="cover" content="cover-img"/>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="ch1" href="Text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+    <item id="ch2" href="Text/chapter2.xhtml" media-type="application/xhtml+xml"/>
+    <item id="cover-img" href="../images/cover.jpg" media-type="image/jpeg"/>
+  </manifest>
+  <spine toc="ncx">
+    <itemref idref="ch1"/>
+    <itemref idref="ch2"/>
+  </spine>
+</package>"#;
+
+    /// Write a zip file with the given entries
+    pub(crate) fn write_epub(path: &Path, entries: &[(&str, &[u8])]) {
+        let file = File::create(path).unwrap();
+        let mut writer = ZipWriter::new(file);
+        for (name, content) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn resolve_epub_href_normalizes_relative_paths() {
+        assert_eq!(
+            resolve_epub_href("OEBPS/content.opf", "Text/ch%201.xhtml#p2"),
+            "OEBPS/Text/ch 1.xhtml"
+        );
+        assert_eq!(
+            resolve_epub_href("OEBPS/Text/ch1.xhtml", "../images/a.png"),
+            "OEBPS/images/a.png"
+        );
+        assert_eq!(resolve_epub_href("content.opf", "ch1.xhtml"), "ch1.xhtml");
+    }
+
+    #[test]
+    fn parse_opf_reads_metadata_manifest_and_spine() {
+        assert_eq!(
+            find_opf_path(CONTAINER_XML).as_deref(),
+            Some("OEBPS/content.opf")
+        );
+
+        let package = parse_opf(OPF_XML, "OEBPS/content.opf");
+
+        assert_eq!(package.title.as_deref(), Some("Sample Book"));
+        assert_eq!(package.creators, vec!["Jane Doe"]);
+        assert_eq!(package.language.as_deref(), Some("en"));
+        assert_eq!(package.spine, vec!["ch1", "ch2"]);
+        assert_eq!(package.toc_id.as_deref(), Some("ncx"));
+        assert_eq!(
+            package.item("ch1").unwrap().href,
+            "OEBPS/Text/chapter 1.xhtml"
+        );
+        assert_eq!(package.cover_item().unwrap().href, "images/cover.jpg");
+    }
+
+    #[test]
+    fn read_epub_cover_returns_image_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.epub");
+        write_epub(
+            &path,
+            &[
+                ("META-INF/container.xml", CONTAINER_XML.as_bytes()),
+                ("OEBPS/content.opf", OPF_XML.as_bytes()),
+                ("images/cover.jpg", b"jpeg-bytes"),
+            ],
+        );
+
+        let cover = read_epub_cover(&path).unwrap();
+
+        assert_eq!(cover.as_deref(), Some(&b"jpeg-bytes"[..]));
+    }
+
+    #[test]
+    fn xhtml_to_text_strips_markup_and_skips_head() {
+        let xhtml = r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>Chapter One</title><style>p { color: red; }</style></head>
+<body>
+  <h1>The <em>Beginning</em></h1>
+  <p>First&nbsp;line with   <b>bold</b>
+     text &amp; more.</p>
+  <script>var x = 1;</script>
+  <p>Second<br/>line</p>
+</body>
+</html>"#;
+
+        let result = xhtml_to_text(xhtml);
+
+        assert_eq!(result.title.as_deref(), Some("The Beginning"));
+        assert_eq!(
+            result.text,
+            "The Beginning\nFirst line with bold text & more.\nSecond\nline"
+        );
+    }
+
+    #[test]
+    fn read_epub_text_returns_chapters_in_spine_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.epub");
+        write_epub(
+            &path,
+            &[
+                ("META-INF/container.xml", CONTAINER_XML.as_bytes()),
+                ("OEBPS/content.opf", OPF_XML.as_bytes()),
+                (
+                    "OEBPS/Text/chapter 1.xhtml",
+                    b"<html><body><h2>One</h2><p>Hello</p></body></html>",
+                ),
+                (
+                    "OEBPS/Text/chapter2.xhtml",
+                    b"<html><head><title>Two</title></head><body><p>World</p></body></html>",
+                ),
+            ],
+        );
+
+        let all = read_epub_text(&path, None).unwrap();
+        assert_eq!(all.chapter_count, 2);
+        assert_eq!(all.chapters.len(), 2);
+        assert_eq!(all.chapters[0].title.as_deref(), Some("One"));
+        assert_eq!(all.chapters[0].text, "One\nHello");
+        assert_eq!(all.chapters[1].title.as_deref(), Some("Two"));
+
+        let second = read_epub_text(&path, Some(1)).unwrap();
+        assert_eq!(second.chapters.len(), 1);
+        assert_eq!(second.chapters[0].index, 1);
+        assert_eq!(second.chapters[0].text, "World");
+
+        assert!(read_epub_text(&path, Some(5)).is_err());
+    }
+
+    const NAV_XHTML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body>
+  <nav epub:type="landmarks"><ol><li><a href="cover.xhtml">Cover</a></li></ol></nav>
+  <nav epub:type="toc">
+    <ol>
+      <li><a href="Text/chapter%201.xhtml">Chapter
+          One</a></li>
+      <li><span>Part Two</span>
+        <ol>
+          <li><a href="Text/chapter2.xhtml#s1">Section 2.1</a></li>
+        </ol>
+      </li>
+    </ol>
+  </nav>
+</body>
+</html>"#;
+
+    #[test]
+    fn parse_nav_toc_builds_nested_entries() {
+        let toc = parse_nav_toc(NAV_XHTML, "OEBPS/nav.xhtml");
+
+        assert_eq!(toc.len(), 2);
+        assert_eq!(toc[0].label, "Chapter One");
+        assert_eq!(toc[0].href.as_deref(), Some("OEBPS/Text/chapter 1.xhtml"));
+        assert_eq!(toc[1].label, "Part Two");
+        assert_eq!(toc[1].href, None);
+        assert_eq!(toc[1].children[0].label, "Section 2.1");
+        assert_eq!(toc[1].children[0].fragment.as_deref(), Some("s1"));
+    }
+
+    #[test]
+    fn parse_ncx_toc_reads_nav_points() {
+        let ncx = r#"<?xml version="1.0"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <navMap>
+    <navPoint id="p1" playOrder="1">
+      <navLabel><text>Chapter 1</text></navLabel>
+      <content src="Text/chapter%201.xhtml"/>
+      <navPoint id="p2" playOrder="2">
+        <navLabel><text>Scene &amp; Setting</text></navLabel>
+        <content src="Text/chapter%201.xhtml#scene"/>
+      </navPoint>
+    </navPoint>
+  </navMap>
+  <pageList><pageTarget><navLabel><text>1</text></navLabel><content src="x.xhtml"/></pageTarget></pageList>
+</ncx>"#;
+
+        let toc = parse_ncx_toc(ncx, "OEBPS/toc.ncx");
+
+        assert_eq!(toc.len(), 1);
+        assert_eq!(toc[0].label, "Chapter 1");
+        assert_eq!(toc[0].children[0].label, "Scene & Setting");
+        assert_eq!(toc[0].children[0].fragment.as_deref(), Some("scene"));
+    }
+
+    #[test]
+    fn write_epub_metadata_rewrites_the_opf_and_cover() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.epub");
+        let opf = OPF_XML.replace(
+            "<dc:creator>Jane Doe</dc:creator>",
+            "<dc:creator id=\"a1\">Jane Doe</dc:creator>\n    \
+             <meta refines=\"#a1\" property=\"role\">aut</meta>\n    \
+             <meta name=\"calibre:series\" content=\"Old\"/>",
+        );
+        write_epub(
+            &path,
+            &[
+                ("mimetype", b"application/epub+zip"),
+                ("META-INF/container.xml", CONTAINER_XML.as_bytes()),
+                ("OEBPS/content.opf", opf.as_bytes()),
+                ("images/cover.jpg", b"jpeg-bytes"),
+            ],
+        );
+        let cover = dir.path().join("new.png");
+        fs::write(&cover, b"png-bytes").unwrap();
+
+        let update = EpubMetadataUpdate {
+            title: Some(" Fixed & Better ".to_string()),
+            authors: Some(vec!["A. Writer".to_string(), "B. Writer".to_string()]),
+            series: Some("Saga".to_string()),
+            series_index: Some(2.0),
+            cover_path: Some(cover.to_string_lossy().to_string()),
+        };
+        let package = write_epub_metadata(&path, &update).unwrap();
+
+        assert_eq!(package.title.as_deref(), Some("Fixed & Better"));
+        assert_eq!(package.creators, vec!["A. Writer", "B. Writer"]);
+        assert_eq!(package.language.as_deref(), Some("en"));
+        assert_eq!(package.spine, vec!["ch1", "ch2"]);
+        let cover_item = package.cover_item().unwrap();
+        assert_eq!(cover_item.media_type, "image/png");
+        assert_eq!(read_epub_cover(&path).unwrap().unwrap(), b"png-bytes");
+
+        let mut archive = open_epub(&path).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        let opf =
+            String::from_utf8(read_epub_entry(&mut archive, "OEBPS/content.opf").unwrap()).unwrap();
+        assert!(!opf.contains("refines=\"#a1\""));
+        assert!(!opf.contains("Old"));
+        assert!(opf.contains(r#"<meta name="calibre:series_index" content="2"/>"#));
+        assert!(opf.contains(r##"<meta refines="#series" property="group-position">2</meta>"##));
+        assert!(opf.contains("Fixed &amp; Better"));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        let err = write_epub_metadata(&path, &EpubMetadataUpdate::default()).unwrap_err();
+        assert!(matches!(err, AppError::InvalidInput(_)));
+    }
+
+    #[test]
+    fn read_epub_structure_links_toc_to_spine() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.epub");
+        write_epub(
+            &path,
+            &[
+                ("META-INF/container.xml", CONTAINER_XML.as_bytes()),
+                ("OEBPS/content.opf", OPF_XML.as_bytes()),
+                ("OEBPS/nav.xhtml", NAV_XHTML.as_bytes()),
+            ],
+        );
+
+        let structure = read_epub_structure(&path).unwrap();
+
+        assert_eq!(structure.toc_source, "nav");
+        assert_eq!(structure.spine.len(), 2);
+        assert_eq!(structure.spine[0].media_type, "application/xhtml+xml");
+        assert!(structure.spine[1].linear);
+        assert_eq!(structure.toc[0].spine_index, Some(0));
+        assert_eq!(structure.toc[1].children[0].spine_index, Some(1));
+    }
+}
@@ -0,0 +1,259 @@
+//! Typed events from the backend to the frontend
+//!
+//! Every event the backend emits has a payload struct implementing
+//! `BackendEvent`, which ties it to its channel name, and is listed in
+//! `event_channels` so the frontend can discover what it may listen for.
+//! Channel names are kebab-case and stable: renaming one breaks listeners.
+//! New events are added here, and emitted with `emit_event`.
+
+use crate::commands::ai_batch::{AIBatchResult, AI_BATCH_RESULT_EVENT};
+use crate::commands::appearance::{SystemTheme, SYSTEM_THEME_EVENT};
+use crate::commands::conversations::{
+    ConversationSummary, RetentionReport, CONVERSATIONS_PRUNED_EVENT, CONVERSATION_TITLE_EVENT,
+};
+use crate::commands::conversion::{ConversionProgress, CONVERSION_PROGRESS_EVENT};
+use crate::commands::feeds::{FeedRefreshResult, FEEDS_UPDATED_EVENT};
+use crate::commands::file_ops::{DataTransferProgress, DATA_TRANSFER_PROGRESS_EVENT};
+use crate::commands::lan_sync::{LanSyncReport, LAN_SYNC_EVENT};
+use crate::commands::library::{LibraryScanProgress, LIBRARY_SCAN_PROGRESS_EVENT};
+#[cfg(feature = "local-inference")]
+use crate::commands::local_inference::{LocalModelDownloadProgress, LOCAL_MODEL_DOWNLOAD_EVENT};
+use crate::commands::logging::{LogEntry, LOG_ENTRY_EVENT};
+use crate::commands::network::{NetworkStatus, NETWORK_STATUS_EVENT};
+use crate::commands::power::{PowerEvent, POWER_EVENT};
+use crate::commands::rag::{RagIndexProgress, RAG_INDEX_PROGRESS_EVENT};
+use crate::commands::settings::{SettingChange, SETTING_CHANGED_EVENT};
+use crate::commands::tts::{TtsBoundary, TtsStateEvent, TTS_BOUNDARY_EVENT, TTS_STATE_EVENT};
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+use crate::commands::updates::{UpdateProgress, UPDATE_PROGRESS_EVENT};
+use serde::Serialize;
+use tauri::Emitter;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// A payload and the channel it is emitted on
+pub trait BackendEvent: Serialize {
+    const CHANNEL: &'static str;
+}
+
+/// A channel for `list_event_channels`
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EventChannel {
+    pub name: &'static str,
+    /// Rust type of the payload
+    pub payload: &'static str,
+    pub description: &'static str,
+}
+
+impl BackendEvent for AIBatchResult {
+    const CHANNEL: &'static str = AI_BATCH_RESULT_EVENT;
+}
+
+impl BackendEvent for SystemTheme {
+    const CHANNEL: &'static str = SYSTEM_THEME_EVENT;
+}
+
+impl BackendEvent for ConversationSummary {
+    const CHANNEL: &'static str = CONVERSATION_TITLE_EVENT;
+}
+
+impl BackendEvent for RetentionReport {
+    const CHANNEL: &'static str = CONVERSATIONS_PRUNED_EVENT;
+}
+
+impl BackendEvent for ConversionProgress {
+    const CHANNEL: &'static str = CONVERSION_PROGRESS_EVENT;
+}
+
+impl BackendEvent for DataTransferProgress {
+    const CHANNEL: &'static str = DATA_TRANSFER_PROGRESS_EVENT;
+}
+
+impl BackendEvent for Vec<FeedRefreshResult> {
+    const CHANNEL: &'static str = FEEDS_UPDATED_EVENT;
+}
+
+impl BackendEvent for LanSyncReport {
+    const CHANNEL: &'static str = LAN_SYNC_EVENT;
+}
+
+impl BackendEvent for LibraryScanProgress {
+    const CHANNEL: &'static str = LIBRARY_SCAN_PROGRESS_EVENT;
+}
+
+#[cfg(feature = "local-inference")]
+impl BackendEvent for LocalModelDownloadProgress {
+    const CHANNEL: &'static str = LOCAL_MODEL_DOWNLOAD_EVENT;
+}
+
+impl BackendEvent for LogEntry {
+    const CHANNEL: &'static str = LOG_ENTRY_EVENT;
+}
+
+impl BackendEvent for NetworkStatus {
+    const CHANNEL: &'static str = NETWORK_STATUS_EVENT;
+}
+
+impl BackendEvent for PowerEvent {
+    const CHANNEL: &'static str = POWER_EVENT;
+}
+
+impl BackendEvent for RagIndexProgress {
+    const CHANNEL: &'static str = RAG_INDEX_PROGRESS_EVENT;
+}
+
+impl BackendEvent for SettingChange {
+    const CHANNEL: &'static str = SETTING_CHANGED_EVENT;
+}
+
+impl BackendEvent for TtsBoundary {
+    const CHANNEL: &'static str = TTS_BOUNDARY_EVENT;
+}
+
+impl BackendEvent for TtsStateEvent {
+    const CHANNEL: &'static str = TTS_STATE_EVENT;
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+impl BackendEvent for UpdateProgress {
+    const CHANNEL: &'static str = UPDATE_PROGRESS_EVENT;
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn channel<E: BackendEvent>(payload: &'static str, description: &'static str) -> EventChannel {
+    EventChannel {
+        name: E::CHANNEL,
+        payload,
+        description,
+    }
+}
+
+/// Every channel the backend emits on, by name
+pub fn event_channels() -> Vec<EventChannel> {
+    let mut channels = vec![
+        channel::<AIBatchResult>("AIBatchResult", "A prompt of an AI batch request completed"),
+        channel::<SystemTheme>("SystemTheme", "The system theme changed"),
+        channel::<ConversationSummary>(
+            "ConversationSummary",
+            "A conversation was given a generated title",
+        ),
+        channel::<RetentionReport>(
+            "RetentionReport",
+            "Conversations were archived or deleted by retention rules",
+        ),
+        channel::<ConversionProgress>(
+            "ConversionProgress",
+            "Output of a running format conversion",
+        ),
+        channel::<DataTransferProgress>(
+            "DataTransferProgress",
+            "Progress of writing an export or reading an import",
+        ),
+        channel::<Vec<FeedRefreshResult>>(
+            "Vec<FeedRefreshResult>",
+            "A scheduled feed refresh found new entries",
+        ),
+        channel::<LanSyncReport>("LanSyncReport", "A peer finished syncing over the LAN"),
+        channel::<LibraryScanProgress>("LibraryScanProgress", "Progress of a library folder scan"),
+        channel::<LogEntry>("LogEntry", "A log entry, while log streaming is on"),
+        channel::<NetworkStatus>("NetworkStatus", "The network state changed"),
+        channel::<PowerEvent>(
+            "PowerEvent",
+            "The system suspended, resumed or changed battery saver",
+        ),
+        channel::<RagIndexProgress>(
+            "RagIndexProgress",
+            "Progress of embedding a document for retrieval",
+        ),
+        channel::<SettingChange>("SettingChange", "A setting changed"),
+        channel::<TtsBoundary>("TtsBoundary", "Speech reached a word or sentence"),
+        channel::<TtsStateEvent>("TtsStateEvent", "An utterance started, ended or failed"),
+    ];
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    channels.push(channel::<UpdateProgress>(
+        "UpdateProgress",
+        "Progress of downloading and installing an update",
+    ));
+    #[cfg(feature = "local-inference")]
+    channels.push(channel::<LocalModelDownloadProgress>(
+        "LocalModelDownloadProgress",
+        "Progress of downloading a local model",
+    ));
+    channels.sort_by_key(|channel| channel.name);
+    channels
+}
+
+/// Emit an event to every webview, logging failures
+pub fn emit_event<E: BackendEvent>(app: &tauri::AppHandle, payload: &E) {
+    if let Err(e) = app.emit(E::CHANNEL, payload) {
+        log::warn!("Failed to emit {}: {}", E::CHANNEL, e);
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// List the event channels the frontend can listen on
+#[tauri::command]
+pub fn list_event_channels() -> Vec<EventChannel> {
+    event_channels()
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn channels_are_unique_and_payloads_serialize() {
+        let channels = event_channels();
+        for pair in channels.windows(2) {
+            assert_ne!(pair[0].name, pair[1].name);
+        }
+        for channel in &channels {
+            assert!(channel
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c == '-'));
+        }
+
+        let change = SettingChange {
+            key: "network.requestTimeoutSecs".to_string(),
+            value: json!(30),
+        };
+        assert_eq!(SettingChange::CHANNEL, "setting-changed");
+        assert_eq!(
+            serde_json::to_value(&change).unwrap(),
+            json!({"key": "network.requestTimeoutSecs", "value": 30})
+        );
+        let progress = RagIndexProgress {
+            document_id: "doc-1".to_string(),
+            embedded: 64,
+            total: 128,
+        };
+        assert_eq!(
+            serde_json::to_value(&progress).unwrap(),
+            json!({"documentId": "doc-1", "embedded": 64, "total": 128})
+        );
+        let state = TtsStateEvent {
+            utterance_id: "u-1".to_string(),
+            state: "error".to_string(),
+            error: Some("no voice".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_value(&state).unwrap(),
+            json!({"utteranceId": "u-1", "state": "error", "error": "no voice"})
+        );
+    }
+}
@@ -0,0 +1,19 @@
+//! RSS/Atom feed reader
+//!
+//! Feeds and their entries are stored in the library database so articles can
+//! be read, clipped or sent to AI for summarization alongside documents:
+//! - RSS 2.0, RSS 1.0 and Atom parsing
+//! - Subscriptions with feed discovery from web pages
+//! - Conditional refresh (ETag/Last-Modified) and read state tracking
+//! - A background scheduler refreshing feeds at their configured interval
+
+mod types;
+mod parser;
+mod storage;
+mod refresh;
+
+// Re-export all public items
+pub use types::*;
+pub use parser::*;
+pub use storage::*;
+pub use refresh::*;
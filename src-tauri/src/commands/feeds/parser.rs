@@ -0,0 +1,335 @@
+//! RSS 2.0, RSS 1.0 (RDF) and Atom feed parsing
+
+use super::types::{ParsedEntry, ParsedFeed};
+use crate::commands::epub::{decode_xhtml_text, xml_attr};
+use crate::error::AppError;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use url::Url;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Parse the date formats found in feeds into unix seconds
+///
+/// RSS uses RFC 2822 and Atom RFC 3339; a few common variants are accepted too.
+pub fn parse_feed_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.timestamp());
+    }
+    if let Ok(date) = DateTime::parse_from_rfc2822(value) {
+        return Some(date.timestamp());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(date.and_utc().timestamp());
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| date.and_utc().timestamp())
+}
+
+fn element_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.name().as_ref()).to_lowercase()
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn resolve_link(base_url: &Url, href: &str) -> Option<String> {
+    base_url.join(href.trim()).ok().map(|url| url.to_string())
+}
+
+fn set_if_empty(field: &mut Option<String>, value: &str) {
+    if field.is_none() && !value.is_empty() {
+        *field = Some(value.to_string());
+    }
+}
+
+/// The alternate (HTML) link of an Atom `<link>` element
+fn atom_alternate_link(e: &BytesStart, base_url: &Url) -> Option<String> {
+    let rel = xml_attr(e, "rel").unwrap_or_else(|| "alternate".to_string());
+    if rel != "alternate" {
+        return None;
+    }
+    xml_attr(e, "href").and_then(|href| resolve_link(base_url, &href))
+}
+
+// ============================================================================
+// Parsing
+// ============================================================================
+
+/// Parse an RSS or Atom document
+///
+/// Relative links are resolved against the feed URL. Entries without any
+/// identity (guid, link or title) are dropped.
+pub fn parse_feed(xml: &str, base_url: &Url) -> Result<ParsedFeed, AppError> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().check_end_names = false;
+
+    let mut feed = ParsedFeed::default();
+    let mut entry: Option<ParsedEntry> = None;
+    let mut stack: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut is_feed = false;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = element_name(&e);
+                if stack.is_empty() {
+                    is_feed = matches!(name.as_str(), "rss" | "rdf:rdf" | "feed");
+                    if !is_feed {
+                        break;
+                    }
+                }
+                text.clear();
+                match local_name(&name) {
+                    "item" | "entry" => entry = Some(ParsedEntry::default()),
+                    "link" => {
+                        if let Some(link) = atom_alternate_link(&e, base_url) {
+                            match entry.as_mut() {
+                                Some(entry) => set_if_empty(&mut entry.url, &link),
+                                None => set_if_empty(&mut feed.site_url, &link),
+                            }
+                        }
+                    }
+                    "content" | "summary"
+                        if entry.is_some() && xml_attr(&e, "type").as_deref() == Some("xhtml") =>
+                    {
+                        // Inline XHTML content is kept as markup
+                        let markup = reader
+                            .read_text(e.name())
+                            .map(|markup| markup.trim().to_string())
+                            .unwrap_or_default();
+                        if let Some(entry) = entry.as_mut() {
+                            let field = if local_name(&name) == "content" {
+                                &mut entry.content
+                            } else {
+                                &mut entry.summary
+                            };
+                            set_if_empty(field, &markup);
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+                stack.push(name);
+            }
+            Ok(Event::Empty(e)) => {
+                let name = element_name(&e);
+                if local_name(&name) == "link" {
+                    if let Some(link) = atom_alternate_link(&e, base_url) {
+                        match entry.as_mut() {
+                            Some(entry) => set_if_empty(&mut entry.url, &link),
+                            None => set_if_empty(&mut feed.site_url, &link),
+                        }
+                    }
+                }
+            }
+            Ok(Event::Text(e)) => text.push_str(&decode_xhtml_text(&e)),
+            Ok(Event::CData(e)) => text.push_str(&String::from_utf8_lossy(&e)),
+            Ok(Event::End(_)) => {
+                let Some(name) = stack.pop() else {
+                    continue;
+                };
+                let value = text.trim().to_string();
+                text.clear();
+                let parent = stack.last().map(|p| local_name(p).to_string());
+
+                if matches!(local_name(&name), "item" | "entry") {
+                    if let Some(finished) = entry.take() {
+                        if finished.identity().is_some() {
+                            feed.entries.push(finished);
+                        }
+                    }
+                    continue;
+                }
+
+                match entry.as_mut() {
+                    Some(entry) => match (name.as_str(), parent.as_deref()) {
+                        ("title" | "atom:title", Some("item" | "entry")) => {
+                            set_if_empty(&mut entry.title, &value)
+                        }
+                        ("link", Some("item")) => {
+                            if let Some(link) = resolve_link(base_url, &value) {
+                                set_if_empty(&mut entry.url, &link);
+                            }
+                        }
+                        ("guid" | "id" | "atom:id", Some("item" | "entry")) => {
+                            set_if_empty(&mut entry.guid, &value)
+                        }
+                        ("author" | "dc:creator", Some("item" | "entry"))
+                        | ("name", Some("author")) => set_if_empty(&mut entry.author, &value),
+                        ("description" | "summary", Some("item" | "entry")) => {
+                            set_if_empty(&mut entry.summary, &value)
+                        }
+                        ("content:encoded" | "content", Some("item" | "entry")) => {
+                            set_if_empty(&mut entry.content, &value)
+                        }
+                        (
+                            "pubdate" | "published" | "dc:date" | "issued",
+                            Some("item" | "entry"),
+                        ) if entry.published_at.is_none() => {
+                            entry.published_at = parse_feed_date(&value);
+                        }
+                        ("updated" | "modified", Some("entry" | "item")) => {
+                            entry.updated_at = parse_feed_date(&value);
+                        }
+                        _ => {}
+                    },
+                    None => match (local_name(&name), parent.as_deref()) {
+                        ("title", Some("channel" | "feed")) => {
+                            set_if_empty(&mut feed.title, &value)
+                        }
+                        ("link", Some("channel")) => {
+                            if let Some(link) = resolve_link(base_url, &value) {
+                                set_if_empty(&mut feed.site_url, &link);
+                            }
+                        }
+                        ("description" | "subtitle", Some("channel" | "feed")) => {
+                            set_if_empty(&mut feed.description, &value)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                if !is_feed {
+                    return Err(AppError::InvalidInput(format!("Invalid feed XML: {}", e)));
+                }
+                log::debug!("Stopped parsing malformed feed: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    if !is_feed {
+        return Err(AppError::InvalidInput(
+            "Document is not an RSS or Atom feed".to_string(),
+        ));
+    }
+    // Entries without a publication date fall back to their update time
+    for entry in &mut feed.entries {
+        if entry.published_at.is_none() {
+            entry.published_at = entry.updated_at;
+        }
+    }
+    Ok(feed)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("https://blog.example.com/feed.xml").unwrap()
+    }
+
+    #[test]
+    fn parse_rss_feed() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/" xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <channel>
+    <title>Example Blog</title>
+    <link>https://blog.example.com/</link>
+    <description>Notes &amp; essays</description>
+    <image><title>Logo</title><url>https://blog.example.com/logo.png</url></image>
+    <item>
+      <title>First post</title>
+      <link>/posts/1</link>
+      <guid isPermaLink="false">post-1</guid>
+      <dc:creator>Ada</dc:creator>
+      <description>&lt;p&gt;Short &amp;amp; sweet&lt;/p&gt;</description>
+      <content:encoded><![CDATA[<p>Full <b>text</b></p>]]></content:encoded>
+      <pubDate>Tue, 02 Jan 2024 10:00:00 GMT</pubDate>
+    </item>
+    <item>
+      <title>No guid</title>
+      <link>https://blog.example.com/posts/2</link>
+    </item>
+    <item><description>Nothing to identify this entry</description></item>
+  </channel>
+</rss>"#;
+        let feed = parse_feed(xml, &base()).unwrap();
+
+        assert_eq!(feed.title.as_deref(), Some("Example Blog"));
+        assert_eq!(feed.site_url.as_deref(), Some("https://blog.example.com/"));
+        assert_eq!(feed.description.as_deref(), Some("Notes & essays"));
+        assert_eq!(feed.entries.len(), 2);
+
+        let first = &feed.entries[0];
+        assert_eq!(first.guid.as_deref(), Some("post-1"));
+        assert_eq!(
+            first.url.as_deref(),
+            Some("https://blog.example.com/posts/1")
+        );
+        assert_eq!(first.author.as_deref(), Some("Ada"));
+        assert_eq!(first.summary.as_deref(), Some("<p>Short &amp; sweet</p>"));
+        assert_eq!(first.content.as_deref(), Some("<p>Full <b>text</b></p>"));
+        assert_eq!(first.published_at, Some(1704189600));
+        assert_eq!(
+            feed.entries[1].identity().as_deref(),
+            Some("https://blog.example.com/posts/2")
+        );
+    }
+
+    #[test]
+    fn parse_atom_feed() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Atom Site</title>
+  <subtitle>Updates</subtitle>
+  <link rel="self" href="https://atom.example.com/atom.xml"/>
+  <link href="https://atom.example.com/"/>
+  <entry>
+    <title>Hello</title>
+    <id>urn:uuid:1</id>
+    <link rel="alternate" href="/hello"/>
+    <author><name>Grace</name></author>
+    <updated>2024-02-01T12:00:00Z</updated>
+    <summary>Plain summary</summary>
+    <content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><p>Hi there</p></div></content>
+  </entry>
+</feed>"#;
+        let feed = parse_feed(xml, &base()).unwrap();
+
+        assert_eq!(feed.title.as_deref(), Some("Atom Site"));
+        assert_eq!(feed.site_url.as_deref(), Some("https://atom.example.com/"));
+        assert_eq!(feed.description.as_deref(), Some("Updates"));
+        let entry = &feed.entries[0];
+        assert_eq!(entry.guid.as_deref(), Some("urn:uuid:1"));
+        assert_eq!(entry.url.as_deref(), Some("https://blog.example.com/hello"));
+        assert_eq!(entry.author.as_deref(), Some("Grace"));
+        assert_eq!(entry.summary.as_deref(), Some("Plain summary"));
+        assert!(entry
+            .content
+            .as_deref()
+            .unwrap()
+            .contains("<p>Hi there</p>"));
+        assert_eq!(entry.published_at, Some(1706788800));
+    }
+
+    #[test]
+    fn parse_feed_rejects_html_and_parses_dates() {
+        assert!(matches!(
+            parse_feed("<html><body>Not a feed</body></html>", &base()),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert_eq!(parse_feed_date("2024-01-02"), Some(1704153600));
+        assert_eq!(parse_feed_date("2024-01-02 00:00:00"), Some(1704153600));
+        assert_eq!(parse_feed_date("yesterday"), None);
+    }
+}
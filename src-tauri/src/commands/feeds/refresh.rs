@@ -0,0 +1,450 @@
+//! Feed fetching, subscription and the background refresh scheduler
+
+use super::parser::parse_feed;
+use super::storage::{
+    due_feed_ids, get_feed_by_url, get_fetch_state, insert_feed, list_feeds, record_refresh,
+    store_entries, FeedFetchState,
+};
+use super::types::{Feed, FeedInput, FeedRefreshResult, ParsedFeed};
+use crate::commands::clipper::{charset_from_content_type, read_limited};
+use crate::commands::events::emit_event;
+use crate::commands::library::{lock_library, LibraryState};
+use crate::commands::power::is_saving_power;
+use crate::commands::retry::with_retry;
+use crate::error::AppError;
+use encoding_rs::{Encoding, UTF_8};
+use reqwest::header::{CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use std::time::Duration;
+use tauri::Manager;
+use url::Url;
+
+/// Event emitted when a background refresh found new entries
+pub const FEEDS_UPDATED_EVENT: &str = "feeds-updated";
+
+const MAX_FEED_BYTES: usize = 5 * 1024 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often the scheduler looks for feeds that are due
+const SCHEDULER_TICK: Duration = Duration::from_secs(5 * 60);
+/// Delay before the first scheduled refresh, keeps startup quiet
+const SCHEDULER_STARTUP_DELAY: Duration = Duration::from_secs(30);
+
+/// A successful feed response before parsing
+struct FeedResponse {
+    body: String,
+    content_type: Option<String>,
+    url: Url,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Result of a conditional feed request
+pub enum FeedFetch {
+    NotModified,
+    Fetched {
+        feed: Box<ParsedFeed>,
+        /// Final URL after redirects and feed discovery
+        url: Url,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+impl FeedFetch {
+    fn fetched(feed: ParsedFeed, response: FeedResponse) -> Self {
+        FeedFetch::Fetched {
+            feed: Box::new(feed),
+            url: response.url,
+            etag: response.etag,
+            last_modified: response.last_modified,
+        }
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Build the HTTP client used for feed requests
+pub fn feed_http_client() -> Result<reqwest::Client, AppError> {
+    reqwest::Client::builder()
+        .user_agent(concat!("sast-readium/", env!("CARGO_PKG_VERSION")))
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(AppError::from)
+}
+
+/// Read the `encoding` of an XML declaration
+fn xml_declared_encoding(bytes: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(256)]).to_string();
+    let declaration = head.strip_prefix("<?xml")?.split("?>").next()?;
+    let start = declaration.find("encoding=")? + "encoding=".len();
+    let label: String = declaration[start..]
+        .trim_start_matches(['"', '\''])
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .collect();
+    (!label.is_empty()).then_some(label)
+}
+
+/// Decode a feed document using its BOM, declared charset or UTF-8
+pub fn decode_feed(bytes: &[u8], content_type: Option<&str>) -> String {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return encoding
+            .decode_without_bom_handling(&bytes[bom_length..])
+            .0
+            .into_owned();
+    }
+    let encoding = content_type
+        .and_then(charset_from_content_type)
+        .or_else(|| xml_declared_encoding(bytes))
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8);
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+/// Find the feed advertised by an HTML page (`<link rel="alternate">`)
+pub fn discover_feed_url(html: &str, base_url: &Url) -> Option<Url> {
+    use kuchikiki::traits::*;
+
+    let document = kuchikiki::parse_html().one(html).document_node;
+    let links = document.select("link[rel~='alternate'][href]").ok()?;
+    links
+        .filter(|link| {
+            let attributes = link.attributes.borrow();
+            let kind = attributes.get("type").unwrap_or_default().to_lowercase();
+            kind.contains("rss") || kind.contains("atom")
+        })
+        .find_map(|link| {
+            let href = link.attributes.borrow().get("href")?.to_string();
+            base_url.join(href.trim()).ok()
+        })
+}
+
+async fn request_feed(
+    client: &reqwest::Client,
+    url: &Url,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> Result<Option<FeedResponse>, AppError> {
+    let response = with_retry("Feed request", || async {
+        let mut request = client.get(url.clone()).header(
+            reqwest::header::ACCEPT,
+            "application/rss+xml, application/atom+xml, application/xml;q=0.9, text/xml;q=0.9, */*;q=0.5",
+        );
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status != StatusCode::NOT_MODIFIED && !status.is_success() {
+            return Err(AppError::http_status(
+                status,
+                format!("Fetching {} failed with status {}", url, status),
+            ));
+        }
+        Ok(response)
+    })
+    .await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let content_type = header(CONTENT_TYPE).map(|v| v.to_lowercase());
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    let url = response.url().clone();
+    let body = read_limited(response, MAX_FEED_BYTES).await?;
+    Ok(Some(FeedResponse {
+        body: decode_feed(&body, content_type.as_deref()),
+        content_type,
+        url,
+        etag,
+        last_modified,
+    }))
+}
+
+/// Fetch and parse a feed, sending the stored validators for a conditional request
+///
+/// When `discover` is set and the URL points at an HTML page, the feed linked
+/// from the page is fetched instead.
+pub async fn fetch_feed(
+    client: &reqwest::Client,
+    url: &Url,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+    discover: bool,
+) -> Result<FeedFetch, AppError> {
+    let Some(response) = request_feed(client, url, etag, last_modified).await? else {
+        return Ok(FeedFetch::NotModified);
+    };
+
+    let response = match parse_feed(&response.body, &response.url) {
+        Ok(feed) => return Ok(FeedFetch::fetched(feed, response)),
+        Err(e) => {
+            let is_html = response
+                .content_type
+                .as_deref()
+                .unwrap_or("")
+                .contains("html");
+            let discovered = if discover && is_html {
+                discover_feed_url(&response.body, &response.url)
+            } else {
+                None
+            };
+            let Some(feed_url) = discovered else {
+                return Err(e);
+            };
+            log::info!("Discovered feed {} from {}", feed_url, response.url);
+            request_feed(client, &feed_url, None, None)
+                .await?
+                .ok_or(e)?
+        }
+    };
+    let feed = parse_feed(&response.body, &response.url)?;
+    Ok(FeedFetch::fetched(feed, response))
+}
+
+/// Refresh one feed and store its new entries
+///
+/// Errors are recorded on the feed and reported in the result rather than
+/// returned, so one broken feed does not abort a batch refresh.
+pub async fn refresh_feed(
+    state: &LibraryState,
+    client: &reqwest::Client,
+    feed_id: &str,
+) -> FeedRefreshResult {
+    let mut result = FeedRefreshResult {
+        feed_id: feed_id.to_string(),
+        ..Default::default()
+    };
+    let fetch_state = match lock_library(state).and_then(|conn| get_fetch_state(&conn, feed_id)) {
+        Ok(fetch_state) => fetch_state,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+
+    let fetched = match Url::parse(&fetch_state.url) {
+        Ok(url) => {
+            fetch_feed(
+                client,
+                &url,
+                fetch_state.etag.as_deref(),
+                fetch_state.last_modified.as_deref(),
+                false,
+            )
+            .await
+        }
+        Err(e) => Err(AppError::InvalidInput(e.to_string())),
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    let stored = lock_library(state).and_then(|mut conn| match fetched {
+        Ok(FeedFetch::NotModified) => {
+            result.not_modified = true;
+            record_refresh(&conn, &fetch_state, None, now)
+        }
+        Ok(FeedFetch::Fetched {
+            feed,
+            etag,
+            last_modified,
+            ..
+        }) => {
+            result.new_entries = store_entries(&mut conn, feed_id, &feed, now)?;
+            let validators = FeedFetchState {
+                etag,
+                last_modified,
+                ..fetch_state.clone()
+            };
+            record_refresh(&conn, &validators, None, now)
+        }
+        Err(e) => {
+            result.error = Some(e.to_string());
+            record_refresh(&conn, &fetch_state, Some(&e.to_string()), now)
+        }
+    });
+    if let Err(e) = stored {
+        result.error = Some(e.to_string());
+    }
+    if let Some(error) = &result.error {
+        log::warn!("Feed refresh failed for {}: {}", fetch_state.url, error);
+    }
+    result
+}
+
+/// Refresh several feeds one after another
+pub async fn refresh_feeds(
+    state: &LibraryState,
+    client: &reqwest::Client,
+    feed_ids: &[String],
+) -> Vec<FeedRefreshResult> {
+    let mut results = Vec::with_capacity(feed_ids.len());
+    for feed_id in feed_ids {
+        results.push(refresh_feed(state, client, feed_id).await);
+    }
+    results
+}
+
+/// Start the background task that refreshes feeds when their interval elapses
+pub fn start_feed_scheduler(app: tauri::AppHandle) {
+    crate::commands::crash::spawn_guarded("feed scheduler", async move {
+        tokio::time::sleep(SCHEDULER_STARTUP_DELAY).await;
+        let client = match feed_http_client() {
+            Ok(client) => client,
+            Err(e) => {
+                log::error!("Feed scheduler disabled: {}", e);
+                return;
+            }
+        };
+        let mut interval = tokio::time::interval(SCHEDULER_TICK);
+        loop {
+            interval.tick().await;
+            if is_saving_power(&app) {
+                continue;
+            }
+            let state = app.state::<LibraryState>().inner().clone();
+            let due = match lock_library(&state)
+                .and_then(|conn| due_feed_ids(&conn, chrono::Utc::now().timestamp()))
+            {
+                Ok(due) => due,
+                Err(e) => {
+                    log::warn!("Failed to list due feeds: {}", e);
+                    continue;
+                }
+            };
+            if due.is_empty() {
+                continue;
+            }
+            let results = refresh_feeds(&state, &client, &due).await;
+            let new_entries: usize = results.iter().map(|r| r.new_entries).sum();
+            log::info!(
+                "Scheduled refresh of {} feeds found {} new entries",
+                results.len(),
+                new_entries
+            );
+            if new_entries > 0 {
+                emit_event(&app, &results);
+            }
+        }
+    });
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Subscribe to an RSS/Atom feed (or the feed advertised by a web page)
+#[tauri::command]
+pub async fn feed_add(
+    state: tauri::State<'_, LibraryState>,
+    input: FeedInput,
+) -> Result<Feed, AppError> {
+    let url = Url::parse(input.url.trim())
+        .map_err(|e| AppError::InvalidInput(format!("Invalid URL '{}': {}", input.url, e)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AppError::InvalidInput(format!(
+            "Only http and https feeds are supported, got '{}'",
+            url.scheme()
+        )));
+    }
+
+    let client = feed_http_client()?;
+    let FeedFetch::Fetched {
+        feed,
+        url,
+        etag,
+        last_modified,
+    } = fetch_feed(&client, &url, None, None, true).await?
+    else {
+        return Err(AppError::http_status(
+            StatusCode::NOT_MODIFIED,
+            "Feed returned no content",
+        ));
+    };
+
+    let mut conn = lock_library(&state)?;
+    let now = chrono::Utc::now().timestamp();
+    let stored = insert_feed(
+        &conn,
+        url.as_str(),
+        input.title.as_deref(),
+        &feed,
+        input.refresh_interval_minutes,
+        now,
+    )?;
+    store_entries(&mut conn, &stored.id, &feed, now)?;
+    let fetch_state = FeedFetchState {
+        id: stored.id.clone(),
+        url: stored.url.clone(),
+        etag,
+        last_modified,
+    };
+    record_refresh(&conn, &fetch_state, None, now)?;
+    log::info!("Subscribed to feed {}", stored.url);
+    get_feed_by_url(&conn, &stored.url)?
+        .ok_or_else(|| AppError::NotFound(format!("Feed '{}' not found", stored.url)))
+}
+
+/// Refresh one feed, or every feed when no id is given
+#[tauri::command]
+pub async fn feed_refresh(
+    state: tauri::State<'_, LibraryState>,
+    feed_id: Option<String>,
+) -> Result<Vec<FeedRefreshResult>, AppError> {
+    let state = state.inner().clone();
+    let feed_ids = match feed_id {
+        Some(id) => vec![id],
+        None => list_feeds(&*lock_library(&state)?)?
+            .into_iter()
+            .map(|feed| feed.id)
+            .collect(),
+    };
+    let client = feed_http_client()?;
+    Ok(refresh_feeds(&state, &client, &feed_ids).await)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_feed_uses_xml_declaration() {
+        let (body, _, _) = encoding_rs::GBK.encode("<title>订阅</title>");
+        let mut xml = br#"<?xml version="1.0" encoding="GBK"?>"#.to_vec();
+        xml.extend_from_slice(&body);
+        assert!(decode_feed(&xml, Some("application/xml")).contains("订阅"));
+        assert!(decode_feed("<rss/>".as_bytes(), None).contains("<rss/>"));
+    }
+
+    #[test]
+    fn discover_feed_url_reads_alternate_links() {
+        let base = Url::parse("https://blog.example.com/posts/").unwrap();
+        let html = r#"<html><head>
+            <link rel="stylesheet" href="/style.css">
+            <link rel="alternate" hreflang="de" href="/de/">
+            <link rel="alternate" type="application/atom+xml" href="../atom.xml">
+            </head><body></body></html>"#;
+        assert_eq!(
+            discover_feed_url(html, &base).unwrap().as_str(),
+            "https://blog.example.com/atom.xml"
+        );
+        assert!(discover_feed_url("<html></html>", &base).is_none());
+    }
+}
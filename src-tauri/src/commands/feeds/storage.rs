@@ -0,0 +1,575 @@
+//! Feed and entry storage in the library database
+
+use super::types::{Feed, FeedEntry, FeedEntryQuery, ParsedEntry, ParsedFeed};
+use crate::commands::epub::xhtml_to_text;
+use crate::commands::library::{lock_library, LibraryState};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use uuid::Uuid;
+
+/// Refresh interval used when a subscription does not specify one
+pub const DEFAULT_REFRESH_INTERVAL_MINUTES: u32 = 60;
+const MIN_REFRESH_INTERVAL_MINUTES: u32 = 5;
+
+/// Read entries that dropped out of their feed are deleted after this many days
+const READ_ENTRY_RETENTION_DAYS: i64 = 90;
+
+const FEED_COLUMNS: &str = "f.id, f.url, f.title, f.site_url, f.description,
+    f.refresh_interval_minutes, f.last_fetched_at, f.last_error, f.created_at,
+    (SELECT COUNT(*) FROM feed_entries e WHERE e.feed_id = f.id AND e.read_at IS NULL),
+    (SELECT COUNT(*) FROM feed_entries e WHERE e.feed_id = f.id)";
+
+const ENTRY_COLUMNS: &str = "e.id, e.feed_id, f.title, e.guid, e.title, e.url, e.author,
+    e.summary, e.content, e.published_at, e.fetched_at, e.read_at";
+
+/// Conditional request state of a feed
+#[derive(Clone, Debug, Default)]
+pub struct FeedFetchState {
+    pub id: String,
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn row_to_feed(row: &Row) -> rusqlite::Result<Feed> {
+    Ok(Feed {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        title: row.get(2)?,
+        site_url: row.get(3)?,
+        description: row.get(4)?,
+        refresh_interval_minutes: row.get(5)?,
+        last_fetched_at: row.get(6)?,
+        last_error: row.get(7)?,
+        created_at: row.get(8)?,
+        unread_count: row.get(9)?,
+        entry_count: row.get(10)?,
+    })
+}
+
+fn row_to_entry(row: &Row) -> rusqlite::Result<FeedEntry> {
+    Ok(FeedEntry {
+        id: row.get(0)?,
+        feed_id: row.get(1)?,
+        feed_title: row.get(2)?,
+        guid: row.get(3)?,
+        title: row.get(4)?,
+        url: row.get(5)?,
+        author: row.get(6)?,
+        summary: row.get(7)?,
+        content: row.get(8)?,
+        published_at: row.get(9)?,
+        fetched_at: row.get(10)?,
+        read_at: row.get(11)?,
+    })
+}
+
+/// Clamp a requested refresh interval to the supported minimum
+pub fn normalize_refresh_interval(minutes: Option<u32>) -> u32 {
+    minutes
+        .unwrap_or(DEFAULT_REFRESH_INTERVAL_MINUTES)
+        .max(MIN_REFRESH_INTERVAL_MINUTES)
+}
+
+/// Get a feed by id
+pub fn get_feed(conn: &Connection, id: &str) -> Result<Option<Feed>, AppError> {
+    let sql = format!("SELECT {} FROM feeds f WHERE f.id = ?1", FEED_COLUMNS);
+    Ok(conn.query_row(&sql, params![id], row_to_feed).optional()?)
+}
+
+/// Get a feed by its URL
+pub fn get_feed_by_url(conn: &Connection, url: &str) -> Result<Option<Feed>, AppError> {
+    let sql = format!("SELECT {} FROM feeds f WHERE f.url = ?1", FEED_COLUMNS);
+    Ok(conn.query_row(&sql, params![url], row_to_feed).optional()?)
+}
+
+/// List all feeds with their unread counts
+pub fn list_feeds(conn: &Connection) -> Result<Vec<Feed>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM feeds f ORDER BY f.title COLLATE NOCASE, f.id",
+        FEED_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let feeds = stmt
+        .query_map([], row_to_feed)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(feeds)
+}
+
+/// Insert a new feed subscription
+pub fn insert_feed(
+    conn: &Connection,
+    url: &str,
+    title: Option<&str>,
+    parsed: &ParsedFeed,
+    refresh_interval_minutes: Option<u32>,
+    now: i64,
+) -> Result<Feed, AppError> {
+    if get_feed_by_url(conn, url)?.is_some() {
+        return Err(AppError::InvalidInput(format!(
+            "Already subscribed to {}",
+            url
+        )));
+    }
+    let id = format!("feed_{}", Uuid::new_v4());
+    let title = title
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .map(str::to_string)
+        .or_else(|| parsed.title.clone())
+        .unwrap_or_else(|| url.to_string());
+    conn.execute(
+        "INSERT INTO feeds (id, url, title, site_url, description, refresh_interval_minutes,
+                            created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            id,
+            url,
+            title,
+            parsed.site_url,
+            parsed.description,
+            normalize_refresh_interval(refresh_interval_minutes),
+            now
+        ],
+    )?;
+    get_feed(conn, &id)?.ok_or_else(|| AppError::NotFound(format!("Feed '{}' not found", id)))
+}
+
+/// Remove a feed and its entries
+pub fn remove_feed(conn: &Connection, id: &str) -> Result<(), AppError> {
+    let changed = conn.execute("DELETE FROM feeds WHERE id = ?1", params![id])?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("Feed '{}' not found", id)));
+    }
+    Ok(())
+}
+
+/// Get the conditional request state of a feed
+pub fn get_fetch_state(conn: &Connection, id: &str) -> Result<FeedFetchState, AppError> {
+    conn.query_row(
+        "SELECT id, url, etag, last_modified FROM feeds WHERE id = ?1",
+        params![id],
+        |row| {
+            Ok(FeedFetchState {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                etag: row.get(2)?,
+                last_modified: row.get(3)?,
+            })
+        },
+    )
+    .optional()?
+    .ok_or_else(|| AppError::NotFound(format!("Feed '{}' not found", id)))
+}
+
+/// Ids of feeds whose refresh interval has elapsed
+pub fn due_feed_ids(conn: &Connection, now: i64) -> Result<Vec<String>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT id FROM feeds
+         WHERE last_fetched_at IS NULL
+            OR last_fetched_at + refresh_interval_minutes * 60 <= ?1
+         ORDER BY COALESCE(last_fetched_at, 0)",
+    )?;
+    let ids = stmt
+        .query_map(params![now], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(ids)
+}
+
+/// Record the outcome of a refresh attempt
+pub fn record_refresh(
+    conn: &Connection,
+    state: &FeedFetchState,
+    error: Option<&str>,
+    now: i64,
+) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE feeds SET last_fetched_at = ?2, last_error = ?3,
+                etag = COALESCE(?4, etag), last_modified = COALESCE(?5, last_modified)
+         WHERE id = ?1",
+        params![state.id, now, error, state.etag, state.last_modified],
+    )?;
+    Ok(())
+}
+
+/// Store the entries of a fetched feed, returning the number of new entries
+///
+/// Existing entries are refreshed in place and keep their read state. Read
+/// entries that are no longer part of the feed are pruned after the retention
+/// period; entries still in the feed are kept so they do not reappear as unread.
+pub fn store_entries(
+    conn: &mut Connection,
+    feed_id: &str,
+    parsed: &ParsedFeed,
+    now: i64,
+) -> Result<usize, AppError> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "UPDATE feeds SET site_url = COALESCE(?2, site_url),
+                description = COALESCE(?3, description)
+         WHERE id = ?1",
+        params![feed_id, parsed.site_url, parsed.description],
+    )?;
+
+    let mut inserted = 0;
+    for entry in &parsed.entries {
+        let Some(guid) = entry.identity() else {
+            continue;
+        };
+        inserted += upsert_entry(&tx, feed_id, &guid, entry, now)?;
+    }
+
+    tx.execute(
+        "DELETE FROM feed_entries
+         WHERE feed_id = ?1 AND last_seen_at < ?2 AND read_at IS NOT NULL AND read_at < ?3",
+        params![feed_id, now, now - READ_ENTRY_RETENTION_DAYS * 24 * 60 * 60],
+    )?;
+    tx.commit()?;
+    Ok(inserted)
+}
+
+fn upsert_entry(
+    conn: &Connection,
+    feed_id: &str,
+    guid: &str,
+    entry: &ParsedEntry,
+    now: i64,
+) -> Result<usize, AppError> {
+    let inserted = conn.execute(
+        "INSERT INTO feed_entries (id, feed_id, guid, title, url, author, summary, content,
+                                   published_at, fetched_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)
+         ON CONFLICT(feed_id, guid) DO NOTHING",
+        params![
+            format!("entry_{}", Uuid::new_v4()),
+            feed_id,
+            guid,
+            entry.title,
+            entry.url,
+            entry.author,
+            entry.summary,
+            entry.content,
+            entry.published_at,
+            now
+        ],
+    )?;
+    if inserted == 0 {
+        conn.execute(
+            "UPDATE feed_entries SET title = ?3, url = ?4, author = ?5, summary = ?6,
+                    content = ?7, published_at = COALESCE(?8, published_at), last_seen_at = ?9
+             WHERE feed_id = ?1 AND guid = ?2",
+            params![
+                feed_id,
+                guid,
+                entry.title,
+                entry.url,
+                entry.author,
+                entry.summary,
+                entry.content,
+                entry.published_at,
+                now
+            ],
+        )?;
+    }
+    Ok(inserted)
+}
+
+/// List feed entries, newest first
+pub fn list_entries(conn: &Connection, query: &FeedEntryQuery) -> Result<Vec<FeedEntry>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM feed_entries e JOIN feeds f ON f.id = e.feed_id
+         WHERE (?1 IS NULL OR e.feed_id = ?1)
+           AND (?2 = 0 OR e.read_at IS NULL)
+         ORDER BY COALESCE(e.published_at, e.fetched_at) DESC, e.id
+         LIMIT ?3 OFFSET ?4",
+        ENTRY_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let entries = stmt
+        .query_map(
+            params![
+                query.feed_id,
+                query.unread_only.unwrap_or(true),
+                query.limit.map(i64::from).unwrap_or(100),
+                query.offset.map(i64::from).unwrap_or(0)
+            ],
+            row_to_entry,
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(entries)
+}
+
+/// Get a single feed entry
+pub fn get_entry(conn: &Connection, id: &str) -> Result<Option<FeedEntry>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM feed_entries e JOIN feeds f ON f.id = e.feed_id WHERE e.id = ?1",
+        ENTRY_COLUMNS
+    );
+    Ok(conn.query_row(&sql, params![id], row_to_entry).optional()?)
+}
+
+/// Mark entries as read or unread, returning the number of entries changed
+pub fn mark_entries_read(
+    conn: &mut Connection,
+    ids: &[String],
+    read: bool,
+    now: i64,
+) -> Result<usize, AppError> {
+    let tx = conn.transaction()?;
+    let mut changed = 0;
+    for id in ids {
+        changed += if read {
+            tx.execute(
+                "UPDATE feed_entries SET read_at = ?2 WHERE id = ?1 AND read_at IS NULL",
+                params![id, now],
+            )?
+        } else {
+            tx.execute(
+                "UPDATE feed_entries SET read_at = NULL WHERE id = ?1 AND read_at IS NOT NULL",
+                params![id],
+            )?
+        };
+    }
+    tx.commit()?;
+    Ok(changed)
+}
+
+/// Mark every unread entry (optionally of one feed) as read
+pub fn mark_all_read(
+    conn: &Connection,
+    feed_id: Option<&str>,
+    now: i64,
+) -> Result<usize, AppError> {
+    Ok(conn.execute(
+        "UPDATE feed_entries SET read_at = ?2
+         WHERE read_at IS NULL AND (?1 IS NULL OR feed_id = ?1)",
+        params![feed_id, now],
+    )?)
+}
+
+/// Plain text of an entry (title, link and body), e.g. for AI summarization
+pub fn entry_plain_text(entry: &FeedEntry) -> String {
+    let mut parts = Vec::new();
+    if let Some(title) = &entry.title {
+        parts.push(title.clone());
+    }
+    if let Some(url) = &entry.url {
+        parts.push(url.clone());
+    }
+    let body = entry
+        .content
+        .as_deref()
+        .or(entry.summary.as_deref())
+        .map(|html| xhtml_to_text(html).text)
+        .unwrap_or_default();
+    if !body.is_empty() {
+        parts.push(body);
+    }
+    parts.join("\n\n")
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// List subscribed feeds
+#[tauri::command]
+pub fn feed_list(state: tauri::State<'_, LibraryState>) -> Result<Vec<Feed>, AppError> {
+    let conn = lock_library(&state)?;
+    list_feeds(&conn)
+}
+
+/// Unsubscribe from a feed, deleting its entries
+#[tauri::command]
+pub fn feed_remove(state: tauri::State<'_, LibraryState>, id: String) -> Result<(), AppError> {
+    let conn = lock_library(&state)?;
+    remove_feed(&conn, &id)?;
+    log::info!("Feed removed: {}", id);
+    Ok(())
+}
+
+/// List feed entries (unread only by default)
+#[tauri::command]
+pub fn feed_list_entries(
+    state: tauri::State<'_, LibraryState>,
+    query: Option<FeedEntryQuery>,
+) -> Result<Vec<FeedEntry>, AppError> {
+    let conn = lock_library(&state)?;
+    list_entries(&conn, &query.unwrap_or_default())
+}
+
+/// Get a feed entry as plain text
+#[tauri::command]
+pub fn feed_get_entry_text(
+    state: tauri::State<'_, LibraryState>,
+    id: String,
+) -> Result<String, AppError> {
+    let conn = lock_library(&state)?;
+    let entry = get_entry(&conn, &id)?
+        .ok_or_else(|| AppError::NotFound(format!("Entry '{}' not found", id)))?;
+    Ok(entry_plain_text(&entry))
+}
+
+/// Mark feed entries as read (or unread with `read: false`)
+#[tauri::command]
+pub fn feed_mark_read(
+    state: tauri::State<'_, LibraryState>,
+    entry_ids: Vec<String>,
+    read: Option<bool>,
+) -> Result<usize, AppError> {
+    let mut conn = lock_library(&state)?;
+    mark_entries_read(
+        &mut conn,
+        &entry_ids,
+        read.unwrap_or(true),
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+/// Mark all entries of a feed (or of every feed) as read
+#[tauri::command]
+pub fn feed_mark_all_read(
+    state: tauri::State<'_, LibraryState>,
+    feed_id: Option<String>,
+) -> Result<usize, AppError> {
+    let conn = lock_library(&state)?;
+    mark_all_read(&conn, feed_id.as_deref(), chrono::Utc::now().timestamp())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::library::LIBRARY_MIGRATIONS;
+    use crate::db;
+
+    fn parsed(guids: &[&str]) -> ParsedFeed {
+        ParsedFeed {
+            title: Some("Blog".to_string()),
+            site_url: Some("https://blog.example.com/".to_string()),
+            entries: guids
+                .iter()
+                .enumerate()
+                .map(|(i, guid)| ParsedEntry {
+                    guid: Some(guid.to_string()),
+                    title: Some(format!("Post {}", guid)),
+                    summary: Some("<p>Hello <b>world</b></p>".to_string()),
+                    published_at: Some(i as i64),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn feeds_store_entries_and_track_read_state() {
+        let mut conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let feed = insert_feed(
+            &conn,
+            "https://blog.example.com/feed",
+            None,
+            &parsed(&[]),
+            None,
+            1,
+        )
+        .unwrap();
+        assert_eq!(feed.title, "Blog");
+        assert_eq!(
+            feed.refresh_interval_minutes,
+            DEFAULT_REFRESH_INTERVAL_MINUTES
+        );
+        assert!(matches!(
+            insert_feed(&conn, &feed.url, None, &parsed(&[]), None, 1),
+            Err(AppError::InvalidInput(_))
+        ));
+
+        assert_eq!(
+            store_entries(&mut conn, &feed.id, &parsed(&["a", "b"]), 10).unwrap(),
+            2
+        );
+        assert_eq!(
+            store_entries(&mut conn, &feed.id, &parsed(&["b", "c"]), 20).unwrap(),
+            1
+        );
+
+        let unread = list_entries(&conn, &FeedEntryQuery::default()).unwrap();
+        assert_eq!(unread.len(), 3);
+        assert_eq!(unread[0].title.as_deref(), Some("Post c"));
+        assert_eq!(unread[0].feed_title, "Blog");
+
+        let ids = vec![unread[0].id.clone(), unread[1].id.clone()];
+        assert_eq!(mark_entries_read(&mut conn, &ids, true, 30).unwrap(), 2);
+        assert_eq!(mark_entries_read(&mut conn, &ids, true, 30).unwrap(), 0);
+        let feed = get_feed(&conn, &feed.id).unwrap().unwrap();
+        assert_eq!((feed.unread_count, feed.entry_count), (1, 3));
+
+        assert_eq!(mark_all_read(&conn, Some(&feed.id), 40).unwrap(), 1);
+        let all = list_entries(
+            &conn,
+            &FeedEntryQuery {
+                unread_only: Some(false),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(all.iter().all(|entry| entry.read_at.is_some()));
+        assert_eq!(entry_plain_text(&all[0]), "Post c\n\nHello world");
+    }
+
+    #[test]
+    fn store_entries_prunes_only_old_read_entries_missing_from_feed() {
+        let mut conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let feed = insert_feed(
+            &conn,
+            "https://x.example/feed",
+            Some("Mine"),
+            &parsed(&[]),
+            Some(1),
+            0,
+        )
+        .unwrap();
+        assert_eq!(feed.title, "Mine");
+        assert_eq!(feed.refresh_interval_minutes, MIN_REFRESH_INTERVAL_MINUTES);
+
+        store_entries(&mut conn, &feed.id, &parsed(&["old", "kept"]), 0).unwrap();
+        mark_all_read(&conn, None, 0).unwrap();
+
+        let later = READ_ENTRY_RETENTION_DAYS * 24 * 60 * 60 + 1;
+        assert_eq!(
+            store_entries(&mut conn, &feed.id, &parsed(&["kept"]), later).unwrap(),
+            0
+        );
+        let guids: Vec<String> = list_entries(
+            &conn,
+            &FeedEntryQuery {
+                unread_only: Some(false),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.guid)
+        .collect();
+        assert_eq!(guids, vec!["kept"]);
+
+        assert_eq!(due_feed_ids(&conn, 0).unwrap(), vec![feed.id.clone()]);
+        let state = get_fetch_state(&conn, &feed.id).unwrap();
+        record_refresh(&conn, &state, None, 100).unwrap();
+        assert!(due_feed_ids(&conn, 100).unwrap().is_empty());
+        assert_eq!(due_feed_ids(&conn, 400).unwrap(), vec![feed.id.clone()]);
+
+        remove_feed(&conn, &feed.id).unwrap();
+        assert!(matches!(
+            remove_feed(&conn, &feed.id),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+//! Feed reader data structures
+
+use serde::{Deserialize, Serialize};
+
+/// A subscribed RSS/Atom feed
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Feed {
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    pub site_url: Option<String>,
+    pub description: Option<String>,
+    pub refresh_interval_minutes: u32,
+    pub last_fetched_at: Option<i64>,
+    /// Error from the last refresh, cleared on success
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub unread_count: u32,
+    pub entry_count: u32,
+}
+
+/// Subscription request
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedInput {
+    pub url: String,
+    /// Custom title, defaults to the feed's own title
+    pub title: Option<String>,
+    pub refresh_interval_minutes: Option<u32>,
+}
+
+/// A stored feed entry
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedEntry {
+    pub id: String,
+    pub feed_id: String,
+    pub feed_title: String,
+    pub guid: String,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub author: Option<String>,
+    /// Summary HTML as published by the feed
+    pub summary: Option<String>,
+    /// Full content HTML, when the feed includes it
+    pub content: Option<String>,
+    pub published_at: Option<i64>,
+    pub fetched_at: i64,
+    pub read_at: Option<i64>,
+}
+
+/// Feed entry listing options
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedEntryQuery {
+    pub feed_id: Option<String>,
+    /// Only return unread entries (default true)
+    pub unread_only: Option<bool>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// Outcome of refreshing a single feed
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedRefreshResult {
+    pub feed_id: String,
+    pub new_entries: usize,
+    /// The server reported the feed as unchanged (HTTP 304)
+    pub not_modified: bool,
+    pub error: Option<String>,
+}
+
+/// A feed document as parsed from RSS or Atom
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParsedFeed {
+    pub title: Option<String>,
+    pub site_url: Option<String>,
+    pub description: Option<String>,
+    pub entries: Vec<ParsedEntry>,
+}
+
+/// An entry of a parsed feed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParsedEntry {
+    pub guid: Option<String>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub author: Option<String>,
+    pub summary: Option<String>,
+    pub content: Option<String>,
+    pub published_at: Option<i64>,
+    pub updated_at: Option<i64>,
+}
+
+impl ParsedEntry {
+    /// Stable identity of the entry within its feed
+    pub fn identity(&self) -> Option<String> {
+        self.guid
+            .clone()
+            .or_else(|| self.url.clone())
+            .or_else(|| self.title.clone())
+    }
+}
@@ -1,13 +1,31 @@
 //! File operations commands (export, import, metadata, etc.)
 
+use crate::commands::events::emit_event;
+use crate::commands::settings::setting;
 use crate::error::AppError;
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
-use std::io::{Read, Write};
-use std::path::Path;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 use std::time::UNIX_EPOCH;
 use tauri::Manager;
 
+/// Event emitted while an export is written or an import read
+pub const DATA_TRANSFER_PROGRESS_EVENT: &str = "data-transfer-progress";
+
+/// Setting holding the largest file `import_data_from_file` reads, in MB
+pub const MAX_IMPORT_SIZE_SETTING: &str = "data.maxImportSizeMb";
+
+/// Limit used when the setting cannot be read
+const DEFAULT_MAX_IMPORT_SIZE_MB: u64 = 1024;
+
+/// Bytes written or read at a time
+const TRANSFER_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Emit a progress event every N bytes
+const PROGRESS_INTERVAL_BYTES: u64 = 8 * 1024 * 1024;
+
 // ============================================================================
 // Data Structures
 // ============================================================================
@@ -56,6 +74,44 @@ pub struct ImportResult {
     pub error: Option<String>,
 }
 
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Export,
+    Import,
+}
+
+/// Progress of a running export or import
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataTransferProgress {
+    pub file_path: String,
+    pub direction: TransferDirection,
+    /// Bytes of the data processed so far
+    pub bytes: u64,
+    pub total: u64,
+}
+
+/// Re-indents JSON as it streams past, like `serde_json::to_string_pretty`
+/// but without parsing the document into memory
+#[derive(Default)]
+struct PrettyPrinter {
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// Bracket just opened, held back until we know whether it is empty
+    open: Option<u8>,
+}
+
+/// Emits progress every `PROGRESS_INTERVAL_BYTES` and on completion
+struct ProgressReporter<F> {
+    file_path: String,
+    direction: TransferDirection,
+    total: u64,
+    next_report: u64,
+    on_progress: F,
+}
+
 /// File metadata for recent files
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -68,44 +124,150 @@ pub struct FileMetadata {
 }
 
 // ============================================================================
-// Commands
+// Helper Functions
 // ============================================================================
 
-/// Get file metadata
-#[tauri::command]
-pub fn get_file_metadata(path: String) -> Option<FileMetadata> {
-    let p = Path::new(&path);
-    if !p.exists() {
-        return None;
+impl PrettyPrinter {
+    /// Re-indent the next chunk of a valid JSON document
+    fn feed<W: Write>(&mut self, chunk: &[u8], out: &mut W) -> io::Result<()> {
+        for &byte in chunk {
+            if self.in_string {
+                out.write_all(&[byte])?;
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+            if byte.is_ascii_whitespace() {
+                continue;
+            }
+            if let Some(open) = self.open.take() {
+                // `}` and `]` follow `{` and `[` by two in ASCII
+                if byte == open + 2 {
+                    out.write_all(&[byte])?;
+                    continue;
+                }
+                self.depth += 1;
+                self.newline(out)?;
+            }
+            match byte {
+                b'{' | b'[' => {
+                    out.write_all(&[byte])?;
+                    self.open = Some(byte);
+                }
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    self.newline(out)?;
+                    out.write_all(&[byte])?;
+                }
+                b',' => {
+                    out.write_all(b",")?;
+                    self.newline(out)?;
+                }
+                b':' => out.write_all(b": ")?,
+                b'"' => {
+                    self.in_string = true;
+                    out.write_all(&[byte])?;
+                }
+                _ => out.write_all(&[byte])?,
+            }
+        }
+        Ok(())
     }
 
-    let metadata = fs::metadata(p).ok()?;
-    let name = p.file_name()?.to_str()?.to_string();
+    fn newline<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"\n")?;
+        for _ in 0..self.depth {
+            out.write_all(b"  ")?;
+        }
+        Ok(())
+    }
+}
 
-    let modified_at = metadata
-        .modified()
-        .ok()
-        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
-        .map(|d| d.as_secs());
+impl<F: FnMut(DataTransferProgress)> ProgressReporter<F> {
+    fn new(file_path: &str, direction: TransferDirection, total: u64, on_progress: F) -> Self {
+        ProgressReporter {
+            file_path: file_path.to_string(),
+            direction,
+            total,
+            next_report: PROGRESS_INTERVAL_BYTES,
+            on_progress,
+        }
+    }
 
-    let created_at = metadata
-        .created()
-        .ok()
-        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
-        .map(|d| d.as_secs());
+    fn report(&mut self, bytes: u64) {
+        if bytes >= self.next_report || bytes == self.total {
+            self.next_report = bytes + PROGRESS_INTERVAL_BYTES;
+            (self.on_progress)(DataTransferProgress {
+                file_path: self.file_path.clone(),
+                direction: self.direction,
+                bytes,
+                total: self.total,
+            });
+        }
+    }
+}
 
-    Some(FileMetadata {
-        path,
-        name,
-        size: metadata.len(),
-        modified_at,
-        created_at,
-    })
+/// Whether a string is valid JSON, checked without building a value
+fn is_valid_json(data: &str) -> Result<(), serde_json::Error> {
+    serde_json::from_str::<IgnoredAny>(data).map(|_| ())
 }
 
-/// Export data to a file
-#[tauri::command]
-pub fn export_data_to_file(options: ExportOptions) -> ExportResult {
+/// Write data to a file in chunks, pretty-printed if asked and valid JSON.
+/// The data goes to a `.part` file first, renamed once complete, so a
+/// failed export never leaves a truncated file behind.
+fn write_json_file<F>(
+    data: &str,
+    path: &Path,
+    pretty_print: bool,
+    on_progress: F,
+) -> io::Result<u64>
+where
+    F: FnMut(DataTransferProgress),
+{
+    let pretty_print = pretty_print && is_valid_json(data).is_ok();
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+    let mut progress = ProgressReporter::new(
+        &path.to_string_lossy(),
+        TransferDirection::Export,
+        data.len() as u64,
+        on_progress,
+    );
+
+    let written = (|| {
+        let mut out = BufWriter::with_capacity(TRANSFER_CHUNK_BYTES, File::create(&partial)?);
+        let mut printer = PrettyPrinter::default();
+        let mut done = 0;
+        for chunk in data.as_bytes().chunks(TRANSFER_CHUNK_BYTES) {
+            if pretty_print {
+                printer.feed(chunk, &mut out)?;
+            } else {
+                out.write_all(chunk)?;
+            }
+            done += chunk.len() as u64;
+            progress.report(done);
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&partial, path)?;
+        Ok(fs::metadata(path)?.len())
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    written
+}
+
+/// Write an export as `export_data_to_file` does, reporting progress
+pub fn export_data<F>(options: ExportOptions, on_progress: F) -> ExportResult
+where
+    F: FnMut(DataTransferProgress),
+{
     let file_path = match options.file_path {
         Some(path) => path,
         None => {
@@ -118,47 +280,34 @@ pub fn export_data_to_file(options: ExportOptions) -> ExportResult {
         }
     };
 
-    // Format JSON if requested
-    let data_to_write = if options.pretty_print.unwrap_or(true) {
-        match serde_json::from_str::<serde_json::Value>(&options.data) {
-            Ok(value) => serde_json::to_string_pretty(&value).unwrap_or(options.data),
-            Err(_) => options.data,
-        }
-    } else {
-        options.data
-    };
-
-    // Write to file
-    match File::create(&file_path) {
-        Ok(mut file) => {
-            let bytes = data_to_write.as_bytes();
-            match file.write_all(bytes) {
-                Ok(_) => ExportResult {
-                    success: true,
-                    file_path: Some(file_path),
-                    bytes_written: Some(bytes.len() as u64),
-                    error: None,
-                },
-                Err(e) => ExportResult {
-                    success: false,
-                    file_path: Some(file_path),
-                    bytes_written: None,
-                    error: Some(format!("Failed to write file: {}", e)),
-                },
-            }
-        }
+    let pretty_print = options.pretty_print.unwrap_or(true);
+    match write_json_file(
+        &options.data,
+        Path::new(&file_path),
+        pretty_print,
+        on_progress,
+    ) {
+        Ok(bytes) => ExportResult {
+            success: true,
+            file_path: Some(file_path),
+            bytes_written: Some(bytes),
+            error: None,
+        },
         Err(e) => ExportResult {
             success: false,
             file_path: Some(file_path),
             bytes_written: None,
-            error: Some(format!("Failed to create file: {}", e)),
+            error: Some(format!("Failed to write file: {}", e)),
         },
     }
 }
 
-/// Import data from a file
-#[tauri::command]
-pub fn import_data_from_file(options: ImportOptions) -> ImportResult {
+/// Read an import as `import_data_from_file` does, refusing files larger
+/// than `max_bytes` and reporting progress
+pub fn import_data<F>(options: ImportOptions, max_bytes: u64, on_progress: F) -> ImportResult
+where
+    F: FnMut(DataTransferProgress),
+{
     let file_path = match options.file_path {
         Some(path) => path,
         None => {
@@ -171,60 +320,140 @@ pub fn import_data_from_file(options: ImportOptions) -> ImportResult {
             };
         }
     };
+    let failed = |file_path: String, bytes_read: Option<u64>, error: String| ImportResult {
+        success: false,
+        data: None,
+        file_path: Some(file_path),
+        bytes_read,
+        error: Some(error),
+    };
 
     let p = Path::new(&file_path);
     if !p.exists() {
-        return ImportResult {
-            success: false,
-            data: None,
-            file_path: Some(file_path),
-            bytes_read: None,
-            error: Some("File does not exist".to_string()),
-        };
+        return failed(file_path, None, "File does not exist".to_string());
+    }
+    let mut file = match File::open(p) {
+        Ok(file) => file,
+        Err(e) => return failed(file_path, None, format!("Failed to open file: {}", e)),
+    };
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let too_large = || {
+        format!(
+            "File is too large to import (limit is {} MB)",
+            max_bytes / (1024 * 1024)
+        )
+    };
+    if size > max_bytes {
+        return failed(file_path, None, too_large());
     }
 
-    match File::open(&file_path) {
-        Ok(mut file) => {
-            let mut content = String::new();
-            match file.read_to_string(&mut content) {
-                Ok(bytes) => {
-                    // Validate JSON
-                    match serde_json::from_str::<serde_json::Value>(&content) {
-                        Ok(_) => ImportResult {
-                            success: true,
-                            data: Some(content),
-                            file_path: Some(file_path),
-                            bytes_read: Some(bytes as u64),
-                            error: None,
-                        },
-                        Err(e) => ImportResult {
-                            success: false,
-                            data: None,
-                            file_path: Some(file_path),
-                            bytes_read: Some(bytes as u64),
-                            error: Some(format!("Invalid JSON: {}", e)),
-                        },
-                    }
-                }
-                Err(e) => ImportResult {
-                    success: false,
-                    data: None,
-                    file_path: Some(file_path),
-                    bytes_read: None,
-                    error: Some(format!("Failed to read file: {}", e)),
-                },
-            }
+    let mut progress =
+        ProgressReporter::new(&file_path, TransferDirection::Import, size, on_progress);
+    let mut content = Vec::with_capacity(size as usize);
+    let mut chunk = vec![0; TRANSFER_CHUNK_BYTES];
+    loop {
+        let read = match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return failed(file_path, None, format!("Failed to read file: {}", e)),
+        };
+        if (content.len() + read) as u64 > max_bytes {
+            return failed(file_path, None, too_large());
         }
-        Err(e) => ImportResult {
-            success: false,
-            data: None,
+        content.extend_from_slice(&chunk[..read]);
+        progress.report(content.len() as u64);
+    }
+
+    let bytes = content.len() as u64;
+    let content = match String::from_utf8(content) {
+        Ok(content) => content,
+        Err(e) => return failed(file_path, None, format!("Failed to read file: {}", e)),
+    };
+    match is_valid_json(&content) {
+        Ok(()) => ImportResult {
+            success: true,
+            data: Some(content),
             file_path: Some(file_path),
-            bytes_read: None,
-            error: Some(format!("Failed to open file: {}", e)),
+            bytes_read: Some(bytes),
+            error: None,
         },
+        Err(e) => failed(file_path, Some(bytes), format!("Invalid JSON: {}", e)),
     }
 }
 
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get file metadata
+#[tauri::command]
+pub fn get_file_metadata(path: String) -> Option<FileMetadata> {
+    let p = Path::new(&path);
+    if !p.exists() {
+        return None;
+    }
+
+    let metadata = fs::metadata(p).ok()?;
+    let name = p.file_name()?.to_str()?.to_string();
+
+    let modified_at = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+
+    let created_at = metadata
+        .created()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+
+    Some(FileMetadata {
+        path,
+        name,
+        size: metadata.len(),
+        modified_at,
+        created_at,
+    })
+}
+
+/// Export data to a file, without blocking the main thread
+#[tauri::command]
+pub async fn export_data_to_file(app: tauri::AppHandle, options: ExportOptions) -> ExportResult {
+    let file_path = options.file_path.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        export_data(options, |progress| emit_event(&app, &progress))
+    })
+    .await
+    .unwrap_or_else(|e| ExportResult {
+        success: false,
+        file_path,
+        bytes_written: None,
+        error: Some(format!("Export failed: {}", e)),
+    })
+}
+
+/// Import data from a file, without blocking the main thread
+#[tauri::command]
+pub async fn import_data_from_file(app: tauri::AppHandle, options: ImportOptions) -> ImportResult {
+    let max_bytes = setting::<u64>(&app, MAX_IMPORT_SIZE_SETTING)
+        .unwrap_or(DEFAULT_MAX_IMPORT_SIZE_MB)
+        .saturating_mul(1024 * 1024);
+    let file_path = options.file_path.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        import_data(options, max_bytes, |progress| emit_event(&app, &progress))
+    })
+    .await
+    .unwrap_or_else(|e| ImportResult {
+        success: false,
+        data: None,
+        file_path,
+        bytes_read: None,
+        error: Some(format!("Import failed: {}", e)),
+    })
+}
+
 /// Get the default export directory (Documents folder)
 #[tauri::command]
 pub fn get_default_export_dir() -> Option<String> {
@@ -281,7 +510,7 @@ pub fn list_files_in_directory(path: String, extension: Option<String>) -> Vec<F
     }
 
     // Sort by modified time, newest first
-    files.sort_by(|a, b| b.modified_at.cmp(&a.modified_at));
+    files.sort_by_key(|f| std::cmp::Reverse(f.modified_at));
     files
 }
 
@@ -346,12 +575,7 @@ pub fn export_conversation(
     let file_path = export_dir.join(&file_name);
 
     // Pretty print JSON if possible
-    let formatted_data = match serde_json::from_str::<serde_json::Value>(&data) {
-        Ok(value) => serde_json::to_string_pretty(&value).unwrap_or(data),
-        Err(_) => data,
-    };
-
-    fs::write(&file_path, formatted_data)?;
+    write_json_file(&data, &file_path, true, |_| {})?;
     log::info!("Conversation exported to: {:?}", file_path);
 
     Ok(file_path.to_string_lossy().to_string())
@@ -402,12 +626,15 @@ mod tests {
 
     #[test]
     fn export_data_to_file_requires_path() {
-        let result = export_data_to_file(ExportOptions {
-            data: "{}".to_string(),
-            file_path: None,
-            file_name: None,
-            pretty_print: None,
-        });
+        let result = export_data(
+            ExportOptions {
+                data: "{}".to_string(),
+                file_path: None,
+                file_name: None,
+                pretty_print: None,
+            },
+            |_| {},
+        );
 
         assert!(!result.success);
         assert!(result.error.unwrap().contains("No file path"));
@@ -418,12 +645,15 @@ mod tests {
         let dir = tempdir().unwrap();
         let path = dir.path().join("export.json");
 
-        let result = export_data_to_file(ExportOptions {
-            data: "{\"a\":1}".to_string(),
-            file_path: Some(path_to_string(&path)),
-            file_name: None,
-            pretty_print: Some(true),
-        });
+        let result = export_data(
+            ExportOptions {
+                data: "{\"a\":1}".to_string(),
+                file_path: Some(path_to_string(&path)),
+                file_name: None,
+                pretty_print: Some(true),
+            },
+            |_| {},
+        );
 
         assert!(result.success);
         assert_eq!(fs::read_to_string(&path).unwrap(), "{\n  \"a\": 1\n}");
@@ -431,7 +661,7 @@ mod tests {
 
     #[test]
     fn import_data_from_file_requires_path() {
-        let result = import_data_from_file(ImportOptions { file_path: None });
+        let result = import_data(ImportOptions { file_path: None }, u64::MAX, |_| {});
 
         assert!(!result.success);
         assert!(result.error.unwrap().contains("No file path"));
@@ -443,9 +673,13 @@ mod tests {
         let path = dir.path().join("invalid.json");
         fs::write(&path, "not-json").unwrap();
 
-        let result = import_data_from_file(ImportOptions {
-            file_path: Some(path_to_string(&path)),
-        });
+        let result = import_data(
+            ImportOptions {
+                file_path: Some(path_to_string(&path)),
+            },
+            u64::MAX,
+            |_| {},
+        );
 
         assert!(!result.success);
         assert!(result.error.unwrap().contains("Invalid JSON"));
@@ -456,9 +690,13 @@ mod tests {
         let dir = tempdir().unwrap();
         let path = create_temp_file(&dir, "valid.json", "{\"test\":true}");
 
-        let result = import_data_from_file(ImportOptions {
-            file_path: Some(path_to_string(&path)),
-        });
+        let result = import_data(
+            ImportOptions {
+                file_path: Some(path_to_string(&path)),
+            },
+            u64::MAX,
+            |_| {},
+        );
 
         assert!(result.success);
         assert_eq!(result.data.unwrap(), "{\"test\":true}");
@@ -539,4 +777,51 @@ mod tests {
         assert!(ensure_directory(path_to_string(&nested)));
         assert!(nested.exists());
     }
+
+    #[test]
+    fn large_exports_stream_in_chunks_and_imports_respect_the_limit() {
+        let document = r#" {"a" : [], "b":{"c":[1, 2.5, {}]},"d":"x\\\"{,: ","e":[{"f":null}]} "#;
+        let expected = serde_json::to_string_pretty(
+            &serde_json::from_str::<serde_json::Value>(document).unwrap(),
+        )
+        .unwrap();
+        let mut printer = PrettyPrinter::default();
+        let mut out = Vec::new();
+        for byte in document.as_bytes().chunks(1) {
+            printer.feed(byte, &mut out).unwrap();
+        }
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("large.json");
+        let data = format!("[{}0]", "1234567,".repeat(1536 * 1024));
+        let mut events = Vec::new();
+        let result = export_data(
+            ExportOptions {
+                data: data.clone(),
+                file_path: Some(path_to_string(&path)),
+                file_name: None,
+                pretty_print: Some(false),
+            },
+            |progress| events.push(progress),
+        );
+        assert!(result.success);
+        assert_eq!(result.bytes_written, Some(data.len() as u64));
+        assert!(!dir.path().join("large.json.part").exists());
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].bytes, data.len() as u64);
+        assert_eq!(events[1].direction, TransferDirection::Export);
+
+        let options = || ImportOptions {
+            file_path: Some(path_to_string(&path)),
+        };
+        let mut events = Vec::new();
+        let result = import_data(options(), u64::MAX, |progress| events.push(progress));
+        assert_eq!(result.data.unwrap(), data);
+        assert_eq!(events.last().unwrap().bytes, data.len() as u64);
+
+        let result = import_data(options(), 1024 * 1024, |_| {});
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("too large"));
+    }
 }
@@ -0,0 +1,330 @@
+//! Namespaced key-value store for frontend state
+//!
+//! Small JSON values the webview would otherwise keep in localStorage (UI
+//! layouts, recent searches) are stored in `kv.db` in the app data directory,
+//! so clearing the webview cache does not lose them. Keys live in a namespace
+//! per feature, values and namespaces have size limits, and a value can be
+//! given a time to live after which it reads as missing.
+
+use crate::commands::migrations::open_store_database;
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tauri::Manager;
+
+/// Key-value database file name in the app data directory
+pub const KV_DB_FILE: &str = "kv.db";
+
+/// Schema migrations of the key-value database, in order (append only)
+pub const KV_MIGRATIONS: &[&str] = &[
+    // v1: values by namespace and key
+    "CREATE TABLE kv_entries (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        expires_at INTEGER,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (namespace, key)
+    );
+    CREATE INDEX idx_kv_entries_expires ON kv_entries(expires_at)
+        WHERE expires_at IS NOT NULL;",
+];
+
+const MAX_NAMESPACE_LEN: usize = 64;
+const MAX_KEY_LEN: usize = 256;
+/// Largest value, as JSON text
+const MAX_VALUE_BYTES: usize = 1024 * 1024;
+/// Largest total of the values in one namespace
+const MAX_NAMESPACE_BYTES: i64 = 16 * 1024 * 1024;
+
+/// Shared key-value database connection
+pub type KvState = Arc<Mutex<Connection>>;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KvEntry {
+    pub key: String,
+    pub value: Value,
+    /// Unix time after which the entry reads as missing
+    pub expires_at: Option<i64>,
+    pub updated_at: i64,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn get_kv_db_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let app_data = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(app_data.join(KV_DB_FILE))
+}
+
+/// Open the key-value database
+pub fn init_kv_state(app: &tauri::AppHandle) -> Result<KvState, AppError> {
+    let path = get_kv_db_path(app)?;
+    let conn = open_store_database(&path, "kv", KV_MIGRATIONS)?;
+    let purged = purge_expired(&conn, chrono::Utc::now().timestamp())?;
+    log::info!(
+        "Key-value database opened: {:?} ({} expired entries removed)",
+        path,
+        purged
+    );
+    Ok(Arc::new(Mutex::new(conn)))
+}
+
+/// Lock the key-value connection
+pub fn lock_kv(state: &KvState) -> Result<MutexGuard<'_, Connection>, AppError> {
+    state.lock().map_err(|e| AppError::Database(e.to_string()))
+}
+
+/// Namespaces are short names of letters, digits, `.`, `_` and `-`
+fn validate_namespace(namespace: &str) -> Result<(), AppError> {
+    let valid = !namespace.is_empty()
+        && namespace.len() <= MAX_NAMESPACE_LEN
+        && namespace
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid namespace: {}",
+            namespace
+        )));
+    }
+    Ok(())
+}
+
+fn validate_key(key: &str) -> Result<(), AppError> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "Keys must be 1 to {} bytes long",
+            MAX_KEY_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Delete expired entries, returning how many
+pub fn purge_expired(conn: &Connection, now: i64) -> Result<usize, AppError> {
+    Ok(conn.execute(
+        "DELETE FROM kv_entries WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+        params![now],
+    )?)
+}
+
+pub fn kv_get_value(
+    conn: &Connection,
+    namespace: &str,
+    key: &str,
+    now: i64,
+) -> Result<Option<Value>, AppError> {
+    validate_namespace(namespace)?;
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM kv_entries
+             WHERE namespace = ?1 AND key = ?2 AND (expires_at IS NULL OR expires_at > ?3)",
+            params![namespace, key, now],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(value
+        .map(|value| serde_json::from_str(&value))
+        .transpose()?)
+}
+
+/// Store a value, replacing any under the same key
+pub fn kv_set_value(
+    conn: &Connection,
+    namespace: &str,
+    key: &str,
+    value: &Value,
+    ttl_secs: Option<u64>,
+    now: i64,
+) -> Result<(), AppError> {
+    validate_namespace(namespace)?;
+    validate_key(key)?;
+    let text = serde_json::to_string(value)?;
+    if text.len() > MAX_VALUE_BYTES {
+        return Err(AppError::InvalidInput(format!(
+            "Value of {} bytes exceeds the limit of {} bytes",
+            text.len(),
+            MAX_VALUE_BYTES
+        )));
+    }
+    let others: i64 = conn.query_row(
+        "SELECT COALESCE(SUM(LENGTH(CAST(value AS BLOB))), 0) FROM kv_entries
+         WHERE namespace = ?1 AND key != ?2 AND (expires_at IS NULL OR expires_at > ?3)",
+        params![namespace, key, now],
+        |row| row.get(0),
+    )?;
+    if others + text.len() as i64 > MAX_NAMESPACE_BYTES {
+        return Err(AppError::InvalidInput(format!(
+            "Namespace {} would exceed its limit of {} bytes",
+            namespace, MAX_NAMESPACE_BYTES
+        )));
+    }
+    let expires_at = ttl_secs.map(|ttl| now.saturating_add(ttl.min(i64::MAX as u64) as i64));
+    conn.execute(
+        "INSERT INTO kv_entries (namespace, key, value, expires_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (namespace, key) DO UPDATE SET
+             value = excluded.value,
+             expires_at = excluded.expires_at,
+             updated_at = excluded.updated_at",
+        params![namespace, key, text, expires_at, now],
+    )?;
+    Ok(())
+}
+
+/// Live entries of a namespace, by key
+pub fn kv_list_entries(
+    conn: &Connection,
+    namespace: &str,
+    now: i64,
+) -> Result<Vec<KvEntry>, AppError> {
+    validate_namespace(namespace)?;
+    let mut stmt = conn.prepare(
+        "SELECT key, value, expires_at, updated_at FROM kv_entries
+         WHERE namespace = ?1 AND (expires_at IS NULL OR expires_at > ?2)
+         ORDER BY key",
+    )?;
+    let rows = stmt.query_map(params![namespace, now], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<i64>>(2)?,
+            row.get::<_, i64>(3)?,
+        ))
+    })?;
+    let mut entries = Vec::new();
+    for row in rows {
+        let (key, value, expires_at, updated_at) = row?;
+        entries.push(KvEntry {
+            key,
+            value: serde_json::from_str(&value)?,
+            expires_at,
+            updated_at,
+        });
+    }
+    Ok(entries)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get a value, or null when missing or expired
+#[tauri::command]
+pub fn kv_get(
+    state: tauri::State<'_, KvState>,
+    namespace: String,
+    key: String,
+) -> Result<Option<Value>, AppError> {
+    let conn = lock_kv(&state)?;
+    kv_get_value(&conn, &namespace, &key, chrono::Utc::now().timestamp())
+}
+
+/// Store a value, optionally expiring after `ttl_secs` seconds
+#[tauri::command]
+pub fn kv_set(
+    state: tauri::State<'_, KvState>,
+    namespace: String,
+    key: String,
+    value: Value,
+    ttl_secs: Option<u64>,
+) -> Result<(), AppError> {
+    let conn = lock_kv(&state)?;
+    kv_set_value(
+        &conn,
+        &namespace,
+        &key,
+        &value,
+        ttl_secs,
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+/// Delete a value, returning whether it existed
+#[tauri::command]
+pub fn kv_delete(
+    state: tauri::State<'_, KvState>,
+    namespace: String,
+    key: String,
+) -> Result<bool, AppError> {
+    validate_namespace(&namespace)?;
+    let conn = lock_kv(&state)?;
+    let deleted = conn.execute(
+        "DELETE FROM kv_entries WHERE namespace = ?1 AND key = ?2",
+        params![namespace, key],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// List the entries of a namespace
+#[tauri::command]
+pub fn kv_list(
+    state: tauri::State<'_, KvState>,
+    namespace: String,
+) -> Result<Vec<KvEntry>, AppError> {
+    let conn = lock_kv(&state)?;
+    kv_list_entries(&conn, &namespace, chrono::Utc::now().timestamp())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use serde_json::json;
+
+    #[test]
+    fn values_expire_and_respect_limits() {
+        let conn = db::open_in_memory(KV_MIGRATIONS).unwrap();
+        let layout = json!({"sidebar": 280, "panels": ["toc", "notes"]});
+        kv_set_value(&conn, "reader.layout", "main", &layout, None, 100).unwrap();
+        kv_set_value(&conn, "search", "recent", &json!(["rust"]), Some(60), 100).unwrap();
+        kv_set_value(&conn, "search", "recent", &json!(["tauri"]), Some(60), 110).unwrap();
+
+        assert_eq!(
+            kv_get_value(&conn, "reader.layout", "main", 1000).unwrap(),
+            Some(layout)
+        );
+        assert_eq!(
+            kv_get_value(&conn, "search", "recent", 169).unwrap(),
+            Some(json!(["tauri"]))
+        );
+        assert_eq!(kv_get_value(&conn, "search", "recent", 170).unwrap(), None);
+        let entries = kv_list_entries(&conn, "search", 120).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].expires_at, Some(170));
+        assert_eq!(purge_expired(&conn, 170).unwrap(), 1);
+        assert!(kv_list_entries(&conn, "search", 120).unwrap().is_empty());
+
+        assert!(kv_set_value(&conn, "../x", "k", &json!(1), None, 0).is_err());
+        assert!(kv_set_value(&conn, "ns", "", &json!(1), None, 0).is_err());
+        let large = json!("x".repeat(MAX_VALUE_BYTES));
+        assert!(kv_set_value(&conn, "ns", "k", &large, None, 0).is_err());
+        let chunk = json!("x".repeat(MAX_VALUE_BYTES - 2));
+        for i in 0..(MAX_NAMESPACE_BYTES as usize / MAX_VALUE_BYTES) {
+            kv_set_value(&conn, "ns", &i.to_string(), &chunk, None, 0).unwrap();
+        }
+        // Replacing a value counts only the new one
+        kv_set_value(&conn, "ns", "0", &chunk, None, 0).unwrap();
+        assert!(matches!(
+            kv_set_value(&conn, "ns", "extra", &chunk, None, 0),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+}
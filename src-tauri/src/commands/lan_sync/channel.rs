@@ -0,0 +1,315 @@
+//! Encrypted channel between two paired devices
+//!
+//! Both sides send a hello with an ephemeral X25519 key, then derive one
+//! AES-256-GCM key per direction with HKDF-SHA256, salted with the pairing
+//! code shown on the listening device. Each side then proves it holds the
+//! same keys, so a wrong code fails before any library data is sent.
+//!
+//! The pairing code keeps passive listeners out and makes a man in the
+//! middle fail the confirmation. It is short enough to type, so an attacker
+//! actively intercepting a pairing could still guess it offline afterwards,
+//! so listeners are meant to run only while the user is pairing.
+
+use super::types::SyncDevice;
+use crate::commands::hex::{from_hex, to_hex};
+use crate::error::AppError;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use ring::agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519};
+use ring::hkdf;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Protocol spoken over the channel
+pub const PROTOCOL: &str = "readium-sync/1";
+
+/// Characters of pairing codes, without look-alikes such as 0/O and 1/I
+const PAIRING_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const PAIRING_CODE_LEN: usize = 8;
+const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+/// Largest hello or key confirmation; anyone on the network can send these
+const MAX_HANDSHAKE_FRAME_LEN: usize = 4 * 1024;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Generate a random pairing code
+pub fn generate_pairing_code() -> Result<String, AppError> {
+    let mut bytes = [0u8; PAIRING_CODE_LEN];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| AppError::Internal("Failed to generate a pairing code".to_string()))?;
+    // The alphabet size divides 256, so every character is equally likely
+    Ok(bytes
+        .iter()
+        .map(|b| PAIRING_ALPHABET[*b as usize % PAIRING_ALPHABET.len()] as char)
+        .collect())
+}
+
+/// Normalize a typed pairing code (case, spaces and dashes)
+pub fn normalize_pairing_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+async fn write_frame<S: AsyncWrite + Unpin>(stream: &mut S, data: &[u8]) -> Result<(), AppError> {
+    if data.len() > MAX_FRAME_LEN {
+        return Err(AppError::InvalidInput(
+            "Sync message is too large".to_string(),
+        ));
+    }
+    stream.write_all(&(data.len() as u32).to_be_bytes()).await?;
+    stream.write_all(data).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Read a frame of at most `max_len` bytes
+async fn read_frame<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    max_len: usize,
+) -> Result<Vec<u8>, AppError> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > max_len {
+        return Err(AppError::InvalidInput(
+            "Sync message is too large".to_string(),
+        ));
+    }
+    let mut data = vec![0u8; len];
+    stream.read_exact(&mut data).await?;
+    Ok(data)
+}
+
+/// Unencrypted opening message of each side
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Hello {
+    protocol: String,
+    device: SyncDevice,
+    /// Ephemeral X25519 public key, hex encoded
+    public_key: String,
+}
+
+/// HKDF output length for both direction keys
+struct KeyMaterial;
+
+impl hkdf::KeyType for KeyMaterial {
+    fn len(&self) -> usize {
+        64
+    }
+}
+
+fn nonce(counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+/// An authenticated, encrypted connection to a paired device
+pub struct SecureChannel<S> {
+    stream: S,
+    sender: Aes256Gcm,
+    receiver: Aes256Gcm,
+    sent: u64,
+    received: u64,
+    /// The device on the other end
+    pub peer: SyncDevice,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SecureChannel<S> {
+    /// Pair with the device on the other end of `stream`
+    pub async fn handshake(
+        mut stream: S,
+        device: &SyncDevice,
+        pairing_code: &str,
+        initiator: bool,
+    ) -> Result<Self, AppError> {
+        let rng = SystemRandom::new();
+        let private_key = EphemeralPrivateKey::generate(&X25519, &rng)
+            .map_err(|_| AppError::Internal("Failed to generate a sync key".to_string()))?;
+        let public_key = private_key
+            .compute_public_key()
+            .map_err(|_| AppError::Internal("Failed to generate a sync key".to_string()))?;
+
+        let hello = Hello {
+            protocol: PROTOCOL.to_string(),
+            device: device.clone(),
+            public_key: to_hex(public_key.as_ref()),
+        };
+        write_frame(&mut stream, &serde_json::to_vec(&hello)?).await?;
+        let peer: Hello =
+            serde_json::from_slice(&read_frame(&mut stream, MAX_HANDSHAKE_FRAME_LEN).await?)?;
+        if peer.protocol != PROTOCOL {
+            return Err(AppError::InvalidInput(format!(
+                "Unsupported sync protocol '{}'",
+                peer.protocol
+            )));
+        }
+        let peer_key = from_hex(&peer.public_key)
+            .ok_or_else(|| AppError::InvalidInput("Invalid sync key".to_string()))?;
+
+        let (client_key, server_key) = if initiator {
+            (public_key.as_ref(), peer_key.as_slice())
+        } else {
+            (peer_key.as_slice(), public_key.as_ref())
+        };
+        let code = normalize_pairing_code(pairing_code);
+        let info = [PROTOCOL.as_bytes(), client_key, server_key];
+        let mut keys = [0u8; 64];
+        agreement::agree_ephemeral(
+            private_key,
+            &UnparsedPublicKey::new(&X25519, &peer_key),
+            |secret| {
+                hkdf::Salt::new(hkdf::HKDF_SHA256, code.as_bytes())
+                    .extract(secret)
+                    .expand(&info, KeyMaterial)
+                    .and_then(|okm| okm.fill(&mut keys))
+            },
+        )
+        .ok()
+        .and_then(Result::ok)
+        .ok_or_else(|| AppError::InvalidInput("Invalid sync key".to_string()))?;
+
+        let (client, server) = keys.split_at(32);
+        let (sender, receiver) = if initiator {
+            (client, server)
+        } else {
+            (server, client)
+        };
+        let mut channel = Self {
+            stream,
+            sender: Aes256Gcm::new_from_slice(sender)
+                .map_err(|e| AppError::Internal(e.to_string()))?,
+            receiver: Aes256Gcm::new_from_slice(receiver)
+                .map_err(|e| AppError::Internal(e.to_string()))?,
+            sent: 0,
+            received: 0,
+            peer: peer.device,
+        };
+
+        // Key confirmation: only a peer with the same code can decrypt this
+        channel.send(PROTOCOL.as_bytes()).await?;
+        match channel.recv_frame(MAX_HANDSHAKE_FRAME_LEN).await {
+            Ok(confirmation) if confirmation == PROTOCOL.as_bytes() => Ok(channel),
+            Ok(_) | Err(AppError::InvalidInput(_)) => Err(AppError::InvalidInput(
+                "The pairing code does not match".to_string(),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), AppError> {
+        let ciphertext = self
+            .sender
+            .encrypt(Nonce::from_slice(&nonce(self.sent)), data)
+            .map_err(|e| AppError::Internal(format!("Encryption failed: {}", e)))?;
+        self.sent += 1;
+        write_frame(&mut self.stream, &ciphertext).await
+    }
+
+    async fn recv(&mut self) -> Result<Vec<u8>, AppError> {
+        self.recv_frame(MAX_FRAME_LEN).await
+    }
+
+    async fn recv_frame(&mut self, max_len: usize) -> Result<Vec<u8>, AppError> {
+        let ciphertext = read_frame(&mut self.stream, max_len).await?;
+        let data = self
+            .receiver
+            .decrypt(
+                Nonce::from_slice(&nonce(self.received)),
+                ciphertext.as_slice(),
+            )
+            .map_err(|_| AppError::InvalidInput("Sync message failed to decrypt".to_string()))?;
+        self.received += 1;
+        Ok(data)
+    }
+
+    /// Send a value as encrypted JSON
+    pub async fn send_json<T: Serialize>(&mut self, value: &T) -> Result<(), AppError> {
+        self.send(&serde_json::to_vec(value)?).await
+    }
+
+    /// Receive a value sent with `send_json`
+    pub async fn recv_json<T: DeserializeOwned>(&mut self) -> Result<T, AppError> {
+        Ok(serde_json::from_slice(&self.recv().await?)?)
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str) -> SyncDevice {
+        SyncDevice {
+            device_id: id.to_string(),
+            device_name: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn pairing_codes_use_the_unambiguous_alphabet() {
+        let code = generate_pairing_code().unwrap();
+        assert_eq!(code.len(), PAIRING_CODE_LEN);
+        assert!(code.bytes().all(|c| PAIRING_ALPHABET.contains(&c)));
+        assert_eq!(normalize_pairing_code("abcd-23 xy"), "ABCD23XY");
+    }
+
+    #[tokio::test]
+    async fn channel_requires_the_same_pairing_code() {
+        let (laptop_device, phone_device) = (device("laptop"), device("phone"));
+        let (a, b) = tokio::io::duplex(1024);
+        let (laptop, phone) = tokio::join!(
+            SecureChannel::handshake(a, &laptop_device, "ABCD-EFGH", true),
+            SecureChannel::handshake(b, &phone_device, "abcdefgh", false),
+        );
+        let (mut laptop, mut phone) = (laptop.unwrap(), phone.unwrap());
+        assert_eq!(laptop.peer, device("phone"));
+        assert_eq!(phone.peer, device("laptop"));
+
+        laptop.send_json(&vec!["first", "second"]).await.unwrap();
+        phone.send_json(&42).await.unwrap();
+        assert_eq!(
+            phone.recv_json::<Vec<String>>().await.unwrap(),
+            vec!["first", "second"]
+        );
+        assert_eq!(laptop.recv_json::<u32>().await.unwrap(), 42);
+
+        let (a, b) = tokio::io::duplex(1024);
+        let (laptop, phone) = tokio::join!(
+            SecureChannel::handshake(a, &laptop_device, "ABCDEFGH", true),
+            SecureChannel::handshake(b, &phone_device, "ABCDEFGK", false),
+        );
+        for result in [laptop.err(), phone.err()] {
+            assert!(
+                matches!(result, Some(AppError::InvalidInput(m)) if m.contains("pairing code"))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn handshake_rejects_large_frames() {
+        let laptop_device = device("laptop");
+        let (a, mut b) = tokio::io::duplex(1024);
+        let stranger = async {
+            let claimed = (MAX_HANDSHAKE_FRAME_LEN as u32 + 1).to_be_bytes();
+            b.write_all(&claimed).await.unwrap();
+            b
+        };
+        let (laptop, _stranger) = tokio::join!(
+            SecureChannel::handshake(a, &laptop_device, "ABCDEFGH", true),
+            stranger,
+        );
+        assert!(matches!(laptop.err(), Some(AppError::InvalidInput(m)) if m.contains("too large")));
+    }
+}
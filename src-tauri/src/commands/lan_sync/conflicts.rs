@@ -0,0 +1,474 @@
+//! Version vectors of synced records and conflicts between devices
+//!
+//! Every synced record (document metadata, annotations) has a fingerprint of
+//! its content and a version vector: the time of the last edit per device.
+//! A change of fingerprint between two syncs counts as an edit by this
+//! device. When neither device's vector includes the other's edits, both
+//! changed the record independently and the conflict is kept until the user
+//! picks their version, the peer's, or a merge of both.
+
+use super::session::{get_sync_device_path, load_or_create_device};
+use super::types::{ResolvedConflict, SyncConflict, SyncMetadata, VersionVector};
+use crate::commands::annotations::AnnotationRecord;
+use crate::commands::library::{lock_library, LibraryState};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+pub const METADATA_RECORD: &str = "metadata";
+pub const ANNOTATION_RECORD: &str = "annotation";
+
+/// How two version vectors relate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Causality {
+    Equal,
+    /// The local version includes every remote edit, and more
+    LocalNewer,
+    /// The remote version includes every local edit, and more
+    RemoteNewer,
+    /// Both have edits the other has not seen
+    Concurrent,
+}
+
+/// What merging a remote record should do with it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reconciled {
+    Unchanged,
+    TakeRemote,
+    Conflict { local_version: VersionVector },
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn compare_versions(local: &VersionVector, remote: &VersionVector) -> Causality {
+    let mut local_ahead = false;
+    let mut remote_ahead = false;
+    for device in local.keys().chain(remote.keys()) {
+        let local_at = local.get(device).copied().unwrap_or(0);
+        let remote_at = remote.get(device).copied().unwrap_or(0);
+        local_ahead |= local_at > remote_at;
+        remote_ahead |= remote_at > local_at;
+    }
+    match (local_ahead, remote_ahead) {
+        (false, false) => Causality::Equal,
+        (true, false) => Causality::LocalNewer,
+        (false, true) => Causality::RemoteNewer,
+        (true, true) => Causality::Concurrent,
+    }
+}
+
+/// Combine two version vectors, keeping the latest edit of each device
+pub fn merge_versions(a: &VersionVector, b: &VersionVector) -> VersionVector {
+    let mut merged = a.clone();
+    for (device, at) in b {
+        let entry = merged.entry(device.clone()).or_insert(*at);
+        *entry = (*entry).max(*at);
+    }
+    merged
+}
+
+/// Add an edit by a device, later than any it made before
+fn bump_version(version: &mut VersionVector, device_id: &str, now: i64) {
+    let at = version
+        .get(device_id)
+        .map_or(now, |previous| now.max(previous + 1));
+    version.insert(device_id.to_string(), at);
+}
+
+/// Key of a synced record, shared by all devices
+pub fn record_key(kind: &str, document_hash: &str, record_id: Option<&str>) -> String {
+    match record_id {
+        Some(id) => format!("{}:{}:{}", kind, document_hash, id),
+        None => format!("{}:{}", kind, document_hash),
+    }
+}
+
+/// Fingerprint of the content of a record
+pub fn fingerprint<T: Serialize>(value: &T) -> Result<String, AppError> {
+    Ok(format!("{:x}", Sha256::digest(serde_json::to_vec(value)?)))
+}
+
+fn get_sync_record(
+    conn: &Connection,
+    key: &str,
+) -> Result<Option<(String, VersionVector)>, AppError> {
+    let record = conn
+        .query_row(
+            "SELECT fingerprint, version FROM sync_records WHERE record_key = ?1",
+            params![key],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?;
+    Ok(record.map(|(fingerprint, version)| {
+        (
+            fingerprint,
+            serde_json::from_str(&version).unwrap_or_default(),
+        )
+    }))
+}
+
+fn save_sync_record(
+    conn: &Connection,
+    key: &str,
+    fingerprint: &str,
+    version: &VersionVector,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO sync_records (record_key, fingerprint, version)
+         VALUES (?1, ?2, ?3)",
+        params![key, fingerprint, serde_json::to_string(version)?],
+    )?;
+    Ok(())
+}
+
+/// Note the current content of a record, returning its version vector
+///
+/// Content that changed since it was last noted is an edit by this device.
+pub fn observe_record<T: Serialize>(
+    conn: &Connection,
+    key: &str,
+    value: &T,
+    device_id: &str,
+    now: i64,
+) -> Result<VersionVector, AppError> {
+    let current = fingerprint(value)?;
+    let mut version = match get_sync_record(conn, key)? {
+        Some((fingerprint, version)) if fingerprint == current => return Ok(version),
+        Some((_, version)) => version,
+        None => VersionVector::new(),
+    };
+    bump_version(&mut version, device_id, now);
+    save_sync_record(conn, key, &current, &version)?;
+    Ok(version)
+}
+
+/// Decide what to do with a remote record, updating the local version
+pub fn reconcile_record<T: Serialize>(
+    conn: &Connection,
+    key: &str,
+    device_id: &str,
+    local: Option<&T>,
+    remote: &T,
+    remote_version: &VersionVector,
+    now: i64,
+) -> Result<Reconciled, AppError> {
+    let remote_fingerprint = fingerprint(remote)?;
+    let Some(local) = local else {
+        save_sync_record(conn, key, &remote_fingerprint, remote_version)?;
+        return Ok(Reconciled::TakeRemote);
+    };
+    let local_version = observe_record(conn, key, local, device_id, now)?;
+    let local_fingerprint = fingerprint(local)?;
+    let merged = merge_versions(&local_version, remote_version);
+
+    if local_fingerprint == remote_fingerprint {
+        save_sync_record(conn, key, &local_fingerprint, &merged)?;
+        return Ok(Reconciled::Unchanged);
+    }
+    match compare_versions(&local_version, remote_version) {
+        Causality::RemoteNewer => {
+            save_sync_record(conn, key, &remote_fingerprint, &merged)?;
+            Ok(Reconciled::TakeRemote)
+        }
+        Causality::Concurrent => Ok(Reconciled::Conflict { local_version }),
+        Causality::Equal | Causality::LocalNewer => {
+            save_sync_record(conn, key, &local_fingerprint, &merged)?;
+            Ok(Reconciled::Unchanged)
+        }
+    }
+}
+
+/// Store a conflict, replacing an earlier one for the same record
+pub fn save_conflict(
+    conn: &Connection,
+    key: &str,
+    conflict: &SyncConflict,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO sync_conflicts (id, record_key, kind, document_id, record_id,
+             peer_device_id, peer_device_name, local_value, remote_value,
+             local_version, remote_version, detected_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+         ON CONFLICT(record_key) DO UPDATE SET
+             document_id = excluded.document_id,
+             peer_device_id = excluded.peer_device_id,
+             peer_device_name = excluded.peer_device_name,
+             local_value = excluded.local_value,
+             remote_value = excluded.remote_value,
+             local_version = excluded.local_version,
+             remote_version = excluded.remote_version,
+             detected_at = excluded.detected_at",
+        params![
+            conflict.id,
+            key,
+            conflict.kind,
+            conflict.document_id,
+            conflict.record_id,
+            conflict.peer_device_id,
+            conflict.peer_device_name,
+            conflict.local.to_string(),
+            conflict.remote.to_string(),
+            serde_json::to_string(&conflict.local_version)?,
+            serde_json::to_string(&conflict.remote_version)?,
+            conflict.detected_at
+        ],
+    )?;
+    Ok(())
+}
+
+const CONFLICT_COLUMNS: &str = "id, kind, document_id, record_id, peer_device_id,
+    peer_device_name, local_value, remote_value, local_version, remote_version,
+    detected_at, record_key";
+
+fn row_to_conflict(row: &Row) -> rusqlite::Result<(SyncConflict, String)> {
+    let json = |index: usize| -> rusqlite::Result<serde_json::Value> {
+        Ok(serde_json::from_str(&row.get::<_, String>(index)?).unwrap_or_default())
+    };
+    let version = |index: usize| -> rusqlite::Result<VersionVector> {
+        Ok(serde_json::from_str(&row.get::<_, String>(index)?).unwrap_or_default())
+    };
+    Ok((
+        SyncConflict {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            document_id: row.get(2)?,
+            record_id: row.get(3)?,
+            peer_device_id: row.get(4)?,
+            peer_device_name: row.get(5)?,
+            local: json(6)?,
+            remote: json(7)?,
+            local_version: version(8)?,
+            remote_version: version(9)?,
+            detected_at: row.get(10)?,
+        },
+        row.get(11)?,
+    ))
+}
+
+/// List conflicts, of one document or all of them
+pub fn list_conflicts(
+    conn: &Connection,
+    document_id: Option<&str>,
+) -> Result<Vec<SyncConflict>, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM sync_conflicts WHERE ?1 IS NULL OR document_id = ?1
+         ORDER BY detected_at DESC, id",
+        CONFLICT_COLUMNS
+    ))?;
+    let conflicts = stmt
+        .query_map(params![document_id], |row| {
+            row_to_conflict(row).map(|(c, _)| c)
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(conflicts)
+}
+
+/// Merge two versions of metadata, preferring this device's on a clash
+fn merge_metadata(local: SyncMetadata, remote: SyncMetadata) -> SyncMetadata {
+    let metadata = match (local.metadata, remote.metadata) {
+        (serde_json::Value::Object(local), serde_json::Value::Object(mut merged)) => {
+            merged.extend(local);
+            serde_json::Value::Object(merged)
+        }
+        (serde_json::Value::Null, remote) => remote,
+        (local, _) => local,
+    };
+    SyncMetadata {
+        title: local.title.or(remote.title),
+        author: local.author.or(remote.author),
+        metadata,
+    }
+}
+
+/// Merge two versions of an annotation, keeping both notes when they differ
+fn merge_annotation(local: AnnotationRecord, remote: AnnotationRecord) -> AnnotationRecord {
+    let note = match (local.note, remote.note) {
+        (Some(mine), Some(theirs)) if mine.trim() != theirs.trim() => {
+            Some(format!("{}\n\n{}", mine, theirs))
+        }
+        (mine, theirs) => mine.or(theirs),
+    };
+    AnnotationRecord {
+        id: local.id,
+        kind: local.kind,
+        page_number: local.page_number.or(remote.page_number),
+        content: local.content.or(remote.content),
+        note,
+        color: local.color.or(remote.color),
+        timestamp: local.timestamp.max(remote.timestamp),
+    }
+}
+
+fn merge_values(
+    kind: &str,
+    local: serde_json::Value,
+    remote: serde_json::Value,
+) -> Result<serde_json::Value, AppError> {
+    Ok(match kind {
+        METADATA_RECORD => serde_json::to_value(merge_metadata(
+            serde_json::from_value(local)?,
+            serde_json::from_value(remote)?,
+        ))?,
+        _ => serde_json::to_value(merge_annotation(
+            serde_json::from_value(local)?,
+            serde_json::from_value(remote)?,
+        ))?,
+    })
+}
+
+/// Resolve a conflict with this device's version, the peer's, or a merge
+pub fn resolve_conflict(
+    conn: &Connection,
+    id: &str,
+    resolution: &str,
+    device_id: &str,
+    now: i64,
+) -> Result<ResolvedConflict, AppError> {
+    let (conflict, key) = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM sync_conflicts WHERE id = ?1",
+                CONFLICT_COLUMNS
+            ),
+            params![id],
+            row_to_conflict,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Sync conflict '{}' not found", id)))?;
+
+    let mut version = merge_versions(&conflict.local_version, &conflict.remote_version);
+    let value = match resolution {
+        "keep-mine" => conflict.local.clone(),
+        "keep-theirs" => conflict.remote.clone(),
+        "merge" => merge_values(
+            &conflict.kind,
+            conflict.local.clone(),
+            conflict.remote.clone(),
+        )?,
+        other => {
+            return Err(AppError::InvalidInput(format!(
+                "Unknown conflict resolution: {}",
+                other
+            )))
+        }
+    };
+    // A new value is a new edit, so the peer takes it on the next sync
+    if resolution != "keep-theirs" {
+        bump_version(&mut version, device_id, now);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    if conflict.kind == METADATA_RECORD {
+        let metadata: SyncMetadata = serde_json::from_value(value.clone())?;
+        tx.execute(
+            "UPDATE documents SET title = ?2, author = ?3, metadata = ?4, updated_at = ?5
+             WHERE hash = (SELECT hash FROM documents WHERE id = ?1)",
+            params![
+                conflict.document_id,
+                metadata.title,
+                metadata.author,
+                metadata.metadata.to_string(),
+                now
+            ],
+        )?;
+    }
+    save_sync_record(&tx, &key, &fingerprint(&value)?, &version)?;
+    tx.execute("DELETE FROM sync_conflicts WHERE id = ?1", params![id])?;
+    tx.commit()?;
+
+    Ok(ResolvedConflict {
+        conflict_id: conflict.id,
+        kind: conflict.kind,
+        document_id: conflict.document_id,
+        record_id: conflict.record_id,
+        resolution: resolution.to_string(),
+        value,
+    })
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// List records edited on this device and a peer, of one document or all
+#[tauri::command]
+pub fn lan_sync_list_conflicts(
+    state: tauri::State<'_, LibraryState>,
+    document_id: Option<String>,
+) -> Result<Vec<SyncConflict>, AppError> {
+    let conn = lock_library(&state)?;
+    list_conflicts(&conn, document_id.as_deref())
+}
+
+/// Resolve a sync conflict: "keep-mine", "keep-theirs" or "merge"
+#[tauri::command]
+pub fn lan_sync_resolve_conflict(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, LibraryState>,
+    id: String,
+    resolution: String,
+) -> Result<ResolvedConflict, AppError> {
+    let device = load_or_create_device(&get_sync_device_path(&app)?)?;
+    let conn = lock_library(&state)?;
+    resolve_conflict(
+        &conn,
+        &id,
+        &resolution,
+        &device.device_id,
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(entries: &[(&str, i64)]) -> VersionVector {
+        entries.iter().map(|(d, at)| (d.to_string(), *at)).collect()
+    }
+
+    #[test]
+    fn version_vectors_order_edits() {
+        let a = version(&[("laptop", 5), ("phone", 3)]);
+        assert_eq!(compare_versions(&a, &a), Causality::Equal);
+        assert_eq!(
+            compare_versions(&a, &version(&[("laptop", 5)])),
+            Causality::LocalNewer
+        );
+        assert_eq!(
+            compare_versions(&a, &version(&[("laptop", 6), ("phone", 3)])),
+            Causality::RemoteNewer
+        );
+        assert_eq!(
+            compare_versions(&a, &version(&[("laptop", 4), ("phone", 9)])),
+            Causality::Concurrent
+        );
+        assert_eq!(
+            merge_versions(&a, &version(&[("laptop", 4), ("tablet", 1)])),
+            version(&[("laptop", 5), ("phone", 3), ("tablet", 1)])
+        );
+    }
+
+    #[test]
+    fn annotations_merge_keeps_both_notes() {
+        let annotation = |note: &str, color: Option<&str>| AnnotationRecord {
+            id: "a1".to_string(),
+            kind: "highlight".to_string(),
+            page_number: Some(3),
+            content: Some("text".to_string()),
+            note: Some(note.to_string()),
+            color: color.map(str::to_string),
+            timestamp: 1,
+        };
+        let merged = merge_annotation(annotation("mine", None), annotation("theirs", Some("red")));
+        assert_eq!(merged.note.as_deref(), Some("mine\n\ntheirs"));
+        assert_eq!(merged.color.as_deref(), Some("red"));
+    }
+}
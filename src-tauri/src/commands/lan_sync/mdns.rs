@@ -0,0 +1,372 @@
+//! Minimal mDNS (RFC 6762) discovery of sync listeners
+//!
+//! Only what discovery needs is implemented: a PTR query for the sync
+//! service type, and an answer carrying the PTR, SRV and TXT records of this
+//! device. Queries are sent from an ephemeral port, so responders answer by
+//! unicast and the browser does not have to join the multicast group.
+
+use super::types::{LanPeer, SyncDevice};
+use crate::error::AppError;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// DNS-SD service type of sync listeners
+pub const SERVICE_TYPE: &str = "_readium-sync._tcp.local";
+
+const MDNS_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// Cache-flush bit of record classes, unicast-response bit of question classes
+const CLASS_FLAG: u16 = 0x8000;
+const RECORD_TTL: u32 = 120;
+const TXT_VERSION: &str = "v=1";
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        out.push(label.len() as u8);
+        out.extend_from_slice(label);
+    }
+    out.push(0);
+}
+
+fn write_record(out: &mut Vec<u8>, name: &str, kind: u16, class: u16, data: &[u8]) {
+    write_name(out, name);
+    write_u16(out, kind);
+    write_u16(out, class);
+    out.extend_from_slice(&RECORD_TTL.to_be_bytes());
+    write_u16(out, data.len() as u16);
+    out.extend_from_slice(data);
+}
+
+fn read_u16(packet: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *packet.get(pos)?,
+        *packet.get(pos + 1)?,
+    ]))
+}
+
+/// Read a possibly compressed name, returning it and the position after it
+fn read_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    for _ in 0..128 {
+        let len = *packet.get(pos)? as usize;
+        match len {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            l if l & 0xC0 == 0xC0 => {
+                let target = (read_u16(packet, pos)? & 0x3FFF) as usize;
+                end.get_or_insert(pos + 2);
+                pos = target;
+            }
+            l => {
+                let label = packet.get(pos + 1..pos + 1 + l)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + l;
+            }
+        }
+    }
+    None
+}
+
+/// A resource record, with its data as a range of the packet
+struct Record {
+    name: String,
+    kind: u16,
+    data: std::ops::Range<usize>,
+}
+
+/// Header flags, questions (name and type) and records of a packet
+struct Packet {
+    flags: u16,
+    questions: Vec<(String, u16)>,
+    records: Vec<Record>,
+}
+
+fn parse_packet(packet: &[u8]) -> Option<Packet> {
+    let flags = read_u16(packet, 2)?;
+    let questions = read_u16(packet, 4)?;
+    let records: usize = (6..12)
+        .step_by(2)
+        .map(|pos| read_u16(packet, pos).map(usize::from))
+        .sum::<Option<usize>>()?;
+
+    let mut pos = 12;
+    let mut parsed_questions = Vec::new();
+    for _ in 0..questions {
+        let (name, next) = read_name(packet, pos)?;
+        parsed_questions.push((name, read_u16(packet, next)?));
+        pos = next + 4;
+    }
+    let mut parsed_records = Vec::new();
+    for _ in 0..records {
+        let (name, next) = read_name(packet, pos)?;
+        let kind = read_u16(packet, next)?;
+        let len = read_u16(packet, next + 8)? as usize;
+        let start = next + 10;
+        if start + len > packet.len() {
+            return None;
+        }
+        parsed_records.push(Record {
+            name,
+            kind,
+            data: start..start + len,
+        });
+        pos = start + len;
+    }
+    Some(Packet {
+        flags,
+        questions: parsed_questions,
+        records: parsed_records,
+    })
+}
+
+fn is_service_name(name: &str) -> bool {
+    name.trim_end_matches('.')
+        .eq_ignore_ascii_case(SERVICE_TYPE)
+}
+
+/// Query for sync listeners, asking for unicast answers
+pub fn build_query() -> Vec<u8> {
+    let mut out = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    write_name(&mut out, SERVICE_TYPE);
+    write_u16(&mut out, TYPE_PTR);
+    write_u16(&mut out, CLASS_IN | CLASS_FLAG);
+    out
+}
+
+/// Answer announcing the listener of a device
+pub fn build_announcement(device: &SyncDevice, port: u16, address: Option<Ipv4Addr>) -> Vec<u8> {
+    let instance = format!("{}.{}", device.device_id, SERVICE_TYPE);
+    let host = format!("{}.local", device.device_id);
+    let count = if address.is_some() { 4 } else { 3 };
+    let mut out = vec![0, 0, 0x84, 0, 0, 0, 0, count, 0, 0, 0, 0];
+
+    let mut ptr = Vec::new();
+    write_name(&mut ptr, &instance);
+    write_record(&mut out, SERVICE_TYPE, TYPE_PTR, CLASS_IN, &ptr);
+
+    let mut srv = vec![0, 0, 0, 0];
+    write_u16(&mut srv, port);
+    write_name(&mut srv, &host);
+    write_record(&mut out, &instance, TYPE_SRV, CLASS_IN | CLASS_FLAG, &srv);
+
+    let mut txt = Vec::new();
+    let mut name = format!("name={}", device.device_name);
+    while name.len() > 255 {
+        name.pop();
+    }
+    for entry in [format!("id={}", device.device_id), name, TXT_VERSION.into()] {
+        txt.push(entry.len() as u8);
+        txt.extend_from_slice(entry.as_bytes());
+    }
+    write_record(&mut out, &instance, TYPE_TXT, CLASS_IN | CLASS_FLAG, &txt);
+
+    if let Some(address) = address {
+        write_record(
+            &mut out,
+            &host,
+            TYPE_A,
+            CLASS_IN | CLASS_FLAG,
+            &address.octets(),
+        );
+    }
+    out
+}
+
+/// Whether a packet is a query for sync listeners
+pub fn is_service_query(packet: &[u8]) -> bool {
+    parse_packet(packet).is_some_and(|parsed| {
+        parsed.flags & 0x8000 == 0
+            && parsed
+                .questions
+                .iter()
+                .any(|(name, kind)| is_service_name(name) && matches!(*kind, TYPE_PTR | TYPE_ANY))
+    })
+}
+
+/// Read the peer announced by an answer; the address is where it came from
+pub fn parse_announcement(packet: &[u8], source: IpAddr) -> Option<LanPeer> {
+    let Packet { flags, records, .. } = parse_packet(packet)?;
+    if flags & 0x8000 == 0 {
+        return None;
+    }
+    let in_service = |r: &&Record| {
+        r.name
+            .split_once('.')
+            .is_some_and(|(_, service)| is_service_name(service))
+    };
+
+    let srv = records
+        .iter()
+        .filter(in_service)
+        .find(|r| r.kind == TYPE_SRV)?;
+    let port = read_u16(packet, srv.data.start + 4)?;
+
+    let txt = records
+        .iter()
+        .filter(in_service)
+        .find(|r| r.kind == TYPE_TXT && r.name == srv.name)?;
+    let mut entries = HashMap::new();
+    let mut pos = txt.data.start;
+    while pos < txt.data.end {
+        let len = packet[pos] as usize;
+        let entry = packet.get(pos + 1..(pos + 1 + len).min(txt.data.end))?;
+        let entry = String::from_utf8_lossy(entry);
+        let (key, value) = entry.split_once('=').unwrap_or((&entry, ""));
+        entries.insert(key.to_string(), value.to_string());
+        pos += 1 + len;
+    }
+    if entries.get("v").map(String::as_str) != Some("1") {
+        return None;
+    }
+
+    Some(LanPeer {
+        device_id: entries.remove("id").filter(|id| !id.is_empty())?,
+        device_name: entries.remove("name").unwrap_or_default(),
+        address: source.to_string(),
+        port,
+    })
+}
+
+/// Address of the interface used to reach other hosts, if any
+pub fn local_ipv4() -> Option<Ipv4Addr> {
+    // Connecting a UDP socket only selects a route, nothing is sent
+    let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).ok()?;
+    match socket.local_addr().ok()?.ip() {
+        IpAddr::V4(address) if !address.is_loopback() => Some(address),
+        _ => None,
+    }
+}
+
+/// Look for sync listeners on the local network until the timeout
+pub async fn discover_peers(own_id: &str, timeout: Duration) -> Result<Vec<LanPeer>, AppError> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket
+        .send_to(&build_query(), (MDNS_ADDR, MDNS_PORT))
+        .await?;
+
+    let mut peers: Vec<LanPeer> = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let deadline = tokio::time::Instant::now() + timeout;
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await
+    {
+        let (len, source) = received?;
+        let Some(peer) = parse_announcement(&buffer[..len], source.ip()) else {
+            continue;
+        };
+        if peer.device_id != own_id && !peers.iter().any(|p| p.device_id == peer.device_id) {
+            peers.push(peer);
+        }
+    }
+    Ok(peers)
+}
+
+/// Bind the shared mDNS port and join the multicast group
+pub fn bind_responder() -> Result<UdpSocket, AppError> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, MDNS_PORT)).into())?;
+    socket.join_multicast_v4(&MDNS_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_nonblocking(true)?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+/// Answer queries for sync listeners until the task is aborted
+pub async fn respond(socket: UdpSocket, device: SyncDevice, port: u16) {
+    let multicast = SocketAddr::V4(SocketAddrV4::new(MDNS_ADDR, MDNS_PORT));
+    let announcement = build_announcement(&device, port, local_ipv4());
+    if let Err(e) = socket.send_to(&announcement, multicast).await {
+        log::warn!("Failed to announce sync listener: {}", e);
+    }
+
+    let mut buffer = [0u8; 4096];
+    loop {
+        let (len, source) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) => {
+                log::warn!("mDNS responder stopped: {}", e);
+                return;
+            }
+        };
+        if !is_service_query(&buffer[..len]) {
+            continue;
+        }
+        // Queries from the mDNS port get multicast answers (RFC 6762 6.7)
+        let target = if source.port() == MDNS_PORT {
+            multicast
+        } else {
+            source
+        };
+        let announcement = build_announcement(&device, port, local_ipv4());
+        if let Err(e) = socket.send_to(&announcement, target).await {
+            log::warn!("Failed to answer mDNS query: {}", e);
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn announcements_round_trip() {
+        let device = SyncDevice {
+            device_id: "2f1c".to_string(),
+            device_name: "Study Laptop".to_string(),
+        };
+        let source: IpAddr = "192.168.1.20".parse().unwrap();
+
+        assert!(is_service_query(&build_query()));
+        let answer = build_announcement(&device, 48123, Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert!(!is_service_query(&answer));
+        assert_eq!(parse_announcement(&build_query(), source), None);
+        assert_eq!(
+            parse_announcement(&answer, source),
+            Some(LanPeer {
+                device_id: "2f1c".to_string(),
+                device_name: "Study Laptop".to_string(),
+                address: "192.168.1.20".to_string(),
+                port: 48123,
+            })
+        );
+    }
+
+    #[test]
+    fn names_follow_compression_pointers() {
+        // "local" at offset 0, then "_tcp" pointing back at it
+        let packet = [
+            5, b'l', b'o', b'c', b'a', b'l', 0, 4, b'_', b't', b'c', b'p', 0xC0, 0,
+        ];
+        assert_eq!(read_name(&packet, 7), Some(("_tcp.local".to_string(), 14)));
+
+        // Pointer loops are rejected
+        assert_eq!(read_name(&[0xC0, 0], 0), None);
+    }
+}
@@ -0,0 +1,475 @@
+//! Library snapshots exchanged with peers and how they are merged
+//!
+//! Documents are matched by content hash, since paths differ between
+//! devices; documents only one side has are left out. Metadata and
+//! annotations follow their version vectors (see `conflicts`), the latest
+//! reading position wins, bookmarks are combined, and annotations are handed
+//! to the frontend, which owns them.
+
+use super::conflicts::{
+    observe_record, reconcile_record, record_key, save_conflict, Reconciled, ANNOTATION_RECORD,
+    METADATA_RECORD,
+};
+use super::types::{
+    LanSyncReport, SyncBookmark, SyncConflict, SyncDevice, SyncDocument, SyncMetadata,
+    SyncProgress, SyncSnapshot, SyncedAnnotations, VersionVector,
+};
+use crate::commands::annotations::{AnnotatedDocument, AnnotationRecord};
+use crate::commands::library::{get_progress, list_bookmarks, save_progress};
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Annotations the frontend has for any of the given documents, by id
+fn local_annotations<'a>(
+    annotations: &'a [AnnotatedDocument],
+    ids: &[String],
+) -> HashMap<&'a str, &'a AnnotationRecord> {
+    annotations
+        .iter()
+        .filter(|a| a.document_id.as_ref().is_some_and(|id| ids.contains(id)))
+        .flat_map(|a| a.annotations.iter())
+        .map(|a| (a.id.as_str(), a))
+        .collect()
+}
+
+/// Snapshot the library, with the frontend's annotations of each document
+///
+/// Local edits since the last snapshot are added to the version vectors.
+pub fn build_snapshot(
+    conn: &Connection,
+    device: &SyncDevice,
+    annotations: &[AnnotatedDocument],
+    now: i64,
+) -> Result<SyncSnapshot, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, hash, title, author, metadata, updated_at FROM documents ORDER BY added_at, id",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            let metadata: String = row.get(4)?;
+            Ok((
+                row.get::<_, String>(0)?,
+                SyncDocument {
+                    hash: row.get(1)?,
+                    title: row.get(2)?,
+                    author: row.get(3)?,
+                    metadata: serde_json::from_str(&metadata).unwrap_or_default(),
+                    updated_at: row.get(5)?,
+                    metadata_version: VersionVector::new(),
+                    progress: None,
+                    bookmarks: Vec::new(),
+                    annotations: Vec::new(),
+                    annotation_versions: BTreeMap::new(),
+                },
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut seen = HashSet::new();
+    let mut documents = Vec::with_capacity(rows.len());
+    for (id, mut document) in rows {
+        // Copies of the same file share progress, send one of them
+        if !seen.insert(document.hash.clone()) {
+            continue;
+        }
+        let metadata = SyncMetadata {
+            title: document.title.clone(),
+            author: document.author.clone(),
+            metadata: document.metadata.clone(),
+        };
+        document.metadata_version = observe_record(
+            conn,
+            &record_key(METADATA_RECORD, &document.hash, None),
+            &metadata,
+            &device.device_id,
+            now,
+        )?;
+        document.progress = get_progress(conn, &id)?.map(|p| SyncProgress {
+            locator: p.locator,
+            percent: p.percent,
+            last_read_at: p.last_read_at,
+        });
+        document.bookmarks = list_bookmarks(conn, &id)?
+            .into_iter()
+            .map(|b| SyncBookmark {
+                id: b.id,
+                locator: b.locator,
+                label: b.label,
+                created_at: b.created_at,
+            })
+            .collect();
+        for annotation in local_annotations(annotations, std::slice::from_ref(&id)).into_values() {
+            let version = observe_record(
+                conn,
+                &record_key(ANNOTATION_RECORD, &document.hash, Some(&annotation.id)),
+                annotation,
+                &device.device_id,
+                now,
+            )?;
+            document
+                .annotation_versions
+                .insert(annotation.id.clone(), version);
+            document.annotations.push(annotation.clone());
+        }
+        document.annotations.sort_by(|a, b| a.id.cmp(&b.id));
+        documents.push(document);
+    }
+
+    Ok(SyncSnapshot {
+        device: device.clone(),
+        generated_at: now,
+        documents,
+    })
+}
+
+/// Merge a peer's snapshot into the library
+///
+/// `annotations` are this device's, to tell edits of the same annotation
+/// on both devices apart from edits on one of them.
+pub fn merge_snapshot(
+    conn: &Connection,
+    device: &SyncDevice,
+    annotations: &[AnnotatedDocument],
+    snapshot: SyncSnapshot,
+    now: i64,
+) -> Result<LanSyncReport, AppError> {
+    let peer = snapshot.device;
+    let mut report = LanSyncReport {
+        peer: peer.clone(),
+        matched_documents: 0,
+        unmatched_documents: 0,
+        updated_metadata: 0,
+        updated_progress: 0,
+        added_bookmarks: 0,
+        annotations: Vec::new(),
+        conflicts: 0,
+        synced_at: now,
+    };
+    let conflict = |kind: &str, document_id: &str, record_id: Option<&str>| SyncConflict {
+        id: uuid::Uuid::new_v4().to_string(),
+        kind: kind.to_string(),
+        document_id: document_id.to_string(),
+        record_id: record_id.map(str::to_string),
+        peer_device_id: peer.device_id.clone(),
+        peer_device_name: peer.device_name.clone(),
+        local: serde_json::Value::Null,
+        remote: serde_json::Value::Null,
+        local_version: VersionVector::new(),
+        remote_version: VersionVector::new(),
+        detected_at: now,
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    for remote in snapshot.documents {
+        let mut stmt = tx.prepare("SELECT id FROM documents WHERE hash = ?1 ORDER BY added_at")?;
+        let ids = stmt
+            .query_map(params![remote.hash], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(document_id) = ids.first() else {
+            report.unmatched_documents += 1;
+            continue;
+        };
+        report.matched_documents += 1;
+
+        let local = tx.query_row(
+            "SELECT title, author, metadata FROM documents WHERE id = ?1",
+            params![document_id],
+            |row| {
+                let metadata: String = row.get(2)?;
+                Ok(SyncMetadata {
+                    title: row.get(0)?,
+                    author: row.get(1)?,
+                    metadata: serde_json::from_str(&metadata).unwrap_or_default(),
+                })
+            },
+        )?;
+        let theirs = SyncMetadata {
+            title: remote.title.clone(),
+            author: remote.author.clone(),
+            metadata: remote.metadata.clone(),
+        };
+        let key = record_key(METADATA_RECORD, &remote.hash, None);
+        match reconcile_record(
+            &tx,
+            &key,
+            &device.device_id,
+            Some(&local),
+            &theirs,
+            &remote.metadata_version,
+            now,
+        )? {
+            Reconciled::TakeRemote => {
+                report.updated_metadata += tx.execute(
+                    "UPDATE documents SET title = ?2, author = ?3, metadata = ?4, updated_at = ?5
+                     WHERE hash = ?1",
+                    params![
+                        remote.hash,
+                        theirs.title,
+                        theirs.author,
+                        theirs.metadata.to_string(),
+                        remote.updated_at
+                    ],
+                )?;
+            }
+            Reconciled::Conflict { local_version } => {
+                let conflict = SyncConflict {
+                    local: serde_json::to_value(&local)?,
+                    remote: serde_json::to_value(&theirs)?,
+                    local_version,
+                    remote_version: remote.metadata_version.clone(),
+                    ..conflict(METADATA_RECORD, document_id, None)
+                };
+                save_conflict(&tx, &key, &conflict)?;
+                report.conflicts += 1;
+            }
+            Reconciled::Unchanged => {}
+        }
+
+        for id in &ids {
+            if let Some(progress) = &remote.progress {
+                let newer = get_progress(&tx, id)?
+                    .map_or(true, |local| local.last_read_at < progress.last_read_at);
+                if newer {
+                    save_progress(
+                        &tx,
+                        id,
+                        &progress.locator,
+                        progress.percent,
+                        progress.last_read_at,
+                    )?;
+                    report.updated_progress += 1;
+                }
+            }
+
+            let local: HashSet<String> = list_bookmarks(&tx, id)?
+                .into_iter()
+                .flat_map(|b| [b.id, b.locator])
+                .collect();
+            for bookmark in &remote.bookmarks {
+                if local.contains(&bookmark.id) || local.contains(&bookmark.locator) {
+                    continue;
+                }
+                report.added_bookmarks += tx.execute(
+                    "INSERT OR IGNORE INTO bookmarks (id, document_id, locator, label, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        bookmark.id,
+                        id,
+                        bookmark.locator,
+                        bookmark.label,
+                        bookmark.created_at
+                    ],
+                )?;
+            }
+        }
+
+        let mine = local_annotations(annotations, &ids);
+        let mut taken = Vec::new();
+        for annotation in &remote.annotations {
+            let key = record_key(ANNOTATION_RECORD, &remote.hash, Some(&annotation.id));
+            let remote_version = remote
+                .annotation_versions
+                .get(&annotation.id)
+                .cloned()
+                .unwrap_or_default();
+            let local = mine.get(annotation.id.as_str()).copied();
+            match reconcile_record(
+                &tx,
+                &key,
+                &device.device_id,
+                local,
+                annotation,
+                &remote_version,
+                now,
+            )? {
+                Reconciled::TakeRemote => taken.push(annotation.clone()),
+                Reconciled::Conflict { local_version } => {
+                    let conflict = SyncConflict {
+                        local: serde_json::to_value(local)?,
+                        remote: serde_json::to_value(annotation)?,
+                        local_version,
+                        remote_version,
+                        ..conflict(ANNOTATION_RECORD, document_id, Some(&annotation.id))
+                    };
+                    save_conflict(&tx, &key, &conflict)?;
+                    report.conflicts += 1;
+                }
+                Reconciled::Unchanged => {}
+            }
+        }
+        if !taken.is_empty() {
+            report
+                .annotations
+                .extend(ids.iter().map(|id| SyncedAnnotations {
+                    document_id: id.clone(),
+                    annotations: taken.clone(),
+                }));
+        }
+    }
+    tx.commit()?;
+    Ok(report)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::conflicts::{list_conflicts, resolve_conflict};
+    use super::*;
+    use crate::commands::library::{
+        add_bookmark, upsert_document, FileFingerprint, LibraryBookmarkInput, LibraryDocumentInput,
+        LIBRARY_MIGRATIONS,
+    };
+    use crate::db;
+
+    fn add_document(conn: &Connection, path: &str, hash: &str, title: &str, at: i64) -> String {
+        let input = LibraryDocumentInput {
+            path: path.to_string(),
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        let fingerprint = FileFingerprint {
+            hash: hash.to_string(),
+            size: 1,
+            modified_at: None,
+        };
+        upsert_document(conn, &input, &fingerprint, at).unwrap().id
+    }
+
+    fn device(id: &str) -> SyncDevice {
+        SyncDevice {
+            device_id: id.to_string(),
+            device_name: id.to_uppercase(),
+        }
+    }
+
+    fn highlight(note: &str) -> AnnotationRecord {
+        AnnotationRecord {
+            id: "a1".to_string(),
+            kind: "highlight".to_string(),
+            page_number: Some(12),
+            content: Some("Blue whales".to_string()),
+            note: Some(note.to_string()),
+            color: None,
+            timestamp: 1,
+        }
+    }
+
+    fn annotated(document_id: &str, note: &str) -> Vec<AnnotatedDocument> {
+        vec![AnnotatedDocument {
+            document_id: Some(document_id.to_string()),
+            annotations: vec![highlight(note)],
+            ..Default::default()
+        }]
+    }
+
+    fn set_title(conn: &Connection, id: &str, title: &str) {
+        conn.execute(
+            "UPDATE documents SET title = ?2 WHERE id = ?1",
+            params![id, title],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn snapshots_merge_by_content_hash_and_version() {
+        let (laptop_device, phone_device) = (device("laptop"), device("phone"));
+        let laptop = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let phone = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let on_laptop = add_document(&laptop, "/home/a/whales.pdf", "h1", "Whales 2e", 20);
+        add_document(&laptop, "/home/a/only-here.pdf", "h2", "Solo", 20);
+        let on_phone = add_document(&phone, "/sdcard/whales.pdf", "h1", "Whales", 10);
+
+        save_progress(&laptop, &on_laptop, "42", 40.0, 30).unwrap();
+        save_progress(&phone, &on_phone, "7", 5.0, 25).unwrap();
+        let bookmark = |document_id: &str, locator: &str| LibraryBookmarkInput {
+            document_id: document_id.to_string(),
+            locator: locator.to_string(),
+            label: None,
+        };
+        add_bookmark(&laptop, &bookmark(&on_laptop, "12"), 1).unwrap();
+        add_bookmark(&laptop, &bookmark(&on_laptop, "3"), 1).unwrap();
+        add_bookmark(&phone, &bookmark(&on_phone, "3"), 1).unwrap();
+
+        // First sync: both titles are edits nobody has seen, so they conflict
+        let laptop_notes = annotated(&on_laptop, "big");
+        let snapshot = build_snapshot(&laptop, &laptop_device, &laptop_notes, 50).unwrap();
+        assert_eq!(snapshot.documents.len(), 2);
+        let report = merge_snapshot(&phone, &phone_device, &[], snapshot.clone(), 60).unwrap();
+        assert_eq!(report.peer, laptop_device);
+        assert_eq!(
+            (report.matched_documents, report.unmatched_documents),
+            (1, 1)
+        );
+        assert_eq!(
+            (
+                report.updated_metadata,
+                report.updated_progress,
+                report.added_bookmarks,
+                report.conflicts
+            ),
+            (0, 1, 1, 1)
+        );
+        assert_eq!(report.annotations[0].document_id, on_phone);
+        assert_eq!(
+            report.annotations[0].annotations[0].note.as_deref(),
+            Some("big")
+        );
+        assert_eq!(
+            get_progress(&phone, &on_phone).unwrap().unwrap().locator,
+            "42"
+        );
+        assert_eq!(list_bookmarks(&phone, &on_phone).unwrap().len(), 2);
+
+        let conflicts = list_conflicts(&phone, None).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, METADATA_RECORD);
+        assert_eq!(conflicts[0].local["title"], "Whales");
+        assert_eq!(conflicts[0].remote["title"], "Whales 2e");
+        resolve_conflict(&phone, &conflicts[0].id, "keep-theirs", "phone", 70).unwrap();
+        assert!(list_conflicts(&phone, None).unwrap().is_empty());
+
+        // Merging again changes nothing
+        let phone_notes = annotated(&on_phone, "big");
+        let again = merge_snapshot(&phone, &phone_device, &phone_notes, snapshot, 70).unwrap();
+        assert_eq!(
+            (
+                again.updated_metadata,
+                again.updated_progress,
+                again.added_bookmarks,
+                again.conflicts
+            ),
+            (0, 0, 0, 0)
+        );
+        assert!(again.annotations.is_empty());
+        let back = build_snapshot(&phone, &phone_device, &phone_notes, 75).unwrap();
+        let back = merge_snapshot(&laptop, &laptop_device, &laptop_notes, back, 75).unwrap();
+        assert_eq!((back.updated_metadata, back.conflicts), (0, 0));
+
+        // An edit on one device is taken by the other
+        set_title(&laptop, &on_laptop, "Whales 3e");
+        let snapshot = build_snapshot(&laptop, &laptop_device, &laptop_notes, 80).unwrap();
+        let report = merge_snapshot(&phone, &phone_device, &phone_notes, snapshot, 90).unwrap();
+        assert_eq!((report.updated_metadata, report.conflicts), (1, 0));
+
+        // Edits of the same annotation on both devices conflict
+        let laptop_notes = annotated(&on_laptop, "biggest animal");
+        let phone_notes = annotated(&on_phone, "largest mammal");
+        let snapshot = build_snapshot(&laptop, &laptop_device, &laptop_notes, 100).unwrap();
+        build_snapshot(&phone, &phone_device, &phone_notes, 100).unwrap();
+        let report = merge_snapshot(&phone, &phone_device, &phone_notes, snapshot, 110).unwrap();
+        assert_eq!(report.conflicts, 1);
+        assert!(report.annotations.is_empty());
+        let conflict = &list_conflicts(&phone, Some(&on_phone)).unwrap()[0];
+        assert_eq!(conflict.record_id.as_deref(), Some("a1"));
+        let merged = resolve_conflict(&phone, &conflict.id, "merge", "phone", 120).unwrap();
+        assert_eq!(merged.value["note"], "largest mammal\n\nbiggest animal");
+    }
+}
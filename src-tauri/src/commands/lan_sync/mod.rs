@@ -0,0 +1,23 @@
+//! Peer-to-peer sync between devices on the same network
+//!
+//! Two Readium instances exchange library data directly, without a server:
+//! - Optional mDNS announcement and discovery of listening devices
+//! - Pairing code and key agreement for an encrypted transfer channel
+//! - Snapshots of library metadata, reading progress and bookmarks
+//! - Annotations passed through to the frontend store that owns them
+//! - Version vectors per record, and conflicts for the user to resolve
+
+mod types;
+mod mdns;
+mod channel;
+mod conflicts;
+mod merge;
+mod session;
+
+// Re-export all public items
+pub use types::*;
+pub use mdns::*;
+pub use channel::*;
+pub use conflicts::*;
+pub use merge::*;
+pub use session::*;
@@ -0,0 +1,422 @@
+//! Sync listener, device identity and LAN sync commands
+
+use super::channel::{generate_pairing_code, SecureChannel};
+use super::mdns::{bind_responder, discover_peers, respond};
+use super::merge::{build_snapshot, merge_snapshot};
+use super::types::{LanPeer, LanSyncReport, LanSyncSession, SyncDevice, SyncSnapshot};
+use crate::commands::annotations::AnnotatedDocument;
+use crate::commands::events::emit_event;
+use crate::commands::library::{lock_library, LibraryState};
+use crate::commands::retry::with_retry;
+use crate::error::AppError;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::Manager;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Device identity file name in the app data directory
+pub const SYNC_DEVICE_FILE: &str = "sync_device.json";
+
+/// Event emitted when a peer has synced with the listener of this device
+pub const LAN_SYNC_EVENT: &str = "lan-sync-completed";
+
+/// Failed pairings after which the listener stops
+const MAX_FAILED_PAIRINGS: u32 = 5;
+/// Time a connecting peer has to pair, before it may send a snapshot
+const PAIRING_TIMEOUT: Duration = Duration::from_secs(10);
+const EXCHANGE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Peers synced with at the same time; further connections are dropped
+const MAX_CONCURRENT_PEERS: usize = 4;
+const DEFAULT_DISCOVERY_TIMEOUT_MS: u64 = 3000;
+
+/// The running sync listener
+pub struct LanSyncHost {
+    session: LanSyncSession,
+    /// Frontend annotations sent to peers that connect
+    annotations: Arc<Mutex<Vec<AnnotatedDocument>>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for LanSyncHost {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Managed state holding the sync listener
+pub type LanSyncState = Arc<Mutex<Option<LanSyncHost>>>;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Get the device identity file path
+pub fn get_sync_device_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    fs::create_dir_all(&data_dir)?;
+    Ok(data_dir.join(SYNC_DEVICE_FILE))
+}
+
+/// The host name of the system, which shells do not always export
+fn default_device_name() -> String {
+    sysinfo::System::host_name()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Readium".to_string())
+}
+
+fn save_device(path: &Path, device: &SyncDevice) -> Result<(), AppError> {
+    fs::write(path, serde_json::to_string_pretty(device)?)?;
+    Ok(())
+}
+
+/// Load the identity of this device, creating it on first use
+pub fn load_or_create_device(path: &Path) -> Result<SyncDevice, AppError> {
+    if path.exists() {
+        return Ok(serde_json::from_str(&fs::read_to_string(path)?)?);
+    }
+    let device = SyncDevice {
+        device_id: uuid::Uuid::new_v4().to_string(),
+        device_name: default_device_name(),
+    };
+    save_device(path, &device)?;
+    Ok(device)
+}
+
+/// Run a closure on the library database off the async runtime
+async fn with_library<T: Send + 'static>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&rusqlite::Connection) -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+    let library = app.state::<LibraryState>().inner().clone();
+    tauri::async_runtime::spawn_blocking(move || f(&*lock_library(&library)?))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Exchange snapshots over a paired channel and merge the peer's one
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    app: &tauri::AppHandle,
+    channel: &mut SecureChannel<S>,
+    device: SyncDevice,
+    annotations: Vec<AnnotatedDocument>,
+    initiator: bool,
+) -> Result<LanSyncReport, AppError> {
+    let now = chrono::Utc::now().timestamp();
+    let (device, annotations, snapshot) = with_library(app, move |conn| {
+        let snapshot = build_snapshot(conn, &device, &annotations, now)?;
+        Ok((device, annotations, snapshot))
+    })
+    .await?;
+
+    // The connecting device speaks first
+    let remote: SyncSnapshot = if initiator {
+        channel.send_json(&snapshot).await?;
+        channel.recv_json().await?
+    } else {
+        let remote = channel.recv_json().await?;
+        channel.send_json(&snapshot).await?;
+        remote
+    };
+    if remote.device.device_id != channel.peer.device_id {
+        return Err(AppError::InvalidInput(
+            "Peer sent a snapshot of another device".to_string(),
+        ));
+    }
+    with_library(app, move |conn| {
+        merge_snapshot(conn, &device, &annotations, remote, now)
+    })
+    .await
+}
+
+async fn with_timeout<T>(
+    future: impl std::future::Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    within(EXCHANGE_TIMEOUT, future).await
+}
+
+async fn within<T>(
+    timeout: Duration,
+    future: impl std::future::Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| AppError::Internal("LAN sync timed out".to_string()))?
+}
+
+/// Sync with one connecting peer
+async fn serve_peer(
+    app: tauri::AppHandle,
+    stream: TcpStream,
+    device: SyncDevice,
+    pairing_code: Arc<str>,
+    annotations: Arc<Mutex<Vec<AnnotatedDocument>>>,
+) -> Result<LanSyncReport, AppError> {
+    let mut channel = within(
+        PAIRING_TIMEOUT,
+        SecureChannel::handshake(stream, &device, &pairing_code, false),
+    )
+    .await?;
+    let annotations = annotations
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .clone();
+    with_timeout(exchange(&app, &mut channel, device, annotations, false)).await
+}
+
+/// Accept peers, each in its own task, until stopped or too many pairings
+/// fail
+async fn accept_peers(
+    app: tauri::AppHandle,
+    listener: TcpListener,
+    device: SyncDevice,
+    pairing_code: String,
+    annotations: Arc<Mutex<Vec<AnnotatedDocument>>>,
+) {
+    let pairing_code: Arc<str> = pairing_code.into();
+    // Dropping the set when the listener stops aborts the peers in progress
+    let mut peers = tokio::task::JoinSet::new();
+    let mut failed_pairings = 0;
+    loop {
+        let finished = tokio::select! {
+            accepted = listener.accept() => {
+                match accepted {
+                    Ok((_, address)) if peers.len() >= MAX_CONCURRENT_PEERS => {
+                        log::warn!("LAN sync busy, dropping the connection from {}", address);
+                    }
+                    Ok((stream, address)) => {
+                        let peer = serve_peer(
+                            app.clone(),
+                            stream,
+                            device.clone(),
+                            pairing_code.clone(),
+                            annotations.clone(),
+                        );
+                        peers.spawn(async move { (address, peer.await) });
+                    }
+                    Err(e) => {
+                        log::warn!("LAN sync listener failed: {}", e);
+                        break;
+                    }
+                }
+                continue;
+            }
+            Some(finished) = peers.join_next() => finished,
+        };
+        let Ok((address, result)) = finished else {
+            continue;
+        };
+        match result {
+            Ok(report) => {
+                log::info!(
+                    "LAN sync with {} ({}): {} documents matched",
+                    report.peer.device_name,
+                    address,
+                    report.matched_documents
+                );
+                emit_event(&app, &report);
+            }
+            Err(AppError::InvalidInput(message)) if message.contains("pairing code") => {
+                failed_pairings += 1;
+                log::warn!("LAN sync pairing from {} failed", address);
+                if failed_pairings >= MAX_FAILED_PAIRINGS {
+                    log::warn!("Too many failed pairings, stopping the LAN sync listener");
+                    break;
+                }
+            }
+            Err(e) => log::warn!("LAN sync with {} failed: {}", address, e),
+        }
+    }
+
+    // Dropping the host aborts this task and the mDNS responder
+    let state = app.state::<LanSyncState>().inner().clone();
+    let stopped = state.lock().map(|mut host| host.take());
+    drop(stopped);
+}
+
+fn stop_host(state: &LanSyncState) -> Result<bool, AppError> {
+    let previous = state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .take();
+    Ok(previous.is_some())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the identity this device syncs as
+#[tauri::command]
+pub fn lan_sync_get_device(app: tauri::AppHandle) -> Result<SyncDevice, AppError> {
+    load_or_create_device(&get_sync_device_path(&app)?)
+}
+
+/// Rename this device as shown to peers
+#[tauri::command]
+pub fn lan_sync_set_device_name(
+    app: tauri::AppHandle,
+    name: String,
+) -> Result<SyncDevice, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Device name cannot be empty".to_string(),
+        ));
+    }
+    let path = get_sync_device_path(&app)?;
+    let mut device = load_or_create_device(&path)?;
+    device.device_name = name.chars().take(64).collect();
+    save_device(&path, &device)?;
+    Ok(device)
+}
+
+/// Start listening for peers, replacing a running listener
+#[tauri::command]
+pub async fn lan_sync_start(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, LanSyncState>,
+    discoverable: Option<bool>,
+    annotations: Option<Vec<AnnotatedDocument>>,
+) -> Result<LanSyncSession, AppError> {
+    stop_host(&state)?;
+    let device = load_or_create_device(&get_sync_device_path(&app)?)?;
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    let port = listener.local_addr()?.port();
+    let pairing_code = generate_pairing_code()?;
+    let annotations = Arc::new(Mutex::new(annotations.unwrap_or_default()));
+
+    let mut tasks = Vec::new();
+    let responder = match discoverable.unwrap_or(true) {
+        true => bind_responder()
+            .map_err(|e| log::warn!("LAN sync will not be discoverable: {}", e))
+            .ok(),
+        false => None,
+    };
+    let discoverable = responder.is_some();
+    if let Some(socket) = responder {
+        tasks.push(tauri::async_runtime::spawn(respond(
+            socket,
+            device.clone(),
+            port,
+        )));
+    }
+    tasks.push(tauri::async_runtime::spawn(accept_peers(
+        app.clone(),
+        listener,
+        device,
+        pairing_code.clone(),
+        annotations.clone(),
+    )));
+
+    let session = LanSyncSession {
+        port,
+        pairing_code,
+        discoverable,
+    };
+    *state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))? = Some(LanSyncHost {
+        session: session.clone(),
+        annotations,
+        tasks,
+    });
+    log::info!("LAN sync listening on port {}", port);
+    Ok(session)
+}
+
+/// Replace the annotations sent to peers by the running listener
+#[tauri::command]
+pub fn lan_sync_update_annotations(
+    state: tauri::State<'_, LanSyncState>,
+    annotations: Vec<AnnotatedDocument>,
+) -> Result<(), AppError> {
+    let host = state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let host = host
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("LAN sync is not running".to_string()))?;
+    *host
+        .annotations
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))? = annotations;
+    Ok(())
+}
+
+/// Stop listening for peers; returns whether a listener was running
+#[tauri::command]
+pub fn lan_sync_stop(state: tauri::State<'_, LanSyncState>) -> Result<bool, AppError> {
+    stop_host(&state)
+}
+
+/// Get the running listener, if any
+#[tauri::command]
+pub fn lan_sync_status(
+    state: tauri::State<'_, LanSyncState>,
+) -> Result<Option<LanSyncSession>, AppError> {
+    let host = state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(host.as_ref().map(|host| host.session.clone()))
+}
+
+/// Look for discoverable devices on the local network
+#[tauri::command]
+pub async fn lan_sync_discover(
+    app: tauri::AppHandle,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<LanPeer>, AppError> {
+    let device = load_or_create_device(&get_sync_device_path(&app)?)?;
+    let timeout = timeout_ms
+        .unwrap_or(DEFAULT_DISCOVERY_TIMEOUT_MS)
+        .clamp(500, 15_000);
+    discover_peers(&device.device_id, Duration::from_millis(timeout)).await
+}
+
+/// Pair with a listening device and sync libraries with it
+#[tauri::command]
+pub async fn lan_sync_connect(
+    app: tauri::AppHandle,
+    address: String,
+    port: u16,
+    pairing_code: String,
+    annotations: Option<Vec<AnnotatedDocument>>,
+) -> Result<LanSyncReport, AppError> {
+    let device = load_or_create_device(&get_sync_device_path(&app)?)?;
+    let stream = with_timeout(with_retry("Connecting to the peer", || async {
+        Ok(TcpStream::connect((address.as_str(), port)).await?)
+    }))
+    .await?;
+    let mut channel = with_timeout(SecureChannel::handshake(
+        stream,
+        &device,
+        &pairing_code,
+        true,
+    ))
+    .await?;
+    let report = with_timeout(exchange(
+        &app,
+        &mut channel,
+        device,
+        annotations.unwrap_or_default(),
+        true,
+    ))
+    .await?;
+    log::info!(
+        "LAN sync with {}: {} documents matched",
+        report.peer.device_name,
+        report.matched_documents
+    );
+    Ok(report)
+}
@@ -0,0 +1,171 @@
+//! LAN sync data structures
+
+use crate::commands::annotations::AnnotationRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Time of the last edit of a synced record, per device id
+pub type VersionVector = BTreeMap<String, i64>;
+
+// ============================================================================
+// Device Types
+// ============================================================================
+
+/// This device as seen by peers
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncDevice {
+    pub device_id: String,
+    pub device_name: String,
+}
+
+/// A device sharing its library on the local network
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LanPeer {
+    pub device_id: String,
+    pub device_name: String,
+    pub address: String,
+    pub port: u16,
+}
+
+/// The running sync listener of this device
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LanSyncSession {
+    pub port: u16,
+    /// Code the other device has to enter to connect
+    pub pairing_code: String,
+    /// Whether the listener is announced over mDNS
+    pub discoverable: bool,
+}
+
+// ============================================================================
+// Snapshot Types
+// ============================================================================
+
+/// Library data sent to a peer
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSnapshot {
+    pub device: SyncDevice,
+    pub generated_at: i64,
+    pub documents: Vec<SyncDocument>,
+}
+
+/// A document, identified across devices by its content hash
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncDocument {
+    pub hash: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub metadata: serde_json::Value,
+    pub updated_at: i64,
+    /// Edits of the title, author and metadata
+    #[serde(default)]
+    pub metadata_version: VersionVector,
+    pub progress: Option<SyncProgress>,
+    pub bookmarks: Vec<SyncBookmark>,
+    /// Annotations from the frontend store
+    #[serde(default)]
+    pub annotations: Vec<AnnotationRecord>,
+    /// Edits of each annotation, by annotation id
+    #[serde(default)]
+    pub annotation_versions: BTreeMap<String, VersionVector>,
+}
+
+/// Title, author and metadata of a document, as compared between devices
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub metadata: serde_json::Value,
+}
+
+/// Reading position of a synced document
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+    pub locator: String,
+    pub percent: f64,
+    pub last_read_at: i64,
+}
+
+/// Bookmark of a synced document
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncBookmark {
+    pub id: String,
+    pub locator: String,
+    pub label: Option<String>,
+    pub created_at: i64,
+}
+
+// ============================================================================
+// Result Types
+// ============================================================================
+
+/// Annotations a peer has for a local document
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncedAnnotations {
+    pub document_id: String,
+    pub annotations: Vec<AnnotationRecord>,
+}
+
+/// Result of a sync with a peer
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LanSyncReport {
+    pub peer: SyncDevice,
+    /// Peer documents also in the local library
+    pub matched_documents: usize,
+    /// Peer documents without a local copy, not synced
+    pub unmatched_documents: usize,
+    pub updated_metadata: usize,
+    pub updated_progress: usize,
+    pub added_bookmarks: usize,
+    /// Peer annotations for the frontend to merge into its store
+    pub annotations: Vec<SyncedAnnotations>,
+    /// Records edited on both devices, see `lan_sync_list_conflicts`
+    pub conflicts: usize,
+    pub synced_at: i64,
+}
+
+/// A record edited on two devices without a sync in between
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConflict {
+    pub id: String,
+    /// "metadata" | "annotation"
+    pub kind: String,
+    pub document_id: String,
+    /// Annotation id, for annotation conflicts
+    pub record_id: Option<String>,
+    pub peer_device_id: String,
+    pub peer_device_name: String,
+    /// `SyncMetadata` or annotation on this device
+    pub local: serde_json::Value,
+    /// `SyncMetadata` or annotation on the peer
+    pub remote: serde_json::Value,
+    pub local_version: VersionVector,
+    pub remote_version: VersionVector,
+    pub detected_at: i64,
+}
+
+/// The value a conflict was resolved to
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedConflict {
+    pub conflict_id: String,
+    pub kind: String,
+    pub document_id: String,
+    pub record_id: Option<String>,
+    /// "keep-mine" | "keep-theirs" | "merge"
+    pub resolution: String,
+    /// Applied to the library for metadata; annotations are for the
+    /// frontend to store
+    pub value: serde_json::Value,
+}
@@ -0,0 +1,247 @@
+//! Library bookmark commands
+
+use super::documents::get_document;
+use super::storage::lock_library;
+use super::types::{DocumentBookmarks, LibraryBookmark, LibraryBookmarkInput, LibraryState};
+use crate::error::AppError;
+use rusqlite::{params, Connection, Row};
+use uuid::Uuid;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn row_to_bookmark(row: &Row) -> rusqlite::Result<LibraryBookmark> {
+    Ok(LibraryBookmark {
+        id: row.get(0)?,
+        document_id: row.get(1)?,
+        locator: row.get(2)?,
+        label: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+/// Add a bookmark to a document
+pub fn add_bookmark(
+    conn: &Connection,
+    input: &LibraryBookmarkInput,
+    now: i64,
+) -> Result<LibraryBookmark, AppError> {
+    if get_document(conn, &input.document_id)?.is_none() {
+        return Err(AppError::NotFound(format!(
+            "Document '{}' not found",
+            input.document_id
+        )));
+    }
+    if input.locator.trim().is_empty() {
+        return Err(AppError::InvalidInput(
+            "Bookmark locator must not be empty".to_string(),
+        ));
+    }
+
+    let bookmark = LibraryBookmark {
+        id: format!("bm_{}", Uuid::new_v4()),
+        document_id: input.document_id.clone(),
+        locator: input.locator.clone(),
+        label: input.label.clone().filter(|label| !label.trim().is_empty()),
+        created_at: now,
+    };
+    conn.execute(
+        "INSERT INTO bookmarks (id, document_id, locator, label, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            bookmark.id,
+            bookmark.document_id,
+            bookmark.locator,
+            bookmark.label,
+            bookmark.created_at
+        ],
+    )?;
+    Ok(bookmark)
+}
+
+/// List the bookmarks of a document, oldest first
+pub fn list_bookmarks(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Vec<LibraryBookmark>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, document_id, locator, label, created_at FROM bookmarks
+         WHERE document_id = ?1 ORDER BY created_at, rowid",
+    )?;
+    let bookmarks = stmt
+        .query_map(params![document_id], row_to_bookmark)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(bookmarks)
+}
+
+/// Remove a bookmark
+pub fn remove_bookmark(conn: &Connection, id: &str) -> Result<(), AppError> {
+    let changed = conn.execute("DELETE FROM bookmarks WHERE id = ?1", params![id])?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("Bookmark '{}' not found", id)));
+    }
+    Ok(())
+}
+
+/// Collect bookmarks grouped by document, for one document or the whole library
+pub fn export_bookmarks(
+    conn: &Connection,
+    document_id: Option<&str>,
+) -> Result<Vec<DocumentBookmarks>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT d.id, d.title, d.author, d.path FROM documents d
+         JOIN bookmarks b ON b.document_id = d.id
+         WHERE ?1 IS NULL OR d.id = ?1
+         ORDER BY d.title COLLATE NOCASE, d.path",
+    )?;
+    let documents = stmt
+        .query_map(params![document_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<rusqlite::Result<Vec<(String, Option<String>, Option<String>, String)>>>()?;
+
+    documents
+        .into_iter()
+        .map(|(document_id, title, author, path)| {
+            let bookmarks = list_bookmarks(conn, &document_id)?;
+            Ok(DocumentBookmarks {
+                document_id,
+                title,
+                author,
+                path,
+                bookmarks,
+            })
+        })
+        .collect()
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Add a bookmark to a library document
+#[tauri::command]
+pub fn library_add_bookmark(
+    state: tauri::State<'_, LibraryState>,
+    bookmark: LibraryBookmarkInput,
+) -> Result<LibraryBookmark, AppError> {
+    let conn = lock_library(&state)?;
+    add_bookmark(&conn, &bookmark, chrono::Utc::now().timestamp())
+}
+
+/// List the bookmarks of a library document
+#[tauri::command]
+pub fn library_list_bookmarks(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+) -> Result<Vec<LibraryBookmark>, AppError> {
+    let conn = lock_library(&state)?;
+    list_bookmarks(&conn, &document_id)
+}
+
+/// Remove a bookmark
+#[tauri::command]
+pub fn library_remove_bookmark(
+    state: tauri::State<'_, LibraryState>,
+    id: String,
+) -> Result<(), AppError> {
+    let conn = lock_library(&state)?;
+    remove_bookmark(&conn, &id)
+}
+
+/// Export bookmarks grouped by document, to be saved alongside annotations
+#[tauri::command]
+pub fn library_export_bookmarks(
+    state: tauri::State<'_, LibraryState>,
+    document_id: Option<String>,
+) -> Result<Vec<DocumentBookmarks>, AppError> {
+    let conn = lock_library(&state)?;
+    export_bookmarks(&conn, document_id.as_deref())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::documents::{remove_document, upsert_document, FileFingerprint};
+    use super::super::storage::LIBRARY_MIGRATIONS;
+    use super::super::types::LibraryDocumentInput;
+    use super::*;
+    use crate::db;
+
+    fn add_document(conn: &Connection, path: &str, title: &str) -> String {
+        let input = LibraryDocumentInput {
+            path: path.to_string(),
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        let fingerprint = FileFingerprint {
+            hash: path.to_string(),
+            size: 1,
+            modified_at: None,
+        };
+        upsert_document(conn, &input, &fingerprint, 1).unwrap().id
+    }
+
+    fn bookmark(document_id: &str, locator: &str) -> LibraryBookmarkInput {
+        LibraryBookmarkInput {
+            document_id: document_id.to_string(),
+            locator: locator.to_string(),
+            label: None,
+        }
+    }
+
+    #[test]
+    fn bookmarks_are_listed_removed_and_cascade_with_documents() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let doc = add_document(&conn, "/books/a.pdf", "A");
+
+        let first = add_bookmark(&conn, &bookmark(&doc, "page=3"), 10).unwrap();
+        add_bookmark(&conn, &bookmark(&doc, "page=9"), 20).unwrap();
+        assert!(add_bookmark(&conn, &bookmark("doc_missing", "page=1"), 30).is_err());
+        assert!(add_bookmark(&conn, &bookmark(&doc, " "), 30).is_err());
+
+        let listed = list_bookmarks(&conn, &doc).unwrap();
+        assert_eq!(
+            listed
+                .iter()
+                .map(|b| b.locator.as_str())
+                .collect::<Vec<_>>(),
+            vec!["page=3", "page=9"]
+        );
+
+        remove_bookmark(&conn, &first.id).unwrap();
+        assert!(remove_bookmark(&conn, &first.id).is_err());
+        assert_eq!(list_bookmarks(&conn, &doc).unwrap().len(), 1);
+
+        remove_document(&conn, &doc).unwrap();
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM bookmarks", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn export_bookmarks_groups_by_document() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let a = add_document(&conn, "/books/a.pdf", "Alpha");
+        let b = add_document(&conn, "/books/b.epub", "Beta");
+        add_document(&conn, "/books/c.pdf", "Gamma");
+        add_bookmark(&conn, &bookmark(&b, "epubcfi(/6/4)"), 1).unwrap();
+        add_bookmark(&conn, &bookmark(&a, "page=1"), 2).unwrap();
+        add_bookmark(&conn, &bookmark(&a, "page=2"), 3).unwrap();
+
+        let all = export_bookmarks(&conn, None).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].title.as_deref(), Some("Alpha"));
+        assert_eq!(all[0].bookmarks.len(), 2);
+        assert_eq!(all[1].document_id, b);
+
+        let single = export_bookmarks(&conn, Some(&b)).unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].bookmarks[0].locator, "epubcfi(/6/4)");
+    }
+}
@@ -0,0 +1,412 @@
+//! Library collections and tags
+//!
+//! Collections and tags share one table and differ only by `kind`, so a
+//! document can belong to any number of either.
+
+use super::documents::get_document;
+use super::storage::lock_library;
+use super::types::{LibraryCollection, LibraryCollectionInput, LibraryState};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use uuid::Uuid;
+
+const COLLECTION_COLUMNS: &str = "c.id, c.name, c.kind, c.description, \
+     (SELECT COUNT(*) FROM collection_documents cd WHERE cd.collection_id = c.id), \
+     c.created_at, c.updated_at";
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn row_to_collection(row: &Row) -> rusqlite::Result<LibraryCollection> {
+    Ok(LibraryCollection {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        kind: row.get(2)?,
+        description: row.get(3)?,
+        document_count: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+/// Validate a collection kind, defaulting to "collection"
+fn normalize_kind(kind: Option<&str>) -> Result<&'static str, AppError> {
+    match kind {
+        None | Some("collection") => Ok("collection"),
+        Some("tag") => Ok("tag"),
+        Some(other) => Err(AppError::InvalidInput(format!(
+            "Unknown collection kind: {}",
+            other
+        ))),
+    }
+}
+
+/// Validate and trim a collection name
+fn normalize_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Collection name must not be empty".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// Find a collection of the given kind by name (case-insensitive)
+fn find_collection_id(
+    conn: &Connection,
+    kind: &str,
+    name: &str,
+) -> Result<Option<String>, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT id FROM collections WHERE kind = ?1 AND name = ?2 COLLATE NOCASE",
+            params![kind, name],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Get a collection by id
+pub fn get_collection(conn: &Connection, id: &str) -> Result<Option<LibraryCollection>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM collections c WHERE c.id = ?1",
+        COLLECTION_COLUMNS
+    );
+    Ok(conn
+        .query_row(&sql, params![id], row_to_collection)
+        .optional()?)
+}
+
+/// Create a collection or tag
+pub fn create_collection(
+    conn: &Connection,
+    input: &LibraryCollectionInput,
+    now: i64,
+) -> Result<LibraryCollection, AppError> {
+    let kind = normalize_kind(input.kind.as_deref())?;
+    let name = normalize_name(&input.name)?;
+    if find_collection_id(conn, kind, &name)?.is_some() {
+        return Err(AppError::InvalidInput(format!(
+            "A {} named '{}' already exists",
+            kind, name
+        )));
+    }
+
+    let id = format!("col_{}", Uuid::new_v4());
+    conn.execute(
+        "INSERT INTO collections (id, name, kind, description, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+        params![id, name, kind, input.description, now],
+    )?;
+    get_collection(conn, &id)?
+        .ok_or_else(|| AppError::NotFound(format!("Collection '{}' not found", id)))
+}
+
+/// List collections, optionally only of one kind, ordered by name
+pub fn list_collections(
+    conn: &Connection,
+    kind: Option<&str>,
+) -> Result<Vec<LibraryCollection>, AppError> {
+    let kind = kind.map(|kind| normalize_kind(Some(kind))).transpose()?;
+    let sql = format!(
+        "SELECT {} FROM collections c WHERE ?1 IS NULL OR c.kind = ?1
+         ORDER BY c.kind, c.name COLLATE NOCASE",
+        COLLECTION_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let collections = stmt
+        .query_map(params![kind], row_to_collection)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(collections)
+}
+
+/// Rename a collection or change its description
+pub fn update_collection(
+    conn: &Connection,
+    id: &str,
+    input: &LibraryCollectionInput,
+    now: i64,
+) -> Result<LibraryCollection, AppError> {
+    let existing = get_collection(conn, id)?
+        .ok_or_else(|| AppError::NotFound(format!("Collection '{}' not found", id)))?;
+    let name = normalize_name(&input.name)?;
+    if let Some(other) = find_collection_id(conn, &existing.kind, &name)? {
+        if other != id {
+            return Err(AppError::InvalidInput(format!(
+                "A {} named '{}' already exists",
+                existing.kind, name
+            )));
+        }
+    }
+
+    conn.execute(
+        "UPDATE collections SET name = ?2, description = ?3, updated_at = ?4 WHERE id = ?1",
+        params![id, name, input.description, now],
+    )?;
+    get_collection(conn, id)?
+        .ok_or_else(|| AppError::NotFound(format!("Collection '{}' not found", id)))
+}
+
+/// Delete a collection (its documents stay in the library)
+pub fn delete_collection(conn: &Connection, id: &str) -> Result<(), AppError> {
+    let changed = conn.execute("DELETE FROM collections WHERE id = ?1", params![id])?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("Collection '{}' not found", id)));
+    }
+    Ok(())
+}
+
+/// Add documents to a collection, returning how many were newly added
+pub fn add_to_collection(
+    conn: &Connection,
+    collection_id: &str,
+    document_ids: &[String],
+    now: i64,
+) -> Result<usize, AppError> {
+    if get_collection(conn, collection_id)?.is_none() {
+        return Err(AppError::NotFound(format!(
+            "Collection '{}' not found",
+            collection_id
+        )));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let mut added = 0;
+    for document_id in document_ids {
+        if get_document(&tx, document_id)?.is_none() {
+            return Err(AppError::NotFound(format!(
+                "Document '{}' not found",
+                document_id
+            )));
+        }
+        added += tx.execute(
+            "INSERT OR IGNORE INTO collection_documents (collection_id, document_id, added_at)
+             VALUES (?1, ?2, ?3)",
+            params![collection_id, document_id, now],
+        )?;
+    }
+    tx.execute(
+        "UPDATE collections SET updated_at = ?2 WHERE id = ?1",
+        params![collection_id, now],
+    )?;
+    tx.commit()?;
+    Ok(added)
+}
+
+/// Remove documents from a collection, returning how many were removed
+pub fn remove_from_collection(
+    conn: &Connection,
+    collection_id: &str,
+    document_ids: &[String],
+) -> Result<usize, AppError> {
+    let tx = conn.unchecked_transaction()?;
+    let mut removed = 0;
+    for document_id in document_ids {
+        removed += tx.execute(
+            "DELETE FROM collection_documents WHERE collection_id = ?1 AND document_id = ?2",
+            params![collection_id, document_id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(removed)
+}
+
+/// List the collections and tags a document belongs to
+pub fn document_collections(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Vec<LibraryCollection>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM collections c
+         JOIN collection_documents m ON m.collection_id = c.id
+         WHERE m.document_id = ?1
+         ORDER BY c.kind, c.name COLLATE NOCASE",
+        COLLECTION_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let collections = stmt
+        .query_map(params![document_id], row_to_collection)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(collections)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Create a collection or tag
+#[tauri::command]
+pub fn library_create_collection(
+    state: tauri::State<'_, LibraryState>,
+    collection: LibraryCollectionInput,
+) -> Result<LibraryCollection, AppError> {
+    let conn = lock_library(&state)?;
+    create_collection(&conn, &collection, chrono::Utc::now().timestamp())
+}
+
+/// List collections and tags (optionally filtered by kind)
+#[tauri::command]
+pub fn library_list_collections(
+    state: tauri::State<'_, LibraryState>,
+    kind: Option<String>,
+) -> Result<Vec<LibraryCollection>, AppError> {
+    let conn = lock_library(&state)?;
+    list_collections(&conn, kind.as_deref())
+}
+
+/// Rename a collection or update its description
+#[tauri::command]
+pub fn library_update_collection(
+    state: tauri::State<'_, LibraryState>,
+    id: String,
+    collection: LibraryCollectionInput,
+) -> Result<LibraryCollection, AppError> {
+    let conn = lock_library(&state)?;
+    update_collection(&conn, &id, &collection, chrono::Utc::now().timestamp())
+}
+
+/// Delete a collection or tag
+#[tauri::command]
+pub fn library_delete_collection(
+    state: tauri::State<'_, LibraryState>,
+    id: String,
+) -> Result<(), AppError> {
+    let conn = lock_library(&state)?;
+    delete_collection(&conn, &id)
+}
+
+/// Add documents to a collection or tag
+#[tauri::command]
+pub fn library_add_to_collection(
+    state: tauri::State<'_, LibraryState>,
+    collection_id: String,
+    document_ids: Vec<String>,
+) -> Result<usize, AppError> {
+    let conn = lock_library(&state)?;
+    add_to_collection(
+        &conn,
+        &collection_id,
+        &document_ids,
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+/// Remove documents from a collection or tag
+#[tauri::command]
+pub fn library_remove_from_collection(
+    state: tauri::State<'_, LibraryState>,
+    collection_id: String,
+    document_ids: Vec<String>,
+) -> Result<usize, AppError> {
+    let conn = lock_library(&state)?;
+    remove_from_collection(&conn, &collection_id, &document_ids)
+}
+
+/// Get the collections and tags of a document
+#[tauri::command]
+pub fn library_get_document_collections(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+) -> Result<Vec<LibraryCollection>, AppError> {
+    let conn = lock_library(&state)?;
+    document_collections(&conn, &document_id)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::documents::{list_documents, upsert_document, FileFingerprint};
+    use super::super::storage::LIBRARY_MIGRATIONS;
+    use super::super::types::{LibraryDocumentInput, LibraryListQuery};
+    use super::*;
+    use crate::db;
+
+    fn add_document(conn: &Connection, path: &str) -> String {
+        let input = LibraryDocumentInput {
+            path: path.to_string(),
+            ..Default::default()
+        };
+        let fingerprint = FileFingerprint {
+            hash: path.to_string(),
+            size: 1,
+            modified_at: None,
+        };
+        upsert_document(conn, &input, &fingerprint, 1).unwrap().id
+    }
+
+    fn input(name: &str, kind: Option<&str>) -> LibraryCollectionInput {
+        LibraryCollectionInput {
+            name: name.to_string(),
+            kind: kind.map(|k| k.to_string()),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn create_collection_validates_kind_and_unique_names() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+
+        let shelf = create_collection(&conn, &input(" To Read ", None), 1).unwrap();
+        assert_eq!(shelf.name, "To Read");
+        assert_eq!(shelf.kind, "collection");
+
+        // Same name is allowed for a different kind, not for the same kind
+        create_collection(&conn, &input("to read", Some("tag")), 1).unwrap();
+        assert!(create_collection(&conn, &input("TO READ", None), 1).is_err());
+        assert!(create_collection(&conn, &input("x", Some("shelf")), 1).is_err());
+        assert!(create_collection(&conn, &input("  ", None), 1).is_err());
+
+        assert_eq!(list_collections(&conn, None).unwrap().len(), 2);
+        assert_eq!(list_collections(&conn, Some("tag")).unwrap().len(), 1);
+
+        let renamed = update_collection(&conn, &shelf.id, &input("Favourites", None), 2).unwrap();
+        assert_eq!(renamed.name, "Favourites");
+    }
+
+    #[test]
+    fn documents_can_be_grouped_and_listed_by_collection() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let a = add_document(&conn, "/books/a.pdf");
+        let b = add_document(&conn, "/books/b.epub");
+        let shelf = create_collection(&conn, &input("Shelf", None), 1).unwrap();
+        let tag = create_collection(&conn, &input("physics", Some("tag")), 1).unwrap();
+
+        let ids = vec![a.clone(), b.clone()];
+        assert_eq!(add_to_collection(&conn, &shelf.id, &ids, 2).unwrap(), 2);
+        assert_eq!(add_to_collection(&conn, &shelf.id, &ids, 3).unwrap(), 0);
+        add_to_collection(&conn, &tag.id, &ids[..1], 3).unwrap();
+        assert!(add_to_collection(&conn, &tag.id, &["doc_missing".to_string()], 3).is_err());
+
+        let in_tag = list_documents(
+            &conn,
+            &LibraryListQuery {
+                collection_id: Some(tag.id.clone()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(in_tag.len(), 1);
+        assert_eq!(in_tag[0].id, a);
+
+        assert_eq!(document_collections(&conn, &a).unwrap().len(), 2);
+        assert_eq!(
+            get_collection(&conn, &shelf.id)
+                .unwrap()
+                .unwrap()
+                .document_count,
+            2
+        );
+
+        assert_eq!(
+            remove_from_collection(&conn, &shelf.id, &ids[1..]).unwrap(),
+            1
+        );
+        delete_collection(&conn, &tag.id).unwrap();
+        assert_eq!(document_collections(&conn, &a).unwrap().len(), 1);
+    }
+}
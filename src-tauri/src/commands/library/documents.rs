@@ -0,0 +1,536 @@
+//! Library document CRUD and query commands
+
+use super::storage::lock_library;
+use super::types::{
+    LibraryDocument, LibraryDocumentInput, LibraryDocumentUpdate, LibraryListQuery, LibraryState,
+};
+use crate::commands::pdf::load_pdf_metadata;
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Columns selected for every document query, in `row_to_document` order
+const DOCUMENT_COLUMNS: &str = "id, path, file_name, hash, format, file_size, title, author, \
+                                metadata, added_at, opened_at, updated_at, file_modified_at";
+
+/// Content fingerprint used to detect file changes
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileFingerprint {
+    /// SHA-256 of the file content (hex encoded)
+    pub hash: String,
+    pub size: u64,
+    /// File modification time (unix seconds)
+    pub modified_at: Option<i64>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Compute the SHA-256 hash of a file's content (hex encoded)
+pub fn hash_file(path: &Path) -> Result<String, AppError> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Get a file's modification time in unix seconds
+pub fn file_modified_at(metadata: &fs::Metadata) -> Option<i64> {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+}
+
+/// Hash a file and record its size and modification time
+pub fn fingerprint_file(path: &Path) -> Result<FileFingerprint, AppError> {
+    let metadata = fs::metadata(path)?;
+    Ok(FileFingerprint {
+        hash: hash_file(path)?,
+        size: metadata.len(),
+        modified_at: file_modified_at(&metadata),
+    })
+}
+
+/// Detect the document format from the file extension
+pub fn detect_document_format(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Fill a missing title/author from the file's embedded metadata (best effort)
+pub fn enrich_document_input(input: &mut LibraryDocumentInput) {
+    if input.title.is_some() && input.author.is_some() {
+        return;
+    }
+    let path = Path::new(&input.path);
+    if detect_document_format(path) != "pdf" {
+        return;
+    }
+    match load_pdf_metadata(path) {
+        Ok(metadata) => {
+            if input.title.is_none() {
+                input.title = metadata.title;
+            }
+            if input.author.is_none() {
+                input.author = metadata.author;
+            }
+        }
+        Err(e) => log::debug!("No embedded metadata for {}: {}", input.path, e),
+    }
+}
+
+fn row_to_document(row: &Row) -> rusqlite::Result<LibraryDocument> {
+    let metadata: String = row.get(8)?;
+    Ok(LibraryDocument {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        file_name: row.get(2)?,
+        hash: row.get(3)?,
+        format: row.get(4)?,
+        file_size: row.get::<_, i64>(5)? as u64,
+        title: row.get(6)?,
+        author: row.get(7)?,
+        metadata: serde_json::from_str(&metadata).unwrap_or(serde_json::Value::Null),
+        added_at: row.get(9)?,
+        opened_at: row.get(10)?,
+        updated_at: row.get(11)?,
+        file_modified_at: row.get(12)?,
+    })
+}
+
+/// Get a document by id
+pub fn get_document(conn: &Connection, id: &str) -> Result<Option<LibraryDocument>, AppError> {
+    let sql = format!("SELECT {} FROM documents WHERE id = ?1", DOCUMENT_COLUMNS);
+    Ok(conn.query_row(&sql, params![id], row_to_document).optional()?)
+}
+
+/// Get a document by its file path
+pub fn get_document_by_path(
+    conn: &Connection,
+    path: &str,
+) -> Result<Option<LibraryDocument>, AppError> {
+    let sql = format!("SELECT {} FROM documents WHERE path = ?1", DOCUMENT_COLUMNS);
+    Ok(conn.query_row(&sql, params![path], row_to_document).optional()?)
+}
+
+/// Insert a document record, or refresh the existing record for the same path
+pub fn upsert_document(
+    conn: &Connection,
+    input: &LibraryDocumentInput,
+    fingerprint: &FileFingerprint,
+    now: i64,
+) -> Result<LibraryDocument, AppError> {
+    let path = Path::new(&input.path);
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| input.path.clone());
+    let format = detect_document_format(path);
+    let metadata = input
+        .metadata
+        .clone()
+        .unwrap_or_else(|| serde_json::json!({}))
+        .to_string();
+
+    conn.execute(
+        "INSERT INTO documents (id, path, file_name, hash, format, file_size, title, author,
+                                metadata, added_at, updated_at, file_modified_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10, ?12)
+         ON CONFLICT(path) DO UPDATE SET
+            file_name = excluded.file_name,
+            hash = excluded.hash,
+            format = excluded.format,
+            file_size = excluded.file_size,
+            file_modified_at = excluded.file_modified_at,
+            title = COALESCE(excluded.title, documents.title),
+            author = COALESCE(excluded.author, documents.author),
+            metadata = CASE WHEN ?11 THEN excluded.metadata ELSE documents.metadata END,
+            updated_at = excluded.updated_at",
+        params![
+            format!("doc_{}", Uuid::new_v4()),
+            input.path,
+            file_name,
+            fingerprint.hash,
+            format,
+            fingerprint.size as i64,
+            input.title,
+            input.author,
+            metadata,
+            now,
+            input.metadata.is_some(),
+            fingerprint.modified_at,
+        ],
+    )?;
+
+    get_document_by_path(conn, &input.path)?
+        .ok_or_else(|| AppError::NotFound(format!("Document '{}' not found", input.path)))
+}
+
+/// List documents with optional filtering, sorting and paging
+pub fn list_documents(
+    conn: &Connection,
+    query: &LibraryListQuery,
+) -> Result<Vec<LibraryDocument>, AppError> {
+    let sort_column = match query.sort_by.as_deref() {
+        Some("opened") => "opened_at",
+        Some("title") => "COALESCE(title, file_name) COLLATE NOCASE",
+        Some("author") => "author COLLATE NOCASE",
+        _ => "added_at",
+    };
+    let direction = if query.ascending.unwrap_or(false) {
+        "ASC"
+    } else {
+        "DESC"
+    };
+    let sql = format!(
+        "SELECT {} FROM documents
+         WHERE (?1 IS NULL OR format = ?1)
+           AND (?4 IS NULL OR id IN (SELECT document_id FROM collection_documents
+                                     WHERE collection_id = ?4))
+         ORDER BY {} {}, id
+         LIMIT ?2 OFFSET ?3",
+        DOCUMENT_COLUMNS, sort_column, direction
+    );
+
+    let limit = query.limit.map(i64::from).unwrap_or(-1);
+    let offset = query.offset.map(i64::from).unwrap_or(0);
+    let mut stmt = conn.prepare(&sql)?;
+    let documents = stmt
+        .query_map(
+            params![query.format, limit, offset, query.collection_id],
+            row_to_document,
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(documents)
+}
+
+/// Search documents by title, author, file name or metadata content
+pub fn search_documents(
+    conn: &Connection,
+    query: &str,
+    limit: Option<u32>,
+) -> Result<Vec<LibraryDocument>, AppError> {
+    let pattern = format!(
+        "%{}%",
+        query
+            .trim()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    let sql = format!(
+        "SELECT {} FROM documents
+         WHERE title LIKE ?1 ESCAPE '\\'
+            OR author LIKE ?1 ESCAPE '\\'
+            OR file_name LIKE ?1 ESCAPE '\\'
+            OR metadata LIKE ?1 ESCAPE '\\'
+         ORDER BY COALESCE(opened_at, added_at) DESC
+         LIMIT ?2",
+        DOCUMENT_COLUMNS
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let documents = stmt
+        .query_map(
+            params![pattern, limit.map(i64::from).unwrap_or(50)],
+            row_to_document,
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(documents)
+}
+
+/// Update the metadata fields of a document
+pub fn update_document(
+    conn: &Connection,
+    id: &str,
+    update: &LibraryDocumentUpdate,
+    now: i64,
+) -> Result<LibraryDocument, AppError> {
+    let metadata = update.metadata.as_ref().map(|m| m.to_string());
+    let changed = conn.execute(
+        "UPDATE documents SET
+            title = COALESCE(?2, title),
+            author = COALESCE(?3, author),
+            metadata = COALESCE(?4, metadata),
+            updated_at = ?5
+         WHERE id = ?1",
+        params![id, update.title, update.author, metadata, now],
+    )?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("Document '{}' not found", id)));
+    }
+    get_document(conn, id)?.ok_or_else(|| AppError::NotFound(format!("Document '{}' not found", id)))
+}
+
+/// Record that a document was opened, moving it to the top of the recents
+pub fn mark_document_opened(conn: &Connection, id: &str, now: i64) -> Result<(), AppError> {
+    let changed = conn.execute(
+        "UPDATE documents SET opened_at = ?2 WHERE id = ?1",
+        params![id, now],
+    )?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("Document '{}' not found", id)));
+    }
+    conn.execute(
+        "INSERT INTO recent_documents (document_id, opened_at) VALUES (?1, ?2)
+         ON CONFLICT(document_id) DO UPDATE SET opened_at = excluded.opened_at",
+        params![id, now],
+    )?;
+    Ok(())
+}
+
+/// Remove a document from the library (the file itself is left untouched)
+pub fn remove_document(conn: &Connection, id: &str) -> Result<(), AppError> {
+    let changed = conn.execute("DELETE FROM documents WHERE id = ?1", params![id])?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("Document '{}' not found", id)));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Add a document to the library (or refresh it if the path is already known)
+#[tauri::command]
+pub fn library_add_document(
+    state: tauri::State<'_, LibraryState>,
+    mut document: LibraryDocumentInput,
+) -> Result<LibraryDocument, AppError> {
+    let path = Path::new(&document.path);
+    if !path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "File not found: {}",
+            document.path
+        )));
+    }
+
+    let fingerprint = fingerprint_file(path)?;
+    enrich_document_input(&mut document);
+
+    let conn = lock_library(&state)?;
+    let now = chrono::Utc::now().timestamp();
+    let stored = upsert_document(&conn, &document, &fingerprint, now)?;
+    log::info!("Library document added: {}", stored.path);
+    Ok(stored)
+}
+
+/// List documents in the library
+#[tauri::command]
+pub fn library_list(
+    state: tauri::State<'_, LibraryState>,
+    query: Option<LibraryListQuery>,
+) -> Result<Vec<LibraryDocument>, AppError> {
+    let conn = lock_library(&state)?;
+    list_documents(&conn, &query.unwrap_or_default())
+}
+
+/// Search library documents by metadata
+#[tauri::command]
+pub fn library_search_metadata(
+    state: tauri::State<'_, LibraryState>,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<LibraryDocument>, AppError> {
+    let conn = lock_library(&state)?;
+    search_documents(&conn, &query, limit)
+}
+
+/// Get a single library document
+#[tauri::command]
+pub fn library_get_document(
+    state: tauri::State<'_, LibraryState>,
+    id: String,
+) -> Result<Option<LibraryDocument>, AppError> {
+    let conn = lock_library(&state)?;
+    get_document(&conn, &id)
+}
+
+/// Update a library document's metadata
+#[tauri::command]
+pub fn library_update_document(
+    state: tauri::State<'_, LibraryState>,
+    id: String,
+    update: LibraryDocumentUpdate,
+) -> Result<LibraryDocument, AppError> {
+    let conn = lock_library(&state)?;
+    update_document(&conn, &id, &update, chrono::Utc::now().timestamp())
+}
+
+/// Record that a library document was opened
+#[tauri::command]
+pub fn library_mark_opened(
+    state: tauri::State<'_, LibraryState>,
+    id: String,
+) -> Result<(), AppError> {
+    let conn = lock_library(&state)?;
+    mark_document_opened(&conn, &id, chrono::Utc::now().timestamp())
+}
+
+/// Remove a document from the library
+#[tauri::command]
+pub fn library_remove_document(
+    state: tauri::State<'_, LibraryState>,
+    id: String,
+) -> Result<(), AppError> {
+    let conn = lock_library(&state)?;
+    remove_document(&conn, &id)?;
+    log::info!("Library document removed: {}", id);
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::storage::LIBRARY_MIGRATIONS;
+    use super::*;
+    use crate::db;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn fp(hash: &str, size: u64) -> FileFingerprint {
+        FileFingerprint {
+            hash: hash.to_string(),
+            size,
+            modified_at: None,
+        }
+    }
+
+    fn input(path: &str, title: Option<&str>) -> LibraryDocumentInput {
+        LibraryDocumentInput {
+            path: path.to_string(),
+            title: title.map(|t| t.to_string()),
+            author: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn hash_file_matches_known_digest() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("hello.txt");
+        fs::write(&path, "hello").unwrap();
+
+        assert_eq!(
+            hash_file(&path).unwrap(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn detect_document_format_lowercases_extension() {
+        assert_eq!(detect_document_format(Path::new("/a/Book.EPUB")), "epub");
+        assert_eq!(detect_document_format(Path::new("/a/notes")), "unknown");
+    }
+
+    #[test]
+    fn upsert_document_inserts_then_refreshes_same_path() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+
+        let first =
+            upsert_document(&conn, &input("/books/a.pdf", Some("A")), &fp("h1", 10), 100).unwrap();
+        assert_eq!(first.format, "pdf");
+        assert_eq!(first.file_name, "a.pdf");
+        assert_eq!(first.added_at, 100);
+
+        let second =
+            upsert_document(&conn, &input("/books/a.pdf", None), &fp("h2", 20), 200).unwrap();
+        assert_eq!(second.id, first.id);
+        assert_eq!(second.hash, "h2");
+        assert_eq!(second.title.as_deref(), Some("A"));
+        assert_eq!(second.added_at, 100);
+        assert_eq!(second.updated_at, 200);
+    }
+
+    #[test]
+    fn list_documents_filters_and_sorts() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        upsert_document(&conn, &input("/b.pdf", Some("Beta")), &fp("1", 1), 1).unwrap();
+        upsert_document(&conn, &input("/a.epub", Some("Alpha")), &fp("2", 1), 2).unwrap();
+        upsert_document(&conn, &input("/c.pdf", Some("Gamma")), &fp("3", 1), 3).unwrap();
+
+        let pdfs = list_documents(
+            &conn,
+            &LibraryListQuery {
+                format: Some("pdf".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(pdfs.len(), 2);
+        assert_eq!(pdfs[0].path, "/c.pdf");
+
+        let by_title = list_documents(
+            &conn,
+            &LibraryListQuery {
+                sort_by: Some("title".to_string()),
+                ascending: Some(true),
+                limit: Some(2),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let titles: Vec<_> = by_title.iter().filter_map(|d| d.title.clone()).collect();
+        assert_eq!(titles, vec!["Alpha", "Beta"]);
+    }
+
+    #[test]
+    fn search_documents_matches_metadata_and_escapes_wildcards() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let mut doc = input("/x.pdf", Some("Compilers"));
+        doc.metadata = Some(serde_json::json!({ "publisher": "Dragon Press" }));
+        upsert_document(&conn, &doc, &fp("1", 1), 1).unwrap();
+        upsert_document(&conn, &input("/y.pdf", Some("100% Rust")), &fp("2", 1), 1).unwrap();
+
+        assert_eq!(search_documents(&conn, "dragon", None).unwrap().len(), 1);
+        assert_eq!(search_documents(&conn, "100%", None).unwrap().len(), 1);
+        assert_eq!(search_documents(&conn, "%", None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn update_and_remove_document_report_missing_ids() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let doc = upsert_document(&conn, &input("/a.pdf", None), &fp("1", 1), 1).unwrap();
+
+        let updated = update_document(
+            &conn,
+            &doc.id,
+            &LibraryDocumentUpdate {
+                author: Some("Knuth".to_string()),
+                ..Default::default()
+            },
+            5,
+        )
+        .unwrap();
+        assert_eq!(updated.author.as_deref(), Some("Knuth"));
+
+        mark_document_opened(&conn, &doc.id, 9).unwrap();
+        assert_eq!(get_document(&conn, &doc.id).unwrap().unwrap().opened_at, Some(9));
+
+        remove_document(&conn, &doc.id).unwrap();
+        assert!(matches!(
+            remove_document(&conn, &doc.id),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,400 @@
+//! Duplicate document detection
+//!
+//! Documents are linked when they share a content hash, an ISBN, or a closely
+//! matching title and author; linked documents form one duplicate group.
+
+use super::documents::list_documents;
+use super::storage::lock_library;
+use super::types::{LibraryDocument, LibraryDuplicateGroup, LibraryListQuery, LibraryState};
+use crate::error::AppError;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+/// Minimum Jaro-Winkler similarity for two normalized titles to match
+const TITLE_SIMILARITY: f64 = 0.92;
+/// Minimum Jaro-Winkler similarity for two normalized authors to match
+const AUTHOR_SIMILARITY: f64 = 0.85;
+
+/// Metadata keys that may hold an ISBN
+const ISBN_KEYS: &[&str] = &["isbn", "isbn13", "isbn10", "identifier"];
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Minimal union-find over document indices
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, index: usize) -> usize {
+        let mut root = index;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut current = index;
+        while self.parent[current] != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
+
+/// Normalize a title or author for comparison: lowercase, no bracketed
+/// suffixes like "(1)" or "[epub]", punctuation removed, leading article dropped
+pub fn normalize_for_matching(value: &str) -> String {
+    let mut stripped = String::with_capacity(value.len());
+    let mut depth = 0usize;
+    for ch in value.chars() {
+        match ch {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => stripped.push(ch),
+            _ => {}
+        }
+    }
+
+    let words: Vec<String> = stripped
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_string())
+        .collect();
+    let skip = match words.first().map(String::as_str) {
+        Some("the" | "a" | "an") if words.len() > 1 => 1,
+        _ => 0,
+    };
+    words[skip..].join(" ")
+}
+
+/// Normalize an ISBN-10 or ISBN-13 to ISBN-13 digits, rejecting bad check digits
+pub fn normalize_isbn(value: &str) -> Option<String> {
+    let value = value.trim().trim_start_matches("urn:isbn:");
+    let chars: Vec<char> = value
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == 'X' || *c == 'x')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    let isbn13_check = |digits: &[u32]| {
+        let sum: u32 = digits
+            .iter()
+            .enumerate()
+            .map(|(i, d)| if i % 2 == 0 { *d } else { d * 3 })
+            .sum();
+        (10 - sum % 10) % 10
+    };
+
+    match chars.len() {
+        13 => {
+            let digits: Vec<u32> = chars.iter().filter_map(|c| c.to_digit(10)).collect();
+            (digits.len() == 13 && isbn13_check(&digits[..12]) == digits[12])
+                .then(|| chars.iter().collect())
+        }
+        10 => {
+            let mut sum = 0;
+            for (i, c) in chars.iter().enumerate() {
+                let value = match c {
+                    'X' if i == 9 => 10,
+                    _ => c.to_digit(10)?,
+                };
+                sum += value * (10 - i as u32);
+            }
+            if sum % 11 != 0 {
+                return None;
+            }
+            let mut digits = vec![9, 7, 8];
+            digits.extend(chars[..9].iter().filter_map(|c| c.to_digit(10)));
+            let check = isbn13_check(&digits);
+            digits.push(check);
+            Some(digits.iter().map(|d| d.to_string()).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Find the first valid ISBN in a document's metadata
+fn document_isbn(document: &LibraryDocument) -> Option<String> {
+    ISBN_KEYS
+        .iter()
+        .find_map(|key| match document.metadata.get(*key) {
+            Some(serde_json::Value::String(value)) => normalize_isbn(value),
+            Some(serde_json::Value::Array(values)) => values
+                .iter()
+                .filter_map(|value| value.as_str())
+                .find_map(normalize_isbn),
+            _ => None,
+        })
+}
+
+/// Normalized title, falling back to the file name without extension
+fn document_title_key(document: &LibraryDocument) -> String {
+    let title = document.title.clone().unwrap_or_else(|| {
+        Path::new(&document.file_name)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    normalize_for_matching(&title)
+}
+
+/// Whether two documents look like the same book by title and author
+fn titles_match(a: (&str, Option<&str>), b: (&str, Option<&str>)) -> bool {
+    let (title_a, author_a) = a;
+    let (title_b, author_b) = b;
+    match (author_a, author_b) {
+        (Some(author_a), Some(author_b)) => {
+            strsim::jaro_winkler(title_a, title_b) >= TITLE_SIMILARITY
+                && strsim::jaro_winkler(author_a, author_b) >= AUTHOR_SIMILARITY
+        }
+        // Without authors on both sides only an exact title match is trusted
+        _ => title_a == title_b,
+    }
+}
+
+/// Rank documents for keeping: file present, recently read, richest metadata,
+/// largest file, then the oldest library entry
+fn keep_rank(document: &LibraryDocument, exists: bool) -> (bool, i64, usize, u64, Reverse<i64>) {
+    let filled = [document.title.is_some(), document.author.is_some()]
+        .iter()
+        .filter(|filled| **filled)
+        .count()
+        + document.metadata.as_object().map(|m| m.len()).unwrap_or(0);
+    (
+        exists,
+        document.opened_at.unwrap_or(0),
+        filled,
+        document.file_size,
+        Reverse(document.added_at),
+    )
+}
+
+/// Group duplicate documents and pick a keep candidate for each group
+pub fn find_duplicate_groups(
+    documents: Vec<LibraryDocument>,
+    exists: impl Fn(&LibraryDocument) -> bool,
+) -> Vec<LibraryDuplicateGroup> {
+    let mut sets = DisjointSet::new(documents.len());
+    let mut links: Vec<(usize, usize, &'static str)> = Vec::new();
+
+    let mut link_by_key = |keys: Vec<Option<String>>, reason: &'static str| {
+        let mut first_with_key: HashMap<String, usize> = HashMap::new();
+        for (index, key) in keys.into_iter().enumerate() {
+            let Some(key) = key else { continue };
+            match first_with_key.get(&key) {
+                Some(&first) => links.push((first, index, reason)),
+                None => {
+                    first_with_key.insert(key, index);
+                }
+            }
+        }
+    };
+    link_by_key(
+        documents.iter().map(|d| Some(d.hash.clone())).collect(),
+        "hash",
+    );
+    link_by_key(documents.iter().map(document_isbn).collect(), "isbn");
+    for (a, b, _) in &links {
+        sets.union(*a, *b);
+    }
+
+    // Fuzzy titles are only compared within blocks sharing a first character
+    let keys: Vec<(String, Option<String>)> = documents
+        .iter()
+        .map(|d| {
+            (
+                document_title_key(d),
+                d.author.as_deref().map(normalize_for_matching),
+            )
+        })
+        .collect();
+    let mut blocks: HashMap<char, Vec<usize>> = HashMap::new();
+    for (index, (title, _)) in keys.iter().enumerate() {
+        if let Some(first) = title.chars().next() {
+            blocks.entry(first).or_default().push(index);
+        }
+    }
+    for block in blocks.values() {
+        for (position, &a) in block.iter().enumerate() {
+            for &b in &block[position + 1..] {
+                if sets.find(a) == sets.find(b) {
+                    continue;
+                }
+                let (title_a, author_a) = &keys[a];
+                let (title_b, author_b) = &keys[b];
+                if titles_match(
+                    (title_a, author_a.as_deref()),
+                    (title_b, author_b.as_deref()),
+                ) {
+                    sets.union(a, b);
+                    links.push((a, b, "title"));
+                }
+            }
+        }
+    }
+
+    let mut reasons: HashMap<usize, BTreeSet<&'static str>> = HashMap::new();
+    for (a, _, reason) in &links {
+        reasons.entry(sets.find(*a)).or_default().insert(reason);
+    }
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..documents.len() {
+        members.entry(sets.find(index)).or_default().push(index);
+    }
+
+    let mut documents: Vec<Option<LibraryDocument>> = documents.into_iter().map(Some).collect();
+    let mut groups: Vec<LibraryDuplicateGroup> = members
+        .into_iter()
+        .filter(|(_, indices)| indices.len() > 1)
+        .map(|(root, indices)| {
+            let mut group: Vec<(LibraryDocument, bool)> = indices
+                .into_iter()
+                .filter_map(|index| documents[index].take())
+                .map(|document| {
+                    let exists = exists(&document);
+                    (document, exists)
+                })
+                .collect();
+            group.sort_by_key(|(document, exists)| Reverse(keep_rank(document, *exists)));
+            LibraryDuplicateGroup {
+                matched_by: reasons
+                    .remove(&root)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|reason| reason.to_string())
+                    .collect(),
+                keep_id: group[0].0.id.clone(),
+                documents: group.into_iter().map(|(document, _)| document).collect(),
+            }
+        })
+        .collect();
+    groups.sort_by_key(|group| document_title_key(&group.documents[0]));
+    groups
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Find groups of duplicate documents in the library
+#[tauri::command]
+pub async fn library_find_duplicates(
+    state: tauri::State<'_, LibraryState>,
+) -> Result<Vec<LibraryDuplicateGroup>, AppError> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let documents = {
+            let conn = lock_library(&state)?;
+            list_documents(&conn, &LibraryListQuery::default())?
+        };
+        Ok(find_duplicate_groups(documents, |document| {
+            Path::new(&document.path).is_file()
+        }))
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Duplicate search failed: {}", e)))?
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(
+        id: &str,
+        hash: &str,
+        title: Option<&str>,
+        author: Option<&str>,
+    ) -> LibraryDocument {
+        LibraryDocument {
+            id: id.to_string(),
+            path: format!("/books/{}.pdf", id),
+            file_name: format!("{}.pdf", id),
+            hash: hash.to_string(),
+            format: "pdf".to_string(),
+            file_size: 100,
+            title: title.map(|t| t.to_string()),
+            author: author.map(|a| a.to_string()),
+            metadata: serde_json::json!({}),
+            added_at: 1,
+            opened_at: None,
+            updated_at: 1,
+            file_modified_at: None,
+        }
+    }
+
+    #[test]
+    fn normalize_helpers_handle_titles_and_isbns() {
+        assert_eq!(
+            normalize_for_matching("The Rust Programming Language (2nd Edition)"),
+            "rust programming language"
+        );
+        assert_eq!(normalize_for_matching("Clean-Code [epub]"), "clean code");
+        assert_eq!(
+            normalize_isbn("0-306-40615-2").as_deref(),
+            Some("9780306406157")
+        );
+        assert_eq!(
+            normalize_isbn("urn:isbn:978-0-306-40615-7").as_deref(),
+            Some("9780306406157")
+        );
+        assert_eq!(normalize_isbn("978-0-306-40615-8"), None);
+        assert_eq!(normalize_isbn("12345"), None);
+    }
+
+    #[test]
+    fn find_duplicate_groups_links_hash_isbn_and_fuzzy_titles() {
+        let mut a = document("a", "h1", Some("Dune"), Some("Frank Herbert"));
+        let b = document("b", "h1", None, None);
+        let mut c = document("c", "h2", Some("Dune (1)"), None);
+        c.metadata = serde_json::json!({ "isbn": "0-306-40615-2" });
+        a.metadata = serde_json::json!({ "identifier": ["x", "9780306406157"] });
+        a.opened_at = Some(50);
+        let d = document(
+            "d",
+            "h3",
+            Some("The Pragmatic Programmer"),
+            Some("Andrew Hunt"),
+        );
+        let e = document("e", "h4", Some("Pragmatic Programmer"), Some("Andy Hunt"));
+        // Close title but no author: not trusted
+        let f = document("f", "h5", Some("Pragmatic Programmers"), None);
+        let g = document("g", "h6", Some("Unrelated"), Some("Someone"));
+
+        let groups = find_duplicate_groups(vec![a, b, c, d, e, f, g], |doc| doc.id != "d");
+
+        assert_eq!(groups.len(), 2);
+        let dune = &groups[0];
+        assert_eq!(dune.documents.len(), 3);
+        assert_eq!(dune.keep_id, "a");
+        assert_eq!(dune.matched_by, vec!["hash", "isbn"]);
+
+        let pragmatic = &groups[1];
+        assert_eq!(pragmatic.matched_by, vec!["title"]);
+        let ids: Vec<_> = pragmatic.documents.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&"d") && ids.contains(&"e"));
+        // "d" is missing on disk, so the copy that still exists is kept
+        assert_eq!(pragmatic.keep_id, "e");
+    }
+}
@@ -0,0 +1,610 @@
+//! Online book metadata lookup
+//!
+//! Queries Open Library and Google Books by ISBN or title/author. Results are
+//! cached in the library database and requests to each service are spaced out
+//! to stay within their fair-use limits.
+
+use super::documents::{get_document, update_document};
+use super::duplicates::{normalize_for_matching, normalize_isbn};
+use super::storage::lock_library;
+use super::types::{
+    BookMetadataCandidate, BookMetadataQuery, LibraryDocument, LibraryDocumentUpdate, LibraryState,
+};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// How long cached lookups stay fresh
+const CACHE_TTL_SECONDS: i64 = 7 * 24 * 60 * 60;
+/// Maximum number of candidates requested from each service
+const RESULTS_PER_SOURCE: usize = 5;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+const OPEN_LIBRARY_BOOKS_URL: &str = "https://openlibrary.org/api/books";
+const OPEN_LIBRARY_SEARCH_URL: &str = "https://openlibrary.org/search.json";
+const GOOGLE_BOOKS_URL: &str = "https://www.googleapis.com/books/v1/volumes";
+
+// ============================================================================
+// Rate Limiting
+// ============================================================================
+
+/// Enforces a minimum interval between requests to one service
+struct RateLimiter {
+    interval: Duration,
+    last_request: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    const fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_request: Mutex::const_new(None),
+        }
+    }
+
+    /// Wait until the next request is allowed and reserve the slot
+    async fn acquire(&self) {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(previous) = *last_request {
+            let elapsed = previous.elapsed();
+            if elapsed < self.interval {
+                tokio::time::sleep(self.interval - elapsed).await;
+            }
+        }
+        *last_request = Some(Instant::now());
+    }
+}
+
+static OPEN_LIBRARY_LIMITER: RateLimiter = RateLimiter::new(Duration::from_millis(1000));
+static GOOGLE_BOOKS_LIMITER: RateLimiter = RateLimiter::new(Duration::from_millis(500));
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn str_at(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+fn strings_at(value: &Value, key: &str) -> Vec<String> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| match item {
+                    Value::String(s) => Some(s.clone()),
+                    // Open Library wraps names in objects
+                    other => str_at(other, "name"),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Pick the first ISBN-13 and ISBN-10 from a list of identifiers
+fn split_isbns<'a>(values: impl IntoIterator<Item = &'a str>) -> (Option<String>, Option<String>) {
+    let mut isbn13 = None;
+    let mut isbn10 = None;
+    for value in values {
+        let digits: String = value
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        match digits.len() {
+            13 if isbn13.is_none() => isbn13 = Some(digits),
+            10 if isbn10.is_none() => isbn10 = Some(digits),
+            _ => {}
+        }
+    }
+    (isbn13, isbn10)
+}
+
+/// Build the cache key for a query, validating that it has an ISBN or a title
+pub fn lookup_cache_key(query: &BookMetadataQuery) -> Result<String, AppError> {
+    if let Some(isbn) = query.isbn.as_deref().filter(|isbn| !isbn.trim().is_empty()) {
+        let isbn = normalize_isbn(isbn)
+            .ok_or_else(|| AppError::InvalidInput(format!("Invalid ISBN: {}", isbn)))?;
+        return Ok(format!("isbn:{}", isbn));
+    }
+    let title = query
+        .title
+        .as_deref()
+        .map(normalize_for_matching)
+        .filter(|title| !title.is_empty())
+        .ok_or_else(|| AppError::InvalidInput("An ISBN or a title is required".to_string()))?;
+    let author = query
+        .author
+        .as_deref()
+        .map(normalize_for_matching)
+        .unwrap_or_default();
+    Ok(format!("q:{}|{}", title, author))
+}
+
+/// Parse an Open Library `api/books?jscmd=data` record
+pub fn parse_open_library_book(book: &Value) -> Option<BookMetadataCandidate> {
+    let identifiers = book.get("identifiers").cloned().unwrap_or(Value::Null);
+    let isbns = strings_at(&identifiers, "isbn_13")
+        .into_iter()
+        .chain(strings_at(&identifiers, "isbn_10"))
+        .collect::<Vec<_>>();
+    let (isbn13, isbn10) = split_isbns(isbns.iter().map(String::as_str));
+    let cover = book.get("cover").cloned().unwrap_or(Value::Null);
+
+    Some(BookMetadataCandidate {
+        source: "openlibrary".to_string(),
+        title: str_at(book, "title")?,
+        subtitle: str_at(book, "subtitle"),
+        authors: strings_at(book, "authors"),
+        publisher: strings_at(book, "publishers").into_iter().next(),
+        published_date: str_at(book, "publish_date"),
+        description: str_at(book, "notes"),
+        isbn13,
+        isbn10,
+        page_count: book
+            .get("number_of_pages")
+            .and_then(Value::as_u64)
+            .map(|n| n as u32),
+        subjects: strings_at(book, "subjects").into_iter().take(10).collect(),
+        language: None,
+        cover_url: str_at(&cover, "large").or_else(|| str_at(&cover, "medium")),
+    })
+}
+
+/// Parse one document of an Open Library `search.json` response
+pub fn parse_open_library_search_doc(doc: &Value) -> Option<BookMetadataCandidate> {
+    let isbns = strings_at(doc, "isbn");
+    let (isbn13, isbn10) = split_isbns(isbns.iter().map(String::as_str));
+
+    Some(BookMetadataCandidate {
+        source: "openlibrary".to_string(),
+        title: str_at(doc, "title")?,
+        subtitle: str_at(doc, "subtitle"),
+        authors: strings_at(doc, "author_name"),
+        publisher: strings_at(doc, "publisher").into_iter().next(),
+        published_date: doc
+            .get("first_publish_year")
+            .and_then(Value::as_i64)
+            .map(|year| year.to_string()),
+        description: None,
+        isbn13,
+        isbn10,
+        page_count: doc
+            .get("number_of_pages_median")
+            .and_then(Value::as_u64)
+            .map(|n| n as u32),
+        subjects: strings_at(doc, "subject").into_iter().take(10).collect(),
+        language: strings_at(doc, "language").into_iter().next(),
+        cover_url: doc
+            .get("cover_i")
+            .and_then(Value::as_i64)
+            .map(|id| format!("https://covers.openlibrary.org/b/id/{}-L.jpg", id)),
+    })
+}
+
+/// Parse one item of a Google Books `volumes` response
+pub fn parse_google_books_item(item: &Value) -> Option<BookMetadataCandidate> {
+    let info = item.get("volumeInfo")?;
+    let identifiers = info
+        .get("industryIdentifiers")
+        .and_then(Value::as_array)
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|id| str_at(id, "identifier"))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let (isbn13, isbn10) = split_isbns(identifiers.iter().map(String::as_str));
+    let images = info.get("imageLinks").cloned().unwrap_or(Value::Null);
+
+    Some(BookMetadataCandidate {
+        source: "googlebooks".to_string(),
+        title: str_at(info, "title")?,
+        subtitle: str_at(info, "subtitle"),
+        authors: strings_at(info, "authors"),
+        publisher: str_at(info, "publisher"),
+        published_date: str_at(info, "publishedDate"),
+        description: str_at(info, "description"),
+        isbn13,
+        isbn10,
+        page_count: info
+            .get("pageCount")
+            .and_then(Value::as_u64)
+            .map(|n| n as u32),
+        subjects: strings_at(info, "categories"),
+        language: str_at(info, "language"),
+        cover_url: str_at(&images, "thumbnail")
+            .or_else(|| str_at(&images, "smallThumbnail"))
+            .map(|url| url.replacen("http://", "https://", 1)),
+    })
+}
+
+/// Merge a fetched candidate into a document's free-form metadata
+pub fn merge_candidate_metadata(existing: &Value, candidate: &BookMetadataCandidate) -> Value {
+    let mut metadata = existing.as_object().cloned().unwrap_or_default();
+    let mut set = |key: &str, value: Option<Value>| {
+        if let Some(value) = value {
+            metadata.insert(key.to_string(), value);
+        }
+    };
+    set("subtitle", candidate.subtitle.clone().map(Value::from));
+    set("publisher", candidate.publisher.clone().map(Value::from));
+    set(
+        "publishedDate",
+        candidate.published_date.clone().map(Value::from),
+    );
+    set(
+        "description",
+        candidate.description.clone().map(Value::from),
+    );
+    set(
+        "isbn",
+        candidate
+            .isbn13
+            .clone()
+            .or_else(|| candidate.isbn10.clone())
+            .map(Value::from),
+    );
+    set("pageCount", candidate.page_count.map(Value::from));
+    set(
+        "subjects",
+        (!candidate.subjects.is_empty()).then(|| Value::from(candidate.subjects.clone())),
+    );
+    set("language", candidate.language.clone().map(Value::from));
+    set("coverUrl", candidate.cover_url.clone().map(Value::from));
+    set(
+        "metadataSource",
+        Some(Value::from(candidate.source.clone())),
+    );
+    Value::Object(metadata)
+}
+
+/// Read fresh cached results for a lookup key
+fn read_cached_lookup(
+    conn: &Connection,
+    key: &str,
+    now: i64,
+) -> Result<Option<Vec<BookMetadataCandidate>>, AppError> {
+    let cached: Option<String> = conn
+        .query_row(
+            "SELECT results FROM metadata_lookup_cache WHERE key = ?1 AND fetched_at > ?2",
+            params![key, now - CACHE_TTL_SECONDS],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(cached.and_then(|json| serde_json::from_str(&json).ok()))
+}
+
+/// Store lookup results in the cache
+fn write_cached_lookup(
+    conn: &Connection,
+    key: &str,
+    results: &[BookMetadataCandidate],
+    now: i64,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO metadata_lookup_cache (key, results, fetched_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(key) DO UPDATE SET results = excluded.results, fetched_at = excluded.fetched_at",
+        params![key, serde_json::to_string(results)?, now],
+    )?;
+    Ok(())
+}
+
+/// Send a rate-limited GET request and parse the JSON body
+async fn get_json(
+    client: &reqwest::Client,
+    limiter: &RateLimiter,
+    url: &str,
+    query: &[(&str, String)],
+) -> Result<Value, AppError> {
+    limiter.acquire().await;
+    let response = client.get(url).query(query).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(AppError::http_status(
+            status,
+            format!("Metadata request to {} failed with status {}", url, status),
+        ));
+    }
+    Ok(response.json().await?)
+}
+
+/// Query Open Library by ISBN or by title/author
+async fn query_open_library(
+    client: &reqwest::Client,
+    isbn: Option<&str>,
+    title: &str,
+    author: &str,
+) -> Result<Vec<BookMetadataCandidate>, AppError> {
+    if let Some(isbn) = isbn {
+        let bibkey = format!("ISBN:{}", isbn);
+        let body = get_json(
+            client,
+            &OPEN_LIBRARY_LIMITER,
+            OPEN_LIBRARY_BOOKS_URL,
+            &[
+                ("bibkeys", bibkey.clone()),
+                ("format", "json".to_string()),
+                ("jscmd", "data".to_string()),
+            ],
+        )
+        .await?;
+        return Ok(body
+            .get(&bibkey)
+            .and_then(parse_open_library_book)
+            .into_iter()
+            .collect());
+    }
+
+    let mut query = vec![
+        ("title", title.to_string()),
+        ("limit", RESULTS_PER_SOURCE.to_string()),
+    ];
+    if !author.is_empty() {
+        query.push(("author", author.to_string()));
+    }
+    let body = get_json(
+        client,
+        &OPEN_LIBRARY_LIMITER,
+        OPEN_LIBRARY_SEARCH_URL,
+        &query,
+    )
+    .await?;
+    Ok(body
+        .get("docs")
+        .and_then(Value::as_array)
+        .map(|docs| {
+            docs.iter()
+                .filter_map(parse_open_library_search_doc)
+                .take(RESULTS_PER_SOURCE)
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Query Google Books by ISBN or by title/author
+async fn query_google_books(
+    client: &reqwest::Client,
+    isbn: Option<&str>,
+    title: &str,
+    author: &str,
+) -> Result<Vec<BookMetadataCandidate>, AppError> {
+    let q = match isbn {
+        Some(isbn) => format!("isbn:{}", isbn),
+        None if author.is_empty() => format!("intitle:{}", title),
+        None => format!("intitle:{} inauthor:{}", title, author),
+    };
+    let body = get_json(
+        client,
+        &GOOGLE_BOOKS_LIMITER,
+        GOOGLE_BOOKS_URL,
+        &[("q", q), ("maxResults", RESULTS_PER_SOURCE.to_string())],
+    )
+    .await?;
+    Ok(body
+        .get("items")
+        .and_then(Value::as_array)
+        .map(|items| items.iter().filter_map(parse_google_books_item).collect())
+        .unwrap_or_default())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Look up book metadata online by ISBN, or by title and optional author
+#[tauri::command]
+pub async fn fetch_book_metadata(
+    state: tauri::State<'_, LibraryState>,
+    query: BookMetadataQuery,
+) -> Result<Vec<BookMetadataCandidate>, AppError> {
+    let key = lookup_cache_key(&query)?;
+    let now = chrono::Utc::now().timestamp();
+    {
+        let conn = lock_library(&state)?;
+        if let Some(cached) = read_cached_lookup(&conn, &key, now)? {
+            log::debug!("Metadata lookup served from cache: {}", key);
+            return Ok(cached);
+        }
+    }
+
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("sast-readium/", env!("CARGO_PKG_VERSION")))
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    let isbn = query.isbn.as_deref().and_then(normalize_isbn);
+    let title = query.title.clone().unwrap_or_default();
+    let author = query.author.clone().unwrap_or_default();
+
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    match query_open_library(&client, isbn.as_deref(), title.trim(), author.trim()).await {
+        Ok(found) => results.extend(found),
+        Err(e) => errors.push(("Open Library", e)),
+    }
+    match query_google_books(&client, isbn.as_deref(), title.trim(), author.trim()).await {
+        Ok(found) => results.extend(found),
+        Err(e) => errors.push(("Google Books", e)),
+    }
+
+    if let [(_, first), _] = errors.as_slice() {
+        let detail = errors
+            .iter()
+            .map(|(source, e)| format!("{}: {}", source, e))
+            .collect::<Vec<_>>()
+            .join("; ");
+        // Classified like the first failure
+        return Err(match first {
+            AppError::Http { status, .. } => AppError::Http {
+                status: *status,
+                detail,
+            },
+            _ => AppError::Internal(detail),
+        });
+    }
+    for (source, error) in &errors {
+        log::warn!("Metadata lookup partially failed: {}: {}", source, error);
+    }
+    // Only complete answers are cached, so a transient failure is retried next time
+    if errors.is_empty() {
+        let conn = lock_library(&state)?;
+        write_cached_lookup(&conn, &key, &results, now)?;
+    }
+    Ok(results)
+}
+
+/// Apply fetched metadata (title, authors, publisher, ISBN, ...) to a library document
+#[tauri::command]
+pub fn library_apply_book_metadata(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+    metadata: BookMetadataCandidate,
+) -> Result<LibraryDocument, AppError> {
+    let conn = lock_library(&state)?;
+    let document = get_document(&conn, &document_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Document '{}' not found", document_id)))?;
+    let update = LibraryDocumentUpdate {
+        title: Some(metadata.title.clone()).filter(|title| !title.trim().is_empty()),
+        author: (!metadata.authors.is_empty()).then(|| metadata.authors.join(", ")),
+        metadata: Some(merge_candidate_metadata(&document.metadata, &metadata)),
+    };
+    update_document(&conn, &document_id, &update, chrono::Utc::now().timestamp())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::storage::LIBRARY_MIGRATIONS;
+    use super::*;
+    use crate::db;
+    use serde_json::json;
+
+    #[test]
+    fn lookup_cache_key_normalizes_queries() {
+        let by_isbn = BookMetadataQuery {
+            isbn: Some("0-306-40615-2".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(lookup_cache_key(&by_isbn).unwrap(), "isbn:9780306406157");
+
+        let by_title = BookMetadataQuery {
+            title: Some("The  Hobbit".to_string()),
+            author: Some("J.R.R. Tolkien".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            lookup_cache_key(&by_title).unwrap(),
+            "q:hobbit|j r r tolkien"
+        );
+
+        assert!(lookup_cache_key(&BookMetadataQuery::default()).is_err());
+        let bad_isbn = BookMetadataQuery {
+            isbn: Some("123".to_string()),
+            ..Default::default()
+        };
+        assert!(lookup_cache_key(&bad_isbn).is_err());
+    }
+
+    #[test]
+    fn parses_open_library_and_google_books_responses() {
+        let book = json!({
+            "title": "The Hobbit",
+            "authors": [{ "name": "J.R.R. Tolkien" }],
+            "publishers": [{ "name": "Allen & Unwin" }],
+            "publish_date": "1937",
+            "number_of_pages": 310,
+            "identifiers": { "isbn_10": ["0261102214"], "isbn_13": ["9780261102217"] },
+            "cover": { "medium": "https://covers.openlibrary.org/b/id/1-M.jpg" }
+        });
+        let parsed = parse_open_library_book(&book).unwrap();
+        assert_eq!(parsed.authors, vec!["J.R.R. Tolkien"]);
+        assert_eq!(parsed.publisher.as_deref(), Some("Allen & Unwin"));
+        assert_eq!(parsed.isbn13.as_deref(), Some("9780261102217"));
+        assert_eq!(parsed.isbn10.as_deref(), Some("0261102214"));
+        assert_eq!(parsed.page_count, Some(310));
+
+        let doc = json!({
+            "title": "The Hobbit",
+            "author_name": ["J.R.R. Tolkien"],
+            "first_publish_year": 1937,
+            "cover_i": 42
+        });
+        let parsed = parse_open_library_search_doc(&doc).unwrap();
+        assert_eq!(parsed.published_date.as_deref(), Some("1937"));
+        assert_eq!(
+            parsed.cover_url.as_deref(),
+            Some("https://covers.openlibrary.org/b/id/42-L.jpg")
+        );
+
+        let item = json!({
+            "volumeInfo": {
+                "title": "The Hobbit",
+                "authors": ["J. R. R. Tolkien"],
+                "description": "A fantasy novel",
+                "industryIdentifiers": [
+                    { "type": "ISBN_13", "identifier": "9780547928227" }
+                ],
+                "imageLinks": { "thumbnail": "http://books.google.com/x.jpg" },
+                "language": "en"
+            }
+        });
+        let parsed = parse_google_books_item(&item).unwrap();
+        assert_eq!(parsed.source, "googlebooks");
+        assert_eq!(parsed.isbn13.as_deref(), Some("9780547928227"));
+        assert_eq!(
+            parsed.cover_url.as_deref(),
+            Some("https://books.google.com/x.jpg")
+        );
+        assert!(parse_google_books_item(&json!({ "volumeInfo": {} })).is_none());
+    }
+
+    #[test]
+    fn merge_candidate_metadata_keeps_existing_fields() {
+        let candidate = BookMetadataCandidate {
+            source: "openlibrary".to_string(),
+            title: "Dune".to_string(),
+            publisher: Some("Chilton".to_string()),
+            isbn10: Some("0441013597".to_string()),
+            ..Default::default()
+        };
+        let merged =
+            merge_candidate_metadata(&json!({ "series": "Dune", "publisher": "x" }), &candidate);
+
+        assert_eq!(merged["series"], "Dune");
+        assert_eq!(merged["publisher"], "Chilton");
+        assert_eq!(merged["isbn"], "0441013597");
+        assert_eq!(merged["metadataSource"], "openlibrary");
+        assert!(merged.get("description").is_none());
+    }
+
+    #[test]
+    fn cached_lookups_expire() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let results = vec![BookMetadataCandidate {
+            source: "googlebooks".to_string(),
+            title: "Dune".to_string(),
+            ..Default::default()
+        }];
+        write_cached_lookup(&conn, "q:dune|", &results, 1_000).unwrap();
+
+        assert_eq!(
+            read_cached_lookup(&conn, "q:dune|", 2_000).unwrap(),
+            Some(results)
+        );
+        assert_eq!(
+            read_cached_lookup(&conn, "q:dune|", 1_000 + CACHE_TTL_SECONDS).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! Document library management commands
+//!
+//! The library is backed by an embedded SQLite database stored in the app data
+//! directory. It provides:
+//! - Document records (path, content hash, format, metadata, timestamps)
+//! - CRUD and metadata search commands
+//! - Incremental folder scanning for EPUB/PDF files
+//! - Per-document bookmarks
+//! - Reading progress for "continue reading"
+//! - Recently opened documents with pinning
+//! - Reading sessions and statistics
+//! - Collections and tags
+//! - Duplicate detection
+//! - Online metadata lookup (Open Library, Google Books)
+
+mod types;
+mod storage;
+mod documents;
+mod scan;
+mod bookmarks;
+mod progress;
+mod recents;
+mod sessions;
+mod collections;
+mod duplicates;
+mod metadata_lookup;
+
+// Re-export all public items
+pub use types::*;
+pub use storage::*;
+pub use documents::*;
+pub use scan::*;
+pub use bookmarks::*;
+pub use progress::*;
+pub use recents::*;
+pub use sessions::*;
+pub use collections::*;
+pub use duplicates::*;
+pub use metadata_lookup::*;
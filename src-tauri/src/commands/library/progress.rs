@@ -0,0 +1,149 @@
+//! Reading progress commands
+
+use super::documents::get_document;
+use super::storage::lock_library;
+use super::types::{LibraryState, ReadingProgress};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension};
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Store the reading position of a document, replacing the previous one
+pub fn save_progress(
+    conn: &Connection,
+    document_id: &str,
+    locator: &str,
+    percent: f64,
+    now: i64,
+) -> Result<ReadingProgress, AppError> {
+    if !percent.is_finite() {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid progress percentage: {}",
+            percent
+        )));
+    }
+    if get_document(conn, document_id)?.is_none() {
+        return Err(AppError::NotFound(format!(
+            "Document '{}' not found",
+            document_id
+        )));
+    }
+
+    let progress = ReadingProgress {
+        document_id: document_id.to_string(),
+        locator: locator.to_string(),
+        percent: percent.clamp(0.0, 100.0),
+        last_read_at: now,
+    };
+    conn.execute(
+        "INSERT INTO reading_progress (document_id, locator, percent, last_read_at)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(document_id) DO UPDATE SET
+            locator = excluded.locator,
+            percent = excluded.percent,
+            last_read_at = excluded.last_read_at",
+        params![
+            progress.document_id,
+            progress.locator,
+            progress.percent,
+            progress.last_read_at
+        ],
+    )?;
+    Ok(progress)
+}
+
+/// Get the saved reading position of a document
+pub fn get_progress(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Option<ReadingProgress>, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT document_id, locator, percent, last_read_at FROM reading_progress
+             WHERE document_id = ?1",
+            params![document_id],
+            |row| {
+                Ok(ReadingProgress {
+                    document_id: row.get(0)?,
+                    locator: row.get(1)?,
+                    percent: row.get(2)?,
+                    last_read_at: row.get(3)?,
+                })
+            },
+        )
+        .optional()?)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Save the current reading position of a library document
+#[tauri::command]
+pub fn save_reading_progress(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+    locator: String,
+    percent: f64,
+) -> Result<ReadingProgress, AppError> {
+    let conn = lock_library(&state)?;
+    save_progress(
+        &conn,
+        &document_id,
+        &locator,
+        percent,
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+/// Get the last saved reading position of a library document
+#[tauri::command]
+pub fn get_reading_progress(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+) -> Result<Option<ReadingProgress>, AppError> {
+    let conn = lock_library(&state)?;
+    get_progress(&conn, &document_id)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::documents::{upsert_document, FileFingerprint};
+    use super::super::storage::LIBRARY_MIGRATIONS;
+    use super::super::types::LibraryDocumentInput;
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn save_progress_replaces_previous_position() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let input = LibraryDocumentInput {
+            path: "/books/a.epub".to_string(),
+            ..Default::default()
+        };
+        let fingerprint = FileFingerprint {
+            hash: "h".to_string(),
+            size: 1,
+            modified_at: None,
+        };
+        let doc = upsert_document(&conn, &input, &fingerprint, 1).unwrap().id;
+
+        assert!(get_progress(&conn, &doc).unwrap().is_none());
+        save_progress(&conn, &doc, "epubcfi(/6/2)", 12.5, 100).unwrap();
+        save_progress(&conn, &doc, "epubcfi(/6/8)", 140.0, 200).unwrap();
+
+        let progress = get_progress(&conn, &doc).unwrap().unwrap();
+        assert_eq!(progress.locator, "epubcfi(/6/8)");
+        assert_eq!(progress.percent, 100.0);
+        assert_eq!(progress.last_read_at, 200);
+
+        assert!(save_progress(&conn, &doc, "x", f64::NAN, 300).is_err());
+        assert!(save_progress(&conn, "doc_missing", "x", 1.0, 300).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+//! Recently opened documents
+//!
+//! Opening a document moves it to the top of `recent_documents`. Pinned
+//! entries are listed before the rest and are never trimmed; unpinned ones
+//! beyond `MAX_RECENT_DOCUMENTS` are dropped as new documents are opened.
+//! Entries whose file no longer exists are pruned when the list is read.
+
+use super::documents::{get_document, mark_document_opened};
+use super::storage::lock_library;
+use super::types::{LibraryState, RecentDocument};
+use crate::commands::file_ops::get_file_metadata;
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+
+/// Unpinned entries kept in the list
+pub const MAX_RECENT_DOCUMENTS: u32 = 100;
+
+/// Entries returned when no limit is given
+const DEFAULT_RECENT_LIMIT: u32 = 20;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Record that a document was opened and drop the oldest unpinned entries
+pub fn record_opened(conn: &Connection, document_id: &str, now: i64) -> Result<(), AppError> {
+    mark_document_opened(conn, document_id, now)?;
+    conn.execute(
+        "DELETE FROM recent_documents WHERE pinned_at IS NULL AND document_id NOT IN (
+            SELECT document_id FROM recent_documents WHERE pinned_at IS NULL
+            ORDER BY opened_at DESC LIMIT ?1
+         )",
+        params![MAX_RECENT_DOCUMENTS],
+    )?;
+    Ok(())
+}
+
+/// List recent documents, pinned first, then most recently opened
+pub fn list_recent(conn: &Connection, limit: u32) -> Result<Vec<RecentDocument>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT document_id, opened_at, pinned_at FROM recent_documents
+         ORDER BY pinned_at IS NULL, pinned_at DESC, opened_at DESC
+         LIMIT ?1",
+    )?;
+    let rows = stmt
+        .query_map(params![limit], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut recents = Vec::with_capacity(rows.len());
+    for (document_id, opened_at, pinned_at) in rows {
+        if let Some(document) = get_document(conn, &document_id)? {
+            recents.push(RecentDocument {
+                document,
+                opened_at,
+                pinned_at,
+            });
+        }
+    }
+    Ok(recents)
+}
+
+/// Pin or unpin a recent document
+pub fn set_recent_pinned(
+    conn: &Connection,
+    document_id: &str,
+    pinned: bool,
+    now: i64,
+) -> Result<(), AppError> {
+    let changed = conn.execute(
+        "UPDATE recent_documents SET pinned_at = ?2 WHERE document_id = ?1",
+        params![document_id, pinned.then_some(now)],
+    )?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!(
+            "Document '{}' is not in the recent documents",
+            document_id
+        )));
+    }
+    Ok(())
+}
+
+/// Remove entries whose file `exists` says is gone; returns how many
+pub fn prune_missing<F>(conn: &Connection, exists: F) -> Result<usize, AppError>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut stmt = conn.prepare(
+        "SELECT r.document_id, d.path FROM recent_documents r
+         JOIN documents d ON d.id = r.document_id",
+    )?;
+    let missing = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|(_, path)| !exists(path))
+        .map(|(document_id, _)| document_id)
+        .collect::<Vec<_>>();
+    for document_id in &missing {
+        conn.execute(
+            "DELETE FROM recent_documents WHERE document_id = ?1",
+            params![document_id],
+        )?;
+    }
+    if !missing.is_empty() {
+        log::info!(
+            "Pruned {} recent documents with missing files",
+            missing.len()
+        );
+    }
+    Ok(missing.len())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Record that a library document was opened
+#[tauri::command]
+pub fn record_document_opened(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+) -> Result<(), AppError> {
+    let conn = lock_library(&state)?;
+    record_opened(&conn, &document_id, chrono::Utc::now().timestamp())
+}
+
+/// Get the recently opened documents, dropping those whose file is gone
+#[tauri::command]
+pub fn get_recent_documents(
+    state: tauri::State<'_, LibraryState>,
+    limit: Option<u32>,
+) -> Result<Vec<RecentDocument>, AppError> {
+    let conn = lock_library(&state)?;
+    prune_missing(&conn, |path| get_file_metadata(path.to_string()).is_some())?;
+    list_recent(&conn, limit.unwrap_or(DEFAULT_RECENT_LIMIT))
+}
+
+/// Pin a recent document to the top of the list
+#[tauri::command]
+pub fn pin_recent_document(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+) -> Result<(), AppError> {
+    let conn = lock_library(&state)?;
+    set_recent_pinned(&conn, &document_id, true, chrono::Utc::now().timestamp())
+}
+
+/// Unpin a recent document
+#[tauri::command]
+pub fn unpin_recent_document(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+) -> Result<(), AppError> {
+    let conn = lock_library(&state)?;
+    set_recent_pinned(&conn, &document_id, false, chrono::Utc::now().timestamp())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::documents::{upsert_document, FileFingerprint};
+    use super::super::storage::LIBRARY_MIGRATIONS;
+    use super::super::types::LibraryDocumentInput;
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn recents_list_pinned_first_and_prune_missing_files() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let fingerprint = FileFingerprint {
+            hash: "h".to_string(),
+            size: 1,
+            modified_at: None,
+        };
+        let add = |path: &str| {
+            let input = LibraryDocumentInput {
+                path: path.to_string(),
+                ..Default::default()
+            };
+            upsert_document(&conn, &input, &fingerprint, 1).unwrap().id
+        };
+        let a = add("/books/a.epub");
+        let b = add("/books/b.pdf");
+        let c = add("/books/c.epub");
+
+        record_opened(&conn, &a, 100).unwrap();
+        record_opened(&conn, &b, 200).unwrap();
+        record_opened(&conn, &c, 300).unwrap();
+        record_opened(&conn, &a, 400).unwrap();
+        set_recent_pinned(&conn, &b, true, 500).unwrap();
+
+        let ids = |recents: Vec<RecentDocument>| -> Vec<String> {
+            recents.into_iter().map(|r| r.document.id).collect()
+        };
+        assert_eq!(
+            ids(list_recent(&conn, 10).unwrap()),
+            [b.clone(), a.clone(), c.clone()]
+        );
+        assert_eq!(ids(list_recent(&conn, 2).unwrap()), [b.clone(), a.clone()]);
+
+        set_recent_pinned(&conn, &b, false, 600).unwrap();
+        assert_eq!(
+            ids(list_recent(&conn, 10).unwrap()),
+            [a.clone(), c.clone(), b.clone()]
+        );
+        assert!(set_recent_pinned(&conn, "doc_missing", true, 700).is_err());
+        assert!(record_opened(&conn, "doc_missing", 700).is_err());
+
+        let pruned = prune_missing(&conn, |path| path != "/books/c.epub").unwrap();
+        assert_eq!(pruned, 1);
+        assert_eq!(ids(list_recent(&conn, 10).unwrap()), [a, b]);
+    }
+}
@@ -0,0 +1,529 @@
+//! Incremental library folder scanning
+//!
+//! Files are matched against existing records by path first. Unchanged size and
+//! modification time skip hashing entirely; otherwise the content hash decides
+//! whether the record is updated, or whether a missing record was moved.
+
+use super::documents::{
+    enrich_document_input, file_modified_at, fingerprint_file, list_documents, upsert_document,
+    FileFingerprint,
+};
+use super::storage::lock_library;
+use super::types::{
+    LibraryDocument, LibraryDocumentInput, LibraryFolder, LibraryListQuery, LibraryScanProgress,
+    LibraryScanResult, LibraryState,
+};
+use crate::commands::events::emit_event;
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Event emitted while a folder scan is running
+pub const LIBRARY_SCAN_PROGRESS_EVENT: &str = "library-scan-progress";
+
+/// File extensions picked up by folder scans
+const SCANNABLE_FORMATS: &[&str] = &["pdf", "epub"];
+
+/// Emit a progress event every N files
+const PROGRESS_INTERVAL: usize = 25;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Check whether a path has a scannable document extension
+pub fn is_scannable_document(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| SCANNABLE_FORMATS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Document files found below a library folder
+#[derive(Debug, Default)]
+pub struct DocumentWalk {
+    pub files: Vec<PathBuf>,
+    /// Hidden directories that were not descended into
+    pub hidden: Vec<PathBuf>,
+    /// Entries that could not be read, with the error
+    pub unreadable: Vec<(PathBuf, String)>,
+}
+
+impl DocumentWalk {
+    /// Whether a path lies in a part of the tree the walk did not see
+    fn was_skipped(&self, path: &Path) -> bool {
+        self.hidden.iter().any(|dir| path.starts_with(dir))
+            || self.unreadable.iter().any(|(dir, _)| path.starts_with(dir))
+    }
+}
+
+/// Recursively collect document files below a folder, skipping hidden entries
+pub fn collect_document_files(root: &Path) -> DocumentWalk {
+    let mut hidden = Vec::new();
+    let mut walk = DocumentWalk::default();
+    let entries = WalkDir::new(root)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|entry| {
+            let visible =
+                entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.');
+            if !visible && entry.file_type().is_dir() {
+                hidden.push(entry.path().to_path_buf());
+            }
+            visible
+        });
+    for entry in entries {
+        match entry {
+            Ok(entry) => {
+                if entry.file_type().is_file() && is_scannable_document(entry.path()) {
+                    walk.files.push(entry.into_path());
+                }
+            }
+            Err(e) => {
+                let path = e.path().unwrap_or(root).to_path_buf();
+                walk.unreadable.push((path, e.to_string()));
+            }
+        }
+    }
+    walk.hidden = hidden;
+    walk.files.sort();
+    walk
+}
+
+/// Fingerprint a file unless its size and mtime match the indexed record
+fn fingerprint_if_changed(
+    path: &Path,
+    existing: Option<&LibraryDocument>,
+) -> Result<Option<FileFingerprint>, AppError> {
+    if let Some(doc) = existing {
+        let metadata = fs::metadata(path)?;
+        let modified_at = file_modified_at(&metadata);
+        if modified_at.is_some()
+            && doc.file_modified_at == modified_at
+            && doc.file_size == metadata.len()
+        {
+            return Ok(None);
+        }
+    }
+    fingerprint_file(path).map(Some)
+}
+
+/// Point an existing record at a new path (file moved or renamed)
+fn relocate_document(
+    conn: &Connection,
+    id: &str,
+    path: &str,
+    fingerprint: &FileFingerprint,
+    now: i64,
+) -> Result<(), AppError> {
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+    conn.execute(
+        "UPDATE documents SET path = ?2, file_name = ?3, file_size = ?4,
+                file_modified_at = ?5, updated_at = ?6
+         WHERE id = ?1",
+        params![
+            id,
+            path,
+            file_name,
+            fingerprint.size as i64,
+            fingerprint.modified_at,
+            now
+        ],
+    )?;
+    Ok(())
+}
+
+/// Record a scanned folder
+fn touch_library_folder(conn: &Connection, path: &str, now: i64) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO library_folders (path, added_at, last_scanned_at) VALUES (?1, ?2, ?2)
+         ON CONFLICT(path) DO UPDATE SET last_scanned_at = excluded.last_scanned_at",
+        params![path, now],
+    )?;
+    Ok(())
+}
+
+/// List registered library folders
+pub fn list_library_folders(conn: &Connection) -> Result<Vec<LibraryFolder>, AppError> {
+    let mut stmt =
+        conn.prepare("SELECT path, added_at, last_scanned_at FROM library_folders ORDER BY path")?;
+    let folders = stmt
+        .query_map([], |row| {
+            Ok(LibraryFolder {
+                path: row.get(0)?,
+                added_at: row.get(1)?,
+                last_scanned_at: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(folders)
+}
+
+/// Scan a folder and apply new/changed/moved/removed documents to the library
+///
+/// The database lock is only held while reading the current snapshot and while
+/// applying the changes, so other library commands stay responsive during hashing.
+pub fn scan_folder<F>(
+    state: &LibraryState,
+    root: &Path,
+    now: i64,
+    on_progress: F,
+) -> Result<LibraryScanResult, AppError>
+where
+    F: FnMut(LibraryScanProgress),
+{
+    let walk = collect_document_files(root);
+    apply_walk(state, root, &walk, now, on_progress)
+}
+
+/// Apply the result of a folder walk to the library
+///
+/// Indexed documents the walk did not see are only removed when they are gone
+/// from disk, lie outside hidden folders, and every folder could be read.
+fn apply_walk<F>(
+    state: &LibraryState,
+    root: &Path,
+    walk: &DocumentWalk,
+    now: i64,
+    mut on_progress: F,
+) -> Result<LibraryScanResult, AppError>
+where
+    F: FnMut(LibraryScanProgress),
+{
+    let root_str = root.to_string_lossy().to_string();
+    let files = &walk.files;
+    let total = files.len();
+
+    let existing: HashMap<String, LibraryDocument> = {
+        let conn = lock_library(state)?;
+        list_documents(&conn, &LibraryListQuery::default())?
+            .into_iter()
+            .filter(|doc| Path::new(&doc.path).starts_with(root))
+            .map(|doc| (doc.path.clone(), doc))
+            .collect()
+    };
+
+    let mut result = LibraryScanResult {
+        root: root_str.clone(),
+        errors: walk
+            .unreadable
+            .iter()
+            .map(|(path, e)| format!("{}: {}", path.display(), e))
+            .collect(),
+        ..Default::default()
+    };
+    let mut changes: Vec<(LibraryDocumentInput, FileFingerprint)> = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (index, file) in files.iter().enumerate() {
+        let path = file.to_string_lossy().to_string();
+        match fingerprint_if_changed(file, existing.get(&path)) {
+            Ok(Some(fingerprint)) => {
+                let mut input = LibraryDocumentInput {
+                    path: path.clone(),
+                    ..Default::default()
+                };
+                if !existing.contains_key(&path) {
+                    enrich_document_input(&mut input);
+                }
+                changes.push((input, fingerprint));
+            }
+            Ok(None) => result.unchanged_count += 1,
+            Err(e) => result.errors.push(format!("{}: {}", path, e)),
+        }
+        seen.insert(path.clone());
+
+        let processed = index + 1;
+        if processed % PROGRESS_INTERVAL == 0 || processed == total {
+            on_progress(LibraryScanProgress {
+                root: root_str.clone(),
+                processed,
+                total,
+                current_path: Some(path),
+            });
+        }
+    }
+
+    let missing: Vec<&LibraryDocument> = existing
+        .values()
+        .filter(|doc| {
+            !seen.contains(&doc.path)
+                && !walk.was_skipped(Path::new(&doc.path))
+                && !Path::new(&doc.path).exists()
+        })
+        .collect();
+    let mut relocated: HashSet<String> = HashSet::new();
+
+    let mut conn = lock_library(state)?;
+    let tx = conn.transaction()?;
+
+    for (input, fingerprint) in changes {
+        let path = input.path.clone();
+        if let Some(doc) = existing.get(&path) {
+            upsert_document(&tx, &input, &fingerprint, now)?;
+            if doc.hash == fingerprint.hash {
+                result.unchanged_count += 1;
+            } else {
+                result.updated.push(path);
+            }
+        } else if let Some(doc) = missing
+            .iter()
+            .find(|doc| doc.hash == fingerprint.hash && !relocated.contains(&doc.id))
+        {
+            relocate_document(&tx, &doc.id, &path, &fingerprint, now)?;
+            relocated.insert(doc.id.clone());
+            result.moved.push(path);
+        } else {
+            upsert_document(&tx, &input, &fingerprint, now)?;
+            result.added.push(path);
+        }
+    }
+
+    if !walk.unreadable.is_empty() {
+        log::warn!(
+            "Library scan of {} could not read {} entries; keeping missing documents",
+            root_str,
+            walk.unreadable.len()
+        );
+    }
+    for doc in missing {
+        if walk.unreadable.is_empty() && !relocated.contains(&doc.id) {
+            tx.execute("DELETE FROM documents WHERE id = ?1", params![doc.id])?;
+            result.removed.push(doc.path.clone());
+        }
+    }
+
+    touch_library_folder(&tx, &root_str, now)?;
+    tx.commit()?;
+
+    log::info!(
+        "Library scan of {}: {} added, {} updated, {} moved, {} removed",
+        root_str,
+        result.added.len(),
+        result.updated.len(),
+        result.moved.len(),
+        result.removed.len()
+    );
+    Ok(result)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Scan a folder for EPUB/PDF files and incrementally update the library
+#[tauri::command]
+pub async fn library_scan_folder(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, LibraryState>,
+    path: String,
+) -> Result<LibraryScanResult, AppError> {
+    let root = PathBuf::from(&path);
+    if !root.is_dir() {
+        return Err(AppError::NotFound(format!("Folder not found: {}", path)));
+    }
+
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let now = chrono::Utc::now().timestamp();
+        scan_folder(&state, &root, now, |progress| {
+            emit_event(&app, &progress);
+        })
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Library scan task failed: {}", e)))?
+}
+
+/// List folders registered for library scanning
+#[tauri::command]
+pub fn library_list_folders(
+    state: tauri::State<'_, LibraryState>,
+) -> Result<Vec<LibraryFolder>, AppError> {
+    let conn = lock_library(&state)?;
+    list_library_folders(&conn)
+}
+
+/// Unregister a library folder, optionally removing its documents from the library
+#[tauri::command]
+pub fn library_remove_folder(
+    state: tauri::State<'_, LibraryState>,
+    path: String,
+    remove_documents: Option<bool>,
+) -> Result<(), AppError> {
+    let mut conn = lock_library(&state)?;
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM library_folders WHERE path = ?1", params![path])?;
+
+    if remove_documents.unwrap_or(false) {
+        let root = Path::new(&path);
+        let ids: Vec<String> = list_documents(&tx, &LibraryListQuery::default())?
+            .into_iter()
+            .filter(|doc| Path::new(&doc.path).starts_with(root))
+            .map(|doc| doc.id)
+            .collect();
+        for id in ids {
+            tx.execute("DELETE FROM documents WHERE id = ?1", params![id])?;
+        }
+    }
+
+    tx.commit()?;
+    log::info!("Library folder removed: {}", path);
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::storage::LIBRARY_MIGRATIONS;
+    use super::*;
+    use crate::db;
+    use std::sync::{Arc, Mutex};
+    use tempfile::tempdir;
+
+    fn library() -> LibraryState {
+        Arc::new(Mutex::new(db::open_in_memory(LIBRARY_MIGRATIONS).unwrap()))
+    }
+
+    #[test]
+    fn collect_document_files_filters_extensions_and_hidden_entries() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("sub")).unwrap();
+        fs::create_dir_all(dir.path().join(".cache")).unwrap();
+        fs::write(dir.path().join("a.PDF"), "a").unwrap();
+        fs::write(dir.path().join("sub/b.epub"), "b").unwrap();
+        fs::write(dir.path().join("notes.txt"), "c").unwrap();
+        fs::write(dir.path().join(".cache/d.pdf"), "d").unwrap();
+
+        let walk = collect_document_files(dir.path());
+
+        assert_eq!(walk.files.len(), 2);
+        assert!(walk.files.iter().all(|f| is_scannable_document(f)));
+        assert_eq!(walk.hidden, vec![dir.path().join(".cache")]);
+    }
+
+    #[test]
+    fn scan_folder_tracks_added_updated_moved_and_removed_files() {
+        let dir = tempdir().unwrap();
+        let state = library();
+        fs::write(dir.path().join("a.pdf"), "first").unwrap();
+        fs::write(dir.path().join("b.epub"), "second").unwrap();
+
+        let mut events = 0;
+        let first = scan_folder(&state, dir.path(), 1, |_| events += 1).unwrap();
+        assert_eq!(first.added.len(), 2);
+        assert_eq!(events, 1);
+
+        let rescan = scan_folder(&state, dir.path(), 2, |_| {}).unwrap();
+        assert!(rescan.added.is_empty());
+        assert_eq!(rescan.unchanged_count, 2);
+
+        let original_id = {
+            let conn = state.lock().unwrap();
+            list_documents(&conn, &LibraryListQuery::default())
+                .unwrap()
+                .into_iter()
+                .find(|d| d.file_name == "b.epub")
+                .unwrap()
+                .id
+        };
+
+        fs::write(dir.path().join("a.pdf"), "first, but longer").unwrap();
+        fs::rename(dir.path().join("b.epub"), dir.path().join("c.epub")).unwrap();
+
+        let changed = scan_folder(&state, dir.path(), 3, |_| {}).unwrap();
+        assert_eq!(changed.updated.len(), 1);
+        assert_eq!(changed.moved.len(), 1);
+        assert!(changed.removed.is_empty());
+
+        let conn = state.lock().unwrap();
+        let moved = list_documents(&conn, &LibraryListQuery::default())
+            .unwrap()
+            .into_iter()
+            .find(|d| d.file_name == "c.epub")
+            .unwrap();
+        assert_eq!(moved.id, original_id);
+        drop(conn);
+
+        fs::remove_file(dir.path().join("a.pdf")).unwrap();
+        let removed = scan_folder(&state, dir.path(), 4, |_| {}).unwrap();
+        assert_eq!(removed.removed.len(), 1);
+
+        let conn = state.lock().unwrap();
+        let folders = list_library_folders(&conn).unwrap();
+        assert_eq!(folders.len(), 1);
+        assert_eq!(folders[0].last_scanned_at, Some(4));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_and_hidden_folders_keep_their_documents() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let state = library();
+        let locked = dir.path().join("locked");
+        fs::create_dir_all(&locked).unwrap();
+        fs::create_dir_all(dir.path().join(".hidden")).unwrap();
+        fs::write(locked.join("a.pdf"), "first").unwrap();
+        fs::write(dir.path().join("gone.pdf"), "second").unwrap();
+        scan_folder(&state, dir.path(), 1, |_| {}).unwrap();
+
+        // Added by hand, so only the hidden-folder rule keeps it
+        let by_hand = dir.path().join(".hidden/b.epub");
+        fs::write(&by_hand, "third").unwrap();
+        {
+            let conn = state.lock().unwrap();
+            let input = LibraryDocumentInput {
+                path: by_hand.to_string_lossy().to_string(),
+                ..Default::default()
+            };
+            upsert_document(&conn, &input, &fingerprint_file(&by_hand).unwrap(), 1).unwrap();
+        }
+
+        fs::remove_file(dir.path().join("gone.pdf")).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        let mut walk = collect_document_files(dir.path());
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        if walk.unreadable.is_empty() {
+            // Permissions are not enforced for root; record the failure by hand
+            walk.files.retain(|file| !file.starts_with(&locked));
+            walk.unreadable
+                .push((locked.clone(), "Permission denied".to_string()));
+        }
+        fs::remove_file(locked.join("a.pdf")).unwrap();
+
+        let result = apply_walk(&state, dir.path(), &walk, 2, |_| {}).unwrap();
+
+        assert!(result.removed.is_empty());
+        assert_eq!(result.errors.len(), 1);
+        let conn = state.lock().unwrap();
+        assert_eq!(
+            list_documents(&conn, &LibraryListQuery::default())
+                .unwrap()
+                .len(),
+            3
+        );
+        drop(conn);
+
+        let walk = collect_document_files(dir.path());
+        let result = apply_walk(&state, dir.path(), &walk, 3, |_| {}).unwrap();
+        let mut removed = result.removed;
+        removed.sort();
+        assert_eq!(
+            removed,
+            vec![
+                dir.path().join("gone.pdf").to_string_lossy().to_string(),
+                locked.join("a.pdf").to_string_lossy().to_string(),
+            ]
+        );
+    }
+}
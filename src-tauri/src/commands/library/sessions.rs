@@ -0,0 +1,364 @@
+//! Reading session tracking and statistics
+
+use super::documents::get_document;
+use super::storage::lock_library;
+use super::types::{
+    DailyReadingStats, DocumentReadingStats, LibraryState, ReadingSession, ReadingStats,
+    ReadingStatsQuery,
+};
+use crate::error::AppError;
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use uuid::Uuid;
+
+/// Upper bound for a single session, so a session left open (e.g. the app was
+/// killed) does not count as days of reading
+const MAX_SESSION_SECONDS: i64 = 4 * 60 * 60;
+
+const SESSION_COLUMNS: &str = "id, document_id, started_at, ended_at, duration_seconds, \
+                               start_locator, end_locator, pages_read";
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn row_to_session(row: &Row) -> rusqlite::Result<ReadingSession> {
+    Ok(ReadingSession {
+        id: row.get(0)?,
+        document_id: row.get(1)?,
+        started_at: row.get(2)?,
+        ended_at: row.get(3)?,
+        duration_seconds: row.get(4)?,
+        start_locator: row.get(5)?,
+        end_locator: row.get(6)?,
+        pages_read: row.get(7)?,
+    })
+}
+
+/// Get the session that is currently open, if any
+pub fn active_session(conn: &Connection) -> Result<Option<ReadingSession>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM reading_sessions WHERE ended_at IS NULL
+         ORDER BY started_at DESC LIMIT 1",
+        SESSION_COLUMNS
+    );
+    Ok(conn.query_row(&sql, [], row_to_session).optional()?)
+}
+
+/// Close a session, recording its duration and the covered range
+fn finish_session(
+    conn: &Connection,
+    mut session: ReadingSession,
+    end_locator: Option<String>,
+    pages_read: Option<u32>,
+    now: i64,
+) -> Result<ReadingSession, AppError> {
+    session.ended_at = Some(now);
+    session.duration_seconds = (now - session.started_at).clamp(0, MAX_SESSION_SECONDS);
+    session.end_locator = end_locator;
+    session.pages_read = pages_read.unwrap_or(0);
+    conn.execute(
+        "UPDATE reading_sessions SET ended_at = ?2, duration_seconds = ?3,
+                end_locator = ?4, pages_read = ?5
+         WHERE id = ?1",
+        params![
+            session.id,
+            session.ended_at,
+            session.duration_seconds,
+            session.end_locator,
+            session.pages_read
+        ],
+    )?;
+    Ok(session)
+}
+
+/// Start a reading session, closing any session that is still open
+pub fn start_session(
+    conn: &Connection,
+    document_id: &str,
+    locator: Option<String>,
+    now: i64,
+) -> Result<ReadingSession, AppError> {
+    if get_document(conn, document_id)?.is_none() {
+        return Err(AppError::NotFound(format!(
+            "Document '{}' not found",
+            document_id
+        )));
+    }
+    if let Some(previous) = active_session(conn)? {
+        log::debug!("Closing unfinished reading session {}", previous.id);
+        finish_session(conn, previous, None, None, now)?;
+    }
+
+    let session = ReadingSession {
+        id: format!("rs_{}", Uuid::new_v4()),
+        document_id: document_id.to_string(),
+        started_at: now,
+        ended_at: None,
+        duration_seconds: 0,
+        start_locator: locator,
+        end_locator: None,
+        pages_read: 0,
+    };
+    conn.execute(
+        "INSERT INTO reading_sessions (id, document_id, started_at, start_locator)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            session.id,
+            session.document_id,
+            session.started_at,
+            session.start_locator
+        ],
+    )?;
+    Ok(session)
+}
+
+/// End the active session; returns None when no session is open
+pub fn end_session(
+    conn: &Connection,
+    locator: Option<String>,
+    pages_read: Option<u32>,
+    now: i64,
+) -> Result<Option<ReadingSession>, AppError> {
+    match active_session(conn)? {
+        Some(session) => Ok(Some(finish_session(
+            conn, session, locator, pages_read, now,
+        )?)),
+        None => Ok(None),
+    }
+}
+
+/// Compute the current and longest streak of consecutive reading days
+///
+/// `days` must be sorted ascending without duplicates. The current streak only
+/// counts if the last reading day is today or yesterday.
+pub fn compute_streaks(days: &[NaiveDate], today: NaiveDate) -> (u32, u32) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for day in days {
+        run = match previous {
+            Some(prev) if prev.succ_opt() == Some(*day) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(*day);
+    }
+
+    let current = match previous {
+        Some(last) if last == today || last.succ_opt() == Some(today) => run,
+        _ => 0,
+    };
+    (current, longest)
+}
+
+/// Aggregate finished sessions per day and per document
+pub fn reading_stats(
+    conn: &Connection,
+    query: &ReadingStatsQuery,
+    now: i64,
+) -> Result<ReadingStats, AppError> {
+    let offset_minutes = query
+        .utc_offset_minutes
+        .unwrap_or_else(|| chrono::Local::now().offset().local_minus_utc() / 60);
+    let offset = offset_minutes as i64 * 60;
+
+    let mut stmt = conn.prepare(
+        "SELECT date(started_at + ?1, 'unixepoch') AS day, SUM(duration_seconds),
+                COUNT(*), SUM(pages_read)
+         FROM reading_sessions
+         WHERE ended_at IS NOT NULL
+           AND (?2 IS NULL OR started_at >= ?2) AND (?3 IS NULL OR started_at < ?3)
+         GROUP BY day ORDER BY day",
+    )?;
+    let per_day = stmt
+        .query_map(params![offset, query.from, query.to], |row| {
+            Ok(DailyReadingStats {
+                date: row.get(0)?,
+                seconds: row.get(1)?,
+                sessions: row.get(2)?,
+                pages_read: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT s.document_id, d.title, SUM(s.duration_seconds), COUNT(*),
+                SUM(s.pages_read), MAX(COALESCE(s.ended_at, s.started_at)) AS last_read
+         FROM reading_sessions s JOIN documents d ON d.id = s.document_id
+         WHERE s.ended_at IS NOT NULL
+           AND (?1 IS NULL OR s.started_at >= ?1) AND (?2 IS NULL OR s.started_at < ?2)
+         GROUP BY s.document_id ORDER BY SUM(s.duration_seconds) DESC, last_read DESC",
+    )?;
+    let per_document = stmt
+        .query_map(params![query.from, query.to], |row| {
+            Ok(DocumentReadingStats {
+                document_id: row.get(0)?,
+                title: row.get(1)?,
+                seconds: row.get(2)?,
+                sessions: row.get(3)?,
+                pages_read: row.get(4)?,
+                last_read_at: row.get(5)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // Streaks always consider the full history, independent of the range filter
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT date(started_at + ?1, 'unixepoch') AS day FROM reading_sessions
+         WHERE ended_at IS NOT NULL AND duration_seconds > 0 ORDER BY day",
+    )?;
+    let days = stmt
+        .query_map(params![offset], |row| row.get::<_, String>(0))?
+        .filter_map(|day| day.ok())
+        .filter_map(|day| NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok())
+        .collect::<Vec<_>>();
+    let today = chrono::DateTime::from_timestamp(now + offset, 0)
+        .map(|date| date.date_naive())
+        .unwrap_or_default();
+    let (current_streak_days, longest_streak_days) = compute_streaks(&days, today);
+
+    Ok(ReadingStats {
+        total_seconds: per_day.iter().map(|day| day.seconds).sum(),
+        total_sessions: per_day.iter().map(|day| day.sessions).sum(),
+        total_pages_read: per_day.iter().map(|day| day.pages_read).sum(),
+        per_day,
+        per_document,
+        current_streak_days,
+        longest_streak_days,
+    })
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Start tracking a reading session for a document
+#[tauri::command]
+pub fn start_reading_session(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+    locator: Option<String>,
+) -> Result<ReadingSession, AppError> {
+    let conn = lock_library(&state)?;
+    start_session(&conn, &document_id, locator, chrono::Utc::now().timestamp())
+}
+
+/// End the active reading session, recording the final position and pages read
+#[tauri::command]
+pub fn end_reading_session(
+    state: tauri::State<'_, LibraryState>,
+    locator: Option<String>,
+    pages_read: Option<u32>,
+) -> Result<Option<ReadingSession>, AppError> {
+    let conn = lock_library(&state)?;
+    end_session(&conn, locator, pages_read, chrono::Utc::now().timestamp())
+}
+
+/// Get reading time per day and per document, plus reading streaks
+#[tauri::command]
+pub fn get_reading_stats(
+    state: tauri::State<'_, LibraryState>,
+    query: Option<ReadingStatsQuery>,
+) -> Result<ReadingStats, AppError> {
+    let conn = lock_library(&state)?;
+    reading_stats(
+        &conn,
+        &query.unwrap_or_default(),
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::documents::{upsert_document, FileFingerprint};
+    use super::super::storage::LIBRARY_MIGRATIONS;
+    use super::super::types::LibraryDocumentInput;
+    use super::*;
+    use crate::db;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn add_document(conn: &Connection, path: &str) -> String {
+        let input = LibraryDocumentInput {
+            path: path.to_string(),
+            ..Default::default()
+        };
+        let fingerprint = FileFingerprint {
+            hash: path.to_string(),
+            size: 1,
+            modified_at: None,
+        };
+        upsert_document(conn, &input, &fingerprint, 1).unwrap().id
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn compute_streaks_requires_recent_activity_for_current_streak() {
+        let days = [
+            date("2024-03-01"),
+            date("2024-03-02"),
+            date("2024-03-03"),
+            date("2024-03-10"),
+            date("2024-03-11"),
+        ];
+
+        assert_eq!(compute_streaks(&days, date("2024-03-12")), (2, 3));
+        assert_eq!(compute_streaks(&days, date("2024-03-13")), (0, 3));
+        assert_eq!(compute_streaks(&[], date("2024-03-13")), (0, 0));
+    }
+
+    #[test]
+    fn sessions_are_closed_and_aggregated() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let a = add_document(&conn, "/books/a.pdf");
+        let b = add_document(&conn, "/books/b.pdf");
+        let day1 = 19_000 * DAY;
+
+        start_session(&conn, &a, Some("1".to_string()), day1 + 100).unwrap();
+        let ended = end_session(&conn, Some("5".to_string()), Some(4), day1 + 700)
+            .unwrap()
+            .unwrap();
+        assert_eq!(ended.duration_seconds, 600);
+        assert!(end_session(&conn, None, None, day1 + 800)
+            .unwrap()
+            .is_none());
+
+        // Starting a new session closes the one left open
+        start_session(&conn, &b, None, day1 + DAY).unwrap();
+        start_session(&conn, &a, None, day1 + DAY + 300).unwrap();
+        end_session(&conn, None, Some(2), day1 + DAY + 100_000).unwrap();
+        assert!(active_session(&conn).unwrap().is_none());
+
+        let query = ReadingStatsQuery {
+            utc_offset_minutes: Some(0),
+            ..Default::default()
+        };
+        let stats = reading_stats(&conn, &query, day1 + DAY + 200_000).unwrap();
+
+        assert_eq!(stats.total_sessions, 3);
+        assert_eq!(stats.total_seconds, 600 + 300 + MAX_SESSION_SECONDS);
+        assert_eq!(stats.per_day.len(), 2);
+        assert_eq!(stats.per_day[0].pages_read, 4);
+        assert_eq!(stats.per_document[0].document_id, a);
+        assert_eq!(stats.per_document[0].sessions, 2);
+        assert_eq!(stats.longest_streak_days, 2);
+        assert_eq!(stats.current_streak_days, 0);
+
+        let recent = ReadingStatsQuery {
+            from: Some(day1 + DAY),
+            utc_offset_minutes: Some(0),
+            ..Default::default()
+        };
+        let stats = reading_stats(&conn, &recent, day1 + DAY + 10).unwrap();
+        assert_eq!(stats.total_sessions, 2);
+        assert_eq!(stats.current_streak_days, 2);
+    }
+}
@@ -0,0 +1,212 @@
+//! Library database schema and connection management
+
+use super::types::LibraryState;
+use crate::commands::migrations::open_store_database;
+use crate::error::AppError;
+use rusqlite::Connection;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tauri::Manager;
+
+/// Library database file name in the app data directory
+pub const LIBRARY_DB_FILE: &str = "library.db";
+
+/// Library schema migrations, applied in order (append only)
+pub const LIBRARY_MIGRATIONS: &[&str] = &[
+    // v1: documents
+    "CREATE TABLE documents (
+        id TEXT PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        file_name TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        format TEXT NOT NULL,
+        file_size INTEGER NOT NULL DEFAULT 0,
+        title TEXT,
+        author TEXT,
+        metadata TEXT NOT NULL DEFAULT '{}',
+        added_at INTEGER NOT NULL,
+        opened_at INTEGER,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_documents_hash ON documents(hash);
+    CREATE INDEX idx_documents_format ON documents(format);",
+    // v2: incremental folder scanning
+    "ALTER TABLE documents ADD COLUMN file_modified_at INTEGER;
+    CREATE TABLE library_folders (
+        path TEXT PRIMARY KEY,
+        added_at INTEGER NOT NULL,
+        last_scanned_at INTEGER
+    );",
+    // v3: bookmarks
+    "CREATE TABLE bookmarks (
+        id TEXT PRIMARY KEY,
+        document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+        locator TEXT NOT NULL,
+        label TEXT,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_bookmarks_document ON bookmarks(document_id);",
+    // v4: reading progress
+    "CREATE TABLE reading_progress (
+        document_id TEXT PRIMARY KEY REFERENCES documents(id) ON DELETE CASCADE,
+        locator TEXT NOT NULL,
+        percent REAL NOT NULL DEFAULT 0,
+        last_read_at INTEGER NOT NULL
+    );",
+    // v5: reading sessions
+    "CREATE TABLE reading_sessions (
+        id TEXT PRIMARY KEY,
+        document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER,
+        duration_seconds INTEGER NOT NULL DEFAULT 0,
+        start_locator TEXT,
+        end_locator TEXT,
+        pages_read INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX idx_reading_sessions_document ON reading_sessions(document_id);
+    CREATE INDEX idx_reading_sessions_started ON reading_sessions(started_at);",
+    // v6: collections and tags
+    "CREATE TABLE collections (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        kind TEXT NOT NULL DEFAULT 'collection',
+        description TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE UNIQUE INDEX idx_collections_kind_name ON collections(kind, name COLLATE NOCASE);
+    CREATE TABLE collection_documents (
+        collection_id TEXT NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+        document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+        added_at INTEGER NOT NULL,
+        PRIMARY KEY (collection_id, document_id)
+    );
+    CREATE INDEX idx_collection_documents_document ON collection_documents(document_id);",
+    // v7: online metadata lookup cache
+    "CREATE TABLE metadata_lookup_cache (
+        key TEXT PRIMARY KEY,
+        results TEXT NOT NULL,
+        fetched_at INTEGER NOT NULL
+    );",
+    // v8: RSS/Atom feeds
+    "CREATE TABLE feeds (
+        id TEXT PRIMARY KEY,
+        url TEXT NOT NULL UNIQUE,
+        title TEXT NOT NULL,
+        site_url TEXT,
+        description TEXT,
+        refresh_interval_minutes INTEGER NOT NULL DEFAULT 60,
+        etag TEXT,
+        last_modified TEXT,
+        last_fetched_at INTEGER,
+        last_error TEXT,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE feed_entries (
+        id TEXT PRIMARY KEY,
+        feed_id TEXT NOT NULL REFERENCES feeds(id) ON DELETE CASCADE,
+        guid TEXT NOT NULL,
+        title TEXT,
+        url TEXT,
+        author TEXT,
+        summary TEXT,
+        content TEXT,
+        published_at INTEGER,
+        fetched_at INTEGER NOT NULL,
+        last_seen_at INTEGER NOT NULL,
+        read_at INTEGER,
+        UNIQUE (feed_id, guid)
+    );
+    CREATE INDEX idx_feed_entries_unread ON feed_entries(feed_id, read_at);
+    CREATE INDEX idx_feed_entries_published ON feed_entries(published_at);",
+    // v9: embedding index for retrieval over document text
+    "CREATE TABLE rag_indexes (
+        document_id TEXT PRIMARY KEY REFERENCES documents(id) ON DELETE CASCADE,
+        document_hash TEXT NOT NULL,
+        provider TEXT NOT NULL,
+        model TEXT NOT NULL,
+        dimensions INTEGER NOT NULL,
+        chunk_count INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE rag_chunks (
+        document_id TEXT NOT NULL REFERENCES rag_indexes(document_id) ON DELETE CASCADE,
+        chunk_index INTEGER NOT NULL,
+        location_kind TEXT NOT NULL,
+        location INTEGER NOT NULL,
+        location_label TEXT,
+        char_start INTEGER NOT NULL,
+        char_end INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        embedding BLOB NOT NULL,
+        PRIMARY KEY (document_id, chunk_index)
+    );",
+    // v10: AI summary cache (chapter -1 is the whole document)
+    "CREATE TABLE ai_summaries (
+        document_hash TEXT NOT NULL,
+        chapter INTEGER NOT NULL,
+        model TEXT NOT NULL,
+        provider TEXT NOT NULL,
+        summary TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        PRIMARY KEY (document_hash, chapter, model)
+    );",
+    // v11: LAN sync version vectors and conflicts
+    "CREATE TABLE sync_records (
+        record_key TEXT PRIMARY KEY,
+        fingerprint TEXT NOT NULL,
+        version TEXT NOT NULL DEFAULT '{}'
+    );
+    CREATE TABLE sync_conflicts (
+        id TEXT PRIMARY KEY,
+        record_key TEXT NOT NULL UNIQUE,
+        kind TEXT NOT NULL,
+        document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+        record_id TEXT,
+        peer_device_id TEXT NOT NULL,
+        peer_device_name TEXT NOT NULL,
+        local_value TEXT NOT NULL,
+        remote_value TEXT NOT NULL,
+        local_version TEXT NOT NULL,
+        remote_version TEXT NOT NULL,
+        detected_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_sync_conflicts_document ON sync_conflicts(document_id);",
+    // v12: recently opened documents, seeded from documents.opened_at
+    "CREATE TABLE recent_documents (
+        document_id TEXT PRIMARY KEY REFERENCES documents(id) ON DELETE CASCADE,
+        opened_at INTEGER NOT NULL,
+        pinned_at INTEGER
+    );
+    CREATE INDEX idx_recent_documents_opened ON recent_documents(opened_at);
+    INSERT INTO recent_documents (document_id, opened_at)
+        SELECT id, opened_at FROM documents WHERE opened_at IS NOT NULL;",
+];
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Get the library database file path
+pub fn get_library_db_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(data_dir.join(LIBRARY_DB_FILE))
+}
+
+/// Open the library database and wrap it as managed state
+pub fn init_library_state(app: &tauri::AppHandle) -> Result<LibraryState, AppError> {
+    let path = get_library_db_path(app)?;
+    let conn = open_store_database(&path, "library", LIBRARY_MIGRATIONS)?;
+    log::info!("Library database opened: {:?}", path);
+    Ok(Arc::new(Mutex::new(conn)))
+}
+
+/// Lock the library connection
+pub fn lock_library(state: &LibraryState) -> Result<MutexGuard<'_, Connection>, AppError> {
+    state.lock().map_err(|e| AppError::Database(e.to_string()))
+}
@@ -0,0 +1,285 @@
+//! Library type definitions
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+// ============================================================================
+// Core Types
+// ============================================================================
+
+/// A document stored in the library
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryDocument {
+    pub id: String,
+    pub path: String,
+    pub file_name: String,
+    /// SHA-256 of the file content (hex encoded)
+    pub hash: String,
+    pub format: String, // "pdf" | "epub" | other lowercase extension
+    pub file_size: u64,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Free-form metadata (publisher, language, ISBN, ...)
+    pub metadata: serde_json::Value,
+    pub added_at: i64,
+    pub opened_at: Option<i64>,
+    pub updated_at: i64,
+    /// File modification time recorded at the last index (unix seconds)
+    pub file_modified_at: Option<i64>,
+}
+
+/// Document input passed from frontend when adding to the library
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryDocumentInput {
+    pub path: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Metadata fields that can be updated on an existing document
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryDocumentUpdate {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Query options for listing documents
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryListQuery {
+    /// Only return documents of this format
+    pub format: Option<String>,
+    /// Sort key: "added" (default) | "opened" | "title" | "author"
+    pub sort_by: Option<String>,
+    /// Sort ascending instead of descending
+    pub ascending: Option<bool>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    /// Only return documents in this collection or tag
+    pub collection_id: Option<String>,
+}
+
+/// A folder registered for library scanning
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryFolder {
+    pub path: String,
+    pub added_at: i64,
+    pub last_scanned_at: Option<i64>,
+}
+
+/// Scan progress event payload
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryScanProgress {
+    pub root: String,
+    pub processed: usize,
+    pub total: usize,
+    pub current_path: Option<String>,
+}
+
+/// Result of a folder scan
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryScanResult {
+    pub root: String,
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub moved: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged_count: usize,
+    pub errors: Vec<String>,
+}
+
+/// A bookmark within a library document
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryBookmark {
+    pub id: String,
+    pub document_id: String,
+    /// Reader-specific position (PDF page number, EPUB CFI, ...)
+    pub locator: String,
+    pub label: Option<String>,
+    pub created_at: i64,
+}
+
+/// Bookmark input passed from frontend
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryBookmarkInput {
+    pub document_id: String,
+    pub locator: String,
+    pub label: Option<String>,
+}
+
+/// Bookmarks of one document, grouped for export
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentBookmarks {
+    pub document_id: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub path: String,
+    pub bookmarks: Vec<LibraryBookmark>,
+}
+
+/// Last reading position within a document
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingProgress {
+    pub document_id: String,
+    /// Reader-specific position (PDF page number, EPUB CFI, ...)
+    pub locator: String,
+    /// Percentage read, 0-100
+    pub percent: f64,
+    pub last_read_at: i64,
+}
+
+/// An entry of the recently opened list
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentDocument {
+    pub document: LibraryDocument,
+    pub opened_at: i64,
+    /// When the entry was pinned; pinned entries are listed first
+    pub pinned_at: Option<i64>,
+}
+
+/// A period of reading one document
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingSession {
+    pub id: String,
+    pub document_id: String,
+    pub started_at: i64,
+    /// None while the session is active
+    pub ended_at: Option<i64>,
+    pub duration_seconds: i64,
+    pub start_locator: Option<String>,
+    pub end_locator: Option<String>,
+    pub pages_read: u32,
+}
+
+/// Options for reading statistics queries
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingStatsQuery {
+    /// Only include sessions started at or after this time (unix seconds)
+    pub from: Option<i64>,
+    /// Only include sessions started before this time (unix seconds)
+    pub to: Option<i64>,
+    /// Offset from UTC used to group sessions into days (defaults to the system timezone)
+    pub utc_offset_minutes: Option<i32>,
+}
+
+/// Reading time on one day
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyReadingStats {
+    /// Local date, "YYYY-MM-DD"
+    pub date: String,
+    pub seconds: i64,
+    pub sessions: u32,
+    pub pages_read: u32,
+}
+
+/// Reading time for one document
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentReadingStats {
+    pub document_id: String,
+    pub title: Option<String>,
+    pub seconds: i64,
+    pub sessions: u32,
+    pub pages_read: u32,
+    pub last_read_at: i64,
+}
+
+/// Aggregated reading statistics for the dashboard
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingStats {
+    pub total_seconds: i64,
+    pub total_sessions: u32,
+    pub total_pages_read: u32,
+    pub per_day: Vec<DailyReadingStats>,
+    pub per_document: Vec<DocumentReadingStats>,
+    /// Consecutive days with reading, ending today or yesterday
+    pub current_streak_days: u32,
+    pub longest_streak_days: u32,
+}
+
+/// A named group of documents: a curated collection or a tag
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryCollection {
+    pub id: String,
+    pub name: String,
+    pub kind: String, // "collection" | "tag"
+    pub description: Option<String>,
+    pub document_count: u32,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Collection input passed from frontend when creating or updating
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryCollectionInput {
+    pub name: String,
+    /// "collection" (default) or "tag"; ignored on update
+    pub kind: Option<String>,
+    pub description: Option<String>,
+}
+
+/// A set of library documents that appear to be the same book
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryDuplicateGroup {
+    /// How the documents were matched: "hash" | "isbn" | "title"
+    pub matched_by: Vec<String>,
+    /// Recommended document to keep, also first in `documents`
+    pub keep_id: String,
+    pub documents: Vec<LibraryDocument>,
+}
+
+/// Online metadata lookup query: an ISBN, or a title with optional author
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BookMetadataQuery {
+    pub isbn: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+/// Book metadata returned by an online catalogue
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BookMetadataCandidate {
+    pub source: String, // "openlibrary" | "googlebooks"
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    pub publisher: Option<String>,
+    pub published_date: Option<String>,
+    pub description: Option<String>,
+    pub isbn13: Option<String>,
+    pub isbn10: Option<String>,
+    pub page_count: Option<u32>,
+    pub subjects: Vec<String>,
+    pub language: Option<String>,
+    pub cover_url: Option<String>,
+}
+
+// ============================================================================
+// State Types
+// ============================================================================
+
+/// Thread-safe library database connection
+pub type LibraryState = Arc<Mutex<Connection>>;
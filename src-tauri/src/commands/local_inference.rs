@@ -0,0 +1,462 @@
+//! Offline inference with local GGUF models
+//!
+//! Built with the `local-inference` feature. Models are GGUF files in the
+//! `models` directory of the app data directory. They are served by
+//! llama.cpp's `llama-server`, taken from `models/bin` when present and from
+//! PATH otherwise, started on demand for the model a request names and kept
+//! running until another model is asked for or the app exits. The server
+//! speaks the OpenAI chat format, so `proxy_ai_request` treats the `local`
+//! provider as one more OpenAI-compatible provider, without an API key.
+
+use crate::commands::ai_proxy::ai_http_client;
+use crate::commands::events::emit_event;
+use crate::commands::library::file_modified_at;
+use crate::commands::retry::with_retry;
+use crate::error::AppError;
+use serde::Serialize;
+use std::fs;
+use std::io::Read;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tauri::Manager;
+use tokio::io::AsyncWriteExt;
+
+/// Provider name of local models in AI requests
+pub const LOCAL_PROVIDER: &str = "local";
+
+/// Event emitted with a `LocalModelDownloadProgress` while a model downloads
+pub const LOCAL_MODEL_DOWNLOAD_EVENT: &str = "local-model-download";
+
+/// Directory of the app data directory holding the models
+const MODELS_DIR: &str = "models";
+
+/// llama.cpp server binary
+const LLAMA_SERVER: &str = "llama-server";
+
+/// How long loading a model may take before the server is given up on
+const LOCAL_SERVER_START_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Bytes downloaded between progress events, at least
+const DOWNLOAD_PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// Models directory, set at startup
+static LOCAL_MODELS_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// The running server, if a model is loaded
+static LOCAL_SERVER: tokio::sync::Mutex<Option<LocalServer>> = tokio::sync::Mutex::const_new(None);
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// A downloaded model
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalModel {
+    /// File name, used as the model of `local` requests
+    pub name: String,
+    pub size: u64,
+    pub modified_at: Option<i64>,
+    /// Currently loaded by the server
+    pub loaded: bool,
+}
+
+/// Payload of `local-model-download`
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalModelDownloadProgress {
+    pub name: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+struct LocalServer {
+    model: String,
+    port: u16,
+    process: Child,
+}
+
+impl LocalServer {
+    fn chat_endpoint(&self) -> String {
+        format!("http://127.0.0.1:{}/v1/chat/completions", self.port)
+    }
+
+    fn stop(mut self) {
+        if let Err(e) = self.process.kill() {
+            log::warn!("Failed to stop llama-server: {}", e);
+        }
+        let _ = self.process.wait();
+        log::info!("Unloaded local model {}", self.model);
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Models directory in the app data directory
+pub fn get_models_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(data_dir.join(MODELS_DIR))
+}
+
+/// Remember where models are, for requests made without an app handle
+pub fn init_local_models(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let dir = get_models_dir(app)?;
+    fs::create_dir_all(&dir)?;
+    let _ = LOCAL_MODELS_DIR.set(dir);
+    Ok(())
+}
+
+fn models_dir() -> Result<&'static PathBuf, AppError> {
+    LOCAL_MODELS_DIR
+        .get()
+        .ok_or_else(|| AppError::Internal("Local models are not initialized".to_string()))
+}
+
+/// Check a model name is a plain `.gguf` file name
+pub fn validate_model_name(name: &str) -> Result<(), AppError> {
+    let plain = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\'])
+        && name.to_lowercase().ends_with(".gguf");
+    if !plain {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid model name: {} (expected a .gguf file name)",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Model name for a download URL: its last path segment
+pub fn model_name_from_url(url: &url::Url) -> Option<String> {
+    url.path_segments()?
+        .next_back()
+        .filter(|name| validate_model_name(name).is_ok())
+        .map(|name| name.to_string())
+}
+
+/// Whether a file starts with the GGUF magic
+pub fn is_gguf(path: &Path) -> Result<bool, AppError> {
+    let mut magic = [0u8; 4];
+    let mut file = fs::File::open(path)?;
+    Ok(file.read_exact(&mut magic).is_ok() && &magic == b"GGUF")
+}
+
+/// Models in a directory, marking the loaded one
+pub fn list_models_in(dir: &Path, loaded: Option<&str>) -> Result<Vec<LocalModel>, AppError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut models = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() || validate_model_name(&name).is_err() {
+            continue;
+        }
+        models.push(LocalModel {
+            loaded: loaded == Some(name.as_str()),
+            size: metadata.len(),
+            modified_at: file_modified_at(&metadata),
+            name,
+        });
+    }
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(models)
+}
+
+/// `llama-server` from `models/bin`, else from PATH
+fn llama_server_command(dir: &Path) -> PathBuf {
+    let bundled = dir
+        .join("bin")
+        .join(format!("{}{}", LLAMA_SERVER, std::env::consts::EXE_SUFFIX));
+    if bundled.is_file() {
+        bundled
+    } else {
+        PathBuf::from(LLAMA_SERVER)
+    }
+}
+
+fn free_port() -> Result<u16, AppError> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+/// Start `llama-server` for a model and wait until it has loaded
+async fn start_server(dir: &Path, model: &str) -> Result<LocalServer, AppError> {
+    let path = dir.join(model);
+    if !path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "Local model {} not found",
+            model
+        )));
+    }
+    let port = free_port()?;
+    let command = llama_server_command(dir);
+    let process = Command::new(&command)
+        .arg("--model")
+        .arg(&path)
+        .args(["--alias", model, "--host", "127.0.0.1"])
+        .args(["--port", &port.to_string()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::NotFound(format!(
+                "{} was not found. Install llama.cpp and put {} on PATH or in {}",
+                LLAMA_SERVER,
+                LLAMA_SERVER,
+                dir.join("bin").display()
+            )),
+            _ => AppError::Io(e),
+        })?;
+    let mut server = LocalServer {
+        model: model.to_string(),
+        port,
+        process,
+    };
+    log::info!("Loading local model {} on port {}", model, port);
+
+    let health = format!("http://127.0.0.1:{}/health", port);
+    let started = Instant::now();
+    loop {
+        if let Some(status) = server.process.try_wait()? {
+            return Err(AppError::Internal(format!(
+                "{} exited with {} while loading {}",
+                LLAMA_SERVER, status, model
+            )));
+        }
+        let ready = ai_http_client()
+            .get(&health)
+            .timeout(Duration::from_secs(2))
+            .send()
+            .await
+            .is_ok_and(|response| response.status().is_success());
+        if ready {
+            log::info!("Loaded local model {} in {:?}", model, started.elapsed());
+            return Ok(server);
+        }
+        if started.elapsed() > LOCAL_SERVER_START_TIMEOUT {
+            server.stop();
+            return Err(AppError::Internal(format!(
+                "Loading {} took longer than {} seconds",
+                model,
+                LOCAL_SERVER_START_TIMEOUT.as_secs()
+            )));
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+/// Chat endpoint serving a model, loading it first if another one (or none)
+/// is loaded
+pub async fn local_chat_endpoint(model: &str) -> Result<String, AppError> {
+    validate_model_name(model)?;
+    let mut server = LOCAL_SERVER.lock().await;
+    if let Some(running) = server.as_mut() {
+        if running.model == model && running.process.try_wait()?.is_none() {
+            return Ok(running.chat_endpoint());
+        }
+    }
+    if let Some(previous) = server.take() {
+        previous.stop();
+    }
+    let started = start_server(models_dir()?, model).await?;
+    let endpoint = started.chat_endpoint();
+    *server = Some(started);
+    Ok(endpoint)
+}
+
+/// Stop the server when the app exits
+pub fn stop_local_server_on_exit() {
+    match LOCAL_SERVER.try_lock() {
+        Ok(mut server) => {
+            if let Some(server) = server.take() {
+                server.stop();
+            }
+        }
+        Err(_) => log::warn!("A local model was loading at exit"),
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// List the downloaded local models
+#[tauri::command]
+pub async fn list_local_models(app: tauri::AppHandle) -> Result<Vec<LocalModel>, AppError> {
+    let server = LOCAL_SERVER.lock().await;
+    let loaded = server.as_ref().map(|server| server.model.as_str());
+    list_models_in(&get_models_dir(&app)?, loaded)
+}
+
+/// Download a GGUF model; the name defaults to the last segment of the URL
+#[tauri::command]
+pub async fn download_local_model(
+    app: tauri::AppHandle,
+    url: String,
+    name: Option<String>,
+) -> Result<LocalModel, AppError> {
+    let parsed =
+        url::Url::parse(&url).map_err(|e| AppError::InvalidInput(format!("Invalid URL: {}", e)))?;
+    let name = match name {
+        Some(name) => name,
+        None => model_name_from_url(&parsed).ok_or_else(|| {
+            AppError::InvalidInput("The URL does not name a .gguf file".to_string())
+        })?,
+    };
+    validate_model_name(&name)?;
+    let dir = get_models_dir(&app)?;
+    let path = dir.join(&name);
+    if path.exists() {
+        return Err(AppError::InvalidInput(format!(
+            "Local model {} already exists",
+            name
+        )));
+    }
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let mut response = with_retry("Model download", || async {
+        let response = ai_http_client()
+            .get(parsed.clone())
+            .timeout(Duration::from_secs(24 * 60 * 60))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(AppError::http_status(
+                response.status(),
+                format!(
+                    "Downloading {} failed with status {}",
+                    url,
+                    response.status()
+                ),
+            ));
+        }
+        Ok(response)
+    })
+    .await?;
+    let mut progress = LocalModelDownloadProgress {
+        name: name.clone(),
+        downloaded: 0,
+        total: response.content_length(),
+    };
+    let step = progress.total.map_or(DOWNLOAD_PROGRESS_STEP, |total| {
+        (total / 100).max(DOWNLOAD_PROGRESS_STEP)
+    });
+    let partial = dir.join(format!(".{}.part", name));
+    let result = async {
+        let mut file = tokio::fs::File::create(&partial).await?;
+        let mut reported = 0;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+            progress.downloaded += chunk.len() as u64;
+            if progress.downloaded - reported >= step {
+                reported = progress.downloaded;
+                emit_event(&app, &progress);
+            }
+        }
+        file.flush().await?;
+        emit_event(&app, &progress);
+        if !is_gguf(&partial)? {
+            return Err(AppError::InvalidInput(format!(
+                "{} is not a GGUF model",
+                url
+            )));
+        }
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(e);
+    }
+    log::info!(
+        "Downloaded local model {} ({} bytes)",
+        name,
+        progress.downloaded
+    );
+
+    let metadata = fs::metadata(&path)?;
+    Ok(LocalModel {
+        name,
+        size: metadata.len(),
+        modified_at: file_modified_at(&metadata),
+        loaded: false,
+    })
+}
+
+/// Delete a local model, unloading it first if it is loaded
+#[tauri::command]
+pub async fn delete_local_model(app: tauri::AppHandle, name: String) -> Result<(), AppError> {
+    validate_model_name(&name)?;
+    let path = get_models_dir(&app)?.join(&name);
+    if !path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "Local model {} not found",
+            name
+        )));
+    }
+    let mut server = LOCAL_SERVER.lock().await;
+    if server.as_ref().is_some_and(|server| server.model == name) {
+        if let Some(server) = server.take() {
+            server.stop();
+        }
+    }
+    fs::remove_file(&path)?;
+    log::info!("Deleted local model {}", name);
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn models_are_gguf_files_in_the_models_dir() {
+        assert!(validate_model_name("qwen2.5-1.5b-instruct-q4_k_m.gguf").is_ok());
+        assert!(validate_model_name("Model.GGUF").is_ok());
+        assert!(validate_model_name("../escape.gguf").is_err());
+        assert!(validate_model_name(".model.gguf.part").is_err());
+        assert!(validate_model_name("notes.txt").is_err());
+
+        let url = url::Url::parse("https://huggingface.co/x/y/resolve/main/tiny.gguf?download=1")
+            .unwrap();
+        assert_eq!(model_name_from_url(&url).as_deref(), Some("tiny.gguf"));
+        let url = url::Url::parse("https://example.com/models/").unwrap();
+        assert_eq!(model_name_from_url(&url), None);
+
+        let dir = tempdir().unwrap();
+        assert!(list_models_in(&dir.path().join("missing"), None)
+            .unwrap()
+            .is_empty());
+        fs::write(dir.path().join("b.gguf"), b"GGUF\x03\x00\x00\x00").unwrap();
+        fs::write(dir.path().join("a.gguf"), b"<html>").unwrap();
+        fs::write(dir.path().join(".c.gguf.part"), b"GGUF").unwrap();
+        fs::create_dir(dir.path().join("bin")).unwrap();
+        let models = list_models_in(dir.path(), Some("b.gguf")).unwrap();
+        let names: Vec<_> = models.iter().map(|m| (m.name.as_str(), m.loaded)).collect();
+        assert_eq!(names, [("a.gguf", false), ("b.gguf", true)]);
+        assert!(is_gguf(&dir.path().join("b.gguf")).unwrap());
+        assert!(!is_gguf(&dir.path().join("a.gguf")).unwrap());
+        assert_eq!(
+            llama_server_command(dir.path()),
+            PathBuf::from(LLAMA_SERVER)
+        );
+    }
+}
@@ -0,0 +1,693 @@
+//! Persistent file logging with rotation
+//!
+//! Logs always go to a file in the app log directory; debug builds also log
+//! to the console. The file is rotated when it grows past the size limit and
+//! on the first start of a new day, and rotated files beyond the retained
+//! count or older than the retention period are deleted. The limits live in
+//! a settings file read at startup.
+//!
+//! The level is a filter such as `info,mcp=debug`: a default level and
+//! per-module directives. A directive names a module path prefix
+//! (`app_lib::commands::rag`) or a single module (`mcp`, `rmcp`), and the
+//! longest matching one wins. It can be changed while the app runs.
+//!
+//! For the in-app log viewer, recent entries can be read back from the files
+//! and, while a viewer subscribes, new entries are streamed as events.
+
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
+use tauri::{Emitter, Manager};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind, TimezoneStrategy};
+
+/// Name of the log file, without extension
+pub const LOG_FILE_NAME: &str = "sast-readium";
+
+/// Log settings file in the app data directory
+pub const LOG_SETTINGS_FILE: &str = "log_settings.json";
+
+/// Date suffix of rotated files, as the log plugin names them
+const ROTATED_DATE_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// Event emitted with each new `LogEntry` while logs are streamed
+pub const LOG_ENTRY_EVENT: &str = "log-entry";
+
+const DEFAULT_RECENT_LOGS: usize = 500;
+const MAX_RECENT_LOGS: usize = 5000;
+
+/// Whether a log viewer subscribed to new entries
+static LOG_STREAMING: AtomicBool = AtomicBool::new(false);
+/// Queue of entries to stream, emitted from a thread of their own so logging
+/// never waits on the webview
+static LOG_STREAM: OnceLock<Mutex<Sender<LogEntry>>> = OnceLock::new();
+
+/// Filter applied to every log record
+static LOG_FILTER: RwLock<LogFilter> = RwLock::new(LogFilter {
+    default: log::LevelFilter::Info,
+    directives: Vec::new(),
+});
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogSettings {
+    /// Size at which the log file is rotated, in megabytes
+    pub max_file_size_mb: u64,
+    /// Rotated files kept, besides the current one
+    pub retained_files: usize,
+    /// Rotated files older than this are deleted
+    pub retention_days: u32,
+    /// Level filter, e.g. "info" or "warn,mcp=debug"
+    pub level: String,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            max_file_size_mb: 10,
+            retained_files: 10,
+            retention_days: 14,
+            level: DEFAULT_LOG_LEVEL.to_string(),
+        }
+    }
+}
+
+/// A parsed log level filter
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogFilter {
+    pub default: log::LevelFilter,
+    /// Module and level, longest module first
+    pub directives: Vec<(String, log::LevelFilter)>,
+}
+
+impl LogFilter {
+    /// Whether a record from `target` at `level` is logged
+    pub fn enabled(&self, target: &str, level: log::Level) -> bool {
+        let limit = self
+            .directives
+            .iter()
+            .find(|(module, _)| module_matches(module, target))
+            .map_or(self.default, |(_, limit)| *limit);
+        level <= limit
+    }
+
+    /// Most verbose level any record can pass at
+    pub fn max_level(&self) -> log::LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, limit)| *limit)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+/// One log record
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    /// Local time, "2026-01-31 12:00:00"
+    pub timestamp: String,
+    pub level: String,
+    /// Module the record came from
+    pub target: String,
+    pub message: String,
+}
+
+/// Which entries `get_recent_logs` returns
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LogQuery {
+    /// Least severe level included, e.g. "warn"
+    pub level: Option<String>,
+    /// Module, matched like a log level directive
+    pub target: Option<String>,
+    /// Text the message contains, ignoring case
+    pub text: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn get_app_data_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))
+}
+
+/// Get the directory log files are written to
+pub fn get_log_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    app.path()
+        .app_log_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))
+}
+
+pub fn load_log_settings(path: &Path) -> Result<LogSettings, AppError> {
+    if !path.exists() {
+        return Ok(LogSettings::default());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn save_log_settings(path: &Path, settings: &LogSettings) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(settings)?)?;
+    Ok(())
+}
+
+/// Check the settings, returning the parsed level filter
+pub fn validate_log_settings(settings: &LogSettings) -> Result<LogFilter, AppError> {
+    if settings.max_file_size_mb == 0 || settings.retained_files == 0 {
+        return Err(AppError::InvalidInput(
+            "Log file size and retained files must be at least 1".to_string(),
+        ));
+    }
+    parse_log_filter(&settings.level)
+}
+
+/// Whether a directive's module covers a record target
+fn module_matches(module: &str, target: &str) -> bool {
+    target
+        .strip_prefix(module)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        || target.split("::").any(|segment| segment == module)
+}
+
+/// Parse a filter such as `info,mcp=debug`
+pub fn parse_log_filter(spec: &str) -> Result<LogFilter, AppError> {
+    let parse_level = |level: &str| {
+        log::LevelFilter::from_str(level.trim())
+            .map_err(|_| AppError::InvalidInput(format!("Unknown log level: {}", level.trim())))
+    };
+    let mut filter = LogFilter {
+        default: log::LevelFilter::Info,
+        directives: Vec::new(),
+    };
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('=') {
+            Some((module, level)) if !module.trim().is_empty() => {
+                filter
+                    .directives
+                    .push((module.trim().to_string(), parse_level(level)?));
+            }
+            Some(_) => {
+                return Err(AppError::InvalidInput(format!(
+                    "Log directive without a module: {}",
+                    part
+                )))
+            }
+            None => filter.default = parse_level(part)?,
+        }
+    }
+    filter
+        .directives
+        .sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+    Ok(filter)
+}
+
+/// Make `filter` the active log filter
+fn apply_log_filter(filter: LogFilter) {
+    log::set_max_level(filter.max_level());
+    if let Ok(mut active) = LOG_FILTER.write() {
+        *active = filter;
+    }
+}
+
+/// Whether the active filter lets a record through
+fn log_filter_enabled(metadata: &log::Metadata) -> bool {
+    LOG_FILTER.read().map_or(true, |filter| {
+        filter.enabled(metadata.target(), metadata.level())
+    })
+}
+
+/// Rotated log files in `dir`, oldest first
+fn rotated_logs(dir: &Path) -> Vec<PathBuf> {
+    let prefix = format!("{}_", LOG_FILE_NAME);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut logs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy())
+                .is_some_and(|name| {
+                    name.starts_with(&prefix) && (name.ends_with(".log") || name.ends_with(".bak"))
+                })
+        })
+        .collect();
+    // The date suffix sorts chronologically
+    logs.sort();
+    logs
+}
+
+/// Rotate the log file when it was last written on an earlier day
+pub fn rotate_stale_log(dir: &Path, now: chrono::DateTime<chrono::Local>) -> Result<(), AppError> {
+    let current = dir.join(format!("{}.log", LOG_FILE_NAME));
+    let Ok(modified) = fs::metadata(&current).and_then(|m| m.modified()) else {
+        return Ok(());
+    };
+    let modified: chrono::DateTime<chrono::Local> = modified.into();
+    if modified.date_naive() >= now.date_naive() {
+        return Ok(());
+    }
+    let rotated = dir.join(format!(
+        "{}_{}.log",
+        LOG_FILE_NAME,
+        modified.format(ROTATED_DATE_FORMAT)
+    ));
+    if !rotated.exists() {
+        fs::rename(&current, rotated)?;
+    }
+    Ok(())
+}
+
+/// Delete rotated files beyond the retained count or retention period
+pub fn prune_logs(dir: &Path, settings: &LogSettings, now: SystemTime) -> usize {
+    let max_age = Duration::from_secs(u64::from(settings.retention_days) * 24 * 60 * 60);
+    let logs = rotated_logs(dir);
+    let excess = logs.len().saturating_sub(settings.retained_files);
+    let mut removed = 0;
+    for (index, path) in logs.iter().enumerate() {
+        let expired = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > max_age);
+        if (index < excess || expired) && fs::remove_file(path).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Parse a line as written by the log plugin:
+/// `[2026-01-31][12:00:00][target][INFO] message`
+pub fn parse_log_line(line: &str) -> Option<LogEntry> {
+    let mut fields = Vec::with_capacity(4);
+    let mut rest = line;
+    for _ in 0..4 {
+        let (field, after) = rest.strip_prefix('[')?.split_once(']')?;
+        fields.push(field);
+        rest = after;
+    }
+    log::Level::from_str(fields[3]).ok()?;
+    Some(LogEntry {
+        timestamp: format!("{} {}", fields[0], fields[1]),
+        level: fields[3].to_string(),
+        target: fields[2].to_string(),
+        message: rest.strip_prefix(' ').unwrap_or(rest).to_string(),
+    })
+}
+
+/// Entries of a log file; lines that do not start an entry continue the
+/// previous one
+fn read_log_entries(path: &Path) -> Vec<LogEntry> {
+    let Ok(content) = fs::read(path) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<LogEntry> = Vec::new();
+    for line in String::from_utf8_lossy(&content).lines() {
+        match (parse_log_line(line), entries.last_mut()) {
+            (Some(entry), _) => entries.push(entry),
+            (None, Some(last)) => {
+                last.message.push('\n');
+                last.message.push_str(line);
+            }
+            (None, None) => {}
+        }
+    }
+    entries
+}
+
+/// Parse the query into a matcher
+fn log_query_matcher(query: &LogQuery) -> Result<impl Fn(&LogEntry) -> bool, AppError> {
+    let level = match &query.level {
+        Some(level) => log::LevelFilter::from_str(level.trim())
+            .map_err(|_| AppError::InvalidInput(format!("Unknown log level: {}", level)))?,
+        None => log::LevelFilter::Trace,
+    };
+    let target = query.target.clone().filter(|t| !t.trim().is_empty());
+    let text = query
+        .text
+        .as_ref()
+        .map(|text| text.to_lowercase())
+        .filter(|text| !text.is_empty());
+    Ok(move |entry: &LogEntry| {
+        log::Level::from_str(&entry.level).is_ok_and(|l| l <= level)
+            && target
+                .as_deref()
+                .map_or(true, |t| module_matches(t.trim(), &entry.target))
+            && text
+                .as_deref()
+                .map_or(true, |t| entry.message.to_lowercase().contains(t))
+    })
+}
+
+/// The last `limit` matching entries of the logs in `dir`, oldest first
+pub fn recent_log_entries(
+    dir: &Path,
+    query: &LogQuery,
+    limit: usize,
+) -> Result<Vec<LogEntry>, AppError> {
+    let matches = log_query_matcher(query)?;
+    let mut files = rotated_logs(dir);
+    files.push(dir.join(format!("{}.log", LOG_FILE_NAME)));
+    let mut recent: Vec<LogEntry> = Vec::new();
+    // Newest file first, until enough entries are found
+    for file in files.iter().rev() {
+        let mut entries: Vec<LogEntry> = read_log_entries(file)
+            .into_iter()
+            .filter(|entry| matches(entry))
+            .collect();
+        let skip = entries.len().saturating_sub(limit - recent.len());
+        entries.drain(..skip);
+        entries.append(&mut recent);
+        recent = entries;
+        if recent.len() >= limit {
+            break;
+        }
+    }
+    Ok(recent)
+}
+
+/// Queue a formatted record for streaming, while a viewer subscribes
+fn stream_log_record(record: &log::Record) {
+    if !LOG_STREAMING.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(stream) = LOG_STREAM.get() else {
+        return;
+    };
+    let line = record.args().to_string();
+    let entry = parse_log_line(&line).unwrap_or_else(|| LogEntry {
+        timestamp: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        level: record.level().to_string(),
+        target: record.target().to_string(),
+        message: line,
+    });
+    if let Ok(stream) = stream.lock() {
+        let _ = stream.send(entry);
+    }
+}
+
+/// Start the thread emitting streamed entries
+fn start_log_stream(app: tauri::AppHandle) {
+    let (sender, receiver) = mpsc::channel::<LogEntry>();
+    if LOG_STREAM.set(Mutex::new(sender)).is_err() {
+        return;
+    }
+    std::thread::spawn(move || {
+        for entry in receiver {
+            // Not logged: the failure would be streamed again
+            let _ = app.emit(LOG_ENTRY_EVENT, &entry);
+        }
+    });
+}
+
+/// Build the log plugin: a rotating file, plus the console in debug builds
+fn log_plugin(settings: &LogSettings) -> tauri::plugin::TauriPlugin<tauri::Wry> {
+    let mut targets = vec![Target::new(TargetKind::LogDir {
+        file_name: Some(LOG_FILE_NAME.to_string()),
+    })];
+    if cfg!(debug_assertions) {
+        targets.push(Target::new(TargetKind::Stdout));
+    }
+    targets.push(Target::new(TargetKind::Dispatch(
+        tauri_plugin_log::fern::Dispatch::new()
+            .chain(tauri_plugin_log::fern::Output::call(stream_log_record)),
+    )));
+    // Levels are left to the runtime filter
+    tauri_plugin_log::Builder::default()
+        .targets(targets)
+        .level(log::LevelFilter::Trace)
+        .filter(log_filter_enabled)
+        .max_file_size(u128::from(settings.max_file_size_mb) * 1024 * 1024)
+        // The current file is counted too
+        .rotation_strategy(RotationStrategy::KeepSome(settings.retained_files + 1))
+        .timezone_strategy(TimezoneStrategy::UseLocal)
+        .build()
+}
+
+/// Rotate and prune old logs, then start logging
+pub fn init_logging(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let settings_path = get_app_data_dir(app)?.join(LOG_SETTINGS_FILE);
+    // Unreadable settings must not keep the app from starting
+    let settings = load_log_settings(&settings_path).unwrap_or_default();
+    let log_dir = get_log_dir(app)?;
+    let rotated = rotate_stale_log(&log_dir, chrono::Local::now());
+    let pruned = prune_logs(&log_dir, &settings, SystemTime::now());
+    let filter = parse_log_filter(&settings.level);
+    start_log_stream(app.clone());
+    app.plugin(log_plugin(&settings))
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    match filter {
+        Ok(filter) => apply_log_filter(filter),
+        Err(e) => {
+            apply_log_filter(parse_log_filter(DEFAULT_LOG_LEVEL)?);
+            log::warn!("Ignoring the saved log level: {}", e);
+        }
+    }
+    if let Err(e) = rotated {
+        log::warn!("Failed to rotate the log file: {}", e);
+    }
+    if pruned > 0 {
+        log::info!("Deleted {} old log files", pruned);
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the directory holding the log files
+#[tauri::command]
+pub fn get_log_directory(app: tauri::AppHandle) -> Result<String, AppError> {
+    Ok(get_log_dir(&app)?.to_string_lossy().to_string())
+}
+
+/// Get the log rotation and retention settings
+#[tauri::command]
+pub fn get_log_settings(app: tauri::AppHandle) -> Result<LogSettings, AppError> {
+    load_log_settings(&get_app_data_dir(&app)?.join(LOG_SETTINGS_FILE))
+}
+
+/// Save the log settings; the level and retention apply now, rotation from
+/// the next start
+#[tauri::command]
+pub fn set_log_settings(app: tauri::AppHandle, settings: LogSettings) -> Result<(), AppError> {
+    let filter = validate_log_settings(&settings)?;
+    save_log_settings(&get_app_data_dir(&app)?.join(LOG_SETTINGS_FILE), &settings)?;
+    apply_log_filter(filter);
+    prune_logs(&get_log_dir(&app)?, &settings, SystemTime::now());
+    Ok(())
+}
+
+/// Get the log level filter, e.g. "info,mcp=debug"
+#[tauri::command]
+pub fn get_log_level(app: tauri::AppHandle) -> Result<String, AppError> {
+    Ok(get_log_settings(app)?.level)
+}
+
+/// Change the log level filter without restarting, e.g. "debug" or
+/// "info,mcp=debug,rmcp=trace"
+#[tauri::command]
+pub fn set_log_level(app: tauri::AppHandle, level: String) -> Result<(), AppError> {
+    let filter = parse_log_filter(&level)?;
+    let path = get_app_data_dir(&app)?.join(LOG_SETTINGS_FILE);
+    let mut settings = load_log_settings(&path).unwrap_or_default();
+    settings.level = level.trim().to_string();
+    save_log_settings(&path, &settings)?;
+    apply_log_filter(filter);
+    log::info!("Log level set to {}", settings.level);
+    Ok(())
+}
+
+/// Get the most recent log entries matching a filter, oldest first
+#[tauri::command]
+pub async fn get_recent_logs(
+    app: tauri::AppHandle,
+    filter: Option<LogQuery>,
+    limit: Option<usize>,
+) -> Result<Vec<LogEntry>, AppError> {
+    let dir = get_log_dir(&app)?;
+    let limit = limit
+        .unwrap_or(DEFAULT_RECENT_LOGS)
+        .clamp(1, MAX_RECENT_LOGS);
+    tauri::async_runtime::spawn_blocking(move || {
+        recent_log_entries(&dir, &filter.unwrap_or_default(), limit)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Start emitting new log entries as `log-entry` events
+#[tauri::command]
+pub fn subscribe_logs() {
+    LOG_STREAMING.store(true, Ordering::Relaxed);
+}
+
+/// Stop emitting new log entries
+#[tauri::command]
+pub fn unsubscribe_logs() {
+    LOG_STREAMING.store(false, Ordering::Relaxed);
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn log_filters_apply_the_longest_matching_directive() {
+        use log::Level;
+        let filter =
+            parse_log_filter("warn, mcp=debug, app_lib::commands::mcp::client=trace").unwrap();
+        assert_eq!(filter.max_level(), log::LevelFilter::Trace);
+        assert!(!filter.enabled("app_lib::commands::rag", Level::Info));
+        assert!(filter.enabled("app_lib::commands::rag", Level::Warn));
+        assert!(filter.enabled("app_lib::commands::mcp::storage", Level::Debug));
+        assert!(!filter.enabled("app_lib::commands::mcp::storage", Level::Trace));
+        assert!(filter.enabled("app_lib::commands::mcp::client", Level::Trace));
+        // Segments match whole names only
+        assert!(!filter.enabled("app_lib::commands::mcpx", Level::Debug));
+
+        assert_eq!(
+            parse_log_filter("").unwrap().default,
+            log::LevelFilter::Info
+        );
+        assert_eq!(
+            parse_log_filter("off").unwrap().max_level(),
+            log::LevelFilter::Off
+        );
+        assert!(parse_log_filter("verbose").is_err());
+        assert!(parse_log_filter("=debug").is_err());
+    }
+
+    #[test]
+    fn recent_logs_are_parsed_and_filtered() {
+        let dir = tempdir().unwrap();
+        let line = |time: &str, target: &str, level: &str, message: &str| {
+            format!(
+                "[2026-01-31][{}][{}][{}] {}\n",
+                time, target, level, message
+            )
+        };
+        let rotated = line("09:00:00", "app_lib::commands::rag", "INFO", "indexed");
+        fs::write(
+            dir.path()
+                .join(format!("{}_2026-01-31_10-00-00.log", LOG_FILE_NAME)),
+            rotated,
+        )
+        .unwrap();
+        let current = [
+            line(
+                "10:00:01",
+                "app_lib::commands::mcp::client",
+                "WARN",
+                "timeout",
+            ),
+            "  caused by: broken pipe\n".to_string(),
+            line(
+                "10:00:02",
+                "app_lib::commands::mcp::client",
+                "DEBUG",
+                "retry",
+            ),
+            line(
+                "10:00:03",
+                "app_lib::commands::ai_proxy",
+                "ERROR",
+                "HTTP 500",
+            ),
+        ]
+        .concat();
+        fs::write(dir.path().join(format!("{}.log", LOG_FILE_NAME)), current).unwrap();
+
+        let all = recent_log_entries(dir.path(), &LogQuery::default(), 10).unwrap();
+        let messages: Vec<&str> = all.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "indexed",
+                "timeout\n  caused by: broken pipe",
+                "retry",
+                "HTTP 500"
+            ]
+        );
+        assert_eq!(all[0].timestamp, "2026-01-31 09:00:00");
+
+        let last_two = recent_log_entries(dir.path(), &LogQuery::default(), 2).unwrap();
+        assert_eq!(last_two[0].message, "retry");
+        let query = LogQuery {
+            level: Some("warn".to_string()),
+            target: Some("mcp".to_string()),
+            text: Some("PIPE".to_string()),
+        };
+        let found = recent_log_entries(dir.path(), &query, 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].level, "WARN");
+        assert!(parse_log_line("no brackets").is_none());
+    }
+
+    #[test]
+    fn stale_logs_are_rotated_and_old_ones_pruned() {
+        let dir = tempdir().unwrap();
+        let current = dir.path().join(format!("{}.log", LOG_FILE_NAME));
+        fs::write(&current, "yesterday\n").unwrap();
+        let tomorrow = chrono::Local::now() + chrono::Duration::days(1);
+        rotate_stale_log(dir.path(), tomorrow).unwrap();
+        assert!(!current.exists());
+        assert_eq!(rotated_logs(dir.path()).len(), 1);
+
+        for day in 1..=3 {
+            let name = format!("{}_2026-01-0{}_00-00-00.log", LOG_FILE_NAME, day);
+            fs::write(dir.path().join(name), "old\n").unwrap();
+        }
+        fs::write(dir.path().join("other.log"), "kept\n").unwrap();
+        let settings = LogSettings {
+            retained_files: 3,
+            ..Default::default()
+        };
+        // The oldest is beyond the retained count
+        assert_eq!(prune_logs(dir.path(), &settings, SystemTime::now()), 1);
+        assert!(!dir
+            .path()
+            .join(format!("{}_2026-01-01_00-00-00.log", LOG_FILE_NAME))
+            .exists());
+        // All are past the retention period a month from now
+        let later = SystemTime::now() + Duration::from_secs(30 * 24 * 60 * 60);
+        assert_eq!(prune_logs(dir.path(), &settings, later), 3);
+        assert!(dir.path().join("other.log").exists());
+
+        assert!(validate_log_settings(&LogSettings::default()).is_ok());
+        assert!(validate_log_settings(&LogSettings {
+            level: "loud".to_string(),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(validate_log_settings(&LogSettings {
+            retained_files: 0,
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
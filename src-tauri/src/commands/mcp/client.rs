@@ -6,14 +6,18 @@
 use crate::error::AppError;
 use rmcp::{
     model::{CallToolRequestParam, GetPromptRequestParam, ReadResourceRequestParam},
-    service::{RunningService, ServiceExt},
+    service::{NotificationContext, RunningService, ServiceExt},
     transport::{ConfigureCommandExt, TokioChildProcess},
-    RoleClient,
+    ClientHandler, RoleClient,
 };
 use serde::Serialize;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::process::Command;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{ChildStderr, Command};
 use tokio::sync::RwLock;
 
 // ============================================================================
@@ -31,6 +35,29 @@ pub struct MCPClientInfo {
     pub status: String,
 }
 
+/// Prompt results cached per server, at most
+const MAX_CACHED_PROMPTS: usize = 64;
+
+/// How long a server may take to answer the initialize handshake
+const MCP_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Bytes of a server's stderr kept for connection errors
+const MAX_STDERR_CAPTURE: usize = 4096;
+
+/// Launcher commands, the runtime providing them and where to get it
+const MCP_RUNTIMES: &[(&[&str], &str, &str)] = &[
+    (&["node", "npx", "npm"], "Node.js", "https://nodejs.org"),
+    (&["uvx", "uv"], "uv", "https://docs.astral.sh/uv/"),
+    (
+        &["python", "python3", "pip"],
+        "Python",
+        "https://www.python.org",
+    ),
+    (&["bun", "bunx"], "Bun", "https://bun.sh"),
+    (&["deno"], "Deno", "https://deno.com"),
+    (&["docker"], "Docker", "https://www.docker.com"),
+];
+
 /// MCP server capabilities
 #[derive(Debug, Clone, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -135,17 +162,90 @@ pub struct MCPPromptMessage {
 // Client Session Management
 // ============================================================================
 
+/// `get_prompt` results by server, then by prompt name and arguments
+#[derive(Default)]
+pub struct MCPPromptCache {
+    servers: Mutex<HashMap<String, HashMap<String, MCPPromptGetResult>>>,
+}
+
+impl MCPPromptCache {
+    /// Key of a prompt call; argument order does not matter
+    fn key(prompt_name: &str, arguments: Option<&HashMap<String, String>>) -> String {
+        let sorted: BTreeMap<&String, &String> = arguments.into_iter().flatten().collect();
+        format!(
+            "{}\n{}",
+            prompt_name,
+            serde_json::to_string(&sorted).unwrap_or_default()
+        )
+    }
+
+    pub fn get(
+        &self,
+        server_id: &str,
+        prompt_name: &str,
+        arguments: Option<&HashMap<String, String>>,
+    ) -> Option<MCPPromptGetResult> {
+        let servers = self.servers.lock().ok()?;
+        servers
+            .get(server_id)?
+            .get(&Self::key(prompt_name, arguments))
+            .cloned()
+    }
+
+    pub fn insert(
+        &self,
+        server_id: &str,
+        prompt_name: &str,
+        arguments: Option<&HashMap<String, String>>,
+        result: MCPPromptGetResult,
+    ) {
+        if let Ok(mut servers) = self.servers.lock() {
+            let prompts = servers.entry(server_id.to_string()).or_default();
+            if prompts.len() >= MAX_CACHED_PROMPTS {
+                prompts.clear();
+            }
+            prompts.insert(Self::key(prompt_name, arguments), result);
+        }
+    }
+
+    /// Forget every prompt result of a server
+    pub fn invalidate(&self, server_id: &str) {
+        if let Ok(mut servers) = self.servers.lock() {
+            servers.remove(server_id);
+        }
+    }
+}
+
+/// Handles notifications from one server
+pub struct MCPClientHandler {
+    server_id: String,
+    prompt_cache: Arc<MCPPromptCache>,
+}
+
+impl ClientHandler for MCPClientHandler {
+    async fn on_prompt_list_changed(&self, _context: NotificationContext<RoleClient>) {
+        tracing::debug!("Prompts of MCP server {} changed", self.server_id);
+        self.prompt_cache.invalidate(&self.server_id);
+    }
+}
+
 /// Active MCP client session
 pub struct MCPClientSession {
     pub server_id: String,
     pub server_name: String,
-    pub service: RunningService<RoleClient, ()>,
+    pub service: RunningService<RoleClient, MCPClientHandler>,
+    /// Process id of the server
+    pub pid: Option<u32>,
 }
 
 /// Global state for managing MCP client sessions
 #[derive(Default)]
 pub struct MCPClientState {
-    pub sessions: HashMap<String, MCPClientSession>,
+    /// Sessions are shared, so a slow request to one server holds neither
+    /// the map nor the other servers
+    pub sessions: HashMap<String, Arc<MCPClientSession>>,
+    /// Prompt results, dropped when a server says its prompts changed
+    pub prompt_cache: Arc<MCPPromptCache>,
 }
 
 /// Thread-safe MCP client state
@@ -160,6 +260,33 @@ pub fn create_mcp_client_state() -> MCPClientStateHandle {
 // Helper Functions
 // ============================================================================
 
+/// Handle of a connected session; the state lock is released before the
+/// caller awaits the server
+async fn get_session(
+    state: &MCPClientStateHandle,
+    server_id: &str,
+) -> Result<Arc<MCPClientSession>, AppError> {
+    state
+        .read()
+        .await
+        .sessions
+        .get(server_id)
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("Server '{}' not found", server_id)))
+}
+
+/// Stop a session removed from the state. Requests still running on it are
+/// cancelled, and the session is dropped when the last of them returns.
+async fn close_session(session: Arc<MCPClientSession>) -> Result<(), tokio::task::JoinError> {
+    match Arc::try_unwrap(session) {
+        Ok(session) => session.service.cancel().await.map(|_| ()),
+        Err(shared) => {
+            shared.service.cancellation_token().cancel();
+            Ok(())
+        }
+    }
+}
+
 /// Extract capabilities from peer info
 fn extract_capabilities(
     peer_info: Option<&rmcp::model::InitializeResult>,
@@ -180,6 +307,128 @@ fn extract_protocol_version(peer_info: Option<&rmcp::model::InitializeResult>) -
     peer_info.map(|info| info.protocol_version.to_string())
 }
 
+/// How connecting to a server failed
+#[derive(Debug)]
+enum ConnectFailure {
+    /// The process could not be started
+    Spawn(std::io::Error),
+    /// The process started but the handshake failed, usually because it exited
+    Handshake(String),
+    /// No answer within `MCP_HANDSHAKE_TIMEOUT`
+    TimedOut,
+}
+
+/// Runtime providing a launcher command, and where to get it
+fn command_runtime(command: &str) -> Option<(&'static str, &'static str)> {
+    let name = Path::new(command).file_stem()?.to_str()?.to_lowercase();
+    MCP_RUNTIMES
+        .iter()
+        .find(|(launchers, _, _)| launchers.contains(&name.as_str()))
+        .map(|(_, runtime, url)| (*runtime, *url))
+}
+
+/// Runtime a server's stderr says is missing, e.g. from a `#!/usr/bin/env node`
+/// script
+fn missing_runtime(stderr: &str) -> Option<(&'static str, &'static str)> {
+    MCP_RUNTIMES.iter().find_map(|(launchers, runtime, url)| {
+        launchers
+            .iter()
+            .any(|name| {
+                [
+                    format!("env: '{}'", name),
+                    format!("env: {}:", name),
+                    format!("{}: not found", name),
+                    format!("{}: command not found", name),
+                    format!("'{}' is not recognized", name),
+                ]
+                .iter()
+                .any(|pattern| stderr.contains(pattern.as_str()))
+            })
+            .then_some((*runtime, *url))
+    })
+}
+
+/// Explain a failed connection with a hint on how to fix it, followed by
+/// what the server printed to stderr
+fn connect_error_message(command: &str, failure: &ConnectFailure, stderr: &str) -> String {
+    let mut message = match failure {
+        ConnectFailure::Spawn(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            match command_runtime(command) {
+                Some((runtime, url)) => format!(
+                    "`{}` was not found on PATH. Install {} ({}) and restart the app, \
+                     or use the full path to `{}` as the server command.",
+                    command, runtime, url, command
+                ),
+                None => format!(
+                    "`{}` was not found on PATH. Check the command name, or use its full path.",
+                    command
+                ),
+            }
+        }
+        ConnectFailure::Spawn(e) if e.kind() == std::io::ErrorKind::PermissionDenied => format!(
+            "`{}` could not be started: permission denied. Make sure the file is \
+             executable and you are allowed to run it.",
+            command
+        ),
+        ConnectFailure::Spawn(e) => format!("`{}` could not be started: {}", command, e),
+        ConnectFailure::Handshake(e) => match missing_runtime(stderr) {
+            Some((runtime, url)) => format!(
+                "The server needs {} ({}), which was not found on PATH. Install it and \
+                 restart the app.",
+                runtime, url
+            ),
+            None => format!(
+                "The server exited or failed during the MCP handshake: {}",
+                e
+            ),
+        },
+        ConnectFailure::TimedOut => format!(
+            "The server did not answer the MCP handshake within {} seconds. Check that it \
+             speaks MCP over stdio and is not waiting for input.",
+            MCP_HANDSHAKE_TIMEOUT.as_secs()
+        ),
+    };
+    let stderr = stderr.trim();
+    if !stderr.is_empty() {
+        message.push_str("\n\nServer output:\n");
+        message.push_str(stderr);
+    }
+    message
+}
+
+/// Append a line to a buffer, dropping the oldest bytes past
+/// `MAX_STDERR_CAPTURE`
+fn push_stderr_line(buffer: &mut String, line: &str) {
+    buffer.push_str(line);
+    buffer.push('\n');
+    if buffer.len() > MAX_STDERR_CAPTURE {
+        let mut cut = buffer.len() - MAX_STDERR_CAPTURE;
+        while !buffer.is_char_boundary(cut) {
+            cut += 1;
+        }
+        buffer.drain(..cut);
+    }
+}
+
+/// Log a server's stderr and keep its tail for connection errors
+fn capture_stderr(
+    server_id: String,
+    stderr: ChildStderr,
+) -> (Arc<Mutex<String>>, tauri::async_runtime::JoinHandle<()>) {
+    let captured = Arc::new(Mutex::new(String::new()));
+    let buffer = captured.clone();
+    let task = tauri::async_runtime::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            tracing::debug!("[{}] {}", server_id, line);
+            if let Ok(mut buffer) = buffer.lock() {
+                push_stderr_line(&mut buffer, &line);
+            }
+        }
+    });
+    (captured, task)
+}
+
 /// Convert Annotated<RawContent> to MCPContent
 fn convert_raw_content(content: rmcp::model::Annotated<rmcp::model::RawContent>) -> MCPContent {
     match &*content {
@@ -304,21 +553,60 @@ pub async fn connect_mcp_server(
     let env_clone = env.clone();
     let args_clone = args.clone();
 
-    let transport = TokioChildProcess::new(Command::new(&command).configure(move |cmd| {
-        cmd.args(&args_clone);
-        if let Some(ref env_vars) = env_clone {
-            for (key, value) in env_vars {
-                cmd.env(key, value);
+    let connect_err = |failure: ConnectFailure, stderr: &str| {
+        AppError::Mcp(format!(
+            "Failed to connect to MCP server: {}",
+            connect_error_message(&command, &failure, stderr)
+        ))
+    };
+    let (transport, stderr) =
+        TokioChildProcess::builder(Command::new(&command).configure(move |cmd| {
+            cmd.args(&args_clone);
+            if let Some(ref env_vars) = env_clone {
+                for (key, value) in env_vars {
+                    cmd.env(key, value);
+                }
             }
-        }
-    }))
-    .map_err(|e| AppError::Mcp(format!("Failed to create transport: {}", e)))?;
+        }))
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| connect_err(ConnectFailure::Spawn(e), ""))?;
+    let pid = transport.id();
+    let stderr = stderr.map(|stderr| capture_stderr(server_id.clone(), stderr));
 
     // Connect and initialize
-    let service = ()
-        .serve(transport)
-        .await
-        .map_err(|e| AppError::Mcp(format!("Failed to connect to MCP server: {}", e)))?;
+    let prompt_cache = state.read().await.prompt_cache.clone();
+    prompt_cache.invalidate(&server_id);
+    let handler = MCPClientHandler {
+        server_id: server_id.clone(),
+        prompt_cache,
+    };
+    let handshake =
+        match tokio::time::timeout(MCP_HANDSHAKE_TIMEOUT, handler.serve(transport)).await {
+            Ok(Ok(service)) => Ok(service),
+            Ok(Err(e)) => Err(ConnectFailure::Handshake(e.to_string())),
+            Err(_) => Err(ConnectFailure::TimedOut),
+        };
+    let service = match handshake {
+        Ok(service) => service,
+        Err(failure) => {
+            // The process is killed once the transport is dropped; let the
+            // reader drain what it printed before exiting
+            let output = match stderr {
+                Some((captured, task)) => {
+                    let _ = tokio::time::timeout(Duration::from_secs(1), task).await;
+                    captured.lock().map(|text| text.clone()).unwrap_or_default()
+                }
+                None => String::new(),
+            };
+            tracing::warn!(
+                "Failed to connect to MCP server {}: {:?}",
+                server_name,
+                failure
+            );
+            return Err(connect_err(failure, &output));
+        }
+    };
 
     // Get server info
     let peer_info = service.peer_info();
@@ -338,11 +626,12 @@ pub async fn connect_mcp_server(
         let mut state_guard = state.write().await;
         state_guard.sessions.insert(
             server_id.clone(),
-            MCPClientSession {
+            Arc::new(MCPClientSession {
                 server_id,
                 server_name,
                 service,
-            },
+                pid,
+            }),
         );
     }
 
@@ -357,16 +646,16 @@ pub async fn disconnect_mcp_server(
 ) -> Result<(), AppError> {
     let session = {
         let mut state_guard = state.write().await;
+        state_guard.prompt_cache.invalidate(server_id);
         state_guard.sessions.remove(server_id)
     };
 
     if let Some(session) = session {
-        session
-            .service
-            .cancel()
+        let server_name = session.server_name.clone();
+        close_session(session)
             .await
             .map_err(|e| AppError::Mcp(format!("Failed to disconnect: {}", e)))?;
-        tracing::info!("Disconnected from MCP server: {}", session.server_name);
+        tracing::info!("Disconnected from MCP server: {}", server_name);
         Ok(())
     } else {
         Err(AppError::NotFound(format!(
@@ -381,11 +670,7 @@ pub async fn list_mcp_tools(
     state: &MCPClientStateHandle,
     server_id: &str,
 ) -> Result<Vec<MCPToolInfo>, AppError> {
-    let state_guard = state.read().await;
-    let session = state_guard
-        .sessions
-        .get(server_id)
-        .ok_or_else(|| AppError::NotFound(format!("Server '{}' not found", server_id)))?;
+    let session = get_session(state, server_id).await?;
 
     let result = session
         .service
@@ -411,11 +696,7 @@ pub async fn list_mcp_resources(
     state: &MCPClientStateHandle,
     server_id: &str,
 ) -> Result<Vec<MCPResourceInfo>, AppError> {
-    let state_guard = state.read().await;
-    let session = state_guard
-        .sessions
-        .get(server_id)
-        .ok_or_else(|| AppError::NotFound(format!("Server '{}' not found", server_id)))?;
+    let session = get_session(state, server_id).await?;
 
     let result = session
         .service
@@ -442,11 +723,7 @@ pub async fn list_mcp_prompts(
     state: &MCPClientStateHandle,
     server_id: &str,
 ) -> Result<Vec<MCPPromptInfo>, AppError> {
-    let state_guard = state.read().await;
-    let session = state_guard
-        .sessions
-        .get(server_id)
-        .ok_or_else(|| AppError::NotFound(format!("Server '{}' not found", server_id)))?;
+    let session = get_session(state, server_id).await?;
 
     let result = session
         .service
@@ -482,11 +759,7 @@ pub async fn call_mcp_tool(
     tool_name: String,
     arguments: Option<serde_json::Value>,
 ) -> Result<MCPToolCallResult, AppError> {
-    let state_guard = state.read().await;
-    let session = state_guard
-        .sessions
-        .get(server_id)
-        .ok_or_else(|| AppError::NotFound(format!("Server '{}' not found", server_id)))?;
+    let session = get_session(state, server_id).await?;
 
     let args = arguments.and_then(|v| v.as_object().cloned());
 
@@ -514,11 +787,7 @@ pub async fn read_mcp_resource(
     server_id: &str,
     uri: &str,
 ) -> Result<MCPResourceReadResult, AppError> {
-    let state_guard = state.read().await;
-    let session = state_guard
-        .sessions
-        .get(server_id)
-        .ok_or_else(|| AppError::NotFound(format!("Server '{}' not found", server_id)))?;
+    let session = get_session(state, server_id).await?;
 
     let result = session
         .service
@@ -551,21 +820,25 @@ pub async fn read_mcp_resource(
     Ok(MCPResourceReadResult { contents })
 }
 
-/// Get a prompt from an MCP server
+/// Get a prompt from an MCP server, from the cache unless `refresh` is set
 pub async fn get_mcp_prompt(
     state: &MCPClientStateHandle,
     server_id: &str,
     prompt_name: &str,
     arguments: Option<HashMap<String, String>>,
+    refresh: bool,
 ) -> Result<MCPPromptGetResult, AppError> {
-    let state_guard = state.read().await;
-    let session = state_guard
-        .sessions
-        .get(server_id)
-        .ok_or_else(|| AppError::NotFound(format!("Server '{}' not found", server_id)))?;
+    let session = get_session(state, server_id).await?;
+
+    let prompt_cache = state.read().await.prompt_cache.clone();
+    if !refresh {
+        if let Some(cached) = prompt_cache.get(server_id, prompt_name, arguments.as_ref()) {
+            return Ok(cached);
+        }
+    }
 
     // Convert HashMap<String, String> to serde_json::Map<String, Value>
-    let args = arguments.map(|map| {
+    let args = arguments.clone().map(|map| {
         map.into_iter()
             .map(|(k, v)| (k, serde_json::Value::String(v)))
             .collect()
@@ -589,10 +862,12 @@ pub async fn get_mcp_prompt(
         })
         .collect();
 
-    Ok(MCPPromptGetResult {
+    let prompt = MCPPromptGetResult {
         description: result.description.clone(),
         messages,
-    })
+    };
+    prompt_cache.insert(server_id, prompt_name, arguments.as_ref(), prompt.clone());
+    Ok(prompt)
 }
 
 /// Get all connected MCP clients info
@@ -621,22 +896,175 @@ pub async fn get_connected_mcp_clients(
 
 /// Disconnect all MCP servers
 pub async fn disconnect_all_mcp_servers(state: &MCPClientStateHandle) -> Result<(), AppError> {
-    let sessions: Vec<MCPClientSession> = {
+    let sessions: Vec<Arc<MCPClientSession>> = {
         let mut state_guard = state.write().await;
+        for server_id in state_guard.sessions.keys() {
+            state_guard.prompt_cache.invalidate(server_id);
+        }
         state_guard.sessions.drain().map(|(_, v)| v).collect()
     };
 
     for session in sessions {
-        if let Err(e) = session.service.cancel().await {
+        let server_name = session.server_name.clone();
+        if let Err(e) = close_session(session).await {
             tracing::warn!(
                 "Failed to disconnect from MCP server {}: {}",
-                session.server_name,
+                server_name,
                 e
             );
         } else {
-            tracing::info!("Disconnected from MCP server: {}", session.server_name);
+            tracing::info!("Disconnected from MCP server: {}", server_name);
         }
     }
 
     Ok(())
 }
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(text: &str) -> MCPPromptGetResult {
+        MCPPromptGetResult {
+            description: Some(text.to_string()),
+            messages: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn prompt_cache_keys_on_arguments_and_invalidates_per_server() {
+        let cache = MCPPromptCache::default();
+        let args: HashMap<String, String> = [("lang", "en"), ("topic", "pdf")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        cache.insert("server-a", "summarize", Some(&args), prompt("a"));
+        cache.insert("server-b", "summarize", Some(&args), prompt("b"));
+        cache.insert("server-a", "summarize", None, prompt("no args"));
+
+        let same: HashMap<String, String> = [("topic", "pdf"), ("lang", "en")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let hit = cache.get("server-a", "summarize", Some(&same)).unwrap();
+        assert_eq!(hit.description.as_deref(), Some("a"));
+        let other: HashMap<String, String> = [("lang".to_string(), "zh".to_string())].into();
+        assert!(cache.get("server-a", "summarize", Some(&other)).is_none());
+        let hit = cache.get("server-a", "summarize", None).unwrap();
+        assert_eq!(hit.description.as_deref(), Some("no args"));
+
+        cache.invalidate("server-a");
+        assert!(cache.get("server-a", "summarize", Some(&args)).is_none());
+        assert!(cache.get("server-b", "summarize", Some(&args)).is_some());
+    }
+
+    #[tokio::test]
+    async fn connect_failures_explain_the_cause() {
+        let not_found = std::io::Error::from(std::io::ErrorKind::NotFound);
+        let message = connect_error_message("npx", &ConnectFailure::Spawn(not_found), "");
+        assert!(message.contains("Install Node.js"));
+        let not_found = std::io::Error::from(std::io::ErrorKind::NotFound);
+        let message = connect_error_message("my-server", &ConnectFailure::Spawn(not_found), "");
+        assert!(message.contains("Check the command name"));
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let message = connect_error_message("/opt/server", &ConnectFailure::Spawn(denied), "");
+        assert!(message.contains("permission denied"));
+        let message = connect_error_message(
+            "./server.js",
+            &ConnectFailure::Handshake("connection closed".to_string()),
+            "/usr/bin/env: 'node': No such file or directory\n",
+        );
+        assert!(message.starts_with("The server needs Node.js"));
+        assert!(
+            message.ends_with("Server output:\n/usr/bin/env: 'node': No such file or directory")
+        );
+        assert!(connect_error_message("uvx", &ConnectFailure::TimedOut, "").contains("30 seconds"));
+
+        let mut buffer = String::new();
+        for _ in 0..1000 {
+            push_stderr_line(&mut buffer, "服务器日志");
+        }
+        assert!(buffer.len() <= MAX_STDERR_CAPTURE);
+        assert!(buffer.ends_with("服务器日志\n"));
+
+        #[cfg(unix)]
+        {
+            let state = create_mcp_client_state();
+            let result = connect_mcp_server(
+                &state,
+                "broken".to_string(),
+                "Broken".to_string(),
+                "sh".to_string(),
+                vec![
+                    "-c".to_string(),
+                    "echo 'missing API_TOKEN' >&2; exit 1".to_string(),
+                ],
+                None,
+            )
+            .await;
+            let message = result.unwrap_err().to_string();
+            assert!(message.contains("failed during the MCP handshake"));
+            assert!(message.ends_with("Server output:\nmissing API_TOKEN"));
+        }
+    }
+
+    /// A stdio MCP server whose tool calls take three seconds
+    #[cfg(unix)]
+    fn slow_server_args() -> Vec<String> {
+        let script = r#"while read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  case "$line" in
+    *'"initialize"'*) printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"slow","version":"1.0"}}}\n' "$id" ;;
+    *'"tools/call"'*) sleep 3; printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"done"}]}}\n' "$id" ;;
+  esac
+done"#;
+        vec!["-c".to_string(), script.to_string()]
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn slow_tool_calls_do_not_hold_the_session_map() {
+        let state = create_mcp_client_state();
+        let connect = |id: &str| {
+            connect_mcp_server(
+                &state,
+                id.to_string(),
+                id.to_string(),
+                "sh".to_string(),
+                slow_server_args(),
+                None,
+            )
+        };
+        connect("slow").await.unwrap();
+
+        let call_state = state.clone();
+        let call = tokio::spawn(async move {
+            call_mcp_tool(&call_state, "slow", "wait".to_string(), None).await
+        });
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        // Connecting needs the write lock, which the running call must not hold
+        tokio::time::timeout(Duration::from_secs(2), connect("other"))
+            .await
+            .expect("connect waited for the tool call")
+            .unwrap();
+        assert_eq!(get_connected_mcp_clients(&state).await.unwrap().len(), 2);
+
+        // Disconnecting cancels the call instead of waiting for it; the call
+        // fails once the server has been shut down
+        tokio::time::timeout(Duration::from_secs(2), disconnect_all_mcp_servers(&state))
+            .await
+            .expect("disconnect waited for the tool call")
+            .unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(10), call)
+            .await
+            .expect("the call outlived its session")
+            .unwrap();
+        assert!(result.is_err());
+        assert!(get_connected_mcp_clients(&state).await.unwrap().is_empty());
+    }
+}
@@ -10,6 +10,7 @@ use super::client::{
     MCPToolInfo,
 };
 use super::types::MCPServerConfig;
+use crate::commands::ai_usage::record_mcp_tool_call;
 use crate::error::AppError;
 use serde::Deserialize;
 use std::collections::HashMap;
@@ -53,6 +54,9 @@ pub struct GetPromptParams {
     pub server_id: String,
     pub prompt_name: String,
     pub arguments: Option<HashMap<String, String>>,
+    /// Ask the server again instead of using a cached result
+    #[serde(default)]
+    pub refresh: bool,
 }
 
 // ============================================================================
@@ -155,13 +159,26 @@ pub async fn mcp_list_prompts(
     list_mcp_prompts(&state, &server_id).await
 }
 
-/// Call a tool on an MCP server
+/// Call a tool on an MCP server, counting the call in the usage statistics
 #[tauri::command]
 pub async fn mcp_call_tool(
+    app: tauri::AppHandle,
     state: tauri::State<'_, MCPClientStateHandle>,
     params: CallToolParams,
 ) -> Result<MCPToolCallResult, AppError> {
-    call_mcp_tool(&state, &params.server_id, params.tool_name, params.arguments).await
+    let tool_name = params.tool_name.clone();
+    let result = call_mcp_tool(
+        &state,
+        &params.server_id,
+        params.tool_name,
+        params.arguments,
+    )
+    .await;
+    let failed = result.as_ref().map_or(true, |r| r.is_error);
+    if let Err(e) = record_mcp_tool_call(&app, &params.server_id, &tool_name, failed) {
+        log::warn!("Failed to record MCP tool usage: {}", e);
+    }
+    result
 }
 
 /// Read a resource from an MCP server
@@ -184,6 +201,7 @@ pub async fn mcp_get_prompt(
         &params.server_id,
         &params.prompt_name,
         params.arguments,
+        params.refresh,
     )
     .await
 }
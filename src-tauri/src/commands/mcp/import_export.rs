@@ -11,10 +11,61 @@ use std::fs;
 use std::path::Path;
 use uuid::Uuid;
 
+/// Env var and header names containing any of these are treated as secrets
+/// by safe-share exports (matched case-insensitively)
+const SECRET_NAME_PATTERNS: &[&str] = &["TOKEN", "KEY", "SECRET", "PASSWORD", "AUTHORIZATION"];
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
+/// Whether an env var or header name looks like it holds a secret
+pub fn is_secret_name(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    SECRET_NAME_PATTERNS
+        .iter()
+        .any(|pattern| name.contains(pattern))
+}
+
+/// Blank secret env values and headers, returning the stripped fields as
+/// `server.env.NAME` / `server.headers.NAME`
+pub fn strip_secrets(servers: &mut [MCPServerConfig]) -> Vec<String> {
+    let mut stripped = Vec::new();
+    for server in servers.iter_mut() {
+        for (kind, values) in [("env", &mut server.env), ("headers", &mut server.headers)] {
+            for (name, value) in values.iter_mut().flatten() {
+                if is_secret_name(name) && !value.is_empty() {
+                    value.clear();
+                    stripped.push(format!("{}.{}.{}", server.name, kind, name));
+                }
+            }
+        }
+    }
+    stripped.sort();
+    stripped
+}
+
+/// Servers in the app's own export format, secrets stripped if asked
+fn export_servers_json(
+    mut servers: Vec<MCPServerConfig>,
+    safe_share: bool,
+) -> (serde_json::Value, Vec<String>) {
+    let mut export_data = serde_json::json!({
+        "version": 1,
+        "source": "sast-readium",
+        "exportedAt": chrono::Utc::now().timestamp(),
+    });
+    let stripped = if safe_share {
+        let stripped = strip_secrets(&mut servers);
+        export_data["strippedSecrets"] = serde_json::json!(stripped);
+        stripped
+    } else {
+        Vec::new()
+    };
+    export_data["servers"] = serde_json::json!(servers);
+    (export_data, stripped)
+}
+
 /// Convert Claude Desktop format to internal format
 pub fn convert_claude_desktop_server(name: &str, server: &ClaudeDesktopMCPServer) -> MCPServerConfig {
     let now = chrono::Utc::now().timestamp();
@@ -172,56 +223,69 @@ pub fn import_mcp_servers_from_file(
     import_mcp_servers(app, data, merge)
 }
 
-/// Export MCP servers to JSON string
+/// Export MCP servers to JSON string; `safe_share` blanks secret env values
+/// and headers
 #[tauri::command]
-pub fn export_mcp_servers(app: tauri::AppHandle) -> Result<String, AppError> {
+pub fn export_mcp_servers(
+    app: tauri::AppHandle,
+    safe_share: Option<bool>,
+) -> Result<String, AppError> {
     let path = get_mcp_servers_path(&app)?;
     let store = load_mcp_servers_from_file(&path)?;
 
-    let export_data = serde_json::json!({
-        "version": 1,
-        "source": "sast-readium",
-        "exportedAt": chrono::Utc::now().timestamp(),
-        "servers": store.servers
-    });
+    let (export_data, _) = export_servers_json(store.servers, safe_share.unwrap_or(false));
 
     Ok(serde_json::to_string_pretty(&export_data)?)
 }
 
-/// Export MCP servers to a file
+/// Export MCP servers to a file; `safe_share` blanks secret env values and
+/// headers
 #[tauri::command]
 pub fn export_mcp_servers_to_file(
     app: tauri::AppHandle,
     file_path: String,
+    safe_share: Option<bool>,
 ) -> Result<MCPExportResult, AppError> {
     let storage_path = get_mcp_servers_path(&app)?;
     let store = load_mcp_servers_from_file(&storage_path)?;
+    let server_count = store.servers.len();
 
-    let export_data = serde_json::json!({
-        "version": 1,
-        "source": "sast-readium",
-        "exportedAt": chrono::Utc::now().timestamp(),
-        "servers": store.servers
-    });
+    let (export_data, stripped_fields) =
+        export_servers_json(store.servers, safe_share.unwrap_or(false));
 
     let content = serde_json::to_string_pretty(&export_data)?;
     fs::write(&file_path, content)?;
 
-    log::info!("MCP servers exported to: {}", file_path);
+    log::info!(
+        "MCP servers exported to: {} ({} secrets stripped)",
+        file_path,
+        stripped_fields.len()
+    );
 
     Ok(MCPExportResult {
         success: true,
         file_path: Some(file_path),
-        server_count: store.servers.len(),
+        server_count,
         error: None,
+        stripped_fields,
     })
 }
 
-/// Export MCP servers in Claude Desktop format
+/// Export MCP servers in Claude Desktop format; `safe_share` blanks secret
+/// env values and headers
 #[tauri::command]
-pub fn export_mcp_servers_claude_format(app: tauri::AppHandle) -> Result<String, AppError> {
+pub fn export_mcp_servers_claude_format(
+    app: tauri::AppHandle,
+    safe_share: Option<bool>,
+) -> Result<String, AppError> {
     let path = get_mcp_servers_path(&app)?;
-    let store = load_mcp_servers_from_file(&path)?;
+    let mut store = load_mcp_servers_from_file(&path)?;
+    let safe_share = safe_share.unwrap_or(false);
+    let stripped = if safe_share {
+        strip_secrets(&mut store.servers)
+    } else {
+        Vec::new()
+    };
 
     let mut mcp_servers: HashMap<String, serde_json::Value> = HashMap::new();
 
@@ -247,9 +311,12 @@ pub fn export_mcp_servers_claude_format(app: tauri::AppHandle) -> Result<String,
         mcp_servers.insert(server.name, serde_json::Value::Object(server_obj));
     }
 
-    let export_data = serde_json::json!({
+    let mut export_data = serde_json::json!({
         "mcpServers": mcp_servers
     });
+    if safe_share {
+        export_data["strippedSecrets"] = serde_json::json!(stripped);
+    }
 
     Ok(serde_json::to_string_pretty(&export_data)?)
 }
@@ -589,6 +656,48 @@ mod tests {
         assert!(servers[0].id.starts_with("imported_"));
     }
 
+    #[test]
+    fn strip_secrets_blanks_secret_env_and_headers() {
+        let data = r#"{
+            "mcpServers": {
+                "github": {
+                    "command": "npx",
+                    "env": {
+                        "GITHUB_TOKEN": "ghp_secret",
+                        "api_key": "sk-123",
+                        "LOG_LEVEL": "debug"
+                    }
+                },
+                "remote": {
+                    "url": "https://example.com/mcp",
+                    "headers": {
+                        "Authorization": "Bearer abc",
+                        "X-Client-Secret": ""
+                    }
+                }
+            }
+        }"#;
+        let mut servers = parse_mcp_import_data(data).unwrap();
+
+        let stripped = strip_secrets(&mut servers);
+
+        assert_eq!(
+            stripped,
+            vec![
+                "github.env.GITHUB_TOKEN",
+                "github.env.api_key",
+                "remote.headers.Authorization",
+            ]
+        );
+        let github = servers.iter().find(|s| s.name == "github").unwrap();
+        let env = github.env.as_ref().unwrap();
+        assert_eq!(env["GITHUB_TOKEN"], "");
+        assert_eq!(env["LOG_LEVEL"], "debug");
+        // Already stripped, so nothing is left to strip
+        let (export_data, _) = export_servers_json(servers, true);
+        assert_eq!(export_data["strippedSecrets"].as_array().unwrap().len(), 0);
+    }
+
     #[test]
     fn detect_external_mcp_configs_returns_valid_vector() {
         // This test just ensures the function runs without panicking
@@ -1,6 +1,7 @@
 //! MCP server configuration storage commands
 
 use super::types::{MCPServerConfig, MCPServersStore};
+use crate::commands::migrations::JsonMigration;
 use crate::error::AppError;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -11,6 +12,39 @@ use uuid::Uuid;
 // Helper Functions
 // ============================================================================
 
+/// MCP servers file name in the app data directory
+pub const MCP_SERVERS_FILE: &str = "mcp_servers.json";
+
+/// Upgrades of the MCP servers file, run at startup (see `migrations`)
+pub const MCP_SERVERS_MIGRATIONS: &[JsonMigration] = &[fill_mcp_server_defaults];
+
+/// v1: fill fields that early versions of the file could miss
+fn fill_mcp_server_defaults(value: &mut serde_json::Value) -> Result<(), AppError> {
+    let Some(store) = value.as_object_mut() else {
+        return Err(AppError::InvalidInput(
+            "MCP servers file is not an object".to_string(),
+        ));
+    };
+    store.entry("version").or_insert(1.into());
+    store.entry("updatedAt").or_insert(0.into());
+    let servers = store.entry("servers").or_insert(serde_json::json!([]));
+    for server in servers.as_array_mut().into_iter().flatten() {
+        let Some(server) = server.as_object_mut() else {
+            continue;
+        };
+        let server_type = if server.contains_key("url") {
+            "http"
+        } else {
+            "stdio"
+        };
+        server.entry("type").or_insert(server_type.into());
+        server.entry("enabled").or_insert(true.into());
+        server.entry("createdAt").or_insert(0.into());
+        server.entry("updatedAt").or_insert(0.into());
+    }
+    Ok(())
+}
+
 /// Get the MCP servers storage file path
 pub fn get_mcp_servers_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
     let data_dir = app
@@ -18,7 +52,7 @@ pub fn get_mcp_servers_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError>
         .app_data_dir()
         .map_err(|e| AppError::NotFound(e.to_string()))?;
     fs::create_dir_all(&data_dir)?;
-    Ok(data_dir.join("mcp_servers.json"))
+    Ok(data_dir.join(MCP_SERVERS_FILE))
 }
 
 /// Load MCP servers from storage
@@ -103,6 +103,9 @@ pub struct MCPExportResult {
     pub file_path: Option<String>,
     pub server_count: usize,
     pub error: Option<String>,
+    /// Fields blanked by a safe-share export, as `server.env.NAME`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stripped_fields: Vec<String>,
 }
 
 /// External MCP config source info
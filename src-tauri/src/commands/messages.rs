@@ -0,0 +1,362 @@
+//! Localized backend messages
+//!
+//! Text the backend shows or returns to the user (error prefixes, native
+//! dialogs) comes from `MESSAGES`, keyed by error code or status id, so it
+//! matches the language of the UI. The frontend sets the locale with
+//! `set_backend_locale`; "system" follows the system locale. The choice is
+//! kept in the `general.backendLocale` setting and loaded at startup.
+//! Messages missing from a locale fall back to English.
+
+use crate::commands::settings::{change_setting, setting};
+use crate::commands::system::normalize_locale;
+use crate::error::AppError;
+use serde::Serialize;
+use std::sync::RwLock;
+
+/// Setting holding the chosen locale
+pub const BACKEND_LOCALE_SETTING: &str = "general.backendLocale";
+
+/// Locale currently used for messages
+static BACKEND_LOCALE: RwLock<Locale> = RwLock::new(Locale::En);
+
+/// Messages by id: error codes of `AppError`, then dotted status ids
+pub const MESSAGES: &[Message] = &[
+    Message {
+        id: "keyring",
+        en: "Keyring error",
+        zh_cn: "密钥环错误",
+    },
+    Message {
+        id: "io",
+        en: "IO error",
+        zh_cn: "IO 错误",
+    },
+    Message {
+        id: "json",
+        en: "JSON error",
+        zh_cn: "JSON 错误",
+    },
+    Message {
+        id: "http",
+        en: "HTTP error",
+        zh_cn: "HTTP 错误",
+    },
+    Message {
+        id: "mcp",
+        en: "MCP error",
+        zh_cn: "MCP 错误",
+    },
+    Message {
+        id: "not_found",
+        en: "Not found",
+        zh_cn: "未找到",
+    },
+    Message {
+        id: "invalid_input",
+        en: "Invalid input",
+        zh_cn: "输入无效",
+    },
+    Message {
+        id: "database",
+        en: "Database error",
+        zh_cn: "数据库错误",
+    },
+    Message {
+        id: "internal",
+        en: "Internal error",
+        zh_cn: "内部错误",
+    },
+    Message {
+        id: "permission_denied",
+        en: "Permission denied",
+        zh_cn: "权限被拒绝",
+    },
+    Message {
+        id: "invalid_output",
+        en: "Invalid model output",
+        zh_cn: "模型输出无效",
+    },
+    Message {
+        id: "permission.title",
+        en: "Permission request",
+        zh_cn: "权限请求",
+    },
+    Message {
+        id: "permission.allow",
+        en: "Allow",
+        zh_cn: "允许",
+    },
+    Message {
+        id: "permission.deny",
+        en: "Deny",
+        zh_cn: "拒绝",
+    },
+    Message {
+        id: "permission.ask",
+        en: "Allow the app to {0}?\n\nCommand: {1}\nFrom: {2}",
+        zh_cn: "是否允许应用{0}？\n\n命令：{1}\n来源：{2}",
+    },
+    Message {
+        id: "permission.change",
+        en: "Change the permission to {0} from {1} to {2}?",
+        zh_cn: "是否将“{0}”的权限从 {1} 改为 {2}？",
+    },
+    Message {
+        id: "permission.not_allowed",
+        en: "{0} ({1}) is not allowed",
+        zh_cn: "不允许 {0}（{1}）",
+    },
+    Message {
+        id: "permission.not_confirmed",
+        en: "Permission change was not confirmed",
+        zh_cn: "权限更改未被确认",
+    },
+    Message {
+        id: "permission.unknown_origin",
+        en: "unknown",
+        zh_cn: "未知",
+    },
+    Message {
+        id: "feature.files.delete",
+        en: "delete files",
+        zh_cn: "删除文件",
+    },
+    Message {
+        id: "feature.mcp.tools",
+        en: "call MCP server tools",
+        zh_cn: "调用 MCP 服务器工具",
+    },
+    Message {
+        id: "feature.keys.read",
+        en: "read stored API keys",
+        zh_cn: "读取已保存的 API 密钥",
+    },
+    Message {
+        id: "feature.keys.write",
+        en: "change or delete stored API keys",
+        zh_cn: "更改或删除已保存的 API 密钥",
+    },
+];
+
+/// Error codes of `AppError`, in the order their messages are matched
+pub const ERROR_CODES: &[&str] = &[
+    "keyring",
+    "io",
+    "json",
+    "http",
+    "mcp",
+    "not_found",
+    "invalid_input",
+    "database",
+    "internal",
+    "permission_denied",
+    "invalid_output",
+];
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Locales with a catalog
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Locale {
+    #[serde(rename = "en")]
+    En,
+    #[serde(rename = "zh-CN")]
+    ZhCn,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Message {
+    pub id: &'static str,
+    pub en: &'static str,
+    pub zh_cn: &'static str,
+}
+
+/// The chosen locale and the catalog it resolves to
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendLocale {
+    /// "system" or a catalog locale
+    pub setting: String,
+    pub locale: Locale,
+}
+
+impl Locale {
+    /// Catalog for a BCP-47 tag, by language; none for unsupported languages
+    pub fn from_tag(tag: &str) -> Option<Locale> {
+        let tag = normalize_locale(tag)?.to_ascii_lowercase();
+        match tag.split('-').next().unwrap_or_default() {
+            "en" => Some(Locale::En),
+            "zh" => Some(Locale::ZhCn),
+            _ => None,
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::ZhCn => "zh-CN",
+        }
+    }
+
+    fn text(self, message: &Message) -> &'static str {
+        match self {
+            Locale::En => message.en,
+            Locale::ZhCn => message.zh_cn,
+        }
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn current_locale() -> Locale {
+    BACKEND_LOCALE.read().map_or(Locale::En, |locale| *locale)
+}
+
+fn set_current_locale(locale: Locale) {
+    if let Ok(mut current) = BACKEND_LOCALE.write() {
+        *current = locale;
+    }
+}
+
+/// Catalog of the first supported system locale, else English
+fn system_locale() -> Locale {
+    sys_locale::get_locales()
+        .find_map(|tag| Locale::from_tag(&tag))
+        .unwrap_or(Locale::En)
+}
+
+/// Catalog for a setting value
+fn resolve_locale(setting: &str) -> Locale {
+    match setting {
+        "system" => system_locale(),
+        tag => Locale::from_tag(tag).unwrap_or(Locale::En),
+    }
+}
+
+/// Message in a locale; unknown ids are returned as they are
+pub fn message_in(locale: Locale, id: &str) -> String {
+    MESSAGES
+        .iter()
+        .find(|message| message.id == id)
+        .map(|message| {
+            let text = locale.text(message);
+            if text.is_empty() { message.en } else { text }.to_string()
+        })
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// Message in the current locale
+pub fn message(id: &str) -> String {
+    message_in(current_locale(), id)
+}
+
+/// Message in the current locale with `{0}`, `{1}`, ... replaced by `args`
+pub fn format_message(id: &str, args: &[&str]) -> String {
+    args.iter().enumerate().fold(message(id), |text, (i, arg)| {
+        text.replace(&format!("{{{}}}", i), arg)
+    })
+}
+
+/// Error code of an `AppError` message in any locale, from the prefix it
+/// starts with; prefixes of errors wrapped in its detail do not count
+pub fn error_code_of(text: &str) -> Option<&'static str> {
+    ERROR_CODES.iter().copied().find(|code| {
+        [Locale::En, Locale::ZhCn]
+            .iter()
+            .any(|locale| text.starts_with(&format!("{}: ", message_in(*locale, code))))
+    })
+}
+
+/// Load the chosen locale at startup
+pub fn init_backend_locale(app: &tauri::AppHandle) {
+    let chosen: String = setting(app, BACKEND_LOCALE_SETTING).unwrap_or_else(|e| {
+        log::warn!("Failed to load the backend locale: {}", e);
+        "system".to_string()
+    });
+    let locale = resolve_locale(&chosen);
+    set_current_locale(locale);
+    log::info!("Backend locale: {} ({})", locale.tag(), chosen);
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the chosen locale and the catalog in use
+#[tauri::command]
+pub fn get_backend_locale(app: tauri::AppHandle) -> Result<BackendLocale, AppError> {
+    let chosen: String = setting(&app, BACKEND_LOCALE_SETTING)?;
+    Ok(BackendLocale {
+        locale: current_locale(),
+        setting: chosen,
+    })
+}
+
+/// Set the locale of backend messages: "system" or a tag such as "zh-CN"
+#[tauri::command]
+pub fn set_backend_locale(
+    app: tauri::AppHandle,
+    locale: String,
+) -> Result<BackendLocale, AppError> {
+    let chosen = match locale.trim() {
+        "system" => "system".to_string(),
+        tag => Locale::from_tag(tag)
+            .ok_or_else(|| AppError::InvalidInput(format!("Unsupported locale: {}", tag)))?
+            .tag()
+            .to_string(),
+    };
+    change_setting(
+        &app,
+        BACKEND_LOCALE_SETTING,
+        Some(serde_json::Value::String(chosen.clone())),
+    )?;
+    let resolved = resolve_locale(&chosen);
+    set_current_locale(resolved);
+    log::info!("Backend locale set to {} ({})", resolved.tag(), chosen);
+    Ok(BackendLocale {
+        setting: chosen,
+        locale: resolved,
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_resolve_by_locale() {
+        assert_eq!(Locale::from_tag("zh_CN.UTF-8"), Some(Locale::ZhCn));
+        assert_eq!(Locale::from_tag("zh-Hant-TW"), Some(Locale::ZhCn));
+        assert_eq!(Locale::from_tag("en-GB"), Some(Locale::En));
+        assert_eq!(Locale::from_tag("fr-FR"), None);
+        assert_eq!(Locale::from_tag("C"), None);
+
+        assert_eq!(message_in(Locale::ZhCn, "not_found"), "未找到");
+        assert_eq!(message_in(Locale::En, "not_found"), "Not found");
+        assert_eq!(message_in(Locale::ZhCn, "no.such.id"), "no.such.id");
+        // Every message has both languages
+        for message in MESSAGES {
+            assert!(!message.en.is_empty() && !message.zh_cn.is_empty());
+        }
+        for code in ERROR_CODES {
+            assert!(MESSAGES.iter().any(|message| message.id == *code));
+        }
+
+        assert_eq!(error_code_of("Not found: book 1"), Some("not_found"));
+        assert_eq!(error_code_of("数据库错误: locked"), Some("database"));
+        assert_eq!(error_code_of("something else"), None);
+        assert_eq!(
+            error_code_of("Internal error: Not found: book 1"),
+            Some("internal")
+        );
+        assert_eq!(error_code_of("Import failed, Not found: book 1"), None);
+    }
+}
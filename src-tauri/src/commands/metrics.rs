@@ -0,0 +1,489 @@
+//! Timing metrics of Tauri commands
+//!
+//! Tauri (built with its `tracing` feature) opens an `ipc::request::handle`
+//! span for every command invocation and closes it once the response is
+//! sent, so its lifetime is the time the frontend waited. The subscriber
+//! installed here follows those spans, along with the request size recorded
+//! on `ipc::request` and any error recorded on `ipc::request::response`, and
+//! keeps per-command totals in memory. Every other span is ignored and every
+//! event is passed on to the log, as tracing does when no subscriber is set.
+
+use crate::commands::messages;
+use crate::error::AppError;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
+
+const REQUEST_SPAN: &str = "ipc::request";
+const COMMAND_SPAN: &str = "ipc::request::handle";
+const RESPOND_SPAN: &str = "ipc::request::respond";
+const RESPONSE_SPAN: &str = "ipc::request::response";
+
+/// Totals of every command invoked since startup (or the last reset)
+static COMMAND_STATS: Mutex<BTreeMap<String, CommandStats>> = Mutex::new(BTreeMap::new());
+
+thread_local! {
+    /// Tracked spans entered on this thread, innermost last
+    static CURRENT_SPANS: RefCell<Vec<Id>> = const { RefCell::new(Vec::new()) };
+}
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Default, Clone, Debug)]
+struct CommandStats {
+    calls: u64,
+    errors: u64,
+    total: Duration,
+    max: Duration,
+    last: Duration,
+    total_arg_bytes: u64,
+    max_arg_bytes: u64,
+    error_codes: BTreeMap<String, u64>,
+}
+
+/// Timing of one command
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandMetrics {
+    pub command: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub total_ms: f64,
+    pub average_ms: f64,
+    pub max_ms: f64,
+    pub last_ms: f64,
+    /// Size of the JSON arguments
+    pub average_arg_bytes: u64,
+    pub max_arg_bytes: u64,
+    /// Failed calls by error code
+    pub error_codes: BTreeMap<String, u64>,
+}
+
+enum SpanKind {
+    Request {
+        arg_bytes: Option<u64>,
+    },
+    Command {
+        name: String,
+        arg_bytes: Option<u64>,
+        started: Instant,
+        error: Option<String>,
+    },
+    /// Sending the response of the command span
+    Respond {
+        command: Id,
+    },
+}
+
+struct TrackedSpan {
+    refs: usize,
+    kind: SpanKind,
+}
+
+/// Follows command spans and forwards events to the log
+#[derive(Default)]
+pub struct CommandMetricsSubscriber {
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, TrackedSpan>>,
+}
+
+/// Collects the fields of a span or event
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.fields.push((field.name(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            self.fields.push((field.name(), format!("{:?}", value)));
+        }
+    }
+}
+
+impl FieldVisitor {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn is_tracked_span(name: &str) -> bool {
+    matches!(
+        name,
+        REQUEST_SPAN | COMMAND_SPAN | RESPOND_SPAN | RESPONSE_SPAN
+    )
+}
+
+fn log_level(level: &tracing::Level) -> log::Level {
+    if *level == tracing::Level::ERROR {
+        log::Level::Error
+    } else if *level == tracing::Level::WARN {
+        log::Level::Warn
+    } else if *level == tracing::Level::INFO {
+        log::Level::Info
+    } else if *level == tracing::Level::DEBUG {
+        log::Level::Debug
+    } else {
+        log::Level::Trace
+    }
+}
+
+/// Error code of a failed response, from the `AppError` message it carries;
+/// the response span records it as `InvokeError(String("<message>"))`
+pub fn error_code(error: &str) -> &'static str {
+    error
+        .strip_prefix("InvokeError(String(\"")
+        .and_then(messages::error_code_of)
+        .unwrap_or("other")
+}
+
+fn record_command(name: String, elapsed: Duration, arg_bytes: u64, error: Option<String>) {
+    let Ok(mut stats) = COMMAND_STATS.lock() else {
+        return;
+    };
+    let stats = stats.entry(name).or_default();
+    stats.calls += 1;
+    stats.total += elapsed;
+    stats.max = stats.max.max(elapsed);
+    stats.last = elapsed;
+    stats.total_arg_bytes += arg_bytes;
+    stats.max_arg_bytes = stats.max_arg_bytes.max(arg_bytes);
+    if let Some(code) = error {
+        stats.errors += 1;
+        *stats.error_codes.entry(code).or_default() += 1;
+    }
+}
+
+fn to_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Metrics of every invoked command, slowest in total first
+pub fn command_metrics() -> Vec<CommandMetrics> {
+    let Ok(stats) = COMMAND_STATS.lock() else {
+        return Vec::new();
+    };
+    let mut metrics: Vec<CommandMetrics> = stats
+        .iter()
+        .map(|(command, stats)| CommandMetrics {
+            command: command.clone(),
+            calls: stats.calls,
+            errors: stats.errors,
+            total_ms: to_millis(stats.total),
+            average_ms: to_millis(stats.total) / stats.calls.max(1) as f64,
+            max_ms: to_millis(stats.max),
+            last_ms: to_millis(stats.last),
+            average_arg_bytes: stats.total_arg_bytes / stats.calls.max(1),
+            max_arg_bytes: stats.max_arg_bytes,
+            error_codes: stats.error_codes.clone(),
+        })
+        .collect();
+    metrics.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    metrics
+}
+
+/// Install the subscriber; call before any command can be invoked
+pub fn install_command_metrics() {
+    if tracing::subscriber::set_global_default(CommandMetricsSubscriber::default()).is_err() {
+        log::warn!("A tracing subscriber is already installed; command metrics are off");
+    }
+}
+
+impl CommandMetricsSubscriber {
+    fn current_span() -> Option<Id> {
+        CURRENT_SPANS.with(|spans| spans.borrow().last().cloned())
+    }
+}
+
+impl Subscriber for CommandMetricsSubscriber {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.is_span() {
+            if is_tracked_span(metadata.name()) {
+                Interest::always()
+            } else {
+                Interest::never()
+            }
+        } else {
+            // The log level can change while the app runs
+            Interest::sometimes()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        if metadata.is_span() {
+            return is_tracked_span(metadata.name());
+        }
+        log::logger().enabled(
+            &log::Metadata::builder()
+                .level(log_level(metadata.level()))
+                .target(metadata.target())
+                .build(),
+        )
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let parent = if attrs.is_contextual() {
+            Self::current_span()
+        } else {
+            attrs.parent().cloned()
+        };
+        let mut fields = FieldVisitor::default();
+        attrs.record(&mut fields);
+        let Ok(mut spans) = self.spans.lock() else {
+            return id;
+        };
+        let parent = parent.and_then(|parent| spans.get(&parent.into_u64()).map(|s| (parent, s)));
+        let kind = match attrs.metadata().name() {
+            REQUEST_SPAN => SpanKind::Request { arg_bytes: None },
+            COMMAND_SPAN => SpanKind::Command {
+                name: fields.field("cmd").unwrap_or_default().to_string(),
+                arg_bytes: match parent {
+                    Some((
+                        _,
+                        TrackedSpan {
+                            kind: SpanKind::Request { arg_bytes },
+                            ..
+                        },
+                    )) => *arg_bytes,
+                    _ => None,
+                },
+                started: Instant::now(),
+                error: None,
+            },
+            RESPOND_SPAN => match parent {
+                Some((command, _)) => SpanKind::Respond { command },
+                None => return id,
+            },
+            RESPONSE_SPAN => {
+                let command = match parent {
+                    Some((
+                        _,
+                        TrackedSpan {
+                            kind: SpanKind::Respond { command },
+                            ..
+                        },
+                    )) => command.clone(),
+                    _ => return id,
+                };
+                if let Some(error) = fields.field("error") {
+                    let code = error_code(error).to_string();
+                    if let Some(TrackedSpan {
+                        kind: SpanKind::Command { error, .. },
+                        ..
+                    }) = spans.get_mut(&command.into_u64())
+                    {
+                        *error = Some(code);
+                    }
+                }
+                return id;
+            }
+            _ => return id,
+        };
+        spans.insert(id.into_u64(), TrackedSpan { refs: 1, kind });
+        id
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = FieldVisitor::default();
+        values.record(&mut fields);
+        let Some(request) = fields.field("request") else {
+            return;
+        };
+        if let Ok(mut spans) = self.spans.lock() {
+            if let Some(TrackedSpan {
+                kind: SpanKind::Request { arg_bytes },
+                ..
+            }) = spans.get_mut(&span.into_u64())
+            {
+                *arg_bytes = Some(request.len() as u64);
+            }
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut fields = FieldVisitor::default();
+        event.record(&mut fields);
+        let mut message = fields.message;
+        for (name, value) in &fields.fields {
+            let _ = write!(message, " {}={}", name, value);
+        }
+        log::logger().log(
+            &log::Record::builder()
+                .level(log_level(metadata.level()))
+                .target(metadata.target())
+                .module_path(metadata.module_path())
+                .file(metadata.file())
+                .line(metadata.line())
+                .args(format_args!("{}", message.trim_start()))
+                .build(),
+        );
+    }
+
+    fn enter(&self, span: &Id) {
+        CURRENT_SPANS.with(|spans| spans.borrow_mut().push(span.clone()));
+    }
+
+    fn exit(&self, span: &Id) {
+        CURRENT_SPANS.with(|spans| {
+            let mut spans = spans.borrow_mut();
+            if let Some(position) = spans.iter().rposition(|id| id == span) {
+                spans.remove(position);
+            }
+        });
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Ok(mut spans) = self.spans.lock() {
+            if let Some(span) = spans.get_mut(&id.into_u64()) {
+                span.refs += 1;
+            }
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        let Ok(mut spans) = self.spans.lock() else {
+            return false;
+        };
+        let Some(span) = spans.get_mut(&id.into_u64()) else {
+            return true;
+        };
+        span.refs -= 1;
+        if span.refs > 0 {
+            return false;
+        }
+        let span = spans.remove(&id.into_u64());
+        drop(spans);
+        if let Some(TrackedSpan {
+            kind:
+                SpanKind::Command {
+                    name,
+                    arg_bytes,
+                    started,
+                    error,
+                },
+            ..
+        }) = span
+        {
+            record_command(name, started.elapsed(), arg_bytes.unwrap_or(0), error);
+        }
+        true
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get call counts, durations, argument sizes and errors of each command
+#[tauri::command]
+pub fn get_command_metrics() -> Vec<CommandMetrics> {
+    command_metrics()
+}
+
+/// Start collecting command metrics afresh
+#[tauri::command]
+pub fn reset_command_metrics() -> Result<(), AppError> {
+    COMMAND_STATS
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .clear();
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The spans Tauri opens for one invocation
+    fn invoke(cmd: &str, request: &str, error: Option<&str>) {
+        let request_span = tracing::trace_span!(
+            "ipc::request",
+            kind = "custom-protocol",
+            request = tracing::field::Empty
+        )
+        .entered();
+        request_span.record("request", request);
+        let command_span = tracing::trace_span!("ipc::request::handle", cmd = cmd);
+        drop(request_span);
+        {
+            let _respond =
+                tracing::trace_span!(parent: &command_span, "ipc::request::respond").entered();
+            let _response = match error {
+                Some(e) => tracing::trace_span!(
+                    "ipc::request::response",
+                    error = format!("InvokeError(String({:?}))", e)
+                )
+                .entered(),
+                None => tracing::trace_span!("ipc::request::response", response = "{}").entered(),
+            };
+        }
+        drop(command_span);
+    }
+
+    #[test]
+    fn command_spans_are_aggregated() {
+        tracing::subscriber::with_default(CommandMetricsSubscriber::default(), || {
+            invoke("metrics_test_scan", r#"{"path":"/books"}"#, None);
+            invoke("metrics_test_scan", "{}", Some("Not found: /books"));
+            invoke("metrics_test_other", "{}", Some("plugin failed"));
+            invoke(
+                "metrics_test_other",
+                "{}",
+                Some("Internal error: Not found: /books"),
+            );
+        });
+        let metrics = command_metrics();
+        let scan = metrics
+            .iter()
+            .find(|m| m.command == "metrics_test_scan")
+            .unwrap();
+        assert_eq!(scan.calls, 2);
+        assert_eq!(scan.errors, 1);
+        assert_eq!(scan.max_arg_bytes, 17);
+        assert_eq!(scan.error_codes.get("not_found"), Some(&1));
+        let other = metrics
+            .iter()
+            .find(|m| m.command == "metrics_test_other")
+            .unwrap();
+        assert_eq!(other.error_codes.get("other"), Some(&1));
+        assert_eq!(other.error_codes.get("internal"), Some(&1));
+        assert_eq!(other.error_codes.get("not_found"), None);
+    }
+}
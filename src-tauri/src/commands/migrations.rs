@@ -0,0 +1,562 @@
+//! Versioned upgrades of every persistent store
+//!
+//! Each store (SQLite database or JSON file in the app data directory) owns
+//! an ordered list of migrations, registered in `DATA_STORES`. At startup,
+//! before any store is opened, pending migrations run store by store. The
+//! store is first copied to `migration-backups/`, and copied back when a
+//! migration fails, so a failed upgrade leaves it as it was. A store that
+//! cannot be migrated never stops the app from starting: the failure is
+//! logged and recorded, and only the feature using that store is affected;
+//! such a database is then opened at its old version (`open_store_database`).
+//!
+//! Databases track their version with `PRAGMA user_version` (see `db`).
+//! JSON files have no room for one, so their versions are kept in
+//! `data_migrations.json`, with the history of every migration run.
+
+use crate::commands::ai_usage::{USAGE_STATS_FILE, USAGE_STATS_MIGRATIONS};
+use crate::commands::conversations::{CONVERSATIONS_DB_FILE, CONVERSATION_MIGRATIONS};
+use crate::commands::kv::{KV_DB_FILE, KV_MIGRATIONS};
+use crate::commands::library::{LIBRARY_DB_FILE, LIBRARY_MIGRATIONS};
+use crate::commands::mcp::{MCP_SERVERS_FILE, MCP_SERVERS_MIGRATIONS};
+use crate::db;
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+/// Applied versions and migration history, in the app data directory
+pub const MIGRATION_REGISTRY_FILE: &str = "data_migrations.json";
+
+/// Directory holding the copy of each store taken before it is migrated
+pub const MIGRATION_BACKUPS_DIR: &str = "migration-backups";
+
+/// Migration runs kept in the history
+const MAX_MIGRATION_HISTORY: usize = 100;
+
+/// An upgrade of a JSON store, applied to its parsed content
+pub type JsonMigration = fn(&mut serde_json::Value) -> Result<(), AppError>;
+
+/// Migrations of a store, in order; the position of a migration is the
+/// version it upgrades to, minus one
+pub enum StoreMigrations {
+    Database(&'static [&'static str]),
+    Json(&'static [JsonMigration]),
+}
+
+impl StoreMigrations {
+    /// Version of a store with all migrations applied
+    pub fn latest(&self) -> usize {
+        match self {
+            StoreMigrations::Database(migrations) => migrations.len(),
+            StoreMigrations::Json(migrations) => migrations.len(),
+        }
+    }
+}
+
+/// A persistent store in the app data directory
+pub struct DataStore {
+    pub name: &'static str,
+    pub file: &'static str,
+    pub migrations: StoreMigrations,
+}
+
+/// Every persistent store and its migrations
+pub const DATA_STORES: &[DataStore] = &[
+    DataStore {
+        name: "library",
+        file: LIBRARY_DB_FILE,
+        migrations: StoreMigrations::Database(LIBRARY_MIGRATIONS),
+    },
+    DataStore {
+        name: "conversations",
+        file: CONVERSATIONS_DB_FILE,
+        migrations: StoreMigrations::Database(CONVERSATION_MIGRATIONS),
+    },
+    DataStore {
+        name: "mcp",
+        file: MCP_SERVERS_FILE,
+        migrations: StoreMigrations::Json(MCP_SERVERS_MIGRATIONS),
+    },
+    DataStore {
+        name: "usage",
+        file: USAGE_STATS_FILE,
+        migrations: StoreMigrations::Json(USAGE_STATS_MIGRATIONS),
+    },
+    DataStore {
+        name: "kv",
+        file: KV_DB_FILE,
+        migrations: StoreMigrations::Database(KV_MIGRATIONS),
+    },
+];
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Stored as `data_migrations.json`
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationRegistry {
+    /// Applied version of each store, by name
+    pub stores: BTreeMap<String, usize>,
+    /// Migration runs, oldest first
+    pub history: Vec<MigrationRun>,
+}
+
+/// One run of the pending migrations of a store
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationRun {
+    pub store: String,
+    pub from_version: usize,
+    pub to_version: usize,
+    pub started_at: i64,
+    /// Set when a migration failed and the store was restored from its copy
+    pub error: Option<String>,
+}
+
+/// Version of a store compared to what this app supports
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreMigrationStatus {
+    pub store: String,
+    pub file: String,
+    /// None when the store does not exist yet
+    pub version: Option<usize>,
+    pub latest: usize,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataMigrationStatus {
+    pub stores: Vec<StoreMigrationStatus>,
+    pub history: Vec<MigrationRun>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn get_app_data_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))
+}
+
+pub fn load_migration_registry(app_data: &Path) -> Result<MigrationRegistry, AppError> {
+    let path = app_data.join(MIGRATION_REGISTRY_FILE);
+    if !path.exists() {
+        return Ok(MigrationRegistry::default());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Load the registry for a migration run; an unreadable one is moved aside
+/// and replaced by an empty one, so JSON stores are migrated from their
+/// first version again
+fn load_or_reset_migration_registry(app_data: &Path, now: i64) -> MigrationRegistry {
+    load_migration_registry(app_data).unwrap_or_else(|e| {
+        let path = app_data.join(MIGRATION_REGISTRY_FILE);
+        let aside = path.with_extension(format!("json.corrupt-{}", now));
+        log::error!(
+            "Unreadable migration registry moved to {}: {}",
+            aside.display(),
+            e
+        );
+        if let Err(e) = fs::rename(&path, &aside) {
+            log::error!("Failed to move the migration registry aside: {}", e);
+        }
+        MigrationRegistry::default()
+    })
+}
+
+fn save_migration_registry(app_data: &Path, registry: &MigrationRegistry) -> Result<(), AppError> {
+    write_atomically(
+        &app_data.join(MIGRATION_REGISTRY_FILE),
+        serde_json::to_string_pretty(registry)?.as_bytes(),
+    )
+}
+
+fn write_atomically(path: &Path, data: &[u8]) -> Result<(), AppError> {
+    let temp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    fs::write(&temp_path, data)?;
+    fs::rename(&temp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })?;
+    Ok(())
+}
+
+/// Current version of a store, None when it does not exist
+fn store_version(
+    app_data: &Path,
+    store: &DataStore,
+    registry: &MigrationRegistry,
+) -> Result<Option<usize>, AppError> {
+    let path = app_data.join(store.file);
+    if !path.exists() {
+        return Ok(None);
+    }
+    match store.migrations {
+        StoreMigrations::Database(_) => Ok(Some(db::schema_version(&Connection::open(path)?)?)),
+        StoreMigrations::Json(_) => Ok(Some(registry.stores.get(store.name).copied().unwrap_or(0))),
+    }
+}
+
+/// Copy a store before it is migrated, replacing an earlier copy
+fn backup_store(app_data: &Path, store: &DataStore) -> Result<PathBuf, AppError> {
+    let dir = app_data.join(MIGRATION_BACKUPS_DIR);
+    fs::create_dir_all(&dir)?;
+    let backup = dir.join(store.file);
+    if backup.exists() {
+        fs::remove_file(&backup)?;
+    }
+    let path = app_data.join(store.file);
+    match store.migrations {
+        // Includes what is still in the write-ahead log
+        StoreMigrations::Database(_) => {
+            Connection::open(&path)?
+                .execute("VACUUM INTO ?1", params![backup.to_string_lossy()])?;
+        }
+        StoreMigrations::Json(_) => {
+            fs::copy(&path, &backup)?;
+        }
+    }
+    Ok(backup)
+}
+
+/// Put back the copy of a store taken before a failed migration
+fn roll_back_store(app_data: &Path, store: &DataStore, backup: &Path) -> Result<(), AppError> {
+    let path = app_data.join(store.file);
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = path.clone().into_os_string();
+        sidecar.push(suffix);
+        let _ = fs::remove_file(PathBuf::from(sidecar));
+    }
+    fs::copy(backup, &path)?;
+    Ok(())
+}
+
+fn migrate_json(path: &Path, migrations: &[JsonMigration], from: usize) -> Result<(), AppError> {
+    let mut value: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    for migration in migrations.iter().skip(from) {
+        migration(&mut value)?;
+    }
+    write_atomically(path, serde_json::to_string_pretty(&value)?.as_bytes())
+}
+
+fn migrate_store(app_data: &Path, store: &DataStore, from: usize) -> Result<(), AppError> {
+    let path = app_data.join(store.file);
+    match store.migrations {
+        StoreMigrations::Database(migrations) => {
+            db::open_database(&path, migrations)?;
+            Ok(())
+        }
+        StoreMigrations::Json(migrations) => migrate_json(&path, migrations, from),
+    }
+}
+
+/// Back up a store and run its pending migrations, restoring the copy when
+/// one fails; returns the error of the run
+fn migrate_with_backup(app_data: &Path, store: &DataStore, from: usize) -> Option<String> {
+    let backup = match backup_store(app_data, store) {
+        Ok(backup) => backup,
+        Err(e) => {
+            log::error!("Cannot back up {}, not migrating it: {}", store.name, e);
+            return Some(e.to_string());
+        }
+    };
+    let e = migrate_store(app_data, store, from).err()?;
+    log::error!("Migration of {} failed, rolling back: {}", store.name, e);
+    if let Err(rollback) = roll_back_store(app_data, store, &backup) {
+        log::error!(
+            "Rolling back {} failed, its copy is in {}: {}",
+            store.name,
+            backup.display(),
+            rollback
+        );
+    }
+    Some(e.to_string())
+}
+
+/// Run the pending migrations of every store in `app_data`
+///
+/// A store whose migration fails is restored from its copy and left at its
+/// old version, and a store whose version cannot be read is left alone; the
+/// other stores are still migrated. Runs, failed ones with their error, are
+/// recorded in the registry and returned. Fails only when the registry
+/// cannot be saved.
+pub fn run_pending_migrations_in(
+    app_data: &Path,
+    stores: &[DataStore],
+    now: i64,
+) -> Result<Vec<MigrationRun>, AppError> {
+    fs::create_dir_all(app_data)?;
+    let mut registry = load_or_reset_migration_registry(app_data, now);
+    let mut runs = Vec::new();
+    for store in stores {
+        let latest = store.migrations.latest();
+        let version = match store_version(app_data, store, &registry) {
+            Ok(Some(version)) => version,
+            Ok(None) => {
+                // New stores are created with the latest version
+                registry.stores.insert(store.name.to_string(), latest);
+                continue;
+            }
+            Err(e) => {
+                log::error!(
+                    "Cannot read the version of {}, leaving it as it is: {}",
+                    store.name,
+                    e
+                );
+                continue;
+            }
+        };
+        if version >= latest {
+            if version > latest {
+                log::warn!(
+                    "Store {} has version {}, newer than this app supports ({})",
+                    store.name,
+                    version,
+                    latest
+                );
+            }
+            registry.stores.insert(store.name.to_string(), version);
+            continue;
+        }
+
+        let error = migrate_with_backup(app_data, store, version);
+        if error.is_none() {
+            log::info!("Migrated {} from v{} to v{}", store.name, version, latest);
+        }
+        registry.stores.insert(
+            store.name.to_string(),
+            if error.is_none() { latest } else { version },
+        );
+        runs.push(MigrationRun {
+            store: store.name.to_string(),
+            from_version: version,
+            to_version: latest,
+            started_at: now,
+            error,
+        });
+    }
+    registry.history.extend(runs.iter().cloned());
+    let excess = registry.history.len().saturating_sub(MAX_MIGRATION_HISTORY);
+    registry.history.drain(..excess);
+    save_migration_registry(app_data, &registry)?;
+    Ok(runs)
+}
+
+/// Whether the last migration run of a store failed
+fn last_migration_failed(app_data: &Path, store: &str) -> bool {
+    load_migration_registry(app_data).is_ok_and(|registry| {
+        registry
+            .history
+            .iter()
+            .rev()
+            .find(|run| run.store == store)
+            .is_some_and(|run| run.error.is_some())
+    })
+}
+
+/// Open a database store, bringing its schema up to date
+///
+/// A store whose last migration failed was rolled back to its old version.
+/// It is opened as it is rather than migrated again, so the app still starts
+/// and only the features using the store report errors.
+pub fn open_store_database(
+    path: &Path,
+    store: &str,
+    migrations: &[&str],
+) -> Result<Connection, AppError> {
+    let failed = path
+        .parent()
+        .is_some_and(|app_data| last_migration_failed(app_data, store));
+    if failed {
+        log::error!(
+            "Opening {} without migrating it, its last migration failed",
+            store
+        );
+        return db::open_connection(path);
+    }
+    db::open_database(path, migrations)
+}
+
+/// Run the pending migrations of every store, before any of them is opened;
+/// failures are logged, and never stop startup
+pub fn run_startup_migrations(app: &tauri::AppHandle) {
+    let migrated = get_app_data_dir(app).and_then(|app_data| {
+        run_pending_migrations_in(&app_data, DATA_STORES, chrono::Utc::now().timestamp())
+    });
+    match migrated {
+        Ok(runs) => {
+            let failed = runs.iter().filter(|run| run.error.is_some()).count();
+            if failed > 0 {
+                log::error!("{} of {} store migrations failed", failed, runs.len());
+            }
+        }
+        Err(e) => log::error!("Data migrations could not run: {}", e),
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the version of every store and the migration history
+#[tauri::command]
+pub fn get_data_migration_status(app: tauri::AppHandle) -> Result<DataMigrationStatus, AppError> {
+    let app_data = get_app_data_dir(&app)?;
+    let registry = load_migration_registry(&app_data)?;
+    let mut stores = Vec::with_capacity(DATA_STORES.len());
+    for store in DATA_STORES {
+        stores.push(StoreMigrationStatus {
+            store: store.name.to_string(),
+            file: store.file.to_string(),
+            version: store_version(&app_data, store, &registry)?,
+            latest: store.migrations.latest(),
+        });
+    }
+    Ok(DataMigrationStatus {
+        stores,
+        history: registry.history,
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const ITEMS_MIGRATIONS: &[&str] = &[
+        "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+        "ALTER TABLE items ADD COLUMN note TEXT;",
+        "INSERT INTO missing_table VALUES (1);",
+    ];
+
+    fn add_count(value: &mut serde_json::Value) -> Result<(), AppError> {
+        value["count"] = serde_json::json!(0);
+        Ok(())
+    }
+
+    fn fail(_: &mut serde_json::Value) -> Result<(), AppError> {
+        Err(AppError::InvalidInput("unsupported".to_string()))
+    }
+
+    const SETTINGS_MIGRATIONS: &[JsonMigration] = &[add_count];
+    const BROKEN_MIGRATIONS: &[JsonMigration] = &[add_count, fail];
+
+    fn stores(db_migrations: usize, json: &'static [JsonMigration]) -> [DataStore; 2] {
+        [
+            DataStore {
+                name: "items",
+                file: "items.db",
+                migrations: StoreMigrations::Database(&ITEMS_MIGRATIONS[..db_migrations]),
+            },
+            DataStore {
+                name: "settings",
+                file: "settings.json",
+                migrations: StoreMigrations::Json(json),
+            },
+        ]
+    }
+
+    #[test]
+    fn pending_migrations_run_once_and_roll_back_on_failure() {
+        let dir = tempdir().unwrap();
+        let app_data = dir.path();
+        db::open_database(&app_data.join("items.db"), &ITEMS_MIGRATIONS[..1])
+            .unwrap()
+            .execute("INSERT INTO items (name) VALUES ('kept')", [])
+            .unwrap();
+        fs::write(app_data.join("settings.json"), r#"{"theme":"dark"}"#).unwrap();
+
+        let runs =
+            run_pending_migrations_in(app_data, &stores(2, SETTINGS_MIGRATIONS), 10).unwrap();
+        assert_eq!(
+            runs.iter()
+                .map(|run| (run.store.as_str(), run.from_version, run.to_version))
+                .collect::<Vec<_>>(),
+            vec![("items", 1, 2), ("settings", 0, 1)]
+        );
+        let settings: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(app_data.join("settings.json")).unwrap())
+                .unwrap();
+        assert_eq!(settings["count"], 0);
+        assert_eq!(settings["theme"], "dark");
+        assert!(
+            run_pending_migrations_in(app_data, &stores(2, SETTINGS_MIGRATIONS), 20)
+                .unwrap()
+                .is_empty()
+        );
+
+        // Failing migrations leave both stores as they were
+        let runs = run_pending_migrations_in(app_data, &stores(3, BROKEN_MIGRATIONS), 30).unwrap();
+        assert!(runs.iter().all(|run| run.error.is_some()));
+        let conn = Connection::open(app_data.join("items.db")).unwrap();
+        assert_eq!(db::schema_version(&conn).unwrap(), 2);
+        let name: String = conn
+            .query_row("SELECT name FROM items", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(name, "kept");
+        let registry = load_migration_registry(app_data).unwrap();
+        assert_eq!(registry.stores["items"], 2);
+        assert_eq!(registry.stores["settings"], 1);
+        assert_eq!(registry.history.len(), 4);
+        assert!(registry.history[2..].iter().all(|run| run.error.is_some()));
+    }
+
+    #[test]
+    fn stores_that_failed_to_migrate_still_open() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("items.db");
+        db::open_database(&path, &ITEMS_MIGRATIONS[..2])
+            .unwrap()
+            .execute("INSERT INTO items (name) VALUES ('kept')", [])
+            .unwrap();
+        assert!(open_store_database(&path, "items", ITEMS_MIGRATIONS).is_err());
+
+        let runs =
+            run_pending_migrations_in(dir.path(), &stores(3, SETTINGS_MIGRATIONS), 10).unwrap();
+        assert!(runs[0].error.is_some());
+
+        let conn = open_store_database(&path, "items", ITEMS_MIGRATIONS).unwrap();
+        assert_eq!(db::schema_version(&conn).unwrap(), 2);
+        let name: String = conn
+            .query_row("SELECT name FROM items", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(name, "kept");
+    }
+
+    #[test]
+    fn corrupt_stores_and_registry_do_not_stop_migrations() {
+        let dir = tempdir().unwrap();
+        let app_data = dir.path();
+        fs::write(app_data.join("items.db"), "not a database").unwrap();
+        fs::write(app_data.join("settings.json"), "{ truncated").unwrap();
+        fs::write(app_data.join(MIGRATION_REGISTRY_FILE), "[").unwrap();
+
+        let runs =
+            run_pending_migrations_in(app_data, &stores(2, SETTINGS_MIGRATIONS), 10).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].store, "settings");
+        assert!(runs[0].error.is_some());
+        assert_eq!(
+            fs::read_to_string(app_data.join("settings.json")).unwrap(),
+            "{ truncated"
+        );
+        assert!(app_data
+            .join(format!("{}.corrupt-10", MIGRATION_REGISTRY_FILE))
+            .exists());
+        let registry = load_migration_registry(app_data).unwrap();
+        assert_eq!(registry.stores["settings"], 0);
+        assert!(!registry.stores.contains_key("items"));
+    }
+}
@@ -3,14 +3,108 @@
 pub mod system;
 pub mod file_ops;
 pub mod ai_keys;
+pub mod ai_providers;
+pub mod ai_failover;
+pub mod ai_headers;
+pub mod ai_moderation;
 pub mod ai_usage;
+pub mod ai_pricing;
 pub mod ai_proxy;
+pub mod ai_batch;
+pub mod ai_structured;
+pub mod ai_images;
+pub mod ai_debug_log;
+pub mod ai_summaries;
+pub mod ai_context;
+pub mod conversations;
 pub mod mcp;
+pub mod library;
+pub mod pdf;
+pub mod epub;
+pub mod thumbnails;
+pub mod annotations;
+pub mod conversion;
+pub mod clipper;
+pub mod feeds;
+pub mod dictionary;
+pub mod tts;
+pub mod rag;
+pub mod backup;
+pub mod lan_sync;
+pub mod migrations;
+pub mod data_usage;
+pub mod network;
+pub mod appearance;
+pub mod power;
+pub mod diagnostics;
+pub mod crash;
+pub mod settings;
+pub mod kv;
+pub mod metrics;
+pub mod permissions;
+pub mod messages;
+pub mod events;
+pub mod self_test;
+pub mod startup;
+pub mod logging;
+pub mod retry;
+pub mod hex;
+#[cfg(feature = "local-inference")]
+pub mod local_inference;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub mod updates;
 
 // Re-export all commands for easy registration
 pub use system::*;
 pub use file_ops::*;
 pub use ai_keys::*;
+pub use ai_providers::*;
+pub use ai_failover::*;
+pub use ai_headers::*;
+pub use ai_moderation::*;
 pub use ai_usage::*;
+pub use ai_pricing::*;
 pub use ai_proxy::*;
+pub use ai_batch::*;
+pub use ai_structured::*;
+pub use ai_images::*;
+pub use ai_debug_log::*;
+pub use ai_summaries::*;
+pub use ai_context::*;
+pub use conversations::*;
 pub use mcp::*;
+pub use library::*;
+pub use pdf::*;
+pub use epub::*;
+pub use thumbnails::*;
+pub use annotations::*;
+pub use conversion::*;
+pub use clipper::*;
+pub use feeds::*;
+pub use dictionary::*;
+pub use tts::*;
+pub use rag::*;
+pub use backup::*;
+pub use lan_sync::*;
+pub use migrations::*;
+pub use data_usage::*;
+pub use network::*;
+pub use appearance::*;
+pub use power::*;
+pub use diagnostics::*;
+pub use crash::*;
+pub use settings::*;
+pub use kv::*;
+pub use metrics::*;
+pub use permissions::*;
+pub use messages::*;
+pub use events::*;
+pub use self_test::*;
+pub use startup::*;
+pub use logging::*;
+pub use retry::*;
+pub use hex::*;
+#[cfg(feature = "local-inference")]
+pub use local_inference::*;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub use updates::*;
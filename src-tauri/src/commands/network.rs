@@ -0,0 +1,335 @@
+//! Network connectivity and captive portal detection
+//!
+//! A check probes a few plain-HTTP connectivity endpoints, whose known
+//! answer is replaced by a login page behind a captive portal, and the
+//! services the app talks to (AI providers, the npm registry MCP servers are
+//! installed from). Any HTTP answer counts as reachable, errors such as 401
+//! included. A background monitor repeats the check and emits an event when
+//! the connectivity state changes, so the UI can switch to offline mode.
+//!
+//! Outgoing requests of AI providers go through the system proxy, a proxy
+//! set in the settings, or directly, as `network.proxyMode` says.
+
+use crate::commands::events::emit_event;
+use crate::commands::power::is_suspended;
+use crate::error::AppError;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::Manager;
+
+/// Event emitted with the new `NetworkStatus` when connectivity changes
+pub const NETWORK_STATUS_EVENT: &str = "network-status-changed";
+
+/// Setting choosing how outgoing requests reach the network
+pub const PROXY_MODE_SETTING: &str = "network.proxyMode";
+
+/// Setting holding the proxy used in manual mode
+pub const PROXY_URL_SETTING: &str = "network.proxyUrl";
+
+/// Setting holding the seconds before an AI request is abandoned
+pub const REQUEST_TIMEOUT_SETTING: &str = "network.requestTimeoutSecs";
+
+/// Hosts always reached directly, so local servers keep working behind a
+/// manual proxy
+const NO_PROXY_HOSTS: &str = "localhost,127.0.0.1,::1";
+
+/// Connectivity states
+pub const NETWORK_ONLINE: &str = "online";
+/// Connected, but some services are unreachable
+pub const NETWORK_LIMITED: &str = "limited";
+pub const NETWORK_CAPTIVE_PORTAL: &str = "captive-portal";
+pub const NETWORK_OFFLINE: &str = "offline";
+
+/// Connectivity probes: URL, expected status and expected start of the body.
+/// Several, since some are blocked in some countries.
+const CONNECTIVITY_PROBES: &[(&str, u16, &str)] = &[
+    ("http://connectivitycheck.gstatic.com/generate_204", 204, ""),
+    (
+        "http://www.msftconnecttest.com/connecttest.txt",
+        200,
+        "Microsoft Connect Test",
+    ),
+    (
+        "http://captive.apple.com/hotspot-detect.html",
+        200,
+        "<HTML><HEAD><TITLE>Success",
+    ),
+];
+
+/// Services whose reachability is reported
+pub const NETWORK_ENDPOINTS: &[(&str, &str)] = &[
+    ("openai", "https://api.openai.com/v1/models"),
+    ("anthropic", "https://api.anthropic.com/v1/models"),
+    ("npm", "https://registry.npmjs.org/"),
+];
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the monitor checks connectivity
+const MONITOR_TICK: Duration = Duration::from_secs(60);
+/// Delay before the first check, keeps startup quiet
+const MONITOR_STARTUP_DELAY: Duration = Duration::from_secs(10);
+
+/// Last connectivity state seen, to emit events on changes only
+pub type NetworkState = Arc<Mutex<Option<String>>>;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Reachability of one service
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointStatus {
+    pub name: String,
+    pub url: String,
+    pub reachable: bool,
+    /// HTTP status of the answer
+    pub status: Option<u16>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkStatus {
+    /// "online", "limited", "captive-portal" or "offline"
+    pub state: String,
+    pub endpoints: Vec<EndpointStatus>,
+    pub checked_at: i64,
+}
+
+/// What the connectivity probes found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    /// A probe got its expected answer
+    Clear,
+    /// A probe got another answer, such as a login page or a redirect to one
+    Intercepted,
+    /// No probe got any answer
+    Unreachable,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Build the HTTP client used for checks; probes must see redirects
+fn network_http_client(follow_redirects: bool) -> Result<reqwest::Client, AppError> {
+    let policy = if follow_redirects {
+        reqwest::redirect::Policy::default()
+    } else {
+        reqwest::redirect::Policy::none()
+    };
+    reqwest::Client::builder()
+        .user_agent(concat!("sast-readium/", env!("CARGO_PKG_VERSION")))
+        .timeout(PROBE_TIMEOUT)
+        .redirect(policy)
+        .build()
+        .map_err(AppError::from)
+}
+
+/// Route a client's requests as the proxy mode says: through the system
+/// proxy (environment variables and OS settings), the given proxy, or
+/// directly
+pub fn apply_proxy(
+    builder: reqwest::ClientBuilder,
+    mode: &str,
+    url: Option<&str>,
+) -> Result<reqwest::ClientBuilder, AppError> {
+    match (mode, url) {
+        ("direct", _) => Ok(builder.no_proxy()),
+        ("manual", Some(url)) => {
+            let proxy = reqwest::Proxy::all(url)
+                .map_err(|e| AppError::InvalidInput(format!("Unsupported proxy {}: {}", url, e)))?
+                .no_proxy(reqwest::NoProxy::from_string(NO_PROXY_HOSTS));
+            Ok(builder.proxy(proxy))
+        }
+        ("manual", None) => Err(AppError::InvalidInput(format!(
+            "Manual proxy mode needs {}",
+            PROXY_URL_SETTING
+        ))),
+        _ => Ok(builder),
+    }
+}
+
+/// Decide the connectivity state from the probes and the services
+pub fn classify_network(probe: ProbeOutcome, endpoints: &[EndpointStatus]) -> &'static str {
+    let reachable = endpoints.iter().filter(|e| e.reachable).count();
+    match probe {
+        ProbeOutcome::Intercepted => NETWORK_CAPTIVE_PORTAL,
+        ProbeOutcome::Unreachable if reachable == 0 => NETWORK_OFFLINE,
+        _ if reachable == endpoints.len() => NETWORK_ONLINE,
+        _ => NETWORK_LIMITED,
+    }
+}
+
+async fn run_probes(client: &reqwest::Client) -> ProbeOutcome {
+    for (url, status, body) in CONNECTIVITY_PROBES {
+        let Ok(response) = client.get(*url).send().await else {
+            continue;
+        };
+        if response.status().as_u16() != *status {
+            return ProbeOutcome::Intercepted;
+        }
+        let text = response.text().await.unwrap_or_default();
+        return if text.trim_start().starts_with(body) {
+            ProbeOutcome::Clear
+        } else {
+            ProbeOutcome::Intercepted
+        };
+    }
+    ProbeOutcome::Unreachable
+}
+
+async fn check_endpoint(client: reqwest::Client, name: &str, url: &str) -> EndpointStatus {
+    let started = Instant::now();
+    let result = client.head(url).send().await;
+    let mut status = EndpointStatus {
+        name: name.to_string(),
+        url: url.to_string(),
+        reachable: result.is_ok(),
+        status: None,
+        latency_ms: None,
+        error: None,
+    };
+    match result {
+        Ok(response) => {
+            status.status = Some(response.status().as_u16());
+            status.latency_ms = Some(started.elapsed().as_millis() as u64);
+        }
+        Err(e) => status.error = Some(e.to_string()),
+    }
+    status
+}
+
+/// Check connectivity and every service, concurrently
+pub async fn check_network() -> Result<NetworkStatus, AppError> {
+    let probe_client = network_http_client(false)?;
+    let client = network_http_client(true)?;
+    let checks: Vec<_> = NETWORK_ENDPOINTS
+        .iter()
+        .map(|(name, url)| tauri::async_runtime::spawn(check_endpoint(client.clone(), name, url)))
+        .collect();
+    let probe = run_probes(&probe_client).await;
+    let mut endpoints = Vec::with_capacity(checks.len());
+    for check in checks {
+        endpoints.push(check.await.map_err(|e| AppError::Internal(e.to_string()))?);
+    }
+    Ok(NetworkStatus {
+        state: classify_network(probe, &endpoints).to_string(),
+        endpoints,
+        checked_at: chrono::Utc::now().timestamp(),
+    })
+}
+
+/// Check the network, emitting an event when the state changed
+pub async fn check_and_publish(app: &tauri::AppHandle) -> Result<NetworkStatus, AppError> {
+    let status = check_network().await?;
+    let changed = {
+        let state = app.state::<NetworkState>();
+        let mut last = state
+            .lock()
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let changed = last.as_deref() != Some(status.state.as_str());
+        *last = Some(status.state.clone());
+        changed
+    };
+    if changed {
+        log::info!("Network state: {}", status.state);
+        emit_event(app, &status);
+    }
+    Ok(status)
+}
+
+/// Start the background task watching connectivity
+pub fn start_network_monitor(app: tauri::AppHandle) {
+    crate::commands::crash::spawn_guarded("network monitor", async move {
+        tokio::time::sleep(MONITOR_STARTUP_DELAY).await;
+        let mut interval = tokio::time::interval(MONITOR_TICK);
+        loop {
+            interval.tick().await;
+            if is_suspended(&app) {
+                continue;
+            }
+            if let Err(e) = check_and_publish(&app).await {
+                log::warn!("Network check failed: {}", e);
+            }
+        }
+    });
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Check connectivity (offline, captive portal) and which services are
+/// reachable
+#[tauri::command]
+pub async fn check_network_status(app: tauri::AppHandle) -> Result<NetworkStatus, AppError> {
+    check_and_publish(&app).await
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(reachable: bool) -> EndpointStatus {
+        EndpointStatus {
+            name: "api".to_string(),
+            url: "https://api.example.com".to_string(),
+            reachable,
+            status: reachable.then_some(401),
+            latency_ms: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn network_state_follows_probes_and_services() {
+        let all = [endpoint(true), endpoint(true)];
+        let some = [endpoint(true), endpoint(false)];
+        let none = [endpoint(false), endpoint(false)];
+        assert_eq!(classify_network(ProbeOutcome::Clear, &all), NETWORK_ONLINE);
+        assert_eq!(
+            classify_network(ProbeOutcome::Clear, &some),
+            NETWORK_LIMITED
+        );
+        assert_eq!(
+            classify_network(ProbeOutcome::Clear, &none),
+            NETWORK_LIMITED
+        );
+        assert_eq!(
+            classify_network(ProbeOutcome::Intercepted, &all),
+            NETWORK_CAPTIVE_PORTAL
+        );
+        // Probes can be blocked while services work
+        assert_eq!(
+            classify_network(ProbeOutcome::Unreachable, &all),
+            NETWORK_ONLINE
+        );
+        assert_eq!(
+            classify_network(ProbeOutcome::Unreachable, &none),
+            NETWORK_OFFLINE
+        );
+    }
+
+    #[test]
+    fn proxy_modes_configure_the_client() {
+        let build = |mode: &str, url: Option<&str>| {
+            apply_proxy(reqwest::Client::builder(), mode, url)
+                .and_then(|builder| builder.build().map_err(AppError::from))
+        };
+        assert!(build("system", None).is_ok());
+        assert!(build("direct", Some("http://proxy.campus.edu:3128")).is_ok());
+        assert!(build("manual", Some("http://proxy.campus.edu:3128")).is_ok());
+        assert!(matches!(
+            build("manual", None),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,593 @@
+//! PDF document inspection commands
+
+use crate::error::AppError;
+use lopdf::{Dictionary, Document};
+use pdfium_render::prelude::Pdfium;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Metadata read from a PDF's document info dictionary and XMP packet
+#[derive(Serialize, Default, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+    /// RFC 3339 timestamp when parseable, otherwise the raw PDF date string
+    pub creation_date: Option<String>,
+    pub modification_date: Option<String>,
+    pub page_count: u32,
+    pub encrypted: bool,
+    pub pdf_version: String,
+}
+
+/// Fields extracted from an XMP metadata packet
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct XmpMetadata {
+    pub title: Option<String>,
+    pub creators: Vec<String>,
+    pub description: Option<String>,
+    pub create_date: Option<String>,
+    pub modify_date: Option<String>,
+    pub producer: Option<String>,
+}
+
+/// 1-based inclusive page range; a missing end means "to the last page"
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfPageRange {
+    pub start: u32,
+    pub end: Option<u32>,
+}
+
+/// Text of a single page
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfPageText {
+    /// 1-based page number
+    pub page_number: u32,
+    pub text: String,
+}
+
+/// Extracted text of a PDF
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfTextContent {
+    pub page_count: u32,
+    pub pages: Vec<PdfPageText>,
+    /// Extraction backend used ("pdfium" or "pdf-extract")
+    pub engine: String,
+}
+
+/// A positioned run of text in PDF user space (origin at the bottom left)
+#[derive(Clone, Debug)]
+pub struct TextRun {
+    pub text: String,
+    pub left: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Bind pdfium, preferring a library bundled next to the executable over a system-wide install
+pub fn load_pdfium() -> Result<Pdfium, AppError> {
+    let bundled = std::env::current_exe().ok().and_then(|exe| {
+        exe.parent()
+            .map(Pdfium::pdfium_platform_library_name_at_path)
+    });
+    let bindings = bundled
+        .map(Pdfium::bind_to_library)
+        .filter(|bindings| bindings.is_ok())
+        .unwrap_or_else(Pdfium::bind_to_system_library)
+        .map_err(|e| AppError::Internal(format!("pdfium unavailable: {}", e)))?;
+    Ok(Pdfium::new(bindings))
+}
+
+/// Validate a requested page range against the page count
+pub fn resolve_page_range(
+    range: Option<PdfPageRange>,
+    page_count: u32,
+) -> Result<RangeInclusive<u32>, AppError> {
+    let Some(range) = range else {
+        return Ok(1..=page_count);
+    };
+    let end = range.end.unwrap_or(page_count).min(page_count);
+    if range.start == 0 || range.start > end {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid page range {}-{} (the document has {} pages)",
+            range.start,
+            range.end.map(|end| end.to_string()).unwrap_or_default(),
+            page_count
+        )));
+    }
+    Ok(range.start..=end)
+}
+
+/// Arrange text runs into reading order: lines top to bottom, runs left to right
+///
+/// Runs whose vertical centre falls within the current line's extent are
+/// treated as the same line, which keeps multi-font lines together.
+pub fn order_text_runs(mut runs: Vec<TextRun>) -> String {
+    runs.retain(|run| !run.text.trim().is_empty());
+    runs.sort_by(|a, b| b.top.total_cmp(&a.top));
+
+    let mut lines: Vec<(f32, f32, Vec<TextRun>)> = Vec::new();
+    for run in runs {
+        let centre = (run.top + run.bottom) / 2.0;
+        match lines.last_mut() {
+            Some((top, bottom, line)) if centre <= *top && centre >= *bottom => {
+                *bottom = bottom.min(run.bottom);
+                line.push(run);
+            }
+            _ => lines.push((run.top, run.bottom, vec![run])),
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|(_, _, mut line)| {
+            line.sort_by(|a, b| a.left.total_cmp(&b.left));
+            line.iter()
+                .map(|run| run.text.trim())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Extract page text with pdfium, ordered by position on the page
+fn extract_text_with_pdfium(
+    path: &Path,
+    range: Option<PdfPageRange>,
+) -> Result<PdfTextContent, AppError> {
+    let pdfium = load_pdfium()?;
+    let document = pdfium
+        .load_pdf_from_file(path, None)
+        .map_err(|e| AppError::Internal(format!("Failed to open PDF: {}", e)))?;
+    let pages = document.pages();
+    let page_count = pages.len() as u32;
+
+    let mut result = Vec::new();
+    for page_number in resolve_page_range(range, page_count)? {
+        let page = pages.get((page_number - 1) as u16).map_err(|e| {
+            AppError::Internal(format!("Failed to load page {}: {}", page_number, e))
+        })?;
+        let text = page.text().map_err(|e| {
+            AppError::Internal(format!("Failed to read page {}: {}", page_number, e))
+        })?;
+        let runs = text
+            .segments()
+            .iter()
+            .map(|segment| {
+                let bounds = segment.bounds();
+                TextRun {
+                    text: segment.text(),
+                    left: bounds.left().value,
+                    top: bounds.top().value,
+                    bottom: bounds.bottom().value,
+                }
+            })
+            .collect();
+        result.push(PdfPageText {
+            page_number,
+            text: order_text_runs(runs),
+        });
+    }
+
+    Ok(PdfTextContent {
+        page_count,
+        pages: result,
+        engine: "pdfium".to_string(),
+    })
+}
+
+/// Extract page text with pdf-extract, in content stream order
+fn extract_text_with_pdf_extract(
+    path: &Path,
+    range: Option<PdfPageRange>,
+) -> Result<PdfTextContent, AppError> {
+    // pdf-extract panics on some malformed fonts; treat that as a parse failure
+    let pages = std::panic::catch_unwind(|| pdf_extract::extract_text_by_pages(path))
+        .map_err(|_| AppError::Internal("Failed to extract PDF text".to_string()))?
+        .map_err(|e| AppError::Internal(format!("Failed to extract PDF text: {}", e)))?;
+    let page_count = pages.len() as u32;
+    let range = resolve_page_range(range, page_count)?;
+
+    Ok(PdfTextContent {
+        page_count,
+        pages: pages
+            .into_iter()
+            .zip(1u32..)
+            .filter(|(_, page_number)| range.contains(page_number))
+            .map(|(text, page_number)| PdfPageText {
+                page_number,
+                text: text.trim().to_string(),
+            })
+            .collect(),
+        engine: "pdf-extract".to_string(),
+    })
+}
+
+/// Extract per-page text, using pdfium when available
+pub fn load_pdf_text(path: &Path, range: Option<PdfPageRange>) -> Result<PdfTextContent, AppError> {
+    match extract_text_with_pdfium(path, range) {
+        // An invalid range is the caller's error, not a reason to retry
+        Err(e) if !matches!(e, AppError::InvalidInput(_)) => {
+            log::debug!("Falling back to pdf-extract for {:?}: {}", path, e);
+            extract_text_with_pdf_extract(path, range)
+        }
+        result => result,
+    }
+}
+
+/// Non-empty trimmed string, with NUL padding removed
+fn clean_text(text: &str) -> Option<String> {
+    let cleaned = text.trim_matches(char::from(0)).trim();
+    if cleaned.is_empty() {
+        None
+    } else {
+        Some(cleaned.to_string())
+    }
+}
+
+/// Convert a PDF date (`D:YYYYMMDDHHmmSSOHH'mm'`) to RFC 3339
+pub fn parse_pdf_date(raw: &str) -> Option<String> {
+    let value = raw.trim().trim_start_matches("D:");
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+
+    let field = |start: usize, len: usize, default: u32| -> u32 {
+        digits
+            .get(start..start + len)
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(default)
+    };
+    let year: i32 = digits[0..4].parse().ok()?;
+    let date = chrono::NaiveDate::from_ymd_opt(year, field(4, 2, 1), field(6, 2, 1))?;
+    let time = chrono::NaiveTime::from_hms_opt(field(8, 2, 0), field(10, 2, 0), field(12, 2, 0))?;
+
+    let tz = &value[digits.len()..];
+    let offset_seconds = match tz.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let tz_digits: String = tz[1..].chars().filter(|c| c.is_ascii_digit()).collect();
+            let hours: i32 = tz_digits
+                .get(0..2)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+            let minutes: i32 = tz_digits
+                .get(2..4)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+            let seconds = hours * 3600 + minutes * 60;
+            if sign == '-' {
+                -seconds
+            } else {
+                seconds
+            }
+        }
+        _ => 0,
+    };
+    let offset = chrono::FixedOffset::east_opt(offset_seconds)?;
+    let datetime = date.and_time(time).and_local_timezone(offset).single()?;
+    Some(datetime.to_rfc3339())
+}
+
+/// Store an XMP property value if it is one of the fields we read
+fn assign_xmp_field(name: &str, value: String, metadata: &mut XmpMetadata) {
+    match name {
+        "dc:title" if metadata.title.is_none() => metadata.title = clean_text(&value),
+        "dc:creator" => metadata.creators.extend(clean_text(&value)),
+        "dc:description" if metadata.description.is_none() => {
+            metadata.description = clean_text(&value)
+        }
+        "xmp:CreateDate" => metadata.create_date = clean_text(&value),
+        "xmp:ModifyDate" => metadata.modify_date = clean_text(&value),
+        "pdf:Producer" => metadata.producer = clean_text(&value),
+        _ => {}
+    }
+}
+
+/// Extract common Dublin Core / XMP fields from an XMP packet
+pub fn parse_xmp_metadata(xml: &str) -> XmpMetadata {
+    let mut metadata = XmpMetadata::default();
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    // Element names from the root to the current position
+    let mut stack: Vec<String> = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                for attr in e.attributes().flatten() {
+                    let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                    if let Ok(value) = attr.unescape_value() {
+                        assign_xmp_field(&key, value.to_string(), &mut metadata);
+                    }
+                }
+                stack.push(name);
+            }
+            Ok(Event::Empty(e)) => {
+                for attr in e.attributes().flatten() {
+                    let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                    if let Ok(value) = attr.unescape_value() {
+                        assign_xmp_field(&key, value.to_string(), &mut metadata);
+                    }
+                }
+            }
+            Ok(Event::Text(e)) => {
+                if let Ok(text) = e.unescape() {
+                    // Values are either direct children of the property element or
+                    // wrapped in rdf:Alt/rdf:Seq/rdf:Bag list items
+                    let property = stack
+                        .iter()
+                        .rev()
+                        .find(|name| !name.starts_with("rdf:"))
+                        .cloned();
+                    if let Some(property) = property {
+                        assign_xmp_field(&property, text.to_string(), &mut metadata);
+                    }
+                }
+            }
+            Ok(Event::End(_)) => {
+                stack.pop();
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    metadata
+}
+
+/// Read a text entry from the info dictionary
+fn info_string(doc: &Document, info: &Dictionary, key: &[u8]) -> Option<String> {
+    let object = info.get(key).ok()?;
+    let (_, object) = doc.dereference(object).ok()?;
+    lopdf::decode_text_string(object)
+        .ok()
+        .and_then(|s| clean_text(&s))
+}
+
+/// Get the document info dictionary, if any
+fn info_dictionary(doc: &Document) -> Option<&Dictionary> {
+    let object = doc.trailer.get(b"Info").ok()?;
+    let (_, object) = doc.dereference(object).ok()?;
+    object.as_dict().ok()
+}
+
+/// Get the XMP packet from the catalog's Metadata stream, if any
+fn xmp_packet(doc: &Document) -> Option<String> {
+    let object = doc.catalog().ok()?.get(b"Metadata").ok()?;
+    let (_, object) = doc.dereference(object).ok()?;
+    let content = object.as_stream().ok()?.get_plain_content().ok()?;
+    Some(String::from_utf8_lossy(&content).to_string())
+}
+
+/// Collect metadata from a loaded PDF document
+pub fn read_pdf_metadata(doc: &Document) -> PdfMetadata {
+    let xmp = xmp_packet(doc)
+        .map(|xml| parse_xmp_metadata(&xml))
+        .unwrap_or_default();
+    let info = info_dictionary(doc);
+    let info_field = |key: &[u8]| info.and_then(|dict| info_string(doc, dict, key));
+
+    let xmp_author = if xmp.creators.is_empty() {
+        None
+    } else {
+        Some(xmp.creators.join(", "))
+    };
+
+    let creation_date = xmp
+        .create_date
+        .clone()
+        .or_else(|| info_field(b"CreationDate").map(|raw| parse_pdf_date(&raw).unwrap_or(raw)));
+    let modification_date = xmp
+        .modify_date
+        .clone()
+        .or_else(|| info_field(b"ModDate").map(|raw| parse_pdf_date(&raw).unwrap_or(raw)));
+
+    PdfMetadata {
+        // XMP is preferred: info dictionaries often carry generator junk titles
+        title: xmp.title.clone().or_else(|| info_field(b"Title")),
+        author: xmp_author.or_else(|| info_field(b"Author")),
+        subject: xmp.description.clone().or_else(|| info_field(b"Subject")),
+        keywords: info_field(b"Keywords"),
+        creator: info_field(b"Creator"),
+        producer: xmp.producer.clone().or_else(|| info_field(b"Producer")),
+        creation_date,
+        modification_date,
+        page_count: doc.get_pages().len() as u32,
+        encrypted: doc.trailer.get(b"Encrypt").is_ok(),
+        pdf_version: doc.version.clone(),
+    }
+}
+
+/// Load a PDF file and read its metadata
+pub fn load_pdf_metadata(path: &Path) -> Result<PdfMetadata, AppError> {
+    let doc = Document::load(path)
+        .map_err(|e| AppError::Internal(format!("Failed to parse PDF: {}", e)))?;
+    Ok(read_pdf_metadata(&doc))
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Extract metadata (title, author, page count, dates, encryption) from a PDF file
+#[tauri::command]
+pub async fn extract_pdf_metadata(path: String) -> Result<PdfMetadata, AppError> {
+    let file_path = Path::new(&path).to_path_buf();
+    if !file_path.is_file() {
+        return Err(AppError::NotFound(format!("File not found: {}", path)));
+    }
+    tauri::async_runtime::spawn_blocking(move || load_pdf_metadata(&file_path))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Extract text from a PDF, one entry per page in the requested range
+#[tauri::command]
+pub async fn extract_pdf_text(
+    path: String,
+    page_range: Option<PdfPageRange>,
+) -> Result<PdfTextContent, AppError> {
+    let file_path = Path::new(&path).to_path_buf();
+    if !file_path.is_file() {
+        return Err(AppError::NotFound(format!("File not found: {}", path)));
+    }
+    tauri::async_runtime::spawn_blocking(move || load_pdf_text(&file_path, page_range))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Object, Stream};
+
+    fn build_pdf(info: Dictionary, xmp: Option<&str>) -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let mut catalog = dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        };
+        if let Some(xml) = xmp {
+            let metadata_id = doc.add_object(Stream::new(
+                dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+                xml.as_bytes().to_vec(),
+            ));
+            catalog.set("Metadata", metadata_id);
+        }
+        let catalog_id = doc.add_object(catalog);
+        let info_id = doc.add_object(info);
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+        doc
+    }
+
+    #[test]
+    fn parse_pdf_date_handles_offsets_and_partial_dates() {
+        assert_eq!(
+            parse_pdf_date("D:20230115123045+08'00'").as_deref(),
+            Some("2023-01-15T12:30:45+08:00")
+        );
+        assert_eq!(
+            parse_pdf_date("D:20231231Z").as_deref(),
+            Some("2023-12-31T00:00:00+00:00")
+        );
+        assert_eq!(parse_pdf_date("garbage"), None);
+    }
+
+    #[test]
+    fn parse_xmp_metadata_reads_alt_and_seq_values() {
+        let xml = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+          <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+            <rdf:Description xmp:CreateDate="2021-03-04T05:06:07Z" pdf:Producer="TeX">
+              <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Operating Systems</rdf:li></rdf:Alt></dc:title>
+              <dc:creator><rdf:Seq><rdf:li>Ann</rdf:li><rdf:li>Bob</rdf:li></rdf:Seq></dc:creator>
+            </rdf:Description>
+          </rdf:RDF>
+        </x:xmpmeta>"#;
+
+        let xmp = parse_xmp_metadata(xml);
+
+        assert_eq!(xmp.title.as_deref(), Some("Operating Systems"));
+        assert_eq!(xmp.creators, vec!["Ann", "Bob"]);
+        assert_eq!(xmp.create_date.as_deref(), Some("2021-03-04T05:06:07Z"));
+        assert_eq!(xmp.producer.as_deref(), Some("TeX"));
+    }
+
+    #[test]
+    fn read_pdf_metadata_merges_info_dictionary_and_xmp() {
+        let info = dictionary! {
+            "Title" => Object::string_literal("Microsoft Word - draft.docx"),
+            "Author" => Object::string_literal("Info Author"),
+            "Keywords" => Object::string_literal("os, kernel"),
+            "CreationDate" => Object::string_literal("D:20200102030405Z"),
+        };
+        let xmp = r#"<rdf:RDF><rdf:Description>
+            <dc:title><rdf:Alt><rdf:li>Real Title</rdf:li></rdf:Alt></dc:title>
+        </rdf:Description></rdf:RDF>"#;
+        let doc = build_pdf(info, Some(xmp));
+
+        let metadata = read_pdf_metadata(&doc);
+
+        assert_eq!(metadata.title.as_deref(), Some("Real Title"));
+        assert_eq!(metadata.author.as_deref(), Some("Info Author"));
+        assert_eq!(metadata.keywords.as_deref(), Some("os, kernel"));
+        assert_eq!(
+            metadata.creation_date.as_deref(),
+            Some("2020-01-02T03:04:05+00:00")
+        );
+        assert_eq!(metadata.page_count, 1);
+        assert!(!metadata.encrypted);
+        assert_eq!(metadata.pdf_version, "1.7");
+    }
+
+    fn run(text: &str, left: f32, top: f32, bottom: f32) -> TextRun {
+        TextRun {
+            text: text.to_string(),
+            left,
+            top,
+            bottom,
+        }
+    }
+
+    #[test]
+    fn order_text_runs_groups_lines_and_sorts_by_position() {
+        let runs = vec![
+            run("world", 120.0, 700.0, 688.0),
+            run("Second line", 72.0, 680.0, 668.0),
+            run("Hello", 72.0, 701.0, 689.0),
+            run("   ", 10.0, 800.0, 790.0),
+        ];
+
+        assert_eq!(order_text_runs(runs), "Hello world\nSecond line");
+    }
+
+    #[test]
+    fn resolve_page_range_clamps_and_validates() {
+        assert_eq!(resolve_page_range(None, 3).unwrap(), 1..=3);
+        let range = |start, end| Some(PdfPageRange { start, end });
+        assert_eq!(resolve_page_range(range(2, Some(10)), 3).unwrap(), 2..=3);
+        assert_eq!(resolve_page_range(range(3, None), 3).unwrap(), 3..=3);
+        assert!(resolve_page_range(range(0, Some(1)), 3).is_err());
+        assert!(resolve_page_range(range(4, None), 3).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! Runtime permission policy for sensitive commands
+//!
+//! Beyond Tauri's static capabilities, sensitive commands (file deletion,
+//! MCP tool calls, API key access) belong to a feature whose mode is
+//! "allow", "ask" or "deny", optionally per origin of the calling webview.
+//! The invoke handler is wrapped with `guard_commands`, so the mode is
+//! checked before the command runs; "ask" shows a native dialog the webview
+//! cannot answer. Modes are stored in the `security.commandPermissions`
+//! setting as `feature` or `feature@origin` keys. Loosening a mode is
+//! confirmed the same way.
+
+use crate::commands::messages::{format_message, message};
+use crate::commands::settings::{change_setting, setting};
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tauri::ipc::Invoke;
+use tauri::Manager;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+/// Setting holding the mode of each feature
+pub const PERMISSIONS_SETTING: &str = "security.commandPermissions";
+
+/// Sensitive features and the commands they cover
+pub const PERMISSION_FEATURES: &[PermissionFeature] = &[
+    PermissionFeature {
+        name: "files.delete",
+        description: "Delete files",
+        commands: &["delete_file"],
+    },
+    PermissionFeature {
+        name: "mcp.tools",
+        description: "Call MCP server tools",
+        commands: &["mcp_call_tool", "send_mcp_message"],
+    },
+    PermissionFeature {
+        name: "keys.read",
+        description: "Read stored API keys",
+        commands: &["get_api_key"],
+    },
+    PermissionFeature {
+        name: "keys.write",
+        description: "Change or delete stored API keys",
+        commands: &[
+            "save_api_key",
+            "delete_api_key",
+            "save_provider_tenant",
+            "save_azure_deployment",
+            "save_custom_provider",
+            "delete_custom_provider",
+        ],
+    },
+];
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionMode {
+    Allow,
+    Ask,
+    Deny,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionFeature {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub commands: &'static [&'static str],
+}
+
+/// A feature and its modes
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandPermission {
+    pub feature: PermissionFeature,
+    /// Mode for origins without one of their own
+    pub mode: PermissionMode,
+    /// Modes of single origins
+    pub origins: BTreeMap<String, PermissionMode>,
+}
+
+impl PermissionMode {
+    /// How much the mode restricts, for telling loosening from tightening
+    fn strictness(self) -> u8 {
+        match self {
+            PermissionMode::Allow => 0,
+            PermissionMode::Ask => 1,
+            PermissionMode::Deny => 2,
+        }
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// The feature covering a command, if it is sensitive
+pub fn command_feature(command: &str) -> Option<&'static PermissionFeature> {
+    PERMISSION_FEATURES
+        .iter()
+        .find(|feature| feature.commands.contains(&command))
+}
+
+fn find_feature(name: &str) -> Result<&'static PermissionFeature, AppError> {
+    PERMISSION_FEATURES
+        .iter()
+        .find(|feature| feature.name == name)
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown permission feature: {}", name)))
+}
+
+/// `scheme://host[:port]` of a webview URL
+pub fn url_origin(url: &url::Url) -> String {
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}://{}:{}", url.scheme(), host, port),
+        (Some(host), None) => format!("{}://{}", url.scheme(), host),
+        _ => format!("{}:", url.scheme()),
+    }
+}
+
+fn rule_key(feature: &str, origin: Option<&str>) -> String {
+    match origin {
+        Some(origin) => format!("{}@{}", feature, origin),
+        None => feature.to_string(),
+    }
+}
+
+/// Mode of a feature for an origin: its own rule, else the feature's, else
+/// allowed
+pub fn resolve_mode(
+    rules: &BTreeMap<String, PermissionMode>,
+    feature: &str,
+    origin: Option<&str>,
+) -> PermissionMode {
+    origin
+        .and_then(|origin| rules.get(&rule_key(feature, Some(origin))))
+        .or_else(|| rules.get(feature))
+        .copied()
+        .unwrap_or(PermissionMode::Allow)
+}
+
+fn load_rules(app: &tauri::AppHandle) -> BTreeMap<String, PermissionMode> {
+    setting(app, PERMISSIONS_SETTING).unwrap_or_else(|e| {
+        log::warn!("Failed to load command permissions: {}", e);
+        BTreeMap::new()
+    })
+}
+
+/// Ask the user through a native dialog
+fn ask_user<F: FnOnce(bool) + Send + 'static>(app: &tauri::AppHandle, prompt: String, answer: F) {
+    app.dialog()
+        .message(prompt)
+        .title(message("permission.title"))
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            message("permission.allow"),
+            message("permission.deny"),
+        ))
+        .show(answer);
+}
+
+async fn confirm(app: &tauri::AppHandle, prompt: String) -> bool {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    ask_user(app, prompt, move |allowed| {
+        let _ = sender.send(allowed);
+    });
+    receiver.await.unwrap_or(false)
+}
+
+/// What a feature lets the app do, in the backend locale
+fn feature_action(feature: &PermissionFeature) -> String {
+    message(&format!("feature.{}", feature.name))
+}
+
+fn denied(command: &str, feature: &PermissionFeature) -> AppError {
+    AppError::PermissionDenied(format_message(
+        "permission.not_allowed",
+        &[command, &feature_action(feature)],
+    ))
+}
+
+/// Wrap the invoke handler so sensitive commands run only as their
+/// permission mode says
+pub fn guard_commands<F>(handler: F) -> impl Fn(Invoke) -> bool + Send + Sync + 'static
+where
+    F: Fn(Invoke) -> bool + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    move |invoke: Invoke| {
+        let command = invoke.message.command().to_string();
+        let Some(feature) = command_feature(&command) else {
+            return handler(invoke);
+        };
+        let webview = invoke.message.webview();
+        let origin = webview.url().ok().map(|url| url_origin(&url));
+        let app = webview.app_handle().clone();
+        match resolve_mode(&load_rules(&app), feature.name, origin.as_deref()) {
+            PermissionMode::Allow => handler(invoke),
+            PermissionMode::Deny => {
+                log::warn!("Denied {} from {:?}", command, origin);
+                invoke.resolver.reject(denied(&command, feature));
+                true
+            }
+            PermissionMode::Ask => {
+                let unknown = message("permission.unknown_origin");
+                let prompt = format_message(
+                    "permission.ask",
+                    &[
+                        &feature_action(feature),
+                        &command,
+                        origin.as_deref().unwrap_or(&unknown),
+                    ],
+                );
+                let handler = handler.clone();
+                ask_user(&app, prompt, move |allowed| {
+                    if allowed {
+                        handler(invoke);
+                    } else {
+                        log::info!("User denied {}", command);
+                        invoke.resolver.reject(denied(&command, feature));
+                    }
+                });
+                true
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the sensitive features and their modes
+#[tauri::command]
+pub fn get_command_permissions(app: tauri::AppHandle) -> Vec<CommandPermission> {
+    let rules = load_rules(&app);
+    PERMISSION_FEATURES
+        .iter()
+        .map(|feature| CommandPermission {
+            feature: feature.clone(),
+            mode: resolve_mode(&rules, feature.name, None),
+            origins: rules
+                .iter()
+                .filter_map(|(key, mode)| {
+                    let (name, origin) = key.split_once('@')?;
+                    (name == feature.name).then(|| (origin.to_string(), *mode))
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Set the mode of a feature, for one origin or all; no mode removes the
+/// rule. Loosening a mode is confirmed by the user.
+#[tauri::command]
+pub async fn set_command_permission(
+    app: tauri::AppHandle,
+    feature: String,
+    origin: Option<String>,
+    mode: Option<PermissionMode>,
+) -> Result<(), AppError> {
+    let definition = find_feature(&feature)?;
+    let origin = origin
+        .map(|origin| origin.trim().trim_end_matches('/').to_string())
+        .filter(|origin| !origin.is_empty());
+    let mut rules = load_rules(&app);
+    let current = resolve_mode(&rules, &feature, origin.as_deref());
+    let key = rule_key(&feature, origin.as_deref());
+    match mode {
+        Some(mode) => rules.insert(key.clone(), mode),
+        None => rules.remove(&key),
+    };
+    let updated = resolve_mode(&rules, &feature, origin.as_deref());
+    if updated.strictness() < current.strictness() {
+        let prompt = format_message(
+            "permission.change",
+            &[
+                &feature_action(definition),
+                &format!("{:?}", current),
+                &format!("{:?}", updated),
+            ],
+        );
+        if !confirm(&app, prompt).await {
+            return Err(AppError::PermissionDenied(message(
+                "permission.not_confirmed",
+            )));
+        }
+    }
+    change_setting(
+        &app,
+        PERMISSIONS_SETTING,
+        Some(serde_json::to_value(&rules)?),
+    )?;
+    log::info!("Command permission {} set to {:?}", key, mode);
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_resolve_by_origin_then_feature() {
+        let rules: BTreeMap<String, PermissionMode> = serde_json::from_value(serde_json::json!({
+            "mcp.tools": "ask",
+            "mcp.tools@http://localhost:3000": "deny",
+            "files.delete@tauri://localhost": "deny",
+        }))
+        .unwrap();
+        let dev = Some("http://localhost:3000");
+        let app = Some("tauri://localhost");
+        assert_eq!(resolve_mode(&rules, "mcp.tools", dev), PermissionMode::Deny);
+        assert_eq!(resolve_mode(&rules, "mcp.tools", app), PermissionMode::Ask);
+        assert_eq!(
+            resolve_mode(&rules, "files.delete", app),
+            PermissionMode::Deny
+        );
+        assert_eq!(
+            resolve_mode(&rules, "files.delete", dev),
+            PermissionMode::Allow
+        );
+        assert_eq!(
+            resolve_mode(&rules, "keys.read", None),
+            PermissionMode::Allow
+        );
+
+        assert_eq!(command_feature("get_api_key").unwrap().name, "keys.read");
+        assert!(command_feature("get_api_key_providers").is_none());
+        let url = url::Url::parse("http://localhost:3000/reader?id=1").unwrap();
+        assert_eq!(url_origin(&url), "http://localhost:3000");
+        let url = url::Url::parse("tauri://localhost/index.html").unwrap();
+        assert_eq!(url_origin(&url), "tauri://localhost");
+        assert!(PermissionMode::Ask.strictness() < PermissionMode::Deny.strictness());
+    }
+}
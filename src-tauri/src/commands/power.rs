@@ -0,0 +1,569 @@
+//! System suspend, resume and battery saver monitoring
+//!
+//! On Linux, logind announces suspend and wake with its `PrepareForSleep`
+//! signal. Everywhere, a wake is also detected from the wall clock jumping
+//! ahead of a sleeping thread, since suspended time does not count towards
+//! the sleep. Battery saver is polled from power-profiles-daemon (Linux),
+//! `pmset` (macOS) or the power status (Windows).
+//!
+//! Background work (feed refresh, network checks, maintenance) is skipped
+//! while suspended, and feed refresh also while saving power. On wake the
+//! network is checked again and MCP sessions whose server went away are
+//! dropped.
+//!
+//! Battery level and charging are read from `/sys/class/power_supply`
+//! (Linux), `pmset -g batt` (macOS) or the power status (Windows).
+
+use crate::commands::events::emit_event;
+use crate::commands::mcp::MCPClientStateHandle;
+use crate::error::AppError;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::Manager;
+
+/// Event emitted with a `PowerEvent` on suspend, wake or battery saver change
+pub const POWER_EVENT: &str = "power-state-changed";
+
+/// Kinds of power events
+pub const POWER_SUSPEND: &str = "suspend";
+pub const POWER_RESUME: &str = "resume";
+pub const POWER_BATTERY_SAVER: &str = "battery-saver";
+
+/// How often the clock is compared with the time slept
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Extra wall clock time that counts as the system having slept
+const SLEEP_TOLERANCE: Duration = Duration::from_secs(30);
+/// How often battery saver is checked
+const BATTERY_SAVER_TICK: Duration = Duration::from_secs(60);
+
+/// Current power state
+pub type PowerState = Arc<Mutex<PowerStatus>>;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerStatus {
+    /// Whether the system announced it is about to sleep
+    pub suspended: bool,
+    /// Whether battery saver is on, if it can be detected
+    pub battery_saver: Option<bool>,
+    pub last_suspend_at: Option<i64>,
+    pub last_resume_at: Option<i64>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerEvent {
+    /// "suspend", "resume" or "battery-saver"
+    pub kind: String,
+    pub status: PowerStatus,
+    /// MCP servers found disconnected on wake
+    pub disconnected_mcp_servers: Vec<String>,
+}
+
+/// Battery level and charging state
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BatteryStatus {
+    /// Whether the device has a battery
+    pub present: bool,
+    /// Charge in percent
+    pub level: Option<u8>,
+    pub charging: Option<bool>,
+    /// Whether running from the battery rather than external power
+    pub on_battery: Option<bool>,
+    /// Whether battery saver (low power mode) is on
+    pub low_power_mode: Option<bool>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn read_status(app: &tauri::AppHandle) -> PowerStatus {
+    app.state::<PowerState>()
+        .lock()
+        .map(|status| status.clone())
+        .unwrap_or_default()
+}
+
+/// Whether the system is going to sleep; periodic work should wait
+pub fn is_suspended(app: &tauri::AppHandle) -> bool {
+    read_status(app).suspended
+}
+
+/// Whether optional background work, such as downloads, should wait
+pub fn is_saving_power(app: &tauri::AppHandle) -> bool {
+    let status = read_status(app);
+    status.suspended || status.battery_saver == Some(true)
+}
+
+/// Whether the wall clock moved further than a sleep of `slept` explains
+pub fn clock_jumped(slept: Duration, wall_elapsed: Duration) -> bool {
+    wall_elapsed > slept + SLEEP_TOLERANCE
+}
+
+/// Argument of a logind `PrepareForSleep` signal line from `gdbus monitor`
+pub fn parse_prepare_for_sleep(line: &str) -> Option<bool> {
+    let args = line.split("PrepareForSleep").nth(1)?;
+    let args = args.trim_start().trim_start_matches('(');
+    if args.starts_with("true") {
+        Some(true)
+    } else if args.starts_with("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Battery saver from `powerprofilesctl get`
+pub fn parse_power_profile(output: &str) -> Option<bool> {
+    let profile = output.trim();
+    (!profile.is_empty()).then(|| profile == "power-saver")
+}
+
+/// Battery saver (low power mode) from `pmset -g`
+pub fn parse_pmset_low_power(output: &str) -> Option<bool> {
+    output.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        (fields.next() == Some("lowpowermode")).then(|| fields.next() == Some("1"))
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Whether battery saver is on, when the platform reports it
+#[cfg(target_os = "linux")]
+pub fn detect_battery_saver() -> Option<bool> {
+    parse_power_profile(&command_output("powerprofilesctl", &["get"])?)
+}
+
+#[cfg(target_os = "macos")]
+pub fn detect_battery_saver() -> Option<bool> {
+    parse_pmset_low_power(&command_output("pmset", &["-g"])?)
+}
+
+#[cfg(windows)]
+fn windows_power_status() -> Option<windows_sys::Win32::System::Power::SYSTEM_POWER_STATUS> {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    (unsafe { GetSystemPowerStatus(&mut status) } != 0).then_some(status)
+}
+
+#[cfg(windows)]
+pub fn detect_battery_saver() -> Option<bool> {
+    Some(windows_power_status()?.SystemStatusFlag == 1)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn detect_battery_saver() -> Option<bool> {
+    None
+}
+
+/// Battery status from a `/sys/class/power_supply` directory
+pub fn read_power_supplies(dir: &std::path::Path) -> BatteryStatus {
+    let read = |path: std::path::PathBuf| {
+        std::fs::read_to_string(path)
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut battery = BatteryStatus::default();
+    let mut external_power = None;
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return battery;
+    };
+    let mut supplies: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    supplies.sort();
+    for supply in supplies {
+        match read(supply.join("type")).as_str() {
+            "Battery" if !battery.present => {
+                // Peripherals (mice, headsets) report their batteries too
+                if read(supply.join("scope")) == "Device" {
+                    continue;
+                }
+                battery.present = true;
+                battery.level = read(supply.join("capacity")).parse().ok();
+                let status = read(supply.join("status"));
+                battery.charging = match status.as_str() {
+                    "Charging" => Some(true),
+                    "Discharging" | "Not charging" | "Full" => Some(false),
+                    _ => None,
+                };
+                battery.on_battery = (status == "Discharging").then_some(true);
+            }
+            "Mains" | "USB" => {
+                let online = read(supply.join("online")) == "1";
+                external_power = Some(external_power.unwrap_or(false) || online);
+            }
+            _ => {}
+        }
+    }
+    if battery.present {
+        battery.on_battery = external_power.map(|online| !online).or(battery.on_battery);
+    }
+    battery
+}
+
+/// Battery status from `pmset -g batt`
+pub fn parse_pmset_battery(output: &str) -> BatteryStatus {
+    let mut battery = BatteryStatus {
+        on_battery: output
+            .lines()
+            .next()
+            .filter(|line| line.contains("drawing from"))
+            .map(|line| line.contains("Battery Power")),
+        ..Default::default()
+    };
+    let Some(line) = output.lines().find(|line| line.contains("InternalBattery")) else {
+        battery.on_battery = None;
+        return battery;
+    };
+    battery.present = true;
+    let fields: Vec<&str> = line.split(['\t', ';']).map(str::trim).collect();
+    battery.level = fields
+        .iter()
+        .find_map(|field| field.strip_suffix('%')?.parse().ok());
+    battery.charging = fields.iter().find_map(|field| match *field {
+        "charging" => Some(true),
+        "discharging" | "charged" | "finishing charge" | "AC attached" => Some(false),
+        _ => None,
+    });
+    battery
+}
+
+#[cfg(target_os = "linux")]
+fn detect_battery() -> BatteryStatus {
+    read_power_supplies(std::path::Path::new("/sys/class/power_supply"))
+}
+
+#[cfg(target_os = "macos")]
+fn detect_battery() -> BatteryStatus {
+    command_output("pmset", &["-g", "batt"])
+        .map(|output| parse_pmset_battery(&output))
+        .unwrap_or_default()
+}
+
+#[cfg(windows)]
+fn detect_battery() -> BatteryStatus {
+    const NO_SYSTEM_BATTERY: u8 = 128;
+    const CHARGING: u8 = 8;
+    const UNKNOWN: u8 = 255;
+    let Some(status) = windows_power_status() else {
+        return BatteryStatus::default();
+    };
+    let present = status.BatteryFlag & NO_SYSTEM_BATTERY == 0 && status.BatteryFlag != UNKNOWN;
+    BatteryStatus {
+        present,
+        level: (present && status.BatteryLifePercent <= 100).then_some(status.BatteryLifePercent),
+        charging: present.then_some(status.BatteryFlag & CHARGING != 0),
+        on_battery: (present && status.ACLineStatus != UNKNOWN).then_some(status.ACLineStatus == 0),
+        low_power_mode: None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn detect_battery() -> BatteryStatus {
+    BatteryStatus::default()
+}
+
+fn emit_power_event(app: &tauri::AppHandle, event: &PowerEvent) {
+    log::info!("Power event: {}", event.kind);
+    emit_event(app, event);
+}
+
+/// Record that the system is about to sleep
+fn handle_suspend(app: &tauri::AppHandle) {
+    let status = {
+        let state = app.state::<PowerState>();
+        let Ok(mut status) = state.lock() else {
+            return;
+        };
+        if status.suspended {
+            return;
+        }
+        status.suspended = true;
+        status.last_suspend_at = Some(chrono::Utc::now().timestamp());
+        status.clone()
+    };
+    emit_power_event(
+        app,
+        &PowerEvent {
+            kind: POWER_SUSPEND.to_string(),
+            status,
+            disconnected_mcp_servers: Vec::new(),
+        },
+    );
+}
+
+/// Record a wake and revalidate connections
+///
+/// A wake seen by both logind and the clock is handled once.
+fn handle_resume(app: &tauri::AppHandle) {
+    let now = chrono::Utc::now().timestamp();
+    {
+        let state = app.state::<PowerState>();
+        let Ok(mut status) = state.lock() else {
+            return;
+        };
+        let recent = status
+            .last_resume_at
+            .is_some_and(|at| now - at < SLEEP_TOLERANCE.as_secs() as i64);
+        if recent && !status.suspended {
+            return;
+        }
+        status.suspended = false;
+        status.last_resume_at = Some(now);
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let disconnected = drop_closed_mcp_sessions(&app.state::<MCPClientStateHandle>()).await;
+        if let Err(e) = crate::commands::network::check_and_publish(&app).await {
+            log::warn!("Network check after wake failed: {}", e);
+        }
+        emit_power_event(
+            &app,
+            &PowerEvent {
+                kind: POWER_RESUME.to_string(),
+                status: read_status(&app),
+                disconnected_mcp_servers: disconnected,
+            },
+        );
+    });
+}
+
+/// Drop MCP sessions whose server process or transport went away
+async fn drop_closed_mcp_sessions(state: &MCPClientStateHandle) -> Vec<String> {
+    let mut state_guard = state.write().await;
+    let closed: Vec<String> = state_guard
+        .sessions
+        .iter()
+        .filter(|(_, session)| session.service.is_transport_closed())
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in &closed {
+        if let Some(session) = state_guard.sessions.remove(id) {
+            log::warn!(
+                "MCP server {} disconnected during sleep",
+                session.server_name
+            );
+        }
+    }
+    closed
+}
+
+/// Update battery saver, emitting an event when it changed
+fn update_battery_saver(app: &tauri::AppHandle, battery_saver: Option<bool>) {
+    let status = {
+        let state = app.state::<PowerState>();
+        let Ok(mut status) = state.lock() else {
+            return;
+        };
+        if status.battery_saver == battery_saver {
+            return;
+        }
+        let known = status.battery_saver.is_some();
+        status.battery_saver = battery_saver;
+        if !known {
+            return;
+        }
+        status.clone()
+    };
+    emit_power_event(
+        app,
+        &PowerEvent {
+            kind: POWER_BATTERY_SAVER.to_string(),
+            status,
+            disconnected_mcp_servers: Vec::new(),
+        },
+    );
+}
+
+/// Follow logind's sleep announcements
+#[cfg(target_os = "linux")]
+fn watch_logind(app: tauri::AppHandle) {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    let monitor = Command::new("gdbus")
+        .args([
+            "monitor",
+            "--system",
+            "--dest",
+            "org.freedesktop.login1",
+            "--object-path",
+            "/org/freedesktop/login1",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = monitor else {
+        log::debug!("logind cannot be monitored, wake is detected from the clock");
+        return;
+    };
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            match parse_prepare_for_sleep(&line) {
+                Some(true) => handle_suspend(&app),
+                Some(false) => handle_resume(&app),
+                None => {}
+            }
+        }
+    }
+    let _ = child.wait();
+}
+
+/// Detect wakes from the wall clock jumping ahead
+fn watch_clock(app: tauri::AppHandle) {
+    loop {
+        let (started, wall_started) = (Instant::now(), SystemTime::now());
+        std::thread::sleep(CLOCK_CHECK_INTERVAL);
+        let slept = started.elapsed();
+        let wall_elapsed = wall_started.elapsed().unwrap_or_default();
+        if clock_jumped(slept, wall_elapsed) {
+            handle_resume(&app);
+        }
+    }
+}
+
+/// Start watching suspend, wake and battery saver
+pub fn start_power_monitor(app: tauri::AppHandle) {
+    #[cfg(target_os = "linux")]
+    {
+        let app = app.clone();
+        std::thread::spawn(move || watch_logind(app));
+    }
+    {
+        let app = app.clone();
+        std::thread::spawn(move || watch_clock(app));
+    }
+    crate::commands::crash::spawn_guarded("battery saver monitor", async move {
+        let mut interval = tokio::time::interval(BATTERY_SAVER_TICK);
+        loop {
+            interval.tick().await;
+            match tauri::async_runtime::spawn_blocking(detect_battery_saver).await {
+                Ok(battery_saver) => update_battery_saver(&app, battery_saver),
+                Err(e) => log::warn!("Battery saver check failed: {}", e),
+            }
+        }
+    });
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get whether the system is suspending and whether battery saver is on
+#[tauri::command]
+pub fn get_power_status(state: tauri::State<'_, PowerState>) -> Result<PowerStatus, AppError> {
+    state
+        .lock()
+        .map(|status| status.clone())
+        .map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Get the battery level, whether it is charging and whether battery saver
+/// is on
+#[tauri::command]
+pub async fn get_battery_status() -> Result<BatteryStatus, AppError> {
+    tauri::async_runtime::spawn_blocking(|| BatteryStatus {
+        low_power_mode: detect_battery_saver(),
+        ..detect_battery()
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_signals_are_parsed() {
+        let signal = "/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep";
+        assert_eq!(
+            parse_prepare_for_sleep(&format!("{} (true,)", signal)),
+            Some(true)
+        );
+        assert_eq!(
+            parse_prepare_for_sleep(&format!("{} (false,)", signal)),
+            Some(false)
+        );
+        assert_eq!(
+            parse_prepare_for_sleep("org.freedesktop.login1.Manager.SessionNew"),
+            None
+        );
+
+        assert_eq!(parse_power_profile("power-saver\n"), Some(true));
+        assert_eq!(parse_power_profile("balanced\n"), Some(false));
+        assert_eq!(parse_power_profile(""), None);
+        let pmset = "System-wide power settings:\n lowpowermode         1\n sleep 1\n";
+        assert_eq!(parse_pmset_low_power(pmset), Some(true));
+        assert_eq!(parse_pmset_low_power(" sleep 1\n"), None);
+
+        let tick = Duration::from_secs(10);
+        assert!(!clock_jumped(tick, Duration::from_secs(11)));
+        assert!(clock_jumped(tick, Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn battery_status_is_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let supply = |name: &str, files: &[(&str, &str)]| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(&path).unwrap();
+            for (file, value) in files {
+                std::fs::write(path.join(file), format!("{}\n", value)).unwrap();
+            }
+        };
+        supply("AC", &[("type", "Mains"), ("online", "0")]);
+        supply(
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("capacity", "64"),
+                ("status", "Discharging"),
+            ],
+        );
+        supply(
+            "hidpp_battery_0",
+            &[("type", "Battery"), ("scope", "Device"), ("capacity", "5")],
+        );
+        let battery = read_power_supplies(dir.path());
+        assert!(battery.present);
+        assert_eq!(battery.level, Some(64));
+        assert_eq!(battery.charging, Some(false));
+        assert_eq!(battery.on_battery, Some(true));
+        assert!(!read_power_supplies(&dir.path().join("missing")).present);
+
+        let pmset = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=4653155)\t85%; charging; 0:42 remaining present: true\n";
+        let battery = parse_pmset_battery(pmset);
+        assert_eq!(
+            (
+                battery.present,
+                battery.level,
+                battery.charging,
+                battery.on_battery
+            ),
+            (true, Some(85), Some(true), Some(false))
+        );
+        assert!(!parse_pmset_battery("Now drawing from 'AC Power'\n").present);
+    }
+}
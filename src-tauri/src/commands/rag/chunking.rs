@@ -0,0 +1,232 @@
+//! Document text extraction and chunking
+
+use super::types::{DocumentSection, TextChunk};
+use crate::commands::epub::{read_epub_text, xhtml_to_text, XhtmlText};
+use crate::commands::pdf::load_pdf_text;
+use crate::error::AppError;
+use std::path::Path;
+
+/// Longest chunk, in characters
+pub const DEFAULT_CHUNK_CHARS: usize = 1200;
+
+/// Characters repeated from the end of the previous chunk
+pub const DEFAULT_CHUNK_OVERLAP: usize = 200;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn whole_document(label: Option<String>, text: String) -> DocumentSection {
+    DocumentSection {
+        kind: "document".to_string(),
+        location: 0,
+        label,
+        text,
+    }
+}
+
+/// Extract the text of a document, split by page or chapter
+pub fn extract_document_sections(
+    path: &Path,
+    format: &str,
+) -> Result<Vec<DocumentSection>, AppError> {
+    let sections = match format {
+        "pdf" => load_pdf_text(path, None)?
+            .pages
+            .into_iter()
+            .map(|page| DocumentSection {
+                kind: "page".to_string(),
+                location: page.page_number,
+                label: None,
+                text: page.text,
+            })
+            .collect(),
+        "epub" => read_epub_text(path, None)?
+            .chapters
+            .into_iter()
+            .map(|chapter| DocumentSection {
+                kind: "chapter".to_string(),
+                location: chapter.index as u32,
+                label: chapter.title,
+                text: chapter.text,
+            })
+            .collect(),
+        "html" | "htm" | "xhtml" => {
+            let XhtmlText { title, text } =
+                xhtml_to_text(&String::from_utf8_lossy(&std::fs::read(path)?));
+            vec![whole_document(title, text)]
+        }
+        "txt" | "md" | "markdown" => {
+            let text = String::from_utf8_lossy(&std::fs::read(path)?).into_owned();
+            vec![whole_document(None, text)]
+        }
+        other => {
+            return Err(AppError::InvalidInput(format!(
+                "Cannot extract text from {} documents",
+                other
+            )))
+        }
+    };
+    Ok(sections
+        .into_iter()
+        .filter(|s: &DocumentSection| !s.text.trim().is_empty())
+        .collect())
+}
+
+fn is_sentence_end(chars: &[char], i: usize) -> bool {
+    match chars[i] {
+        '\u{3002}' | '\u{FF01}' | '\u{FF1F}' => true,
+        '.' | '!' | '?' => chars.get(i + 1).map_or(true, |c| c.is_whitespace()),
+        _ => false,
+    }
+}
+
+/// End of a chunk starting at `start`, preferring a paragraph break, then a
+/// sentence end, then whitespace in the second half of the window
+fn break_position(chars: &[char], start: usize, max_chars: usize) -> usize {
+    let limit = start + max_chars;
+    if limit >= chars.len() {
+        return chars.len();
+    }
+    let floor = start + max_chars / 2;
+    let candidates: [&dyn Fn(usize) -> bool; 3] = [
+        &|i| chars[i] == '\n',
+        &|i| is_sentence_end(chars, i),
+        &|i| chars[i].is_whitespace(),
+    ];
+    candidates
+        .iter()
+        .find_map(|accept| (floor..limit).rev().find(|&i| accept(i)))
+        .map_or(limit, |i| i + 1)
+}
+
+/// Push `chars[start..end]` without surrounding whitespace, if anything is left
+fn push_chunk(
+    chunks: &mut Vec<TextChunk>,
+    section: usize,
+    chars: &[char],
+    start: usize,
+    end: usize,
+) {
+    let Some(first) = (start..end).find(|&i| !chars[i].is_whitespace()) else {
+        return;
+    };
+    let last = (start..end)
+        .rev()
+        .find(|&i| !chars[i].is_whitespace())
+        .unwrap_or(first);
+    chunks.push(TextChunk {
+        section,
+        char_start: first,
+        char_end: last + 1,
+        text: chars[first..=last].iter().collect(),
+    });
+}
+
+/// Split sections into overlapping chunks of at most `max_chars` characters
+///
+/// Chunks never span sections, so each one maps to a single page or chapter.
+pub fn chunk_sections(
+    sections: &[DocumentSection],
+    max_chars: usize,
+    overlap: usize,
+) -> Vec<TextChunk> {
+    let max_chars = max_chars.max(1);
+    let overlap = overlap.min(max_chars / 2);
+    let mut chunks = Vec::new();
+
+    for (index, section) in sections.iter().enumerate() {
+        let chars: Vec<char> = section.text.chars().collect();
+        let mut start = 0;
+        while start < chars.len() {
+            let end = break_position(&chars, start, max_chars);
+            push_chunk(&mut chunks, index, &chars, start, end);
+            if end >= chars.len() {
+                break;
+            }
+            // Step back for the overlap, then forward to the next word start
+            // (text without spaces, such as CJK, is cut anywhere)
+            let next = end.saturating_sub(overlap).max(start + 1);
+            start = (next..end)
+                .find(|&i| chars[i - 1].is_whitespace())
+                .unwrap_or(next);
+        }
+    }
+    chunks
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn section(location: u32, text: &str) -> DocumentSection {
+        DocumentSection {
+            kind: "page".to_string(),
+            location,
+            label: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn chunks_break_at_sentences_and_overlap() {
+        let text = "First sentence here. Second sentence is longer. Third one ends it.";
+        let sections = vec![section(1, text), section(2, "  \n "), section(3, "Tail")];
+        let chunks = chunk_sections(&sections, 30, 10);
+
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "First sentence here.",
+                "here. Second sentence is",
+                "is longer. Third one ends it.",
+                "Tail"
+            ]
+        );
+        assert!(chunks.iter().all(|c| c.text.chars().count() <= 30));
+        let chars: Vec<char> = text.chars().collect();
+        for chunk in chunks.iter().filter(|c| c.section == 0) {
+            let slice: String = chars[chunk.char_start..chunk.char_end].iter().collect();
+            assert_eq!(slice, chunk.text);
+        }
+        assert_eq!(chunks.last().unwrap().section, 2);
+    }
+
+    #[test]
+    fn chunks_unbroken_text_at_the_limit() {
+        let text = "你好世界".repeat(10);
+        let chunks = chunk_sections(&[section(1, &text)], 16, 4);
+        assert_eq!(chunks[0].char_end, 16);
+        assert_eq!(chunks[1].char_start, 12);
+        assert_eq!(chunks.last().unwrap().char_end, 40);
+    }
+
+    #[test]
+    fn extracts_plain_and_html_documents() {
+        let dir = tempdir().unwrap();
+        let html = dir.path().join("clip.html");
+        std::fs::write(
+            &html,
+            "<html><head><title>Clip</title></head><body><p>Hello</p></body></html>",
+        )
+        .unwrap();
+        let sections = extract_document_sections(&html, "html").unwrap();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].label.as_deref(), Some("Clip"));
+        assert_eq!(sections[0].text, "Hello");
+
+        let empty = dir.path().join("empty.txt");
+        std::fs::write(&empty, " \n").unwrap();
+        assert!(extract_document_sections(&empty, "txt").unwrap().is_empty());
+        assert!(matches!(
+            extract_document_sections(&empty, "cbz"),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,173 @@
+//! Building and managing per-document embedding indexes
+
+use super::chunking::{
+    chunk_sections, extract_document_sections, DEFAULT_CHUNK_CHARS, DEFAULT_CHUNK_OVERLAP,
+};
+use super::store::{
+    delete_index, get_index_status, load_cached_embeddings, replace_index, RagIndexKey,
+};
+use super::types::{RagChunk, RagIndexProgress, RagIndexStatus};
+use crate::commands::ai_proxy::request_embeddings;
+use crate::commands::events::emit_event;
+use crate::commands::library::{get_document, lock_library, LibraryState};
+use crate::error::AppError;
+use std::path::PathBuf;
+
+/// Event emitted after each embedding batch
+pub const RAG_INDEX_PROGRESS_EVENT: &str = "rag-index-progress";
+
+/// Chunks sent per embeddings request
+const EMBEDDING_BATCH_SIZE: usize = 64;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn emit_progress(app: &tauri::AppHandle, document_id: &str, embedded: usize, total: usize) {
+    let progress = RagIndexProgress {
+        document_id: document_id.to_string(),
+        embedded,
+        total,
+    };
+    emit_event(app, &progress);
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Build the embedding index of a library document
+///
+/// An up-to-date index built with the same provider and model is returned
+/// as is unless `rebuild` is set. Chunks whose text is unchanged keep their
+/// stored embeddings.
+#[tauri::command]
+pub async fn rag_build_index(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+    provider: String,
+    model: String,
+    rebuild: Option<bool>,
+) -> Result<RagIndexStatus, AppError> {
+    let library = state.inner().clone();
+    let (document, existing) = {
+        let conn = lock_library(&library)?;
+        let document = get_document(&conn, &document_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Document '{}' not found", document_id)))?;
+        (document, get_index_status(&conn, &document_id)?)
+    };
+    let current = existing.filter(|status| {
+        !rebuild.unwrap_or(false)
+            && !status.stale
+            && status.provider == provider
+            && status.model == model
+    });
+    if let Some(status) = current {
+        return Ok(status);
+    }
+
+    let path = PathBuf::from(&document.path);
+    if !path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "File not found: {}",
+            document.path
+        )));
+    }
+    let format = document.format.clone();
+    let sections =
+        tauri::async_runtime::spawn_blocking(move || extract_document_sections(&path, &format))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))??;
+    let chunks = chunk_sections(&sections, DEFAULT_CHUNK_CHARS, DEFAULT_CHUNK_OVERLAP);
+    if chunks.is_empty() {
+        return Err(AppError::InvalidInput(format!(
+            "No text could be extracted from {}",
+            document.file_name
+        )));
+    }
+
+    let cached =
+        load_cached_embeddings(&*lock_library(&library)?, &document_id, &provider, &model)?;
+    let mut embeddings: Vec<Option<Vec<f32>>> = chunks
+        .iter()
+        .map(|chunk| cached.get(&chunk.text).cloned())
+        .collect();
+    let missing: Vec<usize> = (0..chunks.len())
+        .filter(|&i| embeddings[i].is_none())
+        .collect();
+    let total = chunks.len();
+    let reused = total - missing.len();
+    let mut embedded = reused;
+    emit_progress(&app, &document_id, embedded, total);
+
+    for batch in missing.chunks(EMBEDDING_BATCH_SIZE) {
+        let inputs: Vec<String> = batch.iter().map(|&i| chunks[i].text.clone()).collect();
+        let vectors = request_embeddings(&provider, &model, &inputs).await?;
+        for (&i, vector) in batch.iter().zip(vectors) {
+            embeddings[i] = Some(vector);
+        }
+        embedded += batch.len();
+        emit_progress(&app, &document_id, embedded, total);
+    }
+
+    let stored: Vec<RagChunk> = chunks
+        .into_iter()
+        .zip(embeddings)
+        .zip(0u32..)
+        .map(|((chunk, embedding), chunk_index)| {
+            let section = &sections[chunk.section];
+            RagChunk {
+                chunk_index,
+                location_kind: section.kind.clone(),
+                location: section.location,
+                location_label: section.label.clone(),
+                char_start: chunk.char_start,
+                char_end: chunk.char_end,
+                text: chunk.text,
+                embedding: embedding.unwrap_or_default(),
+            }
+        })
+        .collect();
+    let key = RagIndexKey {
+        document_id,
+        document_hash: document.hash,
+        provider,
+        model,
+    };
+    let conn = lock_library(&library)?;
+    let status = replace_index(&conn, &key, &stored, chrono::Utc::now().timestamp())?;
+    log::info!(
+        "Indexed {} chunks of {} ({} reused)",
+        status.chunk_count,
+        document.path,
+        reused
+    );
+    Ok(status)
+}
+
+/// Get the embedding index of a library document, if it has one
+#[tauri::command]
+pub fn rag_get_index_status(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+) -> Result<Option<RagIndexStatus>, AppError> {
+    let conn = lock_library(&state)?;
+    get_index_status(&conn, &document_id)
+}
+
+/// Delete the embedding index of a library document
+#[tauri::command]
+pub fn rag_delete_index(
+    state: tauri::State<'_, LibraryState>,
+    document_id: String,
+) -> Result<(), AppError> {
+    let conn = lock_library(&state)?;
+    if !delete_index(&conn, &document_id)? {
+        return Err(AppError::NotFound(format!(
+            "No index for document '{}'",
+            document_id
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,22 @@
+//! Retrieval-augmented generation over library documents
+//!
+//! Document text is chunked and embedded through the AI proxy, and the vectors
+//! are stored in the library database:
+//! - Text extraction per page (PDF), chapter (EPUB) or whole document
+//! - Overlapping chunks that break at paragraphs and sentences
+//! - Embedding storage keyed by document and chunk location
+//! - Commands to build, rebuild, inspect and delete a document's index
+//! - Semantic search returning the closest chunks with their locations
+
+mod types;
+mod chunking;
+mod store;
+mod index;
+mod search;
+
+// Re-export all public items
+pub use types::*;
+pub use chunking::*;
+pub use store::*;
+pub use index::*;
+pub use search::*;
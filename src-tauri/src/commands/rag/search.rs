@@ -0,0 +1,307 @@
+//! Nearest-neighbor search over stored chunk embeddings
+
+use super::store::decode_embedding;
+use super::types::{SemanticSearchHit, SemanticSearchQuery};
+use crate::commands::ai_proxy::request_embeddings;
+use crate::commands::library::{lock_library, LibraryState};
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+
+/// Results returned when the caller does not ask for a number
+pub const DEFAULT_SEARCH_TOP_K: usize = 10;
+const MAX_SEARCH_TOP_K: usize = 100;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Cosine similarity of two vectors (0 for mismatched or zero vectors)
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a.sqrt() * norm_b.sqrt())
+    }
+}
+
+/// Pick the embedding provider and model to search with
+///
+/// Without an explicit choice the documents must all be indexed with the
+/// same model, since vectors of different models are not comparable.
+pub fn resolve_search_model(
+    conn: &Connection,
+    document_ids: Option<&HashSet<String>>,
+    provider: Option<&str>,
+    model: Option<&str>,
+) -> Result<(String, String), AppError> {
+    let mut stmt = conn.prepare("SELECT document_id, provider, model FROM rag_indexes")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    let mut models: Vec<(String, String)> = Vec::new();
+    for row in rows {
+        let (document_id, index_provider, index_model) = row?;
+        let wanted = document_ids.map_or(true, |ids| ids.contains(&document_id))
+            && provider.map_or(true, |p| p == index_provider)
+            && model.map_or(true, |m| m == index_model);
+        if wanted && !models.contains(&(index_provider.clone(), index_model.clone())) {
+            models.push((index_provider, index_model));
+        }
+    }
+
+    match models.len() {
+        0 => Err(AppError::NotFound(
+            "No indexed documents match the search".to_string(),
+        )),
+        1 => Ok(models.remove(0)),
+        _ => Err(AppError::InvalidInput(format!(
+            "Documents are indexed with different models ({}); choose one",
+            models
+                .iter()
+                .map(|(p, m)| format!("{}/{}", p, m))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+/// Find the chunks closest to a query vector
+pub fn search_chunks(
+    conn: &Connection,
+    provider: &str,
+    model: &str,
+    document_ids: Option<&HashSet<String>>,
+    query: &[f32],
+    top_k: usize,
+) -> Result<Vec<SemanticSearchHit>, AppError> {
+    // Score first and read the text of the best chunks afterwards, so only
+    // one embedding is held in memory at a time
+    let mut stmt = conn.prepare(
+        "SELECT c.document_id, c.chunk_index, c.embedding FROM rag_chunks c
+         JOIN rag_indexes r ON r.document_id = c.document_id
+         WHERE r.provider = ?1 AND r.model = ?2",
+    )?;
+    let mut rows = stmt.query(params![provider, model])?;
+    let mut scored: Vec<(f32, String, u32)> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let document_id: String = row.get(0)?;
+        if document_ids.is_some_and(|ids| !ids.contains(&document_id)) {
+            continue;
+        }
+        let embedding: Vec<u8> = row.get(2)?;
+        scored.push((
+            cosine_similarity(query, &decode_embedding(&embedding)),
+            document_id,
+            row.get(1)?,
+        ));
+    }
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.truncate(top_k);
+
+    let mut detail = conn.prepare(
+        "SELECT d.title, d.file_name, c.location_kind, c.location, c.location_label,
+             c.char_start, c.char_end, c.text
+         FROM rag_chunks c JOIN documents d ON d.id = c.document_id
+         WHERE c.document_id = ?1 AND c.chunk_index = ?2",
+    )?;
+    scored
+        .into_iter()
+        .map(|(score, document_id, chunk_index)| {
+            detail
+                .query_row(params![document_id, chunk_index], |row| {
+                    Ok(SemanticSearchHit {
+                        document_id: document_id.clone(),
+                        document_title: row.get(0)?,
+                        file_name: row.get(1)?,
+                        chunk_index,
+                        location_kind: row.get(2)?,
+                        location: row.get(3)?,
+                        location_label: row.get(4)?,
+                        char_start: row.get::<_, u32>(5)? as usize,
+                        char_end: row.get::<_, u32>(6)? as usize,
+                        text: row.get(7)?,
+                        score,
+                    })
+                })
+                .map_err(AppError::from)
+        })
+        .collect()
+}
+
+/// Embed the query and search the indexed documents
+///
+/// Used by the `semantic_search` command and by backend callers assembling
+/// AI context.
+pub async fn run_semantic_search(
+    library: &LibraryState,
+    query: &SemanticSearchQuery,
+) -> Result<Vec<SemanticSearchHit>, AppError> {
+    let text = query.query.trim();
+    if text.is_empty() {
+        return Err(AppError::InvalidInput("Search query is empty".to_string()));
+    }
+    let top_k = query
+        .top_k
+        .unwrap_or(DEFAULT_SEARCH_TOP_K)
+        .clamp(1, MAX_SEARCH_TOP_K);
+    let document_ids: Option<HashSet<String>> = query
+        .document_ids
+        .as_ref()
+        .map(|ids| ids.iter().cloned().collect());
+
+    let (provider, model) = resolve_search_model(
+        &*lock_library(library)?,
+        document_ids.as_ref(),
+        query.provider.as_deref(),
+        query.model.as_deref(),
+    )?;
+    let vector = request_embeddings(&provider, &model, &[text.to_string()])
+        .await?
+        .pop()
+        .unwrap_or_default();
+
+    let conn = lock_library(library)?;
+    search_chunks(
+        &conn,
+        &provider,
+        &model,
+        document_ids.as_ref(),
+        &vector,
+        top_k,
+    )
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Search indexed documents by meaning, returning the closest chunks
+#[tauri::command]
+pub async fn semantic_search(
+    state: tauri::State<'_, LibraryState>,
+    query: String,
+    document_ids: Option<Vec<String>>,
+    top_k: Option<usize>,
+    provider: Option<String>,
+    model: Option<String>,
+) -> Result<Vec<SemanticSearchHit>, AppError> {
+    let library = state.inner().clone();
+    let query = SemanticSearchQuery {
+        query,
+        document_ids,
+        top_k,
+        provider,
+        model,
+    };
+    run_semantic_search(&library, &query).await
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::store::{replace_index, RagIndexKey};
+    use super::super::types::RagChunk;
+    use super::*;
+    use crate::commands::library::{
+        upsert_document, FileFingerprint, LibraryDocumentInput, LIBRARY_MIGRATIONS,
+    };
+    use crate::db;
+
+    fn index(conn: &Connection, path: &str, model: &str, vectors: &[Vec<f32>]) -> String {
+        let input = LibraryDocumentInput {
+            path: path.to_string(),
+            title: Some(path.to_string()),
+            ..Default::default()
+        };
+        let fingerprint = FileFingerprint {
+            hash: path.to_string(),
+            size: 1,
+            modified_at: None,
+        };
+        let doc = upsert_document(conn, &input, &fingerprint, 1).unwrap();
+        let chunks: Vec<RagChunk> = vectors
+            .iter()
+            .zip(0u32..)
+            .map(|(vector, i)| RagChunk {
+                chunk_index: i,
+                location_kind: "page".to_string(),
+                location: i + 1,
+                location_label: None,
+                char_start: 0,
+                char_end: 4,
+                text: format!("chunk {}", i),
+                embedding: vector.clone(),
+            })
+            .collect();
+        let key = RagIndexKey {
+            document_id: doc.id.clone(),
+            document_hash: path.to_string(),
+            provider: "openai".to_string(),
+            model: model.to_string(),
+        };
+        replace_index(conn, &key, &chunks, 1).unwrap();
+        doc.id
+    }
+
+    #[test]
+    fn cosine_similarity_handles_edge_cases() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn searches_nearest_chunks_of_selected_documents() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let a = index(&conn, "/a.pdf", "small", &[vec![1.0, 0.0], vec![0.6, 0.8]]);
+        let b = index(&conn, "/b.pdf", "small", &[vec![0.9, 0.1]]);
+        let c = index(&conn, "/c.pdf", "large", &[vec![1.0, 0.0, 0.0]]);
+
+        let hits = search_chunks(&conn, "openai", "small", None, &[1.0, 0.0], 2).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(
+            (hits[0].document_id.as_str(), hits[0].chunk_index),
+            (a.as_str(), 0)
+        );
+        assert_eq!(hits[1].document_id, b);
+        assert_eq!(hits[0].document_title.as_deref(), Some("/a.pdf"));
+        assert!(hits[0].score > hits[1].score);
+
+        let only_a: HashSet<String> = [a.clone()].into_iter().collect();
+        let hits = search_chunks(&conn, "openai", "small", Some(&only_a), &[0.0, 1.0], 5).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].location, 2);
+
+        assert!(matches!(
+            resolve_search_model(&conn, None, None, None),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert_eq!(
+            resolve_search_model(&conn, Some(&only_a), None, None).unwrap(),
+            ("openai".to_string(), "small".to_string())
+        );
+        let only_c: HashSet<String> = [c].into_iter().collect();
+        assert!(matches!(
+            resolve_search_model(&conn, Some(&only_c), None, Some("small")),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
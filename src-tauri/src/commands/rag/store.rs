@@ -0,0 +1,252 @@
+//! Embedding storage in the library database
+//!
+//! Vectors are stored as little-endian `f32` blobs next to the chunk text and
+//! its location, one row per chunk.
+
+use super::types::{RagChunk, RagIndexStatus};
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::collections::HashMap;
+
+const INDEX_COLUMNS: &str = "r.document_id, r.provider, r.model, r.dimensions, r.chunk_count,
+    d.hash <> r.document_hash, r.created_at, r.updated_at";
+
+/// Identity of an index being written
+#[derive(Clone, Debug)]
+pub struct RagIndexKey {
+    pub document_id: String,
+    pub document_hash: String,
+    pub provider: String,
+    pub model: String,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Serialize an embedding to a blob
+pub fn encode_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Deserialize an embedding blob
+pub fn decode_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+fn row_to_status(row: &Row) -> rusqlite::Result<RagIndexStatus> {
+    Ok(RagIndexStatus {
+        document_id: row.get(0)?,
+        provider: row.get(1)?,
+        model: row.get(2)?,
+        dimensions: row.get(3)?,
+        chunk_count: row.get(4)?,
+        stale: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+/// Get the index of a document
+pub fn get_index_status(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Option<RagIndexStatus>, AppError> {
+    let sql = format!(
+        "SELECT {} FROM rag_indexes r JOIN documents d ON d.id = r.document_id
+         WHERE r.document_id = ?1",
+        INDEX_COLUMNS
+    );
+    Ok(conn
+        .query_row(&sql, params![document_id], row_to_status)
+        .optional()?)
+}
+
+/// Embeddings of an existing index by chunk text, so unchanged chunks are not
+/// embedded again on rebuild
+///
+/// Empty when the index was built with a different provider or model.
+pub fn load_cached_embeddings(
+    conn: &Connection,
+    document_id: &str,
+    provider: &str,
+    model: &str,
+) -> Result<HashMap<String, Vec<f32>>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT c.text, c.embedding FROM rag_chunks c
+         JOIN rag_indexes r ON r.document_id = c.document_id
+         WHERE c.document_id = ?1 AND r.provider = ?2 AND r.model = ?3",
+    )?;
+    let rows = stmt.query_map(params![document_id, provider, model], |row| {
+        let text: String = row.get(0)?;
+        let embedding: Vec<u8> = row.get(1)?;
+        Ok((text, decode_embedding(&embedding)))
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+/// Replace the index of a document with new chunks
+pub fn replace_index(
+    conn: &Connection,
+    key: &RagIndexKey,
+    chunks: &[RagChunk],
+    now: i64,
+) -> Result<RagIndexStatus, AppError> {
+    let dimensions = chunks.first().map_or(0, |c| c.embedding.len());
+    if chunks.iter().any(|c| c.embedding.len() != dimensions) {
+        return Err(AppError::InvalidInput(
+            "Embeddings have inconsistent dimensions".to_string(),
+        ));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO rag_indexes (document_id, document_hash, provider, model, dimensions,
+             chunk_count, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+         ON CONFLICT(document_id) DO UPDATE SET document_hash = excluded.document_hash,
+             provider = excluded.provider, model = excluded.model,
+             dimensions = excluded.dimensions, chunk_count = excluded.chunk_count,
+             updated_at = excluded.updated_at",
+        params![
+            key.document_id,
+            key.document_hash,
+            key.provider,
+            key.model,
+            dimensions as u32,
+            chunks.len() as u32,
+            now
+        ],
+    )?;
+    tx.execute(
+        "DELETE FROM rag_chunks WHERE document_id = ?1",
+        params![key.document_id],
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO rag_chunks (document_id, chunk_index, location_kind, location,
+                 location_label, char_start, char_end, text, embedding)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )?;
+        for chunk in chunks {
+            stmt.execute(params![
+                key.document_id,
+                chunk.chunk_index,
+                chunk.location_kind,
+                chunk.location,
+                chunk.location_label,
+                chunk.char_start as u32,
+                chunk.char_end as u32,
+                chunk.text,
+                encode_embedding(&chunk.embedding)
+            ])?;
+        }
+    }
+    tx.commit()?;
+
+    get_index_status(conn, &key.document_id)?
+        .ok_or_else(|| AppError::Database("Index was not stored".to_string()))
+}
+
+/// Delete the index of a document, returning whether one existed
+pub fn delete_index(conn: &Connection, document_id: &str) -> Result<bool, AppError> {
+    let changed = conn.execute(
+        "DELETE FROM rag_indexes WHERE document_id = ?1",
+        params![document_id],
+    )?;
+    Ok(changed > 0)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::library::LIBRARY_MIGRATIONS;
+    use crate::commands::library::{upsert_document, FileFingerprint, LibraryDocumentInput};
+    use crate::db;
+
+    fn chunk(index: u32, text: &str, embedding: Vec<f32>) -> RagChunk {
+        RagChunk {
+            chunk_index: index,
+            location_kind: "page".to_string(),
+            location: index + 1,
+            location_label: None,
+            char_start: 0,
+            char_end: text.chars().count(),
+            text: text.to_string(),
+            embedding,
+        }
+    }
+
+    #[test]
+    fn embeddings_round_trip_through_blobs() {
+        let embedding = vec![0.5, -1.25, 3.0e-7];
+        assert_eq!(decode_embedding(&encode_embedding(&embedding)), embedding);
+    }
+
+    #[test]
+    fn index_is_replaced_reused_and_cascades() {
+        let conn = db::open_in_memory(LIBRARY_MIGRATIONS).unwrap();
+        let input = LibraryDocumentInput {
+            path: "/books/a.pdf".to_string(),
+            ..Default::default()
+        };
+        let fingerprint = FileFingerprint {
+            hash: "hash1".to_string(),
+            size: 1,
+            modified_at: None,
+        };
+        let doc = upsert_document(&conn, &input, &fingerprint, 1).unwrap();
+        let key = RagIndexKey {
+            document_id: doc.id.clone(),
+            document_hash: "hash1".to_string(),
+            provider: "openai".to_string(),
+            model: "small".to_string(),
+        };
+
+        let chunks = vec![
+            chunk(0, "one", vec![1.0, 0.0]),
+            chunk(1, "two", vec![0.0, 1.0]),
+        ];
+        let status = replace_index(&conn, &key, &chunks, 10).unwrap();
+        assert_eq!((status.dimensions, status.chunk_count), (2, 2));
+        assert!(!status.stale);
+
+        let cached = load_cached_embeddings(&conn, &doc.id, "openai", "small").unwrap();
+        assert_eq!(cached["two"], vec![0.0, 1.0]);
+        assert!(load_cached_embeddings(&conn, &doc.id, "openai", "large")
+            .unwrap()
+            .is_empty());
+
+        let status = replace_index(&conn, &key, &chunks[..1], 20).unwrap();
+        assert_eq!((status.created_at, status.updated_at), (10, 20));
+        assert_eq!(status.chunk_count, 1);
+        assert!(replace_index(
+            &conn,
+            &key,
+            &[chunk(0, "x", vec![1.0]), chunks[1].clone()],
+            30
+        )
+        .is_err());
+
+        let changed = FileFingerprint {
+            hash: "hash2".to_string(),
+            ..fingerprint
+        };
+        upsert_document(&conn, &input, &changed, 2).unwrap();
+        assert!(get_index_status(&conn, &doc.id).unwrap().unwrap().stale);
+
+        conn.execute("DELETE FROM documents", []).unwrap();
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM rag_chunks", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+        assert!(!delete_index(&conn, &doc.id).unwrap());
+    }
+}
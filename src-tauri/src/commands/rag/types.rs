@@ -0,0 +1,94 @@
+//! Retrieval index data structures
+
+use serde::{Deserialize, Serialize};
+
+/// A located piece of document text: a PDF page, an EPUB chapter or a whole
+/// text document
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentSection {
+    /// "page", "chapter" or "document"
+    pub kind: String,
+    /// 1-based page number, spine index, or 0 for whole documents
+    pub location: u32,
+    /// Chapter title, when known
+    pub label: Option<String>,
+    pub text: String,
+}
+
+/// A chunk of a section, with character offsets into the section text
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextChunk {
+    /// Index into the extracted sections
+    pub section: usize,
+    pub char_start: usize,
+    pub char_end: usize,
+    pub text: String,
+}
+
+/// A stored chunk with its embedding
+#[derive(Clone, Debug)]
+pub struct RagChunk {
+    pub chunk_index: u32,
+    pub location_kind: String,
+    pub location: u32,
+    pub location_label: Option<String>,
+    pub char_start: usize,
+    pub char_end: usize,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+/// Embedding index of a library document
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RagIndexStatus {
+    pub document_id: String,
+    pub provider: String,
+    pub model: String,
+    pub dimensions: u32,
+    pub chunk_count: u32,
+    /// The file changed since the index was built
+    pub stale: bool,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Progress event emitted while embedding a document
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RagIndexProgress {
+    pub document_id: String,
+    pub embedded: usize,
+    pub total: usize,
+}
+
+/// A semantic search over indexed documents
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticSearchQuery {
+    pub query: String,
+    /// Restrict the search to these documents (all indexed documents otherwise)
+    pub document_ids: Option<Vec<String>>,
+    pub top_k: Option<usize>,
+    /// Embedding provider and model; inferred from the indexes when omitted
+    pub provider: Option<String>,
+    pub model: Option<String>,
+}
+
+/// A chunk matching a semantic search
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticSearchHit {
+    pub document_id: String,
+    pub document_title: Option<String>,
+    pub file_name: String,
+    pub chunk_index: u32,
+    pub location_kind: String,
+    pub location: u32,
+    pub location_label: Option<String>,
+    pub char_start: usize,
+    pub char_end: usize,
+    pub text: String,
+    /// Cosine similarity to the query
+    pub score: f32,
+}
@@ -0,0 +1,220 @@
+//! Retries of failed network operations
+//!
+//! `with_retry` runs an operation again while it fails with an error that
+//! `AppError::is_retryable` says may go away (network failures, server
+//! errors, rate limits), waiting twice as long before each retry. How many
+//! attempts are made and the first delay come from the `network.retry*`
+//! settings, loaded at startup and reloaded when they change, so requests
+//! made without an app handle follow them too.
+
+use crate::commands::settings::{setting, SETTING_CHANGED_EVENT};
+use crate::error::AppError;
+use std::future::Future;
+use std::sync::RwLock;
+use std::time::Duration;
+use tauri::Listener;
+
+/// Setting holding the attempts made, the first included
+pub const RETRY_MAX_ATTEMPTS_SETTING: &str = "network.retryMaxAttempts";
+
+/// Setting holding the delay before the first retry, in milliseconds
+pub const RETRY_BASE_DELAY_SETTING: &str = "network.retryBaseDelayMs";
+
+/// Longest wait between two attempts
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Policy in effect
+static RETRY_POLICY: RwLock<RetryPolicy> = RwLock::new(RetryPolicy::DEFAULT);
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts made, the first included
+    pub max_attempts: u32,
+    /// Wait before the first retry
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub const DEFAULT: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(500),
+    };
+
+    /// Wait after the given failed attempt (1 for the first)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay
+            .checked_mul(factor)
+            .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// The policy in effect
+pub fn retry_policy() -> RetryPolicy {
+    RETRY_POLICY
+        .read()
+        .map_or(RetryPolicy::DEFAULT, |policy| *policy)
+}
+
+fn load_retry_policy(app: &tauri::AppHandle) {
+    let policy = setting::<u32>(app, RETRY_MAX_ATTEMPTS_SETTING).and_then(|max_attempts| {
+        Ok(RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(setting(app, RETRY_BASE_DELAY_SETTING)?),
+        })
+    });
+    match policy {
+        Ok(policy) => {
+            if let Ok(mut current) = RETRY_POLICY.write() {
+                *current = policy;
+            }
+        }
+        Err(e) => log::warn!("Failed to load the retry policy: {}", e),
+    }
+}
+
+/// Load the policy and reload it whenever its settings change
+pub fn init_retry_policy(app: &tauri::AppHandle) {
+    load_retry_policy(app);
+    let handle = app.clone();
+    app.listen_any(SETTING_CHANGED_EVENT, move |event| {
+        let key = serde_json::from_str::<serde_json::Value>(event.payload())
+            .ok()
+            .and_then(|change| change["key"].as_str().map(str::to_string));
+        if matches!(
+            key.as_deref(),
+            Some(RETRY_MAX_ATTEMPTS_SETTING | RETRY_BASE_DELAY_SETTING)
+        ) {
+            load_retry_policy(&handle);
+        }
+    });
+}
+
+/// Run an operation, retrying retryable failures as the policy says
+pub async fn retry_with<T, F, Fut>(
+    policy: RetryPolicy,
+    what: &str,
+    mut operation: F,
+) -> Result<T, AppError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, AppError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if e.is_retryable() && attempt < policy.max_attempts => {
+                let delay = policy.delay(attempt);
+                log::warn!(
+                    "{} failed (attempt {} of {}), retrying in {:?}: {}",
+                    what,
+                    attempt,
+                    policy.max_attempts,
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Run an operation, retrying retryable failures under the policy in effect
+pub async fn with_retry<T, F, Fut>(what: &str, operation: F) -> Result<T, AppError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, AppError>>,
+{
+    retry_with(retry_policy(), what, operation).await
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCategory;
+    use std::cell::Cell;
+
+    #[tokio::test]
+    async fn only_retryable_errors_are_retried() {
+        let http = |status: u16| AppError::Http {
+            status: Some(status),
+            detail: format!("failed with status {}", status),
+        };
+        let category = |e: AppError| e.category();
+        assert_eq!(category(http(429)), ErrorCategory::RateLimit);
+        assert_eq!(category(http(503)), ErrorCategory::Network);
+        assert_eq!(
+            category(AppError::http(
+                "error sending request for url (https://api.openai.com/)"
+            )),
+            ErrorCategory::Network
+        );
+        assert_eq!(category(http(401)), ErrorCategory::Auth);
+        assert_eq!(category(http(404)), ErrorCategory::NotFound);
+        assert_eq!(
+            category(AppError::Keyring("locked".to_string())),
+            ErrorCategory::Internal
+        );
+        assert!(!http(400).is_retryable());
+        // Answered, but the reply could not be read
+        assert!(!http(200).is_retryable());
+        assert!(
+            !AppError::InvalidOutput("Expected 2 embeddings, got 1".to_string()).is_retryable()
+        );
+        assert!(AppError::Io(std::io::ErrorKind::TimedOut.into()).is_retryable());
+        assert!(!AppError::InvalidInput("x".to_string()).is_retryable());
+
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+        };
+        assert_eq!(policy.delay(3), Duration::from_millis(4));
+        assert_eq!(RetryPolicy::DEFAULT.delay(40), MAX_RETRY_DELAY);
+
+        let attempts = Cell::new(0);
+        let result = retry_with(policy, "flaky", || {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move {
+                match attempt {
+                    1 => Err(http(502)),
+                    _ => Ok(attempt),
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+
+        attempts.set(0);
+        let result: Result<(), AppError> = retry_with(policy, "down", || {
+            attempts.set(attempts.get() + 1);
+            async { Err(http(500)) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 3);
+
+        attempts.set(0);
+        let result: Result<(), AppError> = retry_with(policy, "rejected", || {
+            attempts.set(attempts.get() + 1);
+            async { Err(http(403)) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+}
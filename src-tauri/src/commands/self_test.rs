@@ -0,0 +1,265 @@
+//! Self-test of the environment the app depends on
+//!
+//! `run_self_test` checks that the OS keyring stores and returns secrets, the
+//! app data directory is writable, the network is reachable, every SQLite
+//! store passes `PRAGMA integrity_check` and child processes can be spawned.
+//! Each check reports "pass", "warn" or "fail" with a detail, so a support
+//! flow can show what is broken before asking for diagnostics.
+
+use crate::commands::ai_keys::KEYRING_SERVICE;
+use crate::commands::migrations::{StoreMigrations, DATA_STORES};
+use crate::commands::network::check_network;
+use crate::error::AppError;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+use tauri::Manager;
+
+/// Keyring entry written and removed by the keyring check
+const SELF_TEST_KEYRING_USER: &str = "self-test";
+
+/// File written and removed by the app data check
+const SELF_TEST_FILE: &str = ".self-test";
+
+/// Problems reported per database, at most
+const MAX_INTEGRITY_ISSUES: u32 = 10;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Result of one check
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestCheck {
+    /// "keyring", "appData", "network", "database" or "process"
+    pub name: String,
+    /// "pass", "warn" or "fail"
+    pub status: String,
+    pub detail: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    /// No check failed
+    pub passed: bool,
+    pub checks: Vec<SelfTestCheck>,
+    pub ran_at: i64,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Time a check; an error fails it
+fn run_check<F>(name: &str, check: F) -> SelfTestCheck
+where
+    F: FnOnce() -> Result<(&'static str, String), AppError>,
+{
+    let started = Instant::now();
+    let (status, detail) = match check() {
+        Ok((status, detail)) => (status, detail),
+        Err(e) => ("fail", e.to_string()),
+    };
+    SelfTestCheck {
+        name: name.to_string(),
+        status: status.to_string(),
+        detail,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+/// Store, read back and delete a throwaway secret
+fn check_keyring() -> Result<(&'static str, String), AppError> {
+    let keyring_err = |e: keyring::Error| AppError::Keyring(e.to_string());
+    let entry =
+        keyring::Entry::new(KEYRING_SERVICE, SELF_TEST_KEYRING_USER).map_err(keyring_err)?;
+    let secret = uuid::Uuid::new_v4().to_string();
+    entry.set_password(&secret).map_err(keyring_err)?;
+    let stored = entry.get_password().map_err(keyring_err);
+    if let Err(e) = entry.delete_credential() {
+        log::warn!("Failed to delete the self-test keyring entry: {}", e);
+    }
+    if stored? != secret {
+        return Err(AppError::Keyring(
+            "The keyring returned a different secret".to_string(),
+        ));
+    }
+    Ok(("pass", "Secrets can be stored and read".to_string()))
+}
+
+/// Write, read back and delete a file in a directory
+pub fn check_writable(dir: &Path) -> Result<(&'static str, String), AppError> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(SELF_TEST_FILE);
+    let content = uuid::Uuid::new_v4().to_string();
+    fs::write(&path, &content)?;
+    let read = fs::read_to_string(&path);
+    fs::remove_file(&path)?;
+    if read? != content {
+        return Err(AppError::Io(std::io::Error::other(
+            "The file read back differs from the one written",
+        )));
+    }
+    Ok(("pass", format!("{} is writable", dir.display())))
+}
+
+/// Problems `PRAGMA integrity_check` finds in a database; none when intact
+pub fn database_integrity(path: &Path) -> Result<Vec<String>, AppError> {
+    let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let mut stmt = conn.prepare(&format!("PRAGMA integrity_check({})", MAX_INTEGRITY_ISSUES))?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let mut issues = Vec::new();
+    for row in rows {
+        let row = row?;
+        if row != "ok" {
+            issues.push(row);
+        }
+    }
+    Ok(issues)
+}
+
+/// Check every existing SQLite store
+fn check_databases(app_data: &Path) -> Result<(&'static str, String), AppError> {
+    let mut checked = Vec::new();
+    let mut failures = Vec::new();
+    for store in DATA_STORES {
+        if !matches!(store.migrations, StoreMigrations::Database(_)) {
+            continue;
+        }
+        let path = app_data.join(store.file);
+        if !path.exists() {
+            continue;
+        }
+        match database_integrity(&path) {
+            Ok(issues) if issues.is_empty() => checked.push(store.name),
+            Ok(issues) => failures.push(format!("{}: {}", store.name, issues.join("; "))),
+            Err(e) => failures.push(format!("{}: {}", store.name, e)),
+        }
+    }
+    if !failures.is_empty() {
+        return Ok(("fail", failures.join("\n")));
+    }
+    Ok(("pass", format!("Intact: {}", checked.join(", "))))
+}
+
+/// Spawn a shell that exits at once
+fn check_process() -> Result<(&'static str, String), AppError> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "exit 0"]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", "exit 0"]);
+        command
+    };
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(AppError::Internal(format!(
+            "The child process exited with {}",
+            status
+        )));
+    }
+    Ok(("pass", "Child processes can be spawned".to_string()))
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Check keyring access, app data writability, the network, the databases
+/// and child processes
+#[tauri::command]
+pub async fn run_self_test(app: tauri::AppHandle) -> Result<SelfTestReport, AppError> {
+    let app_data = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    let local = tauri::async_runtime::spawn_blocking(move || {
+        vec![
+            run_check("keyring", check_keyring),
+            run_check("appData", || check_writable(&app_data)),
+            run_check("database", || check_databases(&app_data)),
+            run_check("process", check_process),
+        ]
+    });
+
+    let started = Instant::now();
+    let network = match check_network().await {
+        Ok(status) => {
+            let status_kind = match status.state.as_str() {
+                "online" => "pass",
+                "offline" => "fail",
+                _ => "warn",
+            };
+            (status_kind, format!("Network is {}", status.state))
+        }
+        Err(e) => ("fail", e.to_string()),
+    };
+    let mut checks = local.await.map_err(|e| AppError::Internal(e.to_string()))?;
+    checks.push(SelfTestCheck {
+        name: "network".to_string(),
+        status: network.0.to_string(),
+        detail: network.1,
+        duration_ms: started.elapsed().as_millis() as u64,
+    });
+
+    let passed = checks.iter().all(|check| check.status != "fail");
+    for check in checks.iter().filter(|check| check.status != "pass") {
+        log::warn!(
+            "Self-test {} {}: {}",
+            check.name,
+            check.status,
+            check.detail
+        );
+    }
+    Ok(SelfTestReport {
+        passed,
+        checks,
+        ran_at: chrono::Utc::now().timestamp(),
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use tempfile::tempdir;
+
+    #[test]
+    fn checks_detect_writable_dirs_and_corrupt_databases() {
+        let dir = tempdir().unwrap();
+        assert_eq!(check_writable(dir.path()).unwrap().0, "pass");
+        assert!(!dir.path().join(SELF_TEST_FILE).exists());
+
+        let intact = dir.path().join("intact.db");
+        db::open_database(&intact, &["CREATE TABLE t (x INTEGER)"]).unwrap();
+        assert!(database_integrity(&intact).unwrap().is_empty());
+        let corrupt = dir.path().join("corrupt.db");
+        fs::write(&corrupt, b"SQLite format 3\0 but not really a database").unwrap();
+        assert!(database_integrity(&corrupt).is_err());
+
+        let check = run_check("process", check_process);
+        assert_eq!(check.status, "pass");
+        let check = run_check("failing", || Err(AppError::Internal("boom".to_string())));
+        assert_eq!(check.status, "fail");
+        assert_eq!(check.detail, "Internal error: boom");
+    }
+}
@@ -0,0 +1,639 @@
+//! Typed application settings
+//!
+//! Every setting is declared in `SETTINGS_SCHEMA` with its type, default and
+//! limits, and stored in `settings.json` in the app data directory. Only
+//! values that differ from the default are stored; values that no longer
+//! validate fall back to the default. Each change is emitted as a
+//! `setting-changed` event, which backend code can also listen for.
+//! Internal settings are changed only through the commands of their feature,
+//! which may check more than the schema can.
+
+use crate::commands::ai_debug_log::{AI_DEBUG_LOG_BODIES_SETTING, AI_DEBUG_LOG_SETTING};
+use crate::commands::ai_failover::FALLBACK_PROVIDERS_SETTING;
+use crate::commands::ai_headers::PROVIDER_HEADERS_SETTING;
+use crate::commands::ai_moderation::{
+    MODERATION_MODEL_SETTING, MODERATION_MODE_SETTING, MODERATION_PROVIDER_SETTING,
+};
+use crate::commands::ai_proxy::RAW_REQUESTS_SETTING;
+use crate::commands::backup::SETTINGS_FILE;
+use crate::commands::events::emit_event;
+use crate::commands::file_ops::MAX_IMPORT_SIZE_SETTING;
+use crate::commands::messages::BACKEND_LOCALE_SETTING;
+use crate::commands::network::{PROXY_MODE_SETTING, PROXY_URL_SETTING, REQUEST_TIMEOUT_SETTING};
+use crate::commands::permissions::PERMISSIONS_SETTING;
+use crate::commands::retry::{RETRY_BASE_DELAY_SETTING, RETRY_MAX_ATTEMPTS_SETTING};
+use crate::error::AppError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Manager;
+
+/// Event emitted with a `SettingChange` when a setting changes
+pub const SETTING_CHANGED_EVENT: &str = "setting-changed";
+
+/// Serializes read-modify-write of the settings file
+static SETTINGS_LOCK: Mutex<()> = Mutex::new(());
+
+pub const SETTINGS_SCHEMA: &[SettingDefinition] = &[
+    SettingDefinition {
+        key: PROXY_MODE_SETTING,
+        description:
+            "How AI requests reach the network: the system proxy, network.proxyUrl, or directly",
+        kind: SettingKind::Choice {
+            default: "system",
+            options: &["system", "manual", "direct"],
+        },
+        internal: false,
+    },
+    SettingDefinition {
+        key: PROXY_URL_SETTING,
+        description: "Proxy used when network.proxyMode is manual",
+        kind: SettingKind::Url {
+            schemes: &["http", "https", "socks5", "socks5h"],
+        },
+        internal: false,
+    },
+    SettingDefinition {
+        key: REQUEST_TIMEOUT_SETTING,
+        description: "Seconds before a request to an AI provider is abandoned",
+        kind: SettingKind::Integer {
+            default: 120,
+            min: 5,
+            max: 900,
+        },
+        internal: false,
+    },
+    SettingDefinition {
+        key: MAX_IMPORT_SIZE_SETTING,
+        description: "Largest file, in MB, that data import reads",
+        kind: SettingKind::Integer {
+            default: 1024,
+            min: 1,
+            max: 16_384,
+        },
+        internal: false,
+    },
+    SettingDefinition {
+        key: RETRY_MAX_ATTEMPTS_SETTING,
+        description: "Attempts at a network request before giving up, the first included",
+        kind: SettingKind::Integer {
+            default: 3,
+            min: 1,
+            max: 10,
+        },
+        internal: false,
+    },
+    SettingDefinition {
+        key: RETRY_BASE_DELAY_SETTING,
+        description: "Milliseconds before the first retry; each later retry waits twice as long",
+        kind: SettingKind::Integer {
+            default: 500,
+            min: 0,
+            max: 60_000,
+        },
+        internal: false,
+    },
+    SettingDefinition {
+        key: "ai.monthlyBudgetUsd",
+        description: "Monthly spending limit for AI requests in US dollars, 0 for none",
+        kind: SettingKind::Number {
+            default: 0.0,
+            min: 0.0,
+            max: 100_000.0,
+        },
+        internal: false,
+    },
+    SettingDefinition {
+        key: FALLBACK_PROVIDERS_SETTING,
+        description: "Providers a failed chat request is sent to next, in order, as provider:model",
+        kind: SettingKind::Strings,
+        internal: true,
+    },
+    SettingDefinition {
+        key: PROVIDER_HEADERS_SETTING,
+        description: "Headers added to every request to a provider, by provider",
+        kind: SettingKind::StringMaps,
+        internal: true,
+    },
+    SettingDefinition {
+        key: MODERATION_MODE_SETTING,
+        description:
+            "Moderation of chat requests before they are sent: off, report flags, or refuse flagged ones",
+        kind: SettingKind::Choice {
+            default: "off",
+            options: &["off", "flag", "block"],
+        },
+        internal: false,
+    },
+    SettingDefinition {
+        key: MODERATION_PROVIDER_SETTING,
+        description: "Provider whose /moderations endpoint checks chat requests",
+        kind: SettingKind::Text { default: "openai" },
+        internal: false,
+    },
+    SettingDefinition {
+        key: MODERATION_MODEL_SETTING,
+        description: "Model that checks chat requests",
+        kind: SettingKind::Text {
+            default: "omni-moderation-latest",
+        },
+        internal: false,
+    },
+    SettingDefinition {
+        key: AI_DEBUG_LOG_SETTING,
+        description: "Record every AI request and response in the AI debug log",
+        kind: SettingKind::Bool { default: false },
+        internal: false,
+    },
+    SettingDefinition {
+        key: AI_DEBUG_LOG_BODIES_SETTING,
+        description: "Message text in the AI debug log: its length only, its start, or all of it",
+        kind: SettingKind::Choice {
+            default: "redacted",
+            options: &["redacted", "truncated", "full"],
+        },
+        internal: false,
+    },
+    SettingDefinition {
+        key: RAW_REQUESTS_SETTING,
+        description: "Allow forwarding raw JSON requests to AI provider APIs",
+        kind: SettingKind::Bool { default: false },
+        internal: false,
+    },
+    SettingDefinition {
+        key: "mcp.sandboxPaths",
+        description: "Directories MCP servers may access",
+        kind: SettingKind::Paths,
+        internal: false,
+    },
+    SettingDefinition {
+        key: PERMISSIONS_SETTING,
+        description: "Whether sensitive commands are allowed, asked for or denied, by feature",
+        kind: SettingKind::ModeMap {
+            modes: &["allow", "ask", "deny"],
+        },
+        internal: true,
+    },
+    SettingDefinition {
+        key: BACKEND_LOCALE_SETTING,
+        description: "Language of messages from the backend, or system to follow the system locale",
+        kind: SettingKind::Choice {
+            default: "system",
+            options: &["system", "en", "zh-CN"],
+        },
+        internal: true,
+    },
+];
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Type, default and limits of a setting
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SettingKind {
+    Bool {
+        default: bool,
+    },
+    Integer {
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    Number {
+        default: f64,
+        min: f64,
+        max: f64,
+    },
+    /// A URL with one of the schemes, or null; null by default
+    Url {
+        schemes: &'static [&'static str],
+    },
+    /// Absolute paths; empty by default
+    Paths,
+    /// Non-empty strings, each listed once; empty by default
+    Strings,
+    /// An object mapping names to objects of strings; empty by default
+    StringMaps,
+    /// An object mapping names to one of the modes; empty by default
+    ModeMap {
+        modes: &'static [&'static str],
+    },
+    /// A non-empty string
+    Text {
+        default: &'static str,
+    },
+    /// One of the options
+    Choice {
+        default: &'static str,
+        options: &'static [&'static str],
+    },
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingDefinition {
+    pub key: &'static str,
+    pub description: &'static str,
+    pub kind: SettingKind,
+    /// Not changed through `set_setting`
+    pub internal: bool,
+}
+
+/// Payload of `setting-changed`
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingChange {
+    pub key: String,
+    pub value: Value,
+}
+
+impl SettingKind {
+    pub fn default_value(&self) -> Value {
+        match self {
+            SettingKind::Bool { default } => Value::from(*default),
+            SettingKind::Integer { default, .. } => Value::from(*default),
+            SettingKind::Number { default, .. } => Value::from(*default),
+            SettingKind::Url { .. } => Value::Null,
+            SettingKind::Paths | SettingKind::Strings => Value::Array(Vec::new()),
+            SettingKind::StringMaps | SettingKind::ModeMap { .. } => Value::Object(Map::new()),
+            SettingKind::Text { default } | SettingKind::Choice { default, .. } => {
+                Value::from(*default)
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn get_settings_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let app_data = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(app_data.join(SETTINGS_FILE))
+}
+
+pub fn find_setting(key: &str) -> Result<&'static SettingDefinition, AppError> {
+    SETTINGS_SCHEMA
+        .iter()
+        .find(|definition| definition.key == key)
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown setting: {}", key)))
+}
+
+/// Check a value against its definition, returning it normalized
+pub fn validate_setting(definition: &SettingDefinition, value: Value) -> Result<Value, AppError> {
+    let invalid = |reason: String| {
+        AppError::InvalidInput(format!("Invalid value for {}: {}", definition.key, reason))
+    };
+    match &definition.kind {
+        SettingKind::Bool { .. } => match value {
+            Value::Bool(_) => Ok(value),
+            _ => Err(invalid("expected true or false".to_string())),
+        },
+        SettingKind::Integer { min, max, .. } => match value.as_i64() {
+            Some(n) if (*min..=*max).contains(&n) => Ok(Value::from(n)),
+            Some(n) => Err(invalid(format!("{} is not within {}..={}", n, min, max))),
+            None => Err(invalid("expected a whole number".to_string())),
+        },
+        SettingKind::Number { min, max, .. } => match value.as_f64() {
+            Some(n) if (*min..=*max).contains(&n) => Ok(Value::from(n)),
+            Some(n) => Err(invalid(format!("{} is not within {}..={}", n, min, max))),
+            None => Err(invalid("expected a number".to_string())),
+        },
+        SettingKind::Url { schemes } => match value {
+            Value::Null => Ok(Value::Null),
+            Value::String(text) if text.trim().is_empty() => Ok(Value::Null),
+            Value::String(text) => {
+                let url = url::Url::parse(text.trim()).map_err(|e| invalid(e.to_string()))?;
+                if !schemes.contains(&url.scheme()) {
+                    return Err(invalid(format!(
+                        "scheme must be one of {}",
+                        schemes.join(", ")
+                    )));
+                }
+                Ok(Value::String(url.to_string()))
+            }
+            _ => Err(invalid("expected a URL".to_string())),
+        },
+        SettingKind::Paths => {
+            let Value::Array(items) = value else {
+                return Err(invalid("expected a list of paths".to_string()));
+            };
+            let mut paths: Vec<Value> = Vec::new();
+            for item in items {
+                let path = item
+                    .as_str()
+                    .map(str::trim)
+                    .filter(|path| Path::new(path).is_absolute())
+                    .ok_or_else(|| invalid(format!("{} is not an absolute path", item)))?;
+                let path = Value::String(path.to_string());
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+            Ok(Value::Array(paths))
+        }
+        SettingKind::Strings => {
+            let Value::Array(items) = value else {
+                return Err(invalid("expected a list of strings".to_string()));
+            };
+            let mut strings: Vec<Value> = Vec::new();
+            for item in items {
+                let text = item
+                    .as_str()
+                    .map(str::trim)
+                    .filter(|text| !text.is_empty())
+                    .ok_or_else(|| invalid(format!("{} is not a non-empty string", item)))?;
+                let text = Value::String(text.to_string());
+                if !strings.contains(&text) {
+                    strings.push(text);
+                }
+            }
+            Ok(Value::Array(strings))
+        }
+        SettingKind::StringMaps => {
+            let Value::Object(entries) = value else {
+                return Err(invalid("expected an object".to_string()));
+            };
+            let mut maps = Map::new();
+            for (name, strings) in entries {
+                if name.trim().is_empty() {
+                    return Err(invalid("names must not be empty".to_string()));
+                }
+                let Value::Object(strings) = strings else {
+                    return Err(invalid(format!("{} must be an object", name)));
+                };
+                if let Some((key, _)) = strings.iter().find(|(_, value)| !value.is_string()) {
+                    return Err(invalid(format!("{}.{} must be a string", name, key)));
+                }
+                maps.insert(name.trim().to_string(), Value::Object(strings));
+            }
+            Ok(Value::Object(maps))
+        }
+        SettingKind::ModeMap { modes } => {
+            let Value::Object(entries) = value else {
+                return Err(invalid("expected an object".to_string()));
+            };
+            let mut map = Map::new();
+            for (name, mode) in entries {
+                let mode = mode
+                    .as_str()
+                    .map(|mode| mode.trim().to_lowercase())
+                    .filter(|mode| modes.contains(&mode.as_str()))
+                    .ok_or_else(|| {
+                        invalid(format!("{} must be one of {}", name, modes.join(", ")))
+                    })?;
+                if name.trim().is_empty() {
+                    return Err(invalid("names must not be empty".to_string()));
+                }
+                map.insert(name.trim().to_string(), Value::String(mode));
+            }
+            Ok(Value::Object(map))
+        }
+        SettingKind::Text { .. } => match value.as_str().map(str::trim) {
+            Some(text) if !text.is_empty() => Ok(Value::from(text)),
+            _ => Err(invalid("expected a non-empty string".to_string())),
+        },
+        SettingKind::Choice { options, .. } => match value.as_str().map(str::trim) {
+            Some(choice) if options.contains(&choice) => Ok(Value::from(choice)),
+            _ => Err(invalid(format!("expected one of {}", options.join(", ")))),
+        },
+    }
+}
+
+/// Stored values by key; a missing file holds no values
+pub fn load_settings(path: &Path) -> Result<Map<String, Value>, AppError> {
+    if !path.exists() {
+        return Ok(Map::new());
+    }
+    match serde_json::from_str(&fs::read_to_string(path)?)? {
+        Value::Object(values) => Ok(values),
+        _ => Err(AppError::InvalidInput(format!(
+            "{} does not hold an object",
+            SETTINGS_FILE
+        ))),
+    }
+}
+
+pub fn save_settings(path: &Path, values: &Map<String, Value>) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(values)?)?;
+    Ok(())
+}
+
+/// The stored value of a setting if it validates, otherwise the default
+pub fn effective_setting(definition: &SettingDefinition, values: &Map<String, Value>) -> Value {
+    match values.get(definition.key) {
+        Some(stored) => validate_setting(definition, stored.clone()).unwrap_or_else(|e| {
+            log::warn!("Using the default for a stored setting: {}", e);
+            definition.kind.default_value()
+        }),
+        None => definition.kind.default_value(),
+    }
+}
+
+/// Store a value, or remove it with `None`; returns the new effective value
+pub fn update_setting(path: &Path, key: &str, value: Option<Value>) -> Result<Value, AppError> {
+    let definition = find_setting(key)?;
+    let value = value
+        .map(|value| validate_setting(definition, value))
+        .transpose()?;
+    let _guard = SETTINGS_LOCK
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut values = load_settings(path)?;
+    match value {
+        Some(value) if value != definition.kind.default_value() => {
+            values.insert(key.to_string(), value);
+        }
+        _ => {
+            values.remove(key);
+        }
+    }
+    save_settings(path, &values)?;
+    Ok(effective_setting(definition, &values))
+}
+
+/// Typed value of a setting, for backend code
+pub fn setting<T: DeserializeOwned>(app: &tauri::AppHandle, key: &str) -> Result<T, AppError> {
+    let definition = find_setting(key)?;
+    let values = load_settings(&get_settings_path(app)?)?;
+    Ok(serde_json::from_value(effective_setting(
+        definition, &values,
+    ))?)
+}
+
+/// Store a setting (or restore its default with `None`) and emit the change,
+/// returning the new value
+pub fn change_setting(
+    app: &tauri::AppHandle,
+    key: &str,
+    value: Option<Value>,
+) -> Result<Value, AppError> {
+    let value = update_setting(&get_settings_path(app)?, key, value)?;
+    let change = SettingChange {
+        key: key.to_string(),
+        value: value.clone(),
+    };
+    emit_event(app, &change);
+    Ok(value)
+}
+
+/// Emit the value of every setting, e.g. after `settings.json` was replaced,
+/// so that listeners reload them
+pub fn announce_settings(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let values = load_settings(&get_settings_path(app)?)?;
+    for definition in SETTINGS_SCHEMA {
+        let change = SettingChange {
+            key: definition.key.to_string(),
+            value: effective_setting(definition, &values),
+        };
+        emit_event(app, &change);
+    }
+    Ok(())
+}
+
+/// Refuse changes to internal settings from `set_setting`
+fn check_not_internal(key: &str) -> Result<(), AppError> {
+    if find_setting(key)?.internal {
+        return Err(AppError::InvalidInput(format!(
+            "{} can only be changed through its own commands",
+            key
+        )));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the declared settings with their types and defaults
+#[tauri::command]
+pub fn get_settings_schema() -> Vec<SettingDefinition> {
+    SETTINGS_SCHEMA.to_vec()
+}
+
+/// Get the value of a setting
+#[tauri::command]
+pub fn get_setting(app: tauri::AppHandle, key: String) -> Result<Value, AppError> {
+    let definition = find_setting(&key)?;
+    let values = load_settings(&get_settings_path(&app)?)?;
+    Ok(effective_setting(definition, &values))
+}
+
+/// Get the values of all settings by key
+#[tauri::command]
+pub fn get_all_settings(app: tauri::AppHandle) -> Result<BTreeMap<String, Value>, AppError> {
+    let values = load_settings(&get_settings_path(&app)?)?;
+    Ok(SETTINGS_SCHEMA
+        .iter()
+        .map(|definition| {
+            (
+                definition.key.to_string(),
+                effective_setting(definition, &values),
+            )
+        })
+        .collect())
+}
+
+/// Validate and store a setting, returning its normalized value
+#[tauri::command]
+pub fn set_setting(app: tauri::AppHandle, key: String, value: Value) -> Result<Value, AppError> {
+    check_not_internal(&key)?;
+    change_setting(&app, &key, Some(value))
+}
+
+/// Restore the default of a setting, returning it
+#[tauri::command]
+pub fn reset_setting(app: tauri::AppHandle, key: String) -> Result<Value, AppError> {
+    check_not_internal(&key)?;
+    change_setting(&app, &key, None)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn settings_are_validated_and_stored() {
+        let timeout = find_setting("network.requestTimeoutSecs").unwrap();
+        assert_eq!(validate_setting(timeout, json!(30)).unwrap(), json!(30));
+        assert!(validate_setting(timeout, json!(1)).is_err());
+        assert!(validate_setting(timeout, json!("30")).is_err());
+        let proxy = find_setting("network.proxyUrl").unwrap();
+        assert_eq!(
+            validate_setting(proxy, json!(" socks5://127.0.0.1:1080 ")).unwrap(),
+            json!("socks5://127.0.0.1:1080")
+        );
+        assert_eq!(validate_setting(proxy, json!("")).unwrap(), Value::Null);
+        assert!(validate_setting(proxy, json!("ftp://proxy")).is_err());
+        let fallbacks = find_setting("ai.fallbackProviders").unwrap();
+        assert_eq!(
+            validate_setting(fallbacks, json!([" deepseek:chat ", "deepseek:chat"])).unwrap(),
+            json!(["deepseek:chat"])
+        );
+        assert!(validate_setting(fallbacks, json!([""])).is_err());
+        let headers = find_setting("ai.providerHeaders").unwrap();
+        assert_eq!(
+            validate_setting(headers, json!({" openrouter ": {"X-Title": "Readium"}})).unwrap(),
+            json!({"openrouter": {"X-Title": "Readium"}})
+        );
+        assert!(validate_setting(headers, json!({"openai": {"X-Retries": 3}})).is_err());
+        let moderation_model = find_setting("ai.moderationModel").unwrap();
+        assert_eq!(
+            validate_setting(moderation_model, json!(" text-moderation-stable ")).unwrap(),
+            json!("text-moderation-stable")
+        );
+        assert!(validate_setting(moderation_model, json!(" ")).is_err());
+        assert!(matches!(
+            find_setting("network.unknown"),
+            Err(AppError::InvalidInput(_))
+        ));
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        fs::write(
+            &path,
+            r#"{"theme":"dark","network.requestTimeoutSecs":"soon"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            effective_setting(timeout, &load_settings(&path).unwrap()),
+            json!(120)
+        );
+
+        let sandbox = if cfg!(windows) { r"C:\books" } else { "/books" };
+        let paths = update_setting(&path, "mcp.sandboxPaths", Some(json!([sandbox, sandbox])));
+        assert_eq!(paths.unwrap(), json!([sandbox]));
+        assert!(update_setting(&path, "mcp.sandboxPaths", Some(json!(["books"]))).is_err());
+        update_setting(&path, "network.requestTimeoutSecs", Some(json!(120))).unwrap();
+        let values = load_settings(&path).unwrap();
+        assert_eq!(values["theme"], "dark");
+        assert!(!values.contains_key("network.requestTimeoutSecs"));
+
+        assert_eq!(
+            update_setting(&path, "mcp.sandboxPaths", None).unwrap(),
+            json!([])
+        );
+        assert!(!load_settings(&path)
+            .unwrap()
+            .contains_key("mcp.sandboxPaths"));
+    }
+}
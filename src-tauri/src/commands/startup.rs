@@ -0,0 +1,172 @@
+//! Startup profiling and deferred initialization
+//!
+//! Each subsystem set up before the window shows is timed with `measure`,
+//! so a slow cold start can be traced to the subsystem responsible. Work the
+//! first screen does not need (background monitors, schedulers, warming up
+//! HTTP clients) is queued with `defer` and run on a background thread once
+//! the event loop is ready. `get_startup_profile` reports both.
+
+use serde::Serialize;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// A task run after the window shows
+type DeferredTask = Box<dyn FnOnce(tauri::AppHandle) + Send>;
+
+/// When `run` was entered
+static PROCESS_START: OnceLock<Instant> = OnceLock::new();
+
+/// Phases timed so far, in the order they finished
+static STARTUP_PHASES: Mutex<Vec<StartupPhase>> = Mutex::new(Vec::new());
+
+/// Time from `run` until the event loop was ready
+static READY_AFTER: OnceLock<Duration> = OnceLock::new();
+
+/// Tasks waiting for the event loop
+static DEFERRED_TASKS: Mutex<Vec<(&'static str, DeferredTask)>> = Mutex::new(Vec::new());
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupPhase {
+    pub name: String,
+    /// Milliseconds after `run` was entered when the phase started
+    pub started_ms: u64,
+    pub duration_ms: u64,
+    /// Run in the background after the window showed
+    pub deferred: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupProfile {
+    /// Milliseconds from `run` until the event loop was ready, if it is
+    pub ready_ms: Option<u64>,
+    pub phases: Vec<StartupPhase>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn since_start(instant: Instant) -> Duration {
+    instant.saturating_duration_since(*PROCESS_START.get_or_init(Instant::now))
+}
+
+fn record_phase(name: &str, started: Instant, deferred: bool) {
+    let phase = StartupPhase {
+        name: name.to_string(),
+        started_ms: since_start(started).as_millis() as u64,
+        duration_ms: started.elapsed().as_millis() as u64,
+        deferred,
+    };
+    if let Ok(mut phases) = STARTUP_PHASES.lock() {
+        phases.push(phase);
+    }
+}
+
+/// Note when the process started; called first thing in `run`
+pub fn mark_process_start() {
+    PROCESS_START.get_or_init(Instant::now);
+}
+
+/// Run a startup phase and record how long it took
+pub fn measure<T>(name: &str, phase: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = phase();
+    record_phase(name, started, false);
+    result
+}
+
+/// Queue a task to run in the background once the window shows
+pub fn defer<F>(name: &'static str, task: F)
+where
+    F: FnOnce(tauri::AppHandle) + Send + 'static,
+{
+    match DEFERRED_TASKS.lock() {
+        Ok(mut tasks) => tasks.push((name, Box::new(task))),
+        Err(e) => log::error!("Failed to defer {}: {}", name, e),
+    }
+}
+
+/// Record that the event loop is ready and run the deferred tasks
+pub fn finish_startup(app: &tauri::AppHandle) {
+    let ready = since_start(Instant::now());
+    if READY_AFTER.set(ready).is_err() {
+        return;
+    }
+    log::info!("Startup took {} ms", ready.as_millis());
+    let tasks = DEFERRED_TASKS
+        .lock()
+        .map(|mut tasks| std::mem::take(&mut *tasks))
+        .unwrap_or_default();
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        for (name, task) in tasks {
+            let started = Instant::now();
+            task(app.clone());
+            record_phase(name, started, true);
+        }
+        log::info!("Deferred startup tasks finished");
+    });
+}
+
+pub fn startup_profile() -> StartupProfile {
+    StartupProfile {
+        ready_ms: READY_AFTER.get().map(|ready| ready.as_millis() as u64),
+        phases: STARTUP_PHASES
+            .lock()
+            .map(|phases| phases.clone())
+            .unwrap_or_default(),
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get how long startup took, by subsystem
+#[tauri::command]
+pub fn get_startup_profile() -> StartupProfile {
+    startup_profile()
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_are_measured_in_order() {
+        mark_process_start();
+        let value = measure("test.first", || {
+            std::thread::sleep(Duration::from_millis(5));
+            42
+        });
+        assert_eq!(value, 42);
+        let result: Result<(), String> = measure("test.second", || Err("failed".to_string()));
+        assert!(result.is_err());
+
+        let profile = startup_profile();
+        let names: Vec<&str> = profile
+            .phases
+            .iter()
+            .map(|phase| phase.name.as_str())
+            .filter(|name| name.starts_with("test."))
+            .collect();
+        assert_eq!(names, ["test.first", "test.second"]);
+        let first = profile
+            .phases
+            .iter()
+            .find(|p| p.name == "test.first")
+            .unwrap();
+        assert!(first.duration_ms >= 5);
+        assert!(!first.deferred);
+    }
+}
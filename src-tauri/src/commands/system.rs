@@ -1,7 +1,11 @@
 //! System information and utility commands
 
+use crate::commands::mcp::{MCPClientStateHandle, MCPState};
+use crate::error::AppError;
 use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
 use std::process::Command;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
 
 #[derive(Serialize)]
 pub struct SystemInfo {
@@ -19,6 +23,54 @@ pub struct AppRuntimeInfo {
     current_dir: Option<String>,
 }
 
+/// Locale and calendar conventions of the system
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LocaleInfo {
+    /// BCP-47 tag of the preferred locale, e.g. "zh-CN"
+    pub locale: String,
+    /// All preferred locales, most preferred first
+    pub locales: Vec<String>,
+    /// IANA time zone, e.g. "Asia/Shanghai"
+    pub timezone: Option<String>,
+    pub utc_offset_minutes: i32,
+    /// "monday", "sunday", "saturday" or "friday"
+    pub first_day_of_week: String,
+    /// Calendar system as a Unicode calendar identifier, e.g. "gregory"
+    pub calendar: String,
+}
+
+/// Memory and CPU usage of one process
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessUsage {
+    pub pid: u32,
+    pub name: String,
+    /// Resident memory in bytes
+    pub memory: u64,
+    pub virtual_memory: u64,
+    /// Percent of one CPU core, above 100 when several cores are busy
+    pub cpu_usage: f32,
+    /// MCP server the process belongs to
+    pub mcp_server_id: Option<String>,
+}
+
+/// Resource usage of the app and the processes it started
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppResourceUsage {
+    pub app: ProcessUsage,
+    /// Processes started by the app (webview, MCP servers, speech, format
+    /// converters) and their own children
+    pub children: Vec<ProcessUsage>,
+    pub mcp_process_count: usize,
+    /// Resident memory of MCP server processes in bytes
+    pub mcp_memory: u64,
+    /// Resident memory of the app and all its child processes in bytes
+    pub total_memory: u64,
+    pub cpu_count: usize,
+}
+
 /// Get system information (OS and architecture)
 #[tauri::command]
 pub fn get_system_info() -> SystemInfo {
@@ -48,27 +100,511 @@ pub fn get_app_runtime_info() -> AppRuntimeInfo {
     }
 }
 
-/// Reveal a file in the system file manager
+/// Check that a path to reveal exists, and make it absolute
+fn resolve_reveal_path(path: &str) -> Result<std::path::PathBuf, AppError> {
+    if path.trim().is_empty() {
+        return Err(AppError::InvalidInput("No path to reveal".to_string()));
+    }
+    let target = std::path::Path::new(path);
+    if !target.exists() {
+        return Err(AppError::NotFound(format!("File not found: {}", path)));
+    }
+    // Explorer does not understand the `\\?\` paths canonicalize returns
+    if cfg!(windows) {
+        return match target.is_absolute() {
+            true => Ok(target.to_path_buf()),
+            false => Ok(std::env::current_dir()?.join(target)),
+        };
+    }
+    Ok(target.canonicalize()?)
+}
+
+/// GVariant text of a string array, as `gdbus call` takes arguments
+pub fn gvariant_string_array(items: &[&str]) -> String {
+    let quoted: Vec<String> = items
+        .iter()
+        .map(|item| format!("'{}'", item.replace('\\', "\\\\").replace('\'', "\\'")))
+        .collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+/// Select a file through the freedesktop `FileManager1` D-Bus interface
+#[cfg(all(unix, not(target_os = "macos")))]
+fn show_items_over_dbus(target: &std::path::Path) -> Result<(), AppError> {
+    let uri = url::Url::from_file_path(target)
+        .map_err(|_| AppError::InvalidInput(format!("Invalid path: {}", target.display())))?;
+    let output = Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.FileManager1",
+            "--object-path",
+            "/org/freedesktop/FileManager1",
+            "--method",
+            "org.freedesktop.FileManager1.ShowItems",
+            &gvariant_string_array(&[uri.as_str()]),
+            "",
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(AppError::Internal(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Reveal a file in the system file manager, selecting it
+///
+/// On Linux, file managers without the `FileManager1` interface only open
+/// the containing directory.
 #[tauri::command]
-pub fn reveal_in_file_manager(path: String) -> bool {
+pub fn reveal_in_file_manager(path: String) -> Result<(), AppError> {
+    let target = resolve_reveal_path(&path)?;
+    let failed =
+        |e: std::io::Error| AppError::Internal(format!("Failed to reveal {}: {}", path, e));
+
     #[cfg(target_os = "windows")]
     {
-        let arg = format!("/select,{}", path);
-        return Command::new("explorer.exe").arg(arg).spawn().is_ok();
+        use std::os::windows::process::CommandExt;
+        // Quoted, since explorer splits `/select` arguments at commas.
+        // Explorer exits with 1 even when it succeeds, so only the launch is
+        // checked.
+        Command::new("explorer.exe")
+            .raw_arg(format!("/select,\"{}\"", target.display()))
+            .spawn()
+            .map_err(failed)?;
     }
 
     #[cfg(target_os = "macos")]
     {
-        return Command::new("open").arg("-R").arg(&path).spawn().is_ok();
+        let output = Command::new("open")
+            .arg("-R")
+            .arg(&target)
+            .output()
+            .map_err(failed)?;
+        if !output.status.success() {
+            return Err(AppError::Internal(format!(
+                "Failed to reveal {}: {}",
+                path,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
     }
 
     #[cfg(all(unix, not(target_os = "macos")))]
+    if let Err(e) = show_items_over_dbus(&target) {
+        log::debug!("FileManager1 unavailable, opening the directory: {}", e);
+        let dir = match target.is_dir() {
+            true => target.as_path(),
+            false => target.parent().unwrap_or(&target),
+        };
+        Command::new("xdg-open").arg(dir).spawn().map_err(failed)?;
+    }
+
+    Ok(())
+}
+
+/// URL schemes `open_external_url` hands to the system
+pub const EXTERNAL_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Parse a URL to open outside the app, allowing only web and mail links
+pub fn validate_external_url(url: &str) -> Result<url::Url, AppError> {
+    let parsed = url::Url::parse(url.trim())
+        .map_err(|e| AppError::InvalidInput(format!("Invalid URL '{}': {}", url, e)))?;
+    if !EXTERNAL_URL_SCHEMES.contains(&parsed.scheme()) {
+        return Err(AppError::InvalidInput(format!(
+            "URL scheme '{}' cannot be opened",
+            parsed.scheme()
+        )));
+    }
+    if parsed.scheme() != "mailto" && parsed.host_str().map_or(true, str::is_empty) {
+        return Err(AppError::InvalidInput(format!("URL has no host: {}", url)));
+    }
+    Ok(parsed)
+}
+
+/// File extensions `open_with_default_app` opens: documents, images, audio
+/// and the archives the app exports. Any other type could have a default
+/// application that runs it, and the list of those is never complete
+pub const OPENABLE_EXTENSIONS: &[&str] = &[
+    "pdf", "epub", "mobi", "azw3", "fb2", "djvu", "cbz", "txt", "md", "markdown", "csv", "json",
+    "html", "htm", "rtf", "docx", "odt", "png", "jpg", "jpeg", "gif", "webp", "bmp", "avif", "mp3",
+    "wav", "ogg", "opus", "m4a", "flac", "zip",
+];
+
+/// Whether a file is of a type that is opened, not run, by its default
+/// application
+pub fn is_openable_type(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| OPENABLE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Build the command that opens a file or URL with its default application
+fn default_app_command(target: &str) -> Command {
+    // Not through `cmd /C start`, which would expand `%` in the target
+    #[cfg(target_os = "windows")]
+    {
+        let mut command = Command::new("rundll32");
+        command.args(["url.dll,FileProtocolHandler", target]);
+        command
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let mut command = Command::new("open");
+        command.arg(target);
+        command
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let mut command = Command::new("xdg-open");
+        command.arg(target);
+        command
+    }
+}
+
+/// Open a file with the application the system associates with its type
+///
+/// Only documents, images, audio and archives are opened; anything else,
+/// programs and scripts among them, is refused rather than run.
+#[tauri::command]
+pub fn open_with_default_app(path: String) -> Result<(), AppError> {
+    let target = std::path::Path::new(&path);
+    if !target.exists() {
+        return Err(AppError::NotFound(format!("File not found: {}", path)));
+    }
+    if !is_openable_type(target) {
+        return Err(AppError::PermissionDenied(format!(
+            "Only documents, images and audio can be opened: {}",
+            path
+        )));
+    }
+    default_app_command(&path)
+        .spawn()
+        .map_err(|e| AppError::Internal(format!("Failed to open {}: {}", path, e)))?;
+    Ok(())
+}
+
+/// Open a web or mail link in the system browser or mail client
+#[tauri::command]
+pub fn open_external_url(url: String) -> Result<(), AppError> {
+    let url = validate_external_url(&url)?;
+    default_app_command(url.as_str())
+        .spawn()
+        .map_err(|e| AppError::Internal(format!("Failed to open {}: {}", url, e)))?;
+    Ok(())
+}
+
+/// Turn a POSIX or Windows locale name into a BCP-47 tag
+pub fn normalize_locale(locale: &str) -> Option<String> {
+    let tag = locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .replace('_', "-");
+    match tag.as_str() {
+        "" | "C" | "POSIX" => None,
+        _ => Some(tag),
+    }
+}
+
+/// Regions whose week does not start on Monday (CLDR week data)
+const SUNDAY_FIRST_REGIONS: &[&str] = &[
+    "AG", "AS", "BD", "BR", "BS", "BT", "BW", "BZ", "CA", "CN", "CO", "DM", "DO", "ET", "GT", "GU",
+    "HK", "HN", "ID", "IL", "IN", "JM", "JP", "KE", "KH", "KR", "LA", "MH", "MM", "MO", "MT", "MX",
+    "MZ", "NI", "NP", "PA", "PE", "PH", "PK", "PR", "PT", "PY", "SA", "SG", "SV", "TH", "TT", "TW",
+    "UM", "US", "VE", "VI", "WS", "YE", "ZA", "ZW",
+];
+const SATURDAY_FIRST_REGIONS: &[&str] = &[
+    "AE", "AF", "BH", "DJ", "DZ", "EG", "IQ", "IR", "JO", "KW", "LY", "OM", "QA", "SD", "SY",
+];
+
+/// Region subtag of a BCP-47 tag (two letters, or three digits)
+fn locale_region(locale: &str) -> Option<String> {
+    locale
+        .split('-')
+        .skip(1)
+        .take_while(|subtag| *subtag != "u" && *subtag != "x")
+        .find(|subtag| {
+            (subtag.len() == 2 && subtag.chars().all(|c| c.is_ascii_alphabetic()))
+                || (subtag.len() == 3 && subtag.chars().all(|c| c.is_ascii_digit()))
+        })
+        .map(|region| region.to_ascii_uppercase())
+}
+
+/// First day of the week in a locale, honoring a `-u-fw-` extension
+pub fn first_day_of_week(locale: &str) -> &'static str {
+    let lower = locale.to_ascii_lowercase();
+    if let Some(day) = unicode_extension(&lower, "fw") {
+        let days = [
+            ("mon", "monday"),
+            ("tue", "tuesday"),
+            ("wed", "wednesday"),
+            ("thu", "thursday"),
+            ("fri", "friday"),
+            ("sat", "saturday"),
+            ("sun", "sunday"),
+        ];
+        if let Some((_, name)) = days.iter().find(|(key, _)| *key == day) {
+            return name;
+        }
+    }
+    match locale_region(locale).as_deref() {
+        Some(region) if SUNDAY_FIRST_REGIONS.contains(&region) => "sunday",
+        Some(region) if SATURDAY_FIRST_REGIONS.contains(&region) => "saturday",
+        Some("MV") => "friday",
+        _ => "monday",
+    }
+}
+
+/// Value of a key in the `-u-` extension of a lowercase BCP-47 tag
+fn unicode_extension<'a>(locale: &'a str, key: &str) -> Option<&'a str> {
+    let mut subtags = locale
+        .split('-')
+        .skip_while(|subtag| *subtag != "u")
+        .skip(1);
+    subtags.find(|subtag| *subtag == key)?;
+    subtags.next().filter(|value| value.len() > 2)
+}
+
+/// Descendants of `root`, each with the MCP server it belongs to: its own,
+/// or that of its closest ancestor running one
+fn descendants(
+    root: u32,
+    parents: &[(u32, u32)],
+    mcp_pids: &HashMap<u32, String>,
+) -> Vec<(u32, Option<String>)> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, parent) in parents {
+        children.entry(*parent).or_default().push(*pid);
+    }
+    let mut found = Vec::new();
+    let mut queue = VecDeque::from([(root, None::<String>)]);
+    while let Some((pid, server)) = queue.pop_front() {
+        for child in children.get(&pid).into_iter().flatten() {
+            let server = mcp_pids.get(child).cloned().or_else(|| server.clone());
+            found.push((*child, server.clone()));
+            queue.push_back((*child, server));
+        }
+    }
+    found
+}
+
+/// Measure the app process and its descendants, over the short interval
+/// CPU usage needs
+fn measure_resource_usage(mcp_pids: &HashMap<u32, String>) -> Result<AppResourceUsage, AppError> {
+    let own_pid = sysinfo::get_current_pid().map_err(|e| AppError::Internal(e.to_string()))?;
+    let refresh = ProcessRefreshKind::nothing().with_memory().with_cpu();
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh);
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh);
+
+    let usage = |pid: u32, mcp_server_id: Option<String>| {
+        let process = system.process(Pid::from_u32(pid))?;
+        Some(ProcessUsage {
+            pid,
+            name: process.name().to_string_lossy().to_string(),
+            memory: process.memory(),
+            virtual_memory: process.virtual_memory(),
+            cpu_usage: process.cpu_usage(),
+            mcp_server_id,
+        })
+    };
+    let app = usage(own_pid.as_u32(), None)
+        .ok_or_else(|| AppError::Internal("App process not found".to_string()))?;
+
+    // Threads are listed as processes on Linux, without their own memory
+    let parents: Vec<(u32, u32)> = system
+        .processes()
+        .values()
+        .filter(|process| process.thread_kind().is_none())
+        .filter_map(|process| Some((process.pid().as_u32(), process.parent()?.as_u32())))
+        .collect();
+    let children: Vec<ProcessUsage> = descendants(app.pid, &parents, mcp_pids)
+        .into_iter()
+        .filter_map(|(pid, server)| usage(pid, server))
+        .collect();
+
+    let mcp: Vec<&ProcessUsage> = children
+        .iter()
+        .filter(|child| child.mcp_server_id.is_some())
+        .collect();
+    Ok(AppResourceUsage {
+        mcp_process_count: mcp.len(),
+        mcp_memory: mcp.iter().map(|child| child.memory).sum(),
+        total_memory: app.memory + children.iter().map(|child| child.memory).sum::<u64>(),
+        cpu_count: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        app,
+        children,
+    })
+}
+
+/// Get the system locale, time zone and calendar conventions
+#[tauri::command]
+pub fn get_locale_info() -> LocaleInfo {
+    let locales: Vec<String> = sys_locale::get_locales()
+        .filter_map(|locale| normalize_locale(&locale))
+        .collect();
+    let locale = locales
+        .first()
+        .cloned()
+        .unwrap_or_else(|| "en-US".to_string());
+    let timezone = iana_time_zone::get_timezone()
+        .map_err(|e| log::warn!("Failed to get the time zone: {}", e))
+        .ok();
+    let calendar = unicode_extension(&locale.to_ascii_lowercase(), "ca")
+        .unwrap_or("gregory")
+        .to_string();
+    LocaleInfo {
+        first_day_of_week: first_day_of_week(&locale).to_string(),
+        utc_offset_minutes: chrono::Local::now().offset().local_minus_utc() / 60,
+        locale,
+        locales,
+        timezone,
+        calendar,
+    }
+}
+
+/// Get the memory and CPU usage of the app and the processes it started
+#[tauri::command]
+pub async fn get_app_resource_usage(
+    mcp_state: tauri::State<'_, MCPState>,
+    mcp_clients: tauri::State<'_, MCPClientStateHandle>,
+) -> Result<AppResourceUsage, AppError> {
+    let mut mcp_pids = HashMap::new();
     {
-        let p = Path::new(&path);
-        let dir = p.parent().unwrap_or(p);
-        return Command::new("xdg-open").arg(dir).spawn().is_ok();
+        let state = mcp_state
+            .lock()
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        for (server_id, child) in &state.processes {
+            mcp_pids.insert(child.id(), server_id.clone());
+        }
+    }
+    for (server_id, session) in &mcp_clients.read().await.sessions {
+        if let Some(pid) = session.pid {
+            mcp_pids.insert(pid, server_id.clone());
+        }
+    }
+    tauri::async_runtime::spawn_blocking(move || measure_resource_usage(&mcp_pids))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn child_processes_belong_to_the_closest_mcp_server() {
+        // 1 → 2 (npx, MCP server "a") → 3 (node) → 4; 1 → 5; 6 is unrelated
+        let parents = [(2, 1), (3, 2), (4, 3), (5, 1), (6, 7)];
+        let mcp_pids = HashMap::from([(2, "a".to_string())]);
+        let mut found = descendants(1, &parents, &mcp_pids);
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                (2, Some("a".to_string())),
+                (3, Some("a".to_string())),
+                (4, Some("a".to_string())),
+                (5, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn reveal_paths_are_checked_and_quoted() {
+        assert!(matches!(
+            reveal_in_file_manager(String::new()),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            reveal_in_file_manager("/no/such/file.pdf".to_string()),
+            Err(AppError::NotFound(_))
+        ));
+        assert_eq!(
+            gvariant_string_array(&["file:///home/a/it's.pdf", "file:///b\\c"]),
+            "['file:///home/a/it\\'s.pdf', 'file:///b\\\\c']"
+        );
     }
 
-    #[allow(unreachable_code)]
-    false
+    #[test]
+    fn only_documents_are_opened() {
+        let dir = tempfile::tempdir().unwrap();
+        let installer = dir.path().join("setup.EXE");
+        std::fs::write(&installer, "MZ").unwrap();
+        assert!(matches!(
+            open_with_default_app(installer.to_string_lossy().to_string()),
+            Err(AppError::PermissionDenied(_))
+        ));
+        for name in [
+            "run.sh",
+            "Tool.app",
+            "notes.url",
+            "a/b.lnk",
+            "tool.py",
+            "Setup.appref-ms",
+            "module.psm1",
+            "archive.tar.gz",
+            "README",
+        ] {
+            assert!(!is_openable_type(std::path::Path::new(name)), "{}", name);
+        }
+        for name in ["book.pdf", "export.md", "Cover.JPG", "chats.zip"] {
+            assert!(is_openable_type(std::path::Path::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn locales_are_normalized_with_their_week_start() {
+        assert_eq!(normalize_locale("zh_CN.UTF-8").as_deref(), Some("zh-CN"));
+        assert_eq!(normalize_locale("de_DE@euro").as_deref(), Some("de-DE"));
+        assert_eq!(normalize_locale("C"), None);
+        assert_eq!(first_day_of_week("zh-Hans-CN"), "sunday");
+        assert_eq!(first_day_of_week("en-US"), "sunday");
+        assert_eq!(first_day_of_week("en-GB"), "monday");
+        assert_eq!(first_day_of_week("ar-EG"), "saturday");
+        assert_eq!(first_day_of_week("es-419"), "monday");
+        assert_eq!(first_day_of_week("fr"), "monday");
+        assert_eq!(first_day_of_week("en-US-u-fw-mon"), "monday");
+        assert_eq!(
+            unicode_extension("th-th-u-ca-buddhist", "ca"),
+            Some("buddhist")
+        );
+    }
+
+    #[test]
+    fn external_urls_are_limited_to_web_and_mail_links() {
+        for url in [
+            "https://example.com/a?b=1&c=2",
+            " http://localhost:8080/",
+            "mailto:reader@example.com",
+        ] {
+            assert!(validate_external_url(url).is_ok(), "{}", url);
+        }
+        for url in [
+            "file:///etc/passwd",
+            "javascript:alert(1)",
+            "smb://server/share",
+            "ms-settings:privacy",
+            "https://",
+            "example.com",
+        ] {
+            assert!(
+                matches!(validate_external_url(url), Err(AppError::InvalidInput(_))),
+                "{}",
+                url
+            );
+        }
+    }
 }
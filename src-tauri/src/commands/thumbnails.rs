@@ -0,0 +1,312 @@
+//! Document cover thumbnails
+//!
+//! Thumbnails are JPEG files cached under `app_data/thumbnails`, named by the
+//! document content hash and width so they survive renames and moves. The
+//! cache directory is exposed through the asset protocol for the library grid.
+
+use crate::commands::epub::read_epub_cover;
+use crate::commands::library::{detect_document_format, hash_file};
+use crate::commands::pdf::load_pdfium;
+use crate::error::AppError;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use lopdf::Document;
+use pdfium_render::prelude::PdfRenderConfig;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+/// Width used when the request does not specify one
+pub const DEFAULT_THUMBNAIL_WIDTH: u32 = 320;
+const MIN_THUMBNAIL_WIDTH: u32 = 64;
+const MAX_THUMBNAIL_WIDTH: u32 = 1024;
+const THUMBNAIL_JPEG_QUALITY: u8 = 85;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Thumbnail request for a single document
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailRequest {
+    pub path: String,
+    /// Content hash if already known (e.g. from the library), avoids rehashing
+    pub hash: Option<String>,
+    pub width: Option<u32>,
+}
+
+/// A cached thumbnail on disk
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentThumbnail {
+    /// Absolute path of the JPEG file, loadable via `convertFileSrc`
+    pub path: String,
+    pub hash: String,
+    pub width: u32,
+    pub height: u32,
+    /// Whether the thumbnail was served from the cache
+    pub cached: bool,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Get the thumbnail cache directory
+pub fn get_thumbnail_cache_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(data_dir.join("thumbnails"))
+}
+
+/// Clamp a requested width to the supported range
+pub fn normalize_thumbnail_width(width: Option<u32>) -> u32 {
+    width
+        .unwrap_or(DEFAULT_THUMBNAIL_WIDTH)
+        .clamp(MIN_THUMBNAIL_WIDTH, MAX_THUMBNAIL_WIDTH)
+}
+
+/// Cache file name for a document hash and width
+pub fn thumbnail_file_name(hash: &str, width: u32) -> String {
+    format!("{}_{}.jpg", hash, width)
+}
+
+/// Render the first page of a PDF with pdfium
+fn render_pdf_first_page(path: &Path, width: u32) -> Result<DynamicImage, AppError> {
+    let pdfium = load_pdfium()?;
+    let document = pdfium
+        .load_pdf_from_file(path, None)
+        .map_err(|e| AppError::Internal(format!("Failed to open PDF: {}", e)))?;
+    let page = document
+        .pages()
+        .first()
+        .map_err(|e| AppError::Internal(format!("PDF has no pages: {}", e)))?;
+    let bitmap = page
+        .render_with_config(&PdfRenderConfig::new().set_target_width(width as i32))
+        .map_err(|e| AppError::Internal(format!("Failed to render PDF page: {}", e)))?;
+    Ok(bitmap.as_image())
+}
+
+/// Decode the largest JPEG image embedded in the first page of a PDF
+///
+/// Used when pdfium is not available; works well for scanned books.
+fn extract_pdf_first_page_image(path: &Path) -> Result<DynamicImage, AppError> {
+    let doc = Document::load(path)
+        .map_err(|e| AppError::Internal(format!("Failed to parse PDF: {}", e)))?;
+    let page_id = *doc
+        .get_pages()
+        .values()
+        .next()
+        .ok_or_else(|| AppError::NotFound("PDF has no pages".to_string()))?;
+    let images = doc.get_page_images(page_id).unwrap_or_default();
+    let image = images
+        .iter()
+        .filter(|image| {
+            image
+                .filters
+                .as_ref()
+                .map(|filters| filters.len() == 1 && filters[0] == "DCTDecode")
+                .unwrap_or(false)
+        })
+        .max_by_key(|image| image.width * image.height)
+        .ok_or_else(|| AppError::NotFound("No cover image found in PDF".to_string()))?;
+    image::load_from_memory(image.content)
+        .map_err(|e| AppError::Internal(format!("Failed to decode image: {}", e)))
+}
+
+/// Load the source cover image of a document
+fn load_cover_image(path: &Path, width: u32) -> Result<DynamicImage, AppError> {
+    match detect_document_format(path).as_str() {
+        "epub" => {
+            let bytes = read_epub_cover(path)?
+                .ok_or_else(|| AppError::NotFound("EPUB has no cover image".to_string()))?;
+            image::load_from_memory(&bytes)
+                .map_err(|e| AppError::Internal(format!("Failed to decode cover: {}", e)))
+        }
+        "pdf" => render_pdf_first_page(path, width).or_else(|e| {
+            log::debug!("Falling back to embedded PDF image for {:?}: {}", path, e);
+            extract_pdf_first_page_image(path)
+        }),
+        "png" | "jpg" | "jpeg" | "gif" | "webp" => image::open(path)
+            .map_err(|e| AppError::Internal(format!("Failed to decode image: {}", e))),
+        other => Err(AppError::InvalidInput(format!(
+            "Thumbnails are not supported for '{}' files",
+            other
+        ))),
+    }
+}
+
+/// Downscale an image to the target width (never upscales) and write it as JPEG
+pub fn write_thumbnail(
+    image: &DynamicImage,
+    width: u32,
+    target: &Path,
+) -> Result<(u32, u32), AppError> {
+    let resized = if image.width() > width {
+        let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1) as u32;
+        image.resize_exact(width, height, FilterType::Triangle)
+    } else {
+        image.clone()
+    };
+
+    // Write to a temporary file first so readers never see a partial image
+    let temp_path = target.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let result = (|| {
+        let file = fs::File::create(&temp_path)?;
+        let encoder = JpegEncoder::new_with_quality(BufWriter::new(file), THUMBNAIL_JPEG_QUALITY);
+        DynamicImage::ImageRgb8(resized.to_rgb8())
+            .write_with_encoder(encoder)
+            .map_err(|e| AppError::Internal(format!("Failed to encode thumbnail: {}", e)))?;
+        fs::rename(&temp_path, target)?;
+        Ok((resized.width(), resized.height()))
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Get a cached thumbnail or generate it into the cache directory
+pub fn get_or_create_thumbnail(
+    cache_dir: &Path,
+    source: &Path,
+    hash: Option<String>,
+    width: Option<u32>,
+) -> Result<DocumentThumbnail, AppError> {
+    if !source.is_file() {
+        return Err(AppError::NotFound(format!("File not found: {:?}", source)));
+    }
+    let width = normalize_thumbnail_width(width);
+    let hash = match hash {
+        // Only trust hex digests, the hash becomes part of a file name
+        Some(hash) if !hash.is_empty() && hash.chars().all(|c| c.is_ascii_hexdigit()) => hash,
+        _ => hash_file(source)?,
+    };
+    let target = cache_dir.join(thumbnail_file_name(&hash, width));
+
+    if target.is_file() {
+        if let Ok((width, height)) = image::image_dimensions(&target) {
+            return Ok(DocumentThumbnail {
+                path: target.to_string_lossy().to_string(),
+                hash,
+                width,
+                height,
+                cached: true,
+            });
+        }
+    }
+
+    fs::create_dir_all(cache_dir)?;
+    let cover = load_cover_image(source, width)?;
+    let (width, height) = write_thumbnail(&cover, width, &target)?;
+    Ok(DocumentThumbnail {
+        path: target.to_string_lossy().to_string(),
+        hash,
+        width,
+        height,
+        cached: false,
+    })
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the cover thumbnail of a document, generating and caching it if needed
+#[tauri::command]
+pub async fn get_document_thumbnail(
+    app: tauri::AppHandle,
+    request: ThumbnailRequest,
+) -> Result<DocumentThumbnail, AppError> {
+    let cache_dir = get_thumbnail_cache_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        get_or_create_thumbnail(
+            &cache_dir,
+            Path::new(&request.path),
+            request.hash,
+            request.width,
+        )
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Remove all cached thumbnails, returning the number of files deleted
+#[tauri::command]
+pub fn clear_thumbnail_cache(app: tauri::AppHandle) -> Result<usize, AppError> {
+    let cache_dir = get_thumbnail_cache_dir(&app)?;
+    if !cache_dir.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for entry in fs::read_dir(&cache_dir)?.flatten() {
+        if entry.path().is_file() && fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    log::info!("Cleared {} cached thumbnails", removed);
+    Ok(removed)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::epub::tests::{write_epub, CONTAINER_XML, OPF_XML};
+    use image::{ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn normalize_thumbnail_width_clamps_range() {
+        assert_eq!(normalize_thumbnail_width(None), DEFAULT_THUMBNAIL_WIDTH);
+        assert_eq!(normalize_thumbnail_width(Some(10)), MIN_THUMBNAIL_WIDTH);
+        assert_eq!(normalize_thumbnail_width(Some(5000)), MAX_THUMBNAIL_WIDTH);
+        assert_eq!(thumbnail_file_name("abc", 320), "abc_320.jpg");
+    }
+
+    #[test]
+    fn epub_cover_is_resized_and_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("thumbnails");
+        let book = dir.path().join("book.epub");
+        let cover = png_bytes(400, 600);
+        write_epub(
+            &book,
+            &[
+                ("META-INF/container.xml", CONTAINER_XML.as_bytes()),
+                ("OEBPS/content.opf", OPF_XML.as_bytes()),
+                ("images/cover.jpg", &cover),
+            ],
+        );
+
+        let first = get_or_create_thumbnail(&cache_dir, &book, None, Some(200)).unwrap();
+        assert!(!first.cached);
+        assert_eq!((first.width, first.height), (200, 300));
+        assert!(first.path.ends_with(&thumbnail_file_name(&first.hash, 200)));
+        assert!(Path::new(&first.path).is_file());
+
+        let second =
+            get_or_create_thumbnail(&cache_dir, &book, Some(first.hash.clone()), Some(200))
+                .unwrap();
+        assert!(second.cached);
+        assert_eq!(second.path, first.path);
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,200 @@
+//! Cloud speech synthesis through OpenAI's speech endpoint
+//!
+//! Uses the API key stored for the "openai" provider. The audio is written to
+//! the cache directory and played by the frontend.
+
+use super::types::TtsVoice;
+use crate::commands::ai_keys::get_api_key;
+use crate::error::AppError;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::Manager;
+
+pub const OPENAI_TTS_PROVIDER: &str = "openai";
+const OPENAI_SPEECH_URL: &str = "https://api.openai.com/v1/audio/speech";
+const OPENAI_TTS_MODEL: &str = "gpt-4o-mini-tts";
+const OPENAI_VOICES: &[&str] = &[
+    "alloy", "ash", "ballad", "coral", "echo", "fable", "nova", "onyx", "sage", "shimmer", "verse",
+];
+const DEFAULT_OPENAI_VOICE: &str = "alloy";
+
+/// Longest input accepted by the speech endpoint
+pub const MAX_CLOUD_TEXT_CHARS: usize = 4096;
+
+/// Synthesized audio files older than this are removed
+const AUDIO_CACHE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize)]
+struct SpeechRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+    voice: &'a str,
+    response_format: &'a str,
+    speed: f32,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Voices of the OpenAI speech endpoint
+pub fn openai_voices() -> Vec<TtsVoice> {
+    OPENAI_VOICES
+        .iter()
+        .map(|voice| TtsVoice {
+            id: voice.to_string(),
+            name: format!("{}{}", voice[..1].to_uppercase(), &voice[1..]),
+            language: None,
+            gender: None,
+            provider: OPENAI_TTS_PROVIDER.to_string(),
+        })
+        .collect()
+}
+
+/// Get the directory for synthesized audio
+pub fn get_tts_cache_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let cache_dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(cache_dir.join("tts"))
+}
+
+/// Remove synthesized audio older than the cache lifetime
+pub fn prune_audio_cache(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > AUDIO_CACHE_MAX_AGE);
+        if expired {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Duration of a PCM WAV file in milliseconds
+///
+/// Streamed WAV responses leave the data size unset, in which case the rest
+/// of the file is taken as audio data.
+pub fn wav_duration_ms(bytes: &[u8]) -> Option<u64> {
+    if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut pos = 12;
+    let mut byte_rate = None;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let size = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let body = pos + 8;
+        if id == b"fmt " {
+            byte_rate = bytes
+                .get(body + 8..body + 12)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as u64);
+        } else if id == b"data" {
+            let available = bytes.len() - body;
+            let data_len = if size == 0 || size == u32::MAX as usize {
+                available
+            } else {
+                size.min(available)
+            } as u64;
+            return byte_rate
+                .filter(|&rate| rate > 0)
+                .map(|rate| data_len * 1000 / rate);
+        }
+        pos = body + size + (size % 2);
+    }
+    None
+}
+
+/// Synthesize speech as WAV audio
+pub async fn synthesize_openai(
+    text: &str,
+    voice: Option<&str>,
+    rate: f32,
+) -> Result<Vec<u8>, AppError> {
+    if text.chars().count() > MAX_CLOUD_TEXT_CHARS {
+        return Err(AppError::InvalidInput(format!(
+            "Text is longer than {} characters",
+            MAX_CLOUD_TEXT_CHARS
+        )));
+    }
+    let voice = voice.unwrap_or(DEFAULT_OPENAI_VOICE);
+    if !OPENAI_VOICES.contains(&voice) {
+        return Err(AppError::InvalidInput(format!(
+            "Unknown OpenAI voice: {}",
+            voice
+        )));
+    }
+
+    let api_key = get_api_key(OPENAI_TTS_PROVIDER.to_string())?.ok_or_else(|| {
+        AppError::PermissionDenied(format!("No API key found for {}", OPENAI_TTS_PROVIDER))
+    })?;
+
+    let request = SpeechRequest {
+        model: OPENAI_TTS_MODEL,
+        input: text,
+        voice,
+        response_format: "wav",
+        speed: rate.clamp(0.25, 4.0),
+    };
+    let response = reqwest::Client::new()
+        .post(OPENAI_SPEECH_URL)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&request)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(AppError::http_status(
+            status,
+            format!(
+                "Speech request failed with status {}: {}",
+                status, error_text
+            ),
+        ));
+    }
+    let audio = response.bytes().await?;
+    Ok(audio.to_vec())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav(data_len: usize, declared: u32) -> Vec<u8> {
+        let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+        bytes.extend_from_slice(&24_000u32.to_le_bytes());
+        bytes.extend_from_slice(&48_000u32.to_le_bytes()); // byte rate
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&declared.to_le_bytes());
+        bytes.extend(std::iter::repeat(0u8).take(data_len));
+        bytes
+    }
+
+    #[test]
+    fn wav_duration_handles_streamed_headers() {
+        assert_eq!(wav_duration_ms(&wav(96_000, 96_000)), Some(2000));
+        assert_eq!(wav_duration_ms(&wav(24_000, u32::MAX)), Some(500));
+        assert_eq!(wav_duration_ms(b"ID3 not a wave file"), None);
+        assert_eq!(openai_voices()[0].name, "Alloy");
+    }
+}
@@ -0,0 +1,21 @@
+//! Text-to-speech
+//!
+//! Speaks text with the operating system's speech tools or a cloud provider
+//! and reports word and sentence boundaries so the reader can highlight along:
+//! - System voices (System.Speech, `say`, espeak-ng, Speech Dispatcher)
+//! - OpenAI speech synthesis using the stored API key
+//! - Pause/resume/stop of the active utterance
+//! - Word and sentence segmentation with estimated timing
+
+mod types;
+mod text;
+mod system;
+mod cloud;
+mod session;
+
+// Re-export all public items
+pub use types::*;
+pub use text::*;
+pub use system::*;
+pub use cloud::*;
+pub use session::*;
@@ -0,0 +1,455 @@
+//! Speech sessions and TTS commands
+
+use super::cloud::{
+    get_tts_cache_dir, openai_voices, prune_audio_cache, synthesize_openai, wav_duration_ms,
+    OPENAI_TTS_PROVIDER,
+};
+use super::system::{parse_progress_line, SystemEngine};
+use super::text::{estimate_timeline, estimated_boundaries, BoundaryTracker, TextSegment};
+use super::types::{TtsBoundary, TtsSpeakResult, TtsStateEvent, TtsVoice};
+use crate::commands::events::emit_event;
+use crate::error::AppError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Child;
+use tokio::sync::{mpsc, watch};
+use uuid::Uuid;
+
+/// Event emitted for every word and sentence reached by system speech
+pub const TTS_BOUNDARY_EVENT: &str = "tts-boundary";
+/// Event emitted when system speech starts, pauses, resumes or ends
+pub const TTS_STATE_EVENT: &str = "tts-state";
+
+pub const SYSTEM_TTS_PROVIDER: &str = "system";
+
+/// Longest text spoken in one utterance by the system engine
+const MAX_SYSTEM_TEXT_CHARS: usize = 20_000;
+const ESTIMATE_TICK: Duration = Duration::from_millis(50);
+
+enum TtsControl {
+    Pause,
+    Resume,
+    Stop,
+}
+
+/// The utterance currently spoken by the system engine
+pub struct TtsSession {
+    utterance_id: String,
+    control: mpsc::UnboundedSender<TtsControl>,
+    /// Whether the engine can pause; if not, pause and resume fail up front
+    supports_pause: bool,
+}
+
+/// An utterance handed to the system engine
+struct SystemUtterance {
+    engine: SystemEngine,
+    utterance_id: String,
+    text: String,
+    rate: f32,
+}
+
+/// Managed state holding the active speech session
+pub type TtsState = Arc<Mutex<Option<TtsSession>>>;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Clamp a rate multiplier to the supported range, 1.0 being normal speed
+pub fn normalize_rate(rate: Option<f32>) -> f32 {
+    rate.filter(|rate| rate.is_finite() && *rate > 0.0)
+        .unwrap_or(1.0)
+        .clamp(0.25, 4.0)
+}
+
+fn emit_state(app: &tauri::AppHandle, utterance_id: &str, state: &str, error: Option<String>) {
+    let event = TtsStateEvent {
+        utterance_id: utterance_id.to_string(),
+        state: state.to_string(),
+        error,
+    };
+    emit_event(app, &event);
+}
+
+fn emit_boundaries(app: &tauri::AppHandle, boundaries: Vec<TtsBoundary>) {
+    for boundary in boundaries {
+        emit_event(app, &boundary);
+    }
+}
+
+fn send_control(state: &TtsState, control: TtsControl) -> Result<(), AppError> {
+    let session = state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let session = session
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("No speech in progress".to_string()))?;
+    if !session.supports_pause && matches!(control, TtsControl::Pause | TtsControl::Resume) {
+        return Err(AppError::InvalidInput(
+            "Pausing is not supported by this speech engine".to_string(),
+        ));
+    }
+    session
+        .control
+        .send(control)
+        .map_err(|_| AppError::NotFound("No speech in progress".to_string()))
+}
+
+/// Stop the active session, if any
+fn stop_current(state: &TtsState) -> Result<(), AppError> {
+    let previous = state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .take();
+    if let Some(session) = previous {
+        let _ = session.control.send(TtsControl::Stop);
+    }
+    Ok(())
+}
+
+/// Emit estimated boundaries on a clock that stops while speech is paused
+async fn emit_estimated_boundaries(
+    app: tauri::AppHandle,
+    utterance_id: String,
+    text: String,
+    rate: f32,
+    mut paused: watch::Receiver<bool>,
+) {
+    let (timeline, _) = estimate_timeline(&text, rate);
+    let mut tracker = BoundaryTracker::new(&utterance_id, &text, true);
+    let mut elapsed = Duration::ZERO;
+    let mut last_tick = Instant::now();
+    let mut next = 0;
+
+    while next < timeline.len() {
+        if *paused.borrow() {
+            if paused.changed().await.is_err() {
+                return;
+            }
+            last_tick = Instant::now();
+            continue;
+        }
+        let now = Instant::now();
+        elapsed += now - last_tick;
+        last_tick = now;
+        while let Some(&(word, offset)) = timeline
+            .get(next)
+            .filter(|(_, offset)| *offset <= elapsed.as_millis() as u64)
+        {
+            emit_boundaries(&app, tracker.word(word, offset));
+            next += 1;
+        }
+        tokio::time::sleep(ESTIMATE_TICK).await;
+    }
+}
+
+/// Drive a system speech process until it finishes or is stopped
+async fn run_system_utterance(
+    app: tauri::AppHandle,
+    state: TtsState,
+    utterance: SystemUtterance,
+    mut child: Child,
+    mut control: mpsc::UnboundedReceiver<TtsControl>,
+) {
+    let SystemUtterance {
+        engine,
+        utterance_id,
+        text,
+        rate,
+    } = utterance;
+    let pid = child.id();
+    let mut stdin = child.stdin.take();
+    if engine.reads_text_from_stdin() {
+        if let Some(mut input) = stdin.take() {
+            let text = text.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = input.write_all(text.as_bytes()).await {
+                    log::warn!("Failed to send text to speech engine: {}", e);
+                }
+            });
+        }
+    }
+    let stderr = child.stderr.take().map(|stderr| {
+        tauri::async_runtime::spawn(async move {
+            let mut output = String::new();
+            let _ = BufReader::new(stderr).read_to_string(&mut output).await;
+            output
+        })
+    });
+
+    let (paused_tx, paused_rx) = watch::channel(false);
+    let boundaries = match child.stdout.take() {
+        Some(stdout) if engine.reports_boundaries() => {
+            let (app, utterance_id, text) = (app.clone(), utterance_id.clone(), text.clone());
+            tauri::async_runtime::spawn(async move {
+                let mut tracker = BoundaryTracker::new(&utterance_id, &text, false);
+                let mut lines = BufReader::new(stdout).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some((start, len, offset)) = parse_progress_line(&line) {
+                        emit_boundaries(&app, tracker.word(TextSegment { start, len }, offset));
+                    }
+                }
+            })
+        }
+        _ => tauri::async_runtime::spawn(emit_estimated_boundaries(
+            app.clone(),
+            utterance_id.clone(),
+            text,
+            rate,
+            paused_rx,
+        )),
+    };
+
+    let (outcome, mut error) = loop {
+        tokio::select! {
+            status = child.wait() => break match status {
+                Ok(status) if status.success() => ("finished", None),
+                Ok(status) => ("error", Some(format!("Speech engine exited with {}", status))),
+                Err(e) => ("error", Some(e.to_string())),
+            },
+            command = control.recv() => match command {
+                Some(command @ (TtsControl::Pause | TtsControl::Resume)) => {
+                    let pause = matches!(command, TtsControl::Pause);
+                    if *paused_tx.borrow() == pause {
+                        continue;
+                    }
+                    match engine.set_paused(pid, stdin.as_mut(), pause).await {
+                        Ok(()) => {
+                            let _ = paused_tx.send(pause);
+                            let state = if pause { "paused" } else { "resumed" };
+                            emit_state(&app, &utterance_id, state, None);
+                        }
+                        Err(e) => log::warn!("Failed to pause or resume speech: {}", e),
+                    }
+                }
+                Some(TtsControl::Stop) | None => {
+                    let _ = child.kill().await;
+                    break ("stopped", None);
+                }
+            },
+        }
+    };
+    boundaries.abort();
+
+    if outcome == "error" {
+        if let Some(stderr) = stderr {
+            let output = stderr.await.unwrap_or_default();
+            if !output.trim().is_empty() {
+                error = Some(output.trim().to_string());
+            }
+        }
+    }
+    if let Ok(mut session) = state.lock() {
+        if session
+            .as_ref()
+            .is_some_and(|s| s.utterance_id == utterance_id)
+        {
+            *session = None;
+        }
+    }
+    emit_state(&app, &utterance_id, outcome, error);
+}
+
+fn speak_system(
+    app: tauri::AppHandle,
+    state: TtsState,
+    utterance_id: String,
+    text: String,
+    voice: Option<String>,
+    rate: f32,
+) -> Result<TtsSpeakResult, AppError> {
+    if text.chars().count() > MAX_SYSTEM_TEXT_CHARS {
+        return Err(AppError::InvalidInput(format!(
+            "Text is longer than {} characters",
+            MAX_SYSTEM_TEXT_CHARS
+        )));
+    }
+    let engine = SystemEngine::detect().ok_or_else(|| {
+        AppError::NotFound(
+            "No speech engine found, install espeak-ng or speech-dispatcher".to_string(),
+        )
+    })?;
+    let child = engine
+        .speak_command(&text, voice.as_deref(), rate)
+        .spawn()?;
+
+    let (control, control_rx) = mpsc::unbounded_channel();
+    *state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))? = Some(TtsSession {
+        utterance_id: utterance_id.clone(),
+        control,
+        supports_pause: engine.supports_pause(),
+    });
+    emit_state(&app, &utterance_id, "started", None);
+    let utterance = SystemUtterance {
+        engine,
+        utterance_id: utterance_id.clone(),
+        text,
+        rate,
+    };
+    tauri::async_runtime::spawn(run_system_utterance(
+        app, state, utterance, child, control_rx,
+    ));
+
+    Ok(TtsSpeakResult {
+        utterance_id,
+        provider: SYSTEM_TTS_PROVIDER.to_string(),
+        ..Default::default()
+    })
+}
+
+async fn speak_cloud(
+    app: &tauri::AppHandle,
+    utterance_id: String,
+    text: &str,
+    voice: Option<&str>,
+    rate: f32,
+) -> Result<TtsSpeakResult, AppError> {
+    let audio = synthesize_openai(text, voice, rate).await?;
+    let duration_ms = wav_duration_ms(&audio);
+
+    let dir = get_tts_cache_dir(app)?;
+    let path = dir.join(format!("{}.wav", utterance_id));
+    let target = path.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        std::fs::create_dir_all(&dir)?;
+        prune_audio_cache(&dir);
+        std::fs::write(&target, &audio)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+
+    Ok(TtsSpeakResult {
+        boundaries: estimated_boundaries(&utterance_id, text, rate, duration_ms),
+        utterance_id,
+        provider: OPENAI_TTS_PROVIDER.to_string(),
+        audio_path: Some(path.to_string_lossy().to_string()),
+        duration_ms,
+    })
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Speak text, replacing any utterance in progress
+///
+/// The system provider speaks directly and emits `tts-state` and
+/// `tts-boundary` events. Cloud providers return an audio file with its
+/// boundary timeline for the frontend to play.
+#[tauri::command]
+pub async fn tts_speak(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, TtsState>,
+    text: String,
+    voice: Option<String>,
+    rate: Option<f32>,
+    provider: Option<String>,
+) -> Result<TtsSpeakResult, AppError> {
+    if text.trim().is_empty() {
+        return Err(AppError::InvalidInput("Text is empty".to_string()));
+    }
+    let rate = normalize_rate(rate);
+    let voice = voice.filter(|voice| !voice.trim().is_empty());
+    let state = state.inner().clone();
+    stop_current(&state)?;
+
+    let utterance_id = format!("tts_{}", Uuid::new_v4());
+    match provider.as_deref().unwrap_or(SYSTEM_TTS_PROVIDER) {
+        SYSTEM_TTS_PROVIDER => speak_system(app, state, utterance_id, text, voice, rate),
+        OPENAI_TTS_PROVIDER => speak_cloud(&app, utterance_id, &text, voice.as_deref(), rate).await,
+        other => Err(AppError::InvalidInput(format!(
+            "Unknown speech provider: {}",
+            other
+        ))),
+    }
+}
+
+/// Pause system speech, failing with engines that cannot pause
+#[tauri::command]
+pub fn tts_pause(state: tauri::State<'_, TtsState>) -> Result<(), AppError> {
+    send_control(&state, TtsControl::Pause)
+}
+
+/// Resume paused system speech
+#[tauri::command]
+pub fn tts_resume(state: tauri::State<'_, TtsState>) -> Result<(), AppError> {
+    send_control(&state, TtsControl::Resume)
+}
+
+/// Stop system speech
+#[tauri::command]
+pub fn tts_stop(state: tauri::State<'_, TtsState>) -> Result<(), AppError> {
+    stop_current(&state)
+}
+
+/// List the voices of a speech provider (default "system")
+#[tauri::command]
+pub async fn tts_list_voices(provider: Option<String>) -> Result<Vec<TtsVoice>, AppError> {
+    match provider.as_deref().unwrap_or(SYSTEM_TTS_PROVIDER) {
+        SYSTEM_TTS_PROVIDER => match SystemEngine::detect() {
+            Some(engine) => engine.list_voices().await,
+            None => Ok(Vec::new()),
+        },
+        OPENAI_TTS_PROVIDER => Ok(openai_voices()),
+        other => Err(AppError::InvalidInput(format!(
+            "Unknown speech provider: {}",
+            other
+        ))),
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_rate_clamps_and_defaults() {
+        assert_eq!(normalize_rate(None), 1.0);
+        assert_eq!(normalize_rate(Some(f32::NAN)), 1.0);
+        assert_eq!(normalize_rate(Some(-2.0)), 1.0);
+        assert_eq!(normalize_rate(Some(10.0)), 4.0);
+        assert_eq!(normalize_rate(Some(0.1)), 0.25);
+        assert_eq!(normalize_rate(Some(1.5)), 1.5);
+    }
+
+    #[test]
+    fn controls_require_an_active_session() {
+        let state = TtsState::default();
+        assert!(matches!(
+            send_control(&state, TtsControl::Pause),
+            Err(AppError::NotFound(_))
+        ));
+        stop_current(&state).unwrap();
+
+        let (control, mut control_rx) = mpsc::unbounded_channel();
+        *state.lock().unwrap() = Some(TtsSession {
+            utterance_id: "tts_1".to_string(),
+            control,
+            supports_pause: true,
+        });
+        send_control(&state, TtsControl::Pause).unwrap();
+        stop_current(&state).unwrap();
+        assert!(state.lock().unwrap().is_none());
+        assert!(matches!(control_rx.try_recv(), Ok(TtsControl::Pause)));
+        assert!(matches!(control_rx.try_recv(), Ok(TtsControl::Stop)));
+
+        let (control, mut control_rx) = mpsc::unbounded_channel();
+        *state.lock().unwrap() = Some(TtsSession {
+            utterance_id: "tts_2".to_string(),
+            control,
+            supports_pause: false,
+        });
+        assert!(matches!(
+            send_control(&state, TtsControl::Pause),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(control_rx.try_recv().is_err());
+    }
+}
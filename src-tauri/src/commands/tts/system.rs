@@ -0,0 +1,409 @@
+//! Speech through the operating system's speech tools
+//!
+//! Windows speech runs through System.Speech in PowerShell, which reports
+//! word positions. macOS uses `say` and Linux `espeak-ng`, `espeak` or
+//! Speech Dispatcher's `spd-say`; their boundaries are estimated.
+
+use super::text::BASE_WORDS_PER_MINUTE;
+use super::types::TtsVoice;
+use crate::commands::conversion::find_executable;
+use crate::error::AppError;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{ChildStdin, Command};
+
+/// Speaks the text from `READIUM_TTS_TEXT`, printing `word|position|length|ms`
+/// for every word and accepting `pause`, `resume` and `stop` on stdin
+const WINDOWS_SPEAK_SCRIPT: &str = r#"
+$ErrorActionPreference = 'Stop'
+[Console]::OutputEncoding = [Text.Encoding]::UTF8
+Add-Type -AssemblyName System.Speech
+$synth = New-Object System.Speech.Synthesis.SpeechSynthesizer
+if ($env:READIUM_TTS_VOICE) { $synth.SelectVoice($env:READIUM_TTS_VOICE) }
+$synth.Rate = [int]$env:READIUM_TTS_RATE
+$null = Register-ObjectEvent -InputObject $synth -EventName SpeakProgress -Action {
+  $e = $EventArgs
+  [Console]::Out.WriteLine("word|$($e.CharacterPosition)|$($e.CharacterCount)|$([int]$e.AudioPosition.TotalMilliseconds)")
+  [Console]::Out.Flush()
+}
+$prompt = $synth.SpeakAsync($env:READIUM_TTS_TEXT)
+$command = [Console]::In.ReadLineAsync()
+while (-not $prompt.IsCompleted) {
+  if ($command.IsCompleted) {
+    $line = $command.Result
+    if ($line -eq $null -or $line -eq 'stop') { $synth.SpeakAsyncCancelAll(); break }
+    if ($line -eq 'pause') { $synth.Pause() }
+    if ($line -eq 'resume') { $synth.Resume() }
+    $command = [Console]::In.ReadLineAsync()
+  }
+  Start-Sleep -Milliseconds 30
+}
+Start-Sleep -Milliseconds 100
+"#;
+
+/// Lists installed voices as `name|culture|gender`
+const WINDOWS_VOICES_SCRIPT: &str = r#"
+[Console]::OutputEncoding = [Text.Encoding]::UTF8
+Add-Type -AssemblyName System.Speech
+$synth = New-Object System.Speech.Synthesis.SpeechSynthesizer
+foreach ($voice in $synth.GetInstalledVoices()) {
+  if ($voice.Enabled) {
+    $info = $voice.VoiceInfo
+    [Console]::Out.WriteLine("$($info.Name)|$($info.Culture.Name)|$($info.Gender)")
+  }
+}
+"#;
+
+/// A speech tool available on this system
+#[derive(Clone, Debug, PartialEq)]
+pub enum SystemEngine {
+    Say(PathBuf),
+    Espeak(PathBuf),
+    SpdSay(PathBuf),
+    WindowsSpeech(PathBuf),
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn path_dirs() -> Vec<PathBuf> {
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default()
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Map a rate multiplier onto a symmetric logarithmic scale where `max`
+/// corresponds to three times the normal speed
+fn log_rate(rate: f32, max: f32) -> i32 {
+    ((rate.ln() / 3f32.ln()) * max).round().clamp(-max, max) as i32
+}
+
+/// Parse `say -v ?` output: `Name    en_US    # Sample sentence`
+pub fn parse_say_voices(output: &str) -> Vec<TtsVoice> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let description = line.split(" #").next()?.trim_end();
+            let (name, language) = description.rsplit_once(char::is_whitespace)?;
+            let name = non_empty(name)?;
+            Some(TtsVoice {
+                id: name.clone(),
+                name,
+                language: non_empty(&language.replace('_', "-")),
+                gender: None,
+                provider: "system".to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse `espeak-ng --voices` output
+pub fn parse_espeak_voices(output: &str) -> Vec<TtsVoice> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let (language, gender, name) = (columns.get(1)?, columns.get(2)?, columns.get(3)?);
+            let gender = match gender.rsplit('/').next() {
+                Some("M") => Some("male".to_string()),
+                Some("F") => Some("female".to_string()),
+                _ => None,
+            };
+            Some(TtsVoice {
+                id: language.to_string(),
+                name: name.replace('_', " "),
+                language: Some(language.to_string()),
+                gender,
+                provider: "system".to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse `spd-say -L` output: `NAME  LANGUAGE  VARIANT`
+pub fn parse_spd_voices(output: &str) -> Vec<TtsVoice> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            if columns.len() < 3 {
+                return None;
+            }
+            let name = columns[..columns.len() - 2].join(" ");
+            Some(TtsVoice {
+                id: name.clone(),
+                name,
+                language: non_empty(columns[columns.len() - 2]),
+                gender: None,
+                provider: "system".to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse the output of the Windows voice listing script
+pub fn parse_windows_voices(output: &str) -> Vec<TtsVoice> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.trim().split('|');
+            let name = non_empty(parts.next()?)?;
+            Some(TtsVoice {
+                id: name.clone(),
+                name,
+                language: parts.next().and_then(non_empty),
+                gender: parts
+                    .next()
+                    .and_then(non_empty)
+                    .filter(|g| g != "NotSet")
+                    .map(|g| g.to_lowercase()),
+                provider: "system".to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse a `word|position|length|ms` progress line of the Windows script
+pub fn parse_progress_line(line: &str) -> Option<(usize, usize, u64)> {
+    let mut parts = line.trim().strip_prefix("word|")?.split('|');
+    Some((
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+    ))
+}
+
+fn powershell(path: &Path, script: &str) -> Command {
+    let mut command = Command::new(path);
+    command.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-ExecutionPolicy",
+        "Bypass",
+        "-Command",
+        script,
+    ]);
+    command
+}
+
+// ============================================================================
+// Engine
+// ============================================================================
+
+impl SystemEngine {
+    /// Find the speech tool of this platform
+    pub fn detect() -> Option<Self> {
+        let dirs = path_dirs();
+        if cfg!(target_os = "windows") {
+            return find_executable("powershell", &dirs)
+                .or_else(|| find_executable("pwsh", &dirs))
+                .map(SystemEngine::WindowsSpeech);
+        }
+        if cfg!(target_os = "macos") {
+            return find_executable("say", &dirs)
+                .or_else(|| find_executable("say", &[PathBuf::from("/usr/bin")]))
+                .map(SystemEngine::Say);
+        }
+        find_executable("espeak-ng", &dirs)
+            .or_else(|| find_executable("espeak", &dirs))
+            .map(SystemEngine::Espeak)
+            .or_else(|| find_executable("spd-say", &dirs).map(SystemEngine::SpdSay))
+    }
+
+    /// Whether the engine reports word positions while speaking
+    pub fn reports_boundaries(&self) -> bool {
+        matches!(self, SystemEngine::WindowsSpeech(_))
+    }
+
+    /// Speech Dispatcher speaks in its own daemon, out of reach of the
+    /// process signals used to pause the other tools
+    pub fn supports_pause(&self) -> bool {
+        !matches!(self, SystemEngine::SpdSay(_))
+    }
+
+    /// Whether the text is written to the tool's stdin
+    pub fn reads_text_from_stdin(&self) -> bool {
+        matches!(self, SystemEngine::Say(_) | SystemEngine::Espeak(_))
+    }
+
+    /// Build the command speaking `text`
+    pub fn speak_command(&self, text: &str, voice: Option<&str>, rate: f32) -> Command {
+        let words_per_minute = (BASE_WORDS_PER_MINUTE * rate).round() as u32;
+        let mut command = match self {
+            SystemEngine::Say(path) => {
+                let mut command = Command::new(path);
+                if let Some(voice) = voice {
+                    command.args(["-v", voice]);
+                }
+                command.args(["-r", &words_per_minute.to_string(), "-f", "-"]);
+                command
+            }
+            SystemEngine::Espeak(path) => {
+                let mut command = Command::new(path);
+                if let Some(voice) = voice {
+                    command.args(["-v", voice]);
+                }
+                command.args(["-s", &words_per_minute.to_string(), "--stdin"]);
+                command
+            }
+            SystemEngine::SpdSay(path) => {
+                let mut command = Command::new(path);
+                command.arg("-w");
+                if let Some(voice) = voice {
+                    command.args(["-y", voice]);
+                }
+                command.args(["-r", &log_rate(rate, 100.0).to_string(), "--", text]);
+                command
+            }
+            SystemEngine::WindowsSpeech(path) => {
+                let mut command = powershell(path, WINDOWS_SPEAK_SCRIPT);
+                command
+                    .env("READIUM_TTS_TEXT", text)
+                    .env("READIUM_TTS_VOICE", voice.unwrap_or_default())
+                    .env("READIUM_TTS_RATE", log_rate(rate, 10.0).to_string());
+                command
+            }
+        };
+        let stdout = if self.reports_boundaries() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        };
+        command
+            .stdin(Stdio::piped())
+            .stdout(stdout)
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        command
+    }
+
+    /// List the installed voices
+    pub async fn list_voices(&self) -> Result<Vec<TtsVoice>, AppError> {
+        let mut command = match self {
+            SystemEngine::Say(path) => {
+                let mut command = Command::new(path);
+                command.args(["-v", "?"]);
+                command
+            }
+            SystemEngine::Espeak(path) => {
+                let mut command = Command::new(path);
+                command.arg("--voices");
+                command
+            }
+            SystemEngine::SpdSay(path) => {
+                let mut command = Command::new(path);
+                command.arg("-L");
+                command
+            }
+            SystemEngine::WindowsSpeech(path) => powershell(path, WINDOWS_VOICES_SCRIPT),
+        };
+        let output = command.stdin(Stdio::null()).output().await?;
+        if !output.status.success() {
+            return Err(AppError::Internal(format!(
+                "Listing voices failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(match self {
+            SystemEngine::Say(_) => parse_say_voices(&stdout),
+            SystemEngine::Espeak(_) => parse_espeak_voices(&stdout),
+            SystemEngine::SpdSay(_) => parse_spd_voices(&stdout),
+            SystemEngine::WindowsSpeech(_) => parse_windows_voices(&stdout),
+        })
+    }
+
+    /// Pause or resume a running utterance
+    pub async fn set_paused(
+        &self,
+        pid: Option<u32>,
+        stdin: Option<&mut ChildStdin>,
+        paused: bool,
+    ) -> Result<(), AppError> {
+        if let SystemEngine::WindowsSpeech(_) = self {
+            let stdin = stdin
+                .ok_or_else(|| AppError::Internal("Speech process has no input".to_string()))?;
+            let command = if paused { "pause\n" } else { "resume\n" };
+            stdin.write_all(command.as_bytes()).await?;
+            return Ok(stdin.flush().await?);
+        }
+        if !self.supports_pause() {
+            return Err(AppError::InvalidInput(
+                "Pausing is not supported by this speech engine".to_string(),
+            ));
+        }
+        let pid = pid.ok_or_else(|| AppError::Internal("Speech process has exited".to_string()))?;
+        let signal = if paused { "-STOP" } else { "-CONT" };
+        let status = Command::new("kill")
+            .args([signal, &pid.to_string()])
+            .status()
+            .await?;
+        if !status.success() {
+            return Err(AppError::Internal(format!(
+                "Could not signal speech process {}",
+                pid
+            )));
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_voice_listings() {
+        let say = "Alex                en_US    # Most people recognize me by my voice.\n\
+                   Eddy (German (Germany)) de_DE    # Hallo! Ich heiße Eddy.\n";
+        let voices = parse_say_voices(say);
+        assert_eq!(voices.len(), 2);
+        assert_eq!(voices[1].id, "Eddy (German (Germany))");
+        assert_eq!(voices[1].language.as_deref(), Some("de-DE"));
+
+        let espeak = "Pty Language       Age/Gender VoiceName          File                 Other Languages\n \
+                      5  af              --/M      Afrikaans          gmw/af\n \
+                      5  en-us           --/F      English_(America)  gmw/en-US            (en 10)\n";
+        let voices = parse_espeak_voices(espeak);
+        assert_eq!(voices.len(), 2);
+        assert_eq!(voices[1].id, "en-us");
+        assert_eq!(voices[1].name, "English (America)");
+        assert_eq!(voices[1].gender.as_deref(), Some("female"));
+
+        let spd = "     NAME                   LANGUAGE        VARIANT\n    \
+                   Afrikaans               af              none\n    \
+                   English (Scotland)      en-GB-scotland  none\n";
+        let voices = parse_spd_voices(spd);
+        assert_eq!(voices[1].id, "English (Scotland)");
+        assert_eq!(voices[1].language.as_deref(), Some("en-GB-scotland"));
+
+        let windows =
+            "Microsoft Zira Desktop|en-US|Female\r\nMicrosoft David Desktop|en-US|NotSet\r\n";
+        let voices = parse_windows_voices(windows);
+        assert_eq!(voices[0].gender.as_deref(), Some("female"));
+        assert_eq!(voices[1].gender, None);
+    }
+
+    #[test]
+    fn parses_progress_and_rates() {
+        assert_eq!(parse_progress_line("word|12|5|830\r"), Some((12, 5, 830)));
+        assert_eq!(parse_progress_line("Exception: boom"), None);
+        assert_eq!(log_rate(1.0, 10.0), 0);
+        assert_eq!(log_rate(3.0, 10.0), 10);
+        assert_eq!(log_rate(0.1, 10.0), -10);
+        assert_eq!(log_rate(1.5, 100.0), 37);
+    }
+}
@@ -0,0 +1,290 @@
+//! Word and sentence segmentation and boundary timing
+
+use super::types::TtsBoundary;
+
+/// Speaking rate of the system voices at rate 1.0
+pub const BASE_WORDS_PER_MINUTE: f32 = 175.0;
+
+/// A range of text in UTF-16 code units
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextSegment {
+    pub start: usize,
+    pub len: usize,
+}
+
+impl TextSegment {
+    pub fn end(&self) -> usize {
+        self.start + self.len
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Ideographic and kana characters, which are spoken as words of their own
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FFFF)
+}
+
+/// Split text into words
+pub fn segment_words(text: &str) -> Vec<TextSegment> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut current: Option<TextSegment> = None;
+    let mut pos = 0;
+
+    for (i, &c) in chars.iter().enumerate() {
+        let width = c.len_utf16();
+        // Apostrophes and hyphens join the letters around them
+        let joins = matches!(c, '\'' | '\u{2019}' | '-')
+            && current.is_some()
+            && chars
+                .get(i + 1)
+                .is_some_and(|next| next.is_alphanumeric() && !is_cjk(*next));
+
+        if is_cjk(c) {
+            words.extend(current.take());
+            words.push(TextSegment {
+                start: pos,
+                len: width,
+            });
+        } else if c.is_alphanumeric() || joins {
+            match current.as_mut() {
+                Some(word) => word.len += width,
+                None => {
+                    current = Some(TextSegment {
+                        start: pos,
+                        len: width,
+                    })
+                }
+            }
+        } else {
+            words.extend(current.take());
+        }
+        pos += width;
+    }
+    words.extend(current);
+    words
+}
+
+/// Split text into sentences, excluding surrounding whitespace
+pub fn segment_sentences(text: &str) -> Vec<TextSegment> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut sentences = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut content_end = 0;
+    let mut pos = 0;
+
+    let mut finish = |start: &mut Option<usize>, end: usize| {
+        if let Some(begin) = start.take() {
+            if end > begin {
+                sentences.push(TextSegment {
+                    start: begin,
+                    len: end - begin,
+                });
+            }
+        }
+    };
+
+    for (i, &c) in chars.iter().enumerate() {
+        let width = c.len_utf16();
+        if c == '\n' {
+            finish(&mut start, content_end);
+        } else if !c.is_whitespace() {
+            start.get_or_insert(pos);
+            content_end = pos + width;
+        }
+        pos += width;
+
+        let next = chars.get(i + 1);
+        let ends_sentence = match c {
+            '\u{3002}' | '\u{FF01}' | '\u{FF1F}' => true,
+            '.' | '!' | '?' => next.map_or(true, |n| n.is_whitespace()),
+            _ => false,
+        };
+        if ends_sentence {
+            finish(&mut start, content_end);
+        }
+    }
+    finish(&mut start, content_end);
+    sentences
+}
+
+/// Relative speaking time of a word
+fn word_weight(text_units: &[u16], word: &TextSegment) -> f32 {
+    let single_cjk = word.len <= 2
+        && char::decode_utf16(text_units[word.start..word.end()].iter().copied())
+            .filter_map(Result::ok)
+            .all(is_cjk);
+    if single_cjk {
+        0.5
+    } else {
+        (word.len as f32 / 5.0).clamp(0.5, 3.0)
+    }
+}
+
+/// Estimate when each word is spoken at the given rate
+///
+/// Returns the words with their start offsets in milliseconds and the total
+/// estimated duration.
+pub fn estimate_timeline(text: &str, rate: f32) -> (Vec<(TextSegment, u64)>, u64) {
+    let units: Vec<u16> = text.encode_utf16().collect();
+    let sentences = segment_sentences(text);
+    let ms_per_word = 60_000.0 / (BASE_WORDS_PER_MINUTE * rate.max(0.1));
+    let sentence_pause = ms_per_word * 0.8;
+
+    let mut timeline = Vec::new();
+    let mut offset = 0.0f32;
+    let mut sentence = 0;
+    for word in segment_words(text) {
+        while sentence < sentences.len() && sentences[sentence].end() <= word.start {
+            sentence += 1;
+            if offset > 0.0 {
+                offset += sentence_pause;
+            }
+        }
+        timeline.push((word, offset as u64));
+        offset += ms_per_word * word_weight(&units, &word);
+    }
+    (timeline, offset as u64)
+}
+
+/// Turns word positions into word and sentence boundary events
+pub struct BoundaryTracker {
+    utterance_id: String,
+    sentences: Vec<TextSegment>,
+    current_sentence: Option<usize>,
+    estimated: bool,
+}
+
+impl BoundaryTracker {
+    pub fn new(utterance_id: &str, text: &str, estimated: bool) -> Self {
+        Self {
+            utterance_id: utterance_id.to_string(),
+            sentences: segment_sentences(text),
+            current_sentence: None,
+            estimated,
+        }
+    }
+
+    fn boundary(&self, kind: &str, segment: TextSegment, offset_ms: u64) -> TtsBoundary {
+        TtsBoundary {
+            utterance_id: self.utterance_id.clone(),
+            kind: kind.to_string(),
+            char_index: segment.start,
+            char_length: segment.len,
+            offset_ms,
+            estimated: self.estimated,
+        }
+    }
+
+    /// Boundaries for a word starting to be spoken, preceded by a sentence
+    /// boundary when the word starts a new sentence
+    pub fn word(&mut self, word: TextSegment, offset_ms: u64) -> Vec<TtsBoundary> {
+        let mut boundaries = Vec::new();
+        let sentence = self
+            .sentences
+            .iter()
+            .position(|s| word.start >= s.start && word.start < s.end());
+        if let Some(index) = sentence.filter(|&index| self.current_sentence != Some(index)) {
+            self.current_sentence = Some(index);
+            boundaries.push(self.boundary("sentence", self.sentences[index], offset_ms));
+        }
+        boundaries.push(self.boundary("word", word, offset_ms));
+        boundaries
+    }
+}
+
+/// Estimated boundary timeline of a whole utterance, stretched to a known
+/// audio duration when one is given
+pub fn estimated_boundaries(
+    utterance_id: &str,
+    text: &str,
+    rate: f32,
+    duration_ms: Option<u64>,
+) -> Vec<TtsBoundary> {
+    let (timeline, total) = estimate_timeline(text, rate);
+    let scale = match duration_ms {
+        Some(duration) if total > 0 => duration as f64 / total as f64,
+        _ => 1.0,
+    };
+    let mut tracker = BoundaryTracker::new(utterance_id, text, true);
+    timeline
+        .into_iter()
+        .flat_map(|(word, offset)| tracker.word(word, (offset as f64 * scale) as u64))
+        .collect()
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slices(text: &str, segments: &[TextSegment]) -> Vec<String> {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        segments
+            .iter()
+            .map(|s| String::from_utf16(&units[s.start..s.end()]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn segments_words_and_sentences() {
+        let text = "Don't panic! It's a well-known 😀 fact.\n你好世界。 Bye";
+        assert_eq!(
+            slices(text, &segment_words(text)),
+            vec![
+                "Don't",
+                "panic",
+                "It's",
+                "a",
+                "well-known",
+                "fact",
+                "你",
+                "好",
+                "世",
+                "界",
+                "Bye"
+            ]
+        );
+        assert_eq!(
+            slices(text, &segment_sentences(text)),
+            vec![
+                "Don't panic!",
+                "It's a well-known 😀 fact.",
+                "你好世界。",
+                "Bye"
+            ]
+        );
+        assert_eq!(
+            slices("Pi is 3.14 today", &segment_sentences("Pi is 3.14 today")),
+            vec!["Pi is 3.14 today"]
+        );
+    }
+
+    #[test]
+    fn estimated_boundaries_follow_sentences_and_scale() {
+        let text = "One two. Three";
+        let boundaries = estimated_boundaries("tts_1", text, 1.0, None);
+        let kinds: Vec<&str> = boundaries.iter().map(|b| b.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["sentence", "word", "word", "sentence", "word"]);
+        assert_eq!(boundaries[0].offset_ms, 0);
+        assert!(boundaries
+            .windows(2)
+            .all(|w| w[0].offset_ms <= w[1].offset_ms));
+        assert_eq!(boundaries[3].char_index, 9);
+        assert!(boundaries.iter().all(|b| b.estimated));
+
+        let slow = estimated_boundaries("tts_1", text, 0.5, None);
+        assert!(slow[4].offset_ms > boundaries[4].offset_ms);
+
+        let (_, total) = estimate_timeline(text, 1.0);
+        let stretched = estimated_boundaries("tts_1", text, 1.0, Some(total * 2));
+        assert!(stretched[4].offset_ms >= boundaries[4].offset_ms * 2 - 1);
+    }
+}
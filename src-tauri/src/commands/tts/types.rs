@@ -0,0 +1,56 @@
+//! Text-to-speech data structures
+
+use serde::Serialize;
+
+/// A voice offered by a speech provider
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TtsVoice {
+    /// Identifier passed back as `voice` to `tts_speak`
+    pub id: String,
+    pub name: String,
+    pub language: Option<String>,
+    pub gender: Option<String>,
+    /// "system" or a cloud provider such as "openai"
+    pub provider: String,
+}
+
+/// A word or sentence boundary reached while speaking
+///
+/// Offsets are in UTF-16 code units so they index JavaScript strings directly.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TtsBoundary {
+    pub utterance_id: String,
+    /// "word" or "sentence"
+    pub kind: String,
+    pub char_index: usize,
+    pub char_length: usize,
+    /// Time since the start of the utterance
+    pub offset_ms: u64,
+    /// The engine does not report boundaries, so timing is derived from the rate
+    pub estimated: bool,
+}
+
+/// Playback state change of a system utterance
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TtsStateEvent {
+    pub utterance_id: String,
+    /// "started", "paused", "resumed", "finished", "stopped" or "error"
+    pub state: String,
+    pub error: Option<String>,
+}
+
+/// Result of starting speech
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TtsSpeakResult {
+    pub utterance_id: String,
+    pub provider: String,
+    /// Synthesized audio file for cloud providers, played by the frontend
+    pub audio_path: Option<String>,
+    pub duration_ms: Option<u64>,
+    /// Boundary timeline for cloud audio; system speech emits events instead
+    pub boundaries: Vec<TtsBoundary>,
+}
@@ -0,0 +1,327 @@
+//! App updates with a selectable release channel
+//!
+//! The stable channel follows the latest GitHub release; the beta channel
+//! follows the update manifest attached to the `beta` release, which beta
+//! builds replace. The checked update is kept so installing it does not
+//! check again. Downloads report progress through events. Desktop only.
+
+use crate::commands::events::emit_event;
+use crate::commands::retry::with_retry;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+/// Event emitted with `UpdateProgress` while an update downloads and installs
+pub const UPDATE_PROGRESS_EVENT: &str = "update-progress";
+
+/// Update channel settings file in the app data directory
+pub const UPDATE_SETTINGS_FILE: &str = "update_settings.json";
+
+pub const UPDATE_CHANNEL_STABLE: &str = "stable";
+pub const UPDATE_CHANNEL_BETA: &str = "beta";
+
+/// Update manifest of each channel
+const UPDATE_CHANNELS: &[(&str, &str)] = &[
+    (
+        UPDATE_CHANNEL_STABLE,
+        "https://github.com/NJUPT-SAST-CXX/sast-readium-web/releases/latest/download/latest.json",
+    ),
+    (
+        UPDATE_CHANNEL_BETA,
+        "https://github.com/NJUPT-SAST-CXX/sast-readium-web/releases/download/beta/latest.json",
+    ),
+];
+
+/// Progress is reported at most once per this many bytes
+const PROGRESS_STEP_BYTES: u64 = 256 * 1024;
+
+/// The update found by the last check
+pub type UpdateState = Arc<Mutex<Option<Update>>>;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateSettings {
+    pub channel: String,
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            channel: UPDATE_CHANNEL_STABLE.to_string(),
+        }
+    }
+}
+
+/// An available update
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub channel: String,
+    /// Release notes
+    pub notes: Option<String>,
+    pub published_at: Option<i64>,
+    /// Download size in bytes, when the server reports it
+    pub size: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProgress {
+    /// "downloading", "installing" or "installed"
+    pub stage: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn updater_err(e: tauri_plugin_updater::Error) -> AppError {
+    let detail = format!("Update failed: {}", e);
+    match e {
+        tauri_plugin_updater::Error::Reqwest(e) if !e.is_builder() && !e.is_decode() => {
+            AppError::Http {
+                status: e.status().map(|status| status.as_u16()),
+                detail,
+            }
+        }
+        // A download cut short
+        tauri_plugin_updater::Error::Network(_) => AppError::http(detail),
+        _ => AppError::Internal(detail),
+    }
+}
+
+/// Manifest URL of a channel
+pub fn channel_endpoint(channel: &str) -> Result<&'static str, AppError> {
+    UPDATE_CHANNELS
+        .iter()
+        .find(|(name, _)| *name == channel)
+        .map(|(_, url)| *url)
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown update channel: {}", channel)))
+}
+
+fn get_update_settings_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let app_data = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    Ok(app_data.join(UPDATE_SETTINGS_FILE))
+}
+
+pub fn load_update_settings(path: &Path) -> Result<UpdateSettings, AppError> {
+    if !path.exists() {
+        return Ok(UpdateSettings::default());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn save_update_settings(path: &Path, settings: &UpdateSettings) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(settings)?)?;
+    Ok(())
+}
+
+/// Whether enough was downloaded since the last report to report again
+pub fn should_report_progress(reported: u64, downloaded: u64, total: Option<u64>) -> bool {
+    downloaded.saturating_sub(reported) >= PROGRESS_STEP_BYTES || Some(downloaded) == total
+}
+
+/// Size of the update package, from the headers of its download
+async fn download_size(update: &Update) -> Option<u64> {
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("sast-readium/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .ok()?;
+    let response = client.head(update.download_url.clone()).send().await.ok()?;
+    response
+        .status()
+        .is_success()
+        .then(|| response.content_length())
+        .flatten()
+}
+
+/// Check the channel's manifest for a newer version
+async fn check_channel(app: &tauri::AppHandle, channel: &str) -> Result<Option<Update>, AppError> {
+    let endpoint = url::Url::parse(channel_endpoint(channel)?)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let updater = app
+        .updater_builder()
+        .endpoints(vec![endpoint])
+        .map_err(updater_err)?
+        .build()
+        .map_err(updater_err)?;
+    with_retry("Update check", || async {
+        updater.check().await.map_err(updater_err)
+    })
+    .await
+}
+
+fn emit_progress(app: &tauri::AppHandle, stage: &str, downloaded: u64, total: Option<u64>) {
+    let progress = UpdateProgress {
+        stage: stage.to_string(),
+        downloaded,
+        total,
+    };
+    emit_event(app, &progress);
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Get the update channel ("stable" or "beta")
+#[tauri::command]
+pub fn get_update_channel(app: tauri::AppHandle) -> Result<String, AppError> {
+    Ok(load_update_settings(&get_update_settings_path(&app)?)?.channel)
+}
+
+/// Switch the update channel to "stable" or "beta"
+#[tauri::command]
+pub fn set_update_channel(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, UpdateState>,
+    channel: String,
+) -> Result<(), AppError> {
+    channel_endpoint(&channel)?;
+    let path = get_update_settings_path(&app)?;
+    save_update_settings(&path, &UpdateSettings { channel })?;
+    // A checked update belongs to the old channel
+    *state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))? = None;
+    Ok(())
+}
+
+/// Check the update channel for a newer version
+#[tauri::command]
+pub async fn check_for_update(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, UpdateState>,
+) -> Result<Option<UpdateInfo>, AppError> {
+    let channel = load_update_settings(&get_update_settings_path(&app)?)?.channel;
+    let update = check_channel(&app, &channel).await?;
+    let info = match &update {
+        Some(update) => Some(UpdateInfo {
+            version: update.version.clone(),
+            current_version: update.current_version.clone(),
+            channel,
+            notes: update.body.clone(),
+            published_at: update.date.map(|date| date.unix_timestamp()),
+            size: download_size(update).await,
+        }),
+        None => None,
+    };
+    *state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))? = update;
+    Ok(info)
+}
+
+/// Download and install the checked update, emitting progress events, then
+/// restart unless `restart` is false
+#[tauri::command]
+pub async fn install_update_with_progress(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, UpdateState>,
+    restart: Option<bool>,
+) -> Result<(), AppError> {
+    let checked = state
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .take();
+    let update = match checked {
+        Some(update) => update,
+        None => {
+            let channel = load_update_settings(&get_update_settings_path(&app)?)?.channel;
+            check_channel(&app, &channel)
+                .await?
+                .ok_or_else(|| AppError::NotFound("No update available".to_string()))?
+        }
+    };
+
+    log::info!("Installing update {}", update.version);
+    // Counted by the chunk callback; each attempt starts over
+    let downloaded = AtomicU64::new(0);
+    let total = Mutex::new(None);
+    let package = with_retry("Update download", || async {
+        downloaded.store(0, Ordering::Relaxed);
+        let mut reported = 0;
+        update
+            .download(
+                |chunk, size| {
+                    let now = downloaded.fetch_add(chunk as u64, Ordering::Relaxed) + chunk as u64;
+                    if let Ok(mut total) = total.lock() {
+                        *total = size;
+                    }
+                    if should_report_progress(reported, now, size) {
+                        reported = now;
+                        emit_progress(&app, "downloading", now, size);
+                    }
+                },
+                || {},
+            )
+            .await
+            .map_err(updater_err)
+    })
+    .await?;
+    let size = total.lock().ok().and_then(|total| *total);
+    emit_progress(&app, "installing", downloaded.load(Ordering::Relaxed), size);
+    update.install(package).map_err(updater_err)?;
+    emit_progress(&app, "installed", downloaded.into_inner(), size);
+
+    if restart.unwrap_or(true) {
+        app.restart();
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn channels_are_stored_and_progress_is_throttled() {
+        assert!(channel_endpoint(UPDATE_CHANNEL_BETA)
+            .unwrap()
+            .contains("/download/beta/"));
+        assert!(matches!(
+            channel_endpoint("nightly"),
+            Err(AppError::InvalidInput(_))
+        ));
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(UPDATE_SETTINGS_FILE);
+        assert_eq!(
+            load_update_settings(&path).unwrap().channel,
+            UPDATE_CHANNEL_STABLE
+        );
+        let beta = UpdateSettings {
+            channel: UPDATE_CHANNEL_BETA.to_string(),
+        };
+        save_update_settings(&path, &beta).unwrap();
+        assert_eq!(load_update_settings(&path).unwrap(), beta);
+
+        assert!(!should_report_progress(0, 1024, Some(1 << 20)));
+        assert!(should_report_progress(0, PROGRESS_STEP_BYTES, None));
+        assert!(should_report_progress(1000, 2048, Some(2048)));
+    }
+}
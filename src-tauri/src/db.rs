@@ -0,0 +1,97 @@
+//! SQLite helpers shared by the backend's persistent stores
+//!
+//! Each store owns an ordered list of migration scripts. The position of a
+//! script in that list is its schema version, tracked with `PRAGMA user_version`,
+//! so migrations must only ever be appended.
+
+use crate::error::AppError;
+use rusqlite::Connection;
+use std::fs;
+use std::path::Path;
+
+/// Open (or create) a SQLite database and bring its schema up to date
+pub fn open_database(path: &Path, migrations: &[&str]) -> Result<Connection, AppError> {
+    let mut conn = open_connection(path)?;
+    apply_migrations(&mut conn, migrations)?;
+    Ok(conn)
+}
+
+/// Open (or create) a SQLite database, leaving its schema as it is
+pub fn open_connection(path: &Path) -> Result<Connection, AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let conn = Connection::open(path)?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    Ok(conn)
+}
+
+/// Open an in-memory database with the given migrations applied (used by tests)
+pub fn open_in_memory(migrations: &[&str]) -> Result<Connection, AppError> {
+    let mut conn = Connection::open_in_memory()?;
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    apply_migrations(&mut conn, migrations)?;
+    Ok(conn)
+}
+
+/// Get the current schema version of a database
+pub fn schema_version(conn: &Connection) -> Result<usize, AppError> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    Ok(version.max(0) as usize)
+}
+
+/// Apply all pending migrations, each in its own transaction
+pub fn apply_migrations(conn: &mut Connection, migrations: &[&str]) -> Result<(), AppError> {
+    let current = schema_version(conn)?;
+    for (index, sql) in migrations.iter().enumerate().skip(current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", (index + 1) as i64)?;
+        tx.commit()?;
+        log::info!("Applied database migration v{}", index + 1);
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const MIGRATIONS: &[&str] = &[
+        "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+        "ALTER TABLE items ADD COLUMN note TEXT;",
+    ];
+
+    #[test]
+    fn apply_migrations_sets_schema_version() {
+        let conn = open_in_memory(MIGRATIONS).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 2);
+        conn.execute("INSERT INTO items (name, note) VALUES ('a', 'b')", [])
+            .unwrap();
+    }
+
+    #[test]
+    fn open_database_only_applies_new_migrations() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("nested/test.db");
+
+        {
+            let conn = open_database(&path, &MIGRATIONS[..1]).unwrap();
+            conn.execute("INSERT INTO items (name) VALUES ('kept')", [])
+                .unwrap();
+        }
+
+        let conn = open_database(&path, MIGRATIONS).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 2);
+        let name: String = conn
+            .query_row("SELECT name FROM items", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(name, "kept");
+    }
+}
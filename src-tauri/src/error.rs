@@ -1,8 +1,23 @@
 //! Application error types
 
+use crate::commands::messages;
 use serde::Serialize;
 use thiserror::Error;
 
+/// Broad cause of an error, for deciding whether to try again
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCategory {
+    /// Connection failures, timeouts and server errors
+    Network,
+    /// Missing or rejected credentials, denied access
+    Auth,
+    RateLimit,
+    NotFound,
+    /// Everything else, including invalid requests
+    Internal,
+}
+
 /// Application-wide error type
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -12,19 +27,153 @@ pub enum AppError {
     Io(#[from] std::io::Error),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
-    #[error("HTTP error: {0}")]
-    Http(String),
+    #[error("HTTP error: {detail}")]
+    Http {
+        /// Status of the reply; None when the request was never answered
+        status: Option<u16>,
+        detail: String,
+    },
     #[error("MCP error: {0}")]
     Mcp(String),
     #[error("Not found: {0}")]
     NotFound(String),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("Database error: {0}")]
+    Database(String),
+    #[error("Internal error: {0}")]
+    Internal(String),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    /// A model reply that is not in the format the request asked for
+    #[error("Invalid model output: {0}")]
+    InvalidOutput(String),
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        AppError::Database(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_builder() || e.is_decode() {
+            return AppError::Internal(e.to_string());
+        }
+        AppError::Http {
+            status: e.status().map(|status| status.as_u16()),
+            detail: e.to_string(),
+        }
+    }
+}
+
+impl AppError {
+    /// A request that was never answered: no connection, a timeout, or a
+    /// reply cut short
+    pub fn http(detail: impl Into<String>) -> Self {
+        AppError::Http {
+            status: None,
+            detail: detail.into(),
+        }
+    }
+
+    /// A reply with the given status that cannot be used
+    pub fn http_status(status: reqwest::StatusCode, detail: impl Into<String>) -> Self {
+        AppError::Http {
+            status: Some(status.as_u16()),
+            detail: detail.into(),
+        }
+    }
+
+    /// Stable code of the variant, also the id of its message
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Keyring(_) => "keyring",
+            AppError::Io(_) => "io",
+            AppError::Json(_) => "json",
+            AppError::Http { .. } => "http",
+            AppError::Mcp(_) => "mcp",
+            AppError::NotFound(_) => "not_found",
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::Database(_) => "database",
+            AppError::Internal(_) => "internal",
+            AppError::PermissionDenied(_) => "permission_denied",
+            AppError::InvalidOutput(_) => "invalid_output",
+        }
+    }
+
+    /// What caused the error; HTTP errors are classified by their status,
+    /// and have none when the request was never answered
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            AppError::Http { status, .. } => match status {
+                None | Some(408) | Some(500..=599) => ErrorCategory::Network,
+                Some(401) | Some(403) => ErrorCategory::Auth,
+                Some(404) | Some(410) => ErrorCategory::NotFound,
+                Some(429) => ErrorCategory::RateLimit,
+                Some(_) => ErrorCategory::Internal,
+            },
+            AppError::Io(e) => match e.kind() {
+                std::io::ErrorKind::TimedOut
+                | std::io::ErrorKind::ConnectionRefused
+                | std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionAborted
+                | std::io::ErrorKind::BrokenPipe
+                | std::io::ErrorKind::UnexpectedEof => ErrorCategory::Network,
+                std::io::ErrorKind::NotFound => ErrorCategory::NotFound,
+                std::io::ErrorKind::PermissionDenied => ErrorCategory::Auth,
+                _ => ErrorCategory::Internal,
+            },
+            AppError::PermissionDenied(_) => ErrorCategory::Auth,
+            AppError::NotFound(_) => ErrorCategory::NotFound,
+            // The keyring being locked or missing says nothing about the key
+            AppError::Keyring(_)
+            | AppError::Json(_)
+            | AppError::Mcp(_)
+            | AppError::InvalidInput(_)
+            | AppError::Database(_)
+            | AppError::Internal(_)
+            | AppError::InvalidOutput(_) => ErrorCategory::Internal,
+        }
+    }
+
+    /// Whether trying the same operation again may succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.category(),
+            ErrorCategory::Network | ErrorCategory::RateLimit
+        )
+    }
+
+    /// The message without its prefix
+    pub fn detail(&self) -> String {
+        match self {
+            AppError::Io(e) => e.to_string(),
+            AppError::Json(e) => e.to_string(),
+            AppError::Keyring(detail)
+            | AppError::Http { detail, .. }
+            | AppError::Mcp(detail)
+            | AppError::NotFound(detail)
+            | AppError::InvalidInput(detail)
+            | AppError::Database(detail)
+            | AppError::Internal(detail)
+            | AppError::PermissionDenied(detail)
+            | AppError::InvalidOutput(detail) => detail.clone(),
+        }
+    }
 }
 
+/// Serialized with its prefix in the backend locale
 impl Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        serializer.serialize_str(&format!(
+            "{}: {}",
+            messages::message(self.code()),
+            self.detail()
+        ))
     }
 }
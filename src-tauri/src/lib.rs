@@ -8,15 +8,64 @@
 //!   - `system` - System information and utilities
 //!   - `file_ops` - File operations (export, import, metadata)
 //!   - `ai_keys` - AI API key secure storage
+//!   - `ai_providers` - User-registered OpenAI-compatible providers
+//!   - `ai_failover` - Failover of chat requests to fallback providers
+//!   - `ai_headers` - Default headers sent to each AI provider
+//!   - `ai_moderation` - Moderation of chat requests before they are sent
 //!   - `ai_usage` - AI usage statistics
+//!   - `ai_pricing` - Model prices for the cost of AI requests
 //!   - `ai_proxy` - AI request proxying
+//!   - `ai_batch` - Concurrent batches of chat requests
+//!   - `ai_structured` - JSON schema output of chat requests
+//!   - `ai_images` - Image generation, saved in the app data
+//!   - `ai_debug_log` - Opt-in, redacted log of AI requests and responses
+//!   - `ai_summaries` - Cached AI summaries of documents and chapters
+//!   - `ai_context` - Document context blocks for AI chat
+//!   - `conversations` - SQLite-backed chat conversation history
 //!   - `mcp` - MCP server management and configuration (with official SDK support)
+//!   - `library` - SQLite-backed document library
+//!   - `pdf` - PDF metadata inspection and text extraction
+//!   - `epub` - EPUB package parsing, structure and text extraction
+//!   - `thumbnails` - Cached document cover thumbnails
+//!   - `annotations` - Annotation export (Markdown, Readwise, Calibre)
+//!   - `conversion` - Format conversion via Calibre `ebook-convert` / pandoc
+//!   - `clipper` - Web article clipping into the library
+//!   - `feeds` - RSS/Atom feed reader with background refresh
+//!   - `dictionary` - Word lookup in local StarDict/MDX dictionaries
+//!   - `tts` - Text-to-speech with word and sentence boundary events
+//!   - `rag` - Document chunking and embedding indexes for retrieval
+//!   - `backup` - Versioned, optionally encrypted backups of app data
+//!   - `lan_sync` - Peer-to-peer library sync on the local network
+//!   - `migrations` - Versioned upgrades of every persistent store at startup
+//!   - `data_usage` - Disk usage of app data and clearing of caches
+//!   - `network` - Connectivity and captive portal detection
+//!   - `appearance` - System theme detection
+//!   - `power` - Suspend, wake and battery saver monitoring
+//!   - `diagnostics` - Diagnostics bundle for bug reports
+//!   - `crash` - Panic hook and crash reports
+//!   - `settings` - Typed settings with defaults, validation and change events
+//!   - `kv` - SQLite-backed key-value store for frontend state
+//!   - `metrics` - Timing metrics of every command
+//!   - `permissions` - Allow, ask or deny modes for sensitive commands
+//!   - `messages` - Localized error and status messages
+//!   - `events` - Typed backend-to-frontend event channels
+//!   - `self_test` - Health checks of the keyring, storage, network and processes
+//!   - `startup` - Startup profiling and deferred initialization
+//!   - `updates` - Update checks and release channels (desktop)
+//!   - `logging` - Rotating file logs
+//!   - `retry` - Retries of network operations with backoff
+//!   - `hex` - Hex encoding of keys, salts and ciphertexts
+//!   - `local_inference` - Local GGUF models served by llama.cpp (`local-inference` feature)
+//! - `db` - SQLite helpers shared by persistent stores
 
 pub mod commands;
+pub mod db;
 pub mod error;
 
 use commands::mcp::{create_mcp_client_state, MCPServerState, MCPState};
+use commands::startup::{defer, measure};
 use std::sync::{Arc, Mutex};
+use tauri::Manager;
 
 // Re-export error type for convenience
 pub use error::AppError;
@@ -27,6 +76,10 @@ pub use commands::mcp::{MCPClientInfo, MCPServerConfig, MCPServerStatus};
 /// Application entry point
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    commands::startup::mark_process_start();
+    // Time every command from its tracing spans
+    commands::metrics::install_command_metrics();
+
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     let builder =
         tauri::Builder::default().plugin(tauri_plugin_updater::Builder::new().build());
@@ -47,11 +100,23 @@ pub fn run() {
         .plugin(tauri_plugin_shell::init())
         .manage(mcp_state)
         .manage(mcp_client_state)
-        .invoke_handler(tauri::generate_handler![
+        .manage(commands::conversion::ConversionState::default())
+        .manage(commands::dictionary::DictionaryState::default())
+        .manage(commands::tts::TtsState::default())
+        .manage(commands::lan_sync::LanSyncState::default())
+        .manage(commands::network::NetworkState::default())
+        .manage(commands::appearance::ThemeState::default())
+        .manage(commands::power::PowerState::default())
+        // Sensitive commands are checked against the permission policy first
+        .invoke_handler(commands::permissions::guard_commands(tauri::generate_handler![
             // System commands
             commands::system::get_system_info,
             commands::system::get_app_runtime_info,
             commands::system::reveal_in_file_manager,
+            commands::system::open_with_default_app,
+            commands::system::open_external_url,
+            commands::system::get_app_resource_usage,
+            commands::system::get_locale_info,
             // File operations
             commands::file_ops::rename_file,
             commands::file_ops::delete_file,
@@ -69,12 +134,90 @@ pub fn run() {
             commands::ai_keys::save_api_key,
             commands::ai_keys::get_api_key,
             commands::ai_keys::delete_api_key,
+            commands::ai_keys::list_api_key_providers,
+            commands::ai_keys::get_api_key_metadata,
+            commands::ai_keys::list_api_key_profiles,
+            commands::ai_keys::set_active_api_key_profile,
+            commands::ai_keys::save_provider_tenant,
+            commands::ai_keys::get_provider_tenant,
+            commands::ai_keys::save_azure_deployment,
+            commands::ai_keys::get_azure_deployment,
+            commands::ai_failover::get_ai_fallback_chain,
+            commands::ai_failover::set_ai_fallback_chain,
+            commands::ai_headers::get_ai_provider_headers,
+            commands::ai_headers::set_ai_provider_headers,
+            commands::ai_debug_log::get_ai_debug_log,
+            commands::ai_debug_log::clear_ai_debug_log,
+            commands::ai_pricing::get_ai_pricing,
+            commands::ai_pricing::set_ai_model_price,
+            commands::ai_pricing::reset_ai_pricing,
+            commands::ai_providers::get_custom_providers,
+            commands::ai_providers::save_custom_provider,
+            commands::ai_providers::delete_custom_provider,
             // AI usage statistics
             commands::ai_usage::get_ai_usage_stats,
             commands::ai_usage::clear_ai_usage_stats,
             commands::ai_usage::update_ai_usage_stats,
             // AI proxy request
             commands::ai_proxy::proxy_ai_request,
+            commands::ai_proxy::proxy_ai_chat,
+            commands::ai_proxy::proxy_ai_request_raw,
+            commands::ai_proxy::proxy_embeddings_request,
+            commands::ai_proxy::proxy_transcription_request,
+            commands::ai_proxy::validate_api_key,
+            commands::ai_images::proxy_image_request,
+            commands::ai_batch::proxy_ai_batch_request,
+            // AI summaries
+            commands::ai_summaries::get_or_generate_summary,
+            // AI document context
+            commands::ai_context::build_document_context,
+            // Conversation history
+            commands::conversations::conversation_create,
+            commands::conversations::conversation_list,
+            commands::conversations::conversation_get,
+            commands::conversations::conversation_update,
+            commands::conversations::conversation_delete,
+            commands::conversations::conversation_append_message,
+            commands::conversations::conversation_store_attachment,
+            commands::conversations::conversation_add_attachments,
+            commands::conversations::collect_conversation_attachments,
+            commands::conversations::conversation_fork_message,
+            commands::conversations::conversation_edit_message,
+            commands::conversations::conversation_switch_branch,
+            commands::conversations::conversation_get_tree,
+            commands::conversations::conversation_archive,
+            commands::conversations::conversation_unarchive,
+            commands::conversations::get_conversation_retention,
+            commands::conversations::set_conversation_retention,
+            commands::conversations::preview_conversation_retention,
+            commands::conversations::run_conversation_retention,
+            commands::conversations::get_conversation_encryption_status,
+            commands::conversations::enable_conversation_encryption,
+            commands::conversations::disable_conversation_encryption,
+            commands::conversations::generate_conversation_title,
+            commands::conversations::suggest_conversation_title,
+            commands::conversations::get_title_generation_settings,
+            commands::conversations::set_title_generation_settings,
+            commands::conversations::search_conversations,
+            // Prompt templates
+            commands::conversations::prompt_template_list,
+            commands::conversations::prompt_template_create,
+            commands::conversations::prompt_template_update,
+            commands::conversations::prompt_template_delete,
+            commands::conversations::prompt_template_render,
+            commands::conversations::export_prompt_templates,
+            commands::conversations::export_prompt_templates_to_file,
+            commands::conversations::import_prompt_templates,
+            commands::conversations::import_prompt_templates_from_file,
+            // System prompt presets
+            commands::conversations::system_prompt_list,
+            commands::conversations::system_prompt_create,
+            commands::conversations::system_prompt_update,
+            commands::conversations::system_prompt_delete,
+            commands::conversations::system_prompt_resolve,
+            // Conversation bundles
+            commands::conversations::export_conversations_bundle,
+            commands::conversations::import_conversations_bundle,
             // MCP server management (legacy)
             commands::mcp::start_mcp_server,
             commands::mcp::stop_mcp_server,
@@ -104,18 +247,254 @@ pub fn run() {
             commands::mcp::commands::mcp_list_prompts,
             commands::mcp::commands::mcp_call_tool,
             commands::mcp::commands::mcp_read_resource,
-            commands::mcp::commands::mcp_get_prompt
-        ])
+            commands::mcp::commands::mcp_get_prompt,
+            // Document library
+            commands::library::library_add_document,
+            commands::library::library_list,
+            commands::library::library_search_metadata,
+            commands::library::library_get_document,
+            commands::library::library_update_document,
+            commands::library::library_mark_opened,
+            commands::library::library_remove_document,
+            commands::library::library_scan_folder,
+            commands::library::library_list_folders,
+            commands::library::library_remove_folder,
+            commands::library::library_add_bookmark,
+            commands::library::library_list_bookmarks,
+            commands::library::library_remove_bookmark,
+            commands::library::library_export_bookmarks,
+            commands::library::save_reading_progress,
+            commands::library::get_reading_progress,
+            commands::library::record_document_opened,
+            commands::library::get_recent_documents,
+            commands::library::pin_recent_document,
+            commands::library::unpin_recent_document,
+            commands::library::start_reading_session,
+            commands::library::end_reading_session,
+            commands::library::get_reading_stats,
+            commands::library::library_create_collection,
+            commands::library::library_list_collections,
+            commands::library::library_update_collection,
+            commands::library::library_delete_collection,
+            commands::library::library_add_to_collection,
+            commands::library::library_remove_from_collection,
+            commands::library::library_get_document_collections,
+            commands::library::library_find_duplicates,
+            commands::library::fetch_book_metadata,
+            commands::library::library_apply_book_metadata,
+            // Document inspection
+            commands::pdf::extract_pdf_metadata,
+            commands::pdf::extract_pdf_text,
+            commands::epub::extract_epub_text,
+            commands::epub::get_epub_structure,
+            commands::epub::update_epub_metadata,
+            commands::thumbnails::get_document_thumbnail,
+            commands::thumbnails::clear_thumbnail_cache,
+            // Annotations
+            commands::annotations::export_annotations,
+            // Format conversion
+            commands::conversion::detect_conversion_tools,
+            commands::conversion::convert_document,
+            commands::conversion::cancel_conversion,
+            // Web clipping
+            commands::clipper::clip_web_article,
+            // Feeds
+            commands::feeds::feed_add,
+            commands::feeds::feed_list,
+            commands::feeds::feed_remove,
+            commands::feeds::feed_refresh,
+            commands::feeds::feed_list_entries,
+            commands::feeds::feed_get_entry_text,
+            commands::feeds::feed_mark_read,
+            commands::feeds::feed_mark_all_read,
+            // Dictionary
+            commands::dictionary::lookup_word,
+            commands::dictionary::list_dictionaries,
+            // Text-to-speech
+            commands::tts::tts_speak,
+            commands::tts::tts_pause,
+            commands::tts::tts_resume,
+            commands::tts::tts_stop,
+            commands::tts::tts_list_voices,
+            // Retrieval indexes
+            commands::rag::rag_build_index,
+            commands::rag::rag_get_index_status,
+            commands::rag::rag_delete_index,
+            commands::rag::semantic_search,
+            // Backups
+            commands::backup::create_backup,
+            commands::backup::list_backups,
+            commands::backup::delete_backup,
+            commands::backup::restore_backup,
+            commands::backup::export_everything,
+            commands::backup::preview_migration_archive,
+            commands::backup::import_everything,
+            // LAN sync
+            commands::lan_sync::lan_sync_get_device,
+            commands::lan_sync::lan_sync_set_device_name,
+            commands::lan_sync::lan_sync_start,
+            commands::lan_sync::lan_sync_update_annotations,
+            commands::lan_sync::lan_sync_stop,
+            commands::lan_sync::lan_sync_status,
+            commands::lan_sync::lan_sync_discover,
+            commands::lan_sync::lan_sync_connect,
+            commands::lan_sync::lan_sync_list_conflicts,
+            commands::lan_sync::lan_sync_resolve_conflict,
+            // Data migrations
+            commands::migrations::get_data_migration_status,
+            // App data disk usage
+            commands::data_usage::get_app_data_usage,
+            commands::data_usage::clear_cache,
+            // Network status
+            commands::network::check_network_status,
+            // System theme
+            commands::appearance::get_system_theme,
+            // Power state
+            commands::power::get_power_status,
+            commands::power::get_battery_status,
+            // Diagnostics
+            commands::diagnostics::export_diagnostics,
+            // Crash reports
+            commands::crash::get_crash_reports,
+            commands::crash::dismiss_crash_report,
+            // Settings
+            commands::settings::get_settings_schema,
+            commands::settings::get_setting,
+            commands::settings::get_all_settings,
+            commands::settings::set_setting,
+            commands::settings::reset_setting,
+            // Key-value store
+            commands::kv::kv_get,
+            commands::kv::kv_set,
+            commands::kv::kv_delete,
+            commands::kv::kv_list,
+            // Command metrics
+            commands::metrics::get_command_metrics,
+            commands::metrics::reset_command_metrics,
+            // Command permissions
+            commands::permissions::get_command_permissions,
+            commands::permissions::set_command_permission,
+            // Backend locale
+            commands::messages::get_backend_locale,
+            commands::messages::set_backend_locale,
+            // Events
+            commands::events::list_event_channels,
+            // Self-test
+            commands::self_test::run_self_test,
+            // Startup
+            commands::startup::get_startup_profile,
+            // Logging
+            commands::logging::get_log_directory,
+            commands::logging::get_log_settings,
+            commands::logging::set_log_settings,
+            commands::logging::get_log_level,
+            commands::logging::set_log_level,
+            commands::logging::get_recent_logs,
+            commands::logging::subscribe_logs,
+            commands::logging::unsubscribe_logs,
+            // Updates
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            commands::updates::get_update_channel,
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            commands::updates::set_update_channel,
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            commands::updates::check_for_update,
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            commands::updates::install_update_with_progress,
+            #[cfg(feature = "local-inference")]
+            commands::local_inference::list_local_models,
+            #[cfg(feature = "local-inference")]
+            commands::local_inference::download_local_model,
+            #[cfg(feature = "local-inference")]
+            commands::local_inference::delete_local_model
+        ]))
         .setup(|app| {
-            if cfg!(debug_assertions) {
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
-            }
+            // Log to a rotating file first, so startup is logged too
+            measure("logging", || commands::logging::init_logging(app.handle()))?;
+            measure("crash reports", || {
+                commands::crash::install_panic_hook(app.handle())
+            })?;
+            measure("locale", || {
+                commands::messages::init_backend_locale(app.handle())
+            });
+
+            // Upgrade every store before any of them is opened
+            measure("migrations", || {
+                commands::migrations::run_startup_migrations(app.handle())
+            });
+            measure("retry policy", || {
+                commands::retry::init_retry_policy(app.handle())
+            });
+            measure("custom AI providers", || {
+                commands::ai_providers::init_custom_providers(app.handle())
+            })?;
+            measure("AI fallback providers", || {
+                commands::ai_failover::init_fallback_chain(app.handle())
+            });
+            measure("AI provider headers", || {
+                commands::ai_headers::init_provider_headers(app.handle())
+            });
+            measure("AI moderation", || {
+                commands::ai_moderation::init_moderation(app.handle())
+            });
+            measure("AI debug log", || {
+                commands::ai_debug_log::init_ai_debug_log(app.handle())
+            });
+            measure("AI pricing", || {
+                commands::ai_pricing::init_ai_pricing(app.handle())
+            });
+
+            // Open the library database
+            let library_state = measure("library", || {
+                commands::library::init_library_state(app.handle())
+            })?;
+            app.manage(library_state);
+
+            // Open the conversation database
+            let conversation_state = measure("conversations", || {
+                commands::conversations::init_conversation_state(app.handle())
+            })?;
+            app.manage(conversation_state);
+
+            // Open the key-value database
+            let kv_state = measure("key-value store", || {
+                commands::kv::init_kv_state(app.handle())
+            })?;
+            app.manage(kv_state);
+
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            app.manage(commands::updates::UpdateState::default());
+
+            #[cfg(feature = "local-inference")]
+            measure("local models", || {
+                commands::local_inference::init_local_models(app.handle())
+            })?;
+
+            // Not needed for the first screen: started once the window shows
+            defer("feed scheduler", commands::feeds::start_feed_scheduler);
+            defer(
+                "conversation maintenance",
+                commands::conversations::start_conversation_maintenance,
+            );
+            defer("network monitor", commands::network::start_network_monitor);
+            defer("theme monitor", commands::appearance::start_theme_monitor);
+            defer("power monitor", commands::power::start_power_monitor);
+            defer("AI HTTP client", |app| {
+                commands::ai_proxy::init_ai_http_client(&app)
+            });
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::ThemeChanged(theme) = event {
+                commands::appearance::handle_window_theme_changed(window.app_handle(), *theme);
+            }
+        })
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| match event {
+            tauri::RunEvent::Ready => commands::startup::finish_startup(app),
+            #[cfg(feature = "local-inference")]
+            tauri::RunEvent::Exit => commands::local_inference::stop_local_server_on_exit(),
+            _ => {}
+        });
 }